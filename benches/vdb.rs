@@ -0,0 +1,317 @@
+//! Benchmarks of reading, writing, voxel access, sampling and meshing on procedurally generated
+//! grids, from sparse narrow band level sets to dense fog volumes.
+//!
+//! Run with `cargo bench`, or `cargo bench -- <filter>` for a single group. Reading `.vdb` files
+//! is benchmarked on the generated grids written as uncompressed archives, set
+//! `VDB_BENCH_ASSET` to the path of a `.vdb` file to also benchmark reading its grids.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use glam::{DVec3, IVec3, Vec3};
+use std::io::Cursor;
+use vdb_rs::{
+    create_level_set_sphere, from_nanovdb, to_nanovdb, volume_to_mesh, volume_to_mesh_adaptive,
+    BoxSampler, FractalNoise, Grid, GridSampler, NoiseFog, QuadraticSampler, VdbReader,
+};
+
+/// Lookups per accessor and sampling iteration.
+const LOOKUPS: usize = 100_000;
+
+/// Named grids of increasing density.
+fn assets() -> Vec<(&'static str, Grid<f32>)> {
+    let fog = |threshold| {
+        NoiseFog::new(FractalNoise::new(7).with_frequency(0.1))
+            .with_threshold(threshold)
+            .fill(Vec3::splat(-32.0), Vec3::splat(32.0), 0.5)
+    };
+    vec![
+        (
+            "sphere_level_set",
+            create_level_set_sphere(30.0, Vec3::ZERO, 0.25, 3.0),
+        ),
+        ("sparse_fog", fog(0.6)),
+        ("dense_fog", fog(0.0)),
+    ]
+}
+
+/// Deterministic pseudo random coordinates within the active bounding box of `grid`.
+fn random_coords(grid: &Grid<f32>, count: usize) -> Vec<IVec3> {
+    let bbox = grid.eval_active_voxel_bounding_box();
+    let extent = (bbox.max - bbox.min + 1).as_uvec3();
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut next = |range: u32| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % range as u64) as i32
+    };
+    (0..count)
+        .map(|_| bbox.min + IVec3::new(next(extent.x), next(extent.y), next(extent.z)))
+        .collect()
+}
+
+/// Coordinates visited in storage order, the best case for leaf caching.
+fn coherent_coords(grid: &Grid<f32>, count: usize) -> Vec<IVec3> {
+    let bbox = grid.eval_active_voxel_bounding_box();
+    let center = (bbox.min + bbox.max) / 2;
+    let side = (count as f64).cbrt().ceil() as i32;
+    (0..side)
+        .flat_map(|x| (0..side).flat_map(move |y| (0..side).map(move |z| IVec3::new(x, y, z))))
+        .map(|offset| center - side / 2 + offset)
+        .take(count)
+        .collect()
+}
+
+fn push_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend(value.to_le_bytes());
+}
+
+fn push_name(out: &mut Vec<u8>, name: &str) {
+    push_u32(out, name.len() as u32);
+    out.extend(name.as_bytes());
+}
+
+fn push_mask(out: &mut Vec<u8>, mask: impl IntoIterator<Item = bool>) {
+    let mask = mask.into_iter().collect::<Vec<_>>();
+    for word in mask.chunks(64) {
+        let bits = word
+            .iter()
+            .enumerate()
+            .fold(0u64, |bits, (bit, &set)| bits | (set as u64) << bit);
+        out.extend(bits.to_le_bytes());
+    }
+}
+
+/// Values of a node, preceded by the metadata byte saying that all of them are stored.
+fn push_values(out: &mut Vec<u8>, values: &[f32]) {
+    out.push(6);
+    out.extend(values.iter().flat_map(|value| value.to_le_bytes()));
+}
+
+/// A `.vdb` file holding `grid` as a `float` grid called `name`, written like OpenVDB writes
+/// uncompressed grids, so reading it exercises the parser without any decompression.
+fn vdb_file(name: &str, grid: &Grid<f32>) -> Vec<u8> {
+    let mut bytes = vec![];
+    bytes.extend(0x5644_4220u64.to_le_bytes());
+    for value in [224, 11, 0] {
+        push_u32(&mut bytes, value);
+    }
+    bytes.push(1);
+    bytes.extend([b'0'; 36]);
+    // No file metadata, one grid
+    push_u32(&mut bytes, 0);
+    push_u32(&mut bytes, 1);
+
+    push_name(&mut bytes, name);
+    push_name(&mut bytes, "Tree_float_5_4_3");
+    push_name(&mut bytes, "");
+    let positions = bytes.len();
+    bytes.extend([0; 24]);
+    let grid_pos = bytes.len();
+
+    // Uncompressed, without grid metadata
+    push_u32(&mut bytes, 0);
+    push_u32(&mut bytes, 0);
+    push_name(&mut bytes, "ScaleTranslateMap");
+    let voxel_size = grid.transform.voxel_size();
+    for vector in [
+        grid.transform.index_to_world_f64(DVec3::ZERO),
+        voxel_size,
+        voxel_size,
+        voxel_size.recip(),
+        (voxel_size * voxel_size).recip(),
+        (voxel_size * 2.0).recip(),
+    ] {
+        bytes.extend(vector.to_array().iter().flat_map(|v| v.to_le_bytes()));
+    }
+
+    let tree = &grid.tree;
+    push_u32(&mut bytes, 1);
+    bytes.extend(tree.background.to_le_bytes());
+    push_u32(&mut bytes, 0);
+    push_u32(&mut bytes, tree.root_nodes.len() as u32);
+    let mut leaves = vec![];
+    for node_5 in &tree.root_nodes {
+        bytes.extend(
+            node_5
+                .origin
+                .to_array()
+                .iter()
+                .flat_map(|v| v.to_le_bytes()),
+        );
+        push_mask(&mut bytes, node_5.child_mask.iter().by_vals());
+        push_mask(&mut bytes, node_5.value_mask.iter().by_vals());
+        push_values(&mut bytes, &node_5.data);
+        for idx in node_5.child_mask.iter_ones() {
+            let node_4 = &node_5.nodes[&(idx as u32)];
+            push_mask(&mut bytes, node_4.child_mask.iter().by_vals());
+            push_mask(&mut bytes, node_4.value_mask.iter().by_vals());
+            push_values(&mut bytes, &node_4.data);
+            for idx in node_4.child_mask.iter_ones() {
+                let node_3 = &node_4.nodes[&(idx as u32)];
+                push_mask(&mut bytes, node_3.value_mask.iter().by_vals());
+                leaves.push(node_3);
+            }
+        }
+    }
+
+    let block_pos = bytes.len();
+    for node_3 in leaves {
+        push_mask(&mut bytes, node_3.value_mask.iter().by_vals());
+        push_values(&mut bytes, &node_3.buffer);
+    }
+    let end_pos = bytes.len();
+    for (i, pos) in [grid_pos, block_pos, end_pos].into_iter().enumerate() {
+        bytes[positions + i * 8..][..8].copy_from_slice(&(pos as u64).to_le_bytes());
+    }
+    bytes
+}
+
+fn read(c: &mut Criterion) {
+    let mut group = c.benchmark_group("read");
+    for (name, grid) in assets() {
+        let buffer = to_nanovdb(&grid);
+        group.throughput(Throughput::Bytes(buffer.len() as u64));
+        group.bench_function(format!("nanovdb/{name}"), |b| {
+            b.iter(|| from_nanovdb::<f32>(black_box(&buffer)).unwrap())
+        });
+
+        let file = vdb_file(name, &grid);
+        let read = VdbReader::new(Cursor::new(&file))
+            .unwrap()
+            .read_grid::<f32>(name)
+            .unwrap();
+        assert_eq!(read.active_voxel_count(), grid.active_voxel_count());
+        group.throughput(Throughput::Bytes(file.len() as u64));
+        group.bench_function(format!("vdb/{name}"), |b| {
+            b.iter(|| {
+                VdbReader::new(Cursor::new(black_box(&file)))
+                    .unwrap()
+                    .read_grid::<f32>(name)
+                    .unwrap()
+            })
+        });
+    }
+
+    if let Ok(path) = std::env::var("VDB_BENCH_ASSET") {
+        let file = std::fs::read(&path).expect("failed to read VDB_BENCH_ASSET");
+        let names = VdbReader::new(Cursor::new(&file))
+            .unwrap()
+            .available_grids();
+        group.throughput(Throughput::Bytes(file.len() as u64));
+        group.bench_function("vdb/asset", |b| {
+            b.iter(|| {
+                let mut reader = VdbReader::new(Cursor::new(black_box(&file))).unwrap();
+                for name in &names {
+                    // Grids of other value types are skipped
+                    let _ = reader.read_grid::<f32>(name);
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn write(c: &mut Criterion) {
+    let mut group = c.benchmark_group("write");
+    for (name, grid) in assets() {
+        group.throughput(Throughput::Elements(grid.active_voxel_count()));
+        group.bench_function(format!("nanovdb/{name}"), |b| {
+            b.iter(|| to_nanovdb(black_box(&grid)))
+        });
+    }
+    group.finish();
+}
+
+fn accessor(c: &mut Criterion) {
+    let mut group = c.benchmark_group("accessor");
+    group.throughput(Throughput::Elements(LOOKUPS as u64));
+    for (name, grid) in assets() {
+        for (pattern, coords) in [
+            ("random", random_coords(&grid, LOOKUPS)),
+            ("coherent", coherent_coords(&grid, LOOKUPS)),
+        ] {
+            group.bench_function(format!("tree/{pattern}/{name}"), |b| {
+                b.iter(|| {
+                    coords
+                        .iter()
+                        .map(|&coord| grid.tree.get_value(coord))
+                        .sum::<f32>()
+                })
+            });
+            group.bench_function(format!("cached/{pattern}/{name}"), |b| {
+                b.iter_batched(
+                    || grid.tree.accessor(),
+                    |mut accessor| {
+                        coords
+                            .iter()
+                            .map(|&coord| accessor.get_value(coord))
+                            .sum::<f32>()
+                    },
+                    BatchSize::SmallInput,
+                )
+            });
+            group.bench_function(format!("shared/{pattern}/{name}"), |b| {
+                let accessor = grid.tree.shared_accessor();
+                b.iter(|| {
+                    coords
+                        .iter()
+                        .map(|&coord| accessor.get_value(coord))
+                        .sum::<f32>()
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+fn sampling(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sampling");
+    group.throughput(Throughput::Elements(LOOKUPS as u64));
+    for (name, grid) in assets() {
+        // Marches a ray through the grid diagonally, as a volume renderer would
+        let bbox = grid.eval_active_voxel_bounding_box();
+        let start = grid.transform.index_to_world_f64(bbox.min.as_dvec3());
+        let end = grid.transform.index_to_world_f64(bbox.max.as_dvec3());
+        let positions = (0..LOOKUPS)
+            .map(|i| start.lerp(end, i as f64 / LOOKUPS as f64))
+            .collect::<Vec<DVec3>>();
+
+        group.bench_function(format!("box/{name}"), |b| {
+            b.iter(|| {
+                let mut sampler = GridSampler::<BoxSampler, _>::new(&grid);
+                positions
+                    .iter()
+                    .map(|&position| sampler.sample(position))
+                    .sum::<f32>()
+            })
+        });
+        group.bench_function(format!("quadratic/{name}"), |b| {
+            b.iter(|| {
+                let mut sampler = GridSampler::<QuadraticSampler, _>::new(&grid);
+                positions
+                    .iter()
+                    .map(|&position| sampler.sample(position))
+                    .sum::<f32>()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn meshing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("meshing");
+    group.sample_size(10);
+    for (name, grid) in assets() {
+        let isovalue = if name == "sphere_level_set" { 0.0 } else { 0.5 };
+        group.bench_function(format!("marching_cubes/{name}"), |b| {
+            b.iter(|| volume_to_mesh(black_box(&grid), isovalue))
+        });
+        group.bench_function(format!("dual_contouring/{name}"), |b| {
+            b.iter(|| volume_to_mesh_adaptive(black_box(&grid), isovalue, 0.5))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, read, write, accessor, sampling, meshing);
+criterion_main!(benches);
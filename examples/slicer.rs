@@ -4,13 +4,12 @@ use bevy_aabb_instancing::{
     VertexPullingRenderPlugin, COLOR_MODE_SCALAR_HUE,
 };
 use bevy_egui::{egui, EguiContexts, EguiPlugin};
-use glam::vec3;
 use half::f16;
 use smooth_bevy_cameras::{
     controllers::orbit::{OrbitCameraBundle, OrbitCameraController, OrbitCameraPlugin},
     LookTransformPlugin,
 };
-use vdb_rs::{Grid, Map, VdbLevel, VdbReader};
+use vdb_rs::{Grid, VdbLevel, VdbReader};
 
 use std::{error::Error, fs::File, io::BufReader};
 
@@ -134,10 +133,7 @@ fn rebuild_model(
             commands.entity(entity).despawn();
         });
 
-        let translation = match model_data.grid.transform {
-            Map::ScaleTranslateMap { translation, .. } => translation.as_vec3(),
-            _ => vec3(0.0, 0.0, 0.0),
-        };
+        let translation = model_data.grid.transform.translation().as_vec3();
 
         let slice_index = settings.render_slice_index;
 
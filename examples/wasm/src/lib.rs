@@ -0,0 +1,107 @@
+//! Loads a `.vdb` file from an `ArrayBuffer` and exposes slices of its first float grid, the
+//! core of a browser based volume viewer. Build it from this directory, which leaves out the
+//! Blosc C library of the `blosc` feature:
+//!
+//! ```sh
+//! cargo build --release --target wasm32-unknown-unknown
+//! ```
+//!
+//! And drive it from JavaScript:
+//!
+//! ```js
+//! const { instance } = await WebAssembly.instantiateStreaming(fetch("vdb_rs_wasm_example.wasm"));
+//! const vdb = instance.exports;
+//! const bytes = new Uint8Array(await (await fetch("smoke.vdb")).arrayBuffer());
+//! const ptr = vdb.vdb_alloc(bytes.length);
+//! new Uint8Array(vdb.memory.buffer, ptr, bytes.length).set(bytes);
+//! if (vdb.vdb_load(ptr, bytes.length) < 0) throw new Error("unreadable VDB file");
+//! const bbox = new Int32Array(vdb.memory.buffer, vdb.vdb_bbox(), 6);
+//! const [width, height] = [bbox[3] - bbox[0] + 1, bbox[4] - bbox[1] + 1];
+//! const slice = new Float32Array(vdb.memory.buffer, vdb.vdb_slice(bbox[2]), width * height);
+//! ```
+
+use glam::IVec3;
+use std::cell::RefCell;
+use std::io::Cursor;
+use vdb_rs::{CoordBBox, Grid, VdbReader};
+
+#[derive(Default)]
+struct Viewer {
+    grid: Option<Grid<f32>>,
+    bbox: [i32; 6],
+    slice: Vec<f32>,
+}
+
+thread_local! {
+    static VIEWER: RefCell<Viewer> = RefCell::new(Viewer::default());
+}
+
+/// Allocates `len` bytes for the contents of a file, passed to [`vdb_load`] which frees them.
+#[no_mangle]
+pub extern "C" fn vdb_alloc(len: usize) -> *mut u8 {
+    Box::into_raw(vec![0u8; len].into_boxed_slice()) as *mut u8
+}
+
+/// Reads the first float grid from the `len` bytes of a VDB file at `ptr`, allocated with
+/// [`vdb_alloc`], and returns its number of active voxels, or `-1` if it can't be read.
+///
+/// # Safety
+///
+/// `ptr` must be returned by `vdb_alloc(len)` with the same `len`, and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn vdb_load(ptr: *mut u8, len: usize) -> i64 {
+    let bytes = Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr, len));
+    let Some(grid) = read_first_float_grid(bytes) else {
+        return -1;
+    };
+    let bbox = grid.eval_active_voxel_bounding_box();
+    let active_voxel_count = grid.active_voxel_count() as i64;
+    VIEWER.with(|viewer| {
+        let mut viewer = viewer.borrow_mut();
+        viewer.bbox = [bbox.min.to_array(), bbox.max.to_array()]
+            .concat()
+            .try_into()
+            .unwrap();
+        viewer.grid = Some(grid);
+    });
+    active_voxel_count
+}
+
+fn read_first_float_grid(bytes: Box<[u8]>) -> Option<Grid<f32>> {
+    let mut reader = VdbReader::new(Cursor::new(bytes)).ok()?;
+    let name = reader
+        .grid_descriptors
+        .values()
+        .filter(|gd| gd.grid_type.starts_with("Tree_float_"))
+        .min_by_key(|gd| gd.grid_pos)?
+        .name
+        .clone();
+    reader.read_grid::<f32>(&name).ok()
+}
+
+/// The active bounding box of the loaded grid as 6 integers, its inclusive minimum and maximum.
+#[no_mangle]
+pub extern "C" fn vdb_bbox() -> *const i32 {
+    VIEWER.with(|viewer| viewer.borrow().bbox.as_ptr())
+}
+
+/// Values of the active bounding box of the loaded grid at index `z`, as `width * height` floats
+/// with x varying fastest. Valid until the next call.
+#[no_mangle]
+pub extern "C" fn vdb_slice(z: i32) -> *const f32 {
+    VIEWER.with(|viewer| {
+        let viewer = &mut *viewer.borrow_mut();
+        viewer.slice.clear();
+        if let Some(grid) = &viewer.grid {
+            let [min_x, min_y, _, max_x, max_y, _] = viewer.bbox;
+            let bbox = CoordBBox::new(IVec3::new(min_x, min_y, z), IVec3::new(max_x, max_y, z));
+            let mut accessor = grid.tree.accessor();
+            for y in bbox.min.y..=bbox.max.y {
+                for x in bbox.min.x..=bbox.max.x {
+                    viewer.slice.push(accessor.get_value(IVec3::new(x, y, z)));
+                }
+            }
+        }
+        viewer.slice.as_ptr()
+    })
+}
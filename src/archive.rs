@@ -0,0 +1,1015 @@
+use crate::data_structure::{
+    ArchiveHeader, Compression, GridDescriptor, Metadata, MetadataType, MetadataValue,
+};
+use crate::reader::{
+    ParseError, VdbReader, OPENVDB_FILE_VERSION_GRID_INSTANCING,
+    OPENVDB_FILE_VERSION_NODE_MASK_COMPRESSION, OPENVDB_FILE_VERSION_SELECTIVE_COMPRESSION,
+};
+use crate::InternedStr;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Magic number at the start of every VDB archive.
+pub(crate) const OPENVDB_MAGIC: u64 = 0x5644_4220;
+
+/// Bytes of `string` as stored in an archive. Strings are read one character per byte, so
+/// those are written back the same way, other strings are stored as UTF-8.
+fn string_bytes(string: &str) -> Vec<u8> {
+    if string.chars().all(|c| (c as u32) < 256) {
+        string.chars().map(|c| c as u8).collect()
+    } else {
+        string.as_bytes().to_vec()
+    }
+}
+
+fn write_name(out: &mut impl Write, name: &str) -> std::io::Result<()> {
+    let bytes = string_bytes(name);
+    out.write_u32::<LittleEndian>(bytes.len() as u32)?;
+    out.write_all(&bytes)
+}
+
+/// Writes `metadata` the way [`VdbReader`] reads it, with the fields sorted by name.
+pub(crate) fn write_metadata(out: &mut impl Write, metadata: &Metadata) -> std::io::Result<()> {
+    let mut fields = metadata.0.iter().collect::<Vec<_>>();
+    fields.sort_by(|a, b| a.0.cmp(b.0));
+    out.write_u32::<LittleEndian>(fields.len() as u32)?;
+    for (name, value) in fields {
+        write_name(out, name)?;
+        let (type_name, bytes) = match value {
+            MetadataValue::String(value) => ("string", string_bytes(value)),
+            MetadataValue::Bool(value) => ("bool", vec![*value as u8]),
+            MetadataValue::I32(value) => ("int32", value.to_le_bytes().to_vec()),
+            MetadataValue::I64(value) => ("int64", value.to_le_bytes().to_vec()),
+            MetadataValue::Float(value) => ("float", value.to_le_bytes().to_vec()),
+            MetadataValue::Vec3i(value) => (
+                "vec3i",
+                value
+                    .to_array()
+                    .iter()
+                    .flat_map(|v| v.to_le_bytes())
+                    .collect(),
+            ),
+            MetadataValue::Unknown { name, data } => (name.as_str(), data.clone()),
+        };
+        write_name(out, type_name)?;
+        out.write_u32::<LittleEndian>(bytes.len() as u32)?;
+        out.write_all(&bytes)?;
+    }
+    Ok(())
+}
+
+/// Name, type and instance parent of a grid, the start of its descriptor. The descriptor ends
+/// with the three positions of the grid.
+fn descriptor_names(descriptor: &GridDescriptor, file_version: u32) -> std::io::Result<Vec<u8>> {
+    let mut names = vec![];
+    write_name(&mut names, &descriptor.name)?;
+    write_name(&mut names, &descriptor.grid_type)?;
+    if file_version >= OPENVDB_FILE_VERSION_GRID_INSTANCING {
+        write_name(&mut names, &descriptor.instance_parent)?;
+    }
+    Ok(names)
+}
+
+/// Compression and metadata of a grid, which start the grid at its `grid_pos`.
+fn grid_header(descriptor: &GridDescriptor, file_version: u32) -> std::io::Result<Vec<u8>> {
+    let mut header = vec![];
+    if file_version >= OPENVDB_FILE_VERSION_NODE_MASK_COMPRESSION {
+        header.write_u32::<LittleEndian>(descriptor.compression.bits())?;
+    }
+    write_metadata(&mut header, &descriptor.meta_data)?;
+    Ok(header)
+}
+
+/// Header of an archive holding `grid_count` grids, which is followed by the first descriptor.
+fn archive_header(header: &ArchiveHeader, grid_count: u32) -> std::io::Result<Vec<u8>> {
+    let mut bytes = vec![];
+    bytes.write_u64::<LittleEndian>(OPENVDB_MAGIC)?;
+    bytes.write_u32::<LittleEndian>(header.file_version)?;
+    bytes.write_u32::<LittleEndian>(header.library_version_major)?;
+    bytes.write_u32::<LittleEndian>(header.library_version_minor)?;
+    bytes.write_u8(header.has_grid_offsets as u8)?;
+    if (OPENVDB_FILE_VERSION_SELECTIVE_COMPRESSION..OPENVDB_FILE_VERSION_NODE_MASK_COMPRESSION)
+        .contains(&header.file_version)
+    {
+        bytes.write_u8(header.compression.contains(Compression::ZIP) as u8)?;
+    }
+    bytes.write_all(&string_bytes(&header.guid))?;
+    write_metadata(&mut bytes, &header.meta_data)?;
+    bytes.write_u32::<LittleEndian>(grid_count)?;
+    Ok(bytes)
+}
+
+/// Where the data of a grid following its metadata is stored in the archive.
+pub(crate) struct GridParts {
+    /// Transform and topology
+    pub(crate) head: Range<u64>,
+    /// Leaf values
+    pub(crate) blocks: Range<u64>,
+}
+
+impl<R: Read + Seek> VdbReader<R> {
+    /// Descriptors of all grids, in the order they are stored in the archive. That is the order
+    /// of their descriptors, the data of grids reordered by an [`ArchiveEditor`] stays in place.
+    pub(crate) fn descriptors_in_file_order(&self) -> Vec<GridDescriptor> {
+        self.grid_order
+            .iter()
+            .filter_map(|name| self.grid_descriptors.get(name))
+            .cloned()
+            .collect()
+    }
+
+    /// Locates the transform, topology and leaf values of the grid described by `gd`.
+    pub(crate) fn grid_parts(&mut self, gd: &GridDescriptor) -> Result<GridParts, ParseError> {
+        let file_len = self.reader.seek(SeekFrom::End(0))?;
+        gd.seek_to_grid(&mut self.reader)?;
+        if self.header.file_version >= OPENVDB_FILE_VERSION_NODE_MASK_COMPRESSION {
+            let _ = self.reader.read_u32::<LittleEndian>()?;
+        }
+        let _ = Self::read_metadata(&mut self.reader)?;
+        let start = self.reader.stream_position()?;
+
+        // Grids written in one go are stored contiguously, the head ends where the leaf values
+        // start. Grids moved by an `ArchiveEditor` keep their leaf values in place and have
+        // their head appended to the file, packed with the heads of the other grids.
+        let end = if gd.block_pos >= start {
+            gd.block_pos
+        } else {
+            self.grid_descriptors
+                .values()
+                .flat_map(|other| [other.grid_pos, other.block_pos])
+                .filter(|&pos| pos > start)
+                .fold(file_len, u64::min)
+        };
+        Ok(GridParts {
+            head: start..end,
+            blocks: gd.block_pos..gd.end_pos,
+        })
+    }
+
+    /// Copies the bytes in `range` to `out`.
+    pub(crate) fn copy_range(
+        &mut self,
+        range: Range<u64>,
+        out: &mut impl Write,
+    ) -> Result<(), ParseError> {
+        self.reader.seek(SeekFrom::Start(range.start))?;
+        let len = range.end.saturating_sub(range.start);
+        if std::io::copy(&mut (&mut self.reader).take(len), out)? != len {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+        Ok(())
+    }
+}
+
+/// Writes archives whose grids are copied from existing archives, with their descriptors and
+/// metadata replaced. Grid data holds no absolute file positions, so it is copied byte for byte
+/// and only the positions stored in the descriptors are rewritten.
+pub(crate) struct ArchiveWriter<W> {
+    out: W,
+    /// Number of bytes written so far
+    pos: u64,
+    file_version: u32,
+}
+
+impl<W: Write> ArchiveWriter<W> {
+    /// Starts an archive holding `grid_count` grids with the version, UUID and file metadata of
+    /// `header`.
+    pub(crate) fn new(out: W, header: &ArchiveHeader, grid_count: u32) -> Result<Self, ParseError> {
+        let mut writer = Self {
+            out,
+            pos: 0,
+            file_version: header.file_version,
+        };
+        writer.write(&archive_header(header, grid_count)?)?;
+        Ok(writer)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.out.write_all(bytes)?;
+        self.pos += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// Copies the grid described by `source` in `reader`, which must be an archive of the same
+    /// file version, storing it under the name, instance parent, compression and metadata of
+    /// `descriptor`.
+    pub(crate) fn copy_grid<R: Read + Seek>(
+        &mut self,
+        reader: &mut VdbReader<R>,
+        source: &GridDescriptor,
+        descriptor: &GridDescriptor,
+    ) -> Result<(), ParseError> {
+        let parts = reader.grid_parts(source)?;
+        let names = descriptor_names(descriptor, self.file_version)?;
+        let grid_header = grid_header(descriptor, self.file_version)?;
+
+        // The grid starts right after its descriptor, which ends with the three positions
+        let grid_pos = self.pos + names.len() as u64 + 24;
+        let block_pos = grid_pos + grid_header.len() as u64 + (parts.head.end - parts.head.start);
+        let end_pos = block_pos + (parts.blocks.end - parts.blocks.start);
+        self.write(&names)?;
+        for pos in [grid_pos, block_pos, end_pos] {
+            self.write(&pos.to_le_bytes())?;
+        }
+        self.write(&grid_header)?;
+        reader.copy_range(parts.head, &mut self.out)?;
+        reader.copy_range(parts.blocks, &mut self.out)?;
+        self.pos = end_pos;
+        Ok(())
+    }
+
+    /// Flushes and returns the underlying writer.
+    pub(crate) fn finish(mut self) -> Result<W, ParseError> {
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ArchiveEditError {
+    #[error("No grid named {0}")]
+    GridNotFound(String),
+    #[error("A grid named {0} already exists")]
+    DuplicateName(String),
+    #[error("Grid {parent} is instanced by grid {instance}")]
+    InstancedGrid { parent: String, instance: String },
+    #[error("The new order must name every grid exactly once")]
+    InvalidOrder,
+    #[error("Archives of file version {found} can't be merged into version {expected}")]
+    VersionMismatch { expected: u32, found: u32 },
+    #[error("No archives to merge")]
+    NoInputs,
+    #[error("No room in place for {0}, rewrite the archive instead")]
+    NoRoom(String),
+    #[error("ParseError: {0}")]
+    ParseError(#[from] ParseError),
+    #[error("IoError")]
+    IoError(#[from] std::io::Error),
+}
+
+/// Renames, removes and reorders the grids of an existing archive and edits their metadata in
+/// place, without rewriting their leaf values, so fixing up a name in a huge cache takes moments
+/// instead of a re-export.
+///
+/// Edits are collected and applied at once by [`ArchiveEditor::commit`]. The leaf values of
+/// every grid stay where they are. Metadata that keeps its size is overwritten in place,
+/// otherwise the metadata, transform and topology of the grid, usually a small fraction of the
+/// file, are appended to the end of the file. The descriptors that chain the grids together are
+/// rewritten in the space these used to take up. The space of removed grids isn't reclaimed,
+/// copy the archive with [`VdbReader::write_with_checksums`] to compact it.
+///
+/// The archive is modified in several writes, so an interrupted commit can leave it unreadable.
+pub struct ArchiveEditor<F: Read + Write + Seek> {
+    reader: VdbReader<F>,
+    /// Grids in the order they will be stored, with their descriptors in the file and the
+    /// edited descriptors they will be stored with
+    grids: Vec<(GridDescriptor, GridDescriptor)>,
+}
+
+impl ArchiveEditor<File> {
+    /// Opens the archive at `path` for editing.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, ArchiveEditError> {
+        Self::new(File::options().read(true).write(true).open(path)?)
+    }
+}
+
+impl<F: Read + Write + Seek> ArchiveEditor<F> {
+    pub fn new(file: F) -> Result<Self, ArchiveEditError> {
+        let reader = VdbReader::new(file)?;
+        let grids = reader
+            .descriptors_in_file_order()
+            .into_iter()
+            .map(|gd| (gd.clone(), gd))
+            .collect();
+        Ok(Self { reader, grids })
+    }
+
+    /// Names of the grids, in the order they will be stored.
+    pub fn grid_names(&self) -> impl Iterator<Item = &str> {
+        self.grids.iter().map(|(_, gd)| gd.name.as_str())
+    }
+
+    /// Descriptor the grid `name` will be stored with.
+    pub fn descriptor(&self, name: &str) -> Option<&GridDescriptor> {
+        self.grids
+            .iter()
+            .map(|(_, gd)| gd)
+            .find(|gd| gd.name == name)
+    }
+
+    /// File-level metadata the archive will be stored with.
+    pub fn file_metadata(&self) -> &Metadata {
+        &self.reader.header.meta_data
+    }
+
+    pub fn file_metadata_mut(&mut self) -> &mut Metadata {
+        &mut self.reader.header.meta_data
+    }
+
+    /// Metadata the grid `name` will be stored with. Changing its `name` field doesn't rename
+    /// the grid, use [`ArchiveEditor::rename`] for that.
+    pub fn grid_metadata_mut(&mut self, name: &str) -> Result<&mut Metadata, ArchiveEditError> {
+        let idx = self.position(name)?;
+        Ok(&mut self.grids[idx].1.meta_data)
+    }
+
+    /// Applies `changes` to the file-level and grid metadata, in the order they were added.
+    pub fn apply_metadata_changes(
+        &mut self,
+        changes: &MetadataChanges,
+    ) -> Result<(), ArchiveEditError> {
+        for (target, key, value) in &changes.changes {
+            let metadata = match target {
+                MetadataTarget::File => vec![&mut self.reader.header.meta_data],
+                MetadataTarget::Grid(name) => vec![self.grid_metadata_mut(name)?],
+                MetadataTarget::AllGrids => self
+                    .grids
+                    .iter_mut()
+                    .map(|(_, gd)| &mut gd.meta_data)
+                    .collect(),
+            };
+            for metadata in metadata {
+                match value {
+                    Some(value) => metadata.0.insert(key.clone(), value.clone()),
+                    None => metadata.0.remove(key),
+                };
+            }
+        }
+        Ok(())
+    }
+
+    fn position(&self, name: &str) -> Result<usize, ArchiveEditError> {
+        self.grids
+            .iter()
+            .position(|(_, gd)| gd.name == name)
+            .ok_or_else(|| ArchiveEditError::GridNotFound(name.to_owned()))
+    }
+
+    /// Renames the grid `name` to `new_name`, along with its `name` metadata and the references
+    /// of the grids instancing it.
+    pub fn rename(&mut self, name: &str, new_name: &str) -> Result<(), ArchiveEditError> {
+        let idx = self.position(name)?;
+        if name == new_name {
+            return Ok(());
+        }
+        if self.descriptor(new_name).is_some() {
+            return Err(ArchiveEditError::DuplicateName(new_name.to_owned()));
+        }
+
+        let new_name = InternedStr::new(new_name);
+        let gd = &mut self.grids[idx].1;
+        gd.name = new_name.clone();
+        if gd.meta_data.name().is_some() {
+            gd.meta_data.insert_typed("name", new_name.to_string());
+        }
+        for (_, gd) in &mut self.grids {
+            if gd.instance_parent == name {
+                gd.instance_parent = new_name.clone();
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the grid `name`, which can't be instanced by other grids.
+    pub fn remove(&mut self, name: &str) -> Result<(), ArchiveEditError> {
+        let idx = self.position(name)?;
+        if let Some((_, instance)) = self.grids.iter().find(|(_, gd)| gd.instance_parent == name) {
+            return Err(ArchiveEditError::InstancedGrid {
+                parent: name.to_owned(),
+                instance: instance.name.to_string(),
+            });
+        }
+        self.grids.remove(idx);
+        Ok(())
+    }
+
+    /// Stores the grids in the order of `names`, which must name every grid exactly once.
+    pub fn reorder(&mut self, names: &[&str]) -> Result<(), ArchiveEditError> {
+        if names.len() != self.grids.len() {
+            return Err(ArchiveEditError::InvalidOrder);
+        }
+        let mut grids = Vec::with_capacity(names.len());
+        for name in names {
+            let idx = self
+                .grids
+                .iter()
+                .position(|(_, gd)| gd.name == *name)
+                .ok_or(ArchiveEditError::InvalidOrder)?;
+            grids.push(self.grids.swap_remove(idx));
+        }
+        self.grids = grids;
+        Ok(())
+    }
+
+    /// Writes the edits to the archive and returns it.
+    pub fn commit(mut self) -> Result<F, ArchiveEditError> {
+        let file_version = self.reader.header.file_version;
+        let file_len = self.reader.reader.seek(SeekFrom::End(0))?;
+        let header = archive_header(&self.reader.header, self.grids.len() as u32)?;
+
+        let mut parts = vec![];
+        let mut grid_headers = vec![];
+        let mut relocate = vec![];
+        for (source, gd) in &self.grids {
+            let grid_parts = self.reader.grid_parts(source)?;
+            let grid_header = grid_header(gd, file_version)?;
+            relocate.push(grid_header.len() as u64 != grid_parts.head.start - source.grid_pos);
+            parts.push(grid_parts);
+            grid_headers.push(grid_header);
+        }
+        // Heads appended by earlier edits end where the next one starts, so they are moved
+        // together to keep them packed
+        let appended = parts
+            .iter()
+            .map(|parts| parts.blocks.start < parts.head.start)
+            .collect::<Vec<_>>();
+
+        // The first descriptor follows the header, every next one is found at the end of the
+        // previous grid, which is where its leaf values end
+        let names = self
+            .grids
+            .iter()
+            .map(|(_, gd)| descriptor_names(gd, file_version))
+            .collect::<Result<Vec<_>, _>>()?;
+        let regions = std::iter::once(("the archive header".to_owned(), 0..header.len() as u64))
+            .chain(
+                std::iter::once(header.len() as u64)
+                    .chain(parts.iter().map(|parts| parts.blocks.end))
+                    .zip(self.grids.iter().zip(&names))
+                    .map(|(pos, ((_, gd), names))| {
+                        let label = format!("the descriptor of grid {}", gd.name);
+                        (label, pos..pos + names.len() as u64 + 24)
+                    }),
+            )
+            .collect::<Vec<_>>();
+
+        // Grids whose metadata is overwritten in place must not be in the way of the header and
+        // descriptors, move them to the end of the file otherwise
+        let overlaps = |a: &Range<u64>, b: &Range<u64>| a.start < b.end && b.start < a.end;
+        'place: loop {
+            if (0..self.grids.len()).any(|k| relocate[k] && appended[k]) {
+                for k in 0..self.grids.len() {
+                    relocate[k] |= appended[k];
+                }
+            }
+            for (i, (label, region)) in regions.iter().enumerate() {
+                let blocked = parts
+                    .iter()
+                    .any(|parts| !parts.blocks.is_empty() && overlaps(region, &parts.blocks))
+                    || regions
+                        .iter()
+                        .enumerate()
+                        .any(|(j, (_, other))| i != j && overlaps(region, other));
+                if blocked {
+                    return Err(ArchiveEditError::NoRoom(label.clone()));
+                }
+                for k in 0..self.grids.len() {
+                    let head = self.grids[k].0.grid_pos..parts[k].head.end;
+                    if !relocate[k] && overlaps(region, &head) {
+                        relocate[k] = true;
+                        continue 'place;
+                    }
+                }
+            }
+            break;
+        }
+
+        // Read the heads to move before anything is overwritten
+        let mut heads = vec![];
+        for (parts, &relocate) in parts.iter().zip(&relocate) {
+            let mut head = vec![];
+            if relocate {
+                self.reader.copy_range(parts.head.clone(), &mut head)?;
+            }
+            heads.push(head);
+        }
+
+        let mut tail_pos = regions
+            .iter()
+            .map(|(_, region)| region.end)
+            .fold(file_len, u64::max);
+        let file = &mut self.reader.reader;
+        let mut grid_positions = vec![];
+        for (k, (source, _)) in self.grids.iter().enumerate() {
+            if relocate[k] {
+                file.seek(SeekFrom::Start(tail_pos))?;
+                grid_positions.push(tail_pos);
+                file.write_all(&grid_headers[k])?;
+                file.write_all(&heads[k])?;
+                tail_pos += (grid_headers[k].len() + heads[k].len()) as u64;
+            } else {
+                grid_positions.push(source.grid_pos);
+                if grid_headers[k] != grid_header(source, file_version)? {
+                    file.seek(SeekFrom::Start(source.grid_pos))?;
+                    file.write_all(&grid_headers[k])?;
+                }
+            }
+        }
+        for (((_, region), names), (grid_pos, parts)) in regions[1..]
+            .iter()
+            .zip(&names)
+            .zip(grid_positions.into_iter().zip(&parts))
+        {
+            file.seek(SeekFrom::Start(region.start))?;
+            file.write_all(names)?;
+            for pos in [grid_pos, parts.blocks.start, parts.blocks.end] {
+                file.write_u64::<LittleEndian>(pos)?;
+            }
+        }
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&header)?;
+        file.flush()?;
+        Ok(self.reader.reader)
+    }
+}
+
+/// Where a metadata change applies.
+#[derive(Clone, Debug)]
+enum MetadataTarget {
+    File,
+    Grid(String),
+    AllGrids,
+}
+
+/// Metadata fields to set or remove in an archive, see [`update_file_metadata`].
+#[derive(Clone, Debug, Default)]
+pub struct MetadataChanges {
+    changes: Vec<(MetadataTarget, InternedStr, Option<MetadataValue>)>,
+}
+
+impl MetadataChanges {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    fn push(mut self, target: MetadataTarget, key: &str, value: Option<MetadataValue>) -> Self {
+        self.changes.push((target, InternedStr::new(key), value));
+        self
+    }
+
+    /// Sets the file-level field `key`.
+    pub fn set_file<T: MetadataType>(self, key: &str, value: T) -> Self {
+        self.push(MetadataTarget::File, key, Some(value.into_metadata()))
+    }
+
+    pub fn remove_file(self, key: &str) -> Self {
+        self.push(MetadataTarget::File, key, None)
+    }
+
+    /// Sets the field `key` of the grid `grid`.
+    pub fn set_grid<T: MetadataType>(self, grid: &str, key: &str, value: T) -> Self {
+        let target = MetadataTarget::Grid(grid.to_owned());
+        self.push(target, key, Some(value.into_metadata()))
+    }
+
+    pub fn remove_grid(self, grid: &str, key: &str) -> Self {
+        self.push(MetadataTarget::Grid(grid.to_owned()), key, None)
+    }
+
+    /// Sets the field `key` of every grid, like the frame number of a cache.
+    pub fn set_all_grids<T: MetadataType>(self, key: &str, value: T) -> Self {
+        self.push(MetadataTarget::AllGrids, key, Some(value.into_metadata()))
+    }
+
+    pub fn remove_all_grids(self, key: &str) -> Self {
+        self.push(MetadataTarget::AllGrids, key, None)
+    }
+
+    /// Sets the `creator` field of every grid, see [`Metadata::creator`].
+    pub fn set_creator(self, creator: &str) -> Self {
+        self.set_all_grids("creator", creator.to_owned())
+    }
+}
+
+/// Applies `changes` to the file-level and grid metadata of the archive at `path` in place,
+/// without touching its tree data, see [`ArchiveEditor`].
+pub fn update_file_metadata(
+    path: impl AsRef<Path>,
+    changes: &MetadataChanges,
+) -> Result<(), ArchiveEditError> {
+    let mut editor = ArchiveEditor::open(path)?;
+    editor.apply_metadata_changes(changes)?;
+    editor.commit()?;
+    Ok(())
+}
+
+/// Writes every grid of the archive at `input` to its own archive, at the path returned by
+/// `output_path` for the name of the grid, and returns the paths written.
+///
+/// Grids are copied as stored, without decompressing them, along with the file-level metadata.
+/// Instances are written together with the grid they instance.
+pub fn split_archive(
+    input: impl AsRef<Path>,
+    mut output_path: impl FnMut(&str) -> PathBuf,
+) -> Result<Vec<PathBuf>, ArchiveEditError> {
+    let mut reader = VdbReader::new(BufReader::new(File::open(input)?))?;
+    let mut paths = vec![];
+    for gd in reader.descriptors_in_file_order() {
+        let mut grids = vec![];
+        if !gd.instance_parent.is_empty() {
+            let parent = reader
+                .grid_descriptors
+                .get(&gd.instance_parent)
+                .cloned()
+                .ok_or_else(|| ArchiveEditError::GridNotFound(gd.instance_parent.to_string()))?;
+            grids.push(parent);
+        }
+        grids.push(gd.clone());
+
+        let path = output_path(&gd.name);
+        let out = BufWriter::new(File::create(&path)?);
+        let mut archive = ArchiveWriter::new(out, &reader.header, grids.len() as u32)?;
+        for grid in &grids {
+            archive.copy_grid(&mut reader, grid, grid)?;
+        }
+        archive.finish()?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// Writes the grids of all `inputs` to a single archive at `output`, in the order of the
+/// inputs.
+///
+/// Grids are copied as stored, without decompressing them, so all inputs must share the same
+/// file version, and grid names must be unique across them. The file-level metadata of the
+/// inputs is combined, the first input to set a field wins. The merged archive gets a new guid,
+/// as it is a different file than any of its inputs. Fails without creating `output` if
+/// `inputs` is empty, as there is no header to take the file version from.
+pub fn merge_archives(
+    inputs: &[impl AsRef<Path>],
+    output: impl AsRef<Path>,
+) -> Result<(), ArchiveEditError> {
+    let mut readers = inputs
+        .iter()
+        .map(|input| Ok(VdbReader::new(BufReader::new(File::open(input)?))?))
+        .collect::<Result<Vec<_>, ArchiveEditError>>()?;
+    let first = readers.first().ok_or(ArchiveEditError::NoInputs)?;
+
+    let mut header = first.header.clone();
+    header.guid = new_guid();
+    let mut names = std::collections::HashSet::new();
+    for reader in &readers {
+        if reader.header.file_version != header.file_version {
+            return Err(ArchiveEditError::VersionMismatch {
+                expected: header.file_version,
+                found: reader.header.file_version,
+            });
+        }
+        for (key, value) in &reader.header.meta_data.0 {
+            header
+                .meta_data
+                .0
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
+        }
+        for name in reader.grid_descriptors.keys() {
+            if !names.insert(name.clone()) {
+                return Err(ArchiveEditError::DuplicateName(name.to_string()));
+            }
+        }
+    }
+
+    let out = BufWriter::new(File::create(output)?);
+    let mut archive = ArchiveWriter::new(out, &header, names.len() as u32)?;
+    for reader in &mut readers {
+        for gd in reader.descriptors_in_file_order() {
+            archive.copy_grid(reader, &gd, &gd)?;
+        }
+    }
+    archive.finish()?;
+    Ok(())
+}
+
+/// Random version 4 UUID, in the lowercase form OpenVDB stores as the guid of an archive.
+fn new_guid() -> String {
+    use std::hash::{BuildHasher, Hasher};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    // Every `RandomState` is seeded differently, the time only adds to that
+    let state = std::collections::hash_map::RandomState::new();
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let half = |salt: u64| {
+        let mut hasher = state.build_hasher();
+        hasher.write_u64(salt);
+        hasher.write_u128(nanos);
+        hasher.finish() as u128
+    };
+    let bits = half(0) << 64 | half(1);
+    // Version 4, variant 1
+    let bits = bits & !(0xf << 76) | 0x4 << 76;
+    let bits = bits & !(0x3 << 62) | 0x2 << 62;
+    let hex = format!("{bits:032x}");
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::reader::OPENVDB_FILE_VERSION_MULTIPASS_IO;
+
+    use glam::IVec3;
+    use std::io::Cursor;
+
+    const VERSION: u32 = OPENVDB_FILE_VERSION_MULTIPASS_IO;
+    /// Only active voxel of the test grids, at slot 83 of the first leaf.
+    pub(crate) const COORD: IVec3 = IVec3::new(1, 2, 3);
+
+    fn write_mask(out: &mut Vec<u8>, len: usize, set: Option<usize>) {
+        for word in 0..len / 64 {
+            let bits = match set {
+                Some(bit) if bit / 64 == word => 1u64 << (bit % 64),
+                _ => 0,
+            };
+            out.write_u64::<LittleEndian>(bits).unwrap();
+        }
+    }
+
+    /// Transform and topology, and leaf values, of a float grid holding `value` at `COORD`.
+    /// Values are stored uncompressed, for active voxels and tiles only, as half floats if
+    /// `half_float` is set.
+    fn grid_data(value: f32, half_float: bool) -> (Vec<u8>, Vec<u8>) {
+        let mut head = vec![];
+        write_name(&mut head, "UniformScaleMap").unwrap();
+        for v in [0.5, 0.5, 2.0, 4.0, 1.0] {
+            for _ in 0..3 {
+                head.write_f64::<LittleEndian>(v).unwrap();
+            }
+        }
+        head.write_u32::<LittleEndian>(1).unwrap();
+        head.write_f32::<LittleEndian>(0.0).unwrap();
+        head.write_u32::<LittleEndian>(0).unwrap();
+        head.write_u32::<LittleEndian>(1).unwrap();
+        head.extend([0; 12]);
+        // Internal nodes with a child in their first slot and no active tiles
+        for log_2_dim in [15, 12] {
+            write_mask(&mut head, 1 << log_2_dim, Some(0));
+            write_mask(&mut head, 1 << log_2_dim, None);
+            head.push(0);
+        }
+        write_mask(&mut head, 512, Some(83));
+
+        let mut blocks = vec![];
+        write_mask(&mut blocks, 512, Some(83));
+        blocks.push(0);
+        if half_float {
+            blocks.extend(half::f16::from_f32(value).to_le_bytes());
+        } else {
+            blocks.write_f32::<LittleEndian>(value).unwrap();
+        }
+        (head, blocks)
+    }
+
+    fn descriptor(name: &str, half_float: bool) -> GridDescriptor {
+        let mut meta_data = Metadata::default();
+        meta_data.insert_typed("name", name.to_owned());
+        meta_data.insert_typed("class", "fog volume".to_owned());
+        let mut grid_type = "Tree_float_5_4_3".to_owned();
+        if half_float {
+            meta_data.insert_typed("is_saved_as_half_float", true);
+            grid_type.push_str("_HalfFloat");
+        }
+        GridDescriptor {
+            name: InternedStr::new(name),
+            file_version: VERSION,
+            instance_parent: InternedStr::new(""),
+            grid_type: InternedStr::new(&grid_type),
+            grid_pos: 0,
+            block_pos: 0,
+            end_pos: 0,
+            compression: Compression::ACTIVE_MASK,
+            meta_data,
+        }
+    }
+
+    /// Archive holding a grid per `(name, value)`, like OpenVDB writes them.
+    pub(crate) fn archive(grids: &[(&str, f32)]) -> Cursor<Vec<u8>> {
+        archive_with(grids, false)
+    }
+
+    /// [`archive`] with the values stored as half floats if `half_float` is set.
+    pub(crate) fn archive_with(grids: &[(&str, f32)], half_float: bool) -> Cursor<Vec<u8>> {
+        let mut header = ArchiveHeader {
+            file_version: VERSION,
+            library_version_major: 11,
+            library_version_minor: 0,
+            guid: "0".repeat(36),
+            has_grid_offsets: true,
+            ..Default::default()
+        };
+        header
+            .meta_data
+            .insert_typed("creator", "vdb-rs".to_owned());
+
+        let mut bytes = archive_header(&header, grids.len() as u32).unwrap();
+        for &(name, value) in grids {
+            let gd = descriptor(name, half_float);
+            let (head, blocks) = grid_data(value, half_float);
+            let names = descriptor_names(&gd, VERSION).unwrap();
+            let grid_header = grid_header(&gd, VERSION).unwrap();
+            let grid_pos = (bytes.len() + names.len() + 24) as u64;
+            let block_pos = grid_pos + (grid_header.len() + head.len()) as u64;
+            let end_pos = block_pos + blocks.len() as u64;
+            bytes.extend(names);
+            for pos in [grid_pos, block_pos, end_pos] {
+                bytes.extend(pos.to_le_bytes());
+            }
+            bytes.extend(grid_header);
+            bytes.extend(head);
+            bytes.extend(blocks);
+        }
+        Cursor::new(bytes)
+    }
+
+    /// Applies `edit` to `file` and checks that the committed archive reads back with the
+    /// descriptors and file metadata the editor reported, and the value of each grid in
+    /// `values`, in that order.
+    fn edit(
+        mut file: Cursor<Vec<u8>>,
+        values: &[(&str, f32)],
+        edit: impl FnOnce(&mut ArchiveEditor<Cursor<Vec<u8>>>) -> Result<(), ArchiveEditError>,
+    ) -> Cursor<Vec<u8>> {
+        file.set_position(0);
+        let mut editor = ArchiveEditor::new(file).unwrap();
+        edit(&mut editor).unwrap();
+        let expected = editor
+            .grid_names()
+            .map(|name| editor.descriptor(name).unwrap().clone())
+            .collect::<Vec<_>>();
+        let file_metadata = editor.file_metadata().clone();
+        let mut file = editor.commit().unwrap();
+        file.set_position(0);
+
+        let mut reader = VdbReader::new(&mut file).unwrap();
+        assert_eq!(reader.header.meta_data.0, file_metadata.0);
+        let descriptors = reader.descriptors_in_file_order();
+        assert_eq!(descriptors.len(), values.len());
+        for ((gd, expected), &(name, value)) in descriptors.iter().zip(&expected).zip(values) {
+            assert_eq!(gd.name, name);
+            assert_eq!(gd.name, expected.name);
+            assert_eq!(gd.grid_type, expected.grid_type);
+            assert_eq!(gd.instance_parent, expected.instance_parent);
+            assert_eq!(gd.compression, expected.compression);
+            assert_eq!(gd.meta_data.0, expected.meta_data.0);
+
+            let grid = reader.read_grid::<f32>(name).unwrap();
+            assert_eq!(grid.tree.get_value(COORD), value, "{name}");
+            assert_eq!(grid.active_voxel_count(), 1, "{name}");
+        }
+        file
+    }
+
+    #[test]
+    fn rename_grows_descriptor() {
+        let file = archive(&[("a", 1.0), ("b", 2.0), ("c", 3.0)]);
+        let long = "a name much longer than the one it replaces";
+        let file = edit(file, &[("a", 1.0), (long, 2.0), ("c", 3.0)], |editor| {
+            editor.rename("b", long)
+        });
+        let reader = VdbReader::new(Cursor::new(file.into_inner())).unwrap();
+        assert_eq!(reader.grid_descriptors[long].meta_data.name(), Some(long));
+    }
+
+    #[test]
+    fn remove_and_reorder() {
+        let file = archive(&[("a", 1.0), ("b", 2.0), ("c", 3.0)]);
+        edit(file, &[("c", 3.0), ("a", 1.0)], |editor| {
+            editor.remove("b")?;
+            editor.reorder(&["c", "a"])
+        });
+    }
+
+    #[test]
+    fn invalid_edits_are_rejected() {
+        let mut editor = ArchiveEditor::new(archive(&[("a", 1.0), ("b", 2.0)])).unwrap();
+        assert!(matches!(
+            editor.rename("a", "b"),
+            Err(ArchiveEditError::DuplicateName(_))
+        ));
+        assert!(matches!(
+            editor.remove("c"),
+            Err(ArchiveEditError::GridNotFound(_))
+        ));
+        assert!(matches!(
+            editor.reorder(&["a", "a"]),
+            Err(ArchiveEditError::InvalidOrder)
+        ));
+    }
+
+    #[test]
+    fn edits_of_edited_archive() {
+        let file = archive(&[("a", 1.0), ("b", 2.0), ("c", 3.0)]);
+        let unchanged = file.get_ref().clone();
+        let file = edit(file, &[("a", 1.0), ("b", 2.0), ("c", 3.0)], |_| Ok(()));
+        assert_eq!(file.get_ref(), &unchanged);
+
+        // Metadata that grows moves the heads of the grids to the end of the file
+        let comment = "x".repeat(200);
+        let file = edit(file, &[("a", 1.0), ("b", 2.0), ("c", 3.0)], |editor| {
+            editor
+                .grid_metadata_mut("b")?
+                .insert_typed("comment", comment.clone());
+            let changes = MetadataChanges::new()
+                .set_file("comment", comment.clone())
+                .set_creator("test");
+            editor.apply_metadata_changes(&changes)
+        });
+        assert!(file.get_ref().len() > unchanged.len());
+
+        let file = edit(file, &[("d", 3.0), ("b", 2.0), ("a", 1.0)], |editor| {
+            editor.rename("c", "d")?;
+            editor.grid_metadata_mut("b")?.0.remove("comment");
+            editor.reorder(&["d", "b", "a"])
+        });
+        let file = edit(file, &[("d", 3.0), ("a", 1.0)], |editor| {
+            let changes = MetadataChanges::new().set_grid("a", "comment", comment.clone());
+            editor.apply_metadata_changes(&changes)?;
+            editor.remove("b")?;
+            editor.rename("d", "a much longer name for d")?;
+            editor.rename("a much longer name for d", "d")
+        });
+        edit(file, &[("a", 1.0), ("d", 3.0)], |editor| {
+            editor.reorder(&["a", "d"])
+        });
+    }
+
+    #[test]
+    fn merging_nothing_fails() {
+        let output = std::env::temp_dir().join(format!("vdb-rs-merge-{}.vdb", std::process::id()));
+        let inputs: [&Path; 0] = [];
+        assert!(matches!(
+            merge_archives(&inputs, &output),
+            Err(ArchiveEditError::NoInputs)
+        ));
+        assert!(!output.exists());
+    }
+
+    #[test]
+    fn split_and_merge_round_trip() {
+        let dir = std::env::temp_dir().join(format!("vdb-rs-split-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("input.vdb");
+        let grids = [("a", 1.0), ("b", 2.0), ("c", 3.0)];
+        std::fs::write(&input, archive(&grids).into_inner()).unwrap();
+
+        let parts = split_archive(&input, |name| dir.join(format!("{name}.vdb"))).unwrap();
+        assert_eq!(parts.len(), grids.len());
+        for (part, &(name, value)) in parts.iter().zip(&grids) {
+            let mut reader = VdbReader::new(BufReader::new(File::open(part).unwrap())).unwrap();
+            assert_eq!(reader.available_grids(), [name]);
+            assert_eq!(
+                reader.read_grid::<f32>(name).unwrap().tree.get_value(COORD),
+                value
+            );
+        }
+
+        let merged = dir.join("merged.vdb");
+        merge_archives(&parts, &merged).unwrap();
+        let original = VdbReader::new(BufReader::new(File::open(&input).unwrap())).unwrap();
+        let mut reader = VdbReader::new(BufReader::new(File::open(&merged).unwrap())).unwrap();
+        assert_eq!(reader.header.file_version, original.header.file_version);
+        assert_eq!(reader.header.meta_data.0, original.header.meta_data.0);
+        assert_ne!(reader.header.guid, original.header.guid);
+        assert_eq!(reader.header.guid.len(), 36);
+
+        let descriptors = reader.descriptors_in_file_order();
+        assert_eq!(descriptors.len(), grids.len());
+        for (gd, &(name, value)) in descriptors.iter().zip(&grids) {
+            let expected = &original.grid_descriptors[name];
+            assert_eq!(gd.name, name);
+            assert_eq!(gd.grid_type, expected.grid_type);
+            assert_eq!(gd.compression, expected.compression);
+            assert_eq!(gd.meta_data.0, expected.meta_data.0);
+
+            let grid = reader.read_grid::<f32>(name).unwrap();
+            assert_eq!(grid.tree.get_value(COORD), value);
+            assert_eq!(grid.active_voxel_count(), 1);
+        }
+
+        let duplicate = dir.join("duplicate.vdb");
+        assert!(matches!(
+            merge_archives(&[&parts[0], &parts[1], &parts[0]], &duplicate),
+            Err(ArchiveEditError::DuplicateName(name)) if name == "a"
+        ));
+        assert!(!duplicate.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,413 @@
+use crate::data_structure::{load_mask_words, mask_words, Node, Node3, Node4, Node5, Tree};
+
+use bitvec::prelude::*;
+use glam::IVec3;
+use std::sync::Arc;
+
+/// Number of 64-bit mask words of a node with `2^(3 * log_2_dim)` slots.
+const fn word_count(log_2_dim: u32) -> usize {
+    (1usize << (3 * log_2_dim)).div_ceil(64)
+}
+
+/// Slot of a node at `log_2_dim` with children covering `2^total` voxels along each axis that
+/// contains `coord`, see [`Node::global_coord_to_offset`].
+fn slot(coord: IVec3, log_2_dim: u32, total: u32) -> usize {
+    let local = coord.as_uvec3() & ((1 << (log_2_dim + total)) - 1);
+    let local = local >> total;
+    ((local.x << (2 * log_2_dim)) + (local.y << log_2_dim) + local.z) as usize
+}
+
+/// Origin of the child in `slot` of a node at `origin`, the inverse of [`slot`].
+pub(crate) fn slot_origin(origin: IVec3, slot: usize, log_2_dim: u32, total: u32) -> IVec3 {
+    let dim_mask = (1 << log_2_dim) - 1;
+    let local = IVec3::new(
+        (slot >> (2 * log_2_dim)) as i32,
+        (slot >> log_2_dim) as i32 & dim_mask,
+        slot as i32 & dim_mask,
+    );
+    origin + (local << total as i32)
+}
+
+fn bit(words: &[u64], idx: usize) -> bool {
+    words[idx / 64] >> (idx % 64) & 1 != 0
+}
+
+/// Upper or lower internal node of an [`ArenaTree`]. Its children are stored next to each other
+/// in the slab of the level below, in the order of their slots, starting at `first_child`.
+#[derive(Debug, Clone)]
+struct ArenaInternal<ValueTy> {
+    origin: IVec3,
+    child_mask: Vec<u64>,
+    value_mask: Vec<u64>,
+    /// Number of children in the mask words before each word
+    child_ranks: Vec<u32>,
+    first_child: u32,
+    tiles: Vec<ValueTy>,
+}
+
+impl<ValueTy> ArenaInternal<ValueTy> {
+    fn new(origin: IVec3, child_mask: Vec<u64>, value_mask: Vec<u64>, tiles: Vec<ValueTy>) -> Self {
+        let child_ranks = child_mask
+            .iter()
+            .scan(0, |rank, word| {
+                let word_rank = *rank;
+                *rank += word.count_ones();
+                Some(word_rank)
+            })
+            .collect();
+        Self {
+            origin,
+            child_mask,
+            value_mask,
+            child_ranks,
+            first_child: 0,
+            tiles,
+        }
+    }
+
+    /// Index in the slab below of the child in `slot`, if the slot holds one.
+    fn child(&self, slot: usize) -> Option<usize> {
+        let (word, bit) = (slot / 64, slot % 64);
+        let mask = self.child_mask[word];
+        if mask >> bit & 1 == 0 {
+            return None;
+        }
+        let rank = self.child_ranks[word] + (mask & ((1 << bit) - 1)).count_ones();
+        Some((self.first_child + rank) as usize)
+    }
+}
+
+/// A leaf node of an [`ArenaTree`], borrowing its mask and values from the slabs of the tree.
+#[derive(Debug, Clone, Copy)]
+pub struct ArenaLeaf<'a, ValueTy> {
+    pub origin: IVec3,
+    /// Active states as 64-bit words, where bit `i` of word `j` is voxel `64 * j + i`
+    pub value_mask: &'a [u64],
+    pub values: &'a [ValueTy],
+}
+
+impl<ValueTy> ArenaLeaf<'_, ValueTy> {
+    pub fn is_value_on(&self, idx: usize) -> bool {
+        bit(self.value_mask, idx)
+    }
+}
+
+/// A [`Tree`] whose nodes are stored in one slab per level instead of being allocated one by
+/// one, with children referenced by index into the slab below.
+///
+/// All leaf masks share one buffer and so do all leaf values, leaf `i` owning the `i`-th run of
+/// `2^(3 * L3)` values, so reading grids with millions of leaves doesn't go through the
+/// allocator for every leaf and traversals touch memory in order. The topology is fixed once
+/// built, values can be changed in place with [`ArenaTree::leaf_values_mut`]. Build one with
+/// [`VdbReader::read_arena_tree`] or from a [`Tree`], and convert back with
+/// [`ArenaTree::to_tree`] to edit the topology.
+///
+/// [`VdbReader::read_arena_tree`]: crate::VdbReader::read_arena_tree
+#[derive(Debug, Clone)]
+pub struct ArenaTree<ValueTy, const L5: u32 = 5, const L4: u32 = 4, const L3: u32 = 3> {
+    pub background: ValueTy,
+    upper: Vec<ArenaInternal<ValueTy>>,
+    lower: Vec<ArenaInternal<ValueTy>>,
+    leaf_origins: Vec<IVec3>,
+    leaf_masks: Vec<u64>,
+    leaf_values: Vec<ValueTy>,
+}
+
+impl<ValueTy: Copy, const L5: u32, const L4: u32, const L3: u32> ArenaTree<ValueTy, L5, L4, L3> {
+    const LEAF_SIZE: usize = 1 << (3 * L3);
+    const LEAF_WORDS: usize = word_count(L3);
+
+    /// Creates an empty tree, where every voxel is an inactive `background` value.
+    pub fn new(background: ValueTy) -> Self {
+        Self {
+            background,
+            upper: vec![],
+            lower: vec![],
+            leaf_origins: vec![],
+            leaf_masks: vec![],
+            leaf_values: vec![],
+        }
+    }
+
+    /// Adds an upper node at `origin` whose children, added next with
+    /// [`ArenaTree::push_lower`], are the set bits of `child_mask`.
+    pub(crate) fn push_upper(
+        &mut self,
+        origin: IVec3,
+        child_mask: Vec<u64>,
+        value_mask: Vec<u64>,
+        tiles: Vec<ValueTy>,
+    ) {
+        let mut node = ArenaInternal::new(origin, child_mask, value_mask, tiles);
+        node.first_child = self.lower.len() as u32;
+        self.upper.push(node);
+    }
+
+    /// Adds a lower node at `origin` to the last upper node, whose leaves are added next with
+    /// [`ArenaTree::push_leaf`].
+    pub(crate) fn push_lower(
+        &mut self,
+        origin: IVec3,
+        child_mask: Vec<u64>,
+        value_mask: Vec<u64>,
+        tiles: Vec<ValueTy>,
+    ) {
+        let mut node = ArenaInternal::new(origin, child_mask, value_mask, tiles);
+        node.first_child = self.leaf_origins.len() as u32;
+        self.lower.push(node);
+    }
+
+    /// Adds a leaf at `origin` to the last lower node, with its active states taken from the
+    /// words of `value_mask`. Its values are allocated by [`ArenaTree::allocate_leaf_values`].
+    pub(crate) fn push_leaf(&mut self, origin: IVec3, value_mask: &[u64]) {
+        self.leaf_origins.push(origin);
+        self.leaf_masks.extend_from_slice(value_mask);
+    }
+
+    /// Allocates the values of all leaves at once, set to `value`, once the topology is known.
+    pub(crate) fn allocate_leaf_values(&mut self, value: ValueTy) {
+        self.leaf_origins.shrink_to_fit();
+        self.leaf_masks.shrink_to_fit();
+        self.leaf_values = vec![value; self.leaf_count() * Self::LEAF_SIZE];
+    }
+
+    /// Origin and values of the `idx`-th leaf, to fill them in once the topology is known.
+    pub(crate) fn leaf_mut(&mut self, idx: usize) -> (&mut IVec3, &mut [ValueTy]) {
+        (
+            &mut self.leaf_origins[idx],
+            &mut self.leaf_values[idx * Self::LEAF_SIZE..(idx + 1) * Self::LEAF_SIZE],
+        )
+    }
+
+    /// Copies `tree` into slabs, keeping the order of its nodes.
+    pub fn from_tree(tree: &Tree<ValueTy, L5, L4, L3>) -> Self {
+        let mut arena = Self::new(tree.background);
+        let leaf_count = tree.leaf_count();
+        arena.leaf_origins.reserve_exact(leaf_count);
+        arena
+            .leaf_masks
+            .reserve_exact(leaf_count * Self::LEAF_WORDS);
+        arena
+            .leaf_values
+            .reserve_exact(leaf_count * Self::LEAF_SIZE);
+        for node_5 in &tree.root_nodes {
+            arena.push_upper(
+                node_5.origin,
+                mask_words(&node_5.child_mask),
+                mask_words(&node_5.value_mask),
+                node_5.data.clone(),
+            );
+            for idx in node_5.child_mask.iter_ones() {
+                let node_4 = &node_5.nodes[&(idx as u32)];
+                arena.push_lower(
+                    node_4.origin,
+                    mask_words(&node_4.child_mask),
+                    mask_words(&node_4.value_mask),
+                    node_4.data.clone(),
+                );
+                for idx in node_4.child_mask.iter_ones() {
+                    let node_3 = &node_4.nodes[&(idx as u32)];
+                    arena.leaf_origins.push(node_3.origin);
+                    arena
+                        .leaf_masks
+                        .extend_from_slice(&mask_words(&node_3.value_mask));
+                    arena.leaf_values.extend_from_slice(&node_3.buffer);
+                }
+            }
+        }
+        arena
+    }
+
+    /// Copies the tree back into individually allocated nodes, see [`Tree`].
+    pub fn to_tree(&self) -> Tree<ValueTy, L5, L4, L3> {
+        fn mask(words: &[u64], log_2_dim: u32) -> BitVec<crate::MaskWord, Lsb0> {
+            let mut mask = bitvec![crate::MaskWord, Lsb0; 0; 1 << (3 * log_2_dim)];
+            load_mask_words(&mut mask, words);
+            mask
+        }
+
+        let mut tree = Tree::new(self.background);
+        for upper in &self.upper {
+            let mut node_5 = Node5 {
+                child_mask: mask(&upper.child_mask, L5),
+                value_mask: mask(&upper.value_mask, L5),
+                nodes: Default::default(),
+                data: upper.tiles.clone(),
+                origin: upper.origin,
+            };
+            for idx in node_5.child_mask.iter_ones() {
+                let lower = &self.lower[upper.child(idx).unwrap()];
+                let mut node_4 = Node4 {
+                    child_mask: mask(&lower.child_mask, L4),
+                    value_mask: mask(&lower.value_mask, L4),
+                    nodes: Default::default(),
+                    data: lower.tiles.clone(),
+                    origin: lower.origin,
+                };
+                for idx in node_4.child_mask.iter_ones() {
+                    let leaf = self.leaf(lower.child(idx).unwrap());
+                    let node_3 = Node3 {
+                        buffer: leaf.values.to_vec(),
+                        value_mask: mask(leaf.value_mask, L3),
+                        origin: leaf.origin,
+                    };
+                    node_4.nodes.insert(idx as u32, Arc::new(node_3));
+                }
+                node_5.nodes.insert(idx as u32, node_4);
+            }
+            tree.root_nodes.push(node_5);
+        }
+        tree
+    }
+
+    /// Number of leaf nodes in the tree.
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_origins.len()
+    }
+
+    /// The `idx`-th leaf, leaves being ordered by their parent nodes and then by their slots.
+    pub fn leaf(&self, idx: usize) -> ArenaLeaf<'_, ValueTy> {
+        ArenaLeaf {
+            origin: self.leaf_origins[idx],
+            value_mask: &self.leaf_masks[idx * Self::LEAF_WORDS..(idx + 1) * Self::LEAF_WORDS],
+            values: &self.leaf_values[idx * Self::LEAF_SIZE..(idx + 1) * Self::LEAF_SIZE],
+        }
+    }
+
+    /// All leaf nodes of the tree, see [`ArenaTree::leaf`].
+    pub fn leaves(&self) -> impl Iterator<Item = ArenaLeaf<'_, ValueTy>> {
+        (0..self.leaf_count()).map(|idx| self.leaf(idx))
+    }
+
+    /// Values of all leaves, one run of `2^(3 * L3)` values per leaf, which can be changed in
+    /// place without changing the topology.
+    pub fn leaf_values_mut(&mut self) -> &mut [ValueTy] {
+        &mut self.leaf_values
+    }
+
+    /// Index of the leaf containing `coord`, if any.
+    pub fn probe_leaf(&self, coord: IVec3) -> Option<usize> {
+        let origin = Tree::<ValueTy, L5, L4, L3>::root_origin(coord);
+        let upper = self.upper.iter().find(|upper| upper.origin == origin)?;
+        let lower = &self.lower[upper.child(slot(coord, L5, L4 + L3))?];
+        lower.child(slot(coord, L4, L3))
+    }
+
+    /// Value and active state of the voxel at `coord`, see [`Tree::probe_value`].
+    pub fn probe_value(&self, coord: IVec3) -> (ValueTy, bool) {
+        let origin = Tree::<ValueTy, L5, L4, L3>::root_origin(coord);
+        let Some(upper) = self.upper.iter().find(|upper| upper.origin == origin) else {
+            return (self.background, false);
+        };
+        let offset = slot(coord, L5, L4 + L3);
+        let Some(lower) = upper.child(offset) else {
+            return (upper.tiles[offset], bit(&upper.value_mask, offset));
+        };
+        let lower = &self.lower[lower];
+        let offset = slot(coord, L4, L3);
+        let Some(leaf) = lower.child(offset) else {
+            return (lower.tiles[offset], bit(&lower.value_mask, offset));
+        };
+        let offset = slot(coord, L3, 0);
+        (
+            self.leaf_values[leaf * Self::LEAF_SIZE + offset],
+            bit(&self.leaf_masks[leaf * Self::LEAF_WORDS..], offset),
+        )
+    }
+
+    /// Value of the voxel at `coord`, see [`ArenaTree::probe_value`].
+    pub fn get_value(&self, coord: IVec3) -> ValueTy {
+        self.probe_value(coord).0
+    }
+
+    /// Active state of the voxel at `coord`, see [`ArenaTree::probe_value`].
+    pub fn is_value_on(&self, coord: IVec3) -> bool {
+        self.probe_value(coord).1
+    }
+
+    /// Number of active voxels in the tree, where every active tile counts for all of the voxels
+    /// it covers.
+    pub fn active_voxel_count(&self) -> u64 {
+        let active_tiles = |node: &ArenaInternal<ValueTy>| {
+            node.value_mask
+                .iter()
+                .zip(&node.child_mask)
+                .map(|(value, child)| (value & !child).count_ones() as u64)
+                .sum::<u64>()
+        };
+        let upper = self.upper.iter().map(active_tiles).sum::<u64>()
+            * Node5::<ValueTy, L5, L4, L3>::TILE_VOXEL_COUNT;
+        let lower = self.lower.iter().map(active_tiles).sum::<u64>()
+            * Node4::<ValueTy, L4, L3>::TILE_VOXEL_COUNT;
+        let leaves = self
+            .leaf_masks
+            .iter()
+            .map(|word| word.count_ones() as u64)
+            .sum::<u64>();
+        upper + lower + leaves
+    }
+
+    /// Approximate number of bytes used by the tree, see [`Tree::memory_usage`].
+    pub fn memory_usage(&self) -> usize {
+        let internal = |node: &ArenaInternal<ValueTy>| {
+            std::mem::size_of::<ArenaInternal<ValueTy>>()
+                + (node.child_mask.capacity() + node.value_mask.capacity()) * 8
+                + node.child_ranks.capacity() * 4
+                + node.tiles.capacity() * std::mem::size_of::<ValueTy>()
+        };
+        std::mem::size_of::<Self>()
+            + self.upper.iter().map(internal).sum::<usize>()
+            + self.lower.iter().map(internal).sum::<usize>()
+            + self.leaf_origins.capacity() * std::mem::size_of::<IVec3>()
+            + self.leaf_masks.capacity() * 8
+            + self.leaf_values.capacity() * std::mem::size_of::<ValueTy>()
+    }
+}
+
+impl<ValueTy: Copy, const L5: u32, const L4: u32, const L3: u32> From<&Tree<ValueTy, L5, L4, L3>>
+    for ArenaTree<ValueTy, L5, L4, L3>
+{
+    fn from(tree: &Tree<ValueTy, L5, L4, L3>) -> Self {
+        Self::from_tree(tree)
+    }
+}
+
+impl<ValueTy: Copy, const L5: u32, const L4: u32, const L3: u32>
+    From<&ArenaTree<ValueTy, L5, L4, L3>> for Tree<ValueTy, L5, L4, L3>
+{
+    fn from(tree: &ArenaTree<ValueTy, L5, L4, L3>) -> Self {
+        tree.to_tree()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::archive::tests::{archive, COORD};
+    use crate::reader::VdbReader;
+
+    use glam::IVec3;
+
+    #[test]
+    fn arena_tree_reads_like_grid() {
+        let mut file = archive(&[("a", 1.5), ("b", -2.0)]);
+        let mut reader = VdbReader::new(&mut file).unwrap();
+        for name in ["a", "b"] {
+            let arena = reader.read_arena_tree::<f32>(name).unwrap();
+            let grid = reader.read_grid::<f32>(name).unwrap();
+            assert_eq!(arena.leaf_count(), grid.tree.leaf_count());
+            assert_eq!(arena.active_voxel_count(), grid.active_voxel_count());
+            for coord in [
+                COORD,
+                IVec3::ZERO,
+                IVec3::new(7, 7, 7),
+                IVec3::new(-1, 40, 9),
+            ] {
+                assert_eq!(
+                    arena.probe_value(coord),
+                    grid.tree.probe_value(coord),
+                    "{name} {coord}"
+                );
+            }
+        }
+    }
+}
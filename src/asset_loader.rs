@@ -0,0 +1,147 @@
+use crate::data_structure::{ArchiveHeader, Grid, GridDescriptor, GridInfo, Tree};
+use crate::reader::{ParseError, VdbReader};
+
+use bevy::app::{App, Plugin};
+use bevy::asset::{AddAsset, AssetLoader, BoxedFuture, LoadContext, LoadedAsset};
+use bevy::reflect::{Reflect, TypeUuid};
+use std::collections::HashMap;
+use std::io::Cursor;
+
+/// A grid of a [`VdbAsset`], by value type. Half precision grids are loaded as `float` grids.
+#[derive(Debug)]
+pub enum VdbAssetGrid {
+    Float(Grid<f32>),
+    Double(Grid<f64>),
+    Int32(Grid<i32>),
+    Int64(Grid<i64>),
+    /// A grid whose value type or tree configuration can't be loaded, with just its descriptor
+    Unsupported(GridDescriptor),
+}
+
+impl VdbAssetGrid {
+    pub fn descriptor(&self) -> &GridDescriptor {
+        match self {
+            VdbAssetGrid::Float(grid) => &grid.descriptor,
+            VdbAssetGrid::Double(grid) => &grid.descriptor,
+            VdbAssetGrid::Int32(grid) => &grid.descriptor,
+            VdbAssetGrid::Int64(grid) => &grid.descriptor,
+            VdbAssetGrid::Unsupported(descriptor) => descriptor,
+        }
+    }
+
+    /// Summary of the grid, `None` for unsupported grids.
+    pub fn info(&self) -> Option<GridInfo> {
+        match self {
+            VdbAssetGrid::Float(grid) => Some(grid.info()),
+            VdbAssetGrid::Double(grid) => Some(grid.info()),
+            VdbAssetGrid::Int32(grid) => Some(grid.info()),
+            VdbAssetGrid::Int64(grid) => Some(grid.info()),
+            VdbAssetGrid::Unsupported(_) => None,
+        }
+    }
+
+    /// The grid, if it holds `float` values.
+    pub fn as_float(&self) -> Option<&Grid<f32>> {
+        match self {
+            VdbAssetGrid::Float(grid) => Some(grid),
+            _ => None,
+        }
+    }
+}
+
+/// All grids of a `.vdb` file, loaded by [`VdbAssetLoader`].
+///
+/// Only the header is reflected, see [`VdbAsset::grid_infos`] for the properties of the grids.
+#[derive(Debug, Reflect, TypeUuid)]
+#[uuid = "d71dd43d-945b-46f3-b15f-c2970ffd9a1c"]
+pub struct VdbAsset {
+    /// Header of the file, holding the file level metadata
+    pub header: ArchiveHeader,
+    /// Grids by name, each holding its own metadata in its descriptor
+    #[reflect(ignore)]
+    pub grids: HashMap<String, VdbAssetGrid>,
+}
+
+impl VdbAsset {
+    /// Reads all grids of the VDB file in `bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ParseError> {
+        let mut reader = VdbReader::new(Cursor::new(bytes))?;
+        let mut grids = HashMap::new();
+        for name in reader.available_grids() {
+            let gd = &reader.grid_descriptors[name.as_str()];
+            let value_type = gd
+                .grid_type
+                .strip_prefix("Tree_")
+                .and_then(|rest| rest.strip_suffix(&Tree::<f32>::config_suffix()));
+            let grid = match value_type {
+                Some("float") => VdbAssetGrid::Float(reader.read_grid(&name)?),
+                Some("double") => VdbAssetGrid::Double(reader.read_grid(&name)?),
+                Some("int32") => VdbAssetGrid::Int32(reader.read_grid(&name)?),
+                Some("int64") => VdbAssetGrid::Int64(reader.read_grid(&name)?),
+                _ => VdbAssetGrid::Unsupported(gd.clone()),
+            };
+            grids.insert(name, grid);
+        }
+        Ok(Self {
+            header: reader.header,
+            grids,
+        })
+    }
+
+    /// Summaries of the supported grids, sorted by name, see [`GridInfo`].
+    pub fn grid_infos(&self) -> Vec<GridInfo> {
+        let mut infos = self
+            .grids
+            .values()
+            .filter_map(VdbAssetGrid::info)
+            .collect::<Vec<_>>();
+        infos.sort_by(|a, b| a.name.cmp(&b.name));
+        infos
+    }
+
+    /// The `float` grid called `name`, if any.
+    pub fn float_grid(&self, name: &str) -> Option<&Grid<f32>> {
+        self.grids.get(name)?.as_float()
+    }
+}
+
+/// Loads `.vdb` files as [`VdbAsset`]s on Bevy's IO task pool. A file that fails to parse puts
+/// its handle in the `LoadState::Failed` state of the `AssetServer`, which logs the
+/// [`ParseError`].
+#[derive(Default)]
+pub struct VdbAssetLoader;
+
+impl AssetLoader for VdbAssetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let asset = VdbAsset::from_bytes(bytes).map_err(|error| {
+                bevy::asset::Error::new(error)
+                    .context(format!("Failed to load {}", load_context.path().display()))
+            })?;
+            load_context.set_default_asset(LoadedAsset::new(asset));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["vdb"]
+    }
+}
+
+/// Registers [`VdbAsset`] and [`VdbAssetLoader`], so `asset_server.load("smoke.vdb")` returns
+/// a `Handle<VdbAsset>`, along with the reflected grid properties for inspectors.
+pub struct VdbPlugin;
+
+impl Plugin for VdbPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<VdbAsset>()
+            .register_asset_reflect::<VdbAsset>()
+            .register_type::<GridDescriptor>()
+            .register_type::<GridInfo>()
+            .init_asset_loader::<VdbAssetLoader>();
+    }
+}
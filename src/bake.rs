@@ -0,0 +1,192 @@
+use crate::collider::sdf_to_trimesh;
+use crate::data_structure::{GridClass, Tree};
+use crate::mesh_io::write_ply;
+use crate::nanovdb::write_nanovdb;
+use crate::reader::{ParseError, VdbReader};
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[derive(thiserror::Error, Debug)]
+pub enum BakeError {
+    #[error("Failed to parse {0}: {1}")]
+    ParseError(PathBuf, ParseError),
+    #[error("IoError")]
+    IoError(#[from] std::io::Error),
+}
+
+/// What [`bake_vdb`] produces from the `float` grids of a `.vdb` file. With the `serde` feature
+/// the settings can be kept in a file next to the assets and read by a build script, with the
+/// `bevy` feature they are loaded from `.vdbbake.ron` files by [`crate::VdbBakePlugin`].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+#[cfg_attr(
+    feature = "bevy",
+    derive(bevy::reflect::TypeUuid, bevy::reflect::TypePath),
+    uuid = "4c2f8e71-9a3d-4b6e-8f05-d1e7a2c94b38"
+)]
+pub struct BakeSettings {
+    /// Write every grid to a `.nvdb` file, see [`write_nanovdb`]
+    pub nanovdb: bool,
+    /// Mesh the level sets into `.ply` files, see [`sdf_to_trimesh`]
+    pub meshes: bool,
+    pub isovalue: f32,
+    pub adaptivity: f32,
+    /// Names of the grids to bake, all `float` grids when `None`
+    pub grids: Option<Vec<String>>,
+}
+
+impl Default for BakeSettings {
+    /// Bakes all `float` grids to NanoVDB and the level sets to meshes of their zero crossing,
+    /// without decimation.
+    fn default() -> Self {
+        Self {
+            nanovdb: true,
+            meshes: true,
+            isovalue: 0.0,
+            adaptivity: 0.0,
+            grids: None,
+        }
+    }
+}
+
+impl BakeSettings {
+    pub fn with_nanovdb(mut self, nanovdb: bool) -> Self {
+        self.nanovdb = nanovdb;
+        self
+    }
+
+    pub fn with_meshes(mut self, meshes: bool) -> Self {
+        self.meshes = meshes;
+        self
+    }
+
+    pub fn with_isovalue(mut self, isovalue: f32) -> Self {
+        self.isovalue = isovalue;
+        self
+    }
+
+    pub fn with_adaptivity(mut self, adaptivity: f32) -> Self {
+        self.adaptivity = adaptivity;
+        self
+    }
+
+    pub fn with_grids(mut self, grids: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.grids = Some(grids.into_iter().map(Into::into).collect());
+        self
+    }
+}
+
+/// Paths of the files baked from `grid` of the `.vdb` file `path` into `out_dir`, named after
+/// both, such as `smoke.density.nvdb` for the `density` grid of `smoke.vdb`.
+fn output_path(path: &Path, out_dir: &Path, grid: &str, extension: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    out_dir.join(format!("{stem}.{grid}.{extension}"))
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    path.metadata()
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Bakes the `float` grids of the `.vdb` file at `path` into `out_dir` as set by `settings`,
+/// so that games load ready to upload buffers and meshes instead of converting grids at
+/// startup. Returns the paths of the written files.
+///
+/// Grids are written to `<stem>.<grid>.nvdb`, loaded in Bevy as a `NanoVdbBuffer`, and level
+/// sets are meshed into `<stem>.<grid>.ply`, loaded as a `Mesh`. Outputs newer than `path` are
+/// kept as they are, so the bake can run from a build script on every build.
+pub fn bake_vdb(
+    path: impl AsRef<Path>,
+    out_dir: impl AsRef<Path>,
+    settings: &BakeSettings,
+) -> Result<Vec<PathBuf>, BakeError> {
+    let (path, out_dir) = (path.as_ref(), out_dir.as_ref());
+    let parse_error = |error| BakeError::ParseError(path.to_owned(), error);
+    let mut reader = VdbReader::new(BufReader::new(File::open(path)?)).map_err(parse_error)?;
+    let source_modified = modified(path);
+
+    let mut names = reader.available_grids();
+    names.sort();
+    let mut written = vec![];
+    for name in names {
+        let descriptor = &reader.grid_descriptors[name.as_str()];
+        let is_float = descriptor.grid_type == Tree::<f32>::type_name("float");
+        let selected = settings
+            .grids
+            .as_ref()
+            .is_none_or(|grids| grids.contains(&name));
+        if !is_float || !selected {
+            continue;
+        }
+        let nvdb_path = settings
+            .nanovdb
+            .then(|| output_path(path, out_dir, &name, "nvdb"));
+        let ply_path = (settings.meshes && descriptor.grid_class() == GridClass::LevelSet)
+            .then(|| output_path(path, out_dir, &name, "ply"));
+        let outdated = [&nvdb_path, &ply_path].into_iter().flatten().any(|output| {
+            match (modified(output), source_modified) {
+                (Some(output), Some(source)) => output < source,
+                _ => true,
+            }
+        });
+        if !outdated {
+            continue;
+        }
+
+        std::fs::create_dir_all(out_dir)?;
+        let grid = reader.read_grid::<f32>(&name).map_err(parse_error)?;
+        if let Some(nvdb_path) = nvdb_path {
+            write_nanovdb(&grid, &nvdb_path)?;
+            written.push(nvdb_path);
+        }
+        if let Some(ply_path) = ply_path {
+            let (positions, triangles) =
+                sdf_to_trimesh(&grid, settings.isovalue, settings.adaptivity);
+            write_ply(&ply_path, &positions, &triangles, &[])?;
+            written.push(ply_path);
+        }
+    }
+    Ok(written)
+}
+
+/// Bakes every `.vdb` file under `dir` with [`bake_vdb`], recreating its subdirectories in
+/// `out_dir`. Returns the paths of the written files.
+///
+/// A build script can bake the sources of a game into its `assets` directory:
+///
+/// ```no_run
+/// # fn main() -> Result<(), vdb_rs::BakeError> {
+/// println!("cargo:rerun-if-changed=vdb_sources");
+/// vdb_rs::bake_vdb_dir("vdb_sources", "assets/baked", &vdb_rs::BakeSettings::default())?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn bake_vdb_dir(
+    dir: impl AsRef<Path>,
+    out_dir: impl AsRef<Path>,
+    settings: &BakeSettings,
+) -> Result<Vec<PathBuf>, BakeError> {
+    let (dir, out_dir) = (dir.as_ref(), out_dir.as_ref());
+    let mut entries = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+    let mut written = vec![];
+    for entry in entries {
+        if entry.is_dir() {
+            let sub_dir = out_dir.join(entry.file_name().unwrap_or_default());
+            written.extend(bake_vdb_dir(&entry, sub_dir, settings)?);
+        } else if entry
+            .extension()
+            .is_some_and(|extension| extension == "vdb")
+        {
+            written.extend(bake_vdb(&entry, out_dir, settings)?);
+        }
+    }
+    Ok(written)
+}
@@ -0,0 +1,129 @@
+use crate::bake::{bake_vdb_dir, BakeError, BakeSettings};
+
+use bevy::asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::tasks::{IoTaskPool, Task};
+use futures_lite::future;
+use std::path::PathBuf;
+
+/// Loads `.vdbbake.ron` files as [`BakeSettings`], written like
+///
+/// ```ron
+/// (nanovdb: true, meshes: true, adaptivity: 0.2, grids: Some(["density"]))
+/// ```
+///
+/// with the fields left out taking their default values.
+#[derive(Default)]
+pub struct BakeSettingsLoader;
+
+impl AssetLoader for BakeSettingsLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let settings = ron::de::from_bytes::<BakeSettings>(bytes).map_err(|error| {
+                bevy::asset::Error::new(error)
+                    .context(format!("Failed to load {}", load_context.path().display()))
+            })?;
+            load_context.set_default_asset(LoadedAsset::new(settings));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["vdbbake.ron"]
+    }
+}
+
+/// Bakes the `.vdb` files under `sources` into `out_dir` with [`bake_vdb_dir`], using the
+/// [`BakeSettings`] loaded from the asset `settings`.
+///
+/// Bevy 0.11 has no asset processor to run the bake as part of importing assets, so this plugin
+/// stands in for it during development instead: it bakes once the settings are loaded, and
+/// again whenever asset hot reloading picks up a change to them. Outputs newer than their
+/// source are kept, so restarts are cheap. Both directories are relative to the working
+/// directory, like for [`bake_vdb_dir`]. Bake into the asset folder to load the outputs with
+/// [`crate::NanoVdbBufferLoader`] and Bevy's mesh loaders, and ship them without this plugin,
+/// or run [`bake_vdb_dir`] from a build script instead.
+pub struct VdbBakePlugin {
+    pub settings: String,
+    pub sources: PathBuf,
+    pub out_dir: PathBuf,
+}
+
+/// State of the [`VdbBakePlugin`].
+#[derive(Resource)]
+struct VdbBake {
+    settings: Handle<BakeSettings>,
+    sources: PathBuf,
+    out_dir: PathBuf,
+    /// Whether the settings changed since the last bake started
+    outdated: bool,
+    task: Option<Task<Result<Vec<PathBuf>, BakeError>>>,
+}
+
+fn bake_on_settings_change(
+    mut events: EventReader<AssetEvent<BakeSettings>>,
+    settings: Res<Assets<BakeSettings>>,
+    mut bake: ResMut<VdbBake>,
+) {
+    let changed = events.iter().any(|event| match event {
+        AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+            *handle == bake.settings
+        }
+        AssetEvent::Removed { .. } => false,
+    });
+    // Changes made while baking are picked up once the bake is done
+    bake.outdated |= changed;
+
+    if let Some(task) = &mut bake.task {
+        let Some(result) = future::block_on(future::poll_once(task)) else {
+            return;
+        };
+        bake.task = None;
+        match result {
+            Ok(written) => {
+                for path in written {
+                    log::info!("Baked {}", path.display());
+                }
+            }
+            Err(error) => log::error!("Failed to bake {}: {error}", bake.sources.display()),
+        }
+    }
+
+    if !bake.outdated {
+        return;
+    }
+    let Some(settings) = settings.get(&bake.settings).cloned() else {
+        return;
+    };
+    let (sources, out_dir) = (bake.sources.clone(), bake.out_dir.clone());
+    bake.outdated = false;
+    bake.task =
+        Some(IoTaskPool::get().spawn(async move { bake_vdb_dir(sources, out_dir, &settings) }));
+}
+
+impl Plugin for VdbBakePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<BakeSettings>()
+            .init_asset_loader::<BakeSettingsLoader>()
+            .add_systems(Update, bake_on_settings_change);
+    }
+
+    fn finish(&self, app: &mut App) {
+        // The asset server is only added by `AssetPlugin` when the app builds its plugins
+        let settings = app
+            .world
+            .resource::<AssetServer>()
+            .load(self.settings.as_str());
+        app.insert_resource(VdbBake {
+            settings,
+            sources: self.sources.clone(),
+            out_dir: self.out_dir.clone(),
+            outdated: false,
+            task: None,
+        });
+    }
+}
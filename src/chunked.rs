@@ -0,0 +1,564 @@
+use crate::coordinates::{CoordBBox, Index};
+use crate::data_structure::{
+    active_tiles, load_mask_words, mask_words, Grid, GridClass, GridDescriptor, GridValueType,
+    MaskWord, Node, Node3, Node4, Node5, Tree,
+};
+use crate::json::{json_array, json_string};
+use crate::par_slice::par_try_map;
+use crate::simd::count_ones;
+use crate::transform::Transform;
+
+use bitvec::prelude::*;
+use bytemuck::Pod;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use glam::{DMat4, IVec3};
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, ErrorKind, Read, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+const MAGIC: &[u8; 8] = b"VDBCHUNK";
+const VERSION: u32 = 1;
+const ROOT_KEY: &str = "root";
+const INDEX_KEY: &str = "index.json";
+
+#[derive(thiserror::Error, Debug)]
+pub enum ChunkedError {
+    #[error("Not a chunked grid")]
+    MagicMismatch,
+    #[error("Unsupported chunked grid version {0}")]
+    UnsupportedVersion(u32),
+    #[error("Stored value type {0} doesn't match the requested type")]
+    ValueTypeMismatch(String),
+    #[error("IoError")]
+    IoError(#[from] std::io::Error),
+}
+
+/// Key-value storage for the objects of a chunked grid, such as a directory or a cloud bucket,
+/// see [`write_chunked`].
+///
+/// Objects are written from multiple threads at once, which suits the latency of cloud storage.
+pub trait ChunkStore: Sync {
+    /// Stores `data` under `key`, replacing any object already stored there.
+    fn put(&self, key: &str, data: &[u8]) -> std::io::Result<()>;
+    /// The object stored under `key`, a [`ErrorKind::NotFound`] error if there is none.
+    fn get(&self, key: &str) -> std::io::Result<Vec<u8>>;
+    /// Removes the object stored under `key`, if any.
+    fn delete(&self, key: &str) -> std::io::Result<()>;
+}
+
+/// Stores objects as files in a directory, with `/` in keys separating subdirectories.
+#[derive(Clone, Debug)]
+pub struct DirectoryStore {
+    root: PathBuf,
+}
+
+impl DirectoryStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl ChunkStore for DirectoryStore {
+    fn put(&self, key: &str, data: &[u8]) -> std::io::Result<()> {
+        let path = self.root.join(key);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, data)
+    }
+
+    fn get(&self, key: &str) -> std::io::Result<Vec<u8>> {
+        std::fs::read(self.root.join(key))
+    }
+
+    fn delete(&self, key: &str) -> std::io::Result<()> {
+        match std::fs::remove_file(self.root.join(key)) {
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+}
+
+/// Key of the chunk holding the internal node at `origin`.
+pub fn chunk_key(origin: IVec3) -> String {
+    format!("chunks/{}_{}_{}", origin.x, origin.y, origin.z)
+}
+
+fn write_mask(out: &mut Vec<u8>, mask: &BitSlice<MaskWord, Lsb0>) {
+    for word in mask_words(mask) {
+        out.write_u64::<LittleEndian>(word).unwrap();
+    }
+}
+
+fn read_mask(input: &mut impl Read, len: usize) -> std::io::Result<BitVec<MaskWord, Lsb0>> {
+    let mut words = vec![0; len.div_ceil(64)];
+    input.read_u64_into::<LittleEndian>(&mut words)?;
+    let mut mask = bitvec![MaskWord, Lsb0; 0; len];
+    load_mask_words(&mut mask, &words);
+    Ok(mask)
+}
+
+/// Converts values between native and little-endian byte order, in place.
+fn swap_to_little_endian<ValueTy: GridValueType>(bytes: &mut [u8]) {
+    if cfg!(target_endian = "big") {
+        let component_size = std::mem::size_of::<ValueTy>() / ValueTy::COMPONENT_COUNT;
+        for component in bytes.chunks_exact_mut(component_size) {
+            component.reverse();
+        }
+    }
+}
+
+fn write_values<ValueTy: Pod + GridValueType>(out: &mut Vec<u8>, values: &[ValueTy]) {
+    let start = out.len();
+    out.extend_from_slice(bytemuck::cast_slice(values));
+    swap_to_little_endian::<ValueTy>(&mut out[start..]);
+}
+
+fn read_values<ValueTy: Pod + GridValueType>(
+    input: &mut impl Read,
+    len: usize,
+) -> std::io::Result<Vec<ValueTy>> {
+    let mut values = vec![ValueTy::zeroed(); len];
+    let bytes = bytemuck::cast_slice_mut(&mut values);
+    input.read_exact(bytes)?;
+    swap_to_little_endian::<ValueTy>(bytes);
+    Ok(values)
+}
+
+fn write_string(out: &mut Vec<u8>, value: &str) {
+    out.write_u32::<LittleEndian>(value.len() as u32).unwrap();
+    out.extend_from_slice(value.as_bytes());
+}
+
+fn read_string(input: &mut impl Read) -> std::io::Result<String> {
+    let mut bytes = vec![0; input.read_u32::<LittleEndian>()? as usize];
+    input.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|error| std::io::Error::new(ErrorKind::InvalidData, error))
+}
+
+fn compress(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(vec![], flate2::Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn decompress(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut decompressed = vec![];
+    ZlibDecoder::new(data).read_to_end(&mut decompressed)?;
+    Ok(decompressed)
+}
+
+/// A chunk holds an internal node with its tiles and leaves.
+fn encode_chunk<ValueTy: Pod + GridValueType>(node_4: &Node4<ValueTy>) -> Vec<u8> {
+    let mut out = vec![];
+    for component in node_4.origin.to_array() {
+        out.write_i32::<LittleEndian>(component).unwrap();
+    }
+    write_mask(&mut out, &node_4.child_mask);
+    write_mask(&mut out, &node_4.value_mask);
+    write_values(&mut out, &node_4.data);
+    for idx in node_4.child_mask.iter_ones() {
+        let node_3 = &node_4.nodes[&(idx as u32)];
+        write_mask(&mut out, &node_3.value_mask);
+        write_values(&mut out, &node_3.buffer);
+    }
+    compress(&out)
+}
+
+fn decode_chunk<ValueTy: Pod + GridValueType>(data: &[u8]) -> std::io::Result<Node4<ValueTy>> {
+    let mut input = Cursor::new(decompress(data)?);
+    let mut origin = [0; 3];
+    input.read_i32_into::<LittleEndian>(&mut origin)?;
+    let size = 1 << (3 * Node4::<ValueTy>::LOG_2_DIM);
+    let mut node_4 = Node4 {
+        child_mask: read_mask(&mut input, size)?,
+        value_mask: read_mask(&mut input, size)?,
+        nodes: HashMap::new(),
+        data: read_values(&mut input, size)?,
+        origin: IVec3::from_array(origin),
+    };
+    let leaf_size = 1 << (3 * Node3::<ValueTy>::LOG_2_DIM);
+    for idx in node_4.child_mask.iter_ones() {
+        let node_3 = Node3 {
+            value_mask: read_mask(&mut input, leaf_size)?,
+            buffer: read_values(&mut input, leaf_size)?,
+            origin: node_4.offset_to_global_coord(Index(idx as u32)).0,
+        };
+        node_4.nodes.insert(idx as u32, Arc::new(node_3));
+    }
+    Ok(node_4)
+}
+
+/// The root holds the grid description and the root level nodes with their tiles, whose child
+/// slots refer to chunks.
+fn encode_root<ValueTy: Pod + GridValueType>(grid: &Grid<ValueTy>) -> Vec<u8> {
+    let mut out = MAGIC.to_vec();
+    out.write_u32::<LittleEndian>(VERSION).unwrap();
+    write_string(&mut out, ValueTy::TYPE_NAME);
+    write_string(&mut out, &grid.descriptor.name);
+    write_string(&mut out, &grid.descriptor.grid_type);
+    write_string(&mut out, grid.grid_class().name());
+    for value in grid.transform.map.to_matrix().to_cols_array() {
+        out.write_f64::<LittleEndian>(value).unwrap();
+    }
+    write_values(&mut out, &[grid.tree.background]);
+    out.write_u32::<LittleEndian>(grid.tree.root_nodes.len() as u32)
+        .unwrap();
+    for node_5 in &grid.tree.root_nodes {
+        for component in node_5.origin.to_array() {
+            out.write_i32::<LittleEndian>(component).unwrap();
+        }
+        write_mask(&mut out, &node_5.child_mask);
+        write_mask(&mut out, &node_5.value_mask);
+        write_values(&mut out, &node_5.data);
+    }
+    compress(&out)
+}
+
+/// Decodes the root, with the child slots of its nodes marked in their child masks but no
+/// children loaded yet.
+fn decode_root<ValueTy: Pod + GridValueType>(data: &[u8]) -> Result<Grid<ValueTy>, ChunkedError> {
+    let mut input = Cursor::new(decompress(data)?);
+    let mut magic = [0; 8];
+    input.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(ChunkedError::MagicMismatch);
+    }
+    let version = input.read_u32::<LittleEndian>()?;
+    if version != VERSION {
+        return Err(ChunkedError::UnsupportedVersion(version));
+    }
+    let value_type = read_string(&mut input)?;
+    if value_type != ValueTy::TYPE_NAME {
+        return Err(ChunkedError::ValueTypeMismatch(value_type));
+    }
+    let name = read_string(&mut input)?;
+    let grid_type = read_string(&mut input)?;
+    let class = GridClass::from_name(&read_string(&mut input)?);
+    let mut matrix = [0.0; 16];
+    input.read_f64_into::<LittleEndian>(&mut matrix)?;
+    let background = read_values::<ValueTy>(&mut input, 1)?[0];
+
+    let size = 1 << (3 * Node5::<ValueTy>::LOG_2_DIM);
+    let mut tree = Tree::new(background);
+    for _ in 0..input.read_u32::<LittleEndian>()? {
+        let mut origin = [0; 3];
+        input.read_i32_into::<LittleEndian>(&mut origin)?;
+        tree.root_nodes.push(Node5 {
+            child_mask: read_mask(&mut input, size)?,
+            value_mask: read_mask(&mut input, size)?,
+            nodes: HashMap::new(),
+            data: read_values(&mut input, size)?,
+            origin: IVec3::from_array(origin),
+        });
+    }
+
+    let mut grid = Grid {
+        tree,
+        transform: Transform::from_matrix_simplified(DMat4::from_cols_array(&matrix)),
+        descriptor: GridDescriptor::new(name, grid_type),
+    };
+    grid.set_grid_class(class);
+    Ok(grid)
+}
+
+/// Human readable description of the stored grid, for tools that list or fetch chunks without
+/// decoding the root.
+fn encode_index<ValueTy: Pod + GridValueType>(grid: &Grid<ValueTy>) -> String {
+    let matrix = grid.transform.map.to_matrix();
+    let rows = (0..4).flat_map(|row| (0..4).map(move |column| matrix.col(column)[row]));
+    let bbox = grid.eval_active_voxel_bounding_box();
+    let (bbox_min, bbox_max) = if bbox.is_empty() {
+        ("null".to_owned(), "null".to_owned())
+    } else {
+        (
+            json_array(bbox.min.to_array()),
+            json_array(bbox.max.to_array()),
+        )
+    };
+    let leaf_voxels = 1 << (3 * Node3::<ValueTy>::LOG_2_DIM);
+    let chunks = chunk_nodes(grid)
+        .into_iter()
+        .map(|node_4| {
+            let tiles = active_tiles(&node_4.child_mask, &node_4.value_mask).count();
+            let voxels = node_4
+                .nodes
+                .values()
+                .map(|node_3| count_ones(&node_3.value_mask))
+                .sum::<usize>();
+            format!(
+                "    {{\"key\": {}, \"origin\": {}, \"active_voxel_count\": {}}}",
+                json_string(&chunk_key(node_4.origin)),
+                json_array(node_4.origin.to_array()),
+                tiles * leaf_voxels + voxels
+            )
+        })
+        .collect::<Vec<_>>();
+    let chunks = if chunks.is_empty() {
+        "[]".to_owned()
+    } else {
+        format!("[\n{}\n  ]", chunks.join(",\n"))
+    };
+    format!(
+        concat!(
+            "{{\n",
+            "  \"format\": \"vdb-chunked\",\n",
+            "  \"version\": {},\n",
+            "  \"name\": {},\n",
+            "  \"grid_type\": {},\n",
+            "  \"class\": {},\n",
+            "  \"value_type\": {},\n",
+            "  \"chunk_dim\": {},\n",
+            "  \"index_to_world\": {},\n",
+            "  \"bbox_min\": {},\n",
+            "  \"bbox_max\": {},\n",
+            "  \"chunks\": {}\n",
+            "}}\n"
+        ),
+        VERSION,
+        json_string(&grid.descriptor.name),
+        json_string(&grid.descriptor.grid_type),
+        json_string(grid.grid_class().name()),
+        json_string(ValueTy::TYPE_NAME),
+        Node4::<ValueTy>::VOXEL_DIM,
+        json_array(rows),
+        bbox_min,
+        bbox_max,
+        chunks,
+    )
+}
+
+/// Internal nodes of the grid, each stored as one chunk, ordered by origin.
+fn chunk_nodes<ValueTy>(grid: &Grid<ValueTy>) -> Vec<&Node4<ValueTy>> {
+    let mut nodes = grid
+        .tree
+        .root_nodes
+        .iter()
+        .flat_map(|node_5| node_5.nodes.values())
+        .collect::<Vec<_>>();
+    nodes.sort_by_key(|node_4| node_4.origin.to_array());
+    nodes
+}
+
+/// Keys of the chunks the child slots of a decoded root refer to.
+fn stored_chunk_keys<ValueTy>(grid: &Grid<ValueTy>) -> HashSet<String> {
+    grid.tree
+        .root_nodes
+        .iter()
+        .flat_map(|node_5| {
+            node_5
+                .child_mask
+                .iter_ones()
+                .map(|idx| chunk_key(node_5.offset_to_global_coord(Index(idx as u32)).0))
+        })
+        .collect()
+}
+
+/// The decoded root stored in `store`, if any.
+fn stored_root<ValueTy: Pod + GridValueType>(
+    store: &(impl ChunkStore + ?Sized),
+) -> Result<Option<Grid<ValueTy>>, ChunkedError> {
+    match store.get(ROOT_KEY) {
+        Ok(data) => Ok(Some(decode_root(&data)?)),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Writes the chunks of `grid` selected by `filter`, and any chunk missing from the store, then
+/// removes chunks the grid no longer has and replaces the root and index.
+fn write_chunks<ValueTy: Pod + GridValueType + Send + Sync>(
+    grid: &Grid<ValueTy>,
+    store: &(impl ChunkStore + ?Sized),
+    filter: impl Fn(&Node4<ValueTy>) -> bool,
+) -> Result<(), ChunkedError> {
+    let stored_keys = stored_root::<ValueTy>(store)?
+        .map(|root| stored_chunk_keys(&root))
+        .unwrap_or_default();
+    let nodes = chunk_nodes(grid);
+    let keys = nodes
+        .iter()
+        .map(|node_4| chunk_key(node_4.origin))
+        .collect::<HashSet<_>>();
+    let changed = nodes
+        .into_iter()
+        .filter(|node_4| filter(node_4) || !stored_keys.contains(&chunk_key(node_4.origin)))
+        .collect::<Vec<_>>();
+    par_try_map(&changed, |node_4| {
+        store.put(&chunk_key(node_4.origin), &encode_chunk(node_4))
+    })?;
+
+    // Chunks are in place before the root refers to them, so readers never see a missing one
+    store.put(ROOT_KEY, &encode_root(grid))?;
+    store.put(INDEX_KEY, encode_index(grid).as_bytes())?;
+    for key in stored_keys.difference(&keys) {
+        store.delete(key)?;
+    }
+    Ok(())
+}
+
+/// Writes `grid` to `store`, one compressed chunk per internal node holding its tiles and leaf
+/// nodes, plus a `root` object with the grid description and root level nodes and an
+/// `index.json` listing the chunks. Chunks are written in parallel, and chunks of a grid
+/// previously stored in `store` that `grid` no longer has are removed. Everything is stored
+/// little-endian, with the value type under its OpenVDB name.
+///
+/// Unlike a `.vdb` file, the stored grid can be changed in place with [`update_chunked`] and
+/// partially read with [`read_chunked_region`].
+pub fn write_chunked<ValueTy: Pod + GridValueType + Send + Sync>(
+    grid: &Grid<ValueTy>,
+    store: &(impl ChunkStore + ?Sized),
+) -> Result<(), ChunkedError> {
+    write_chunks(grid, store, |_| true)
+}
+
+/// Updates the grid stored in `store` to `grid`, which may only differ from it inside the index
+/// space `bbox`. Only the chunks overlapping `bbox` are rewritten, along with chunks that are new
+/// or removed.
+pub fn update_chunked<ValueTy: Pod + GridValueType + Send + Sync>(
+    grid: &Grid<ValueTy>,
+    store: &(impl ChunkStore + ?Sized),
+    bbox: CoordBBox,
+) -> Result<(), ChunkedError> {
+    write_chunks(grid, store, |node_4| {
+        let min = node_4.origin;
+        let node_bbox = CoordBBox::new(
+            min,
+            min + IVec3::splat(Node4::<ValueTy>::VOXEL_DIM as i32 - 1),
+        );
+        node_bbox.has_overlap(&bbox)
+    })
+}
+
+/// Reads the grid stored in `store` with [`write_chunked`].
+pub fn read_chunked<ValueTy: Pod + GridValueType + Send + Sync>(
+    store: &(impl ChunkStore + ?Sized),
+) -> Result<Grid<ValueTy>, ChunkedError> {
+    read_chunked_region(store, CoordBBox::new(IVec3::MIN, IVec3::MAX))
+}
+
+/// Reads the part of the grid stored in `store` that overlaps the index space `bbox`, fetching
+/// only the chunks it needs in parallel. Internal nodes outside `bbox` become inactive
+/// background tiles, while root level tiles are kept.
+pub fn read_chunked_region<ValueTy: Pod + GridValueType + Send + Sync>(
+    store: &(impl ChunkStore + ?Sized),
+    bbox: CoordBBox,
+) -> Result<Grid<ValueTy>, ChunkedError> {
+    let mut grid = stored_root::<ValueTy>(store)?
+        .ok_or_else(|| std::io::Error::new(ErrorKind::NotFound, "no chunked grid in the store"))?;
+    let background = grid.tree.background;
+
+    let mut slots = vec![];
+    for (root_idx, node_5) in grid.tree.root_nodes.iter_mut().enumerate() {
+        for idx in node_5.child_mask.iter_ones().collect::<Vec<_>>() {
+            if node_5.tile_bbox(Index(idx as u32)).has_overlap(&bbox) {
+                let origin = node_5.offset_to_global_coord(Index(idx as u32)).0;
+                slots.push((root_idx, idx as u32, origin));
+            } else {
+                node_5.child_mask.set(idx, false);
+                node_5.value_mask.set(idx, false);
+                node_5.data[idx] = background;
+            }
+        }
+    }
+
+    let nodes = par_try_map(&slots, |&(_, _, origin)| {
+        let node_4 = decode_chunk::<ValueTy>(&store.get(&chunk_key(origin))?)?;
+        if node_4.origin != origin {
+            let message = format!(
+                "chunk {} holds the node at {}",
+                chunk_key(origin),
+                node_4.origin
+            );
+            return Err(std::io::Error::new(ErrorKind::InvalidData, message));
+        }
+        Ok(node_4)
+    })?;
+    for ((root_idx, idx, _), node_4) in slots.into_iter().zip(nodes) {
+        grid.tree.root_nodes[root_idx].nodes.insert(idx, node_4);
+    }
+    Ok(grid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::diff_grids;
+    use crate::primitives::tests::sphere;
+
+    use glam::Vec3;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct MemoryStore(Mutex<HashMap<String, Vec<u8>>>);
+
+    impl ChunkStore for MemoryStore {
+        fn put(&self, key: &str, data: &[u8]) -> std::io::Result<()> {
+            self.0.lock().unwrap().insert(key.to_owned(), data.to_vec());
+            Ok(())
+        }
+
+        fn get(&self, key: &str) -> std::io::Result<Vec<u8>> {
+            let objects = self.0.lock().unwrap();
+            let data = objects.get(key).ok_or(ErrorKind::NotFound)?;
+            Ok(data.clone())
+        }
+
+        fn delete(&self, key: &str) -> std::io::Result<()> {
+            self.0.lock().unwrap().remove(key);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn sphere_round_trips() {
+        let store = MemoryStore::default();
+        let grid = sphere(Vec3::ZERO);
+        write_chunked(&grid, &store).unwrap();
+        let read = read_chunked::<f32>(&store).unwrap();
+
+        let diff = diff_grids(&grid, &read, 0.0);
+        assert!(diff.is_match(), "{diff:?}");
+        assert_eq!(diff.common_active, grid.active_voxel_count());
+        assert_eq!(read.descriptor.grid_type, grid.descriptor.grid_type);
+
+        let index = store.get(INDEX_KEY).unwrap();
+        assert!(String::from_utf8(index)
+            .unwrap()
+            .contains("\"value_type\": \"float\""));
+        assert!(matches!(
+            read_chunked::<f64>(&store),
+            Err(ChunkedError::ValueTypeMismatch(value_type)) if value_type == "float"
+        ));
+    }
+
+    #[test]
+    fn update_replaces_moved_chunks() {
+        let store = MemoryStore::default();
+        write_chunked(&sphere(Vec3::ZERO), &store).unwrap();
+        let moved = sphere(Vec3::new(20.0, 0.0, 0.0));
+        let bbox = CoordBBox::new(IVec3::splat(-20), IVec3::new(220, 20, 20));
+        update_chunked(&moved, &store, bbox).unwrap();
+
+        let read = read_chunked::<f32>(&store).unwrap();
+        assert!(diff_grids(&moved, &read, 0.0).is_match());
+        let chunk_count = store.0.lock().unwrap().len() - 2;
+        assert_eq!(chunk_count, chunk_nodes(&moved).len());
+    }
+
+    #[test]
+    fn region_reads_only_overlapping_chunks() {
+        let store = MemoryStore::default();
+        let grid = sphere(Vec3::ZERO);
+        write_chunked(&grid, &store).unwrap();
+        let bbox = CoordBBox::new(IVec3::ZERO, IVec3::splat(20));
+        let read = read_chunked_region::<f32>(&store, bbox).unwrap();
+
+        assert_eq!(read.tree.get_value(IVec3::new(10, 0, 0)), 0.0);
+        assert_eq!(read.tree.get_value(IVec3::new(-10, 0, 0)), 0.3);
+        assert!(read.active_voxel_count() < grid.active_voxel_count());
+    }
+}
@@ -0,0 +1,257 @@
+use crate::coordinates::{CoordBBox, GlobalCoord, Index};
+use crate::data_structure::{Grid, Node, Node3, Node4, Node5, Tree};
+
+use glam::IVec3;
+use std::sync::Arc;
+
+/// How a region of the tree relates to the region that is kept when clipping.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Coverage {
+    Inside,
+    Outside,
+    Partial,
+}
+
+impl<ValueTy, const L5: u32, const L4: u32, const L3: u32> Tree<ValueTy, L5, L4, L3> {
+    /// Whether the active voxels of this tree cover `bbox`, which is expected to be aligned to a
+    /// node, tile or voxel of a tree with the same configuration. Regions of this tree that aren't
+    /// uniform over `bbox` are reported as [`Coverage::Partial`].
+    pub fn active_coverage(&self, bbox: &CoordBBox) -> Coverage {
+        let from_active = |active: bool| {
+            if active {
+                Coverage::Inside
+            } else {
+                Coverage::Outside
+            }
+        };
+        let Some(node_5) = self.root_node(bbox.min) else {
+            return Coverage::Outside;
+        };
+        if bbox.volume() > Node5::<ValueTy, L5, L4, L3>::TILE_VOXEL_COUNT {
+            return Coverage::Partial;
+        }
+        let offset = node_5.global_coord_to_offset(GlobalCoord(bbox.min)).0;
+        let Some(node_4) = node_5.nodes.get(&offset) else {
+            return from_active(node_5.value_mask[offset as usize]);
+        };
+        if bbox.volume() > Node4::<ValueTy, L4, L3>::TILE_VOXEL_COUNT {
+            return Coverage::Partial;
+        }
+        let offset = node_4.global_coord_to_offset(GlobalCoord(bbox.min)).0;
+        let Some(node_3) = node_4.nodes.get(&offset) else {
+            return from_active(node_4.value_mask[offset as usize]);
+        };
+        if bbox.volume() > 1 {
+            return Coverage::Partial;
+        }
+        let offset = node_3.global_coord_to_offset(GlobalCoord(bbox.min)).0;
+        from_active(node_3.value_mask[offset as usize])
+    }
+}
+
+impl<ValueTy, const L5: u32, const L4: u32, const L3: u32> Tree<ValueTy, L5, L4, L3>
+where
+    ValueTy: Copy + PartialEq,
+{
+    /// Removes everything outside `bbox`: voxels and tiles outside it become inactive
+    /// background, and nodes that end up empty are pruned.
+    pub fn clip(&mut self, bbox: &CoordBBox) {
+        self.clip_with(|region| {
+            if bbox.is_inside_bbox(region) {
+                Coverage::Inside
+            } else if !bbox.has_overlap(region) {
+                Coverage::Outside
+            } else {
+                Coverage::Partial
+            }
+        });
+    }
+
+    /// Removes everything outside the active voxels and tiles of `mask`, see [`Tree::clip`].
+    pub fn clip_by_mask<MaskTy>(&mut self, mask: &Tree<MaskTy, L5, L4, L3>) {
+        self.clip_with(|region| mask.active_coverage(region));
+    }
+
+    /// Clips the tree to the region described by `coverage`, which is queried for the bounding
+    /// box of every root node, tile, child node and voxel until it reports a uniform result.
+    pub fn clip_with(&mut self, mut coverage: impl FnMut(&CoordBBox) -> Coverage) {
+        let background = self.background;
+        self.root_nodes.retain_mut(|node_5| {
+            let bbox = CoordBBox::new(
+                node_5.origin,
+                node_5.origin + IVec3::splat(Node5::<ValueTy, L5, L4, L3>::VOXEL_DIM as i32 - 1),
+            );
+            match coverage(&bbox) {
+                Coverage::Inside => true,
+                Coverage::Outside => false,
+                Coverage::Partial => {
+                    clip_node_5(node_5, background, &mut coverage);
+                    true
+                }
+            }
+        });
+        self.prune_inactive();
+    }
+
+    /// Collapses leaves and internal nodes that only contain inactive background values into
+    /// tiles, and removes root nodes that end up empty.
+    pub fn prune_inactive(&mut self) {
+        let background = self.background;
+        let is_background = |value: ValueTy, active: bool| !active && value == background;
+        for node_5 in &mut self.root_nodes {
+            for node_4 in node_5.nodes.values_mut() {
+                node_4.nodes.retain(|&idx, node_3| {
+                    let empty = node_3.value_mask.not_any()
+                        && node_3.buffer.iter().all(|&value| value == background);
+                    if empty {
+                        node_4.child_mask.set(idx as usize, false);
+                        node_4.value_mask.set(idx as usize, false);
+                        node_4.data[idx as usize] = background;
+                    }
+                    !empty
+                });
+            }
+            node_5.nodes.retain(|&idx, node_4| {
+                let empty = node_4.nodes.is_empty()
+                    && (0..node_4.data.len())
+                        .all(|idx| is_background(node_4.data[idx], node_4.value_mask[idx]));
+                if empty {
+                    node_5.child_mask.set(idx as usize, false);
+                    node_5.value_mask.set(idx as usize, false);
+                    node_5.data[idx as usize] = background;
+                }
+                !empty
+            });
+        }
+        self.root_nodes.retain(|node_5| {
+            !node_5.nodes.is_empty()
+                || (0..node_5.data.len())
+                    .any(|idx| !is_background(node_5.data[idx], node_5.value_mask[idx]))
+        });
+    }
+}
+
+fn clip_node_5<ValueTy: Copy + PartialEq, const L5: u32, const L4: u32, const L3: u32>(
+    node_5: &mut Node5<ValueTy, L5, L4, L3>,
+    background: ValueTy,
+    coverage: &mut impl FnMut(&CoordBBox) -> Coverage,
+) {
+    for idx in 0..node_5.data.len() {
+        match coverage(&node_5.tile_bbox(Index(idx as u32))) {
+            Coverage::Inside => {}
+            Coverage::Outside => {
+                node_5.nodes.remove(&(idx as u32));
+                node_5.child_mask.set(idx, false);
+                node_5.value_mask.set(idx, false);
+                node_5.data[idx] = background;
+            }
+            Coverage::Partial => {
+                if !node_5.child_mask[idx] {
+                    if !node_5.value_mask[idx] && node_5.data[idx] == background {
+                        continue;
+                    }
+                    let origin = node_5.offset_to_global_coord(Index(idx as u32)).0;
+                    let node_4 = Node4::new(origin, node_5.data[idx], node_5.value_mask[idx]);
+                    node_5.nodes.insert(idx as u32, node_4);
+                    node_5.child_mask.set(idx, true);
+                    node_5.value_mask.set(idx, false);
+                }
+                let node_4 = node_5.nodes.get_mut(&(idx as u32)).unwrap();
+                clip_node_4(node_4, background, coverage);
+            }
+        }
+    }
+}
+
+fn clip_node_4<ValueTy: Copy + PartialEq, const L4: u32, const L3: u32>(
+    node_4: &mut Node4<ValueTy, L4, L3>,
+    background: ValueTy,
+    coverage: &mut impl FnMut(&CoordBBox) -> Coverage,
+) {
+    for idx in 0..node_4.data.len() {
+        match coverage(&node_4.tile_bbox(Index(idx as u32))) {
+            Coverage::Inside => {}
+            Coverage::Outside => {
+                node_4.nodes.remove(&(idx as u32));
+                node_4.child_mask.set(idx, false);
+                node_4.value_mask.set(idx, false);
+                node_4.data[idx] = background;
+            }
+            Coverage::Partial => {
+                if !node_4.child_mask[idx] {
+                    if !node_4.value_mask[idx] && node_4.data[idx] == background {
+                        continue;
+                    }
+                    let origin = node_4.offset_to_global_coord(Index(idx as u32)).0;
+                    let node_3 = Node3::new(origin, node_4.data[idx], node_4.value_mask[idx]);
+                    node_4.nodes.insert(idx as u32, Arc::new(node_3));
+                    node_4.child_mask.set(idx, true);
+                    node_4.value_mask.set(idx, false);
+                }
+                let node_3 = Arc::make_mut(node_4.nodes.get_mut(&(idx as u32)).unwrap());
+                for idx in 0..node_3.buffer.len() {
+                    let coord = node_3.offset_to_global_coord(Index(idx as u32)).0;
+                    if coverage(&CoordBBox::new(coord, coord)) == Coverage::Outside {
+                        node_3.buffer[idx] = background;
+                        node_3.value_mask.set(idx, false);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<ValueTy, const L5: u32, const L4: u32, const L3: u32> Grid<ValueTy, L5, L4, L3>
+where
+    ValueTy: Copy + PartialEq,
+{
+    /// Removes everything outside `bbox`, see [`Tree::clip`].
+    pub fn clip(&mut self, bbox: &CoordBBox) {
+        self.tree.clip(bbox);
+    }
+
+    /// Removes everything outside the active region of `mask`, see [`Tree::clip_by_mask`].
+    pub fn clip_by_mask<MaskTy>(&mut self, mask: &Grid<MaskTy, L5, L4, L3>) {
+        self.tree.clip_by_mask(&mask.tree);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::tests::sphere;
+
+    use glam::Vec3;
+
+    #[test]
+    fn sphere_clipped_to_bbox() {
+        let original = sphere(Vec3::ZERO);
+        let mut grid = original.clone();
+        // Cuts through leaves and keeps the part of the surface around the positive x axis
+        let bbox = CoordBBox::new(IVec3::new(3, -6, -20), IVec3::new(20, 5, 20));
+        grid.clip(&bbox);
+
+        let mut inside = 0;
+        original.tree.for_each_active_voxel(|coord| {
+            if bbox.is_inside(coord) {
+                inside += 1;
+                assert!(grid.tree.is_value_on(coord), "{coord}");
+                assert_eq!(
+                    grid.tree.get_value(coord),
+                    original.tree.get_value(coord),
+                    "{coord}"
+                );
+            } else {
+                assert!(!grid.tree.is_value_on(coord), "{coord}");
+                assert_eq!(grid.tree.get_value(coord), grid.tree.background, "{coord}");
+            }
+        });
+        assert!(inside > 0);
+        assert_eq!(grid.active_voxel_count(), inside);
+        grid.tree.for_each_active_voxel(|coord| {
+            assert!(bbox.is_inside(coord), "{coord}");
+        });
+        // Leaves entirely outside the box are pruned
+        assert!(grid.tree.probe_leaf(IVec3::new(-10, 0, 0)).is_none());
+    }
+}
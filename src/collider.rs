@@ -0,0 +1,400 @@
+use crate::coordinates::CoordBBox;
+use crate::data_structure::Grid;
+use crate::ray::Ray;
+use crate::ray_intersector::LevelSetRayIntersector;
+use crate::sampling::{BoxSampler, GridSampler, SharedValueAccessor};
+use crate::visitor::NodeVisitor;
+use crate::volume_to_mesh::volume_to_mesh_adaptive;
+
+use glam::{DVec3, IVec3, Vec3};
+use std::fmt;
+use std::sync::Arc;
+
+/// Number of Newton steps [`SdfCollider::project_point`] takes towards the surface.
+const PROJECT_STEPS: u32 = 4;
+
+/// Triangle mesh of the `isovalue` surface of the level set `grid` for trimesh colliders, with
+/// world space positions and triangles wound counter-clockwise when seen from the outside.
+///
+/// The mesh is decimated by [`volume_to_mesh_adaptive`] with the given `adaptivity`, flat
+/// regions being covered by few large faces, and its quads are split into two triangles.
+pub fn sdf_to_trimesh<const L5: u32, const L4: u32, const L3: u32>(
+    grid: &Grid<f32, L5, L4, L3>,
+    isovalue: f32,
+    adaptivity: f32,
+) -> (Vec<Vec3>, Vec<[u32; 3]>) {
+    let (positions, mut triangles, quads) = volume_to_mesh_adaptive(grid, isovalue, adaptivity);
+    triangles.extend(
+        quads
+            .into_iter()
+            .flat_map(|[a, b, c, d]| [[a, b, c], [a, c, d]]),
+    );
+    (positions, triangles)
+}
+
+/// Collects the index space boxes of the voxels and tiles inside the surface, merging voxels
+/// that follow each other along `z` in a leaf into a single box.
+struct InsideBoxes {
+    isovalue: f32,
+    boxes: Vec<CoordBBox>,
+}
+
+impl NodeVisitor<f32> for InsideBoxes {
+    fn visit_tile(&mut self, bbox: CoordBBox, value: f32, _active: bool) {
+        if value < self.isovalue {
+            self.boxes.push(bbox);
+        }
+    }
+
+    fn visit_voxel(&mut self, coord: IVec3, value: f32, _active: bool) {
+        if value >= self.isovalue {
+            return;
+        }
+        // Voxels of a leaf are visited with z varying fastest
+        match self.boxes.last_mut() {
+            Some(run) if run.max + IVec3::Z == coord && run.min.truncate() == coord.truncate() => {
+                run.max = coord;
+            }
+            _ => self.boxes.push(CoordBBox::new(coord, coord)),
+        }
+    }
+}
+
+/// World space boxes covering the voxels and tiles of the level set `grid` inside its
+/// `isovalue` surface, for compound colliders of cuboids, as `(min, max)` corners.
+///
+/// Voxels are centered on their index space coordinates, and runs of inside voxels along `z`
+/// within a leaf node share a box. The boxes are the world space bounds of index space boxes,
+/// so they are exact for grids whose transform doesn't rotate.
+pub fn sdf_to_voxel_aabbs(grid: &Grid<f32>, isovalue: f32) -> Vec<(Vec3, Vec3)> {
+    let mut inside = InsideBoxes {
+        isovalue,
+        boxes: vec![],
+    };
+    grid.tree.visit(&mut inside);
+    inside
+        .boxes
+        .iter()
+        .map(|bbox| world_bounds(grid, bbox))
+        .map(|(min, max)| (min.as_vec3(), max.as_vec3()))
+        .collect()
+}
+
+/// World space bounds of the region covered by the voxels in `bbox`.
+fn world_bounds(grid: &Grid<f32>, bbox: &CoordBBox) -> (DVec3, DVec3) {
+    let (min, max) = (bbox.min.as_dvec3() - 0.5, bbox.max.as_dvec3() + 0.5);
+    (0..8)
+        .map(|corner| {
+            let index = DVec3::new(
+                if corner & 1 == 0 { min.x } else { max.x },
+                if corner & 2 == 0 { min.y } else { max.y },
+                if corner & 4 == 0 { min.z } else { max.z },
+            );
+            grid.transform.index_to_world_f64(index)
+        })
+        .fold(
+            (DVec3::splat(f64::INFINITY), DVec3::splat(f64::NEG_INFINITY)),
+            |(world_min, world_max), world| (world_min.min(world), world_max.max(world)),
+        )
+}
+
+/// Collision queries answered directly by a level set, without meshing it: signed distances,
+/// containment, closest surface points and ray casts, all in world space. Values below
+/// `isovalue` are inside.
+///
+/// The grid is shared through an [`Arc`], so the collider can be owned by a physics engine's
+/// shape. With the `rapier` feature it implements the Rapier `Shape` trait, see
+/// `SdfCollider::into_shared_shape`.
+pub struct SdfCollider {
+    /// Sampler over `grid`, built once so its leaf caches carry over between queries. Declared
+    /// before `grid` so it is dropped first.
+    sampler: SdfSampler,
+    grid: Arc<Grid<f32>>,
+    isovalue: f32,
+    aabb: (DVec3, DVec3),
+}
+
+type SdfSampler = GridSampler<'static, BoxSampler, f32, 5, 4, 3, SharedValueAccessor<'static, f32>>;
+
+/// Sampler over the grid behind `grid`, which must outlive it.
+fn sdf_sampler(grid: &Arc<Grid<f32>>) -> SdfSampler {
+    // SAFETY: the grid lives on the heap behind an `Arc` that the collider keeps and never
+    // mutates through, and the sampler is dropped before it.
+    let grid: &'static Grid<f32> = unsafe { &*Arc::as_ptr(grid) };
+    GridSampler::new_shared(grid)
+}
+
+impl Clone for SdfCollider {
+    fn clone(&self) -> Self {
+        Self {
+            sampler: sdf_sampler(&self.grid),
+            grid: self.grid.clone(),
+            isovalue: self.isovalue,
+            aabb: self.aabb,
+        }
+    }
+}
+
+impl fmt::Debug for SdfCollider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SdfCollider")
+            .field("grid", &self.grid)
+            .field("isovalue", &self.isovalue)
+            .field("aabb", &self.aabb)
+            .finish()
+    }
+}
+
+impl SdfCollider {
+    /// Collider of the zero crossing of the level set `grid`.
+    pub fn new(grid: Arc<Grid<f32>>) -> Self {
+        let mut bbox = grid.eval_active_voxel_bounding_box();
+        let aabb = if bbox.is_empty() {
+            (DVec3::ZERO, DVec3::ZERO)
+        } else {
+            bbox.expand(1);
+            world_bounds(&grid, &bbox)
+        };
+        Self {
+            sampler: sdf_sampler(&grid),
+            grid,
+            isovalue: 0.0,
+            aabb,
+        }
+    }
+
+    /// Collides with the `isovalue` crossing of the level set instead of its zero crossing.
+    pub fn with_isovalue(mut self, isovalue: f32) -> Self {
+        self.isovalue = isovalue;
+        self
+    }
+
+    pub fn grid(&self) -> &Arc<Grid<f32>> {
+        &self.grid
+    }
+
+    /// World space bounds of the narrow band, outside of which there is no surface.
+    pub fn aabb(&self) -> (DVec3, DVec3) {
+        self.aabb
+    }
+
+    /// Signed distance from `point` to the surface, negative inside. Outside the narrow band the
+    /// distance is clamped to the background value.
+    pub fn distance(&self, point: DVec3) -> f64 {
+        (self.sampler.sample_shared(point) - self.isovalue) as f64
+    }
+
+    pub fn contains_point(&self, point: DVec3) -> bool {
+        self.distance(point) < 0.0
+    }
+
+    /// Outward unit normal of the level set at `point`, from central differences over half a
+    /// voxel, or zero where the level set is flat such as outside the narrow band.
+    pub fn normal(&self, point: DVec3) -> DVec3 {
+        let index = self.grid.transform.world_to_index_f64(point);
+        let gradient = DVec3::from_array([IVec3::X, IVec3::Y, IVec3::Z].map(|axis| {
+            let offset = axis.as_dvec3() * 0.5;
+            let sampler = &self.sampler;
+            (sampler.sample_index_shared(index + offset)
+                - sampler.sample_index_shared(index - offset)) as f64
+        }));
+        // Gradients transform with the inverse transpose of the index to world matrix
+        let matrix = self.grid.transform.map.to_matrix().inverse().transpose();
+        matrix.transform_vector3(gradient).normalize_or_zero()
+    }
+
+    /// Closest point on the surface to `point` and whether `point` is inside, found by stepping
+    /// along the normal by the signed distance a few times. Only points within the narrow band
+    /// are projected accurately.
+    pub fn project_point(&self, point: DVec3) -> (DVec3, bool) {
+        let inside = self.contains_point(point);
+        let mut projected = point;
+        for _ in 0..PROJECT_STEPS {
+            let distance = self.distance(projected);
+            let normal = self.normal(projected);
+            if distance == 0.0 || normal == DVec3::ZERO {
+                break;
+            }
+            projected -= normal * distance;
+        }
+        (projected, inside)
+    }
+
+    /// First hit of `ray` with the surface, returning the ray parameter and the outward unit
+    /// normal there, see [`LevelSetRayIntersector`].
+    pub fn cast_ray(&self, ray: &Ray) -> Option<(f64, DVec3)> {
+        LevelSetRayIntersector::new(&self.grid)
+            .with_isovalue(self.isovalue)
+            .intersects(ray)
+            .map(|(t, _, normal)| (t, normal))
+    }
+}
+
+/// [`SdfCollider`] as a Rapier shape, so level sets collide through a `SharedShape` without
+/// being meshed.
+#[cfg(feature = "rapier")]
+mod rapier {
+    use super::*;
+
+    use rapier3d::parry::bounding_volume::{Aabb, BoundingSphere};
+    use rapier3d::parry::mass_properties::MassProperties;
+    use rapier3d::parry::math::{Isometry, Point, Real, Vector};
+    use rapier3d::parry::query::{self, PointProjection, PointQuery, RayCast, RayIntersection};
+    use rapier3d::parry::shape::{FeatureId, Shape, ShapeType, SharedShape, TypedShape};
+
+    /// Identifies [`SdfCollider`]s in [`TypedShape::Custom`].
+    pub const SDF_COLLIDER_SHAPE_ID: u32 = u32::from_le_bytes(*b"vdb0");
+
+    fn to_dvec3(vector: &Vector<Real>) -> DVec3 {
+        DVec3::new(vector.x as f64, vector.y as f64, vector.z as f64)
+    }
+
+    fn to_vector(vector: DVec3) -> Vector<Real> {
+        Vector::new(vector.x as Real, vector.y as Real, vector.z as Real)
+    }
+
+    impl SdfCollider {
+        /// Rapier shape colliding with this level set, see [`SdfCollider`].
+        pub fn into_shared_shape(self) -> SharedShape {
+            SharedShape::new(self)
+        }
+    }
+
+    impl PointQuery for SdfCollider {
+        fn project_local_point(&self, pt: &Point<Real>, solid: bool) -> PointProjection {
+            let (projected, inside) = self.project_point(to_dvec3(&pt.coords));
+            if solid && inside {
+                PointProjection::new(true, *pt)
+            } else {
+                PointProjection::new(inside, to_vector(projected).into())
+            }
+        }
+
+        fn project_local_point_and_get_feature(
+            &self,
+            pt: &Point<Real>,
+        ) -> (PointProjection, FeatureId) {
+            (self.project_local_point(pt, false), FeatureId::Unknown)
+        }
+
+        fn contains_local_point(&self, pt: &Point<Real>) -> bool {
+            self.contains_point(to_dvec3(&pt.coords))
+        }
+    }
+
+    impl RayCast for SdfCollider {
+        fn cast_local_ray_and_get_normal(
+            &self,
+            ray: &query::Ray,
+            max_toi: Real,
+            solid: bool,
+        ) -> Option<RayIntersection> {
+            let origin = to_dvec3(&ray.origin.coords);
+            let inside = self.contains_point(origin);
+            if solid && inside {
+                return Some(RayIntersection::new(
+                    0.0,
+                    Vector::zeros(),
+                    FeatureId::Unknown,
+                ));
+            }
+            let ray = Ray::new(origin, to_dvec3(&ray.dir)).with_range(0.0, max_toi as f64);
+            // Rays leaving the surface from the inside report the normal facing inward
+            let (toi, normal) = self.cast_ray(&ray)?;
+            let normal = if inside { -normal } else { normal };
+            Some(RayIntersection::new(
+                toi as Real,
+                to_vector(normal),
+                FeatureId::Unknown,
+            ))
+        }
+    }
+
+    impl Shape for SdfCollider {
+        fn compute_local_aabb(&self) -> Aabb {
+            let (min, max) = self.aabb;
+            Aabb::new(to_vector(min).into(), to_vector(max).into())
+        }
+
+        fn compute_local_bounding_sphere(&self) -> BoundingSphere {
+            self.compute_local_aabb().bounding_sphere()
+        }
+
+        fn clone_box(&self) -> Box<dyn Shape> {
+            Box::new(self.clone())
+        }
+
+        /// Mass properties of the boxes of [`sdf_to_voxel_aabbs`] filling the inside.
+        fn mass_properties(&self, density: Real) -> MassProperties {
+            sdf_to_voxel_aabbs(&self.grid, self.isovalue)
+                .into_iter()
+                .map(|(min, max)| {
+                    let half_extents = (max - min) * 0.5;
+                    let center = (min + max) * 0.5;
+                    MassProperties::from_cuboid(density, to_vector(half_extents.as_dvec3()))
+                        .transform_by(&Isometry::translation(center.x, center.y, center.z))
+                })
+                .sum()
+        }
+
+        fn shape_type(&self) -> ShapeType {
+            ShapeType::Custom
+        }
+
+        fn as_typed_shape(&self) -> TypedShape<'_> {
+            TypedShape::Custom(SDF_COLLIDER_SHAPE_ID)
+        }
+
+        /// Thinnest features a level set can resolve are about a voxel wide.
+        fn ccd_thickness(&self) -> Real {
+            self.grid.transform.voxel_size().min_element() as Real
+        }
+
+        fn ccd_angular_thickness(&self) -> Real {
+            std::f32::consts::FRAC_PI_4
+        }
+    }
+}
+#[cfg(feature = "rapier")]
+pub use rapier::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::tests::sphere;
+
+    #[test]
+    fn sphere_collider_matches_analytic_sphere() {
+        let center = DVec3::new(0.5, 0.0, 0.0);
+        let collider = SdfCollider::new(Arc::new(sphere(center.as_vec3())));
+        for i in 0..200 {
+            let t = i as f64 * 0.1;
+            let direction = DVec3::new(t.sin(), (3.0 * t).cos(), (7.0 * t).sin()).normalize();
+            // Points within two voxels of the surface, inside the narrow band
+            let radius = [0.83, 0.95, 1.04, 1.17][i % 4];
+            let point = center + direction * radius;
+
+            let distance = collider.distance(point);
+            assert!(
+                (distance - (radius - 1.0)).abs() < 0.01,
+                "{point}: {distance}"
+            );
+            assert_eq!(collider.contains_point(point), radius < 1.0, "{point}");
+
+            let normal = collider.normal(point);
+            assert!(normal.dot(direction) > 0.999, "{point}: {normal}");
+
+            let (projected, inside) = collider.project_point(point);
+            assert_eq!(inside, radius < 1.0, "{point}");
+            let expected = center + direction;
+            assert!(projected.distance(expected) < 0.01, "{point}: {projected}");
+        }
+        assert_eq!(collider.distance(DVec3::splat(5.0)), 0.3f32 as f64);
+        assert_eq!(collider.normal(DVec3::splat(5.0)), DVec3::ZERO);
+
+        let ray = Ray::new(DVec3::new(-2.0, 0.0, 0.0), DVec3::X);
+        let (t, normal) = collider.clone().cast_ray(&ray).unwrap();
+        assert!((t - 1.5).abs() < 0.01, "{t}");
+        assert!(normal.distance(-DVec3::X) < 0.01, "{normal}");
+    }
+}
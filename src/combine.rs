@@ -0,0 +1,243 @@
+use crate::coordinates::Index;
+use crate::data_structure::{Grid, Node, Node3, Node4, Node5, Tree};
+
+use std::ops::{Add, Mul};
+use std::sync::Arc;
+
+/// Per-voxel operations for [`Tree::combine`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CombineOp {
+    /// Maximum of both values.
+    Max,
+    /// Minimum of both values.
+    Min,
+    /// Sum of both values.
+    Sum,
+    /// Product of both values.
+    Mult,
+    /// Composites the other grid over this one: active values of the other grid replace the
+    /// values of this grid, everything else is left untouched.
+    Replace,
+}
+
+impl CombineOp {
+    /// Combines two `(value, active)` pairs, the result is active if either input is.
+    pub fn apply<ValueTy>(self, a: (ValueTy, bool), b: (ValueTy, bool)) -> (ValueTy, bool)
+    where
+        ValueTy: Copy + PartialOrd + Add<Output = ValueTy> + Mul<Output = ValueTy>,
+    {
+        let value = match self {
+            CombineOp::Max => {
+                if b.0 > a.0 {
+                    b.0
+                } else {
+                    a.0
+                }
+            }
+            CombineOp::Min => {
+                if b.0 < a.0 {
+                    b.0
+                } else {
+                    a.0
+                }
+            }
+            CombineOp::Sum => a.0 + b.0,
+            CombineOp::Mult => a.0 * b.0,
+            CombineOp::Replace => {
+                if b.1 {
+                    b.0
+                } else {
+                    a.0
+                }
+            }
+        };
+        (value, a.1 || b.1)
+    }
+}
+
+/// What the other tree holds in the region covered by a slot of this tree.
+enum Other<'a, NodeTy, ValueTy> {
+    Node(&'a NodeTy),
+    Tile((ValueTy, bool)),
+}
+
+impl<ValueTy: Copy, const L5: u32, const L4: u32, const L3: u32> Tree<ValueTy, L5, L4, L3> {
+    /// Combines `other` into this tree with `op`, see [`Tree::combine_with`].
+    pub fn combine(&mut self, other: &Self, op: CombineOp)
+    where
+        ValueTy: PartialOrd + Add<Output = ValueTy> + Mul<Output = ValueTy>,
+    {
+        self.combine_with(other, |a, b| op.apply(a, b));
+    }
+
+    /// Combines every voxel of this tree with the voxel at the same coordinate in `other`,
+    /// where `op` receives and returns `(value, active)` pairs.
+    ///
+    /// The result covers the union of both topologies: regions where both trees only hold tiles
+    /// stay tiles, and only regions where either tree has child nodes are densified. The new
+    /// background is `op` applied to both backgrounds.
+    pub fn combine_with(
+        &mut self,
+        other: &Self,
+        mut op: impl FnMut((ValueTy, bool), (ValueTy, bool)) -> (ValueTy, bool),
+    ) {
+        for other_5 in &other.root_nodes {
+            if self.root_node(other_5.origin).is_none() {
+                self.root_nodes
+                    .push(Node5::new(other_5.origin, self.background, false));
+            }
+        }
+
+        let other_background = (other.background, false);
+        for node_5 in &mut self.root_nodes {
+            let other_5 = other.root_node(node_5.origin);
+            for idx in 0..node_5.data.len() {
+                let other_slot = match other_5 {
+                    Some(other_5) if other_5.child_mask[idx] => {
+                        Other::Node(&other_5.nodes[&(idx as u32)])
+                    }
+                    Some(other_5) => Other::Tile((other_5.data[idx], other_5.value_mask[idx])),
+                    None => Other::Tile(other_background),
+                };
+
+                match other_slot {
+                    Other::Tile(b) if !node_5.child_mask[idx] => {
+                        let (value, active) = op((node_5.data[idx], node_5.value_mask[idx]), b);
+                        node_5.data[idx] = value;
+                        node_5.value_mask.set(idx, active);
+                    }
+                    other_slot => {
+                        if !node_5.child_mask[idx] {
+                            let origin = node_5.offset_to_global_coord(Index(idx as u32)).0;
+                            let node_4 =
+                                Node4::new(origin, node_5.data[idx], node_5.value_mask[idx]);
+                            node_5.nodes.insert(idx as u32, node_4);
+                            node_5.child_mask.set(idx, true);
+                            node_5.value_mask.set(idx, false);
+                        }
+                        let node_4 = node_5.nodes.get_mut(&(idx as u32)).unwrap();
+                        combine_node_4(node_4, other_slot, &mut op);
+                    }
+                }
+            }
+        }
+
+        self.background = op((self.background, false), other_background).0;
+    }
+}
+
+fn combine_node_4<ValueTy: Copy, const L4: u32, const L3: u32>(
+    node_4: &mut Node4<ValueTy, L4, L3>,
+    other: Other<'_, Node4<ValueTy, L4, L3>, ValueTy>,
+    op: &mut impl FnMut((ValueTy, bool), (ValueTy, bool)) -> (ValueTy, bool),
+) {
+    for idx in 0..node_4.data.len() {
+        let other_slot = match other {
+            Other::Node(other_4) if other_4.child_mask[idx] => {
+                Other::Node(other_4.nodes[&(idx as u32)].as_ref())
+            }
+            Other::Node(other_4) => Other::Tile((other_4.data[idx], other_4.value_mask[idx])),
+            Other::Tile(b) => Other::Tile(b),
+        };
+
+        match other_slot {
+            Other::Tile(b) if !node_4.child_mask[idx] => {
+                let (value, active) = op((node_4.data[idx], node_4.value_mask[idx]), b);
+                node_4.data[idx] = value;
+                node_4.value_mask.set(idx, active);
+            }
+            other_slot => {
+                if !node_4.child_mask[idx] {
+                    let origin = node_4.offset_to_global_coord(Index(idx as u32)).0;
+                    let node_3 = Node3::new(origin, node_4.data[idx], node_4.value_mask[idx]);
+                    node_4.nodes.insert(idx as u32, Arc::new(node_3));
+                    node_4.child_mask.set(idx, true);
+                    node_4.value_mask.set(idx, false);
+                }
+                let node_3 = Arc::make_mut(node_4.nodes.get_mut(&(idx as u32)).unwrap());
+                combine_node_3(node_3, other_slot, op);
+            }
+        }
+    }
+}
+
+fn combine_node_3<ValueTy: Copy, const L3: u32>(
+    node_3: &mut Node3<ValueTy, L3>,
+    other: Other<'_, Node3<ValueTy, L3>, ValueTy>,
+    op: &mut impl FnMut((ValueTy, bool), (ValueTy, bool)) -> (ValueTy, bool),
+) {
+    for idx in 0..node_3.buffer.len() {
+        let b = match other {
+            Other::Node(other_3) => (other_3.buffer[idx], other_3.value_mask[idx]),
+            Other::Tile(b) => b,
+        };
+        let (value, active) = op((node_3.buffer[idx], node_3.value_mask[idx]), b);
+        node_3.buffer[idx] = value;
+        node_3.value_mask.set(idx, active);
+    }
+}
+
+impl<ValueTy: Copy, const L5: u32, const L4: u32, const L3: u32> Grid<ValueTy, L5, L4, L3> {
+    /// Combines the tree of `other` into the tree of this grid, see [`Tree::combine`]. Both grids
+    /// are assumed to share the same transform.
+    pub fn combine(&mut self, other: &Self, op: CombineOp)
+    where
+        ValueTy: PartialOrd + Add<Output = ValueTy> + Mul<Output = ValueTy>,
+    {
+        self.tree.combine(&other.tree, op);
+    }
+
+    /// Combines the tree of `other` into the tree of this grid, see [`Tree::combine_with`].
+    pub fn combine_with(
+        &mut self,
+        other: &Self,
+        op: impl FnMut((ValueTy, bool), (ValueTy, bool)) -> (ValueTy, bool),
+    ) {
+        self.tree.combine_with(&other.tree, op);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::tests::sphere;
+
+    use glam::{IVec3, Vec3};
+
+    #[test]
+    fn sphere_grids_combine_per_voxel() {
+        let a = sphere(Vec3::ZERO);
+        let b = sphere(Vec3::new(0.5, 0.0, 0.0));
+        for op in [CombineOp::Min, CombineOp::Max, CombineOp::Sum] {
+            let mut combined = a.clone();
+            combined.combine(&b, op);
+            assert_eq!(
+                combined.tree.background,
+                op.apply((0.3, false), (0.3, false)).0
+            );
+            let mut check = |coord: IVec3| {
+                let a = (a.tree.get_value(coord), a.tree.is_value_on(coord));
+                let b = (b.tree.get_value(coord), b.tree.is_value_on(coord));
+                let result = (
+                    combined.tree.get_value(coord),
+                    combined.tree.is_value_on(coord),
+                );
+                assert_eq!(result, op.apply(a, b), "{op:?} {coord}");
+            };
+            a.tree.for_each_active_voxel(&mut check);
+            b.tree.for_each_active_voxel(&mut check);
+            assert!(combined.active_voxel_count() >= a.active_voxel_count());
+        }
+
+        // The union keeps the leftmost crossing of `a` and the rightmost crossing of `b`, both
+        // active, and the inside of `b` where `a` crosses on the right
+        let mut union = a.clone();
+        union.combine(&b, CombineOp::Min);
+        assert!(union.tree.get_value(IVec3::new(-10, 0, 0)).abs() < 1e-4);
+        assert!(union.tree.get_value(IVec3::new(15, 0, 0)).abs() < 1e-4);
+        assert_eq!(union.tree.get_value(IVec3::new(10, 0, 0)), -0.3);
+        assert!(union.tree.is_value_on(IVec3::new(10, 0, 0)));
+        assert_eq!(union.tree.get_value(IVec3::new(30, 0, 0)), 0.3);
+        assert!(!union.tree.is_value_on(IVec3::new(30, 0, 0)));
+    }
+}
@@ -0,0 +1,314 @@
+use crate::data_structure::{Compression, Grid, MaskWord};
+use crate::reader::negative;
+
+use bitvec::prelude::*;
+use bytemuck::{bytes_of, cast_slice, Pod};
+use half::f16;
+use std::any::TypeId;
+use std::io::Write;
+
+/// Codec the values of a grid are compressed with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Codec {
+    None,
+    Zip,
+    /// Only estimated with the `blosc` feature
+    Blosc,
+}
+
+/// Estimated size of a grid stored with one combination of settings, see [`analyze`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CompressionEstimate {
+    pub codec: Codec,
+    /// Whether only the active values of nodes are stored, see [`Compression::ACTIVE_MASK`]
+    pub active_mask: bool,
+    /// Whether values are stored as half floats, which loses precision
+    pub half: bool,
+    /// Estimated size in bytes of the topology and values of the grid
+    pub bytes: u64,
+}
+
+impl CompressionEstimate {
+    /// Compression flags a grid stored with these settings is written with. Half floats are
+    /// requested with the `is_saved_as_half_float` metadata instead.
+    pub fn compression(&self) -> Compression {
+        let codec = match self.codec {
+            Codec::None => Compression::NONE,
+            Codec::Zip => Compression::ZIP,
+            Codec::Blosc => Compression::BLOSC,
+        };
+        if self.active_mask {
+            codec | Compression::ACTIVE_MASK
+        } else {
+            codec
+        }
+    }
+}
+
+/// Estimated sizes of a grid under every combination of codec, active mask compression and
+/// half float quantization, computed by [`analyze`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CompressionReport {
+    /// Estimates sorted from smallest to largest
+    pub estimates: Vec<CompressionEstimate>,
+}
+
+impl CompressionReport {
+    pub fn get(&self, codec: Codec, active_mask: bool, half: bool) -> Option<&CompressionEstimate> {
+        self.estimates.iter().find(|estimate| {
+            estimate.codec == codec && estimate.active_mask == active_mask && estimate.half == half
+        })
+    }
+
+    /// Size of the grid stored without any compression.
+    pub fn uncompressed_bytes(&self) -> u64 {
+        self.get(Codec::None, false, false)
+            .map_or(0, |estimate| estimate.bytes)
+    }
+
+    pub fn smallest(&self) -> Option<&CompressionEstimate> {
+        self.estimates.first()
+    }
+
+    /// Smallest estimate that keeps the values at full precision.
+    pub fn smallest_lossless(&self) -> Option<&CompressionEstimate> {
+        self.estimates.iter().find(|estimate| !estimate.half)
+    }
+
+    /// How many times smaller `estimate` is than the uncompressed grid.
+    pub fn ratio(&self, estimate: &CompressionEstimate) -> f64 {
+        self.uncompressed_bytes() as f64 / estimate.bytes.max(1) as f64
+    }
+}
+
+/// Size of `bytes` once compressed with `codec`, including the size prefix written before
+/// compressed data. Data that doesn't compress is stored as is.
+fn compressed_size(bytes: &[u8], type_size: usize, codec: Codec) -> std::io::Result<u64> {
+    let len = bytes.len() as u64;
+    Ok(match codec {
+        Codec::None => len,
+        Codec::Zip => {
+            let mut encoder =
+                flate2::write::ZlibEncoder::new(vec![], flate2::Compression::default());
+            encoder.write_all(bytes)?;
+            8 + (encoder.finish()?.len() as u64).min(len)
+        }
+        Codec::Blosc => 8 + blosc_size(bytes, type_size).min(len),
+    })
+}
+
+/// Blosc settings match those OpenVDB writes with: LZ4 at level 9 with byte shuffling.
+#[cfg(feature = "blosc")]
+fn blosc_size(bytes: &[u8], type_size: usize) -> u64 {
+    let mut dest = vec![0u8; bytes.len() + blosc_src::BLOSC_MAX_OVERHEAD as usize];
+    let size = unsafe {
+        blosc_src::blosc_compress_ctx(
+            9,
+            blosc_src::BLOSC_SHUFFLE as i32,
+            type_size,
+            bytes.len(),
+            bytes.as_ptr().cast(),
+            dest.as_mut_ptr().cast(),
+            dest.len(),
+            blosc_src::BLOSC_LZ4_COMPNAME.as_ptr().cast(),
+            0,
+            1,
+        )
+    };
+    if size > 0 {
+        size as u64
+    } else {
+        bytes.len() as u64
+    }
+}
+
+#[cfg(not(feature = "blosc"))]
+fn blosc_size(bytes: &[u8], _type_size: usize) -> u64 {
+    bytes.len() as u64
+}
+
+fn mask_bytes(len: usize) -> u64 {
+    len.div_ceil(64) as u64 * 8
+}
+
+/// Whether values of type `T` can be stored as half floats, which is the case for types made
+/// of `f32` components.
+fn supports_half<T: 'static>() -> bool {
+    TypeId::of::<T>() == TypeId::of::<f32>() || TypeId::of::<T>() == TypeId::of::<[f32; 3]>()
+}
+
+/// Size of the values of a node, one per slot, stored the way OpenVDB writes them: a byte
+/// describing which inactive values are stored, followed by those values, the mask selecting
+/// between them, and the compressed values themselves.
+fn values_size<T: Pod>(
+    values: &[T],
+    value_mask: &BitSlice<MaskWord, Lsb0>,
+    background: T,
+    settings: &CompressionEstimate,
+) -> std::io::Result<u64> {
+    let same = |a: &T, b: &T| bytes_of(a) == bytes_of(b);
+    let value_size = std::mem::size_of::<T>() as u64;
+    let mut size = 1;
+    let mut stored = values.to_vec();
+
+    if settings.active_mask {
+        let mut inactive: Vec<T> = vec![];
+        for (value, active) in values.iter().zip(value_mask.iter().by_vals()) {
+            if !active && !inactive.iter().any(|other| same(other, value)) {
+                inactive.push(*value);
+                if inactive.len() > 2 {
+                    break;
+                }
+            }
+        }
+        let minus_background = negative(background);
+        let is_background = |value: &T| same(value, &background);
+        let is_either = |value: &T| {
+            is_background(value) || minus_background.is_some_and(|minus| same(value, &minus))
+        };
+        let selection_mask = mask_bytes(values.len());
+        let extra = match inactive.as_slice() {
+            [] => Some(0),
+            [a] if is_either(a) => Some(0),
+            [_] => Some(value_size),
+            [a, b] if is_either(a) && is_either(b) => Some(selection_mask),
+            [a, b] if is_background(a) || is_background(b) => Some(value_size + selection_mask),
+            [_, _] => Some(2 * value_size + selection_mask),
+            _ => None,
+        };
+        if let Some(extra) = extra {
+            size += extra;
+            stored = values
+                .iter()
+                .zip(value_mask.iter().by_vals())
+                .filter(|(_, active)| *active)
+                .map(|(value, _)| *value)
+                .collect();
+        }
+    }
+
+    if settings.half {
+        let halves = cast_slice::<T, f32>(&stored)
+            .iter()
+            .map(|&value| f16::from_f32(value))
+            .collect::<Vec<_>>();
+        let type_size = std::mem::size_of::<T>() / 2;
+        Ok(size + compressed_size(cast_slice(&halves), type_size, settings.codec)?)
+    } else {
+        let type_size = std::mem::size_of::<T>();
+        Ok(size + compressed_size(cast_slice(&stored), type_size, settings.codec)?)
+    }
+}
+
+/// Estimates the size of `grid` when stored with every combination of codec, active mask
+/// compression and, for `f32` based grids, half float quantization, by compressing its nodes
+/// the way OpenVDB writes them. Use it to pick per-grid settings from the data rather than by
+/// guesswork.
+///
+/// Grid metadata and transform are left out, they take the same space with every setting.
+/// Fails if compressing the values does.
+pub fn analyze<ValueTy: Pod, const L5: u32, const L4: u32, const L3: u32>(
+    grid: &Grid<ValueTy, L5, L4, L3>,
+) -> std::io::Result<CompressionReport> {
+    let mut codecs = vec![Codec::None, Codec::Zip];
+    if cfg!(feature = "blosc") {
+        codecs.push(Codec::Blosc);
+    }
+    let half = if supports_half::<ValueTy>() {
+        vec![false, true]
+    } else {
+        vec![false]
+    };
+    let mut estimates = vec![];
+    for &codec in &codecs {
+        for active_mask in [false, true] {
+            for &half in &half {
+                estimates.push(CompressionEstimate {
+                    codec,
+                    active_mask,
+                    half,
+                    bytes: 0,
+                });
+            }
+        }
+    }
+
+    // Every node stores its masks uncompressed, followed by its values. Leaves store their
+    // value mask twice, once with the topology and once with their values.
+    let tree = &grid.tree;
+    let background = tree.background;
+    let value_size = std::mem::size_of::<ValueTy>() as u64;
+    let mut fixed = 4 + value_size + 4 + 4;
+    for node_5 in &tree.root_nodes {
+        fixed += 12 + 2 * mask_bytes(node_5.value_mask.len());
+        for estimate in &mut estimates {
+            estimate.bytes += values_size(&node_5.data, &node_5.value_mask, background, estimate)?;
+        }
+        for node_4 in node_5.nodes.values() {
+            fixed += 2 * mask_bytes(node_4.value_mask.len());
+            for estimate in &mut estimates {
+                estimate.bytes +=
+                    values_size(&node_4.data, &node_4.value_mask, background, estimate)?;
+            }
+            for node_3 in node_4.nodes.values() {
+                fixed += 2 * mask_bytes(node_3.value_mask.len());
+                for estimate in &mut estimates {
+                    estimate.bytes +=
+                        values_size(&node_3.buffer, &node_3.value_mask, background, estimate)?;
+                }
+            }
+        }
+    }
+
+    for estimate in &mut estimates {
+        estimate.bytes += fixed;
+    }
+    estimates.sort_by_key(|estimate| estimate.bytes);
+    Ok(CompressionReport { estimates })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::tests::archive;
+    use crate::reader::VdbReader;
+
+    #[test]
+    fn report_covers_every_codec() {
+        let mut reader = VdbReader::new(archive(&[("a", 1.5)])).unwrap();
+        let grid = reader.read_grid::<f32>("a").unwrap();
+        let report = analyze(&grid).unwrap();
+
+        let mut codecs = vec![Codec::None, Codec::Zip];
+        if cfg!(feature = "blosc") {
+            codecs.push(Codec::Blosc);
+        }
+        assert_eq!(report.estimates.len(), codecs.len() * 4);
+        for codec in codecs {
+            for active_mask in [false, true] {
+                for half in [false, true] {
+                    let estimate = report.get(codec, active_mask, half).unwrap();
+                    assert!(estimate.bytes > 0, "{estimate:?}");
+                    assert_eq!(
+                        estimate.compression().contains(Compression::ACTIVE_MASK),
+                        active_mask
+                    );
+                }
+            }
+        }
+        assert!(report
+            .estimates
+            .windows(2)
+            .all(|pair| pair[0].bytes <= pair[1].bytes));
+
+        // The grid holds a single active voxel, so storing only active values pays off, and
+        // half floats save two bytes per stored value
+        let full = report.get(Codec::None, false, false).unwrap().bytes;
+        let active = report.get(Codec::None, true, false).unwrap().bytes;
+        let half = report.get(Codec::None, false, true).unwrap().bytes;
+        assert_eq!(report.uncompressed_bytes(), full);
+        assert!(active < full);
+        assert!(half < full);
+        assert!(report.ratio(report.smallest().unwrap()) > 1.0);
+    }
+}
@@ -0,0 +1,132 @@
+use crate::data_structure::Grid;
+use crate::morphology::NearestNeighbors;
+
+use glam::IVec3;
+use std::ops::{Add, Mul};
+
+/// Separable convolution over the active voxels of a grid: a 1D kernel applied along the x, y
+/// and z axes in turn, which amounts to convolving with the outer product of the kernel with
+/// itself, at a cost linear in the kernel size.
+///
+/// By default the active region is first dilated by the kernel radius, so values can spread
+/// into the empty space around them, as expected when blurring fog volumes. Inactive voxels
+/// read as their stored value, usually the background.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Convolution {
+    weights: Vec<f32>,
+    dilate: bool,
+}
+
+impl Convolution {
+    /// Convolution with the 1D kernel `weights`, centered on its middle element.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `weights` doesn't have an odd number of elements.
+    pub fn new(weights: Vec<f32>) -> Self {
+        assert!(
+            weights.len() % 2 == 1,
+            "convolution kernels need an odd number of weights"
+        );
+        Self {
+            weights,
+            dilate: true,
+        }
+    }
+
+    /// Normalized Gaussian kernel with a standard deviation of `sigma` voxels, cut off at three
+    /// standard deviations.
+    pub fn gaussian(sigma: f32) -> Self {
+        let sigma = sigma.max(f32::EPSILON);
+        let radius = (3.0 * sigma).ceil() as i32;
+        let weights = (-radius..=radius)
+            .map(|offset| (-(offset * offset) as f32 / (2.0 * sigma * sigma)).exp())
+            .collect::<Vec<_>>();
+        let sum = weights.iter().sum::<f32>();
+        Self::new(weights.into_iter().map(|weight| weight / sum).collect())
+    }
+
+    /// Box kernel averaging the `(2 * width + 1)^3` voxels around every voxel.
+    pub fn mean(width: u32) -> Self {
+        let size = 2 * width as usize + 1;
+        Self::new(vec![1.0 / size as f32; size])
+    }
+
+    /// Whether to dilate the active region by the kernel radius before convolving, on by
+    /// default. Without dilation the topology of the grid is left unchanged.
+    pub fn with_dilation(mut self, dilate: bool) -> Self {
+        self.dilate = dilate;
+        self
+    }
+
+    /// Number of voxels the kernel reaches on either side of its center.
+    pub fn radius(&self) -> u32 {
+        (self.weights.len() / 2) as u32
+    }
+
+    /// Convolves the active voxels of `grid`, returning the result with the same transform and
+    /// descriptor.
+    pub fn apply<ValueTy, const L5: u32, const L4: u32, const L3: u32>(
+        &self,
+        grid: &Grid<ValueTy, L5, L4, L3>,
+    ) -> Grid<ValueTy, L5, L4, L3>
+    where
+        ValueTy: Copy + Add<Output = ValueTy> + Mul<f32, Output = ValueTy>,
+    {
+        let radius = self.radius() as i32;
+        let mut result = grid.clone();
+        if self.dilate {
+            result
+                .tree
+                .dilate_active_values(self.radius(), NearestNeighbors::Vertex);
+        }
+        for axis in [IVec3::X, IVec3::Y, IVec3::Z] {
+            let source = result.tree.clone();
+            let mut accessor = source.accessor();
+            source.for_each_active_voxel(|coord| {
+                let value = (-radius..=radius)
+                    .zip(&self.weights)
+                    .map(|(offset, &weight)| accessor.get_value(coord + axis * offset) * weight)
+                    .reduce(|sum, value| sum + value)
+                    .unwrap();
+                result.tree.set_value_on(coord, value);
+            });
+        }
+        result
+    }
+}
+
+/// Blurs `grid` with a Gaussian of `sigma` voxels, see [`Convolution::gaussian`].
+pub fn gaussian_blur<ValueTy, const L5: u32, const L4: u32, const L3: u32>(
+    grid: &Grid<ValueTy, L5, L4, L3>,
+    sigma: f32,
+) -> Grid<ValueTy, L5, L4, L3>
+where
+    ValueTy: Copy + Add<Output = ValueTy> + Mul<f32, Output = ValueTy>,
+{
+    Convolution::gaussian(sigma).apply(grid)
+}
+
+/// Sharpens `grid` with an unsharp mask: the difference between every active value and its
+/// Gaussian blur over `sigma` voxels is scaled by `amount` and added back. The topology is
+/// left unchanged.
+pub fn sharpen<ValueTy, const L5: u32, const L4: u32, const L3: u32>(
+    grid: &Grid<ValueTy, L5, L4, L3>,
+    sigma: f32,
+    amount: f32,
+) -> Grid<ValueTy, L5, L4, L3>
+where
+    ValueTy: Copy + Add<Output = ValueTy> + Mul<f32, Output = ValueTy>,
+{
+    let blurred = Convolution::gaussian(sigma)
+        .with_dilation(false)
+        .apply(grid);
+    let mut blurred = blurred.tree.accessor();
+    let mut result = grid.clone();
+    grid.tree.for_each_active_voxel(|coord| {
+        let value = grid.tree.get_value(coord);
+        let detail = value * (1.0 + amount) + blurred.get_value(coord) * -amount;
+        result.tree.set_value_on(coord, detail);
+    });
+    result
+}
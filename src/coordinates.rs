@@ -1,3 +1,187 @@
+use glam::{IVec3, UVec3};
+
 pub struct GlobalCoord(pub glam::IVec3);
 pub struct LocalCoord(pub glam::UVec3);
 pub struct Index(pub u32);
+
+/// Axis-aligned bounding box in index space, both `min` and `max` are inclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "bevy", derive(bevy::reflect::Reflect))]
+pub struct CoordBBox {
+    pub min: IVec3,
+    pub max: IVec3,
+}
+
+impl Default for CoordBBox {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl CoordBBox {
+    pub fn new(min: IVec3, max: IVec3) -> Self {
+        Self { min, max }
+    }
+
+    /// A bounding box that contains nothing, expanding it by any coordinate results in a box
+    /// containing just that coordinate.
+    pub fn empty() -> Self {
+        Self {
+            min: IVec3::splat(i32::MAX),
+            max: IVec3::splat(i32::MIN),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.cmpgt(self.max).any()
+    }
+
+    /// Grows the box to include `coord`.
+    pub fn expand_coord(&mut self, coord: IVec3) {
+        self.min = self.min.min(coord);
+        self.max = self.max.max(coord);
+    }
+
+    /// Grows the box to include all of `other`.
+    pub fn expand_bbox(&mut self, other: &CoordBBox) {
+        if !other.is_empty() {
+            self.min = self.min.min(other.min);
+            self.max = self.max.max(other.max);
+        }
+    }
+
+    /// Number of voxels along each axis, zero for an empty box.
+    pub fn dim(&self) -> UVec3 {
+        if self.is_empty() {
+            UVec3::ZERO
+        } else {
+            (self.max - self.min + IVec3::ONE).as_uvec3()
+        }
+    }
+
+    /// Number of voxels inside the box.
+    pub fn volume(&self) -> u64 {
+        let dim = self.dim();
+        dim.x as u64 * dim.y as u64 * dim.z as u64
+    }
+
+    pub fn is_inside(&self, coord: IVec3) -> bool {
+        coord.cmpge(self.min).all() && coord.cmple(self.max).all()
+    }
+
+    /// Whether `other` lies completely inside this box, an empty box is inside every box.
+    pub fn is_inside_bbox(&self, other: &CoordBBox) -> bool {
+        other.is_empty() || (other.min.cmpge(self.min).all() && other.max.cmple(self.max).all())
+    }
+
+    pub fn has_overlap(&self, other: &CoordBBox) -> bool {
+        !self.intersection(other).is_empty()
+    }
+
+    /// Box covering the voxels inside both boxes, empty if they don't overlap.
+    pub fn intersection(&self, other: &CoordBBox) -> CoordBBox {
+        CoordBBox::new(self.min.max(other.min), self.max.min(other.max))
+    }
+
+    /// Smallest box enclosing both boxes.
+    pub fn union(&self, other: &CoordBBox) -> CoordBBox {
+        let mut bbox = *self;
+        bbox.expand_bbox(other);
+        bbox
+    }
+
+    /// Grows the box by `padding` voxels on every side, a negative `padding` shrinks it.
+    pub fn expand(&mut self, padding: i32) {
+        if !self.is_empty() {
+            self.min -= IVec3::splat(padding);
+            self.max += IVec3::splat(padding);
+        }
+    }
+
+    /// Iterates over all coordinates in the box in lexicographic order, with `z` varying fastest.
+    pub fn iter(&self) -> CoordBBoxIter {
+        CoordBBoxIter {
+            bbox: *self,
+            next: (!self.is_empty()).then_some(self.min),
+        }
+    }
+
+    /// Iterates over all coordinates in the box in z-order (Morton order), which keeps
+    /// consecutive coordinates spatially close.
+    pub fn iter_z_order(&self) -> CoordBBoxZOrderIter {
+        let mut stack = vec![];
+        if !self.is_empty() {
+            let size = self.dim().max_element().next_power_of_two();
+            stack.push((self.min, size));
+        }
+        CoordBBoxZOrderIter { bbox: *self, stack }
+    }
+}
+
+impl IntoIterator for CoordBBox {
+    type Item = IVec3;
+    type IntoIter = CoordBBoxIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Lexicographic iterator over a [`CoordBBox`], see [`CoordBBox::iter`].
+#[derive(Clone, Debug)]
+pub struct CoordBBoxIter {
+    bbox: CoordBBox,
+    next: Option<IVec3>,
+}
+
+impl Iterator for CoordBBoxIter {
+    type Item = IVec3;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let coord = self.next?;
+        let mut next = coord;
+        next.z += 1;
+        if next.z > self.bbox.max.z {
+            next.z = self.bbox.min.z;
+            next.y += 1;
+            if next.y > self.bbox.max.y {
+                next.y = self.bbox.min.y;
+                next.x += 1;
+            }
+        }
+        self.next = (next.x <= self.bbox.max.x).then_some(next);
+        Some(coord)
+    }
+}
+
+/// Z-order iterator over a [`CoordBBox`], see [`CoordBBox::iter_z_order`].
+#[derive(Clone, Debug)]
+pub struct CoordBBoxZOrderIter {
+    bbox: CoordBBox,
+    /// Cubes that still need to be visited, as their minimum corner and edge length
+    stack: Vec<(IVec3, u32)>,
+}
+
+impl Iterator for CoordBBoxZOrderIter {
+    type Item = IVec3;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((min, size)) = self.stack.pop() {
+            let cube = CoordBBox::new(min, min + IVec3::splat(size as i32 - 1));
+            if !self.bbox.has_overlap(&cube) {
+                continue;
+            }
+            if size == 1 {
+                return Some(min);
+            }
+            // Push in reverse so the octant with the lowest Morton code is visited first
+            let half = (size / 2) as i32;
+            for octant in (0..8).rev() {
+                let offset = IVec3::new(octant >> 2 & 1, octant >> 1 & 1, octant & 1) * half;
+                self.stack.push((min + offset, size / 2));
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,230 @@
+use crate::data_structure::{Grid, Tree};
+
+use bytemuck::Zeroable;
+use std::ops::Neg;
+
+impl<ValueTy, const L5: u32, const L4: u32, const L3: u32> Tree<ValueTy, L5, L4, L3>
+where
+    ValueTy: Copy + PartialOrd + Neg<Output = ValueTy> + Zeroable,
+{
+    /// Replaces this level set with the union of itself and `other`, the minimum of both
+    /// signed distances. Each voxel keeps the active state of the side its value was taken
+    /// from, so the result is a valid narrow band. Both trees are assumed to share the same
+    /// background.
+    pub fn csg_union(&mut self, other: &Self) {
+        self.combine_with(other, |a, b| if b.0 < a.0 { b } else { a });
+        self.prune_level_set();
+    }
+
+    /// Replaces this level set with the intersection of itself and `other`, the maximum of
+    /// both signed distances, see [`Tree::csg_union`].
+    pub fn csg_intersection(&mut self, other: &Self) {
+        self.combine_with(other, |a, b| if b.0 > a.0 { b } else { a });
+        self.prune_level_set();
+    }
+
+    /// Subtracts the level set `other` from this one, the maximum of this signed distance and
+    /// the negated distance of `other`, see [`Tree::csg_union`].
+    pub fn csg_difference(&mut self, other: &Self) {
+        self.combine_with(
+            other,
+            |a, (b, b_active)| {
+                if -b > a.0 {
+                    (-b, b_active)
+                } else {
+                    a
+                }
+            },
+        );
+        self.prune_level_set();
+    }
+
+    /// Level set variant of [`Tree::prune_inactive`]: leaves and internal nodes without active
+    /// values are collapsed into inactive tiles of `background` or `-background`, depending on
+    /// whether they lie outside or inside the surface.
+    pub fn prune_level_set(&mut self) {
+        let zero = ValueTy::zeroed();
+        let background = self.background;
+        let signed_background = |value: ValueTy| {
+            if value < zero {
+                -background
+            } else {
+                background
+            }
+        };
+
+        for node_5 in &mut self.root_nodes {
+            for node_4 in node_5.nodes.values_mut() {
+                node_4.nodes.retain(|&idx, node_3| {
+                    let empty = node_3.value_mask.not_any();
+                    if empty {
+                        node_4.child_mask.set(idx as usize, false);
+                        node_4.value_mask.set(idx as usize, false);
+                        node_4.data[idx as usize] = signed_background(node_3.buffer[0]);
+                    }
+                    !empty
+                });
+            }
+            node_5.nodes.retain(|&idx, node_4| {
+                let value = signed_background(node_4.data[0]);
+                let empty = node_4.nodes.is_empty()
+                    && node_4.value_mask.not_any()
+                    && node_4
+                        .data
+                        .iter()
+                        .all(|&data| signed_background(data) == value);
+                if empty {
+                    node_5.child_mask.set(idx as usize, false);
+                    node_5.value_mask.set(idx as usize, false);
+                    node_5.data[idx as usize] = value;
+                }
+                !empty
+            });
+        }
+        self.prune_inactive();
+    }
+}
+
+/// Polynomial smooth minimum of `a` and `b`, equal to `min(a, b)` where they differ by more than
+/// `radius`.
+fn smooth_min(a: f32, b: f32, radius: f32) -> f32 {
+    let h = (radius - (a - b).abs()).max(0.0) / radius;
+    a.min(b) - h * h * radius * 0.25
+}
+
+impl<const L5: u32, const L4: u32, const L3: u32> Tree<f32, L5, L4, L3> {
+    /// Smooth variant of [`Tree::csg_union`], blending both surfaces where their distances
+    /// differ by less than `radius`.
+    ///
+    /// Blending happens only where at least one of the inputs has active values, so `radius`
+    /// should be small compared to the narrow band width. Blended values that leave the band are
+    /// clamped to the background and deactivated.
+    pub fn csg_smooth_union(&mut self, other: &Self, radius: f32) {
+        let background = self.background;
+        self.combine_with(other, |a, b| {
+            if !a.1 && !b.1 || radius <= 0.0 {
+                return if b.0 < a.0 { b } else { a };
+            }
+            let value = smooth_min(a.0, b.0, radius).clamp(-background, background);
+            (value, value.abs() < background)
+        });
+        self.prune_level_set();
+    }
+
+    /// Smooth variant of [`Tree::csg_intersection`], see [`Tree::csg_smooth_union`].
+    pub fn csg_smooth_intersection(&mut self, other: &Self, radius: f32) {
+        let background = self.background;
+        self.combine_with(other, |a, b| {
+            if !a.1 && !b.1 || radius <= 0.0 {
+                return if b.0 > a.0 { b } else { a };
+            }
+            let value = -smooth_min(-a.0, -b.0, radius).clamp(-background, background);
+            (value, value.abs() < background)
+        });
+        self.prune_level_set();
+    }
+}
+
+/// Replaces the level set `a` with the union of `a` and `b`, see [`Tree::csg_union`].
+pub fn csg_union<ValueTy, const L5: u32, const L4: u32, const L3: u32>(
+    a: &mut Grid<ValueTy, L5, L4, L3>,
+    b: &Grid<ValueTy, L5, L4, L3>,
+) where
+    ValueTy: Copy + PartialOrd + Neg<Output = ValueTy> + Zeroable,
+{
+    a.tree.csg_union(&b.tree);
+}
+
+/// Replaces the level set `a` with the intersection of `a` and `b`, see
+/// [`Tree::csg_intersection`].
+pub fn csg_intersection<ValueTy, const L5: u32, const L4: u32, const L3: u32>(
+    a: &mut Grid<ValueTy, L5, L4, L3>,
+    b: &Grid<ValueTy, L5, L4, L3>,
+) where
+    ValueTy: Copy + PartialOrd + Neg<Output = ValueTy> + Zeroable,
+{
+    a.tree.csg_intersection(&b.tree);
+}
+
+/// Subtracts the level set `b` from `a`, see [`Tree::csg_difference`].
+pub fn csg_difference<ValueTy, const L5: u32, const L4: u32, const L3: u32>(
+    a: &mut Grid<ValueTy, L5, L4, L3>,
+    b: &Grid<ValueTy, L5, L4, L3>,
+) where
+    ValueTy: Copy + PartialOrd + Neg<Output = ValueTy> + Zeroable,
+{
+    a.tree.csg_difference(&b.tree);
+}
+
+/// Replaces the level set `a` with the smooth union of `a` and `b`, see
+/// [`Tree::csg_smooth_union`].
+pub fn csg_smooth_union<const L5: u32, const L4: u32, const L3: u32>(
+    a: &mut Grid<f32, L5, L4, L3>,
+    b: &Grid<f32, L5, L4, L3>,
+    radius: f32,
+) {
+    a.tree.csg_smooth_union(&b.tree, radius);
+}
+
+/// Replaces the level set `a` with the smooth intersection of `a` and `b`, see
+/// [`Tree::csg_smooth_intersection`].
+pub fn csg_smooth_intersection<const L5: u32, const L4: u32, const L3: u32>(
+    a: &mut Grid<f32, L5, L4, L3>,
+    b: &Grid<f32, L5, L4, L3>,
+    radius: f32,
+) {
+    a.tree.csg_smooth_intersection(&b.tree, radius);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::tests::sphere;
+
+    use glam::{IVec3, Vec3};
+
+    /// Spheres of radius one around `x = ∓0.5` with voxels of 0.1 and a background of 0.3, and
+    /// their exact distances at index space `x` along the x axis.
+    fn spheres() -> (Grid<f32>, Grid<f32>, impl Fn(i32) -> (f32, f32)) {
+        let a = sphere(Vec3::new(-0.5, 0.0, 0.0));
+        let b = sphere(Vec3::new(0.5, 0.0, 0.0));
+        let distances = |x: i32| {
+            let x = x as f32 * 0.1;
+            ((x + 0.5).abs() - 1.0, (x - 0.5).abs() - 1.0)
+        };
+        (a, b, distances)
+    }
+
+    fn assert_along_x(grid: &Grid<f32>, expected: impl Fn(i32) -> f32) {
+        for x in -25..=25 {
+            let value = grid.tree.get_value(IVec3::new(x, 0, 0));
+            let expected = expected(x).clamp(-0.3, 0.3);
+            assert!(
+                (value - expected).abs() < 1e-4,
+                "{x}: {value} != {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn union_takes_minimum() {
+        let (mut a, b, distances) = spheres();
+        csg_union(&mut a, &b);
+        assert_along_x(&a, |x| distances(x).0.min(distances(x).1));
+    }
+
+    #[test]
+    fn intersection_takes_maximum() {
+        let (mut a, b, distances) = spheres();
+        csg_intersection(&mut a, &b);
+        assert_along_x(&a, |x| distances(x).0.max(distances(x).1));
+    }
+
+    #[test]
+    fn difference_removes_second_shape() {
+        let (mut a, b, distances) = spheres();
+        csg_difference(&mut a, &b);
+        assert_along_x(&a, |x| distances(x).0.max(-distances(x).1));
+        assert!(a.tree.get_value(IVec3::new(5, 0, 0)) > 0.0);
+    }
+}
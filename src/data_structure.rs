@@ -1,12 +1,39 @@
-use crate::coordinates::{GlobalCoord, Index, LocalCoord};
-use crate::transform::Map;
-use crate::OPENVDB_FILE_VERSION_NODE_MASK_COMPRESSION;
+use crate::coordinates::{CoordBBox, GlobalCoord, Index, LocalCoord};
+use crate::interning::InternedStr;
+use crate::reader::OPENVDB_FILE_VERSION_MULTIPASS_IO;
+use crate::simd::count_ones;
+use crate::transform::Transform;
 use bitflags::bitflags;
 use bitvec::prelude::*;
 use bitvec::slice::IterOnes;
+use bytemuck::Zeroable;
 use glam::{IVec3, Vec3};
 use std::collections::HashMap;
 use std::io::{Read, Seek, SeekFrom};
+use std::ops::Neg;
+use std::sync::Arc;
+
+/// Storage word of node masks. bitvec only stores bits in `u64` words on 64-bit targets, so
+/// 32-bit targets such as WebAssembly use `u32` words, holding the bits in the same order.
+#[cfg(target_pointer_width = "64")]
+pub type MaskWord = u64;
+#[cfg(not(target_pointer_width = "64"))]
+pub type MaskWord = u32;
+
+/// Fills `mask` from the little endian 64-bit words that masks are stored as in files, where bit
+/// `i` of word `j` is bit `64 * j + i` of the mask.
+pub(crate) fn load_mask_words(mask: &mut BitSlice<MaskWord, Lsb0>, words: &[u64]) {
+    for (chunk, &word) in mask.chunks_mut(64).zip(words) {
+        chunk.store_le(word);
+    }
+}
+
+/// The bits of `mask` as 64-bit words, see [`load_mask_words`].
+pub(crate) fn mask_words(mask: &BitSlice<MaskWord, Lsb0>) -> Vec<u64> {
+    mask.chunks(64)
+        .map(|chunk| chunk.load_le::<u64>())
+        .collect()
+}
 
 #[derive(thiserror::Error, Debug)]
 pub enum GridMetadataError {
@@ -14,15 +41,182 @@ pub enum GridMetadataError {
     FieldNotPresent(String),
 }
 
-#[derive(Debug)]
-pub struct Grid<ValueTy> {
-    pub tree: Tree<ValueTy>,
-    pub transform: Map,
+/// Lightweight summary of the properties of a grid, without its tree, for displaying them
+/// such as in an inspector. See [`Grid::info`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "bevy", derive(bevy::reflect::Reflect))]
+pub struct GridInfo {
+    pub name: String,
+    pub grid_type: String,
+    /// Name of the [`GridClass`]
+    pub class: String,
+    pub voxel_size: Vec3,
+    pub active_voxel_count: u64,
+    pub active_bbox: CoordBBox,
+    pub leaf_count: usize,
+    /// Approximate in-memory size in bytes
+    pub memory_usage: usize,
+    pub meta_data: Metadata,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Grid<ValueTy, const L5: u32 = 5, const L4: u32 = 4, const L3: u32 = 3> {
+    pub tree: Tree<ValueTy, L5, L4, L3>,
+    pub transform: Transform,
     pub descriptor: GridDescriptor,
 }
 
-impl<ValueTy> Grid<ValueTy> {
-    pub fn iter(&self) -> GridIter<'_, ValueTy> {
+impl<ValueTy: Copy, const L5: u32, const L4: u32, const L3: u32> Grid<ValueTy, L5, L4, L3> {
+    /// Copy of this grid that shares no leaf nodes with it, see [`Tree::deep_copy`].
+    ///
+    /// Cloning a grid is cheap as the clone shares its leaf nodes with the original, they are
+    /// only copied once either grid modifies them.
+    pub fn deep_copy(&self) -> Self {
+        Self {
+            tree: self.tree.deep_copy(),
+            transform: self.transform.clone(),
+            descriptor: self.descriptor.clone(),
+        }
+    }
+
+    /// Mask grid mirroring the active topology of this grid, see [`Tree::topology_mask`].
+    pub fn topology_mask(&self) -> Grid<bool, L5, L4, L3> {
+        let mut descriptor = self.descriptor.clone();
+        descriptor.grid_type = Tree::<bool, L5, L4, L3>::type_name("mask").into();
+        Grid {
+            tree: self.tree.topology_mask(),
+            transform: self.transform.clone(),
+            descriptor,
+        }
+    }
+
+    /// Densifies active tiles into leaf nodes, see [`Tree::voxelize_active_tiles`].
+    pub fn voxelize_active_tiles(&mut self) {
+        self.tree.voxelize_active_tiles();
+    }
+
+    /// Grid with the same topology and transform where every value is replaced by `f(value)`,
+    /// see [`Tree::map_values`].
+    ///
+    /// The descriptor is copied as-is, so its `grid_type` still names the original value type.
+    /// Use [`Grid::convert_values`] to convert to another value type OpenVDB can store.
+    pub fn map_values<U: Copy>(&self, f: impl FnMut(ValueTy) -> U) -> Grid<U, L5, L4, L3> {
+        Grid {
+            tree: self.tree.map_values(f),
+            transform: self.transform.clone(),
+            descriptor: self.descriptor.clone(),
+        }
+    }
+
+    /// [`Grid::map_values`] to a value type OpenVDB can store, with the `grid_type` of the
+    /// descriptor naming the new value type.
+    pub fn convert_values<U: GridValueType>(
+        &self,
+        f: impl FnMut(ValueTy) -> U,
+    ) -> Grid<U, L5, L4, L3> {
+        let mut grid = self.map_values(f);
+        grid.descriptor.grid_type = Tree::<U, L5, L4, L3>::type_name(U::TYPE_NAME).into();
+        grid
+    }
+}
+
+impl<ValueTy, const L5: u32, const L4: u32, const L3: u32> Grid<ValueTy, L5, L4, L3>
+where
+    ValueTy: Copy + PartialEq,
+{
+    /// Changes the background value of this grid, see [`Tree::set_background`].
+    pub fn change_background(&mut self, new_background: ValueTy) {
+        self.tree.set_background(new_background);
+    }
+}
+
+impl<ValueTy, const L5: u32, const L4: u32, const L3: u32> Grid<ValueTy, L5, L4, L3>
+where
+    ValueTy: Copy + PartialOrd + Neg<Output = ValueTy> + Zeroable,
+{
+    /// Changes the background value of this level set grid, see [`Tree::set_level_set_background`].
+    pub fn change_level_set_background(&mut self, new_background: ValueTy) {
+        self.tree.set_level_set_background(new_background);
+    }
+}
+
+impl<ValueTy, const L5: u32, const L4: u32, const L3: u32> Grid<ValueTy, L5, L4, L3> {
+    /// Bounding box of all active voxels and tiles, see [`Tree::eval_active_voxel_bounding_box`].
+    pub fn eval_active_voxel_bounding_box(&self) -> CoordBBox {
+        self.tree.eval_active_voxel_bounding_box()
+    }
+
+    /// Number of active voxels, see [`Tree::active_voxel_count`].
+    pub fn active_voxel_count(&self) -> u64 {
+        self.tree.active_voxel_count()
+    }
+
+    /// World space position of an index space coordinate, see [`Transform::index_to_world`].
+    pub fn index_to_world(&self, index: Vec3) -> Vec3 {
+        self.transform.index_to_world(index)
+    }
+
+    /// Index space position of a world space coordinate, see [`Transform::world_to_index`].
+    pub fn world_to_index(&self, world: Vec3) -> Vec3 {
+        self.transform.world_to_index(world)
+    }
+
+    /// Size of a voxel along each world space axis.
+    pub fn voxel_size(&self) -> Vec3 {
+        self.transform.voxel_size().as_vec3()
+    }
+
+    /// Class of this grid, stored in the `class` metadata of its descriptor.
+    pub fn grid_class(&self) -> GridClass {
+        self.descriptor.grid_class()
+    }
+
+    pub fn set_grid_class(&mut self, class: GridClass) {
+        self.descriptor.set_grid_class(class);
+    }
+
+    pub fn metadata(&self) -> &Metadata {
+        &self.descriptor.meta_data
+    }
+
+    pub fn metadata_mut(&mut self) -> &mut Metadata {
+        &mut self.descriptor.meta_data
+    }
+
+    /// Number of leaf nodes, see [`Tree::leaf_count`].
+    pub fn leaf_count(&self) -> usize {
+        self.tree.leaf_count()
+    }
+
+    /// Approximate in-memory size of this grid in bytes, see [`Tree::memory_usage`].
+    pub fn memory_usage(&self) -> usize {
+        std::mem::size_of::<Self>() + self.tree.memory_usage()
+    }
+
+    /// Summary of the properties of this grid, see [`GridInfo`].
+    pub fn info(&self) -> GridInfo {
+        GridInfo {
+            name: self.descriptor.name.to_string(),
+            grid_type: self.descriptor.grid_type.to_string(),
+            class: self.grid_class().name().to_string(),
+            voxel_size: self.voxel_size(),
+            active_voxel_count: self.active_voxel_count(),
+            active_bbox: self.eval_active_voxel_bounding_box(),
+            leaf_count: self.leaf_count(),
+            memory_usage: self.memory_usage(),
+            meta_data: self.descriptor.meta_data.clone(),
+        }
+    }
+
+    /// Edge length in voxels of a value at `level` in this grid, such as the tiles returned by
+    /// [`Grid::iter`].
+    pub fn level_scale(&self, level: VdbLevel) -> f32 {
+        level.scale_with_config::<L4, L3>()
+    }
+
+    pub fn iter(&self) -> GridIter<'_, ValueTy, L5, L4, L3> {
         GridIter {
             grid: self,
             root_idx: 0,
@@ -39,37 +233,46 @@ impl<ValueTy> Grid<ValueTy> {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum VdbLevel {
     Node4,
     Node3,
     Voxel,
 }
 impl VdbLevel {
+    /// Edge length in voxels of a value at this level, for the standard 5-4-3 configuration.
+    /// Use [`VdbLevel::scale_with_config`] or [`Grid::level_scale`] for other trees.
     pub fn scale(self) -> f32 {
+        self.scale_with_config::<4, 3>()
+    }
+
+    /// Edge length in voxels of a value at this level, in a tree whose internal nodes below the
+    /// root and leaf nodes are `1 << L4` and `1 << L3` children wide.
+    pub fn scale_with_config<const L4: u32, const L3: u32>(self) -> f32 {
         match self {
-            VdbLevel::Node4 => (1 << (4 + 3)) as f32,
-            VdbLevel::Node3 => (1 << 3) as f32,
+            VdbLevel::Node4 => (1u64 << (L4 + L3)) as f32,
+            VdbLevel::Node3 => (1u64 << L3) as f32,
             VdbLevel::Voxel => 1.0,
         }
     }
 }
 
-pub struct GridIter<'a, ValueTy> {
-    grid: &'a Grid<ValueTy>,
+pub struct GridIter<'a, ValueTy, const L5: u32, const L4: u32, const L3: u32> {
+    grid: &'a Grid<ValueTy, L5, L4, L3>,
     root_idx: usize,
-    node_5_iter_active: IterOnes<'a, u64, Lsb0>,
-    node_5_iter_child: IterOnes<'a, u64, Lsb0>,
-    node_4_iter_active: IterOnes<'a, u64, Lsb0>,
-    node_4_iter_child: IterOnes<'a, u64, Lsb0>,
-    node_3_iter_child: IterOnes<'a, u64, Lsb0>,
+    node_5_iter_active: IterOnes<'a, MaskWord, Lsb0>,
+    node_5_iter_child: IterOnes<'a, MaskWord, Lsb0>,
+    node_4_iter_active: IterOnes<'a, MaskWord, Lsb0>,
+    node_4_iter_child: IterOnes<'a, MaskWord, Lsb0>,
+    node_3_iter_child: IterOnes<'a, MaskWord, Lsb0>,
 
-    node_5: Option<&'a Node5<ValueTy>>,
-    node_4: Option<&'a Node4<ValueTy>>,
-    node_3: Option<&'a Node3<ValueTy>>,
+    node_5: Option<&'a Node5<ValueTy, L5, L4, L3>>,
+    node_4: Option<&'a Node4<ValueTy, L4, L3>>,
+    node_3: Option<&'a Node3<ValueTy, L3>>,
 }
 
-impl<'a, ValueTy> Iterator for GridIter<'a, ValueTy>
+impl<'a, ValueTy, const L5: u32, const L4: u32, const L3: u32> Iterator
+    for GridIter<'a, ValueTy, L5, L4, L3>
 where
     ValueTy: Copy,
 {
@@ -94,19 +297,7 @@ where
             {
                 return Some((
                     node_4.offset_to_global_coord(Index(idx as u32)).0.as_vec3(),
-                    if self.grid.descriptor.file_version
-                        < OPENVDB_FILE_VERSION_NODE_MASK_COMPRESSION
-                    {
-                        let node_mask_compression_idx = node_4
-                            .child_mask
-                            .iter()
-                            .by_vals()
-                            .take(idx)
-                            .fold(0, |old, val| old + (!val as usize)); // count 0's before idx
-                        node_4.data[node_mask_compression_idx]
-                    } else {
-                        node_4.data[idx]
-                    },
+                    node_4.data[idx],
                     VdbLevel::Node3,
                 ));
             }
@@ -141,12 +332,14 @@ where
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "bevy", derive(bevy::reflect::Reflect))]
 pub struct GridDescriptor {
-    pub name: String,
+    pub name: InternedStr,
     pub file_version: u32,
     /// If not empty, the name of another grid that shares this grid's tree
-    pub instance_parent: String,
-    pub grid_type: String,
+    pub instance_parent: InternedStr,
+    pub grid_type: InternedStr,
     /// Location in the stream where the grid data is stored
     pub grid_pos: u64,
     /// Location in the stream where the grid blocks are stored
@@ -158,6 +351,22 @@ pub struct GridDescriptor {
 }
 
 impl GridDescriptor {
+    /// Descriptor for a grid created in memory rather than read from a file, `grid_type` is the
+    /// OpenVDB type name of its tree, see [`Tree::type_name`].
+    pub fn new(name: impl Into<InternedStr>, grid_type: impl Into<InternedStr>) -> Self {
+        Self {
+            name: name.into(),
+            file_version: OPENVDB_FILE_VERSION_MULTIPASS_IO,
+            instance_parent: InternedStr::default(),
+            grid_type: grid_type.into(),
+            grid_pos: 0,
+            block_pos: 0,
+            end_pos: 0,
+            compression: Compression::DEFAULT_COMPRESSION,
+            meta_data: Metadata::default(),
+        }
+    }
+
     pub(crate) fn seek_to_grid<R: Read + Seek>(
         &self,
         reader: &mut R,
@@ -174,49 +383,166 @@ impl GridDescriptor {
 
     // below values should always be present, see https://github.com/AcademySoftwareFoundation/openvdb/blob/master/openvdb/openvdb/Grid.cc#L387
     pub fn aabb_min(&self) -> Result<IVec3, GridMetadataError> {
-        match self.meta_data.0["file_bbox_min"] {
-            MetadataValue::Vec3i(v) => Ok(v),
-            _ => Err(GridMetadataError::FieldNotPresent(
-                "file_bbox_min".to_string(),
-            )),
-        }
+        self.meta_data.require("file_bbox_min")
     }
     pub fn aabb_max(&self) -> Result<IVec3, GridMetadataError> {
-        match self.meta_data.0["file_bbox_max"] {
-            MetadataValue::Vec3i(v) => Ok(v),
-            _ => Err(GridMetadataError::FieldNotPresent(
-                "file_bbox_max".to_string(),
-            )),
-        }
+        self.meta_data.require("file_bbox_max")
+    }
+    pub fn grid_class(&self) -> GridClass {
+        self.meta_data.grid_class()
+    }
+    pub fn set_grid_class(&mut self, class: GridClass) {
+        self.meta_data.set_grid_class(class);
     }
     pub fn mem_bytes(&self) -> Result<i64, GridMetadataError> {
-        match self.meta_data.0["file_mem_bytes"] {
-            MetadataValue::I64(v) => Ok(v),
-            _ => Err(GridMetadataError::FieldNotPresent(
-                "file_mem_bytes".to_string(),
-            )),
-        }
+        self.meta_data.require("file_mem_bytes")
     }
     pub fn voxel_count(&self) -> Result<i64, GridMetadataError> {
-        match self.meta_data.0["file_voxel_count"] {
-            MetadataValue::I64(v) => Ok(v),
-            _ => Err(GridMetadataError::FieldNotPresent(
-                "file_voxel_count".to_string(),
-            )),
-        }
+        self.meta_data.require("file_voxel_count")
     }
 }
 
 #[derive(Debug, Default, Clone)]
-pub struct Metadata(pub HashMap<String, MetadataValue>);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "bevy", derive(bevy::reflect::Reflect))]
+pub struct Metadata(pub HashMap<InternedStr, MetadataValue>);
 
 impl Metadata {
+    /// Value of the field `name`, `None` if it is missing or holds a different type.
+    pub fn get<T: MetadataType>(&self, name: &str) -> Option<T> {
+        self.0.get(name).and_then(T::from_metadata)
+    }
+
+    /// Like [`Metadata::get`], but reports a missing field as an error.
+    pub fn require<T: MetadataType>(&self, name: &str) -> Result<T, GridMetadataError> {
+        self.get(name)
+            .ok_or_else(|| GridMetadataError::FieldNotPresent(name.to_string()))
+    }
+
+    /// Borrowing variant of `get::<String>`.
+    pub fn get_string(&self, name: &str) -> Option<&str> {
+        match self.0.get(name) {
+            Some(MetadataValue::String(v)) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn get_vec3i(&self, name: &str) -> Option<IVec3> {
+        self.get(name)
+    }
+
+    /// Stores `value` under `name`, replacing any existing value.
+    pub fn insert_typed<T: MetadataType>(&mut self, name: impl Into<InternedStr>, value: T) {
+        self.0.insert(name.into(), value.into_metadata());
+    }
+
+    /// Name of the grid, stored under the `name` key.
+    pub fn name(&self) -> Option<&str> {
+        self.get_string("name")
+    }
+
+    /// Class of the grid, stored under the `class` key. Missing or unrecognized classes are
+    /// reported as [`GridClass::Unknown`].
+    pub fn grid_class(&self) -> GridClass {
+        self.get_string("class")
+            .map_or(GridClass::Unknown, GridClass::from_name)
+    }
+
+    pub fn set_grid_class(&mut self, class: GridClass) {
+        self.insert_typed("class", class.name().to_owned());
+    }
+
+    /// Application that created the grid, stored under the `creator` key.
+    pub fn creator(&self) -> Option<&str> {
+        self.get_string("creator")
+    }
+
     pub fn is_half_float(&self) -> bool {
-        self.0.get("is_saved_as_half_float") == Some(&MetadataValue::Bool(true))
+        self.get("is_saved_as_half_float") == Some(true)
     }
 }
 
+/// Number of voxels on either side of the surface that a level set stores by default.
+pub const LEVEL_SET_HALF_WIDTH: f64 = 3.0;
+
+/// Semantic class of a grid, which tools use to pick sensible defaults.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GridClass {
+    /// Narrow band signed distance field
+    LevelSet,
+    /// Density values in `[0, 1]` with a zero background
+    FogVolume,
+    /// Vector grid whose components are stored on the faces of the voxels (MAC grid)
+    Staggered,
+    #[default]
+    Unknown,
+}
+
+impl GridClass {
+    /// Parses the name stored in the `class` metadata.
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "level set" => GridClass::LevelSet,
+            "fog volume" => GridClass::FogVolume,
+            "staggered" => GridClass::Staggered,
+            _ => GridClass::Unknown,
+        }
+    }
+
+    /// Name of the class as stored in the `class` metadata.
+    pub fn name(self) -> &'static str {
+        match self {
+            GridClass::LevelSet => "level set",
+            GridClass::FogVolume => "fog volume",
+            GridClass::Staggered => "staggered",
+            GridClass::Unknown => "unknown",
+        }
+    }
+
+    /// Background value a grid of this class should have, a level set uses its narrow band
+    /// half-width of [`LEVEL_SET_HALF_WIDTH`] voxels of `voxel_size`, other classes use zero.
+    pub fn default_background(self, voxel_size: f64) -> f64 {
+        match self {
+            GridClass::LevelSet => LEVEL_SET_HALF_WIDTH * voxel_size,
+            _ => 0.0,
+        }
+    }
+}
+
+/// Rust types that can be stored in [`Metadata`].
+pub trait MetadataType: Sized {
+    fn from_metadata(value: &MetadataValue) -> Option<Self>;
+    fn into_metadata(self) -> MetadataValue;
+}
+
+macro_rules! impl_metadata_type {
+    ($ty:ty, $variant:ident) => {
+        impl MetadataType for $ty {
+            fn from_metadata(value: &MetadataValue) -> Option<Self> {
+                match value {
+                    MetadataValue::$variant(v) => Some(v.clone()),
+                    _ => None,
+                }
+            }
+
+            fn into_metadata(self) -> MetadataValue {
+                MetadataValue::$variant(self)
+            }
+        }
+    };
+}
+
+impl_metadata_type!(String, String);
+impl_metadata_type!(IVec3, Vec3i);
+impl_metadata_type!(i32, I32);
+impl_metadata_type!(i64, I64);
+impl_metadata_type!(f32, Float);
+impl_metadata_type!(bool, Bool);
+
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "bevy", derive(bevy::reflect::Reflect))]
 pub enum MetadataValue {
     String(String),
     Vec3i(glam::IVec3),
@@ -231,15 +557,25 @@ pub trait Node {
     const DIM: u32 = 1 << Self::LOG_2_DIM;
     const LOG_2_DIM: u32;
     const TOTAL: u32;
+    /// Number of voxels covered by a single tile (or child node) of this node.
+    const TILE_VOXEL_COUNT: u64 = 1 << (3 * Self::TOTAL);
+
+    /// Number of voxels along each axis covered by this node.
+    const VOXEL_DIM: u32 = 1 << (Self::TOTAL + Self::LOG_2_DIM);
 
     fn local_coord_to_offset(&self, xyz: LocalCoord) -> Index {
         Index(
-            (((xyz.0[0] & (Self::DIM - 1)) >> Self::TOTAL) << (2 * Self::LOG_2_DIM))
-                + (((xyz.0[1] & (Self::DIM - 1)) >> Self::TOTAL) << Self::LOG_2_DIM)
-                + ((xyz.0[2] & (Self::DIM - 1)) >> Self::TOTAL),
+            (((xyz.0[0] & (Self::VOXEL_DIM - 1)) >> Self::TOTAL) << (2 * Self::LOG_2_DIM))
+                + (((xyz.0[1] & (Self::VOXEL_DIM - 1)) >> Self::TOTAL) << Self::LOG_2_DIM)
+                + ((xyz.0[2] & (Self::VOXEL_DIM - 1)) >> Self::TOTAL),
         )
     }
 
+    /// Offset of the tile (or child node) in this node that contains the global coordinate.
+    fn global_coord_to_offset(&self, xyz: GlobalCoord) -> Index {
+        self.local_coord_to_offset(LocalCoord(xyz.0.as_uvec3()))
+    }
+
     fn offset_to_local_coord(&self, offset: Index) -> LocalCoord {
         assert!(
             offset.0 < (1 << (3 * Self::LOG_2_DIM)),
@@ -265,25 +601,44 @@ pub trait Node {
     }
 
     fn offset(&self) -> glam::IVec3;
+
+    /// Index-space bounding box covered by the tile (or child node) at `offset`.
+    fn tile_bbox(&self, offset: Index) -> CoordBBox {
+        let min = self.offset_to_global_coord(offset).0;
+        CoordBBox::new(min, min + IVec3::splat((1 << Self::TOTAL) - 1))
+    }
 }
 
 #[derive(Debug)]
 pub struct NodeHeader<ValueTy> {
-    pub child_mask: BitVec<u64, Lsb0>,
-    pub value_mask: BitVec<u64, Lsb0>,
+    pub child_mask: BitVec<MaskWord, Lsb0>,
+    pub value_mask: BitVec<MaskWord, Lsb0>,
     pub data: Vec<ValueTy>,
     pub log_2_dim: u32,
 }
 
-#[derive(Debug)]
-pub struct Node3<ValueTy> {
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Node3<ValueTy, const L3: u32 = 3> {
     pub buffer: Vec<ValueTy>,
-    pub value_mask: BitVec<u64, Lsb0>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_mask"))]
+    pub value_mask: BitVec<MaskWord, Lsb0>,
     pub origin: glam::IVec3,
 }
 
-impl<ValueTy> Node for Node3<ValueTy> {
-    const LOG_2_DIM: u32 = 3;
+impl<ValueTy: Copy, const L3: u32> Node3<ValueTy, L3> {
+    /// Creates a leaf node where all voxels have the same value and active state.
+    pub fn new(origin: glam::IVec3, value: ValueTy, active: bool) -> Self {
+        Self {
+            buffer: vec![value; 1 << (3 * Self::LOG_2_DIM)],
+            value_mask: bitvec![MaskWord, Lsb0; active as u64; 1 << (3 * Self::LOG_2_DIM)],
+            origin,
+        }
+    }
+}
+
+impl<ValueTy, const L3: u32> Node for Node3<ValueTy, L3> {
+    const LOG_2_DIM: u32 = L3;
     const TOTAL: u32 = 0;
 
     fn offset(&self) -> glam::IVec3 {
@@ -291,45 +646,624 @@ impl<ValueTy> Node for Node3<ValueTy> {
     }
 }
 
-#[derive(Debug)]
-pub struct Node4<ValueTy> {
-    pub child_mask: BitVec<u64, Lsb0>,
-    pub value_mask: BitVec<u64, Lsb0>,
-    pub nodes: HashMap<u32, Node3<ValueTy>>,
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Node4<ValueTy, const L4: u32 = 4, const L3: u32 = 3> {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_mask"))]
+    pub child_mask: BitVec<MaskWord, Lsb0>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_mask"))]
+    pub value_mask: BitVec<MaskWord, Lsb0>,
+    /// Leaf nodes are reference counted so cloned trees share them until one of the clones
+    /// modifies a leaf, see [`Tree::deep_copy`].
+    pub nodes: HashMap<u32, Arc<Node3<ValueTy, L3>>>,
     pub data: Vec<ValueTy>,
     pub origin: glam::IVec3,
 }
 
-impl<ValueTy> Node for Node4<ValueTy> {
-    const LOG_2_DIM: u32 = 4;
-    const TOTAL: u32 = 3;
+impl<ValueTy: Copy, const L4: u32, const L3: u32> Node4<ValueTy, L4, L3> {
+    /// Creates a node without children where all tiles have the same value and active state.
+    pub fn new(origin: glam::IVec3, value: ValueTy, active: bool) -> Self {
+        Self {
+            child_mask: bitvec![MaskWord, Lsb0; 0; 1 << (3 * Self::LOG_2_DIM)],
+            value_mask: bitvec![MaskWord, Lsb0; active as u64; 1 << (3 * Self::LOG_2_DIM)],
+            nodes: Default::default(),
+            data: vec![value; 1 << (3 * Self::LOG_2_DIM)],
+            origin,
+        }
+    }
+}
+
+impl<ValueTy, const L4: u32, const L3: u32> Node for Node4<ValueTy, L4, L3> {
+    const LOG_2_DIM: u32 = L4;
+    const TOTAL: u32 = L3;
 
     fn offset(&self) -> glam::IVec3 {
         self.origin
     }
 }
 
-#[derive(Debug)]
-pub struct Node5<ValueTy> {
-    pub child_mask: BitVec<u64, Lsb0>,
-    pub value_mask: BitVec<u64, Lsb0>,
-    pub nodes: HashMap<u32, Node4<ValueTy>>,
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Node5<ValueTy, const L5: u32 = 5, const L4: u32 = 4, const L3: u32 = 3> {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_mask"))]
+    pub child_mask: BitVec<MaskWord, Lsb0>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_mask"))]
+    pub value_mask: BitVec<MaskWord, Lsb0>,
+    pub nodes: HashMap<u32, Node4<ValueTy, L4, L3>>,
     pub data: Vec<ValueTy>,
     pub origin: glam::IVec3,
 }
 
-impl<ValueTy> Node for Node5<ValueTy> {
-    const LOG_2_DIM: u32 = 5;
-    const TOTAL: u32 = 7;
+impl<ValueTy: Copy, const L5: u32, const L4: u32, const L3: u32> Node5<ValueTy, L5, L4, L3> {
+    /// Creates a node without children where all tiles have the same value and active state.
+    pub fn new(origin: glam::IVec3, value: ValueTy, active: bool) -> Self {
+        Self {
+            child_mask: bitvec![MaskWord, Lsb0; 0; 1 << (3 * Self::LOG_2_DIM)],
+            value_mask: bitvec![MaskWord, Lsb0; active as u64; 1 << (3 * Self::LOG_2_DIM)],
+            nodes: Default::default(),
+            data: vec![value; 1 << (3 * Self::LOG_2_DIM)],
+            origin,
+        }
+    }
+}
+
+impl<ValueTy, const L5: u32, const L4: u32, const L3: u32> Node for Node5<ValueTy, L5, L4, L3> {
+    const LOG_2_DIM: u32 = L5;
+    const TOTAL: u32 = L4 + L3;
 
     fn offset(&self) -> glam::IVec3 {
         self.origin
     }
 }
 
-#[derive(Debug)]
-pub struct Tree<ValueTy> {
-    pub root_nodes: Vec<Node5<ValueTy>>,
+/// Indices of the slots in an internal node that hold an active tile rather than a child node.
+pub(crate) fn active_tiles<'a>(
+    child_mask: &'a BitVec<MaskWord, Lsb0>,
+    value_mask: &'a BitVec<MaskWord, Lsb0>,
+) -> impl Iterator<Item = usize> + 'a {
+    value_mask.iter_ones().filter(|&idx| !child_mask[idx])
+}
+
+/// Value types of grids, with the names OpenVDB stores them under.
+pub trait GridValueType: Copy + Default {
+    /// OpenVDB name of this value type, see [`Tree::type_name`]
+    const TYPE_NAME: &'static str;
+    /// Number of scalar components making up a value
+    const COMPONENT_COUNT: usize = 1;
+}
+
+macro_rules! impl_grid_value_type {
+    ($ty:ty, $name:literal) => {
+        impl GridValueType for $ty {
+            const TYPE_NAME: &'static str = $name;
+        }
+    };
+    ($ty:ty, $name:literal, $component_count:literal) => {
+        impl GridValueType for $ty {
+            const TYPE_NAME: &'static str = $name;
+            const COMPONENT_COUNT: usize = $component_count;
+        }
+    };
+}
+
+impl_grid_value_type!(f32, "float");
+impl_grid_value_type!(f64, "double");
+impl_grid_value_type!(half::f16, "half");
+impl_grid_value_type!(i32, "int32");
+impl_grid_value_type!(i64, "int64");
+impl_grid_value_type!(bool, "bool");
+impl_grid_value_type!(Vec3, "vec3s", 3);
+impl_grid_value_type!([f32; 3], "vec3s", 3);
+impl_grid_value_type!(glam::DVec3, "vec3d", 3);
+impl_grid_value_type!([f64; 3], "vec3d", 3);
+impl_grid_value_type!(IVec3, "vec3i", 3);
+impl_grid_value_type!([i32; 3], "vec3i", 3);
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Tree<ValueTy, const L5: u32 = 5, const L4: u32 = 4, const L3: u32 = 3> {
+    pub root_nodes: Vec<Node5<ValueTy, L5, L4, L3>>,
+    /// Value of all voxels and tiles that are not explicitly stored in the tree
+    pub background: ValueTy,
+}
+
+impl<ValueTy, const L5: u32, const L4: u32, const L3: u32> Tree<ValueTy, L5, L4, L3> {
+    /// Creates an empty tree, where every voxel is an inactive `background` value.
+    pub fn new(background: ValueTy) -> Self {
+        Self {
+            root_nodes: vec![],
+            background,
+        }
+    }
+
+    /// Node size suffix of the OpenVDB tree type name, `_5_4_3` for the standard configuration.
+    pub fn config_suffix() -> String {
+        format!("_{L5}_{L4}_{L3}")
+    }
+
+    /// OpenVDB type name of this tree when storing values of type `value_type`, e.g.
+    /// `Tree_float_5_4_3`.
+    pub fn type_name(value_type: &str) -> String {
+        format!("Tree_{value_type}{}", Self::config_suffix())
+    }
+
+    /// Origin of the root level node that contains `coord`.
+    pub fn root_origin(coord: IVec3) -> IVec3 {
+        coord & !(Node5::<ValueTy, L5, L4, L3>::VOXEL_DIM as i32 - 1)
+    }
+
+    /// The root level node containing `coord`, if any.
+    pub fn root_node(&self, coord: IVec3) -> Option<&Node5<ValueTy, L5, L4, L3>> {
+        let origin = Self::root_origin(coord);
+        self.root_nodes
+            .iter()
+            .find(|node_5| node_5.origin == origin)
+    }
+
+    /// Mutable variant of [`Tree::root_node`].
+    pub fn root_node_mut(&mut self, coord: IVec3) -> Option<&mut Node5<ValueTy, L5, L4, L3>> {
+        let origin = Self::root_origin(coord);
+        self.root_nodes
+            .iter_mut()
+            .find(|node_5| node_5.origin == origin)
+    }
+
+    /// The leaf node containing `coord`, if any.
+    pub fn probe_leaf(&self, coord: IVec3) -> Option<&Node3<ValueTy, L3>> {
+        let node_5 = self.root_node(coord)?;
+        let node_4 = node_5
+            .nodes
+            .get(&node_5.global_coord_to_offset(GlobalCoord(coord)).0)?;
+        node_4
+            .nodes
+            .get(&node_4.global_coord_to_offset(GlobalCoord(coord)).0)
+            .map(Arc::as_ref)
+    }
+
+    /// All leaf nodes of the tree.
+    pub fn leaves(&self) -> impl Iterator<Item = &Node3<ValueTy, L3>> {
+        self.root_nodes
+            .iter()
+            .flat_map(|node_5| node_5.nodes.values())
+            .flat_map(|node_4| node_4.nodes.values().map(Arc::as_ref))
+    }
+
+    /// Calls `f` with the coordinate of every active voxel, including every voxel covered by an
+    /// active tile.
+    pub(crate) fn for_each_active_voxel(&self, mut f: impl FnMut(IVec3)) {
+        for node_5 in &self.root_nodes {
+            for idx in active_tiles(&node_5.child_mask, &node_5.value_mask) {
+                node_5
+                    .tile_bbox(Index(idx as u32))
+                    .into_iter()
+                    .for_each(&mut f);
+            }
+            for node_4 in node_5.nodes.values() {
+                for idx in active_tiles(&node_4.child_mask, &node_4.value_mask) {
+                    node_4
+                        .tile_bbox(Index(idx as u32))
+                        .into_iter()
+                        .for_each(&mut f);
+                }
+            }
+        }
+        for node_3 in self.leaves() {
+            for idx in node_3.value_mask.iter_ones() {
+                f(node_3.offset_to_global_coord(Index(idx as u32)).0);
+            }
+        }
+    }
+
+    /// Index-space bounding box enclosing all active voxels, active tiles count as their full
+    /// extent. Returns an empty box if nothing is active.
+    pub fn eval_active_voxel_bounding_box(&self) -> CoordBBox {
+        let mut bbox = CoordBBox::empty();
+        for node_5 in &self.root_nodes {
+            for idx in active_tiles(&node_5.child_mask, &node_5.value_mask) {
+                bbox.expand_bbox(&node_5.tile_bbox(Index(idx as u32)));
+            }
+            for node_4 in node_5.nodes.values() {
+                for idx in active_tiles(&node_4.child_mask, &node_4.value_mask) {
+                    bbox.expand_bbox(&node_4.tile_bbox(Index(idx as u32)));
+                }
+                for node_3 in node_4.nodes.values() {
+                    for idx in node_3.value_mask.iter_ones() {
+                        bbox.expand_coord(node_3.offset_to_global_coord(Index(idx as u32)).0);
+                    }
+                }
+            }
+        }
+        bbox
+    }
+
+    /// Number of active voxels in the tree, where every active tile counts for all of the voxels
+    /// it covers.
+    pub fn active_voxel_count(&self) -> u64 {
+        let mut count = 0;
+        for node_5 in &self.root_nodes {
+            count += active_tiles(&node_5.child_mask, &node_5.value_mask).count() as u64
+                * Node5::<ValueTy, L5, L4, L3>::TILE_VOXEL_COUNT;
+            for node_4 in node_5.nodes.values() {
+                count += active_tiles(&node_4.child_mask, &node_4.value_mask).count() as u64
+                    * Node4::<ValueTy, L4, L3>::TILE_VOXEL_COUNT;
+                for node_3 in node_4.nodes.values() {
+                    count += count_ones(&node_3.value_mask) as u64;
+                }
+            }
+        }
+        count
+    }
+
+    /// Number of leaf ([`Node3`]) nodes in the tree.
+    pub fn leaf_count(&self) -> usize {
+        self.root_nodes
+            .iter()
+            .flat_map(|node_5| node_5.nodes.values())
+            .map(|node_4| node_4.nodes.len())
+            .sum()
+    }
+
+    /// Approximate number of bytes used by the tree, including node buffers and masks.
+    pub fn memory_usage(&self) -> usize {
+        let value_size = std::mem::size_of::<ValueTy>();
+        let mask_size = |mask: &BitVec<MaskWord, Lsb0>| std::mem::size_of_val(mask.as_raw_slice());
+
+        let mut bytes = std::mem::size_of::<Self>();
+        for node_5 in &self.root_nodes {
+            bytes += std::mem::size_of::<Node5<ValueTy, L5, L4, L3>>()
+                + mask_size(&node_5.child_mask)
+                + mask_size(&node_5.value_mask)
+                + node_5.data.capacity() * value_size
+                + node_5.nodes.capacity() * std::mem::size_of::<(u32, Node4<ValueTy, L4, L3>)>();
+            for node_4 in node_5.nodes.values() {
+                bytes += mask_size(&node_4.child_mask)
+                    + mask_size(&node_4.value_mask)
+                    + node_4.data.capacity() * value_size
+                    + node_4.nodes.capacity()
+                        * std::mem::size_of::<(u32, Arc<Node3<ValueTy, L3>>)>();
+                for node_3 in node_4.nodes.values() {
+                    bytes += mask_size(&node_3.value_mask) + node_3.buffer.capacity() * value_size;
+                }
+            }
+        }
+        bytes
+    }
+}
+
+impl<ValueTy: Copy, const L5: u32, const L4: u32, const L3: u32> Tree<ValueTy, L5, L4, L3> {
+    /// Copy of this tree that shares no leaf nodes with it, unlike [`Clone::clone`] which only
+    /// copies leaves once they are modified.
+    pub fn deep_copy(&self) -> Self {
+        let mut tree = self.clone();
+        for node_5 in &mut tree.root_nodes {
+            for node_4 in node_5.nodes.values_mut() {
+                for node_3 in node_4.nodes.values_mut() {
+                    *node_3 = Arc::new(node_3.as_ref().clone());
+                }
+            }
+        }
+        tree
+    }
+
+    /// Tree with the same topology where every voxel, tile and background value is replaced by
+    /// `f(value)`. Useful both for converting value types (e.g. `f64` to `f32`) and for applying
+    /// transfer functions.
+    pub fn map_values<U: Copy>(&self, mut f: impl FnMut(ValueTy) -> U) -> Tree<U, L5, L4, L3> {
+        self.map_values_and_states(|value, _| f(value))
+    }
+
+    /// Mask tree mirroring the topology of this tree, where every voxel and tile holds its
+    /// active state as value.
+    pub fn topology_mask(&self) -> Tree<bool, L5, L4, L3> {
+        self.map_values_and_states(|_, active| active)
+    }
+
+    /// Like [`Tree::map_values`], but `f` also receives whether the value is active.
+    pub fn map_values_and_states<U: Copy>(
+        &self,
+        mut f: impl FnMut(ValueTy, bool) -> U,
+    ) -> Tree<U, L5, L4, L3> {
+        let root_nodes = self
+            .root_nodes
+            .iter()
+            .map(|node_5| Node5 {
+                child_mask: node_5.child_mask.clone(),
+                value_mask: node_5.value_mask.clone(),
+                nodes: node_5
+                    .nodes
+                    .iter()
+                    .map(|(&idx, node_4)| {
+                        let node_4 = Node4 {
+                            child_mask: node_4.child_mask.clone(),
+                            value_mask: node_4.value_mask.clone(),
+                            nodes: node_4
+                                .nodes
+                                .iter()
+                                .map(|(&idx, node_3)| {
+                                    let node_3 = Node3 {
+                                        buffer: node_3
+                                            .buffer
+                                            .iter()
+                                            .zip(node_3.value_mask.iter().by_vals())
+                                            .map(|(&v, active)| f(v, active))
+                                            .collect(),
+                                        value_mask: node_3.value_mask.clone(),
+                                        origin: node_3.origin,
+                                    };
+                                    (idx, Arc::new(node_3))
+                                })
+                                .collect(),
+                            data: node_4
+                                .data
+                                .iter()
+                                .zip(node_4.value_mask.iter().by_vals())
+                                .map(|(&v, active)| f(v, active))
+                                .collect(),
+                            origin: node_4.origin,
+                        };
+                        (idx, node_4)
+                    })
+                    .collect(),
+                data: node_5
+                    .data
+                    .iter()
+                    .zip(node_5.value_mask.iter().by_vals())
+                    .map(|(&v, active)| f(v, active))
+                    .collect(),
+                origin: node_5.origin,
+            })
+            .collect();
+
+        Tree {
+            root_nodes,
+            background: f(self.background, false),
+        }
+    }
+
+    /// Replaces every active tile with leaf nodes holding the tile value, so that all active
+    /// voxels are stored in leaves. Inactive tiles are left untouched.
+    pub fn voxelize_active_tiles(&mut self) {
+        for node_5 in &mut self.root_nodes {
+            for idx in active_tiles(&node_5.child_mask, &node_5.value_mask).collect::<Vec<_>>() {
+                let origin = node_5.offset_to_global_coord(Index(idx as u32)).0;
+                node_5
+                    .nodes
+                    .insert(idx as u32, Node4::new(origin, node_5.data[idx], true));
+                node_5.child_mask.set(idx, true);
+                node_5.value_mask.set(idx, false);
+            }
+            for node_4 in node_5.nodes.values_mut() {
+                for idx in active_tiles(&node_4.child_mask, &node_4.value_mask).collect::<Vec<_>>()
+                {
+                    let origin = node_4.offset_to_global_coord(Index(idx as u32)).0;
+                    node_4.nodes.insert(
+                        idx as u32,
+                        Arc::new(Node3::new(origin, node_4.data[idx], true)),
+                    );
+                    node_4.child_mask.set(idx, true);
+                    node_4.value_mask.set(idx, false);
+                }
+            }
+        }
+    }
+
+    /// Mutable variant of [`Tree::probe_leaf`], unshares the leaf if it is shared with a clone.
+    pub fn probe_leaf_mut(&mut self, coord: IVec3) -> Option<&mut Node3<ValueTy, L3>> {
+        let node_5 = self.root_node_mut(coord)?;
+        let offset = node_5.global_coord_to_offset(GlobalCoord(coord)).0;
+        let node_4 = node_5.nodes.get_mut(&offset)?;
+        let offset = node_4.global_coord_to_offset(GlobalCoord(coord)).0;
+        node_4.nodes.get_mut(&offset).map(Arc::make_mut)
+    }
+
+    /// Value and active state of the voxel at `coord`, either stored in a leaf, in a tile, or the
+    /// background value if no node covers the coordinate.
+    pub fn probe_value(&self, coord: IVec3) -> (ValueTy, bool) {
+        let Some(node_5) = self.root_node(coord) else {
+            return (self.background, false);
+        };
+        let offset = node_5.global_coord_to_offset(GlobalCoord(coord)).0;
+        let Some(node_4) = node_5.nodes.get(&offset) else {
+            return (
+                node_5.data[offset as usize],
+                node_5.value_mask[offset as usize],
+            );
+        };
+        let offset = node_4.global_coord_to_offset(GlobalCoord(coord)).0;
+        let Some(node_3) = node_4.nodes.get(&offset) else {
+            return (
+                node_4.data[offset as usize],
+                node_4.value_mask[offset as usize],
+            );
+        };
+        let offset = node_3.global_coord_to_offset(GlobalCoord(coord)).0 as usize;
+        (node_3.buffer[offset], node_3.value_mask[offset])
+    }
+
+    /// Value of the voxel at `coord`, see [`Tree::probe_value`].
+    pub fn get_value(&self, coord: IVec3) -> ValueTy {
+        self.probe_value(coord).0
+    }
+
+    /// Active state of the voxel at `coord`, see [`Tree::probe_value`].
+    pub fn is_value_on(&self, coord: IVec3) -> bool {
+        self.probe_value(coord).1
+    }
+
+    /// Sets the value of the voxel at `coord` and marks it as active.
+    pub fn set_value_on(&mut self, coord: IVec3, value: ValueTy) {
+        self.set_value(coord, value, true);
+    }
+
+    /// Sets the value of the voxel at `coord` and marks it as inactive.
+    pub fn set_value_off(&mut self, coord: IVec3, value: ValueTy) {
+        self.set_value(coord, value, false);
+    }
+
+    /// Sets the value and active state of the voxel at `coord`, creating (and densifying tiles
+    /// into) nodes down to the leaf level where needed.
+    pub fn set_value(&mut self, coord: IVec3, value: ValueTy, active: bool) {
+        let node_3 = self.touch_leaf(coord);
+        let offset = node_3.global_coord_to_offset(GlobalCoord(coord)).0 as usize;
+        node_3.buffer[offset] = value;
+        node_3.value_mask.set(offset, active);
+    }
+
+    /// Returns the leaf node containing `coord`, creating it if it doesn't exist yet. A newly
+    /// created leaf inherits the value and active state of the tile it replaces.
+    pub fn touch_leaf(&mut self, coord: IVec3) -> &mut Node3<ValueTy, L3> {
+        let node_4 = self.touch_node_4(coord);
+        let offset = node_4.global_coord_to_offset(GlobalCoord(coord));
+        let origin = node_4.offset_to_global_coord(Index(offset.0)).0;
+        let offset = offset.0 as usize;
+        if !node_4.child_mask[offset] {
+            let node_3 = Node3::new(origin, node_4.data[offset], node_4.value_mask[offset]);
+            node_4.nodes.insert(offset as u32, Arc::new(node_3));
+            node_4.child_mask.set(offset, true);
+            node_4.value_mask.set(offset, false);
+        }
+        Arc::make_mut(node_4.nodes.get_mut(&(offset as u32)).unwrap())
+    }
+
+    /// Returns the [`Node4`] containing `coord`, creating it (and its root node) if it doesn't
+    /// exist yet. A newly created node inherits the value and active state of the tile it
+    /// replaces.
+    pub fn touch_node_4(&mut self, coord: IVec3) -> &mut Node4<ValueTy, L4, L3> {
+        let node_5 = self.touch_root(coord);
+        let offset = node_5.global_coord_to_offset(GlobalCoord(coord));
+        let origin = node_5.offset_to_global_coord(Index(offset.0)).0;
+        let offset = offset.0 as usize;
+        if !node_5.child_mask[offset] {
+            let node_4 = Node4::new(origin, node_5.data[offset], node_5.value_mask[offset]);
+            node_5.nodes.insert(offset as u32, node_4);
+            node_5.child_mask.set(offset, true);
+            node_5.value_mask.set(offset, false);
+        }
+        node_5.nodes.get_mut(&(offset as u32)).unwrap()
+    }
+
+    /// Returns the root node containing `coord`, creating one filled with inactive background
+    /// tiles if it doesn't exist yet.
+    pub fn touch_root(&mut self, coord: IVec3) -> &mut Node5<ValueTy, L5, L4, L3> {
+        let origin = Self::root_origin(coord);
+        let root_idx = match self
+            .root_nodes
+            .iter()
+            .position(|node_5| node_5.origin == origin)
+        {
+            Some(root_idx) => root_idx,
+            None => {
+                self.root_nodes
+                    .push(Node5::new(origin, self.background, false));
+                self.root_nodes.len() - 1
+            }
+        };
+        &mut self.root_nodes[root_idx]
+    }
+
+    /// Inserts `leaf` at its origin, replacing the leaf or tile that was there.
+    pub fn add_leaf(&mut self, leaf: impl Into<Arc<Node3<ValueTy, L3>>>) {
+        let leaf = leaf.into();
+        let node_4 = self.touch_node_4(leaf.origin);
+        let offset = node_4.global_coord_to_offset(GlobalCoord(leaf.origin)).0;
+        node_4.nodes.insert(offset, leaf);
+        node_4.child_mask.set(offset as usize, true);
+        node_4.value_mask.set(offset as usize, false);
+    }
+
+    /// Sets the tile containing `coord` at `level` (the size of the node it replaces) to a
+    /// constant value and active state, discarding any child node that was there.
+    /// [`VdbLevel::Voxel`] sets a single voxel.
+    pub fn add_tile(&mut self, level: VdbLevel, coord: IVec3, value: ValueTy, active: bool) {
+        match level {
+            VdbLevel::Node4 => {
+                let node_5 = self.touch_root(coord);
+                let offset = node_5.global_coord_to_offset(GlobalCoord(coord)).0;
+                node_5.nodes.remove(&offset);
+                node_5.child_mask.set(offset as usize, false);
+                node_5.value_mask.set(offset as usize, active);
+                node_5.data[offset as usize] = value;
+            }
+            VdbLevel::Node3 => {
+                let node_4 = self.touch_node_4(coord);
+                let offset = node_4.global_coord_to_offset(GlobalCoord(coord)).0;
+                node_4.nodes.remove(&offset);
+                node_4.child_mask.set(offset as usize, false);
+                node_4.value_mask.set(offset as usize, active);
+                node_4.data[offset as usize] = value;
+            }
+            VdbLevel::Voxel => self.set_value(coord, value, active),
+        }
+    }
+}
+
+impl<ValueTy, const L5: u32, const L4: u32, const L3: u32> Tree<ValueTy, L5, L4, L3>
+where
+    ValueTy: Copy + PartialEq,
+{
+    /// Replaces the background value of the tree, rewriting every inactive voxel and tile that
+    /// holds the old background value so the tree stays consistent.
+    pub fn set_background(&mut self, new_background: ValueTy) {
+        let old_background = self.background;
+        self.map_inactive_values(|value| {
+            if value == old_background {
+                new_background
+            } else {
+                value
+            }
+        });
+        self.background = new_background;
+    }
+
+    /// Replaces every inactive value with `map(value)`. Leaves whose inactive values all map to
+    /// themselves are left untouched, so they stay shared with copies of the tree.
+    fn map_inactive_values(&mut self, map: impl Fn(ValueTy) -> ValueTy) {
+        for node_5 in &mut self.root_nodes {
+            for idx in node_5.child_mask.iter_zeros() {
+                if !node_5.value_mask[idx] {
+                    node_5.data[idx] = map(node_5.data[idx]);
+                }
+            }
+            for node_4 in node_5.nodes.values_mut() {
+                for idx in node_4.child_mask.iter_zeros() {
+                    if !node_4.value_mask[idx] {
+                        node_4.data[idx] = map(node_4.data[idx]);
+                    }
+                }
+                for node_3 in node_4.nodes.values_mut() {
+                    let changes = node_3.value_mask.iter_zeros().any(|idx| {
+                        let value = node_3.buffer[idx];
+                        map(value) != value
+                    });
+                    if changes {
+                        let node_3 = Arc::make_mut(node_3);
+                        for idx in node_3.value_mask.iter_zeros() {
+                            node_3.buffer[idx] = map(node_3.buffer[idx]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<ValueTy, const L5: u32, const L4: u32, const L3: u32> Tree<ValueTy, L5, L4, L3>
+where
+    ValueTy: Copy + PartialOrd + Neg<Output = ValueTy> + Zeroable,
+{
+    /// Level set variant of [`Tree::set_background`]: inactive values on the inside of the
+    /// surface (negative values) become `-new_background`, all others become `new_background`.
+    pub fn set_level_set_background(&mut self, new_background: ValueTy) {
+        let zero = ValueTy::zeroed();
+        self.map_inactive_values(|value| {
+            if value < zero {
+                -new_background
+            } else {
+                new_background
+            }
+        });
+        self.background = new_background;
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -345,6 +1279,8 @@ pub enum NodeMetaData {
 
 bitflags! {
     #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "bevy", derive(bevy::reflect::Reflect), reflect_value(Debug, PartialEq, Hash))]
     pub struct Compression: u32 {
         const NONE = 0;
         const ZIP = 0x1;
@@ -354,7 +1290,8 @@ bitflags! {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "bevy", derive(bevy::reflect::Reflect))]
 pub struct ArchiveHeader {
     /// The version of the file that was read
     pub file_version: u32,
@@ -372,3 +1309,53 @@ pub struct ArchiveHeader {
     /// The metadata for the input stream
     pub meta_data: Metadata,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves(tree: &Tree<f32>) -> Vec<&Arc<Node3<f32>>> {
+        tree.root_nodes
+            .iter()
+            .flat_map(|node_5| node_5.nodes.values())
+            .flat_map(|node_4| node_4.nodes.values())
+            .collect()
+    }
+
+    #[test]
+    fn set_background_only_unshares_changed_leaves() {
+        let mut tree = Tree::new(0.0f32);
+        tree.set_value_on(IVec3::ZERO, 1.0);
+        tree.set_value_on(IVec3::new(100, 0, 0), 1.0);
+        let copy = tree.clone();
+
+        tree.set_background(0.0);
+        let mut unchanged = leaves(&tree).into_iter().zip(leaves(&copy));
+        assert!(unchanged.all(|(a, b)| Arc::ptr_eq(a, b)));
+
+        tree.set_background(2.0);
+        let mut changed = leaves(&tree).into_iter().zip(leaves(&copy));
+        assert!(changed.all(|(a, b)| !Arc::ptr_eq(a, b)));
+        assert_eq!(tree.get_value(IVec3::new(1, 0, 0)), 2.0);
+        assert_eq!(copy.get_value(IVec3::new(1, 0, 0)), 0.0);
+    }
+
+    #[test]
+    fn map_values_keeps_grid_type_and_convert_values_sets_it() {
+        let mut tree: Tree<f32> = Tree::new(0.0);
+        tree.set_value_on(IVec3::ZERO, 0.5);
+        let grid = Grid {
+            tree,
+            transform: Default::default(),
+            descriptor: GridDescriptor::new("density", "Tree_float_5_4_3"),
+        };
+
+        let bytes = grid.map_values(|value| (value * 255.0) as u8);
+        assert_eq!(bytes.tree.get_value(IVec3::ZERO), 127);
+        assert_eq!(bytes.descriptor.grid_type, "Tree_float_5_4_3");
+
+        let doubles = grid.convert_values(f64::from);
+        assert_eq!(doubles.tree.get_value(IVec3::ZERO), 0.5);
+        assert_eq!(doubles.descriptor.grid_type, "Tree_double_5_4_3");
+    }
+}
@@ -0,0 +1,195 @@
+use crate::coordinates::{CoordBBox, CoordBBoxIter};
+use crate::data_structure::Grid;
+
+use glam::{IVec3, UVec3};
+use std::ops::Sub;
+
+/// A dense block of voxel values covering a [`CoordBBox`].
+///
+/// Values are stored with `z` varying fastest, then `y`, then `x`, matching the layout of the
+/// buffers inside the tree nodes, so `data` can be used as a `[x][y][z]` array directly.
+#[derive(Debug, Clone)]
+pub struct Dense<ValueTy> {
+    pub bbox: CoordBBox,
+    pub data: Vec<ValueTy>,
+}
+
+impl<ValueTy> Dense<ValueTy> {
+    /// Number of voxels along each axis.
+    pub fn dims(&self) -> UVec3 {
+        self.bbox.dim()
+    }
+
+    /// Global coordinates of all voxels in the block, in the same order as `data`.
+    pub fn coords(&self) -> CoordBBoxIter {
+        self.bbox.iter()
+    }
+}
+
+impl<ValueTy: Copy> Dense<ValueTy> {
+    /// Creates a dense block over `bbox` where every voxel holds `value`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the box holds more voxels than fit in `usize`.
+    pub fn new(bbox: CoordBBox, value: ValueTy) -> Self {
+        let volume =
+            usize::try_from(bbox.volume()).expect("Dense block volume exceeds the address space");
+        Self {
+            bbox,
+            data: vec![value; volume],
+        }
+    }
+
+    /// Wraps an existing buffer, returns `None` if its length doesn't match the box volume.
+    pub fn from_data(bbox: CoordBBox, data: Vec<ValueTy>) -> Option<Self> {
+        (usize::try_from(bbox.volume()) == Ok(data.len())).then_some(Self { bbox, data })
+    }
+
+    /// Linear index into `data` for a global coordinate, `None` if it lies outside the block.
+    pub fn index(&self, coord: IVec3) -> Option<usize> {
+        if !self.bbox.is_inside(coord) {
+            return None;
+        }
+        let [_, dim_y, dim_z] = self.dims().to_array().map(|dim| dim as usize);
+        let [x, y, z] = (coord - self.bbox.min)
+            .as_uvec3()
+            .to_array()
+            .map(|v| v as usize);
+        Some((x * dim_y + y) * dim_z + z)
+    }
+
+    pub fn get(&self, coord: IVec3) -> Option<ValueTy> {
+        self.index(coord).map(|idx| self.data[idx])
+    }
+
+    pub fn set(&mut self, coord: IVec3, value: ValueTy) {
+        if let Some(idx) = self.index(coord) {
+            self.data[idx] = value;
+        }
+    }
+}
+
+/// Copies the values of `grid` inside `bbox` into a dense block, voxels not stored in the tree
+/// take their tile or background value.
+pub fn copy_to_dense<ValueTy: Copy, const L5: u32, const L4: u32, const L3: u32>(
+    grid: &Grid<ValueTy, L5, L4, L3>,
+    bbox: CoordBBox,
+) -> Dense<ValueTy> {
+    let mut dense = Dense::new(bbox, grid.tree.background);
+    for (value, coord) in dense.data.iter_mut().zip(bbox) {
+        *value = grid.tree.get_value(coord);
+    }
+    dense
+}
+
+/// Writes the values of `dense` into `grid` as active voxels. Values that are within
+/// `tolerance` of the grid's background are written as inactive background voxels instead, and
+/// don't allocate new nodes where the grid is already empty.
+pub fn copy_from_dense<ValueTy, const L5: u32, const L4: u32, const L3: u32>(
+    dense: &Dense<ValueTy>,
+    grid: &mut Grid<ValueTy, L5, L4, L3>,
+    tolerance: ValueTy,
+) where
+    ValueTy: Copy + PartialOrd + Sub<Output = ValueTy>,
+{
+    let background = grid.tree.background;
+    for (&value, coord) in dense.data.iter().zip(dense.coords()) {
+        let difference = if value > background {
+            value - background
+        } else {
+            background - value
+        };
+        if difference > tolerance {
+            grid.tree.set_value_on(coord, value);
+        } else if grid.tree.probe_value(coord) != (background, false) {
+            grid.tree.set_value_off(coord, background);
+        }
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl<ValueTy> Dense<ValueTy> {
+    /// Converts the block into a `[x][y][z]` indexed array, the box origin maps to `[0, 0, 0]`.
+    pub fn into_array3(self) -> ndarray::Array3<ValueTy> {
+        let dims = self.dims();
+        ndarray::Array3::from_shape_vec(
+            (dims.x as usize, dims.y as usize, dims.z as usize),
+            self.data,
+        )
+        .expect("Dense data length always matches the box volume")
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl<ValueTy: Copy> Dense<ValueTy> {
+    /// Wraps an array as a dense block with its `[0, 0, 0]` element at `origin`.
+    pub fn from_array3(origin: IVec3, array: ndarray::Array3<ValueTy>) -> Self {
+        let (x, y, z) = array.dim();
+        let bbox = CoordBBox::new(
+            origin,
+            origin + IVec3::new(x as i32, y as i32, z as i32) - 1,
+        );
+        let data = if array.is_standard_layout() {
+            array.into_raw_vec()
+        } else {
+            array.iter().copied().collect()
+        };
+        Self { bbox, data }
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl<ValueTy: Copy, const L5: u32, const L4: u32, const L3: u32> crate::Tree<ValueTy, L5, L4, L3> {
+    /// Values inside `bbox` as a `[x][y][z]` indexed array, with `bbox.min` at `[0, 0, 0]`.
+    /// Voxels not stored in the tree take their tile or background value.
+    pub fn to_ndarray(&self, bbox: CoordBBox) -> ndarray::Array3<ValueTy> {
+        let dims = bbox.dim();
+        let mut accessor = self.accessor();
+        ndarray::Array3::from_shape_fn(
+            (dims.x as usize, dims.y as usize, dims.z as usize),
+            |(x, y, z)| accessor.get_value(bbox.min + IVec3::new(x as i32, y as i32, z as i32)),
+        )
+    }
+
+    /// Tree holding the elements of a `[x][y][z]` indexed `array` as active voxels, with its
+    /// `[0, 0, 0]` element at `origin`. Elements equal to `background` are left out.
+    pub fn from_ndarray(
+        array: ndarray::ArrayView3<'_, ValueTy>,
+        origin: IVec3,
+        background: ValueTy,
+    ) -> Self
+    where
+        ValueTy: PartialEq,
+    {
+        let mut tree = Self::new(background);
+        for ((x, y, z), &value) in array.indexed_iter() {
+            if value != background {
+                let coord = origin + IVec3::new(x as i32, y as i32, z as i32);
+                tree.set_value_on(coord, value);
+            }
+        }
+        tree
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl<ValueTy, const L5: u32, const L4: u32, const L3: u32> From<ndarray::ArrayView3<'_, ValueTy>>
+    for crate::Tree<ValueTy, L5, L4, L3>
+where
+    ValueTy: Copy + Default + PartialEq,
+{
+    /// Tree with the nonzero elements of `array` as active voxels, with its `[0, 0, 0]`
+    /// element at the origin and a default background, see [`Tree::from_ndarray`].
+    fn from(array: ndarray::ArrayView3<'_, ValueTy>) -> Self {
+        Self::from_ndarray(array, IVec3::ZERO, ValueTy::default())
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl<ValueTy: Copy, const L5: u32, const L4: u32, const L3: u32> Grid<ValueTy, L5, L4, L3> {
+    /// Values inside `bbox` as a `[x][y][z]` indexed array, see [`Tree::to_ndarray`].
+    pub fn to_ndarray(&self, bbox: CoordBBox) -> ndarray::Array3<ValueTy> {
+        self.tree.to_ndarray(bbox)
+    }
+}
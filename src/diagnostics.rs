@@ -0,0 +1,233 @@
+use crate::coordinates::{CoordBBox, Index, LocalCoord};
+use crate::data_structure::{Grid, GridClass, Node, Node3};
+use crate::visitor::NodeVisitor;
+
+use glam::IVec3;
+
+/// A problem found by [`Grid::check_level_set`] or [`Grid::check_fog_volume`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Diagnostic {
+    /// The `class` metadata doesn't match the checked kind of grid.
+    WrongGridClass { found: GridClass },
+    /// Level sets need cubic voxels for their distances to be meaningful.
+    NonUniformVoxelSize { voxel_size: glam::DVec3 },
+    /// The background of a level set should equal its narrow band half-width in world units.
+    BackgroundNotHalfWidth { background: f64, expected: f64 },
+    /// The background of a fog volume should be zero.
+    NonZeroBackground { background: f64 },
+    /// Active or inactive values that are NaN or infinite.
+    NonFiniteValues(IssueCount),
+    /// Active values outside of the valid range of the grid class.
+    ActiveValuesOutOfRange {
+        min: f64,
+        max: f64,
+        values: IssueCount,
+    },
+    /// Inactive values that don't match the background, or minus the background for the inside
+    /// of a level set.
+    InactiveValuesNotBackground(IssueCount),
+    /// Neighboring inactive level set voxels with opposite signs, meaning the surface crosses
+    /// between them without being represented by active voxels.
+    InconsistentSigns(IssueCount),
+}
+
+/// How many voxels have a certain issue, and where the first one was found. Tiles count for
+/// every voxel they cover and report their minimum corner.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IssueCount {
+    pub count: u64,
+    pub first: IVec3,
+}
+
+/// Result of a grid validation, empty if the grid is valid.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DiagnosticsReport {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl DiagnosticsReport {
+    pub fn is_valid(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+}
+
+#[derive(Default)]
+struct Counter(Option<IssueCount>);
+
+impl Counter {
+    fn add(&mut self, coord: IVec3, count: u64) {
+        match &mut self.0 {
+            Some(issue) => issue.count += count,
+            None => {
+                self.0 = Some(IssueCount {
+                    count,
+                    first: coord,
+                })
+            }
+        }
+    }
+}
+
+/// Visitor shared by both checks, `inactive_ok` decides whether an inactive value is valid.
+struct ValueChecker<F> {
+    min: f64,
+    max: f64,
+    inactive_ok: F,
+    check_signs: bool,
+    non_finite: Counter,
+    out_of_range: Counter,
+    inactive: Counter,
+    signs: Counter,
+}
+
+impl<F: Fn(f64) -> bool> ValueChecker<F> {
+    fn check(&mut self, coord: IVec3, value: f64, active: bool, count: u64) {
+        if !value.is_finite() {
+            self.non_finite.add(coord, count);
+        } else if active && !(self.min..=self.max).contains(&value) {
+            self.out_of_range.add(coord, count);
+        } else if !active && !(self.inactive_ok)(value) {
+            self.inactive.add(coord, count);
+        }
+    }
+
+    fn into_diagnostics(self) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+        diagnostics.extend(self.non_finite.0.map(Diagnostic::NonFiniteValues));
+        diagnostics.extend(
+            self.out_of_range
+                .0
+                .map(|values| Diagnostic::ActiveValuesOutOfRange {
+                    min: self.min,
+                    max: self.max,
+                    values,
+                }),
+        );
+        diagnostics.extend(self.inactive.0.map(Diagnostic::InactiveValuesNotBackground));
+        diagnostics.extend(self.signs.0.map(Diagnostic::InconsistentSigns));
+        diagnostics
+    }
+}
+
+impl<ValueTy, F, const L5: u32, const L4: u32, const L3: u32> NodeVisitor<ValueTy, L5, L4, L3>
+    for ValueChecker<F>
+where
+    ValueTy: Copy + Into<f64>,
+    F: Fn(f64) -> bool,
+{
+    fn visit_node_3(&mut self, node: &Node3<ValueTy, L3>) -> bool {
+        if self.check_signs {
+            let dim = 1u32 << L3;
+            for idx in node.value_mask.iter_zeros() {
+                let local = node.offset_to_local_coord(Index(idx as u32)).0;
+                let value: f64 = node.buffer[idx].into();
+                // Only look in the positive direction so every pair is checked once
+                for axis in 0..3 {
+                    if local[axis] + 1 >= dim {
+                        continue;
+                    }
+                    let mut neighbor = local;
+                    neighbor[axis] += 1;
+                    let neighbor_idx = node.local_coord_to_offset(LocalCoord(neighbor)).0 as usize;
+                    let neighbor_value: f64 = node.buffer[neighbor_idx].into();
+                    if !node.value_mask[neighbor_idx]
+                        && value.is_finite()
+                        && neighbor_value.is_finite()
+                        && (value < 0.0) != (neighbor_value < 0.0)
+                    {
+                        let coord = node.offset_to_global_coord(Index(idx as u32)).0;
+                        self.signs.add(coord, 1);
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    fn visit_tile(&mut self, bbox: CoordBBox, value: ValueTy, active: bool) {
+        self.check(bbox.min, value.into(), active, bbox.volume());
+    }
+
+    fn visit_voxel(&mut self, coord: IVec3, value: ValueTy, active: bool) {
+        self.check(coord, value.into(), active, 1);
+    }
+}
+
+impl<ValueTy, const L5: u32, const L4: u32, const L3: u32> Grid<ValueTy, L5, L4, L3>
+where
+    ValueTy: Copy + Into<f64>,
+{
+    /// Validates that this grid is a level set with a narrow band of `half_width` voxels on both
+    /// sides of the surface: cubic voxels, a background equal to the half-width in world units,
+    /// finite values, active values within the narrow band, and inactive values equal to plus
+    /// or minus the background without sign changes between them.
+    pub fn check_level_set(&self, half_width: f64) -> DiagnosticsReport {
+        let mut diagnostics = vec![];
+
+        let class = self.grid_class();
+        if class != GridClass::LevelSet {
+            diagnostics.push(Diagnostic::WrongGridClass { found: class });
+        }
+
+        let voxel_size = self.transform.voxel_size();
+        if (voxel_size.max_element() - voxel_size.min_element()).abs() > 1e-6 * voxel_size.x {
+            diagnostics.push(Diagnostic::NonUniformVoxelSize { voxel_size });
+        }
+
+        let background: f64 = self.tree.background.into();
+        let expected = half_width * voxel_size.x;
+        if (background - expected).abs() > 1e-4 * expected.abs() {
+            diagnostics.push(Diagnostic::BackgroundNotHalfWidth {
+                background,
+                expected,
+            });
+        }
+
+        let tolerance = 1e-4 * background.abs();
+        let mut checker = ValueChecker {
+            min: -background.abs(),
+            max: background.abs(),
+            inactive_ok: |value: f64| (value.abs() - background.abs()).abs() <= tolerance,
+            check_signs: true,
+            non_finite: Counter::default(),
+            out_of_range: Counter::default(),
+            inactive: Counter::default(),
+            signs: Counter::default(),
+        };
+        self.visit(&mut checker);
+        diagnostics.extend(checker.into_diagnostics());
+
+        DiagnosticsReport { diagnostics }
+    }
+
+    /// Validates that this grid is a fog volume: a zero background, finite values, active values
+    /// in `[0, 1]` and inactive values equal to zero.
+    pub fn check_fog_volume(&self) -> DiagnosticsReport {
+        let mut diagnostics = vec![];
+
+        let class = self.grid_class();
+        if class != GridClass::FogVolume {
+            diagnostics.push(Diagnostic::WrongGridClass { found: class });
+        }
+
+        let background: f64 = self.tree.background.into();
+        if background != 0.0 {
+            diagnostics.push(Diagnostic::NonZeroBackground { background });
+        }
+
+        let mut checker = ValueChecker {
+            min: 0.0,
+            max: 1.0,
+            inactive_ok: |value: f64| value == 0.0,
+            check_signs: false,
+            non_finite: Counter::default(),
+            out_of_range: Counter::default(),
+            inactive: Counter::default(),
+            signs: Counter::default(),
+        };
+        self.visit(&mut checker);
+        diagnostics.extend(checker.into_diagnostics());
+
+        DiagnosticsReport { diagnostics }
+    }
+}
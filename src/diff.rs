@@ -0,0 +1,251 @@
+use crate::coordinates::CoordBBox;
+use crate::data_structure::{Grid, MetadataValue, Node3, Tree};
+use crate::diagnostics::IssueCount;
+use crate::visitor::NodeVisitor;
+
+use glam::{DVec3, IVec3, Vec3};
+
+/// Values that can be compared by [`diff_grids`].
+pub trait DiffValue: Copy {
+    /// Absolute difference between two values, the length of the difference for vectors.
+    fn deviation(self, other: Self) -> f64;
+}
+
+impl DiffValue for f32 {
+    fn deviation(self, other: Self) -> f64 {
+        (self as f64 - other as f64).abs()
+    }
+}
+
+impl DiffValue for f64 {
+    fn deviation(self, other: Self) -> f64 {
+        (self - other).abs()
+    }
+}
+
+impl DiffValue for i32 {
+    fn deviation(self, other: Self) -> f64 {
+        (self as f64 - other as f64).abs()
+    }
+}
+
+impl DiffValue for i64 {
+    fn deviation(self, other: Self) -> f64 {
+        (self as f64 - other as f64).abs()
+    }
+}
+
+impl DiffValue for bool {
+    fn deviation(self, other: Self) -> f64 {
+        (self != other) as u8 as f64
+    }
+}
+
+impl DiffValue for Vec3 {
+    fn deviation(self, other: Self) -> f64 {
+        self.as_dvec3().distance(other.as_dvec3())
+    }
+}
+
+impl DiffValue for DVec3 {
+    fn deviation(self, other: Self) -> f64 {
+        self.distance(other)
+    }
+}
+
+impl DiffValue for [f32; 3] {
+    fn deviation(self, other: Self) -> f64 {
+        Vec3::from(self).deviation(Vec3::from(other))
+    }
+}
+
+impl DiffValue for [f64; 3] {
+    fn deviation(self, other: Self) -> f64 {
+        DVec3::from(self).deviation(DVec3::from(other))
+    }
+}
+
+/// A metadata field that differs between the compared grids, `None` where it is missing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MetadataDiff {
+    pub key: String,
+    pub a: Option<MetadataValue>,
+    pub b: Option<MetadataValue>,
+}
+
+/// Differences between two grids found by [`diff_grids`]. Active tiles count for every voxel
+/// they cover.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DiffReport {
+    /// Voxels active in the first grid but not in the second
+    pub only_in_a: Option<IssueCount>,
+    /// Voxels active in the second grid but not in the first
+    pub only_in_b: Option<IssueCount>,
+    /// Number of voxels active in both grids
+    pub common_active: u64,
+    /// Largest deviation between the values of voxels active in both grids
+    pub max_deviation: f64,
+    pub mean_deviation: f64,
+    /// Voxels active in both grids whose values deviate by more than the tolerance
+    pub exceeding_tolerance: Option<IssueCount>,
+    /// Deviation between the backgrounds of the grids
+    pub background_deviation: f64,
+    /// Largest distance in world space between the positions the two transforms map the index
+    /// space origin and unit axes to
+    pub transform_deviation: f64,
+    /// Metadata fields that differ, sorted by key
+    pub metadata: Vec<MetadataDiff>,
+    /// The tolerance the values were compared with
+    pub tolerance: f64,
+}
+
+impl DiffReport {
+    /// Whether the grids have the same topology, transform and metadata, and all their values
+    /// are within the tolerance.
+    pub fn is_match(&self) -> bool {
+        self.only_in_a.is_none()
+            && self.only_in_b.is_none()
+            && self.exceeding_tolerance.is_none()
+            && self.background_deviation <= self.tolerance
+            && self.transform_deviation <= self.tolerance
+            && self.metadata.is_empty()
+    }
+}
+
+fn add_issue(issue: &mut Option<IssueCount>, coord: IVec3) {
+    match issue {
+        Some(issue) => issue.count += 1,
+        None => {
+            *issue = Some(IssueCount {
+                count: 1,
+                first: coord,
+            })
+        }
+    }
+}
+
+/// Compares the active voxels of the visited tree with those of `other`. Voxels active in both
+/// are only compared when `compare_values` is set, so they are counted once across both passes.
+struct TopologyDiff<'a, ValueTy, const L5: u32, const L4: u32, const L3: u32> {
+    other: &'a Tree<ValueTy, L5, L4, L3>,
+    compare_values: bool,
+    tolerance: f64,
+    only_in_tree: Option<IssueCount>,
+    common_active: u64,
+    deviation_sum: f64,
+    max_deviation: f64,
+    exceeding_tolerance: Option<IssueCount>,
+}
+
+impl<ValueTy: DiffValue, const L5: u32, const L4: u32, const L3: u32>
+    TopologyDiff<'_, ValueTy, L5, L4, L3>
+{
+    fn compare(&mut self, coord: IVec3, value: ValueTy) {
+        let (other_value, other_active) = self.other.probe_value(coord);
+        if !other_active {
+            add_issue(&mut self.only_in_tree, coord);
+        } else if self.compare_values {
+            let deviation = value.deviation(other_value);
+            self.common_active += 1;
+            self.deviation_sum += deviation;
+            self.max_deviation = self.max_deviation.max(deviation);
+            if deviation > self.tolerance {
+                add_issue(&mut self.exceeding_tolerance, coord);
+            }
+        }
+    }
+}
+
+impl<ValueTy: DiffValue, const L5: u32, const L4: u32, const L3: u32>
+    NodeVisitor<ValueTy, L5, L4, L3> for TopologyDiff<'_, ValueTy, L5, L4, L3>
+{
+    fn visit_node_3(&mut self, node: &Node3<ValueTy, L3>) -> bool {
+        node.value_mask.any()
+    }
+
+    fn visit_tile(&mut self, bbox: CoordBBox, value: ValueTy, active: bool) {
+        if active {
+            for coord in bbox.iter() {
+                self.compare(coord, value);
+            }
+        }
+    }
+
+    fn visit_voxel(&mut self, coord: IVec3, value: ValueTy, active: bool) {
+        if active {
+            self.compare(coord, value);
+        }
+    }
+}
+
+/// Compares two grids voxel by voxel, reporting the voxels active in only one of them, how far
+/// the values of the voxels active in both deviate, and differences in background, transform
+/// and metadata. Values within `tolerance` of each other count as equal.
+///
+/// Useful to validate files written by other tools against reference output, or to catch
+/// regressions in simulation caches.
+pub fn diff_grids<ValueTy: DiffValue, const L5: u32, const L4: u32, const L3: u32>(
+    a: &Grid<ValueTy, L5, L4, L3>,
+    b: &Grid<ValueTy, L5, L4, L3>,
+    tolerance: f64,
+) -> DiffReport {
+    let topology_diff = |other, compare_values| TopologyDiff {
+        other,
+        compare_values,
+        tolerance,
+        only_in_tree: None,
+        common_active: 0,
+        deviation_sum: 0.0,
+        max_deviation: 0.0,
+        exceeding_tolerance: None,
+    };
+    let mut a_to_b = topology_diff(&b.tree, true);
+    a.tree.visit(&mut a_to_b);
+    let mut b_to_a = topology_diff(&a.tree, false);
+    b.tree.visit(&mut b_to_a);
+
+    let points = [DVec3::ZERO, DVec3::X, DVec3::Y, DVec3::Z];
+    let transform_deviation = points
+        .iter()
+        .map(|&p| {
+            a.transform
+                .index_to_world_f64(p)
+                .distance(b.transform.index_to_world_f64(p))
+        })
+        .fold(0.0, f64::max);
+
+    let a_metadata = &a.descriptor.meta_data.0;
+    let b_metadata = &b.descriptor.meta_data.0;
+    let mut keys = a_metadata
+        .keys()
+        .chain(b_metadata.keys())
+        .collect::<Vec<_>>();
+    keys.sort();
+    keys.dedup();
+    let metadata = keys
+        .into_iter()
+        .filter(|key| a_metadata.get(*key) != b_metadata.get(*key))
+        .map(|key| MetadataDiff {
+            key: key.to_string(),
+            a: a_metadata.get(key).cloned(),
+            b: b_metadata.get(key).cloned(),
+        })
+        .collect();
+
+    DiffReport {
+        only_in_a: a_to_b.only_in_tree,
+        only_in_b: b_to_a.only_in_tree,
+        common_active: a_to_b.common_active,
+        max_deviation: a_to_b.max_deviation,
+        mean_deviation: if a_to_b.common_active > 0 {
+            a_to_b.deviation_sum / a_to_b.common_active as f64
+        } else {
+            0.0
+        },
+        exceeding_tolerance: a_to_b.exceeding_tolerance,
+        background_deviation: a.tree.background.deviation(b.tree.background),
+        transform_deviation,
+        metadata,
+        tolerance,
+    }
+}
@@ -0,0 +1,653 @@
+use crate::data_structure::Grid;
+
+use glam::IVec3;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ExpressionError {
+    #[error("Syntax error at byte {position}: {message}")]
+    Syntax { position: usize, message: String },
+    #[error("No grid named {0} is bound")]
+    UnknownGrid(String),
+    #[error("Several bound grids are named {0}")]
+    DuplicateGrid(String),
+}
+
+/// Built-in functions by name, with their number of arguments.
+#[allow(clippy::type_complexity)]
+const FUNCTIONS: &[(&str, usize, fn(&[f32]) -> f32)] = &[
+    ("abs", 1, |a| a[0].abs()),
+    ("sign", 1, |a| if a[0] == 0.0 { 0.0 } else { a[0].signum() }),
+    ("sqrt", 1, |a| a[0].sqrt()),
+    ("exp", 1, |a| a[0].exp()),
+    ("log", 1, |a| a[0].ln()),
+    ("sin", 1, |a| a[0].sin()),
+    ("cos", 1, |a| a[0].cos()),
+    ("tan", 1, |a| a[0].tan()),
+    ("floor", 1, |a| a[0].floor()),
+    ("ceil", 1, |a| a[0].ceil()),
+    ("round", 1, |a| a[0].round()),
+    ("pow", 2, |a| a[0].powf(a[1])),
+    ("atan2", 2, |a| a[0].atan2(a[1])),
+    ("min", 2, |a| a[0].min(a[1])),
+    ("max", 2, |a| a[0].max(a[1])),
+    ("clamp", 3, |a| a[0].max(a[1]).min(a[2])),
+    ("lerp", 3, |a| a[0] + (a[1] - a[0]) * a[2]),
+    ("smoothstep", 3, |a| {
+        let t = ((a[2] - a[0]) / (a[1] - a[0])).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    }),
+    ("fit", 5, |a| {
+        let t = ((a[0] - a[1]) / (a[2] - a[1])).clamp(0.0, 1.0);
+        a[3] + (a[4] - a[3]) * t
+    }),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+impl BinaryOp {
+    fn apply(self, a: f32, b: f32) -> f32 {
+        match self {
+            BinaryOp::Add => a + b,
+            BinaryOp::Sub => a - b,
+            BinaryOp::Mul => a * b,
+            BinaryOp::Div => a / b,
+            BinaryOp::Rem => a % b,
+            BinaryOp::Lt => (a < b) as u8 as f32,
+            BinaryOp::Le => (a <= b) as u8 as f32,
+            BinaryOp::Gt => (a > b) as u8 as f32,
+            BinaryOp::Ge => (a >= b) as u8 as f32,
+            BinaryOp::Eq => (a == b) as u8 as f32,
+            BinaryOp::Ne => (a != b) as u8 as f32,
+        }
+    }
+}
+
+/// Instruction of the stack machine an [`Expression`] compiles to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Push(f32),
+    LoadAttribute(usize),
+    StoreAttribute(usize),
+    LoadLocal(usize),
+    StoreLocal(usize),
+    /// Pushes the index space coordinate of the voxel along an axis
+    Coord(usize),
+    Neg,
+    Not,
+    Binary(BinaryOp),
+    Call(usize),
+    Jump(usize),
+    /// Pops a value and jumps if it is zero
+    JumpIfZero(usize),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f32),
+    Attribute(String),
+    Identifier(String),
+    Punct(&'static str),
+    End,
+}
+
+const PUNCTS: [&str; 27] = [
+    "+=", "-=", "*=", "/=", "==", "!=", "<=", ">=", "&&", "||", "+", "-", "*", "/", "%", "<", ">",
+    "!", "=", "(", ")", "{", "}", ";", ",", "?", ":",
+];
+
+fn tokenize(code: &str) -> Result<Vec<(Token, usize)>, ExpressionError> {
+    let bytes = code.as_bytes();
+    let is_word = |c: u8| c.is_ascii_alphanumeric() || c == b'_';
+    let mut tokens = vec![];
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let c = bytes[pos];
+        let start = pos;
+        if c.is_ascii_whitespace() {
+            pos += 1;
+        } else if code[pos..].starts_with("//") {
+            pos = code[pos..].find('\n').map_or(bytes.len(), |end| pos + end);
+        } else if c.is_ascii_digit()
+            || (c == b'.' && bytes.get(pos + 1).is_some_and(u8::is_ascii_digit))
+        {
+            while pos < bytes.len() && (bytes[pos].is_ascii_digit() || bytes[pos] == b'.') {
+                pos += 1;
+            }
+            if pos < bytes.len() && matches!(bytes[pos], b'e' | b'E') {
+                pos += 1;
+                if pos < bytes.len() && matches!(bytes[pos], b'+' | b'-') {
+                    pos += 1;
+                }
+                while pos < bytes.len() && bytes[pos].is_ascii_digit() {
+                    pos += 1;
+                }
+            }
+            let number = code[start..pos]
+                .parse()
+                .map_err(|_| ExpressionError::Syntax {
+                    position: start,
+                    message: format!("invalid number {}", &code[start..pos]),
+                })?;
+            // Allow the `f` suffix of float literals
+            if pos < bytes.len() && bytes[pos] == b'f' {
+                pos += 1;
+            }
+            tokens.push((Token::Number(number), start));
+        } else if c == b'@' || is_word(c) {
+            pos += 1;
+            while pos < bytes.len() && is_word(bytes[pos]) {
+                pos += 1;
+            }
+            let token = if c == b'@' {
+                if pos == start + 1 {
+                    return Err(ExpressionError::Syntax {
+                        position: start,
+                        message: "expected a grid name after @".to_owned(),
+                    });
+                }
+                Token::Attribute(code[start + 1..pos].to_owned())
+            } else {
+                Token::Identifier(code[start..pos].to_owned())
+            };
+            tokens.push((token, start));
+        } else if let Some(punct) = PUNCTS.iter().find(|punct| code[pos..].starts_with(**punct)) {
+            pos += punct.len();
+            tokens.push((Token::Punct(punct), start));
+        } else {
+            return Err(ExpressionError::Syntax {
+                position: start,
+                message: format!(
+                    "unexpected character {:?}",
+                    code[pos..].chars().next().unwrap()
+                ),
+            });
+        }
+    }
+    tokens.push((Token::End, code.len()));
+    Ok(tokens)
+}
+
+/// Recursive descent parser that emits instructions while parsing.
+struct Compiler {
+    tokens: Vec<(Token, usize)>,
+    next: usize,
+    ops: Vec<Op>,
+    attributes: Vec<String>,
+    locals: Vec<String>,
+}
+
+impl Compiler {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.next].0
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.next].0.clone();
+        if token != Token::End {
+            self.next += 1;
+        }
+        token
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, ExpressionError> {
+        Err(ExpressionError::Syntax {
+            position: self.tokens[self.next].1,
+            message: message.into(),
+        })
+    }
+
+    fn accept(&mut self, punct: &'static str) -> bool {
+        let found = *self.peek() == Token::Punct(punct);
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, punct: &'static str) -> Result<(), ExpressionError> {
+        if self.accept(punct) {
+            Ok(())
+        } else {
+            self.error(format!("expected {punct}"))
+        }
+    }
+
+    fn attribute(&mut self, name: String) -> usize {
+        self.attributes
+            .iter()
+            .position(|attribute| *attribute == name)
+            .unwrap_or_else(|| {
+                self.attributes.push(name);
+                self.attributes.len() - 1
+            })
+    }
+
+    fn local(&self, name: &str) -> Option<usize> {
+        self.locals.iter().position(|local| local == name)
+    }
+
+    /// Emits a jump whose target is patched later, returning its position.
+    fn emit_jump(&mut self, op: fn(usize) -> Op) -> usize {
+        self.ops.push(op(usize::MAX));
+        self.ops.len() - 1
+    }
+
+    /// Points the jump at `at` to the next instruction.
+    fn patch(&mut self, at: usize) {
+        let target = self.ops.len();
+        self.ops[at] = match self.ops[at] {
+            Op::Jump(_) => Op::Jump(target),
+            Op::JumpIfZero(_) => Op::JumpIfZero(target),
+            op => op,
+        };
+    }
+
+    fn statement(&mut self) -> Result<(), ExpressionError> {
+        if self.accept(";") {
+            return Ok(());
+        }
+        if self.accept("{") {
+            while !self.accept("}") {
+                if *self.peek() == Token::End {
+                    return self.error("expected }");
+                }
+                self.statement()?;
+            }
+            return Ok(());
+        }
+        match self.advance() {
+            Token::Identifier(keyword) if keyword == "if" => {
+                self.expect("(")?;
+                self.expression()?;
+                self.expect(")")?;
+                let skip_then = self.emit_jump(Op::JumpIfZero);
+                self.statement()?;
+                if matches!(self.peek(), Token::Identifier(keyword) if keyword == "else") {
+                    self.next += 1;
+                    let skip_else = self.emit_jump(Op::Jump);
+                    self.patch(skip_then);
+                    self.statement()?;
+                    self.patch(skip_else);
+                } else {
+                    self.patch(skip_then);
+                }
+                Ok(())
+            }
+            Token::Identifier(keyword) if keyword == "float" => {
+                let Token::Identifier(name) = self.advance() else {
+                    self.next -= 1;
+                    return self.error("expected a variable name");
+                };
+                let local = self.local(&name).unwrap_or_else(|| {
+                    self.locals.push(name);
+                    self.locals.len() - 1
+                });
+                if self.accept("=") {
+                    self.expression()?;
+                } else {
+                    self.ops.push(Op::Push(0.0));
+                }
+                self.ops.push(Op::StoreLocal(local));
+                self.expect(";")
+            }
+            Token::Attribute(name) => {
+                let attribute = self.attribute(name);
+                self.assignment(Op::LoadAttribute(attribute), Op::StoreAttribute(attribute))
+            }
+            Token::Identifier(name) => {
+                let local = self.local(&name).unwrap_or_else(|| {
+                    self.locals.push(name);
+                    self.locals.len() - 1
+                });
+                self.assignment(Op::LoadLocal(local), Op::StoreLocal(local))
+            }
+            _ => {
+                self.next -= 1;
+                self.error("expected a statement")
+            }
+        }
+    }
+
+    /// Parses the rest of an assignment to the target read by `load` and written by `store`.
+    fn assignment(&mut self, load: Op, store: Op) -> Result<(), ExpressionError> {
+        let op = match self.advance() {
+            Token::Punct("=") => None,
+            Token::Punct("+=") => Some(BinaryOp::Add),
+            Token::Punct("-=") => Some(BinaryOp::Sub),
+            Token::Punct("*=") => Some(BinaryOp::Mul),
+            Token::Punct("/=") => Some(BinaryOp::Div),
+            _ => {
+                self.next -= 1;
+                return self.error("expected an assignment");
+            }
+        };
+        if let Some(op) = op {
+            self.ops.push(load);
+            self.expression()?;
+            self.ops.push(Op::Binary(op));
+        } else {
+            self.expression()?;
+        }
+        self.ops.push(store);
+        self.expect(";")
+    }
+
+    fn expression(&mut self) -> Result<(), ExpressionError> {
+        self.or()?;
+        if self.accept("?") {
+            let skip_then = self.emit_jump(Op::JumpIfZero);
+            self.expression()?;
+            self.expect(":")?;
+            let skip_else = self.emit_jump(Op::Jump);
+            self.patch(skip_then);
+            self.expression()?;
+            self.patch(skip_else);
+        }
+        Ok(())
+    }
+
+    fn or(&mut self) -> Result<(), ExpressionError> {
+        self.and()?;
+        while self.accept("||") {
+            let rhs = self.emit_jump(Op::JumpIfZero);
+            self.ops.push(Op::Push(1.0));
+            let end = self.emit_jump(Op::Jump);
+            self.patch(rhs);
+            self.and()?;
+            self.ops.extend([Op::Not, Op::Not]);
+            self.patch(end);
+        }
+        Ok(())
+    }
+
+    fn and(&mut self) -> Result<(), ExpressionError> {
+        self.binary(0)?;
+        while self.accept("&&") {
+            let short = self.emit_jump(Op::JumpIfZero);
+            self.binary(0)?;
+            self.ops.extend([Op::Not, Op::Not]);
+            let end = self.emit_jump(Op::Jump);
+            self.patch(short);
+            self.ops.push(Op::Push(0.0));
+            self.patch(end);
+        }
+        Ok(())
+    }
+
+    /// Left associative binary operators, from the loosest binding `level` on.
+    fn binary(&mut self, level: usize) -> Result<(), ExpressionError> {
+        const LEVELS: [&[(&str, BinaryOp)]; 4] = [
+            &[("==", BinaryOp::Eq), ("!=", BinaryOp::Ne)],
+            &[
+                ("<=", BinaryOp::Le),
+                (">=", BinaryOp::Ge),
+                ("<", BinaryOp::Lt),
+                (">", BinaryOp::Gt),
+            ],
+            &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+            &[
+                ("*", BinaryOp::Mul),
+                ("/", BinaryOp::Div),
+                ("%", BinaryOp::Rem),
+            ],
+        ];
+        let Some(operators) = LEVELS.get(level) else {
+            return self.unary();
+        };
+        self.binary(level + 1)?;
+        while let Some(&(_, op)) = operators
+            .iter()
+            .find(|(punct, _)| *self.peek() == Token::Punct(punct))
+        {
+            self.next += 1;
+            self.binary(level + 1)?;
+            self.ops.push(Op::Binary(op));
+        }
+        Ok(())
+    }
+
+    fn unary(&mut self) -> Result<(), ExpressionError> {
+        if self.accept("-") {
+            self.unary()?;
+            self.ops.push(Op::Neg);
+        } else if self.accept("!") {
+            self.unary()?;
+            self.ops.push(Op::Not);
+        } else if self.accept("+") {
+            self.unary()?;
+        } else {
+            self.primary()?;
+        }
+        Ok(())
+    }
+
+    fn primary(&mut self) -> Result<(), ExpressionError> {
+        match self.advance() {
+            Token::Number(value) => self.ops.push(Op::Push(value)),
+            Token::Attribute(name) => {
+                let attribute = self.attribute(name);
+                self.ops.push(Op::LoadAttribute(attribute));
+            }
+            Token::Punct("(") => {
+                self.expression()?;
+                self.expect(")")?;
+            }
+            Token::Identifier(name) if self.accept("(") => {
+                let mut arg_count = 0;
+                if !self.accept(")") {
+                    loop {
+                        self.expression()?;
+                        arg_count += 1;
+                        if self.accept(")") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                let axis = ["getcoordx", "getcoordy", "getcoordz"]
+                    .iter()
+                    .position(|function| *function == name);
+                let (op, expected) = if let Some(axis) = axis {
+                    (Op::Coord(axis), 0)
+                } else if let Some(function) = FUNCTIONS
+                    .iter()
+                    .position(|(function, ..)| *function == name)
+                {
+                    (Op::Call(function), FUNCTIONS[function].1)
+                } else {
+                    self.next -= 1;
+                    return self.error(format!("unknown function {name}"));
+                };
+                if arg_count != expected {
+                    self.next -= 1;
+                    return self.error(format!(
+                        "{name} takes {expected} arguments, not {arg_count}"
+                    ));
+                }
+                self.ops.push(op);
+            }
+            Token::Identifier(name) => match self.local(&name) {
+                Some(local) => self.ops.push(Op::LoadLocal(local)),
+                None => {
+                    self.next -= 1;
+                    return self.error(format!("unknown variable {name}"));
+                }
+            },
+            _ => {
+                self.next -= 1;
+                return self.error("expected an expression");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A compiled per voxel program in a small subset of the OpenVDB AX language, run over the active
+/// voxels of one or more `float` grids, see [`Grid::eval`] and [`Expression::eval`].
+///
+/// A program is a sequence of statements:
+/// - assignments to grids, `@density = @density * 2;`, with `=`, `+=`, `-=`, `*=` and `/=`,
+///   where `@name` is the value of the voxel in the bound grid called `name`
+/// - local variables, `float scale = 0.5;`, which start at zero for every voxel
+/// - `if (condition) statement else statement`, and `{ }` blocks
+///
+/// Expressions use the usual arithmetic, comparison and logical operators with C precedence,
+/// where zero is false and comparisons yield zero or one, the ternary `? :`, and the functions
+/// `abs`, `sign`, `sqrt`, `exp`, `log`, `sin`, `cos`, `tan`, `floor`, `ceil`, `round`, `pow`,
+/// `atan2`, `min`, `max`, `clamp`, `lerp`, `smoothstep`, `fit` and `getcoordx`, `getcoordy` and
+/// `getcoordz` for the index space coordinate of the voxel. Line comments start with `//`.
+///
+/// The program is compiled once to instructions for a stack machine, so evaluating it doesn't
+/// reparse the source for every voxel.
+#[derive(Clone, Debug)]
+pub struct Expression {
+    ops: Vec<Op>,
+    attributes: Vec<String>,
+    written: Vec<bool>,
+    local_count: usize,
+}
+
+impl Expression {
+    pub fn new(code: &str) -> Result<Self, ExpressionError> {
+        let mut compiler = Compiler {
+            tokens: tokenize(code)?,
+            next: 0,
+            ops: vec![],
+            attributes: vec![],
+            locals: vec![],
+        };
+        while *compiler.peek() != Token::End {
+            compiler.statement()?;
+        }
+        let written = (0..compiler.attributes.len())
+            .map(|attribute| compiler.ops.contains(&Op::StoreAttribute(attribute)))
+            .collect();
+        Ok(Self {
+            ops: compiler.ops,
+            attributes: compiler.attributes,
+            written,
+            local_count: compiler.locals.len(),
+        })
+    }
+
+    /// Names of the grids the program reads or writes, without the `@`.
+    pub fn grid_names(&self) -> &[String] {
+        &self.attributes
+    }
+
+    /// Runs the program on `coord`, with `values` holding the values of the attributes there.
+    fn run(&self, coord: IVec3, values: &mut [f32], locals: &mut [f32], stack: &mut Vec<f32>) {
+        locals.fill(0.0);
+        let mut next = 0;
+        while let Some(&op) = self.ops.get(next) {
+            next += 1;
+            match op {
+                Op::Push(value) => stack.push(value),
+                Op::LoadAttribute(attribute) => stack.push(values[attribute]),
+                Op::StoreAttribute(attribute) => values[attribute] = stack.pop().unwrap(),
+                Op::LoadLocal(local) => stack.push(locals[local]),
+                Op::StoreLocal(local) => locals[local] = stack.pop().unwrap(),
+                Op::Coord(axis) => stack.push(coord[axis] as f32),
+                Op::Neg => {
+                    let value = stack.pop().unwrap();
+                    stack.push(-value);
+                }
+                Op::Not => {
+                    let value = stack.pop().unwrap();
+                    stack.push((value == 0.0) as u8 as f32);
+                }
+                Op::Binary(op) => {
+                    let b = stack.pop().unwrap();
+                    let a = stack.pop().unwrap();
+                    stack.push(op.apply(a, b));
+                }
+                Op::Call(function) => {
+                    let (_, arg_count, f) = FUNCTIONS[function];
+                    let value = f(&stack[stack.len() - arg_count..]);
+                    stack.truncate(stack.len() - arg_count);
+                    stack.push(value);
+                }
+                Op::Jump(target) => next = target,
+                Op::JumpIfZero(target) => {
+                    if stack.pop().unwrap() == 0.0 {
+                        next = target;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Runs the program over `grids`, binding every `@name` to the grid whose descriptor has that
+    /// name. Like OpenVDB AX, the program runs once over the active voxels of every grid it
+    /// assigns to, in the order the grids appear in the program, and only keeps the assignments
+    /// to that grid, so the active topology of the grids doesn't change. Active tiles are
+    /// expanded into voxels.
+    pub fn eval<const L5: u32, const L4: u32, const L3: u32>(
+        &self,
+        grids: &mut [&mut Grid<f32, L5, L4, L3>],
+    ) -> Result<(), ExpressionError> {
+        let bindings = self
+            .attributes
+            .iter()
+            .map(|name| {
+                let mut matches = grids
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, grid)| grid.descriptor.name == *name)
+                    .map(|(idx, _)| idx);
+                match (matches.next(), matches.next()) {
+                    (Some(idx), None) => Ok(idx),
+                    (Some(_), Some(_)) => Err(ExpressionError::DuplicateGrid(name.clone())),
+                    (None, _) => Err(ExpressionError::UnknownGrid(name.clone())),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut values = vec![0.0; self.attributes.len()];
+        let mut locals = vec![0.0; self.local_count];
+        let mut stack = vec![];
+        for target in (0..self.attributes.len()).filter(|&attribute| self.written[attribute]) {
+            let mut writes = vec![];
+            {
+                let mut accessors = bindings
+                    .iter()
+                    .map(|&idx| grids[idx].tree.accessor())
+                    .collect::<Vec<_>>();
+                grids[bindings[target]].tree.for_each_active_voxel(|coord| {
+                    for (value, accessor) in values.iter_mut().zip(&mut accessors) {
+                        *value = accessor.get_value(coord);
+                    }
+                    self.run(coord, &mut values, &mut locals, &mut stack);
+                    writes.push((coord, values[target]));
+                });
+            }
+            let tree = &mut grids[bindings[target]].tree;
+            for (coord, value) in writes {
+                tree.set_value_on(coord, value);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<const L5: u32, const L4: u32, const L3: u32> Grid<f32, L5, L4, L3> {
+    /// Compiles and runs `code` over the active voxels of this grid, which `@name` refers to
+    /// when `name` is the name of the grid, e.g. `@density = clamp(@density * 2.0, 0, 1);`.
+    /// See [`Expression`] for the language, and [`Expression::eval`] to bind several grids.
+    pub fn eval(&mut self, code: &str) -> Result<(), ExpressionError> {
+        Expression::new(code)?.eval(&mut [self])
+    }
+}
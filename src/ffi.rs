@@ -0,0 +1,337 @@
+//! Stable C ABI for reading VDB files, so C, C++ and C# applications can use this crate as a
+//! decoder without linking OpenVDB. Build it as a shared library with
+//! `cargo rustc --release --features ffi --crate-type cdylib`.
+//!
+//! Files and grids are opaque handles that must be released with [`vdb_close`] and
+//! [`vdb_grid_free`]. Only `float` grids can be read. Grids can't be written back as VDB files,
+//! the only output is NanoVDB through [`vdb_grid_write_nanovdb`].
+//!
+//! Every function accepts null handles and output pointers. Failing calls return a null
+//! pointer, `-1`, `0` for [`vdb_grid_count`] or NaN for values, and [`vdb_last_error`]
+//! describes the failure. The matching C declarations are:
+//!
+//! ```c
+//! typedef struct VdbFile VdbFile;
+//! typedef struct VdbGrid VdbGrid;
+//!
+//! const char *vdb_last_error(void);
+//! VdbFile *vdb_open(const char *path);
+//! void vdb_close(VdbFile *file);
+//! size_t vdb_grid_count(const VdbFile *file);
+//! const char *vdb_grid_name(const VdbFile *file, size_t index);
+//! VdbGrid *vdb_read_grid(VdbFile *file, const char *name);
+//! void vdb_grid_free(VdbGrid *grid);
+//! float vdb_grid_value(const VdbGrid *grid, int32_t x, int32_t y, int32_t z);
+//! float vdb_grid_sample(const VdbGrid *grid, double x, double y, double z);
+//! int vdb_grid_active_bbox(const VdbGrid *grid, int32_t min[3], int32_t max[3]);
+//! int vdb_grid_index_to_world(const VdbGrid *grid, double matrix[16]);
+//! int vdb_grid_write_nanovdb(const VdbGrid *grid, const char *path);
+//! ```
+
+use crate::data_structure::Grid;
+use crate::nanovdb::write_nanovdb;
+use crate::reader::VdbReader;
+use crate::sampling::{BoxSampler, Sampler};
+
+use glam::{DVec3, IVec3};
+use std::cell::RefCell;
+use std::error::Error;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::fs::File;
+use std::io::BufReader;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap();
+    LAST_ERROR.with(|error| *error.borrow_mut() = Some(message));
+}
+
+/// Runs `f`, recording its error or panic for [`vdb_last_error`] and returning `failure`
+/// instead, as neither can cross the C ABI.
+fn call<T>(failure: T, f: impl FnOnce() -> Result<T, Box<dyn Error>>) -> T {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(error)) => {
+            set_last_error(error.to_string());
+            failure
+        }
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_owned());
+            set_last_error(format!("panicked: {message}"));
+            failure
+        }
+    }
+}
+
+/// The handle or output value `ptr` points to.
+///
+/// # Safety
+///
+/// `ptr` must be null or valid for the returned lifetime.
+unsafe fn deref<'a, T>(ptr: *const T) -> Result<&'a T, Box<dyn Error>> {
+    ptr.as_ref().ok_or_else(|| "null pointer".into())
+}
+
+/// The `len` values `ptr` points to.
+///
+/// # Safety
+///
+/// `ptr` must be null or point to `len` writable values that are valid for the returned
+/// lifetime.
+unsafe fn out_slice<'a, T>(ptr: *mut T, len: usize) -> Result<&'a mut [T], Box<dyn Error>> {
+    if ptr.is_null() {
+        return Err("null pointer".into());
+    }
+    Ok(std::slice::from_raw_parts_mut(ptr, len))
+}
+
+/// `path` as a UTF-8 string slice.
+///
+/// # Safety
+///
+/// `path` must be null or a valid nul terminated string.
+unsafe fn to_str<'a>(path: *const c_char) -> Result<&'a str, Box<dyn Error>> {
+    if path.is_null() {
+        return Err("null string".into());
+    }
+    Ok(CStr::from_ptr(path).to_str()?)
+}
+
+/// An open VDB file, see [`vdb_open`].
+pub struct VdbFile {
+    reader: VdbReader<BufReader<File>>,
+    /// Grid names in file order
+    names: Vec<CString>,
+}
+
+/// A `float` grid read from a [`VdbFile`], see [`vdb_read_grid`].
+pub struct VdbGrid(Grid<f32>);
+
+/// Message describing the last failed call on this thread, or null if none failed. The string
+/// stays valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn vdb_last_error() -> *const c_char {
+    LAST_ERROR.with(|error| {
+        error
+            .borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Opens the VDB file at `path` and reads its grid descriptors, returns null on failure.
+///
+/// # Safety
+///
+/// `path` must be null or a valid nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vdb_open(path: *const c_char) -> *mut VdbFile {
+    call(std::ptr::null_mut(), || {
+        let file = File::open(to_str(path)?)?;
+        let reader = VdbReader::new(BufReader::new(file))?;
+        let names = reader
+            .descriptors_in_file_order()
+            .into_iter()
+            .map(|gd| CString::new(gd.name.as_str()))
+            .collect::<Result<_, _>>()?;
+        Ok(Box::into_raw(Box::new(VdbFile { reader, names })))
+    })
+}
+
+/// Closes `file`, invalidating the grid names obtained from it. Grids read from it stay valid.
+///
+/// # Safety
+///
+/// `file` must be null or a handle returned by [`vdb_open`] that wasn't closed yet.
+#[no_mangle]
+pub unsafe extern "C" fn vdb_close(file: *mut VdbFile) {
+    if !file.is_null() {
+        drop(Box::from_raw(file));
+    }
+}
+
+/// Number of grids in `file`, `0` if `file` is null.
+///
+/// # Safety
+///
+/// `file` must be null or a valid handle returned by [`vdb_open`].
+#[no_mangle]
+pub unsafe extern "C" fn vdb_grid_count(file: *const VdbFile) -> usize {
+    call(0, || Ok(deref(file)?.names.len()))
+}
+
+/// Name of the grid at `index` in file order, or null if `index` is out of range. The string
+/// is owned by `file`.
+///
+/// # Safety
+///
+/// `file` must be null or a valid handle returned by [`vdb_open`].
+#[no_mangle]
+pub unsafe extern "C" fn vdb_grid_name(file: *const VdbFile, index: usize) -> *const c_char {
+    call(std::ptr::null(), || {
+        let name = deref(file)?
+            .names
+            .get(index)
+            .ok_or_else(|| format!("Grid index {index} is out of range"))?;
+        Ok(name.as_ptr())
+    })
+}
+
+/// Reads the `float` grid called `name` from `file`, returns null on failure, including when
+/// the grid holds another value type. Half precision grids are widened to `float`.
+///
+/// # Safety
+///
+/// `file` must be null or a valid handle returned by [`vdb_open`] and `name` null or a valid
+/// nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vdb_read_grid(file: *mut VdbFile, name: *const c_char) -> *mut VdbGrid {
+    call(std::ptr::null_mut(), || {
+        let reader = &mut file.as_mut().ok_or("null pointer")?.reader;
+        let name = to_str(name)?;
+        let grid_type = reader
+            .grid_descriptors
+            .get(name)
+            .map(|gd| gd.grid_type.as_str())
+            .ok_or_else(|| format!("Invalid grid name: {name}."))?;
+        if !grid_type.starts_with("Tree_float_") {
+            return Err(format!("Grid type {grid_type} isn't a float grid").into());
+        }
+        let grid = reader.read_grid::<f32>(name)?;
+        Ok(Box::into_raw(Box::new(VdbGrid(grid))))
+    })
+}
+
+/// Frees `grid`.
+///
+/// # Safety
+///
+/// `grid` must be null or a handle returned by [`vdb_read_grid`] that wasn't freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn vdb_grid_free(grid: *mut VdbGrid) {
+    if !grid.is_null() {
+        drop(Box::from_raw(grid));
+    }
+}
+
+/// Value of the voxel at index space coordinate `(x, y, z)`, NaN if `grid` is null.
+///
+/// # Safety
+///
+/// `grid` must be null or a valid handle returned by [`vdb_read_grid`].
+#[no_mangle]
+pub unsafe extern "C" fn vdb_grid_value(grid: *const VdbGrid, x: i32, y: i32, z: i32) -> f32 {
+    call(f32::NAN, || {
+        Ok(deref(grid)?.0.tree.get_value(IVec3::new(x, y, z)))
+    })
+}
+
+/// Trilinearly interpolated value at the world space position `(x, y, z)`, NaN if `grid` is
+/// null.
+///
+/// # Safety
+///
+/// `grid` must be null or a valid handle returned by [`vdb_read_grid`].
+#[no_mangle]
+pub unsafe extern "C" fn vdb_grid_sample(grid: *const VdbGrid, x: f64, y: f64, z: f64) -> f32 {
+    call(f32::NAN, || {
+        Ok(BoxSampler::sample(&deref(grid)?.0, DVec3::new(x, y, z)))
+    })
+}
+
+/// Writes the inclusive index space bounding box of the active voxels of `grid` to `min` and
+/// `max`, returns `-1` if the grid has no active voxels.
+///
+/// # Safety
+///
+/// `grid` must be null or a valid handle returned by [`vdb_read_grid`], `min` and `max` must be
+/// null or point to 3 writable integers each.
+#[no_mangle]
+pub unsafe extern "C" fn vdb_grid_active_bbox(
+    grid: *const VdbGrid,
+    min: *mut i32,
+    max: *mut i32,
+) -> c_int {
+    call(-1, || {
+        let bbox = deref(grid)?.0.tree.eval_active_voxel_bounding_box();
+        if bbox.is_empty() {
+            return Err("Grid has no active voxels".into());
+        }
+        out_slice(min, 3)?.copy_from_slice(&bbox.min.to_array());
+        out_slice(max, 3)?.copy_from_slice(&bbox.max.to_array());
+        Ok(0)
+    })
+}
+
+/// Writes the index to world matrix of `grid` to `matrix` in row major order, with positions
+/// as column vectors, returns `-1` if either is null.
+///
+/// # Safety
+///
+/// `grid` must be null or a valid handle returned by [`vdb_read_grid`] and `matrix` null or
+/// point to 16 writable doubles.
+#[no_mangle]
+pub unsafe extern "C" fn vdb_grid_index_to_world(grid: *const VdbGrid, matrix: *mut f64) -> c_int {
+    call(-1, || {
+        let transposed = deref(grid)?.0.transform.map.to_matrix().transpose();
+        out_slice(matrix, 16)?.copy_from_slice(&transposed.to_cols_array());
+        Ok(0)
+    })
+}
+
+/// Writes `grid` to a NanoVDB file at `path`, ready for GPU upload, returns `-1` on failure.
+///
+/// # Safety
+///
+/// `grid` must be null or a valid handle returned by [`vdb_read_grid`] and `path` null or a
+/// valid nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn vdb_grid_write_nanovdb(
+    grid: *const VdbGrid,
+    path: *const c_char,
+) -> c_int {
+    call(-1, || {
+        write_nanovdb(&deref(grid)?.0, to_str(path)?)?;
+        Ok(0)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn null_handles_fail() {
+        // SAFETY: every function accepts null pointers
+        unsafe {
+            assert!(vdb_open(std::ptr::null()).is_null());
+            assert!(!vdb_last_error().is_null());
+            assert_eq!(vdb_grid_count(std::ptr::null()), 0);
+            assert!(vdb_grid_name(std::ptr::null(), 0).is_null());
+            assert!(vdb_read_grid(std::ptr::null_mut(), std::ptr::null()).is_null());
+            assert!(vdb_grid_value(std::ptr::null(), 0, 0, 0).is_nan());
+            assert!(vdb_grid_sample(std::ptr::null(), 0.0, 0.0, 0.0).is_nan());
+            let mut bounds = [0; 3];
+            let min = bounds.as_mut_ptr();
+            assert_eq!(vdb_grid_active_bbox(std::ptr::null(), min, min), -1);
+            assert_eq!(
+                vdb_grid_index_to_world(std::ptr::null(), std::ptr::null_mut()),
+                -1
+            );
+            assert_eq!(
+                vdb_grid_write_nanovdb(std::ptr::null(), std::ptr::null()),
+                -1
+            );
+            vdb_close(std::ptr::null_mut());
+            vdb_grid_free(std::ptr::null_mut());
+        }
+    }
+}
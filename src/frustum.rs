@@ -0,0 +1,113 @@
+use crate::clip::Coverage;
+use crate::coordinates::CoordBBox;
+use crate::data_structure::Grid;
+use crate::render::Camera;
+use crate::transform::Transform;
+
+use glam::DVec3;
+
+/// World space view frustum of a [`Camera`] between a near and a far distance, used to clip
+/// grids to what the camera can see, see [`Grid::clip_to_frustum`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frustum {
+    /// Outward unit normals and offsets of the six bounding planes, a point `p` is inside if
+    /// `normal.dot(p) <= offset` for every plane
+    planes: [(DVec3, f64); 6],
+}
+
+impl Frustum {
+    /// Frustum of `camera` from `near` to `far` world units in front of the eye.
+    pub fn new(camera: &Camera, near: f64, far: f64) -> Self {
+        let forward = (camera.target - camera.eye).normalize();
+        let right = forward.cross(camera.up).normalize();
+        let up = right.cross(forward);
+        let half_height = (camera.fov_y.to_radians() * 0.5).tan();
+        let half_width = half_height * camera.width as f64 / camera.height as f64;
+
+        // Directions of the edges of the frustum, in order around the image
+        let edges = [(-1.0, 1.0), (1.0, 1.0), (1.0, -1.0), (-1.0, -1.0)]
+            .map(|(u, v)| forward + right * (u * half_width) + up * (v * half_height));
+        let side = |a: DVec3, b: DVec3| {
+            let mut normal = a.cross(b).normalize();
+            if normal.dot(forward) > 0.0 {
+                normal = -normal;
+            }
+            (normal, normal.dot(camera.eye))
+        };
+        Self {
+            planes: [
+                side(edges[0], edges[1]),
+                side(edges[1], edges[2]),
+                side(edges[2], edges[3]),
+                side(edges[3], edges[0]),
+                (-forward, -forward.dot(camera.eye + forward * near)),
+                (forward, forward.dot(camera.eye + forward * far)),
+            ],
+        }
+    }
+
+    /// Moves every plane outwards by `padding` world units, keeping things just outside the
+    /// view that may still cast shadows or be blurred into it.
+    pub fn with_padding(mut self, padding: f64) -> Self {
+        for (_, offset) in &mut self.planes {
+            *offset += padding;
+        }
+        self
+    }
+
+    /// Whether the world space `point` lies inside the frustum.
+    pub fn contains(&self, point: DVec3) -> bool {
+        self.planes
+            .iter()
+            .all(|&(normal, offset)| normal.dot(point) <= offset)
+    }
+
+    /// How the world space region covered by the index space `bbox` relates to the frustum.
+    /// Regions outside of one plane are outside, regions are reported as partially covered
+    /// whenever this can't be decided from the planes alone.
+    pub(crate) fn coverage(&self, bbox: &CoordBBox, transform: &Transform) -> Coverage {
+        let (min, max) = (bbox.min.as_dvec3() - 0.5, bbox.max.as_dvec3() + 0.5);
+        let corners = [0, 1, 2, 3, 4, 5, 6, 7].map(|corner| {
+            let select = |bit: i32, axis: usize| {
+                if corner & bit == 0 {
+                    min[axis]
+                } else {
+                    max[axis]
+                }
+            };
+            transform.index_to_world_f64(DVec3::new(select(1, 0), select(2, 1), select(4, 2)))
+        });
+        let mut inside = true;
+        for &(normal, offset) in &self.planes {
+            let outside = corners
+                .iter()
+                .filter(|&&corner| normal.dot(corner) > offset)
+                .count();
+            if outside == corners.len() {
+                return Coverage::Outside;
+            }
+            inside &= outside == 0;
+        }
+        if inside {
+            Coverage::Inside
+        } else {
+            Coverage::Partial
+        }
+    }
+}
+
+impl<ValueTy, const L5: u32, const L4: u32, const L3: u32> Grid<ValueTy, L5, L4, L3>
+where
+    ValueTy: Copy + PartialEq,
+{
+    /// Removes everything outside `frustum`, see [`Tree::clip`], so only the part of the grid
+    /// a camera can see needs to be kept around. Voxels that overlap the frustum are kept
+    /// whole.
+    ///
+    /// [`Tree::clip`]: crate::Tree::clip
+    pub fn clip_to_frustum(&mut self, frustum: &Frustum) {
+        let transform = &self.transform;
+        self.tree
+            .clip_with(|bbox| frustum.coverage(bbox, transform));
+    }
+}
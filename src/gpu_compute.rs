@@ -0,0 +1,454 @@
+use crate::coordinates::CoordBBox;
+use crate::data_structure::Grid;
+use crate::dense::Dense;
+use crate::nanovdb::to_nanovdb;
+use crate::transform::Transform;
+use crate::volume_to_mesh::triangle_table;
+
+use bytemuck::{cast_slice, Pod, Zeroable};
+use glam::{IVec3, UVec3, Vec3};
+use std::collections::{HashMap, HashSet};
+use wgpu::util::DeviceExt;
+
+/// Workgroup size of the one dimensional kernels.
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Uniforms shared by all kernels, see `Params` in `gpu_compute.wgsl`.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct Params {
+    min: [i32; 3],
+    isovalue: f32,
+    dims: [u32; 3],
+    count: u32,
+}
+
+/// A `float` grid uploaded to the GPU in the NanoVDB layout, see [`to_nanovdb`], ready to be
+/// processed by [`GpuKernels`].
+///
+/// The NanoVDB buffer is bound as a single storage buffer, so grids larger than the
+/// `max_storage_buffer_binding_size` limit of the device can't be uploaded.
+pub struct GpuGrid {
+    pub buffer: wgpu::Buffer,
+    pub transform: Transform,
+    /// Origins of the blocks of 8x8x8 cubes that touch a leaf, which are the cubes meshed by
+    /// [`GpuKernels::volume_to_mesh`]
+    blocks: Vec<IVec3>,
+}
+
+impl GpuGrid {
+    pub fn new(device: &wgpu::Device, grid: &Grid<f32>) -> Self {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("vdb_nanovdb"),
+            contents: &to_nanovdb(grid),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        // A cube belongs to the block of its lower corner, so the cubes with a corner in a leaf
+        // lie in the block of the leaf and the 7 blocks below it
+        let mut blocks = HashSet::<IVec3>::new();
+        for leaf in grid.tree.leaves() {
+            for corner in 0..8 {
+                let offset = IVec3::new(corner & 1, corner >> 1 & 1, corner >> 2 & 1);
+                blocks.insert(((leaf.origin >> 3) - offset) << 3);
+            }
+        }
+        let mut blocks = blocks.into_iter().collect::<Vec<_>>();
+        blocks.sort_unstable_by_key(|block| block.to_array());
+
+        Self {
+            buffer,
+            transform: grid.transform.clone(),
+            blocks,
+        }
+    }
+}
+
+/// Compute pipelines that convert grids to dense buffers and textures, and extract their
+/// surfaces, on the GPU. Every voxel or cube is handled by its own invocation, which is orders
+/// of magnitude faster than [`copy_to_dense`] and [`volume_to_mesh`] for production sized
+/// grids.
+///
+/// Results of the kernels are read back with blocking waits on the device, so avoid calling
+/// them from inside a frame of a renderer.
+///
+/// [`copy_to_dense`]: crate::copy_to_dense
+/// [`volume_to_mesh`]: crate::volume_to_mesh
+pub struct GpuKernels {
+    fill_dense: wgpu::ComputePipeline,
+    fill_texture: wgpu::ComputePipeline,
+    count_triangles: wgpu::ComputePipeline,
+    emit_triangles: wgpu::ComputePipeline,
+    triangle_table: wgpu::Buffer,
+}
+
+impl GpuKernels {
+    pub fn new(device: &wgpu::Device) -> Self {
+        // Triangles of every case padded to the case with the most of them, after their count
+        let table = triangle_table();
+        let stride = 1 + 3 * table.iter().map(Vec::len).max().unwrap_or(0);
+        let mut flat = vec![0u32; table.len() * stride];
+        for (case, triangles) in flat.chunks_mut(stride).zip(&table) {
+            case[0] = triangles.len() as u32;
+            for (out, &edge) in case[1..].iter_mut().zip(triangles.iter().flatten()) {
+                *out = edge as u32;
+            }
+        }
+        let triangle_table = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("vdb_triangle_table"),
+            contents: cast_slice(&flat),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        // The NanoVDB lookups are a Bevy shader module, resolve its imports and defs by hand
+        let nanovdb = include_str!("nanovdb_buffer.wgsl")
+            .replace("#define_import_path vdb_rs::nanovdb", "")
+            .replace("#{NANOVDB_GROUP}", "0")
+            .replace("#{NANOVDB_BINDING}", "0");
+        let kernels =
+            include_str!("gpu_compute.wgsl").replace("#{TABLE_STRIDE}", &stride.to_string());
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("vdb_gpu_compute"),
+            source: wgpu::ShaderSource::Wgsl(format!("{nanovdb}\n{kernels}").into()),
+        });
+        let pipeline = |entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: None,
+                module: &module,
+                entry_point,
+            })
+        };
+
+        Self {
+            fill_dense: pipeline("fill_dense"),
+            fill_texture: pipeline("fill_texture"),
+            count_triangles: pipeline("count_triangles"),
+            emit_triangles: pipeline("emit_triangles"),
+            triangle_table,
+        }
+    }
+
+    /// Copies the values of `grid` inside `bbox` into a dense block, like [`copy_to_dense`].
+    ///
+    /// [`copy_to_dense`]: crate::copy_to_dense
+    pub fn copy_to_dense(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        grid: &GpuGrid,
+        bbox: CoordBBox,
+    ) -> Dense<f32> {
+        let count = bbox.volume();
+        if bbox.is_empty() || count == 0 {
+            return Dense::new(bbox, 0.0);
+        }
+        let size = count * std::mem::size_of::<f32>() as u64;
+        let dense = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("vdb_dense"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let params = params_buffer(device, bbox.min, bbox.dim(), count as u32, 0.0);
+
+        let mut encoder = device.create_command_encoder(&Default::default());
+        dispatch_linear(
+            device,
+            &mut encoder,
+            &self.fill_dense,
+            grid,
+            &[(0, &params), (1, &dense)],
+            count as u32,
+        );
+        queue.submit(Some(encoder.finish()));
+        let data = read_buffer(device, queue, &dense, size);
+        Dense::from_data(bbox, cast_slice(&data).to_vec()).unwrap()
+    }
+
+    /// 3D texture in the `R32Float` format holding the values of `grid` inside `bbox`, filled
+    /// without leaving the GPU. Like `grid_to_image_3d`, texel `(x, y, z)` holds the voxel at
+    /// `bbox.min + (x, y, z)`. An empty `bbox` yields a single texel holding the background.
+    ///
+    /// The texture can be bound as a storage texture, sampled and copied from.
+    pub fn grid_to_texture_3d(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        grid: &GpuGrid,
+        bbox: CoordBBox,
+    ) -> wgpu::Texture {
+        let (min, dims) = if bbox.is_empty() {
+            // A single voxel far outside any tree holds the background
+            (IVec3::MIN, UVec3::ONE)
+        } else {
+            (bbox.min, bbox.dim())
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("vdb_texture_3d"),
+            size: wgpu::Extent3d {
+                width: dims.x,
+                height: dims.y,
+                depth_or_array_layers: dims.z,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: wgpu::TextureFormat::R32Float,
+            usage: wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&Default::default());
+        let params = params_buffer(device, min, dims, 0, 0.0);
+
+        let mut encoder = device.create_command_encoder(&Default::default());
+        {
+            let bind_groups = bind_groups(
+                device,
+                &self.fill_texture,
+                grid,
+                &[
+                    params.as_entire_binding(),
+                    wgpu::BindingResource::TextureView(&view),
+                ],
+                &[0, 2],
+            );
+            let mut pass = encoder.begin_compute_pass(&Default::default());
+            pass.set_pipeline(&self.fill_texture);
+            pass.set_bind_group(0, &bind_groups[0], &[]);
+            pass.set_bind_group(1, &bind_groups[1], &[]);
+            let groups = (dims + 3) / 4;
+            pass.dispatch_workgroups(groups.x, groups.y, groups.z);
+        }
+        queue.submit(Some(encoder.finish()));
+        texture
+    }
+
+    /// Extracts the `isovalue` surface of `grid` with marching cubes, like [`volume_to_mesh`],
+    /// returning the same positions and triangles up to their order.
+    ///
+    /// Only cubes with a corner inside a leaf are meshed, so surfaces that run between tiles
+    /// and the background are missed. Voxelize the active tiles first if those matter, see
+    /// [`Grid::voxelize_active_tiles`].
+    ///
+    /// [`volume_to_mesh`]: crate::volume_to_mesh
+    pub fn volume_to_mesh(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        grid: &GpuGrid,
+        isovalue: f32,
+    ) -> (Vec<Vec3>, Vec<[u32; 3]>) {
+        if grid.blocks.is_empty() {
+            return (vec![], vec![]);
+        }
+        let count = grid.blocks.len() as u32 * 512;
+        let blocks = grid
+            .blocks
+            .iter()
+            .map(|block| block.extend(0).to_array())
+            .collect::<Vec<_>>();
+        let blocks = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("vdb_mesh_blocks"),
+            contents: cast_slice(&blocks),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let counter = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("vdb_triangle_count"),
+            size: 4,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let params = params_buffer(device, IVec3::ZERO, UVec3::ZERO, count, isovalue);
+
+        // Count the triangles first so the vertex buffer can be sized exactly
+        let mut encoder = device.create_command_encoder(&Default::default());
+        dispatch_linear(
+            device,
+            &mut encoder,
+            &self.count_triangles,
+            grid,
+            &[
+                (0, &params),
+                (3, &blocks),
+                (4, &self.triangle_table),
+                (5, &counter),
+            ],
+            count,
+        );
+        queue.submit(Some(encoder.finish()));
+        let triangle_count = cast_slice::<u8, u32>(&read_buffer(device, queue, &counter, 4))[0];
+        if triangle_count == 0 {
+            return (vec![], vec![]);
+        }
+
+        let size = triangle_count as u64 * 3 * 5 * 4;
+        let vertices = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("vdb_mesh_vertices"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&Default::default());
+        encoder.clear_buffer(&counter, 0, None);
+        dispatch_linear(
+            device,
+            &mut encoder,
+            &self.emit_triangles,
+            grid,
+            &[
+                (0, &params),
+                (3, &blocks),
+                (4, &self.triangle_table),
+                (5, &counter),
+                (6, &vertices),
+            ],
+            count,
+        );
+        queue.submit(Some(encoder.finish()));
+        let data = read_buffer(device, queue, &vertices, size);
+
+        // Share vertices between triangles by the edge they lie on, like the CPU mesher
+        let mut positions = vec![];
+        let mut indices = HashMap::<(IVec3, u32), u32>::new();
+        let mut vertex = |words: &[u32]| {
+            let start = IVec3::new(words[0] as i32, words[1] as i32, words[2] as i32);
+            let axis = words[3];
+            *indices.entry((start, axis)).or_insert_with(|| {
+                let t = f32::from_bits(words[4]);
+                let index = start.as_vec3() + IVec3::AXES[axis as usize].as_vec3() * t;
+                positions.push(
+                    grid.transform
+                        .index_to_world_f64(index.as_dvec3())
+                        .as_vec3(),
+                );
+                positions.len() as u32 - 1
+            })
+        };
+        let triangles = cast_slice::<u8, u32>(&data)
+            .chunks_exact(15)
+            .map(|triangle| {
+                [
+                    vertex(&triangle[0..5]),
+                    vertex(&triangle[5..10]),
+                    vertex(&triangle[10..15]),
+                ]
+            })
+            .collect();
+        (positions, triangles)
+    }
+}
+
+fn params_buffer(
+    device: &wgpu::Device,
+    min: IVec3,
+    dims: UVec3,
+    count: u32,
+    isovalue: f32,
+) -> wgpu::Buffer {
+    let params = Params {
+        min: min.to_array(),
+        isovalue,
+        dims: dims.to_array(),
+        count,
+    };
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("vdb_compute_params"),
+        contents: bytemuck::bytes_of(&params),
+        usage: wgpu::BufferUsages::UNIFORM,
+    })
+}
+
+/// Bind groups 0 and 1 of `pipeline`, with the NanoVDB buffer of `grid` in group 0 and
+/// `resources` at `bindings` in group 1.
+fn bind_groups(
+    device: &wgpu::Device,
+    pipeline: &wgpu::ComputePipeline,
+    grid: &GpuGrid,
+    resources: &[wgpu::BindingResource<'_>],
+    bindings: &[u32],
+) -> [wgpu::BindGroup; 2] {
+    let nanovdb = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout: &pipeline.get_bind_group_layout(0),
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: grid.buffer.as_entire_binding(),
+        }],
+    });
+    let entries = bindings
+        .iter()
+        .zip(resources)
+        .map(|(&binding, resource)| wgpu::BindGroupEntry {
+            binding,
+            resource: resource.clone(),
+        })
+        .collect::<Vec<_>>();
+    let kernel = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout: &pipeline.get_bind_group_layout(1),
+        entries: &entries,
+    });
+    [nanovdb, kernel]
+}
+
+/// Records `pipeline` running once for each of `count` items, with the NanoVDB buffer of `grid`
+/// in group 0 and `buffers` at their bindings in group 1.
+fn dispatch_linear(
+    device: &wgpu::Device,
+    encoder: &mut wgpu::CommandEncoder,
+    pipeline: &wgpu::ComputePipeline,
+    grid: &GpuGrid,
+    buffers: &[(u32, &wgpu::Buffer)],
+    count: u32,
+) {
+    let (bindings, resources): (Vec<_>, Vec<_>) = buffers
+        .iter()
+        .map(|&(binding, buffer)| (binding, buffer.as_entire_binding()))
+        .unzip();
+    let bind_groups = bind_groups(device, pipeline, grid, &resources, &bindings);
+
+    let mut pass = encoder.begin_compute_pass(&Default::default());
+    pass.set_pipeline(pipeline);
+    pass.set_bind_group(0, &bind_groups[0], &[]);
+    pass.set_bind_group(1, &bind_groups[1], &[]);
+    let groups = count.div_ceil(WORKGROUP_SIZE);
+    let max_groups = device.limits().max_compute_workgroups_per_dimension;
+    pass.dispatch_workgroups(groups.min(max_groups), groups.div_ceil(max_groups), 1);
+}
+
+/// Copies the first `size` bytes of `buffer` back to the CPU, waiting for the GPU to finish.
+fn read_buffer(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    buffer: &wgpu::Buffer,
+    size: u64,
+) -> Vec<u8> {
+    let staging = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("vdb_readback"),
+        size,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&Default::default());
+    encoder.copy_buffer_to_buffer(buffer, 0, &staging, 0, size);
+    queue.submit(Some(encoder.finish()));
+
+    let slice = staging.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(wgpu::Maintain::Wait);
+    receiver
+        .recv()
+        .unwrap()
+        .expect("Failed to read back a compute buffer");
+    let data = slice.get_mapped_range().to_vec();
+    staging.unmap();
+    data
+}
@@ -0,0 +1,157 @@
+// Compute kernels of `GpuKernels`, appended to the `vdb_rs::nanovdb` lookups with the NanoVDB
+// buffer at group 0, binding 0. `TABLE_STRIDE` is substituted when the shader is built.
+
+struct Params {
+    // Lower corner of the box in index space
+    min: vec3<i32>,
+    isovalue: f32,
+    // Size of the box in voxels
+    dims: vec3<u32>,
+    // Number of voxels or cubes to process
+    count: u32,
+};
+
+@group(1) @binding(0)
+var<uniform> params: Params;
+
+@group(1) @binding(1)
+var<storage, read_write> dense: array<f32>;
+
+@group(1) @binding(2)
+var texture: texture_storage_3d<r32float, write>;
+
+// Origins of the blocks of 8x8x8 cubes to mesh
+@group(1) @binding(3)
+var<storage, read> blocks: array<vec4<i32>>;
+
+// For every case, the number of triangles followed by the edges of each triangle
+@group(1) @binding(4)
+var<storage, read> triangle_table: array<u32>;
+
+@group(1) @binding(5)
+var<storage, read_write> triangle_count: atomic<u32>;
+
+// Every vertex is the lower corner of its edge, the axis of the edge and where along the edge
+// the surface crosses it, as 5 words
+@group(1) @binding(6)
+var<storage, read_write> vertices: array<u32>;
+
+const TABLE_STRIDE: u32 = #{TABLE_STRIDE}u;
+
+// Linear index of the invocation, dispatches of more than 65535 workgroups wrap into rows.
+fn invocation_index(id: vec3<u32>, groups: vec3<u32>) -> u32 {
+    return id.x + id.y * groups.x * 64u;
+}
+
+@compute @workgroup_size(64)
+fn fill_dense(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+) {
+    let i = invocation_index(id, groups);
+    if i >= params.count {
+        return;
+    }
+    // `z` varies fastest, like `Dense`
+    let z = i % params.dims.z;
+    let y = i / params.dims.z % params.dims.y;
+    let x = i / (params.dims.z * params.dims.y);
+    dense[i] = nanovdb_get_value(params.min + vec3<i32>(vec3(x, y, z)));
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn fill_texture(@builtin(global_invocation_id) id: vec3<u32>) {
+    if any(id >= params.dims) {
+        return;
+    }
+    let value = nanovdb_get_value(params.min + vec3<i32>(id));
+    textureStore(texture, vec3<i32>(id), vec4(value, 0.0, 0.0, 1.0));
+}
+
+// Corner where `edge` starts, edges run along x, y and z in groups of 4 like `EDGES` on the CPU.
+fn edge_start(edge: u32) -> u32 {
+    let axis = edge / 4u;
+    let k = edge % 4u;
+    // The two bits of `k` select the corner along the other two axes
+    if axis == 0u {
+        return k << 1u;
+    }
+    if axis == 1u {
+        return (k & 1u) | ((k & 2u) << 1u);
+    }
+    return k;
+}
+
+fn corner_offset(corner: u32) -> vec3<i32> {
+    return vec3<i32>(vec3(corner & 1u, corner >> 1u & 1u, corner >> 2u & 1u));
+}
+
+struct Cube {
+    origin: vec3<i32>,
+    values: array<f32, 8>,
+    case_index: u32,
+};
+
+fn load_cube(i: u32) -> Cube {
+    let block = blocks[i / 512u].xyz;
+    let local = i % 512u;
+    var cube: Cube;
+    cube.origin = block + vec3<i32>(vec3(local >> 6u, local >> 3u & 7u, local & 7u));
+    cube.case_index = 0u;
+    for (var corner = 0u; corner < 8u; corner += 1u) {
+        cube.values[corner] = nanovdb_get_value(cube.origin + corner_offset(corner));
+        if cube.values[corner] < params.isovalue {
+            cube.case_index |= 1u << corner;
+        }
+    }
+    return cube;
+}
+
+@compute @workgroup_size(64)
+fn count_triangles(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+) {
+    let i = invocation_index(id, groups);
+    if i >= params.count {
+        return;
+    }
+    let cube = load_cube(i);
+    let count = triangle_table[cube.case_index * TABLE_STRIDE];
+    if count > 0u {
+        atomicAdd(&triangle_count, count);
+    }
+}
+
+@compute @workgroup_size(64)
+fn emit_triangles(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+) {
+    let i = invocation_index(id, groups);
+    if i >= params.count {
+        return;
+    }
+    var cube = load_cube(i);
+    let table = cube.case_index * TABLE_STRIDE;
+    let count = triangle_table[table];
+    if count == 0u {
+        return;
+    }
+    let first = atomicAdd(&triangle_count, count);
+    for (var triangle = 0u; triangle < count; triangle += 1u) {
+        for (var k = 0u; k < 3u; k += 1u) {
+            let edge = triangle_table[table + 1u + triangle * 3u + k];
+            let a = edge_start(edge);
+            let b = a | (1u << (edge / 4u));
+            let t = (params.isovalue - cube.values[a]) / (cube.values[b] - cube.values[a]);
+            let start = bitcast<vec3<u32>>(cube.origin + corner_offset(a));
+            let out = ((first + triangle) * 3u + k) * 5u;
+            vertices[out] = start.x;
+            vertices[out + 1u] = start.y;
+            vertices[out + 2u] = start.z;
+            vertices[out + 3u] = edge / 4u;
+            vertices[out + 4u] = bitcast<u32>(t);
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use crate::data_structure::{Grid, GridClass, GridDescriptor, GridValueType, MetadataType, Tree};
+use crate::transform::Transform;
+
+use glam::IVec3;
+
+/// Fluent construction of grids in memory, filling in the descriptor, transform and tree:
+///
+/// ```
+/// use vdb_rs::{Grid, GridBuilder, GridClass};
+///
+/// let grid: Grid<f32> = GridBuilder::new()
+///     .name("density")
+///     .class(GridClass::FogVolume)
+///     .voxel_size(0.1)
+///     .background(0.0)
+///     .with_values([(glam::IVec3::ZERO, 1.0), (glam::IVec3::X, 0.5)])
+///     .build();
+/// assert_eq!(grid.active_voxel_count(), 2);
+/// ```
+#[derive(Clone, Debug)]
+pub struct GridBuilder<ValueTy, const L5: u32 = 5, const L4: u32 = 4, const L3: u32 = 3> {
+    descriptor: GridDescriptor,
+    transform: Transform,
+    background: ValueTy,
+    /// Active voxels, set in order so later values win
+    values: Vec<(IVec3, ValueTy)>,
+}
+
+impl<ValueTy: GridValueType> GridBuilder<ValueTy> {
+    /// Builder for a grid with the standard 5-4-3 tree configuration, use
+    /// [`GridBuilder::default`] for other configurations.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<ValueTy: GridValueType, const L5: u32, const L4: u32, const L3: u32> Default
+    for GridBuilder<ValueTy, L5, L4, L3>
+{
+    /// Unnamed grid of unknown class with unit voxels and a default background.
+    fn default() -> Self {
+        let grid_type = Tree::<ValueTy, L5, L4, L3>::type_name(ValueTy::TYPE_NAME);
+        Self {
+            descriptor: GridDescriptor::new("", grid_type),
+            transform: Transform::default(),
+            background: ValueTy::default(),
+            values: vec![],
+        }
+    }
+}
+
+impl<ValueTy: GridValueType, const L5: u32, const L4: u32, const L3: u32>
+    GridBuilder<ValueTy, L5, L4, L3>
+{
+    pub fn name(mut self, name: &str) -> Self {
+        self.descriptor.name = name.into();
+        self
+    }
+
+    pub fn class(mut self, class: GridClass) -> Self {
+        self.descriptor.set_grid_class(class);
+        self
+    }
+
+    /// Uses cubic voxels of edge length `voxel_size`, see [`Transform::from_voxel_size`].
+    pub fn voxel_size(mut self, voxel_size: f64) -> Self {
+        self.transform = Transform::from_voxel_size(voxel_size);
+        self
+    }
+
+    pub fn transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    pub fn background(mut self, background: ValueTy) -> Self {
+        self.background = background;
+        self
+    }
+
+    /// Stores `value` under the metadata field `key`.
+    pub fn metadata<T: MetadataType>(mut self, key: &str, value: T) -> Self {
+        self.descriptor.meta_data.insert_typed(key, value);
+        self
+    }
+
+    /// Sets the voxels of `values` active, later values overwrite earlier ones at the same
+    /// coordinate.
+    pub fn with_values(mut self, values: impl IntoIterator<Item = (IVec3, ValueTy)>) -> Self {
+        self.values.extend(values);
+        self
+    }
+
+    pub fn build(self) -> Grid<ValueTy, L5, L4, L3> {
+        let mut tree = Tree::new(self.background);
+        for (coord, value) in self.values {
+            tree.set_value_on(coord, value);
+        }
+        Grid {
+            tree,
+            transform: self.transform,
+            descriptor: self.descriptor,
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use crate::coordinates::{CoordBBox, GlobalCoord, Index};
+use crate::data_structure::{Grid, Node, Node3, Node4, Node5, Tree, VdbLevel};
+use crate::ray::Ray;
+
+use glam::{DVec3, IVec3};
+
+/// Digital differential analyzer stepping a ray through a uniform grid of cubic cells, the
+/// classic Amanatides and Woo traversal. Cells are `dim` units wide and aligned to multiples of
+/// `dim`.
+#[derive(Clone, Copy, Debug)]
+struct Dda {
+    /// Lower corner of the current cell
+    cell: IVec3,
+    step: IVec3,
+    /// Ray parameter needed to cross one cell along each axis
+    delta: DVec3,
+    /// Ray parameter at which the ray crosses into the next cell along each axis
+    next: DVec3,
+    /// Ray parameter at which the ray entered the current cell
+    t: f64,
+    t1: f64,
+}
+
+impl Dda {
+    /// Starts at parameter `t0` of `ray`, in the cell clamped to lie within `bounds`.
+    fn new(ray: &Ray, t0: f64, t1: f64, dim: i32, bounds: &CoordBBox) -> Self {
+        let start = ray.at(t0);
+        let mut cell = (start / dim as f64).floor().as_ivec3() * dim;
+        cell = cell.clamp(bounds.min, bounds.max + 1 - dim);
+
+        let mut step = IVec3::ZERO;
+        let mut delta = DVec3::splat(f64::INFINITY);
+        let mut next = DVec3::splat(f64::INFINITY);
+        for axis in 0..3 {
+            let direction = ray.direction[axis];
+            if direction > 0.0 {
+                step[axis] = dim;
+                delta[axis] = dim as f64 / direction;
+                next[axis] = (cell[axis] + dim) as f64 - ray.origin[axis];
+                next[axis] /= direction;
+            } else if direction < 0.0 {
+                step[axis] = -dim;
+                delta[axis] = dim as f64 / -direction;
+                next[axis] = (cell[axis] as f64 - ray.origin[axis]) / direction;
+            }
+        }
+        Self {
+            cell,
+            step,
+            delta,
+            next,
+            t: t0,
+            t1,
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.t >= self.t1
+    }
+
+    /// Ray parameter at which the ray leaves the current cell.
+    fn exit(&self) -> f64 {
+        self.next.min_element().min(self.t1)
+    }
+
+    fn advance(&mut self) {
+        let axis = if self.next.x <= self.next.y && self.next.x <= self.next.z {
+            0
+        } else if self.next.y <= self.next.z {
+            1
+        } else {
+            2
+        };
+        self.t = self.next[axis];
+        self.cell[axis] += self.step[axis];
+        self.next[axis] += self.delta[axis];
+    }
+}
+
+/// Something active a ray passes through, along with the range of the ray parameter over
+/// which it does.
+#[derive(Clone, Copy, Debug)]
+pub enum HddaHit<'a, ValueTy, const L3: u32 = 3> {
+    /// Active tile at `level` covering `bbox`.
+    Tile {
+        level: VdbLevel,
+        bbox: CoordBBox,
+        value: ValueTy,
+        t0: f64,
+        t1: f64,
+    },
+    /// Leaf node with at least one active voxel, see [`Hdda::stop_at_leaves`].
+    Leaf {
+        node: &'a Node3<ValueTy, L3>,
+        t0: f64,
+        t1: f64,
+    },
+    /// Active voxel at `coord`.
+    Voxel {
+        coord: IVec3,
+        value: ValueTy,
+        t0: f64,
+        t1: f64,
+    },
+}
+
+enum Frame<'a, ValueTy, const L5: u32, const L4: u32, const L3: u32> {
+    Root,
+    Node5(&'a Node5<ValueTy, L5, L4, L3>),
+    Node4(&'a Node4<ValueTy, L4, L3>),
+    Leaf(&'a Node3<ValueTy, L3>),
+}
+
+/// Hierarchical DDA, stepping a ray through the tree one level at a time and yielding the
+/// active tiles and voxels it passes through in order along the ray, matching OpenVDB's
+/// `math::VolumeHDDA`.
+///
+/// Empty space is skipped at the coarsest level that represents it, so rays through sparse
+/// grids visit few cells. Voxels and nodes cover the index space region up to half a voxel
+/// around their integer coordinates, like in OpenVDB.
+pub struct Hdda<'a, ValueTy, const L5: u32 = 5, const L4: u32 = 4, const L3: u32 = 3> {
+    tree: &'a Tree<ValueTy, L5, L4, L3>,
+    /// Index space ray shifted by half a voxel, so cells start at integer coordinates
+    ray: Ray,
+    stack: Vec<(Frame<'a, ValueTy, L5, L4, L3>, Dda, CoordBBox)>,
+    stop_at_leaves: bool,
+}
+
+impl<'a, ValueTy, const L5: u32, const L4: u32, const L3: u32> Hdda<'a, ValueTy, L5, L4, L3> {
+    /// Traverses `tree` along the index space `ray` within its range.
+    pub fn new(tree: &'a Tree<ValueTy, L5, L4, L3>, ray: &Ray) -> Self {
+        let ray = Ray {
+            origin: ray.origin + 0.5,
+            ..*ray
+        };
+        let root_dim = Node5::<ValueTy, L5, L4, L3>::VOXEL_DIM as i32;
+        let mut bounds = CoordBBox::empty();
+        for node_5 in &tree.root_nodes {
+            bounds.expand_bbox(&CoordBBox::new(
+                node_5.origin,
+                node_5.origin + (root_dim - 1),
+            ));
+        }
+
+        let mut stack = vec![];
+        if !bounds.is_empty() {
+            let clipped = ray.clip(bounds.min.as_dvec3(), (bounds.max + 1).as_dvec3());
+            if let Some(clipped) = clipped {
+                let dda = Dda::new(&ray, clipped.t0, clipped.t1, root_dim, &bounds);
+                stack.push((Frame::Root, dda, bounds));
+            }
+        }
+        Self {
+            tree,
+            ray,
+            stack,
+            stop_at_leaves: false,
+        }
+    }
+
+    /// Yields leaf nodes as a whole instead of their active voxels, which is all volume
+    /// renderers need to find the spans of the ray worth marching through.
+    pub fn stop_at_leaves(mut self) -> Self {
+        self.stop_at_leaves = true;
+        self
+    }
+
+    fn push(&mut self, frame: Frame<'a, ValueTy, L5, L4, L3>, origin: IVec3, t0: f64, t1: f64) {
+        let (dim, child_dim) = match frame {
+            Frame::Root => unreachable!(),
+            Frame::Node5(_) => (
+                Node5::<ValueTy, L5, L4, L3>::VOXEL_DIM,
+                Node4::<ValueTy, L4, L3>::VOXEL_DIM,
+            ),
+            Frame::Node4(_) => (
+                Node4::<ValueTy, L4, L3>::VOXEL_DIM,
+                Node3::<ValueTy, L3>::VOXEL_DIM,
+            ),
+            Frame::Leaf(_) => (Node3::<ValueTy, L3>::VOXEL_DIM, 1),
+        };
+        let bounds = CoordBBox::new(origin, origin + (dim as i32 - 1));
+        let dda = Dda::new(&self.ray, t0, t1, child_dim as i32, &bounds);
+        self.stack.push((frame, dda, bounds));
+    }
+}
+
+impl<'a, ValueTy: Copy, const L5: u32, const L4: u32, const L3: u32> Iterator
+    for Hdda<'a, ValueTy, L5, L4, L3>
+{
+    type Item = HddaHit<'a, ValueTy, L3>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (frame, dda, bounds) = self.stack.last_mut()?;
+            if dda.is_done() || !bounds.is_inside(dda.cell) {
+                self.stack.pop();
+                continue;
+            }
+            let (cell, t0, t1) = (dda.cell, dda.t, dda.exit());
+            dda.advance();
+            if t1 <= t0 {
+                continue;
+            }
+
+            match *frame {
+                Frame::Root => {
+                    if let Some(node_5) = self.tree.root_node(cell) {
+                        self.push(Frame::Node5(node_5), node_5.origin, t0, t1);
+                    }
+                }
+                Frame::Node5(node_5) => {
+                    let offset = node_5.global_coord_to_offset(GlobalCoord(cell)).0;
+                    if let Some(node_4) = node_5.nodes.get(&offset) {
+                        self.push(Frame::Node4(node_4), cell, t0, t1);
+                    } else if node_5.value_mask[offset as usize] {
+                        return Some(HddaHit::Tile {
+                            level: VdbLevel::Node4,
+                            bbox: node_5.tile_bbox(Index(offset)),
+                            value: node_5.data[offset as usize],
+                            t0,
+                            t1,
+                        });
+                    }
+                }
+                Frame::Node4(node_4) => {
+                    let offset = node_4.global_coord_to_offset(GlobalCoord(cell)).0;
+                    if let Some(node_3) = node_4.nodes.get(&offset) {
+                        if node_3.value_mask.not_any() {
+                            continue;
+                        }
+                        if self.stop_at_leaves {
+                            return Some(HddaHit::Leaf {
+                                node: node_3,
+                                t0,
+                                t1,
+                            });
+                        }
+                        self.push(Frame::Leaf(node_3), cell, t0, t1);
+                    } else if node_4.value_mask[offset as usize] {
+                        return Some(HddaHit::Tile {
+                            level: VdbLevel::Node3,
+                            bbox: node_4.tile_bbox(Index(offset)),
+                            value: node_4.data[offset as usize],
+                            t0,
+                            t1,
+                        });
+                    }
+                }
+                Frame::Leaf(node_3) => {
+                    let offset = node_3.global_coord_to_offset(GlobalCoord(cell)).0 as usize;
+                    if node_3.value_mask[offset] {
+                        return Some(HddaHit::Voxel {
+                            coord: cell,
+                            value: node_3.buffer[offset],
+                            t0,
+                            t1,
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<ValueTy, const L5: u32, const L4: u32, const L3: u32> Tree<ValueTy, L5, L4, L3> {
+    /// Hierarchical traversal of the index space `ray` through this tree, see [`Hdda`].
+    pub fn hdda(&self, ray: &Ray) -> Hdda<'_, ValueTy, L5, L4, L3> {
+        Hdda::new(self, ray)
+    }
+}
+
+impl<ValueTy, const L5: u32, const L4: u32, const L3: u32> Grid<ValueTy, L5, L4, L3> {
+    /// Hierarchical traversal of the world space `ray` through this grid, see [`Hdda`]. Ray
+    /// parameters of the hits apply to `ray` as well as to its index space counterpart.
+    pub fn hdda(&self, ray: &Ray) -> Hdda<'_, ValueTy, L5, L4, L3> {
+        Hdda::new(&self.tree, &ray.world_to_index(&self.transform))
+    }
+}
@@ -0,0 +1,121 @@
+use crate::data_structure::{Grid, GridClass, GridDescriptor, Tree};
+use crate::dense::copy_to_dense;
+use crate::transform::Transform;
+
+use glam::IVec3;
+use image::{ImageBuffer, ImageError, ImageFormat, Luma};
+use std::path::{Path, PathBuf};
+
+/// The two axes spanning slices perpendicular to `axis`, as image columns and rows.
+fn slice_axes(axis: usize) -> (usize, usize) {
+    match axis {
+        0 => (1, 2),
+        1 => (0, 2),
+        2 => (0, 1),
+        _ => panic!("axis {axis} out of range, expected 0, 1 or 2"),
+    }
+}
+
+/// Builds a fog volume from a stack of 2D images, such as microscopy slices, where the image
+/// at `paths[z]` holds the voxels at index `z`, with cubic voxels of `voxel_size`.
+///
+/// Any image format supported by the `image` crate can be read, in particular PNG and TIFF.
+/// Pixels are converted to grayscale intensities in `[0, 1]`, and nonzero ones become active
+/// voxels. Image rows run downwards while `y` runs upwards, so the top row of every image holds
+/// the largest `y`, and slices show upright when exported with [`to_image_stack`].
+pub fn from_image_stack(
+    paths: &[impl AsRef<Path>],
+    voxel_size: f64,
+) -> Result<Grid<f32>, ImageError> {
+    let mut tree = Tree::new(0.0);
+    for (z, path) in paths.iter().enumerate() {
+        let image = image::open(path)?.to_luma32f();
+        let height = image.height() as i32;
+        for (x, y, pixel) in image.enumerate_pixels() {
+            if pixel.0[0] != 0.0 {
+                let coord = IVec3::new(x as i32, height - 1 - y as i32, z as i32);
+                tree.set_value_on(coord, pixel.0[0]);
+            }
+        }
+    }
+    let mut grid = Grid {
+        tree,
+        transform: Transform::from_voxel_size(voxel_size),
+        descriptor: GridDescriptor::new("", Tree::<f32>::type_name("float")),
+    };
+    grid.set_grid_class(GridClass::FogVolume);
+    Ok(grid)
+}
+
+/// Writes the active bounding box of `grid` to `dir` as 16-bit grayscale PNG images, one per
+/// index along `axis` (0 for x, 1 for y and 2 for z), returning their paths in order, see
+/// [`to_image_stack_with_format`].
+///
+/// # Panics
+///
+/// Panics if `axis` is larger than 2.
+pub fn to_image_stack(
+    grid: &Grid<f32>,
+    axis: usize,
+    dir: impl AsRef<Path>,
+) -> Result<Vec<PathBuf>, ImageError> {
+    to_image_stack_with_format(grid, axis, dir, ImageFormat::Png)
+}
+
+/// Writes the active bounding box of `grid` to `dir` as 16-bit grayscale images of `format`,
+/// one per index along `axis`, named `slice_0000`, `slice_0001` and so on, and returns their
+/// paths in order. `dir` is created if it doesn't exist.
+///
+/// Slices perpendicular to x span y and z, slices perpendicular to y span x and z, and slices
+/// perpendicular to z span x and y, with the first axis along the image columns and the second
+/// upwards along its rows. Values are mapped linearly from their smallest to their largest
+/// value inside the box to the full range of the image, so level sets and fog volumes alike
+/// can be inspected. Empty grids produce no images.
+///
+/// # Panics
+///
+/// Panics if `axis` is larger than 2.
+pub fn to_image_stack_with_format(
+    grid: &Grid<f32>,
+    axis: usize,
+    dir: impl AsRef<Path>,
+    format: ImageFormat,
+) -> Result<Vec<PathBuf>, ImageError> {
+    let (column_axis, row_axis) = slice_axes(axis);
+    let bbox = grid.tree.eval_active_voxel_bounding_box();
+    if bbox.is_empty() {
+        return Ok(vec![]);
+    }
+    let dir = dir.as_ref();
+    std::fs::create_dir_all(dir)?;
+
+    let dense = copy_to_dense(grid, bbox);
+    let min = dense.data.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = dense.data.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let scale = if max > min {
+        u16::MAX as f32 / (max - min)
+    } else {
+        0.0
+    };
+
+    let extension = format.extensions_str().first().copied().unwrap_or("img");
+    let (width, height) = (
+        (bbox.max[column_axis] - bbox.min[column_axis] + 1) as u32,
+        (bbox.max[row_axis] - bbox.min[row_axis] + 1) as u32,
+    );
+    let mut paths = vec![];
+    for (slice, index) in (bbox.min[axis]..=bbox.max[axis]).enumerate() {
+        let image = ImageBuffer::from_fn(width, height, |column, row| {
+            let mut coord = IVec3::ZERO;
+            coord[axis] = index;
+            coord[column_axis] = bbox.min[column_axis] + column as i32;
+            coord[row_axis] = bbox.max[row_axis] - row as i32;
+            let value = dense.get(coord).unwrap();
+            Luma([((value - min) * scale).round() as u16])
+        });
+        let path = dir.join(format!("slice_{slice:04}.{extension}"));
+        image.save_with_format(&path, format)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
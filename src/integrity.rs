@@ -0,0 +1,272 @@
+use crate::archive::ArchiveWriter;
+use crate::data_structure::{GridDescriptor, Metadata};
+use crate::reader::{ParseError, VdbReader};
+
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Metadata field holding the CRC32 checksum of a grid, see [`VdbReader::grid_checksum`].
+///
+/// Like the other `file_` fields it describes the grid as it is stored, so tools that rewrite
+/// the grid data without updating it make [`VdbReader::verify`] report a mismatch.
+pub const CHECKSUM_METADATA_KEY: &str = "file_crc32";
+
+impl Metadata {
+    /// Checksum stored under [`CHECKSUM_METADATA_KEY`], if any.
+    pub fn checksum(&self) -> Option<u32> {
+        self.get::<i64>(CHECKSUM_METADATA_KEY)
+            .and_then(|checksum| u32::try_from(checksum).ok())
+    }
+
+    pub fn set_checksum(&mut self, checksum: u32) {
+        self.insert_typed(CHECKSUM_METADATA_KEY, checksum as i64);
+    }
+}
+
+/// A problem found in a grid by [`VdbReader::verify`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IntegrityIssue {
+    /// The grid extends past the end of the file.
+    Truncated { end_pos: u64, file_len: u64 },
+    /// The checksum of the grid data doesn't match the one stored in its metadata.
+    ChecksumMismatch { stored: u32, computed: u32 },
+    /// Reading the grid failed, with the error message.
+    Unreadable(String),
+}
+
+/// Result of verifying a single grid, see [`VdbReader::verify`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GridIntegrity {
+    pub name: String,
+    /// Whether the grid has a checksum and it was compared
+    pub checksum_verified: bool,
+    /// Whether all of the values of the grid were read back. Only done for the value types and
+    /// tree configurations this crate reads, and not for instances of other grids.
+    pub values_read: bool,
+    pub issues: Vec<IntegrityIssue>,
+}
+
+/// Result of verifying an archive, with one entry per grid in file order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IntegrityReport {
+    pub grids: Vec<GridIntegrity>,
+}
+
+impl IntegrityReport {
+    /// Whether no issues were found. Grids without a checksum whose values can't be read back
+    /// are only checked for truncation, see [`IntegrityReport::fully_verified`].
+    pub fn is_ok(&self) -> bool {
+        self.grids.iter().all(|grid| grid.issues.is_empty())
+    }
+
+    /// Whether every grid was either checked against its checksum or read back completely.
+    pub fn fully_verified(&self) -> bool {
+        self.grids
+            .iter()
+            .all(|grid| grid.checksum_verified || grid.values_read)
+    }
+
+    /// Grids with at least one issue.
+    pub fn failed_grids(&self) -> impl Iterator<Item = &GridIntegrity> {
+        self.grids.iter().filter(|grid| !grid.issues.is_empty())
+    }
+}
+
+impl VdbReader<BufReader<File>> {
+    /// Opens the archive at `path` and verifies every grid in it, see [`VdbReader::verify`].
+    ///
+    /// Archives truncated before the end of their grid descriptors can't be opened and are
+    /// reported as an error.
+    pub fn verify_integrity(path: impl AsRef<Path>) -> Result<IntegrityReport, ParseError> {
+        VdbReader::new(BufReader::new(File::open(path)?))?.verify()
+    }
+}
+
+impl<R: Read + Seek> VdbReader<R> {
+    /// CRC32 checksum of the transform, topology and values of the grid `name`, everything
+    /// stored for the grid after its metadata. Renaming the grid or changing its metadata
+    /// doesn't change the checksum.
+    pub fn grid_checksum(&mut self, name: &str) -> Result<u32, ParseError> {
+        let gd = self
+            .grid_descriptors
+            .get(name)
+            .cloned()
+            .ok_or_else(|| ParseError::InvalidGridName(name.to_owned()))?;
+        self.checksum(&gd)
+    }
+
+    fn checksum(&mut self, gd: &GridDescriptor) -> Result<u32, ParseError> {
+        let parts = self.grid_parts(gd)?;
+        let mut crc = flate2::CrcWriter::new(std::io::sink());
+        self.copy_range(parts.head, &mut crc)?;
+        self.copy_range(parts.blocks, &mut crc)?;
+        Ok(crc.crc().sum())
+    }
+
+    /// Checks every grid for truncation, compares the checksums stored in their metadata, and
+    /// reads back the values of the grids whose type this crate supports, to catch truncated
+    /// or corrupted caches before they are used.
+    pub fn verify(&mut self) -> Result<IntegrityReport, ParseError> {
+        let file_len = self.reader.seek(SeekFrom::End(0))?;
+        let mut grids = vec![];
+        for gd in self.descriptors_in_file_order() {
+            let mut grid = GridIntegrity {
+                name: gd.name.to_string(),
+                checksum_verified: false,
+                values_read: false,
+                issues: vec![],
+            };
+            if gd.end_pos > file_len {
+                grid.issues.push(IntegrityIssue::Truncated {
+                    end_pos: gd.end_pos,
+                    file_len,
+                });
+                grids.push(grid);
+                continue;
+            }
+
+            if let Some(stored) = gd.meta_data.checksum() {
+                match self.checksum(&gd) {
+                    Ok(computed) if computed == stored => grid.checksum_verified = true,
+                    Ok(computed) => {
+                        grid.checksum_verified = true;
+                        grid.issues
+                            .push(IntegrityIssue::ChecksumMismatch { stored, computed });
+                    }
+                    Err(err) => grid
+                        .issues
+                        .push(IntegrityIssue::Unreadable(err.to_string())),
+                }
+            }
+
+            if gd.instance_parent.is_empty() {
+                match self.read_back(&gd) {
+                    Some(Ok(())) => grid.values_read = true,
+                    Some(Err(err)) => grid
+                        .issues
+                        .push(IntegrityIssue::Unreadable(err.to_string())),
+                    None => {}
+                }
+            }
+            grids.push(grid);
+        }
+        Ok(IntegrityReport { grids })
+    }
+
+    /// Reads all values of the grid `gd`, `None` if its type isn't supported.
+    fn read_back(&mut self, gd: &GridDescriptor) -> Option<Result<(), ParseError>> {
+        // Float grids saved at half precision carry a `_HalfFloat` suffix, and read as `f32`
+        let (value_type, suffix) = gd.grid_type.strip_prefix("Tree_")?.split_once("_5_4_3")?;
+        if !matches!(suffix, "" | "_HalfFloat") {
+            return None;
+        }
+        let name = &gd.name;
+        Some(match value_type {
+            "float" => self.read_grid::<f32>(name).map(drop),
+            "double" => self.read_grid::<f64>(name).map(drop),
+            "int32" => self.read_grid::<i32>(name).map(drop),
+            "int64" => self.read_grid::<i64>(name).map(drop),
+            "vec3s" => self.read_grid::<[f32; 3]>(name).map(drop),
+            "vec3d" => self.read_grid::<[f64; 3]>(name).map(drop),
+            "vec3i" => self.read_grid::<[i32; 3]>(name).map(drop),
+            _ => return None,
+        })
+    }
+
+    /// Copies this archive to `writer` with the checksum of every grid stored in its metadata,
+    /// so the copy can be checked with [`VdbReader::verify`]. The grid data is copied as is.
+    pub fn write_with_checksums(&mut self, writer: impl Write) -> Result<(), ParseError> {
+        let descriptors = self.descriptors_in_file_order();
+        let mut archive = ArchiveWriter::new(writer, &self.header, descriptors.len() as u32)?;
+        for gd in descriptors {
+            let mut descriptor = gd.clone();
+            descriptor.meta_data.set_checksum(self.checksum(&gd)?);
+            archive.copy_grid(self, &gd, &descriptor)?;
+        }
+        archive.finish()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::tests::{archive, archive_with};
+
+    use std::io::Cursor;
+
+    const GRIDS: [(&str, f32); 2] = [("a", 1.5), ("b", -2.0)];
+
+    fn with_checksums(mut file: Cursor<Vec<u8>>) -> Vec<u8> {
+        let mut bytes = vec![];
+        VdbReader::new(&mut file)
+            .unwrap()
+            .write_with_checksums(&mut bytes)
+            .unwrap();
+        bytes
+    }
+
+    fn verify(bytes: Vec<u8>) -> IntegrityReport {
+        VdbReader::new(Cursor::new(bytes))
+            .unwrap()
+            .verify()
+            .unwrap()
+    }
+
+    #[test]
+    fn checksummed_archive_verifies() {
+        for half_float in [false, true] {
+            let report = verify(with_checksums(archive_with(&GRIDS, half_float)));
+            assert!(report.is_ok(), "{report:?}");
+            assert!(report.fully_verified());
+            for (grid, (name, _)) in report.grids.iter().zip(GRIDS) {
+                assert_eq!(grid.name, name);
+                assert!(grid.checksum_verified);
+                assert!(grid.values_read, "{half_float}");
+            }
+        }
+    }
+
+    #[test]
+    fn flipped_byte_fails_checksum() {
+        let mut bytes = with_checksums(archive(&GRIDS));
+        let gd = VdbReader::new(Cursor::new(&bytes))
+            .unwrap()
+            .grid_descriptors["a"]
+            .clone();
+        // Last byte of the only value of "a"
+        bytes[gd.end_pos as usize - 1] ^= 0x40;
+
+        let report = verify(bytes);
+        let failed = report.failed_grids().collect::<Vec<_>>();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].name, "a");
+        assert!(matches!(
+            failed[0].issues[..],
+            [IntegrityIssue::ChecksumMismatch { stored, computed }] if stored != computed
+        ));
+    }
+
+    #[test]
+    fn truncated_grid_is_reported() {
+        let mut bytes = with_checksums(archive(&GRIDS));
+        let gd = VdbReader::new(Cursor::new(&bytes))
+            .unwrap()
+            .grid_descriptors["b"]
+            .clone();
+        bytes.truncate(gd.end_pos as usize - 2);
+        let file_len = bytes.len() as u64;
+
+        let report = verify(bytes);
+        assert!(!report.is_ok());
+        assert!(report.grids[0].issues.is_empty());
+        assert_eq!(
+            report.grids[1].issues,
+            [IntegrityIssue::Truncated {
+                end_pos: gd.end_pos,
+                file_len
+            }]
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use std::borrow::Borrow;
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Strings handed out by [`InternedStr::new`], with the table size after the last cleanup.
+struct InternTable {
+    strings: HashSet<Arc<str>>,
+    retained: usize,
+}
+
+fn table() -> &'static Mutex<InternTable> {
+    static TABLE: OnceLock<Mutex<InternTable>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        Mutex::new(InternTable {
+            strings: HashSet::new(),
+            retained: 0,
+        })
+    })
+}
+
+/// Immutable string shared through a process wide table, so the grid names, type names and
+/// metadata keys repeated across the grids of an archive are stored once, and cloning them is
+/// a reference count increment.
+///
+/// Strings are dropped from the table once the table has doubled in size since the last
+/// cleanup and no [`InternedStr`] refers to them anymore.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "bevy",
+    derive(bevy::reflect::Reflect),
+    reflect_value(Debug, PartialEq, Hash)
+)]
+pub struct InternedStr(Arc<str>);
+
+impl InternedStr {
+    /// The shared copy of `string`, adding it to the table if it isn't in there yet.
+    pub fn new(string: &str) -> Self {
+        let mut table = table().lock().unwrap();
+        if let Some(interned) = table.strings.get(string) {
+            return Self(interned.clone());
+        }
+
+        if table.strings.len() >= 2 * table.retained.max(64) {
+            table
+                .strings
+                .retain(|interned| Arc::strong_count(interned) > 1);
+            table.retained = table.strings.len();
+        }
+        let interned = Arc::<str>::from(string);
+        table.strings.insert(interned.clone());
+        Self(interned)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for InternedStr {
+    fn default() -> Self {
+        Self::new("")
+    }
+}
+
+impl Deref for InternedStr {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for InternedStr {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for InternedStr {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for InternedStr {
+    fn from(string: &str) -> Self {
+        Self::new(string)
+    }
+}
+
+impl From<String> for InternedStr {
+    fn from(string: String) -> Self {
+        Self::new(&string)
+    }
+}
+
+impl From<&String> for InternedStr {
+    fn from(string: &String) -> Self {
+        Self::new(string)
+    }
+}
+
+impl From<InternedStr> for String {
+    fn from(string: InternedStr) -> Self {
+        string.0.as_ref().to_owned()
+    }
+}
+
+impl PartialEq<str> for InternedStr {
+    fn eq(&self, other: &str) -> bool {
+        *self.0 == *other
+    }
+}
+
+impl PartialEq<&str> for InternedStr {
+    fn eq(&self, other: &&str) -> bool {
+        *self.0 == **other
+    }
+}
+
+impl PartialEq<String> for InternedStr {
+    fn eq(&self, other: &String) -> bool {
+        *self.0 == **other
+    }
+}
+
+impl fmt::Debug for InternedStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl fmt::Display for InternedStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&*self.0, f)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for InternedStr {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for InternedStr {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self::new(&<std::borrow::Cow<'de, str>>::deserialize(
+            deserializer,
+        )?))
+    }
+}
@@ -0,0 +1,237 @@
+use crate::data_structure::{Grid, GridClass, Tree};
+use crate::math_ops::DifferenceScheme;
+use crate::morphology::NearestNeighbors;
+
+use glam::{DVec3, IVec3, Vec3};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Marks every voxel and tile of `tree` as inactive, leaving values untouched.
+fn deactivate_all<ValueTy: Clone, const L5: u32, const L4: u32, const L3: u32>(
+    tree: &mut Tree<ValueTy, L5, L4, L3>,
+) {
+    for node_5 in &mut tree.root_nodes {
+        node_5.value_mask.fill(false);
+        for node_4 in node_5.nodes.values_mut() {
+            node_4.value_mask.fill(false);
+            for node_3 in node_4.nodes.values_mut() {
+                Arc::make_mut(node_3).value_mask.fill(false);
+            }
+        }
+    }
+}
+
+/// Point on a surface, with the surface normal there if it is known.
+#[derive(Clone, Copy)]
+struct SurfacePoint {
+    point: DVec3,
+    normal: DVec3,
+}
+
+impl SurfacePoint {
+    /// Distance from `position` to the tangent plane through this point, which is much closer
+    /// to the true distance to a curved surface than the distance to the point itself.
+    fn distance(&self, position: DVec3) -> f64 {
+        let offset = position - self.point;
+        if self.normal == DVec3::ZERO {
+            offset.length()
+        } else {
+            offset.dot(self.normal).abs()
+        }
+    }
+}
+
+/// Rebuilds `grid` into a proper signed distance field of its `isovalue` surface, with a narrow
+/// band of `half_width` voxels on either side. Values below `isovalue` are considered inside.
+///
+/// The surface is located at the crossings of `isovalue` between neighboring voxels, and every
+/// voxel of the new band receives the distance to the tangent plane at the closest crossing,
+/// found by propagating closest points outward from the surface. This fixes level sets whose
+/// values have drifted away from true distances, and converts fog volumes to level sets.
+///
+/// The result has a background of `half_width` voxels in world units and its class is set to
+/// [`GridClass::LevelSet`].
+pub fn level_set_rebuild<const L5: u32, const L4: u32, const L3: u32>(
+    grid: &Grid<f32, L5, L4, L3>,
+    isovalue: f32,
+    half_width: f32,
+) -> Grid<f32, L5, L4, L3> {
+    let background = half_width * grid.voxel_size().x;
+    let world = |coord: IVec3| grid.transform.index_to_world_f64(coord.as_dvec3());
+
+    // Closest point on the surface found so far for each voxel near the surface, along with the
+    // surface normal at that point
+    let mut closest = HashMap::<IVec3, SurfacePoint>::new();
+    let propose =
+        |closest: &mut HashMap<IVec3, SurfacePoint>, coord: IVec3, surface: SurfacePoint| {
+            let distance = world(coord).distance_squared(surface.point);
+            match closest.get(&coord) {
+                Some(current) if world(coord).distance_squared(current.point) <= distance => false,
+                _ => {
+                    closest.insert(coord, surface);
+                    true
+                }
+            }
+        };
+
+    // Seed the voxels on either side of every crossing of the isovalue between face neighbors
+    let face_offsets = NearestNeighbors::Face.offsets();
+    let mut accessor = grid.tree.accessor();
+    let mut crossings = vec![];
+    grid.tree.for_each_boundary_candidate(|coord| {
+        let value = accessor.get_value(coord);
+        for &offset in &face_offsets {
+            let neighbor = coord + offset;
+            let neighbor_value = accessor.get_value(neighbor);
+            if (value < isovalue) != (neighbor_value < isovalue) {
+                let t = ((isovalue - value) / (neighbor_value - value)) as f64;
+                let point = world(coord).lerp(world(neighbor), t);
+                crossings.push((coord, point));
+                crossings.push((neighbor, point));
+            }
+        }
+    });
+    let inv_dx = grid.transform.voxel_size().recip();
+    let mut frontier = vec![];
+    for (coord, point) in crossings {
+        let gradient =
+            Vec3::from([IVec3::X, IVec3::Y, IVec3::Z].map(|axis| {
+                DifferenceScheme::Central.first_derivative(&mut accessor, coord, axis)
+            }));
+        let normal = (gradient.as_dvec3() * inv_dx).normalize_or_zero();
+        if propose(&mut closest, coord, SurfacePoint { point, normal }) {
+            frontier.push(coord);
+        }
+    }
+
+    // Propagate closest points outward one layer at a time, until the band is covered
+    let offsets = NearestNeighbors::Vertex.offsets();
+    let max_distance = background as f64;
+    for _ in 0..half_width.ceil() as u32 + 1 {
+        let mut next = vec![];
+        for coord in frontier {
+            let surface = closest[&coord];
+            for &offset in &offsets {
+                let neighbor = coord + offset;
+                if world(neighbor).distance(surface.point) < max_distance
+                    && propose(&mut closest, neighbor, surface)
+                {
+                    next.push(neighbor);
+                }
+            }
+        }
+        next.sort_unstable_by_key(|coord| coord.to_array());
+        next.dedup();
+        frontier = next;
+    }
+
+    let mut tree = grid.tree.map_values(|value| {
+        if value < isovalue {
+            -background
+        } else {
+            background
+        }
+    });
+    deactivate_all(&mut tree);
+    for (coord, surface) in closest {
+        let distance = surface.distance(world(coord)) as f32;
+        if distance < background {
+            let inside = accessor.get_value(coord) < isovalue;
+            tree.set_value_on(coord, if inside { -distance } else { distance });
+        }
+    }
+    tree.prune_level_set();
+
+    let mut result = Grid {
+        tree,
+        transform: grid.transform.clone(),
+        descriptor: grid.descriptor.clone(),
+    };
+    result.set_grid_class(GridClass::LevelSet);
+    result
+}
+
+/// Grows the surface of the level set `grid` outward by the world space `distance`, or shrinks
+/// it for negative distances, keeping the width of its narrow band.
+///
+/// Offsets larger than half the band width are applied in several equal steps, re-tracking the
+/// narrow band with [`level_set_rebuild`] after each one so the surface never leaves the band.
+///
+/// # Panics
+///
+/// Panics if `distance` isn't finite, or if the background of `grid` isn't a positive finite
+/// distance.
+pub fn level_set_offset<const L5: u32, const L4: u32, const L3: u32>(
+    grid: &mut Grid<f32, L5, L4, L3>,
+    distance: f32,
+) {
+    let background = grid.tree.background;
+    assert!(distance.is_finite(), "level set offset must be finite");
+    assert!(
+        background.is_finite() && background > 0.0,
+        "level set background must be a positive distance"
+    );
+    let half_width = background / grid.voxel_size().x;
+    let max_step = background * 0.5;
+
+    let step_count = (distance.abs() / max_step).ceil() as u32;
+    let step = distance / step_count.max(1) as f32;
+    for _ in 0..step_count {
+        *grid = level_set_rebuild(&grid.map_values(|value| value - step), 0.0, half_width);
+    }
+}
+
+/// Converts the level set `grid` to a fog volume: values inside the narrow band ramp linearly
+/// from zero at the surface to one at the inner edge of the band, the remaining interior
+/// becomes one and the exterior becomes inactive zero.
+///
+/// The class of the result is set to [`GridClass::FogVolume`].
+pub fn sdf_to_fog_volume<const L5: u32, const L4: u32, const L3: u32>(
+    grid: &Grid<f32, L5, L4, L3>,
+) -> Grid<f32, L5, L4, L3> {
+    let cutoff = grid.tree.background;
+    let mut tree = grid
+        .tree
+        .map_values(|value| (-value / cutoff).clamp(0.0, 1.0));
+
+    // Everything with a non-zero density is active, everything else is inactive background
+    for node_5 in &mut tree.root_nodes {
+        for idx in 0..node_5.data.len() {
+            if !node_5.child_mask[idx] {
+                node_5.value_mask.set(idx, node_5.data[idx] > 0.0);
+            }
+        }
+        for node_4 in node_5.nodes.values_mut() {
+            for idx in 0..node_4.data.len() {
+                if !node_4.child_mask[idx] {
+                    node_4.value_mask.set(idx, node_4.data[idx] > 0.0);
+                }
+            }
+            for node_3 in node_4.nodes.values_mut() {
+                let node_3 = Arc::make_mut(node_3);
+                for idx in 0..node_3.buffer.len() {
+                    node_3.value_mask.set(idx, node_3.buffer[idx] > 0.0);
+                }
+            }
+        }
+    }
+    tree.prune_inactive();
+
+    let mut result = Grid {
+        tree,
+        transform: grid.transform.clone(),
+        descriptor: grid.descriptor.clone(),
+    };
+    result.set_grid_class(GridClass::FogVolume);
+    result
+}
+
+/// Converts the fog volume `grid` to a level set of its `isovalue` surface with a narrow band of
+/// `half_width` voxels, where densities above `isovalue` are inside, see [`level_set_rebuild`].
+pub fn fog_to_sdf<const L5: u32, const L4: u32, const L3: u32>(
+    grid: &Grid<f32, L5, L4, L3>,
+    isovalue: f32,
+    half_width: f32,
+) -> Grid<f32, L5, L4, L3> {
+    level_set_rebuild(&grid.map_values(|value| -value), -isovalue, half_width)
+}
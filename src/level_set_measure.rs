@@ -0,0 +1,87 @@
+use crate::data_structure::Grid;
+
+use glam::{DVec3, IVec3};
+use std::f64::consts::PI;
+
+/// Half width of the smeared Dirac delta function, in voxels.
+const DIRAC_WIDTH: f64 = 1.5;
+
+/// Measures of the surface of a level set, see [`level_set_measure`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Measure {
+    /// Surface area in world units squared
+    pub area: f64,
+    /// Enclosed volume in world units cubed
+    pub volume: f64,
+    /// Area weighted average of the mean curvature, half the sum of the principal curvatures,
+    /// which is positive for convex surfaces and `1 / r` for a sphere of radius `r`
+    pub avg_mean_curvature: f64,
+}
+
+/// Computes the surface area, enclosed volume and average mean curvature of the surface of the
+/// level set `grid` from its narrow band, matching OpenVDB's `tools::LevelSetMeasure`.
+///
+/// Surface integrals are evaluated over the active voxels with a smeared Dirac delta function
+/// of one and a half voxels on either side of the surface, and the volume follows from the
+/// divergence theorem, so the band needs to be at least two voxels wide and the surface
+/// closed. Voxels are assumed to be cubic.
+pub fn level_set_measure<const L5: u32, const L4: u32, const L3: u32>(
+    grid: &Grid<f32, L5, L4, L3>,
+) -> Measure {
+    let dx = grid.voxel_size().x as f64;
+    let epsilon = DIRAC_WIDTH * dx;
+    let voxel_volume = dx * dx * dx;
+
+    let mut area = 0.0;
+    let mut volume = 0.0;
+    let mut curvature = 0.0;
+    let mut accessor = grid.tree.accessor();
+    grid.tree.for_each_active_voxel(|coord| {
+        let mut f = |offset: IVec3| accessor.get_value(coord + offset) as f64;
+        let value = f(IVec3::ZERO);
+        if value.abs() >= epsilon {
+            return;
+        }
+        let dirac = (1.0 + (PI * value / epsilon).cos()) / (2.0 * epsilon);
+
+        let [x, y, z] = [IVec3::X, IVec3::Y, IVec3::Z];
+        let gradient = DVec3::new(f(x) - f(-x), f(y) - f(-y), f(z) - f(-z)) / (2.0 * dx);
+        let gradient_length = gradient.length();
+        if gradient_length <= f64::EPSILON {
+            return;
+        }
+        let weight = dirac * gradient_length * voxel_volume;
+        area += weight;
+        let position = grid.transform.index_to_world_f64(coord.as_dvec3());
+        volume += dirac * position.dot(gradient) * voxel_volume / 3.0;
+
+        // Mean curvature from the second derivatives, see `LevelSetFilter::mean_curvature`
+        let second = |f: &mut dyn FnMut(IVec3) -> f64, axis: IVec3| {
+            (f(axis) - 2.0 * value + f(-axis)) / (dx * dx)
+        };
+        let cross = |f: &mut dyn FnMut(IVec3) -> f64, a: IVec3, b: IVec3| {
+            (f(a + b) - f(a - b) - f(b - a) + f(-a - b)) / (4.0 * dx * dx)
+        };
+        let (dxx, dyy, dzz) = (second(&mut f, x), second(&mut f, y), second(&mut f, z));
+        let (dxy, dyz, dxz) = (
+            cross(&mut f, x, y),
+            cross(&mut f, y, z),
+            cross(&mut f, x, z),
+        );
+        let DVec3 {
+            x: gx,
+            y: gy,
+            z: gz,
+        } = gradient;
+        let divergence = (gx * gx * (dyy + dzz) + gy * gy * (dxx + dzz) + gz * gz * (dxx + dyy)
+            - 2.0 * (gx * gy * dxy + gy * gz * dyz + gx * gz * dxz))
+            / gradient_length.powi(3);
+        curvature += divergence * 0.5 * weight;
+    });
+
+    Measure {
+        area,
+        volume,
+        avg_mean_curvature: if area > 0.0 { curvature / area } else { 0.0 },
+    }
+}
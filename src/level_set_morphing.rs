@@ -0,0 +1,232 @@
+use crate::data_structure::Grid;
+use crate::level_set::level_set_rebuild;
+use crate::math_ops::DifferenceScheme;
+use crate::sampling::{BoxSampler, GridSampler};
+
+use glam::IVec3;
+
+/// Morphing of one level set into another, matching OpenVDB's `tools::LevelSetMorphing`.
+///
+/// The surface of the source level set moves along its normal with a speed equal to the signed
+/// distance to the surface of the target level set, shrinking where it lies outside the target
+/// and growing where it lies inside. Within the narrow band of the target a gap `d` between the
+/// surfaces closes at speed `d`, so it shrinks to `d * exp(-time)` after advecting by `time`.
+/// Farther out the speed is capped at the band width, so such gaps first close at that constant
+/// speed. [`LevelSetMorphing::morph`] hides this behind a fraction `t` from `0` to `1` of the
+/// substeps it takes the source to match the target, for shape interpolation.
+#[derive(Clone, Copy)]
+pub struct LevelSetMorphing<'a, const L5: u32 = 5, const L4: u32 = 4, const L3: u32 = 3> {
+    target: &'a Grid<f32, L5, L4, L3>,
+    cfl: f32,
+    tolerance: f32,
+}
+
+impl<'a, const L5: u32, const L4: u32, const L3: u32> LevelSetMorphing<'a, L5, L4, L3> {
+    /// Morphing toward the level set `target` with a CFL number of 0.5.
+    pub fn new(target: &'a Grid<f32, L5, L4, L3>) -> Self {
+        Self {
+            target,
+            cfl: 0.5,
+            tolerance: 0.1,
+        }
+    }
+
+    /// Limits every substep to moving the surface by at most `cfl` voxels, see
+    /// [`crate::LevelSetAdvection::with_cfl`].
+    pub fn with_cfl(mut self, cfl: f32) -> Self {
+        self.cfl = cfl.clamp(0.01, 1.0);
+        self
+    }
+
+    /// Distance in voxels between the surfaces at which [`LevelSetMorphing::morph`] considers
+    /// the source to match the target, 0.1 by default.
+    pub fn with_tolerance(mut self, tolerance: f32) -> Self {
+        self.tolerance = tolerance.max(0.0);
+        self
+    }
+
+    /// Morphs the level set `source` toward the target over the amount of `time`, returning
+    /// the number of substeps it took.
+    ///
+    /// Every substep is a first order upwind update of the active voxels of `source`, followed
+    /// by re-tracking its narrow band with [`level_set_rebuild`].
+    pub fn advect(&self, source: &mut Grid<f32, L5, L4, L3>, time: f32) -> u32 {
+        let voxel_size = source.voxel_size().x;
+        let max_speed = self.target.tree.background.abs();
+        let substeps = ((max_speed * time.abs() / (self.cfl * voxel_size)).ceil() as u32).max(1);
+        let dt = time / substeps as f32;
+        for _ in 0..substeps {
+            self.substep(source, dt);
+        }
+        substeps
+    }
+
+    /// Morphs the level set `source` a fraction `t` of the way to the target, from `0` leaving
+    /// it unchanged to `1` matching the target, returning the number of substeps it took.
+    ///
+    /// The substeps needed to match the target are counted first, by morphing a copy of
+    /// `source` until its surface lies within [`LevelSetMorphing::with_tolerance`] of the target
+    /// surface or stops getting closer, and `t` selects that fraction of them. As the surface
+    /// slows down near the target, `t = 0.5` usually covers much more than half of the way.
+    pub fn morph(&self, source: &mut Grid<f32, L5, L4, L3>, t: f32) -> u32 {
+        if t <= 0.0 {
+            return 0;
+        }
+        let substeps = (t.clamp(0.0, 1.0) * self.substeps_to_converge(source) as f32).round();
+        let dt = self.max_dt(source);
+        for _ in 0..substeps as u32 {
+            self.substep(source, dt);
+        }
+        substeps as u32
+    }
+
+    /// Number of substeps of [`LevelSetMorphing::morph`] it takes `source` to match the target.
+    pub fn substeps_to_converge(&self, source: &Grid<f32, L5, L4, L3>) -> u32 {
+        let dt = self.max_dt(source);
+        let band = self.target.tree.background.abs();
+        let tolerance = self.tolerance * source.voxel_size().x;
+
+        // Surfaces farther apart than the band approach each other at its width per unit of
+        // time, and can't be farther apart than the extent of both grids
+        let (min, max) = [source, self.target]
+            .map(|grid| {
+                let bbox = grid.eval_active_voxel_bounding_box();
+                (
+                    grid.transform.index_to_world_f64(bbox.min.as_dvec3()),
+                    grid.transform.index_to_world_f64(bbox.max.as_dvec3()),
+                )
+            })
+            .into_iter()
+            .reduce(|(min_a, max_a), (min_b, max_b)| (min_a.min(min_b), max_a.max(max_b)))
+            .unwrap();
+        let extent = (max - min).length() as f32;
+        let time = extent / band + (band / tolerance.max(f32::EPSILON)).ln().max(1.0);
+        let max_substeps = 2 * (time / dt).ceil() as u32;
+
+        let mut morphed = source.clone();
+        let mut distance = self.surface_distance(&morphed);
+        let mut substeps = 0;
+        while distance > tolerance && substeps < max_substeps {
+            self.substep(&mut morphed, dt);
+            substeps += 1;
+            let next = self.surface_distance(&morphed);
+            // Within the band the distance shrinks every substep, until the surfaces match as
+            // closely as the voxels allow
+            if next >= distance && next < band {
+                break;
+            }
+            distance = next;
+        }
+        substeps
+    }
+
+    /// Longest substep that keeps the surface moving at most `cfl` voxels.
+    fn max_dt(&self, source: &Grid<f32, L5, L4, L3>) -> f32 {
+        let max_speed = self.target.tree.background.abs().max(f32::EPSILON);
+        self.cfl * source.voxel_size().x / max_speed
+    }
+
+    /// Largest world space distance between the surfaces of `source` and the target, measured
+    /// at the zero crossings along the voxel edges of each in the other. Distances larger than
+    /// the narrow bands read as the band width.
+    fn surface_distance(&self, source: &Grid<f32, L5, L4, L3>) -> f32 {
+        fn distance_to<const L5: u32, const L4: u32, const L3: u32>(
+            from: &Grid<f32, L5, L4, L3>,
+            to: &Grid<f32, L5, L4, L3>,
+        ) -> f32 {
+            let mut accessor = from.tree.accessor();
+            let mut sampler = GridSampler::<BoxSampler, _, L5, L4, L3>::new(to);
+            let mut distance = 0.0f32;
+            from.tree.for_each_active_voxel(|coord| {
+                let value = accessor.get_value(coord);
+                for axis in [IVec3::X, IVec3::Y, IVec3::Z] {
+                    let next = accessor.get_value(coord + axis);
+                    if (value < 0.0) != (next < 0.0) {
+                        let crossing =
+                            coord.as_dvec3() + axis.as_dvec3() * (value / (value - next)) as f64;
+                        let world = from.transform.index_to_world_f64(crossing);
+                        distance = distance.max(sampler.sample(world).abs());
+                    }
+                }
+            });
+            distance
+        }
+        distance_to(source, self.target).max(distance_to(self.target, source))
+    }
+
+    /// Advances the surface of `source` by `dt`, see [`LevelSetMorphing::advect`].
+    fn substep(&self, source: &mut Grid<f32, L5, L4, L3>, dt: f32) {
+        let voxel_size = source.voxel_size().x;
+        let half_width = source.tree.background / voxel_size;
+        let mut target = GridSampler::<BoxSampler, _, L5, L4, L3>::new(self.target);
+        let snapshot = source.tree.clone();
+        let mut accessor = snapshot.accessor();
+        let tree = &mut source.tree;
+        snapshot.for_each_active_voxel(|coord| {
+            let world = source.transform.index_to_world_f64(coord.as_dvec3());
+            // Outward speed of the surface, moving it toward the zero crossing of the target
+            let speed = -target.sample(world);
+
+            // Godunov upwind approximation of the gradient magnitude
+            let mut gradient_length_squared = 0.0;
+            for axis in [IVec3::X, IVec3::Y, IVec3::Z] {
+                let backward =
+                    DifferenceScheme::Backward.first_derivative(&mut accessor, coord, axis);
+                let forward =
+                    DifferenceScheme::Forward.first_derivative(&mut accessor, coord, axis);
+                let (a, b) = if speed > 0.0 {
+                    (backward.max(0.0), forward.min(0.0))
+                } else {
+                    (forward.max(0.0), backward.min(0.0))
+                };
+                gradient_length_squared += a * a + b * b;
+            }
+            let gradient_length = gradient_length_squared.sqrt() / voxel_size;
+
+            let value = accessor.get_value(coord) - dt * speed * gradient_length;
+            tree.set_value_on(coord, value);
+        });
+        *source = level_set_rebuild(source, 0.0, half_width);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::create_level_set_box;
+    use crate::primitives::tests::sphere;
+
+    use glam::Vec3;
+
+    #[test]
+    fn sphere_morphs_into_box() {
+        let target: Grid<f32> = create_level_set_box(Vec3::splat(-0.6), Vec3::splat(0.6), 0.1, 3.0);
+        let morphing = LevelSetMorphing::new(&target);
+        let source = sphere(Vec3::ZERO);
+        let start = morphing.surface_distance(&source);
+        assert!(start >= 0.3, "{start}");
+
+        let mut unchanged = source.clone();
+        assert_eq!(morphing.morph(&mut unchanged, 0.0), 0);
+        assert_eq!(morphing.surface_distance(&unchanged), start);
+
+        let mut morphed = source.clone();
+        let substeps = morphing.morph(&mut morphed, 1.0);
+        let end = morphing.surface_distance(&morphed);
+        assert!(end < 0.1, "{end}");
+
+        let mut halfway = source.clone();
+        assert_eq!(
+            morphing.morph(&mut halfway, 0.5),
+            (substeps as f32 * 0.5).round() as u32
+        );
+        let distance = morphing.surface_distance(&halfway);
+        assert!(distance < start && distance > end, "{distance}");
+
+        // Centers of a face, an edge and a corner of the box
+        for coord in [IVec3::new(6, 0, 0), IVec3::new(0, -6, 6), IVec3::splat(6)] {
+            let value = morphed.tree.get_value(coord);
+            assert!(value.abs() < 0.1, "{coord}: {value}");
+        }
+    }
+}
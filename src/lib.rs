@@ -1,8 +1,176 @@
+mod archive;
+pub use archive::*;
+mod arena;
+pub use arena::*;
+#[cfg(feature = "bevy")]
+mod asset_loader;
+#[cfg(feature = "bevy")]
+pub use asset_loader::*;
+mod bake;
+pub use bake::*;
+#[cfg(feature = "bevy")]
+mod bake_plugin;
+#[cfg(feature = "bevy")]
+pub use bake_plugin::*;
+mod chunked;
+pub use chunked::*;
+mod clip;
+pub use clip::*;
+mod collider;
+pub use collider::*;
+mod combine;
+pub use combine::*;
+mod compression_report;
+pub use compression_report::*;
+mod convolution;
+pub use convolution::*;
 mod coordinates;
 pub use coordinates::*;
+mod csg;
+pub use csg::*;
 mod data_structure;
 pub use data_structure::*;
+mod dense;
+pub use dense::*;
+mod diagnostics;
+pub use diagnostics::*;
+mod diff;
+pub use diff::*;
+mod expression;
+pub use expression::*;
+mod fast_sweeping;
+pub use fast_sweeping::*;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod frustum;
+pub use frustum::*;
+#[cfg(feature = "wgpu")]
+mod gpu_compute;
+#[cfg(feature = "wgpu")]
+pub use gpu_compute::*;
+mod grid_builder;
+pub use grid_builder::*;
+mod hdda;
+pub use hdda::*;
+#[cfg(feature = "image")]
+mod image_stack;
+#[cfg(feature = "image")]
+pub use image_stack::*;
+mod integrity;
+pub use integrity::*;
+mod interning;
+pub use interning::*;
+mod json;
+mod level_set;
+pub use level_set::*;
+mod level_set_advection;
+pub use level_set_advection::*;
+mod level_set_filter;
+pub use level_set_filter::*;
+mod level_set_measure;
+pub use level_set_measure::*;
+mod level_set_morphing;
+pub use level_set_morphing::*;
+mod math_ops;
+pub use math_ops::*;
+#[cfg(feature = "bevy")]
+mod lod_streaming;
+#[cfg(feature = "bevy")]
+pub use lod_streaming::*;
+mod manifest;
+mod medical;
+pub use medical::*;
+mod merge;
+pub use merge::*;
+mod mesh_io;
+pub use mesh_io::*;
+mod mesh_to_volume;
+pub use mesh_to_volume::*;
+mod mipmap;
+pub use mipmap::*;
+mod morphology;
+pub use morphology::*;
+mod nanovdb;
+pub use nanovdb::*;
+#[cfg(feature = "bevy")]
+mod nanovdb_buffer;
+#[cfg(feature = "bevy")]
+pub use nanovdb_buffer::*;
+mod noise;
+pub use noise::*;
+mod npy;
+pub use npy::*;
+mod paging;
+pub use paging::*;
+mod par_slice;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod particles_to_sdf;
+pub use particles_to_sdf::*;
+mod poisson;
+pub use poisson::*;
+mod primitives;
+pub use primitives::*;
+#[cfg(feature = "python")]
+mod python;
+mod raw;
+pub use raw::*;
+mod ray;
+pub use ray::*;
+mod ray_intersector;
+pub use ray_intersector::*;
 mod reader;
 pub use reader::*;
+mod remote;
+pub use remote::*;
+mod render;
+pub use render::*;
+mod resample;
+pub use resample::*;
+mod salvage;
+pub use salvage::*;
+mod sampling;
+pub use sampling::*;
+mod scatter;
+pub use scatter::*;
+mod segmentation;
+pub use segmentation::*;
+#[cfg(feature = "bevy")]
+mod sequence;
+#[cfg(feature = "bevy")]
+pub use sequence::*;
+#[cfg(feature = "serde")]
+mod serde_mask;
+mod simd;
+mod soa;
+pub use soa::*;
+mod stats;
+pub use stats::*;
+#[cfg(feature = "bevy")]
+mod surface_mesh;
+#[cfg(feature = "bevy")]
+pub use surface_mesh::*;
+#[cfg(feature = "bevy")]
+mod texture_3d;
+#[cfg(feature = "bevy")]
+pub use texture_3d::*;
 mod transform;
 pub use transform::*;
+mod translate;
+#[cfg(feature = "bevy")]
+mod tree_gizmos;
+#[cfg(feature = "bevy")]
+pub use tree_gizmos::*;
+mod vector;
+mod visitor;
+pub use visitor::*;
+mod volume_advection;
+pub use volume_advection::*;
+#[cfg(feature = "bevy")]
+mod volume_render;
+#[cfg(feature = "bevy")]
+pub use volume_render::*;
+mod volume_to_mesh;
+pub use volume_to_mesh::*;
+mod vtk;
+pub use vtk::*;
@@ -0,0 +1,328 @@
+use crate::asset_loader::{VdbAsset, VdbAssetGrid, VdbPlugin};
+use crate::coordinates::CoordBBox;
+use crate::data_structure::{ArchiveHeader, Grid};
+use crate::mipmap::create_mipmaps;
+use crate::surface_mesh::VdbSurface;
+use crate::volume_render::VdbVolume;
+
+use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use futures_lite::future;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// Upper bound on the memory taken by the grids [`VdbLod`] hands to renderers, summed over all
+/// volumes. When the levels picked by distance exceed it, the farthest volumes are coarsened
+/// first. Defaults to 512 MiB.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct VdbLodBudget {
+    pub max_bytes: usize,
+}
+
+impl Default for VdbLodBudget {
+    fn default() -> Self {
+        Self {
+            max_bytes: 512 << 20,
+        }
+    }
+}
+
+/// Streams a `float` grid of a [`VdbAsset`] at a resolution depending on the distance to the
+/// active camera, see [`create_mipmaps`].
+///
+/// The mipmap pyramid is built on a background task once the asset has loaded. Level `n`, with
+/// voxels `2^n` times as large, is used from `2^n` times `lod_distance` away from the bounds of
+/// the grid, within the [`VdbLodBudget`]. With a `range`, only the part of the level within that
+/// distance of the camera is kept, and leaves falling out of range are unloaded as the camera
+/// moves. Distances are in the world space of the grid.
+///
+/// Every selection is clipped on a background task into a new asset holding just that grid,
+/// which replaces the asset of a [`VdbVolume`] or [`VdbSurface`] on the same entity once ready,
+/// so their grid names must match `grid`.
+#[derive(Component, Clone, Debug)]
+pub struct VdbLod {
+    pub asset: Handle<VdbAsset>,
+    pub grid: String,
+    /// Number of levels of the pyramid, including the full resolution grid
+    pub levels: u32,
+    /// Distance up to which the full resolution grid is used
+    pub lod_distance: f32,
+    /// Distance from the camera beyond which the grid is clipped away
+    pub range: Option<f32>,
+}
+
+impl VdbLod {
+    /// Streams `grid` of `asset` with 4 levels, switching to half resolution 50 units away.
+    pub fn new(asset: Handle<VdbAsset>, grid: impl Into<String>) -> Self {
+        Self {
+            asset,
+            grid: grid.into(),
+            levels: 4,
+            lod_distance: 50.0,
+            range: None,
+        }
+    }
+
+    pub fn with_levels(mut self, levels: u32) -> Self {
+        self.levels = levels.max(1);
+        self
+    }
+
+    pub fn with_lod_distance(mut self, lod_distance: f32) -> Self {
+        self.lod_distance = lod_distance;
+        self
+    }
+
+    pub fn with_range(mut self, range: f32) -> Self {
+        self.range = Some(range);
+        self
+    }
+}
+
+/// Mipmaps of a grid along with what's needed to pick and stream them.
+struct Pyramid {
+    levels: Vec<Grid<f32>>,
+    /// Memory taken by each level, see [`Grid::memory_usage`]
+    memory: Vec<usize>,
+    /// World space bounds of the active voxels of the full resolution grid
+    bounds: (Vec3, Vec3),
+    header: ArchiveHeader,
+}
+
+/// Level of the pyramid and the index space region of it that is streamed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct LodSelection {
+    level: u32,
+    region: Option<CoordBBox>,
+}
+
+#[derive(Component, Default)]
+struct LodState {
+    /// Asset, grid name and number of levels the pyramid was built from
+    source: Option<(Handle<VdbAsset>, String, u32)>,
+    /// Whether the source asset lacks the grid, until the source changes
+    missing_grid: bool,
+    pyramid: Option<Arc<Pyramid>>,
+    pyramid_task: Option<Task<Pyramid>>,
+    /// Selection shown, or being streamed if `stream_task` is set
+    selection: Option<LodSelection>,
+    stream_task: Option<Task<VdbAsset>>,
+    /// Asset last handed to renderers, dropping it unloads the previous selection
+    streamed: Option<Handle<VdbAsset>>,
+}
+
+fn build_pyramid(grid: Grid<f32>, levels: u32, header: ArchiveHeader) -> Pyramid {
+    let bbox = grid.eval_active_voxel_bounding_box();
+    let bounds = if bbox.is_empty() {
+        (Vec3::ZERO, Vec3::ZERO)
+    } else {
+        let (min, max) = (bbox.min.as_vec3() - 0.5, bbox.max.as_vec3() + 0.5);
+        (0..8)
+            .map(|corner| {
+                let index = Vec3::select(
+                    BVec3::new(corner & 1 != 0, corner & 2 != 0, corner & 4 != 0),
+                    max,
+                    min,
+                );
+                grid.transform.index_to_world(index)
+            })
+            .fold((Vec3::INFINITY, Vec3::NEG_INFINITY), |(lo, hi), p| {
+                (lo.min(p), hi.max(p))
+            })
+    };
+    let levels = create_mipmaps(&grid, levels);
+    let memory = levels.iter().map(Grid::memory_usage).collect();
+    Pyramid {
+        levels,
+        memory,
+        bounds,
+        header,
+    }
+}
+
+/// Index space region of `grid` within `range` of `center`, both in world space.
+fn region_around(grid: &Grid<f32>, center: Vec3, range: f32) -> CoordBBox {
+    let (min, max) = (center - range, center + range);
+    let (lo, hi) = (0..8)
+        .map(|corner| {
+            let world = Vec3::select(
+                BVec3::new(corner & 1 != 0, corner & 2 != 0, corner & 4 != 0),
+                max,
+                min,
+            );
+            grid.transform.world_to_index(world)
+        })
+        .fold((Vec3::INFINITY, Vec3::NEG_INFINITY), |(lo, hi), p| {
+            (lo.min(p), hi.max(p))
+        });
+    CoordBBox::new(lo.floor().as_ivec3(), hi.ceil().as_ivec3())
+}
+
+/// A volume whose pyramid is ready, with the level picked for it.
+struct LodCandidate {
+    entity: Entity,
+    /// Camera position in the world space of the grid
+    camera: Vec3,
+    distance: f32,
+    level: u32,
+    pyramid: Arc<Pyramid>,
+}
+
+#[allow(clippy::type_complexity)]
+fn stream_lods(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<VdbAsset>>,
+    budget: Res<VdbLodBudget>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    mut lods: Query<(
+        Entity,
+        &VdbLod,
+        Option<&mut LodState>,
+        Option<&GlobalTransform>,
+        Option<&mut VdbVolume>,
+        Option<&mut VdbSurface>,
+    )>,
+    mut assets: ResMut<Assets<VdbAsset>>,
+) {
+    let changed_assets = events
+        .iter()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { handle } => Some(handle.clone()),
+            AssetEvent::Created { .. } | AssetEvent::Removed { .. } => None,
+        })
+        .collect::<HashSet<_>>();
+    let camera = cameras
+        .iter()
+        .find(|(camera, _)| camera.is_active)
+        .map(|(_, transform)| transform.translation());
+
+    // Build pyramids and pick levels by distance
+    let mut candidates = vec![];
+    for (entity, lod, state, to_scene, _, _) in &mut lods {
+        let Some(mut state) = state else {
+            commands.entity(entity).insert(LodState::default());
+            continue;
+        };
+        let source = (lod.asset.clone(), lod.grid.clone(), lod.levels.max(1));
+        if state.source.as_ref() != Some(&source) || changed_assets.contains(&lod.asset) {
+            *state = LodState {
+                source: Some(source),
+                streamed: state.streamed.take(),
+                ..default()
+            };
+        }
+        if state.pyramid.is_none() && state.pyramid_task.is_none() && !state.missing_grid {
+            let Some(asset) = assets.get(&lod.asset) else {
+                // Not loaded yet
+                continue;
+            };
+            let Some(grid) = asset.float_grid(&lod.grid) else {
+                log::warn!("No float grid named {} to stream", lod.grid);
+                state.missing_grid = true;
+                continue;
+            };
+            let (grid, levels, header) = (grid.clone(), lod.levels.max(1), asset.header.clone());
+            state.pyramid_task = Some(
+                AsyncComputeTaskPool::get()
+                    .spawn(async move { build_pyramid(grid, levels, header) }),
+            );
+        }
+        if let Some(task) = &mut state.pyramid_task {
+            if let Some(pyramid) = future::block_on(future::poll_once(task)) {
+                state.pyramid = Some(Arc::new(pyramid));
+                state.pyramid_task = None;
+            }
+        }
+        let (Some(pyramid), Some(camera)) = (&state.pyramid, camera) else {
+            continue;
+        };
+        let to_grid = to_scene.copied().unwrap_or_default().affine().inverse();
+        let camera = to_grid.transform_point3(camera);
+        let (min, max) = pyramid.bounds;
+        let distance = camera.clamp(min, max).distance(camera);
+        let level = (distance / lod.lod_distance).max(1.0).log2().floor() as u32;
+        candidates.push(LodCandidate {
+            entity,
+            camera,
+            distance,
+            level: level.min(pyramid.levels.len() as u32 - 1),
+            pyramid: pyramid.clone(),
+        });
+    }
+
+    // Coarsen the farthest volumes until the selection fits the budget
+    let mut total = candidates
+        .iter()
+        .map(|candidate| candidate.pyramid.memory[candidate.level as usize])
+        .sum::<usize>();
+    candidates.sort_by(|a, b| b.distance.total_cmp(&a.distance));
+    for candidate in &mut candidates {
+        let pyramid = &candidate.pyramid;
+        while total > budget.max_bytes && (candidate.level as usize) + 1 < pyramid.levels.len() {
+            total -= pyramid.memory[candidate.level as usize];
+            candidate.level += 1;
+            total += pyramid.memory[candidate.level as usize];
+        }
+    }
+
+    // Stream the selections that changed and hand finished ones to the renderers
+    for candidate in candidates {
+        let Ok((_, lod, Some(mut state), _, volume, surface)) = lods.get_mut(candidate.entity)
+        else {
+            continue;
+        };
+        let pyramid = candidate.pyramid;
+        let level = candidate.level;
+        let region = lod.range.map(|range| {
+            // Snap the region to a quarter of the range, so it isn't clipped again every frame
+            let snap = range / 4.0;
+            let center = (candidate.camera / snap).round() * snap;
+            region_around(&pyramid.levels[level as usize], center, range + snap)
+        });
+        let selection = LodSelection { level, region };
+
+        if let Some(task) = &mut state.stream_task {
+            let Some(asset) = future::block_on(future::poll_once(task)) else {
+                continue;
+            };
+            state.stream_task = None;
+            let handle = assets.add(asset);
+            if let Some(mut volume) = volume {
+                volume.asset = handle.clone();
+            }
+            if let Some(mut surface) = surface {
+                surface.asset = handle.clone();
+            }
+            state.streamed = Some(handle);
+        }
+        if state.selection == Some(selection) {
+            continue;
+        }
+        state.selection = Some(selection);
+        let name = lod.grid.clone();
+        state.stream_task = Some(AsyncComputeTaskPool::get().spawn(async move {
+            let mut grid = pyramid.levels[level as usize].clone();
+            if let Some(region) = &region {
+                grid.clip(region);
+            }
+            VdbAsset {
+                header: pyramid.header.clone(),
+                grids: HashMap::from([(name, VdbAssetGrid::Float(grid))]),
+            }
+        }));
+    }
+}
+
+/// Streams the [`VdbLod`]s, add [`crate::VdbVolumePlugin`] or [`crate::VdbSurfacePlugin`] to
+/// display them and insert a [`VdbLodBudget`] to change the default budget.
+pub struct VdbLodPlugin;
+
+impl Plugin for VdbLodPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<VdbPlugin>() {
+            app.add_plugins(VdbPlugin);
+        }
+        app.init_resource::<VdbLodBudget>()
+            .add_systems(Update, stream_lods);
+    }
+}
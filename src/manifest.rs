@@ -0,0 +1,144 @@
+use crate::data_structure::{GridDescriptor, Metadata, MetadataValue};
+use crate::json::{json_array, json_string};
+use crate::reader::{ParseError, VdbReader};
+
+use std::io::{Read, Seek};
+
+/// `value` as a JSON number, or `null` for infinities and NaN which JSON can't represent.
+fn json_number(value: impl Into<f64>) -> String {
+    let value = value.into();
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "null".to_owned()
+    }
+}
+
+fn metadata_value_json(value: &MetadataValue) -> String {
+    match value {
+        MetadataValue::String(value) => json_string(value),
+        MetadataValue::Vec3i(value) => json_array(value.to_array()),
+        MetadataValue::I32(value) => value.to_string(),
+        MetadataValue::I64(value) => value.to_string(),
+        MetadataValue::Float(value) => json_number(*value),
+        MetadataValue::Bool(value) => value.to_string(),
+        // The payload of unknown types isn't meaningful without their definition
+        MetadataValue::Unknown { name, data } => format!(
+            "{{\"type\": {}, \"size\": {}}}",
+            json_string(name),
+            data.len()
+        ),
+    }
+}
+
+/// `metadata` as a JSON object with sorted keys, nested at `indent` spaces.
+fn metadata_json(metadata: &Metadata, indent: usize) -> String {
+    if metadata.0.is_empty() {
+        return "{}".to_owned();
+    }
+    let mut entries = metadata.0.iter().collect::<Vec<_>>();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    let padding = " ".repeat(indent + 2);
+    let fields = entries
+        .into_iter()
+        .map(|(name, value)| {
+            format!(
+                "{padding}{}: {}",
+                json_string(name),
+                metadata_value_json(value)
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("{{\n{}\n{}}}", fields.join(",\n"), " ".repeat(indent))
+}
+
+fn or_null(value: Option<String>) -> String {
+    value.unwrap_or_else(|| "null".to_owned())
+}
+
+impl<R: Read + Seek> VdbReader<R> {
+    /// Describes the archive and every grid in it as a JSON document, for indexing large VDB
+    /// libraries in asset catalogs. Only the grid headers and transforms are read, no voxel
+    /// data is loaded.
+    ///
+    /// The document holds the `file_version`, `library_version` and `uuid` of the archive, its
+    /// `metadata`, and a `grids` array in file order, each with:
+    ///
+    /// - `name`, `type`, `class` and `instance_parent`, `null` for grids that aren't instances
+    /// - `bbox_min`, `bbox_max`, `voxel_count` and `mem_bytes`, as recorded by the writer of the
+    ///   file, `null` when missing
+    /// - `voxel_size` and `index_to_world`, the row major 4x4 matrix of the grid transform
+    /// - `metadata`, all metadata of the grid
+    ///
+    /// Metadata values of types this crate doesn't know are described by their `type` name and
+    /// byte `size`. Metadata keys are sorted, so manifests of the same file are identical.
+    pub fn to_manifest_json(&mut self) -> Result<String, ParseError> {
+        let mut descriptors = self.grid_descriptors.values().cloned().collect::<Vec<_>>();
+        descriptors.sort_by_key(|gd| gd.grid_pos);
+
+        let mut grids = vec![];
+        for gd in &descriptors {
+            grids.push(self.grid_manifest_json(gd)?);
+        }
+        let grids = if grids.is_empty() {
+            "[]".to_owned()
+        } else {
+            format!("[\n{}\n  ]", grids.join(",\n"))
+        };
+
+        Ok(format!(
+            concat!(
+                "{{\n",
+                "  \"file_version\": {},\n",
+                "  \"library_version\": \"{}.{}\",\n",
+                "  \"uuid\": {},\n",
+                "  \"metadata\": {},\n",
+                "  \"grids\": {}\n",
+                "}}\n"
+            ),
+            self.header.file_version,
+            self.header.library_version_major,
+            self.header.library_version_minor,
+            json_string(&self.header.guid),
+            metadata_json(&self.header.meta_data, 2),
+            grids,
+        ))
+    }
+
+    fn grid_manifest_json(&mut self, gd: &GridDescriptor) -> Result<String, ParseError> {
+        let transform = self.read_grid_transform(&gd.name)?;
+        let matrix = transform.map.to_matrix();
+        let rows = (0..4).flat_map(|row| (0..4).map(move |column| matrix.col(column)[row]));
+        let instance_parent =
+            (!gd.instance_parent.is_empty()).then(|| json_string(&gd.instance_parent));
+
+        Ok(format!(
+            concat!(
+                "    {{\n",
+                "      \"name\": {},\n",
+                "      \"type\": {},\n",
+                "      \"class\": {},\n",
+                "      \"instance_parent\": {},\n",
+                "      \"bbox_min\": {},\n",
+                "      \"bbox_max\": {},\n",
+                "      \"voxel_count\": {},\n",
+                "      \"mem_bytes\": {},\n",
+                "      \"voxel_size\": {},\n",
+                "      \"index_to_world\": {},\n",
+                "      \"metadata\": {}\n",
+                "    }}"
+            ),
+            json_string(&gd.name),
+            json_string(&gd.grid_type),
+            json_string(gd.grid_class().name()),
+            or_null(instance_parent),
+            or_null(gd.aabb_min().ok().map(|min| json_array(min.to_array()))),
+            or_null(gd.aabb_max().ok().map(|max| json_array(max.to_array()))),
+            or_null(gd.voxel_count().ok().map(|count| count.to_string())),
+            or_null(gd.mem_bytes().ok().map(|bytes| bytes.to_string())),
+            json_array(transform.voxel_size().to_array().map(json_number)),
+            json_array(rows.map(json_number)),
+            metadata_json(&gd.meta_data, 6),
+        ))
+    }
+}
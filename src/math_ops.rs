@@ -0,0 +1,284 @@
+use crate::data_structure::{Grid, GridValueType, Tree};
+use crate::sampling::ValueAccessor;
+
+use glam::{IVec3, Vec3};
+use std::ops::{Add, Mul};
+
+/// Finite-difference stencil used to approximate derivatives.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DifferenceScheme {
+    /// Second-order central difference over the two face neighbors.
+    #[default]
+    Central,
+    /// First-order one-sided difference towards the positive neighbor.
+    Forward,
+    /// First-order one-sided difference towards the negative neighbor.
+    Backward,
+    /// Fourth-order central difference over two neighbors on each side.
+    FourthOrderCentral,
+}
+
+impl DifferenceScheme {
+    /// Derivative along the unit `axis` at `coord`, in index space.
+    pub(crate) fn first_derivative<ValueTy, const L5: u32, const L4: u32, const L3: u32>(
+        self,
+        accessor: &mut ValueAccessor<'_, ValueTy, L5, L4, L3>,
+        coord: IVec3,
+        axis: IVec3,
+    ) -> ValueTy
+    where
+        ValueTy: Copy + Add<Output = ValueTy> + Mul<f32, Output = ValueTy>,
+    {
+        let mut f = |offset: i32| accessor.get_value(coord + axis * offset);
+        match self {
+            DifferenceScheme::Central => (f(1) + f(-1) * -1.0) * 0.5,
+            DifferenceScheme::Forward => f(1) + f(0) * -1.0,
+            DifferenceScheme::Backward => f(0) + f(-1) * -1.0,
+            DifferenceScheme::FourthOrderCentral => {
+                (f(-2) + f(-1) * -8.0 + f(1) * 8.0 + f(2) * -1.0) * (1.0 / 12.0)
+            }
+        }
+    }
+
+    /// Second derivative along the unit `axis` at `coord`, in index space. The one-sided
+    /// schemes have no second derivative of their own and use the central stencil.
+    pub(crate) fn second_derivative<ValueTy, const L5: u32, const L4: u32, const L3: u32>(
+        self,
+        accessor: &mut ValueAccessor<'_, ValueTy, L5, L4, L3>,
+        coord: IVec3,
+        axis: IVec3,
+    ) -> ValueTy
+    where
+        ValueTy: Copy + Add<Output = ValueTy> + Mul<f32, Output = ValueTy>,
+    {
+        let mut f = |offset: i32| accessor.get_value(coord + axis * offset);
+        match self {
+            DifferenceScheme::Central | DifferenceScheme::Forward | DifferenceScheme::Backward => {
+                f(-1) + f(0) * -2.0 + f(1)
+            }
+            DifferenceScheme::FourthOrderCentral => {
+                (f(-2) * -1.0 + f(-1) * 16.0 + f(0) * -30.0 + f(1) * 16.0 + f(2) * -1.0)
+                    * (1.0 / 12.0)
+            }
+        }
+    }
+}
+
+/// Builds a tree with the active topology of `tree`, where the value of every active voxel is
+/// computed by `f`. Active tiles are expanded into voxels.
+fn map_active_voxels<ValueTy, OutTy, const L5: u32, const L4: u32, const L3: u32>(
+    tree: &Tree<ValueTy, L5, L4, L3>,
+    background: OutTy,
+    mut f: impl FnMut(&mut ValueAccessor<'_, ValueTy, L5, L4, L3>, IVec3) -> OutTy,
+) -> Tree<OutTy, L5, L4, L3>
+where
+    ValueTy: Copy,
+    OutTy: Copy,
+{
+    let mut result = Tree::new(background);
+    let mut accessor = tree.accessor();
+    tree.for_each_active_voxel(|coord| {
+        let value = f(&mut accessor, coord);
+        result.set_value_on(coord, value);
+    });
+    result
+}
+
+const AXES: [IVec3; 3] = [IVec3::X, IVec3::Y, IVec3::Z];
+
+fn gradient<const L5: u32, const L4: u32, const L3: u32>(
+    tree: &Tree<f32, L5, L4, L3>,
+    scheme: DifferenceScheme,
+    inv_dx: Vec3,
+) -> Tree<Vec3, L5, L4, L3> {
+    map_active_voxels(tree, Vec3::ZERO, |accessor, coord| {
+        Vec3::from(AXES.map(|axis| scheme.first_derivative(accessor, coord, axis))) * inv_dx
+    })
+}
+
+fn laplacian<ValueTy, const L5: u32, const L4: u32, const L3: u32>(
+    tree: &Tree<ValueTy, L5, L4, L3>,
+    scheme: DifferenceScheme,
+    inv_dx: Vec3,
+) -> Tree<ValueTy, L5, L4, L3>
+where
+    ValueTy: Copy + Add<Output = ValueTy> + Mul<f32, Output = ValueTy>,
+{
+    map_active_voxels(tree, tree.background * 0.0, |accessor, coord| {
+        let [x, y, z] = AXES.map(|axis| scheme.second_derivative(accessor, coord, axis));
+        x * (inv_dx.x * inv_dx.x) + y * (inv_dx.y * inv_dx.y) + z * (inv_dx.z * inv_dx.z)
+    })
+}
+
+fn divergence<const L5: u32, const L4: u32, const L3: u32>(
+    tree: &Tree<Vec3, L5, L4, L3>,
+    scheme: DifferenceScheme,
+    inv_dx: Vec3,
+) -> Tree<f32, L5, L4, L3> {
+    map_active_voxels(tree, 0.0, |accessor, coord| {
+        let [x, y, z] = AXES.map(|axis| scheme.first_derivative(accessor, coord, axis));
+        x.x * inv_dx.x + y.y * inv_dx.y + z.z * inv_dx.z
+    })
+}
+
+fn curl<const L5: u32, const L4: u32, const L3: u32>(
+    tree: &Tree<Vec3, L5, L4, L3>,
+    scheme: DifferenceScheme,
+    inv_dx: Vec3,
+) -> Tree<Vec3, L5, L4, L3> {
+    map_active_voxels(tree, Vec3::ZERO, |accessor, coord| {
+        let [dx, dy, dz] = AXES.map(|axis| scheme.first_derivative(accessor, coord, axis));
+        let (dx, dy, dz) = (dx * inv_dx.x, dy * inv_dx.y, dz * inv_dx.z);
+        Vec3::new(dy.z - dz.y, dz.x - dx.z, dx.y - dy.x)
+    })
+}
+
+fn closest_point_transform<const L5: u32, const L4: u32, const L3: u32>(
+    grid: &Grid<f32, L5, L4, L3>,
+) -> Tree<Vec3, L5, L4, L3> {
+    let inv_dx = grid.voxel_size().recip();
+    map_active_voxels(&grid.tree, Vec3::ZERO, |accessor, coord| {
+        let distance = accessor.get_value(coord);
+        let gradient = Vec3::from(
+            AXES.map(|axis| DifferenceScheme::Central.first_derivative(accessor, coord, axis)),
+        ) * inv_dx;
+        let world = grid
+            .transform
+            .index_to_world_f64(coord.as_dvec3())
+            .as_vec3();
+        world - distance * gradient.normalize_or_zero()
+    })
+}
+
+impl<const L5: u32, const L4: u32, const L3: u32> Tree<f32, L5, L4, L3> {
+    /// Index space gradient at every active voxel, active tiles are expanded into voxels.
+    pub fn gradient(&self, scheme: DifferenceScheme) -> Tree<Vec3, L5, L4, L3> {
+        gradient(self, scheme, Vec3::ONE)
+    }
+}
+
+impl<const L5: u32, const L4: u32, const L3: u32> Tree<Vec3, L5, L4, L3> {
+    /// Index space divergence at every active voxel, active tiles are expanded into voxels.
+    pub fn divergence(&self, scheme: DifferenceScheme) -> Tree<f32, L5, L4, L3> {
+        divergence(self, scheme, Vec3::ONE)
+    }
+
+    /// Index space curl at every active voxel, active tiles are expanded into voxels.
+    pub fn curl(&self, scheme: DifferenceScheme) -> Tree<Vec3, L5, L4, L3> {
+        curl(self, scheme, Vec3::ONE)
+    }
+}
+
+impl<ValueTy, const L5: u32, const L4: u32, const L3: u32> Tree<ValueTy, L5, L4, L3>
+where
+    ValueTy: Copy + Add<Output = ValueTy> + Mul<f32, Output = ValueTy>,
+{
+    /// Index space Laplacian at every active voxel, active tiles are expanded into voxels.
+    pub fn laplacian(&self, scheme: DifferenceScheme) -> Self {
+        laplacian(self, scheme, Vec3::ONE)
+    }
+}
+
+impl<ValueTy, const L5: u32, const L4: u32, const L3: u32> Grid<ValueTy, L5, L4, L3> {
+    /// Wraps `tree` in a grid with the transform and descriptor of this grid, with the
+    /// `grid_type` of the descriptor naming the value type of `tree`.
+    fn with_tree<OutTy: GridValueType>(
+        &self,
+        tree: Tree<OutTy, L5, L4, L3>,
+    ) -> Grid<OutTy, L5, L4, L3> {
+        let mut descriptor = self.descriptor.clone();
+        descriptor.grid_type = Tree::<OutTy, L5, L4, L3>::type_name(OutTy::TYPE_NAME).into();
+        Grid {
+            tree,
+            transform: self.transform.clone(),
+            descriptor,
+        }
+    }
+}
+
+impl<const L5: u32, const L4: u32, const L3: u32> Grid<f32, L5, L4, L3> {
+    /// World space gradient at every active voxel, see [`Tree::gradient`]. The transform and
+    /// descriptor are copied to the result, with the grid type set to a vector tree.
+    pub fn gradient(&self, scheme: DifferenceScheme) -> Grid<Vec3, L5, L4, L3> {
+        self.with_tree(gradient(&self.tree, scheme, self.voxel_size().recip()))
+    }
+
+    /// Closest point transform of a level set: the world space position of the closest point on
+    /// the zero crossing for every active voxel, found by stepping the signed distance along the
+    /// normalized gradient. Active tiles are expanded into voxels. The transform and descriptor
+    /// are copied to the result, with the grid type set to a vector tree.
+    pub fn closest_point_transform(&self) -> Grid<Vec3, L5, L4, L3> {
+        self.with_tree(closest_point_transform(self))
+    }
+}
+
+impl<const L5: u32, const L4: u32, const L3: u32> Grid<Vec3, L5, L4, L3> {
+    /// World space divergence at every active voxel, see [`Tree::divergence`]. The transform
+    /// and descriptor are copied to the result, with the grid type set to a float tree.
+    pub fn divergence(&self, scheme: DifferenceScheme) -> Grid<f32, L5, L4, L3> {
+        self.with_tree(divergence(&self.tree, scheme, self.voxel_size().recip()))
+    }
+
+    /// World space curl at every active voxel, see [`Tree::curl`]. The transform and descriptor
+    /// are copied to the result.
+    pub fn curl(&self, scheme: DifferenceScheme) -> Grid<Vec3, L5, L4, L3> {
+        self.with_tree(curl(&self.tree, scheme, self.voxel_size().recip()))
+    }
+}
+
+impl<ValueTy, const L5: u32, const L4: u32, const L3: u32> Grid<ValueTy, L5, L4, L3>
+where
+    ValueTy: Copy + Add<Output = ValueTy> + Mul<f32, Output = ValueTy>,
+{
+    /// World space Laplacian at every active voxel, see [`Tree::laplacian`]. The transform and
+    /// descriptor are copied to the result.
+    pub fn laplacian(&self, scheme: DifferenceScheme) -> Self {
+        Grid {
+            tree: laplacian(&self.tree, scheme, self.voxel_size().recip()),
+            transform: self.transform.clone(),
+            descriptor: self.descriptor.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::tests::sphere;
+
+    #[test]
+    fn gradient_of_sphere_is_vector_grid_of_unit_normals() {
+        let gradient = sphere(Vec3::ZERO).gradient(DifferenceScheme::Central);
+        assert_eq!(gradient.descriptor.grid_type, "Tree_vec3s_5_4_3");
+        let normal = gradient.tree.get_value(IVec3::new(10, 0, 0));
+        assert!(normal.distance(Vec3::X) < 0.01, "{normal}");
+
+        let divergence = gradient.divergence(DifferenceScheme::Central);
+        assert_eq!(divergence.descriptor.grid_type, "Tree_float_5_4_3");
+        // The divergence of the normals of a sphere is twice its mean curvature, 2 / r
+        let curvature = divergence.tree.get_value(IVec3::new(10, 0, 0));
+        assert!((curvature - 2.0).abs() < 0.1, "{curvature}");
+        assert_eq!(
+            gradient
+                .curl(DifferenceScheme::Central)
+                .descriptor
+                .grid_type,
+            "Tree_vec3s_5_4_3"
+        );
+    }
+
+    #[test]
+    fn closest_points_of_sphere_lie_on_its_surface() {
+        let cpt = sphere(Vec3::ZERO).closest_point_transform();
+        assert_eq!(cpt.descriptor.grid_type, "Tree_vec3s_5_4_3");
+        for coord in [
+            IVec3::new(12, 0, 0),
+            IVec3::new(0, -9, 0),
+            IVec3::new(7, 7, 0),
+        ] {
+            let expected = (coord.as_vec3() * 0.1).normalize();
+            let closest = cpt.tree.get_value(coord);
+            assert!(closest.distance(expected) < 0.01, "{coord}: {closest}");
+        }
+    }
+}
@@ -0,0 +1,829 @@
+use crate::data_structure::{Grid, GridClass, GridDescriptor, Tree};
+use crate::transform::Transform;
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use glam::{DMat4, DVec3, IVec3, UVec3};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+#[derive(thiserror::Error, Debug)]
+pub enum MedicalImportError {
+    #[error("Invalid header: {0}")]
+    InvalidHeader(String),
+    #[error("Unsupported: {0}")]
+    Unsupported(String),
+    #[error("No DICOM slices found")]
+    NoSlices,
+    #[error("IoError")]
+    IoError(#[from] std::io::Error),
+}
+
+/// Imports medical volumes, CT and MRI scans stored as NRRD files or DICOM series, into
+/// `Grid<f32>`s whose transforms place voxels at their scanner coordinates.
+///
+/// Scans are dense, so voxels within a tolerance of the background, such as the air around a
+/// patient, are left inactive to keep the grid sparse.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MedicalImport {
+    background: f32,
+    tolerance: f32,
+    normalize: bool,
+}
+
+impl Default for MedicalImport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MedicalImport {
+    /// Keeps the stored intensities, with every voxel that isn't exactly zero active.
+    pub fn new() -> Self {
+        Self {
+            background: 0.0,
+            tolerance: 0.0,
+            normalize: false,
+        }
+    }
+
+    /// Background value of the grid, e.g. -1000 for the Hounsfield units of air.
+    pub fn with_background(mut self, background: f32) -> Self {
+        self.background = background;
+        self
+    }
+
+    /// Voxels within `tolerance` of the background are left inactive.
+    pub fn with_tolerance(mut self, tolerance: f32) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Whether to rescale intensities so they range from zero to one before comparing them to
+    /// the background, off by default. Normalized grids are marked as fog volumes.
+    pub fn with_normalization(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+        self
+    }
+
+    /// Builds a grid from `values` sampled on a `dims` lattice, with `x` varying fastest, then
+    /// `y`, then `z`.
+    fn build(
+        &self,
+        name: &str,
+        dims: UVec3,
+        mut values: Vec<f32>,
+        index_to_world: DMat4,
+    ) -> Grid<f32> {
+        let mut class = GridClass::Unknown;
+        if self.normalize {
+            let min = values.iter().copied().fold(f32::INFINITY, f32::min);
+            let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+            let scale = if max > min { 1.0 / (max - min) } else { 0.0 };
+            for value in &mut values {
+                *value = (*value - min) * scale;
+            }
+            class = GridClass::FogVolume;
+        }
+
+        let mut tree = Tree::new(self.background);
+        let mut values = values.into_iter();
+        for z in 0..dims.z as i32 {
+            for y in 0..dims.y as i32 {
+                for x in 0..dims.x as i32 {
+                    let value = values.next().unwrap();
+                    if (value - self.background).abs() > self.tolerance {
+                        tree.set_value_on(IVec3::new(x, y, z), value);
+                    }
+                }
+            }
+        }
+        let mut grid = Grid {
+            tree,
+            transform: Transform::from_matrix_simplified(index_to_world),
+            descriptor: GridDescriptor::new(name, Tree::<f32>::type_name("float")),
+        };
+        grid.set_grid_class(class);
+        grid
+    }
+
+    /// Reads a three dimensional scalar NRRD file, with its data attached or in a separate
+    /// file, stored as raw bytes, gzip compressed or as text.
+    ///
+    /// The index to world transform comes from the `space directions` and `space origin`
+    /// fields when present, and from `spacings` otherwise. The grid is named after the file.
+    pub fn read_nrrd(&self, path: impl AsRef<Path>) -> Result<Grid<f32>, MedicalImportError> {
+        let path = path.as_ref();
+        let file = std::fs::read(path)?;
+        let invalid = |message: &str| MedicalImportError::InvalidHeader(message.to_owned());
+        if !file.starts_with(b"NRRD000") {
+            return Err(invalid("missing NRRD magic"));
+        }
+
+        // Fields up to the first empty line, the data follows directly after
+        let mut fields = HashMap::new();
+        let mut position = 0;
+        for line in file.split_inclusive(|&c| c == b'\n') {
+            position += line.len();
+            let line = String::from_utf8_lossy(line);
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line.is_empty() {
+                break;
+            }
+            if line.starts_with('#') || line.starts_with("NRRD") || line.contains(":=") {
+                continue;
+            }
+            if let Some((key, value)) = line.split_once(": ") {
+                fields.insert(key.trim().to_lowercase(), value.trim().to_owned());
+            }
+        }
+        let field = |key: &str| {
+            fields
+                .get(key)
+                .map(String::as_str)
+                .ok_or_else(|| invalid(&format!("missing field `{key}`")))
+        };
+        let numbers = |value: &str| {
+            value
+                .split(|c: char| c.is_whitespace() || "(),".contains(c))
+                .filter(|token| !token.is_empty())
+                .map(|token| token.parse::<f64>().map_err(|_| invalid(value)))
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        if field("dimension")? != "3" {
+            return Err(MedicalImportError::Unsupported(
+                "NRRD files with other than three dimensions".to_owned(),
+            ));
+        }
+        let sizes = numbers(field("sizes")?)?;
+        if sizes.len() != 3 {
+            return Err(invalid("sizes"));
+        }
+        let dims = UVec3::new(sizes[0] as u32, sizes[1] as u32, sizes[2] as u32);
+        let count = (dims.x as usize)
+            .checked_mul(dims.y as usize)
+            .and_then(|count| count.checked_mul(dims.z as usize))
+            .ok_or_else(|| invalid("sizes"))?;
+
+        let mut index_to_world = DMat4::IDENTITY;
+        if let Ok(directions) = field("space directions") {
+            let directions = numbers(directions)?;
+            if directions.len() != 9 {
+                return Err(invalid("space directions"));
+            }
+            for (axis, direction) in directions.chunks(3).enumerate() {
+                *index_to_world.col_mut(axis) = DVec3::from_slice(direction).extend(0.0);
+            }
+        } else if let Ok(spacings) = field("spacings") {
+            let spacings = numbers(spacings)?;
+            if spacings.len() != 3 {
+                return Err(invalid("spacings"));
+            }
+            let spacings = spacings
+                .into_iter()
+                .map(|spacing| if spacing.is_nan() { 1.0 } else { spacing })
+                .collect::<Vec<_>>();
+            index_to_world = DMat4::from_scale(DVec3::from_slice(&spacings));
+        }
+        if let Ok(origin) = field("space origin") {
+            let origin = numbers(origin)?;
+            if origin.len() != 3 {
+                return Err(invalid("space origin"));
+            }
+            index_to_world.w_axis = DVec3::from_slice(&origin).extend(1.0);
+        }
+
+        let data = match fields.get("data file").or_else(|| fields.get("datafile")) {
+            Some(data_file) => std::fs::read(path.with_file_name(data_file))?,
+            None => file[position..].to_vec(),
+        };
+        let data = match field("encoding")? {
+            "raw" => data,
+            "gzip" | "gz" => {
+                let mut decoded = vec![];
+                flate2::read::GzDecoder::new(data.as_slice()).read_to_end(&mut decoded)?;
+                decoded
+            }
+            "ascii" | "text" | "txt" => {
+                let values = String::from_utf8_lossy(&data)
+                    .split_whitespace()
+                    .take(count)
+                    .map(|token| token.parse::<f32>().map_err(|_| invalid(token)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if values.len() != count {
+                    return Err(invalid("not enough values"));
+                }
+                let name = path.file_stem().unwrap_or_default().to_string_lossy();
+                return Ok(self.build(&name, dims, values, index_to_world));
+            }
+            encoding => {
+                return Err(MedicalImportError::Unsupported(format!(
+                    "NRRD encoding {encoding}"
+                )))
+            }
+        };
+
+        let (size, signed, float) = match field("type")? {
+            "signed char" | "int8" | "int8_t" => (1, true, false),
+            "uchar" | "unsigned char" | "uint8" | "uint8_t" => (1, false, false),
+            "short" | "short int" | "signed short" | "signed short int" | "int16" | "int16_t" => {
+                (2, true, false)
+            }
+            "ushort" | "unsigned short" | "unsigned short int" | "uint16" | "uint16_t" => {
+                (2, false, false)
+            }
+            "int" | "signed int" | "int32" | "int32_t" => (4, true, false),
+            "uint" | "unsigned int" | "uint32" | "uint32_t" => (4, false, false),
+            "float" => (4, true, true),
+            "double" => (8, true, true),
+            value_type => {
+                return Err(MedicalImportError::Unsupported(format!(
+                    "NRRD type {value_type}"
+                )))
+            }
+        };
+        let big_endian = size > 1 && field("endian")? == "big";
+        let len = count.checked_mul(size).ok_or_else(|| invalid("sizes"))?;
+        // Skipped bytes precede the data, or follow it when negative
+        let skip = match fields.get("byte skip").map(|skip| skip.parse::<i64>()) {
+            Some(Ok(-1)) => data.len().saturating_sub(len),
+            Some(Ok(skip)) if skip >= 0 => skip as usize,
+            None => 0,
+            Some(_) => return Err(invalid("byte skip")),
+        };
+        let end = skip.checked_add(len).ok_or_else(|| invalid("byte skip"))?;
+        let data = data
+            .get(skip..end)
+            .ok_or_else(|| invalid("not enough data"))?;
+        let values = if big_endian {
+            decode_samples::<BigEndian>(data, size, signed, float)
+        } else {
+            decode_samples::<LittleEndian>(data, size, signed, float)
+        };
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        Ok(self.build(&name, dims, values, index_to_world))
+    }
+
+    /// Reads the uncompressed DICOM slices in the directory `dir` as a volume, with intensities
+    /// rescaled by the slope and intercept of every slice, which gives Hounsfield units for CT
+    /// scans.
+    ///
+    /// Slices are ordered along the normal of the image planes and placed by their patient
+    /// position and orientation, so the grid transform maps voxels to patient coordinates in
+    /// millimeters. Files that aren't DICOM images and slices of other series than the first
+    /// file in name order are skipped. Compressed transfer syntaxes and multi-frame files are
+    /// not supported. The grid is named after the series description.
+    pub fn read_dicom_series(
+        &self,
+        dir: impl AsRef<Path>,
+    ) -> Result<Grid<f32>, MedicalImportError> {
+        let mut paths = std::fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        paths.sort();
+
+        let mut slices = vec![];
+        for path in paths {
+            if !path.is_file() {
+                continue;
+            }
+            let file = std::fs::read(path)?;
+            if file.get(128..132) != Some(b"DICM") {
+                continue;
+            }
+            let slice = DicomSlice::parse(&file)?;
+            if slice.pixels.is_empty() {
+                continue;
+            }
+            if slices
+                .first()
+                .is_none_or(|first: &DicomSlice| first.series == slice.series)
+            {
+                slices.push(slice);
+            }
+        }
+        let Some(first) = slices.first() else {
+            return Err(MedicalImportError::NoSlices);
+        };
+
+        // Order slices along the normal of their planes
+        let normal = first.row_direction.cross(first.column_direction);
+        slices.sort_by(|a, b| {
+            let (a, b) = (a.position.dot(normal), b.position.dot(normal));
+            a.total_cmp(&b)
+        });
+        let first = &slices[0];
+        let (rows, columns) = (first.rows, first.columns);
+        if slices
+            .iter()
+            .any(|slice| slice.rows != rows || slice.columns != columns)
+        {
+            return Err(MedicalImportError::Unsupported(
+                "DICOM slices of different sizes".to_owned(),
+            ));
+        }
+        let slice_step = if slices.len() > 1 {
+            (slices[slices.len() - 1].position - first.position) / (slices.len() - 1) as f64
+        } else {
+            normal * first.slice_thickness
+        };
+
+        // Columns advance along the row direction, rows along the column direction
+        let index_to_world = DMat4::from_cols(
+            (first.row_direction * first.spacing.y).extend(0.0),
+            (first.column_direction * first.spacing.x).extend(0.0),
+            slice_step.extend(0.0),
+            first.position.extend(1.0),
+        );
+        let dims = UVec3::new(columns, rows, slices.len() as u32);
+        let name = first.description.clone();
+        let values = slices.into_iter().flat_map(|slice| slice.pixels).collect();
+        Ok(self.build(&name, dims, values, index_to_world))
+    }
+}
+
+/// Converts `data` holding samples of `size` bytes to floats.
+fn decode_samples<B: ByteOrder>(data: &[u8], size: usize, signed: bool, float: bool) -> Vec<f32> {
+    data.chunks_exact(size)
+        .map(|sample| match (size, signed, float) {
+            (1, true, _) => sample[0] as i8 as f32,
+            (1, false, _) => sample[0] as f32,
+            (2, true, _) => B::read_i16(sample) as f32,
+            (2, false, _) => B::read_u16(sample) as f32,
+            (4, _, true) => B::read_f32(sample),
+            (4, true, _) => B::read_i32(sample) as f32,
+            (4, false, _) => B::read_u32(sample) as f32,
+            (8, _, true) => B::read_f64(sample) as f32,
+            _ => unreachable!("unsupported sample size {size}"),
+        })
+        .collect()
+}
+
+const TAG_TRANSFER_SYNTAX: u32 = 0x0002_0010;
+const TAG_SERIES_DESCRIPTION: u32 = 0x0008_103e;
+const TAG_SLICE_THICKNESS: u32 = 0x0018_0050;
+const TAG_SERIES_INSTANCE: u32 = 0x0020_000e;
+const TAG_IMAGE_POSITION: u32 = 0x0020_0032;
+const TAG_IMAGE_ORIENTATION: u32 = 0x0020_0037;
+const TAG_SAMPLES_PER_PIXEL: u32 = 0x0028_0002;
+const TAG_NUMBER_OF_FRAMES: u32 = 0x0028_0008;
+const TAG_ROWS: u32 = 0x0028_0010;
+const TAG_COLUMNS: u32 = 0x0028_0011;
+const TAG_PIXEL_SPACING: u32 = 0x0028_0030;
+const TAG_BITS_ALLOCATED: u32 = 0x0028_0100;
+const TAG_PIXEL_REPRESENTATION: u32 = 0x0028_0103;
+const TAG_RESCALE_INTERCEPT: u32 = 0x0028_1052;
+const TAG_RESCALE_SLOPE: u32 = 0x0028_1053;
+const TAG_PIXEL_DATA: u32 = 0x7fe0_0010;
+
+const UNDEFINED_LENGTH: u32 = u32::MAX;
+
+/// Implicit and explicit VR little endian, the uncompressed transfer syntaxes
+const IMPLICIT_LITTLE_ENDIAN: &str = "1.2.840.10008.1.2";
+const EXPLICIT_LITTLE_ENDIAN: &str = "1.2.840.10008.1.2.1";
+
+/// The attributes of a DICOM image needed to place it in a volume.
+struct DicomSlice {
+    series: String,
+    description: String,
+    rows: u32,
+    columns: u32,
+    /// Spacing between rows and between columns, in millimeters
+    spacing: glam::DVec2,
+    slice_thickness: f64,
+    position: DVec3,
+    row_direction: DVec3,
+    column_direction: DVec3,
+    /// Rescaled intensities, row by row
+    pixels: Vec<f32>,
+}
+
+/// Top level data elements of a DICOM file, by tag.
+struct DicomElements<'a> {
+    bytes: &'a [u8],
+    explicit: bool,
+    values: HashMap<u32, &'a [u8]>,
+}
+
+impl<'a> DicomElements<'a> {
+    fn truncated() -> MedicalImportError {
+        MedicalImportError::InvalidHeader("truncated DICOM data element".to_owned())
+    }
+
+    fn u16(&self, position: usize) -> Result<u16, MedicalImportError> {
+        let bytes = self.bytes.get(position..position + 2);
+        bytes
+            .map(LittleEndian::read_u16)
+            .ok_or_else(Self::truncated)
+    }
+
+    fn u32(&self, position: usize) -> Result<u32, MedicalImportError> {
+        let bytes = self.bytes.get(position..position + 4);
+        bytes
+            .map(LittleEndian::read_u32)
+            .ok_or_else(Self::truncated)
+    }
+
+    /// Tag, value length and value position of the data element at `position`.
+    fn element(&self, position: usize) -> Result<(u32, u32, usize), MedicalImportError> {
+        let tag = ((self.u16(position)? as u32) << 16) | self.u16(position + 2)? as u32;
+        // Items and delimiters never have a value representation
+        if !self.explicit || tag >> 16 == 0xfffe {
+            return Ok((tag, self.u32(position + 4)?, position + 8));
+        }
+        let vr = self
+            .bytes
+            .get(position + 4..position + 6)
+            .ok_or_else(Self::truncated)?;
+        match vr {
+            b"OB" | b"OD" | b"OF" | b"OL" | b"OV" | b"OW" | b"SQ" | b"SV" | b"UC" | b"UN"
+            | b"UR" | b"UT" | b"UV" => Ok((tag, self.u32(position + 8)?, position + 12)),
+            _ => Ok((tag, self.u16(position + 6)? as u32, position + 8)),
+        }
+    }
+
+    /// Skips the items of a sequence or the elements of an item of undefined length, returning
+    /// the position after their delimiter.
+    fn skip_undefined(&self, mut position: usize) -> Result<usize, MedicalImportError> {
+        loop {
+            let (tag, len, value) = self.element(position)?;
+            match tag {
+                0xfffe_e0dd | 0xfffe_e00d => return Ok(value),
+                _ if len == UNDEFINED_LENGTH => position = self.skip_undefined(value)?,
+                _ => {
+                    position = value
+                        .checked_add(len as usize)
+                        .ok_or_else(Self::truncated)?
+                }
+            }
+        }
+    }
+
+    /// Reads the elements from `position` on until the end of the file or, if `group` is
+    /// given, the first element of another group.
+    fn read(
+        &mut self,
+        mut position: usize,
+        group: Option<u16>,
+    ) -> Result<usize, MedicalImportError> {
+        while position < self.bytes.len() {
+            let (tag, len, value) = self.element(position)?;
+            if group.is_some_and(|group| tag >> 16 != group as u32) {
+                break;
+            }
+            if len == UNDEFINED_LENGTH {
+                if tag == TAG_PIXEL_DATA {
+                    return Err(MedicalImportError::Unsupported(
+                        "compressed DICOM pixel data".to_owned(),
+                    ));
+                }
+                position = self.skip_undefined(value)?;
+                continue;
+            }
+            let end = value
+                .checked_add(len as usize)
+                .ok_or_else(Self::truncated)?;
+            let bytes = self.bytes.get(value..end).ok_or_else(Self::truncated)?;
+            self.values.insert(tag, bytes);
+            position = end;
+        }
+        Ok(position)
+    }
+
+    fn string(&self, tag: u32) -> Option<String> {
+        let value = self.values.get(&tag)?;
+        let value = String::from_utf8_lossy(value);
+        Some(
+            value
+                .trim_matches(|c: char| c.is_whitespace() || c == '\0')
+                .to_owned(),
+        )
+    }
+
+    /// Backslash separated decimal strings.
+    fn numbers(&self, tag: u32) -> Option<Vec<f64>> {
+        let value = self.string(tag)?;
+        value
+            .split('\\')
+            .map(|number| number.trim().parse().ok())
+            .collect()
+    }
+
+    fn unsigned(&self, tag: u32) -> Option<u32> {
+        let value = self.values.get(&tag)?;
+        (value.len() >= 2).then(|| LittleEndian::read_u16(value) as u32)
+    }
+}
+
+impl DicomSlice {
+    fn parse(file: &[u8]) -> Result<Self, MedicalImportError> {
+        let invalid = |tag: u32| {
+            MedicalImportError::InvalidHeader(format!(
+                "missing or invalid DICOM attribute ({:04x},{:04x})",
+                tag >> 16,
+                tag & 0xffff
+            ))
+        };
+
+        // The file meta information is always explicit VR little endian
+        let mut elements = DicomElements {
+            bytes: file,
+            explicit: true,
+            values: HashMap::new(),
+        };
+        let position = elements.read(132, Some(0x0002))?;
+        let transfer_syntax = elements.string(TAG_TRANSFER_SYNTAX).unwrap_or_default();
+        elements.explicit = match transfer_syntax.as_str() {
+            EXPLICIT_LITTLE_ENDIAN => true,
+            IMPLICIT_LITTLE_ENDIAN => false,
+            _ => {
+                return Err(MedicalImportError::Unsupported(format!(
+                    "DICOM transfer syntax {transfer_syntax}"
+                )))
+            }
+        };
+        elements.read(position, None)?;
+
+        let Some(pixel_data) = elements.values.get(&TAG_PIXEL_DATA).copied() else {
+            return Ok(Self {
+                series: String::new(),
+                description: String::new(),
+                rows: 0,
+                columns: 0,
+                spacing: glam::DVec2::ONE,
+                slice_thickness: 1.0,
+                position: DVec3::ZERO,
+                row_direction: DVec3::X,
+                column_direction: DVec3::Y,
+                pixels: vec![],
+            });
+        };
+        if elements.unsigned(TAG_SAMPLES_PER_PIXEL).unwrap_or(1) != 1 {
+            return Err(MedicalImportError::Unsupported(
+                "DICOM images with multiple samples per pixel".to_owned(),
+            ));
+        }
+        if elements
+            .string(TAG_NUMBER_OF_FRAMES)
+            .is_some_and(|frames| frames.parse::<u32>().unwrap_or(1) > 1)
+        {
+            return Err(MedicalImportError::Unsupported(
+                "multi-frame DICOM images".to_owned(),
+            ));
+        }
+
+        let rows = elements
+            .unsigned(TAG_ROWS)
+            .ok_or_else(|| invalid(TAG_ROWS))?;
+        let columns = elements
+            .unsigned(TAG_COLUMNS)
+            .ok_or_else(|| invalid(TAG_COLUMNS))?;
+        let bits = elements.unsigned(TAG_BITS_ALLOCATED).unwrap_or(16);
+        let signed = elements.unsigned(TAG_PIXEL_REPRESENTATION).unwrap_or(0) == 1;
+        let size = bits as usize / 8;
+        if ![1, 2, 4].contains(&size) {
+            return Err(MedicalImportError::Unsupported(format!(
+                "DICOM pixels of {bits} bits"
+            )));
+        }
+        let len = (rows as usize)
+            .checked_mul(columns as usize)
+            .and_then(|count| count.checked_mul(size))
+            .ok_or_else(|| invalid(TAG_PIXEL_DATA))?;
+        let pixel_data = pixel_data
+            .get(..len)
+            .ok_or_else(|| invalid(TAG_PIXEL_DATA))?;
+        let slope = elements
+            .numbers(TAG_RESCALE_SLOPE)
+            .and_then(|slope| slope.first().copied())
+            .unwrap_or(1.0) as f32;
+        let intercept = elements
+            .numbers(TAG_RESCALE_INTERCEPT)
+            .and_then(|intercept| intercept.first().copied())
+            .unwrap_or(0.0) as f32;
+        let pixels = decode_samples::<LittleEndian>(pixel_data, size, signed, false)
+            .into_iter()
+            .map(|value| value * slope + intercept)
+            .collect();
+
+        let spacing = match elements.numbers(TAG_PIXEL_SPACING).as_deref() {
+            Some(&[row, column]) => glam::DVec2::new(row, column),
+            _ => glam::DVec2::ONE,
+        };
+        let position = match elements.numbers(TAG_IMAGE_POSITION).as_deref() {
+            Some(position) if position.len() == 3 => DVec3::from_slice(position),
+            _ => DVec3::ZERO,
+        };
+        let (row_direction, column_direction) =
+            match elements.numbers(TAG_IMAGE_ORIENTATION).as_deref() {
+                Some(directions) if directions.len() == 6 => (
+                    DVec3::from_slice(&directions[..3]),
+                    DVec3::from_slice(&directions[3..]),
+                ),
+                _ => (DVec3::X, DVec3::Y),
+            };
+        let slice_thickness = elements
+            .numbers(TAG_SLICE_THICKNESS)
+            .and_then(|thickness| thickness.first().copied())
+            .unwrap_or(1.0);
+
+        Ok(Self {
+            series: elements.string(TAG_SERIES_INSTANCE).unwrap_or_default(),
+            description: elements.string(TAG_SERIES_DESCRIPTION).unwrap_or_default(),
+            rows,
+            columns,
+            spacing,
+            slice_thickness,
+            position,
+            row_direction,
+            column_direction,
+            pixels,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    /// Empty directory in the system temp dir, unique to this process and `name`.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vdb-rs-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Samples of a 3 by 2 by 2 volume, with `x` varying fastest.
+    const SAMPLES: [i16; 12] = [0, 1, -2, 300, 0, 5, -600, 7, 8, 0, 10, 11];
+
+    fn sample(coord: IVec3) -> f32 {
+        SAMPLES[(coord.x + 3 * (coord.y + 2 * coord.z)) as usize] as f32
+    }
+
+    fn assert_volume(grid: &Grid<f32>, value: impl Fn(f32) -> f32, inactive: f32) {
+        for z in 0..2 {
+            for y in 0..2 {
+                for x in 0..3 {
+                    let coord = IVec3::new(x, y, z);
+                    let expected = value(sample(coord));
+                    assert_eq!(grid.tree.get_value(coord), expected, "{coord}");
+                    assert_eq!(
+                        grid.tree.is_value_on(coord),
+                        expected != inactive,
+                        "{coord}"
+                    );
+                }
+            }
+        }
+        assert_eq!(grid.active_voxel_count(), 9);
+    }
+
+    #[test]
+    fn nrrd_round_trips_raw_and_gzip() {
+        let dir = temp_dir("nrrd");
+        let data = SAMPLES
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect::<Vec<_>>();
+        for (encoding, data) in [
+            ("raw", data.clone()),
+            ("gzip", {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+                encoder.write_all(&data).unwrap();
+                encoder.finish().unwrap()
+            }),
+        ] {
+            let path = dir.join(format!("{encoding}.nrrd"));
+            let mut file = format!(
+                "NRRD0004\n# comment\ntype: short\ndimension: 3\nsizes: 3 2 2\n\
+                 space: left-posterior-superior\n\
+                 space directions: (0.5,0,0) (0,0.25,0) (0,0,2)\n\
+                 space origin: (10,-5,1)\nendian: little\nencoding: {encoding}\n\n"
+            )
+            .into_bytes();
+            file.extend(&data);
+            std::fs::write(&path, file).unwrap();
+
+            let grid = MedicalImport::new().read_nrrd(&path).unwrap();
+            assert_eq!(grid.descriptor.name, encoding);
+            assert_volume(&grid, |sample| sample, 0.0);
+            assert_eq!(
+                grid.transform.index_to_world_f64(DVec3::new(2.0, 1.0, 1.0)),
+                DVec3::new(11.0, -4.75, 3.0)
+            );
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn oversized_nrrd_is_invalid() {
+        let dir = temp_dir("nrrd-oversized");
+        let path = dir.join("oversized.nrrd");
+        std::fs::write(
+            &path,
+            "NRRD0004\ntype: double\ndimension: 3\nsizes: 4294967295 4294967295 4294967295\n\
+             endian: little\nencoding: raw\n\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            MedicalImport::new().read_nrrd(&path),
+            Err(MedicalImportError::InvalidHeader(_))
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Explicit VR little endian data element, padded to an even length.
+    fn element(out: &mut Vec<u8>, tag: u32, vr: &[u8; 2], value: &[u8]) {
+        let mut value = value.to_vec();
+        if value.len() % 2 == 1 {
+            value.push(0);
+        }
+        out.extend(((tag >> 16) as u16).to_le_bytes());
+        out.extend((tag as u16).to_le_bytes());
+        out.extend(vr);
+        if vr == b"OW" {
+            out.extend([0, 0]);
+            out.extend((value.len() as u32).to_le_bytes());
+        } else {
+            out.extend((value.len() as u16).to_le_bytes());
+        }
+        out.extend(value);
+    }
+
+    /// DICOM file holding the slice `z` of the volume, placed at `z` times 2 millimeters.
+    fn dicom_slice(z: i32) -> Vec<u8> {
+        let mut file = vec![0; 128];
+        file.extend(b"DICM");
+        element(
+            &mut file,
+            TAG_TRANSFER_SYNTAX,
+            b"UI",
+            EXPLICIT_LITTLE_ENDIAN.as_bytes(),
+        );
+        element(&mut file, TAG_SERIES_DESCRIPTION, b"LO", b"Head CT");
+        element(&mut file, TAG_SLICE_THICKNESS, b"DS", b"2");
+        element(&mut file, TAG_SERIES_INSTANCE, b"UI", b"1.2.3");
+        let position = format!("10\\-5\\{}", 1 + 2 * z);
+        element(&mut file, TAG_IMAGE_POSITION, b"DS", position.as_bytes());
+        element(&mut file, TAG_IMAGE_ORIENTATION, b"DS", b"1\\0\\0\\0\\1\\0");
+        element(&mut file, TAG_ROWS, b"US", &2u16.to_le_bytes());
+        element(&mut file, TAG_COLUMNS, b"US", &3u16.to_le_bytes());
+        // Spacing between rows, then between columns
+        element(&mut file, TAG_PIXEL_SPACING, b"DS", b"0.25\\0.5");
+        element(&mut file, TAG_BITS_ALLOCATED, b"US", &16u16.to_le_bytes());
+        element(
+            &mut file,
+            TAG_PIXEL_REPRESENTATION,
+            b"US",
+            &1u16.to_le_bytes(),
+        );
+        element(&mut file, TAG_RESCALE_INTERCEPT, b"DS", b"-1000");
+        element(&mut file, TAG_RESCALE_SLOPE, b"DS", b"2");
+        let pixels = SAMPLES[6 * z as usize..6 * (z as usize + 1)]
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect::<Vec<_>>();
+        element(&mut file, TAG_PIXEL_DATA, b"OW", &pixels);
+        file
+    }
+
+    #[test]
+    fn dicom_series_is_ordered_along_the_slice_normal() {
+        let dir = temp_dir("dicom");
+        // Name order is the reverse of the slice order
+        std::fs::write(dir.join("a.dcm"), dicom_slice(1)).unwrap();
+        std::fs::write(dir.join("b.dcm"), dicom_slice(0)).unwrap();
+        std::fs::write(dir.join("readme.txt"), "not a slice").unwrap();
+
+        let grid = MedicalImport::new()
+            .with_background(-1000.0)
+            .read_dicom_series(&dir)
+            .unwrap();
+        assert_eq!(grid.descriptor.name, "Head CT");
+        assert_volume(&grid, |sample| sample * 2.0 - 1000.0, -1000.0);
+        assert_eq!(
+            grid.transform.index_to_world_f64(DVec3::new(2.0, 1.0, 1.0)),
+            DVec3::new(11.0, -4.75, 3.0)
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn truncated_dicom_is_invalid() {
+        let dir = temp_dir("dicom-truncated");
+        let mut file = dicom_slice(0);
+        file.truncate(file.len() - 3);
+        std::fs::write(dir.join("a.dcm"), file).unwrap();
+        assert!(matches!(
+            MedicalImport::new().read_dicom_series(&dir),
+            Err(MedicalImportError::InvalidHeader(_))
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,165 @@
+use crate::data_structure::{Grid, Node3, Node4, Node5, Tree};
+
+use std::sync::Arc;
+
+/// How [`Tree::merge`] resolves voxels that are present in both trees.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// Active values of the existing tree win, the other tree only fills in where the existing
+    /// tree is inactive.
+    KeepExisting,
+    /// Active values of the merged-in tree win, the existing tree only remains where the other
+    /// tree is inactive.
+    Overwrite,
+}
+
+impl MergePolicy {
+    /// Resolves the `(value, active)` pair of the existing tree against that of the other tree.
+    /// Where both are inactive the existing value is kept.
+    pub fn resolve<ValueTy>(
+        self,
+        existing: (ValueTy, bool),
+        other: (ValueTy, bool),
+    ) -> (ValueTy, bool) {
+        match self {
+            MergePolicy::KeepExisting if existing.1 || !other.1 => existing,
+            MergePolicy::Overwrite if !other.1 => existing,
+            _ => other,
+        }
+    }
+}
+
+impl<ValueTy: Copy, const L5: u32, const L4: u32, const L3: u32> Tree<ValueTy, L5, L4, L3> {
+    /// Moves the nodes of `other` into this tree, resulting in the union of both topologies.
+    ///
+    /// Nodes that only exist in `other` are moved over without copying their buffers, where both
+    /// trees hold data `policy` decides which value is kept. Inactive tiles don't affect nodes of
+    /// the other tree, and the background of this tree is kept.
+    pub fn merge(&mut self, other: Tree<ValueTy, L5, L4, L3>, policy: MergePolicy) {
+        for other_5 in other.root_nodes {
+            match self
+                .root_nodes
+                .iter_mut()
+                .find(|node_5| node_5.origin == other_5.origin)
+            {
+                Some(node_5) => merge_node_5(node_5, other_5, policy),
+                None => self.root_nodes.push(other_5),
+            }
+        }
+    }
+}
+
+fn merge_node_5<ValueTy: Copy, const L5: u32, const L4: u32, const L3: u32>(
+    node_5: &mut Node5<ValueTy, L5, L4, L3>,
+    mut other: Node5<ValueTy, L5, L4, L3>,
+    policy: MergePolicy,
+) {
+    for idx in 0..node_5.data.len() {
+        let tile = (node_5.data[idx], node_5.value_mask[idx]);
+        let other_tile = (other.data[idx], other.value_mask[idx]);
+        match (node_5.child_mask[idx], other.nodes.remove(&(idx as u32))) {
+            (true, Some(other_4)) => {
+                let node_4 = node_5.nodes.get_mut(&(idx as u32)).unwrap();
+                merge_node_4(node_4, other_4, policy);
+            }
+            (true, None) => {
+                if other_tile.1 {
+                    let node_4 = node_5.nodes.get_mut(&(idx as u32)).unwrap();
+                    apply_node_4(node_4, &|value| policy.resolve(value, other_tile));
+                }
+            }
+            (false, Some(mut other_4)) => {
+                if tile.1 {
+                    apply_node_4(&mut other_4, &|value| policy.resolve(tile, value));
+                }
+                node_5.nodes.insert(idx as u32, other_4);
+                node_5.child_mask.set(idx, true);
+                node_5.value_mask.set(idx, false);
+            }
+            (false, None) => {
+                let (value, active) = policy.resolve(tile, other_tile);
+                node_5.data[idx] = value;
+                node_5.value_mask.set(idx, active);
+            }
+        }
+    }
+}
+
+fn merge_node_4<ValueTy: Copy, const L4: u32, const L3: u32>(
+    node_4: &mut Node4<ValueTy, L4, L3>,
+    mut other: Node4<ValueTy, L4, L3>,
+    policy: MergePolicy,
+) {
+    for idx in 0..node_4.data.len() {
+        let tile = (node_4.data[idx], node_4.value_mask[idx]);
+        let other_tile = (other.data[idx], other.value_mask[idx]);
+        match (node_4.child_mask[idx], other.nodes.remove(&(idx as u32))) {
+            (true, Some(other_3)) => {
+                let node_3 = Arc::make_mut(node_4.nodes.get_mut(&(idx as u32)).unwrap());
+                for idx in 0..node_3.buffer.len() {
+                    let (value, active) = policy.resolve(
+                        (node_3.buffer[idx], node_3.value_mask[idx]),
+                        (other_3.buffer[idx], other_3.value_mask[idx]),
+                    );
+                    node_3.buffer[idx] = value;
+                    node_3.value_mask.set(idx, active);
+                }
+            }
+            (true, None) => {
+                if other_tile.1 {
+                    let node_3 = Arc::make_mut(node_4.nodes.get_mut(&(idx as u32)).unwrap());
+                    apply_node_3(node_3, &|value| policy.resolve(value, other_tile));
+                }
+            }
+            (false, Some(mut other_3)) => {
+                if tile.1 {
+                    apply_node_3(Arc::make_mut(&mut other_3), &|value| {
+                        policy.resolve(tile, value)
+                    });
+                }
+                node_4.nodes.insert(idx as u32, other_3);
+                node_4.child_mask.set(idx, true);
+                node_4.value_mask.set(idx, false);
+            }
+            (false, None) => {
+                let (value, active) = policy.resolve(tile, other_tile);
+                node_4.data[idx] = value;
+                node_4.value_mask.set(idx, active);
+            }
+        }
+    }
+}
+
+/// Replaces every tile and voxel `(value, active)` pair in `node_4` with `f(pair)`.
+fn apply_node_4<ValueTy: Copy, const L4: u32, const L3: u32>(
+    node_4: &mut Node4<ValueTy, L4, L3>,
+    f: &impl Fn((ValueTy, bool)) -> (ValueTy, bool),
+) {
+    for idx in node_4.child_mask.iter_zeros() {
+        let (value, active) = f((node_4.data[idx], node_4.value_mask[idx]));
+        node_4.data[idx] = value;
+        node_4.value_mask.set(idx, active);
+    }
+    for node_3 in node_4.nodes.values_mut() {
+        apply_node_3(Arc::make_mut(node_3), f);
+    }
+}
+
+fn apply_node_3<ValueTy: Copy, const L3: u32>(
+    node_3: &mut Node3<ValueTy, L3>,
+    f: &impl Fn((ValueTy, bool)) -> (ValueTy, bool),
+) {
+    for idx in 0..node_3.buffer.len() {
+        let (value, active) = f((node_3.buffer[idx], node_3.value_mask[idx]));
+        node_3.buffer[idx] = value;
+        node_3.value_mask.set(idx, active);
+    }
+}
+
+impl<ValueTy: Copy, const L5: u32, const L4: u32, const L3: u32> Grid<ValueTy, L5, L4, L3> {
+    /// Moves the tree of `other` into the tree of this grid, see [`Tree::merge`]. Both grids are
+    /// assumed to share the same transform.
+    pub fn merge(&mut self, other: Grid<ValueTy, L5, L4, L3>, policy: MergePolicy) {
+        self.tree.merge(other.tree, policy);
+    }
+}
@@ -0,0 +1,262 @@
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use glam::Vec3;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+
+/// Per vertex normals of a mesh as returned by [`volume_to_mesh`] or [`volume_to_mesh_adaptive`],
+/// the area weighted mean of the normals of the faces around each vertex. Faces are expected to
+/// be wound counter-clockwise when seen from the outside, so normals point outward.
+///
+/// [`volume_to_mesh`]: crate::volume_to_mesh
+/// [`volume_to_mesh_adaptive`]: crate::volume_to_mesh_adaptive
+pub fn mesh_normals(positions: &[Vec3], triangles: &[[u32; 3]], quads: &[[u32; 4]]) -> Vec<Vec3> {
+    let mut normals = vec![Vec3::ZERO; positions.len()];
+    let faces = triangles
+        .iter()
+        .map(|triangle| triangle.as_slice())
+        .chain(quads.iter().map(|quad| quad.as_slice()));
+    for face in faces {
+        let corners = face.iter().map(|&idx| positions[idx as usize]);
+        // Twice the vector area of the polygon, which also handles non-planar quads
+        let area = corners
+            .clone()
+            .zip(corners.cycle().skip(1))
+            .map(|(a, b)| a.cross(b))
+            .sum::<Vec3>();
+        for &idx in face {
+            normals[idx as usize] += area;
+        }
+    }
+    normals
+        .iter()
+        .map(|normal| normal.normalize_or_zero())
+        .collect()
+}
+
+/// Writes a mesh as returned by [`volume_to_mesh`] or [`volume_to_mesh_adaptive`] to a Wavefront
+/// OBJ file at `path`, with the vertex normals of [`mesh_normals`]. Pass no `quads` for a
+/// triangle mesh.
+///
+/// [`volume_to_mesh`]: crate::volume_to_mesh
+/// [`volume_to_mesh_adaptive`]: crate::volume_to_mesh_adaptive
+pub fn write_obj(
+    path: impl AsRef<Path>,
+    positions: &[Vec3],
+    triangles: &[[u32; 3]],
+    quads: &[[u32; 4]],
+) -> std::io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    for position in positions {
+        writeln!(out, "v {} {} {}", position.x, position.y, position.z)?;
+    }
+    for normal in mesh_normals(positions, triangles, quads) {
+        writeln!(out, "vn {} {} {}", normal.x, normal.y, normal.z)?;
+    }
+    let faces = triangles
+        .iter()
+        .map(|triangle| triangle.as_slice())
+        .chain(quads.iter().map(|quad| quad.as_slice()));
+    for face in faces {
+        write!(out, "f")?;
+        // OBJ indices start at 1
+        for &idx in face {
+            write!(out, " {0}//{0}", idx + 1)?;
+        }
+        writeln!(out)?;
+    }
+    out.flush()
+}
+
+/// Writes a mesh as returned by [`volume_to_mesh`] or [`volume_to_mesh_adaptive`] to a binary
+/// PLY file at `path`, with the vertex normals of [`mesh_normals`]. Pass no `quads` for a
+/// triangle mesh.
+///
+/// [`volume_to_mesh`]: crate::volume_to_mesh
+/// [`volume_to_mesh_adaptive`]: crate::volume_to_mesh_adaptive
+pub fn write_ply(
+    path: impl AsRef<Path>,
+    positions: &[Vec3],
+    triangles: &[[u32; 3]],
+    quads: &[[u32; 4]],
+) -> std::io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "ply")?;
+    writeln!(out, "format binary_little_endian 1.0")?;
+    writeln!(out, "element vertex {}", positions.len())?;
+    for property in ["x", "y", "z", "nx", "ny", "nz"] {
+        writeln!(out, "property float {property}")?;
+    }
+    writeln!(out, "element face {}", triangles.len() + quads.len())?;
+    writeln!(out, "property list uchar uint vertex_indices")?;
+    writeln!(out, "end_header")?;
+
+    let normals = mesh_normals(positions, triangles, quads);
+    for (position, normal) in positions.iter().zip(normals) {
+        for component in position.to_array().into_iter().chain(normal.to_array()) {
+            out.write_f32::<LittleEndian>(component)?;
+        }
+    }
+    let faces = triangles
+        .iter()
+        .map(|triangle| triangle.as_slice())
+        .chain(quads.iter().map(|quad| quad.as_slice()));
+    for face in faces {
+        out.write_u8(face.len() as u8)?;
+        for &idx in face {
+            out.write_u32::<LittleEndian>(idx)?;
+        }
+    }
+    out.flush()
+}
+
+/// Type of a property of a PLY element, by its size in bytes and how it is read.
+#[derive(Clone, Copy)]
+enum PlyScalar {
+    Int(usize),
+    UInt(usize),
+    Float,
+    Double,
+}
+
+impl PlyScalar {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "char" | "int8" => Self::Int(1),
+            "uchar" | "uint8" => Self::UInt(1),
+            "short" | "int16" => Self::Int(2),
+            "ushort" | "uint16" => Self::UInt(2),
+            "int" | "int32" => Self::Int(4),
+            "uint" | "uint32" => Self::UInt(4),
+            "float" | "float32" => Self::Float,
+            "double" | "float64" => Self::Double,
+            _ => return None,
+        })
+    }
+
+    fn read(self, reader: &mut impl Read) -> std::io::Result<f64> {
+        Ok(match self {
+            Self::Int(size) => reader.read_int::<LittleEndian>(size)? as f64,
+            Self::UInt(size) => reader.read_uint::<LittleEndian>(size)? as f64,
+            Self::Float => reader.read_f32::<LittleEndian>()? as f64,
+            Self::Double => reader.read_f64::<LittleEndian>()?,
+        })
+    }
+}
+
+/// A property of a PLY element, lists store their length before their items.
+struct PlyProperty {
+    name: String,
+    scalar: PlyScalar,
+    list_len: Option<PlyScalar>,
+}
+
+/// Reads a binary little endian PLY mesh, such as those written by [`write_ply`], returning its
+/// positions, its normals, empty if it has none, and its faces split into triangles.
+#[allow(clippy::type_complexity)]
+pub fn read_ply(reader: impl Read) -> std::io::Result<(Vec<Vec3>, Vec<Vec3>, Vec<[u32; 3]>)> {
+    let invalid = |message: &str| std::io::Error::new(ErrorKind::InvalidData, message.to_owned());
+    let mut reader = BufReader::new(reader);
+
+    // Elements with their count and properties, in the order they are stored
+    let mut elements: Vec<(String, usize, Vec<PlyProperty>)> = vec![];
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if line.trim_end() != "ply" {
+        return Err(invalid("Not a PLY file"));
+    }
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid("Unterminated PLY header"));
+        }
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        match words.as_slice() {
+            ["end_header"] => break,
+            ["format", format, _] if *format != "binary_little_endian" => {
+                return Err(invalid("Only binary little endian PLY files are supported"));
+            }
+            ["element", name, count] => {
+                let count = count
+                    .parse()
+                    .map_err(|_| invalid("Invalid element count"))?;
+                elements.push((name.to_string(), count, vec![]));
+            }
+            ["property", "list", len, scalar, name] => {
+                let property = PlyProperty {
+                    name: name.to_string(),
+                    scalar: PlyScalar::parse(scalar).ok_or_else(|| invalid("Invalid type"))?,
+                    list_len: Some(PlyScalar::parse(len).ok_or_else(|| invalid("Invalid type"))?),
+                };
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| invalid("Stray property"))?;
+                element.2.push(property);
+            }
+            ["property", scalar, name] => {
+                let property = PlyProperty {
+                    name: name.to_string(),
+                    scalar: PlyScalar::parse(scalar).ok_or_else(|| invalid("Invalid type"))?,
+                    list_len: None,
+                };
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| invalid("Stray property"))?;
+                element.2.push(property);
+            }
+            _ => {}
+        }
+    }
+
+    let (mut positions, mut normals, mut triangles) = (vec![], vec![], vec![]);
+    for (element, count, properties) in &elements {
+        let has_normals = properties.iter().any(|property| property.name == "nx");
+        for _ in 0..*count {
+            let (mut position, mut normal, mut face) = (Vec3::ZERO, Vec3::ZERO, vec![]);
+            for property in properties {
+                let values = match property.list_len {
+                    Some(len) => {
+                        let len = len.read(&mut reader)? as usize;
+                        (0..len)
+                            .map(|_| property.scalar.read(&mut reader))
+                            .collect::<Result<Vec<_>, _>>()?
+                    }
+                    None => vec![property.scalar.read(&mut reader)?],
+                };
+                match property.name.as_str() {
+                    "x" => position.x = values[0] as f32,
+                    "y" => position.y = values[0] as f32,
+                    "z" => position.z = values[0] as f32,
+                    "nx" => normal.x = values[0] as f32,
+                    "ny" => normal.y = values[0] as f32,
+                    "nz" => normal.z = values[0] as f32,
+                    "vertex_indices" | "vertex_index" => {
+                        face = values.into_iter().map(|idx| idx as u32).collect()
+                    }
+                    _ => {}
+                }
+            }
+            match element.as_str() {
+                "vertex" => {
+                    positions.push(position);
+                    if has_normals {
+                        normals.push(normal);
+                    }
+                }
+                // Polygons are split into fans around their first corner
+                "face" => {
+                    triangles.extend((2..face.len()).map(|i| [face[0], face[i - 1], face[i]]))
+                }
+                _ => {}
+            }
+        }
+    }
+    if triangles
+        .iter()
+        .flatten()
+        .any(|&idx| idx as usize >= positions.len())
+    {
+        return Err(invalid("Face index out of bounds"));
+    }
+    Ok((positions, normals, triangles))
+}
@@ -0,0 +1,203 @@
+use crate::coordinates::{CoordBBox, Index};
+use crate::data_structure::{Grid, GridClass, GridDescriptor, Node, Node5, Tree};
+use crate::transform::Transform;
+
+use glam::{DVec2, DVec3, IVec3, Vec3};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Small offset applied to the rays used for inside/outside classification, so they don't pass
+/// exactly through the edges and vertices of meshes aligned with the voxel grid.
+const RAY_OFFSET: DVec2 = DVec2::new(1.234_567e-5, 2.345_678e-5);
+
+/// Closest point to `p` on the triangle `a`, `b`, `c`.
+fn closest_point_on_triangle(p: DVec3, a: DVec3, b: DVec3, c: DVec3) -> DVec3 {
+    let ab = b - a;
+    let ac = c - a;
+    let ap = p - a;
+    let d1 = ab.dot(ap);
+    let d2 = ac.dot(ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+
+    let bp = p - b;
+    let d3 = ab.dot(bp);
+    let d4 = ac.dot(bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return a + ab * (d1 / (d1 - d3));
+    }
+
+    let cp = p - c;
+    let d5 = ab.dot(cp);
+    let d6 = ac.dot(cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return a + ac * (d2 / (d2 - d6));
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+
+    let denom = 1.0 / (va + vb + vc);
+    a + ab * (vb * denom) + ac * (vc * denom)
+}
+
+/// Positions along x where rays parallel to the x axis through each row of voxels cross the
+/// mesh, used to classify voxels as inside or outside by counting crossings.
+struct RayCrossings(HashMap<(i32, i32), Vec<f64>>);
+
+impl RayCrossings {
+    fn new(triangles: &[[DVec3; 3]]) -> Self {
+        let mut rows = HashMap::<(i32, i32), Vec<f64>>::new();
+        for [a, b, c] in triangles {
+            let (a_yz, b_yz, c_yz) = (
+                DVec2::new(a.y, a.z),
+                DVec2::new(b.y, b.z),
+                DVec2::new(c.y, c.z),
+            );
+            let area = (b_yz - a_yz).perp_dot(c_yz - a_yz);
+            if area == 0.0 {
+                // Parallel to the rays
+                continue;
+            }
+            let min = a_yz.min(b_yz).min(c_yz) - RAY_OFFSET;
+            let max = a_yz.max(b_yz).max(c_yz) - RAY_OFFSET;
+            for y in min.x.ceil() as i32..=max.x.floor() as i32 {
+                for z in min.y.ceil() as i32..=max.y.floor() as i32 {
+                    let q = DVec2::new(y as f64, z as f64) + RAY_OFFSET;
+                    let u = (c_yz - b_yz).perp_dot(q - b_yz) / area;
+                    let v = (a_yz - c_yz).perp_dot(q - c_yz) / area;
+                    let w = 1.0 - u - v;
+                    if u >= 0.0 && v >= 0.0 && w >= 0.0 {
+                        rows.entry((y, z))
+                            .or_default()
+                            .push(a.x * u + b.x * v + c.x * w);
+                    }
+                }
+            }
+        }
+        for crossings in rows.values_mut() {
+            crossings.sort_unstable_by(f64::total_cmp);
+        }
+        Self(rows)
+    }
+
+    /// Whether `coord` is inside the mesh, i.e. the ray towards negative x crosses the mesh an
+    /// odd number of times.
+    fn is_inside(&self, coord: IVec3) -> bool {
+        self.0
+            .get(&(coord.y, coord.z))
+            .is_some_and(|crossings| crossings.partition_point(|&x| x < coord.x as f64) % 2 == 1)
+    }
+}
+
+/// Converts the closed triangle mesh given by `positions` and `indices` into a level set with
+/// voxels of `voxel_size` world units and a narrow band of `half_width` voxels on either side
+/// of the surface.
+///
+/// Distances within the band are exact distances to the closest triangle. Voxels are classified
+/// as inside or outside by casting rays through the mesh, so the mesh should be watertight;
+/// its winding order does not matter. `half_width` is raised to one voxel if smaller, so every
+/// point on the surface is covered by the band.
+pub fn mesh_to_volume<const L5: u32, const L4: u32, const L3: u32>(
+    positions: &[Vec3],
+    indices: &[[u32; 3]],
+    voxel_size: f64,
+    half_width: f32,
+) -> Grid<f32, L5, L4, L3> {
+    let half_width = half_width.max(1.0);
+    let background = half_width * voxel_size as f32;
+    let transform = Transform::from_voxel_size(voxel_size);
+
+    let triangles = indices
+        .iter()
+        .map(|triangle| {
+            triangle.map(|idx| transform.world_to_index_f64(positions[idx as usize].as_dvec3()))
+        })
+        .collect::<Vec<_>>();
+
+    // Unsigned distance in voxels to the closest triangle for every voxel within the band
+    let mut tree = Tree::<f32, L5, L4, L3>::new(half_width);
+    let mut mesh_bbox = CoordBBox::empty();
+    let band = half_width as f64;
+    for &[a, b, c] in &triangles {
+        let min = (a.min(b).min(c) - band).floor().as_ivec3();
+        let max = (a.max(b).max(c) + band).ceil().as_ivec3();
+        mesh_bbox.expand_bbox(&CoordBBox::new(min, max));
+        for coord in CoordBBox::new(min, max) {
+            let p = coord.as_dvec3();
+            let distance = p.distance(closest_point_on_triangle(p, a, b, c)) as f32;
+            if distance < half_width && distance < tree.get_value(coord) {
+                tree.set_value_on(coord, distance);
+            }
+        }
+    }
+
+    // Every root node overlapping the mesh is created, so the interior can be stored as tiles
+    let root_dim = Node5::<f32, L5, L4, L3>::VOXEL_DIM as i32;
+    if !mesh_bbox.is_empty() {
+        let min = Tree::<f32, L5, L4, L3>::root_origin(mesh_bbox.min);
+        for x in (min.x..=mesh_bbox.max.x).step_by(root_dim as usize) {
+            for y in (min.y..=mesh_bbox.max.y).step_by(root_dim as usize) {
+                for z in (min.z..=mesh_bbox.max.z).step_by(root_dim as usize) {
+                    tree.touch_root(IVec3::new(x, y, z));
+                }
+            }
+        }
+    }
+
+    // Sign every voxel and tile, tiles never straddle the surface as it is covered by the band
+    let rays = RayCrossings::new(&triangles);
+    let signed = |inside: bool, distance: f32| {
+        if inside {
+            -distance * voxel_size as f32
+        } else {
+            distance * voxel_size as f32
+        }
+    };
+    for node_5 in &mut tree.root_nodes {
+        for idx in 0..node_5.data.len() {
+            if !node_5.child_mask[idx] {
+                let origin = node_5.offset_to_global_coord(Index(idx as u32)).0;
+                node_5.data[idx] = signed(rays.is_inside(origin), half_width);
+            }
+        }
+        for node_4 in node_5.nodes.values_mut() {
+            for idx in 0..node_4.data.len() {
+                if !node_4.child_mask[idx] {
+                    let origin = node_4.offset_to_global_coord(Index(idx as u32)).0;
+                    node_4.data[idx] = signed(rays.is_inside(origin), half_width);
+                }
+            }
+            for node_3 in node_4.nodes.values_mut() {
+                let node_3 = Arc::make_mut(node_3);
+                for idx in 0..node_3.buffer.len() {
+                    let coord = node_3.offset_to_global_coord(Index(idx as u32)).0;
+                    node_3.buffer[idx] = signed(rays.is_inside(coord), node_3.buffer[idx]);
+                }
+            }
+        }
+    }
+    tree.background = background;
+    tree.prune_level_set();
+
+    let mut descriptor = GridDescriptor::new("", Tree::<f32, L5, L4, L3>::type_name("float"));
+    descriptor.set_grid_class(GridClass::LevelSet);
+    Grid {
+        tree,
+        transform,
+        descriptor,
+    }
+}
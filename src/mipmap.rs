@@ -0,0 +1,122 @@
+use crate::data_structure::{Grid, GridClass, Tree};
+use crate::transform::Transform;
+
+use glam::{DVec3, IVec3, Vec3};
+use std::collections::HashSet;
+
+/// Values that can be downsampled by [`create_mipmaps`].
+pub trait MipmapValue: Copy {
+    /// Mean of `values`, used for fog volumes and every other grid class.
+    fn average(values: &[Self]) -> Self;
+    /// Value of smallest magnitude among `values`, used for level sets so the surface doesn't
+    /// erode at coarser levels.
+    fn closest_to_zero(values: &[Self]) -> Self;
+}
+
+impl MipmapValue for f32 {
+    fn average(values: &[Self]) -> Self {
+        values.iter().sum::<f32>() / values.len() as f32
+    }
+
+    fn closest_to_zero(values: &[Self]) -> Self {
+        values
+            .iter()
+            .copied()
+            .min_by(|a, b| a.abs().total_cmp(&b.abs()))
+            .unwrap()
+    }
+}
+
+impl MipmapValue for f64 {
+    fn average(values: &[Self]) -> Self {
+        values.iter().sum::<f64>() / values.len() as f64
+    }
+
+    fn closest_to_zero(values: &[Self]) -> Self {
+        values
+            .iter()
+            .copied()
+            .min_by(|a, b| a.abs().total_cmp(&b.abs()))
+            .unwrap()
+    }
+}
+
+impl MipmapValue for Vec3 {
+    fn average(values: &[Self]) -> Self {
+        values.iter().sum::<Vec3>() / values.len() as f32
+    }
+
+    fn closest_to_zero(values: &[Self]) -> Self {
+        values
+            .iter()
+            .copied()
+            .min_by(|a, b| a.length_squared().total_cmp(&b.length_squared()))
+            .unwrap()
+    }
+}
+
+/// Halves the resolution of `grid`: every coarse voxel covers two by two by two voxels of
+/// `grid` and is active if any of them is.
+fn downsample<ValueTy: MipmapValue, const L5: u32, const L4: u32, const L3: u32>(
+    grid: &Grid<ValueTy, L5, L4, L3>,
+) -> Grid<ValueTy, L5, L4, L3> {
+    let reduce = if grid.grid_class() == GridClass::LevelSet {
+        ValueTy::closest_to_zero
+    } else {
+        ValueTy::average
+    };
+
+    let mut coarse_coords = HashSet::<IVec3>::new();
+    grid.tree
+        .for_each_active_voxel(|coord| _ = coarse_coords.insert(coord >> 1));
+    let mut coarse_coords = coarse_coords.into_iter().collect::<Vec<_>>();
+    coarse_coords.sort_by_key(|coord| coord.to_array());
+
+    let mut tree = Tree::<ValueTy, L5, L4, L3>::new(grid.tree.background);
+    let mut accessor = grid.tree.accessor();
+    let mut values = Vec::with_capacity(8);
+    for coarse in coarse_coords {
+        values.clear();
+        for corner in 0..8 {
+            let offset = IVec3::new(corner & 1, (corner >> 1) & 1, (corner >> 2) & 1);
+            values.push(accessor.get_value(coarse * 2 + offset));
+        }
+        tree.set_value_on(coarse, reduce(&values));
+    }
+
+    // Coarse voxels sit at the center of the fine voxels they cover
+    let coarse_to_fine = Transform::from_scale_translation(DVec3::splat(2.0), DVec3::splat(0.5));
+    Grid {
+        tree,
+        transform: coarse_to_fine.then(&grid.transform),
+        descriptor: grid.descriptor.clone(),
+    }
+}
+
+/// Builds a pyramid of `levels` grids of decreasing resolution, for level of detail rendering
+/// and coarse to fine algorithms, matching OpenVDB's `tools::MultiResGrid`.
+///
+/// The first grid is `grid` itself, and every following one halves the resolution of the
+/// previous one, with a transform that covers the same world space region with voxels twice
+/// as large. Downsampling is conservative: level sets keep the value closest to the surface of
+/// the voxels they cover, so thin features don't vanish, everything else keeps their average,
+/// which preserves the total density of fog volumes. Active tiles are split into voxels.
+///
+/// Level set values stay world space distances, so their narrow band covers fewer voxels at
+/// every level, use [`level_set_rebuild`] to widen it again where needed.
+///
+/// [`level_set_rebuild`]: crate::level_set_rebuild
+pub fn create_mipmaps<ValueTy: MipmapValue, const L5: u32, const L4: u32, const L3: u32>(
+    grid: &Grid<ValueTy, L5, L4, L3>,
+    levels: u32,
+) -> Vec<Grid<ValueTy, L5, L4, L3>> {
+    let mut mipmaps = Vec::with_capacity(levels as usize);
+    if levels > 0 {
+        mipmaps.push(grid.clone());
+    }
+    for level in 1..levels as usize {
+        let coarser = downsample(&mipmaps[level - 1]);
+        mipmaps.push(coarser);
+    }
+    mipmaps
+}
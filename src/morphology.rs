@@ -0,0 +1,128 @@
+use crate::coordinates::{CoordBBox, Index};
+use crate::data_structure::{active_tiles, Grid, Node, Tree};
+
+use glam::IVec3;
+
+/// Which neighbors of a voxel are considered by dilation and erosion.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NearestNeighbors {
+    /// The 6 voxels sharing a face
+    #[default]
+    Face,
+    /// The 18 voxels sharing a face or an edge
+    Edge,
+    /// The 26 voxels sharing a face, an edge or a corner
+    Vertex,
+}
+
+impl NearestNeighbors {
+    /// Offsets from a voxel to each of its neighbors.
+    pub fn offsets(self) -> Vec<IVec3> {
+        let max_nonzero = match self {
+            NearestNeighbors::Face => 1,
+            NearestNeighbors::Edge => 2,
+            NearestNeighbors::Vertex => 3,
+        };
+        CoordBBox::new(IVec3::NEG_ONE, IVec3::ONE)
+            .iter()
+            .filter(|offset| {
+                let nonzero = offset.abs().dot(IVec3::ONE);
+                nonzero > 0 && nonzero <= max_nonzero
+            })
+            .collect()
+    }
+}
+
+impl<ValueTy: Copy, const L5: u32, const L4: u32, const L3: u32> Tree<ValueTy, L5, L4, L3> {
+    /// Calls `f` for every active voxel that may have an inactive neighbor: all active voxels in
+    /// leaves, and the voxels on the surface of active tiles.
+    pub(crate) fn for_each_boundary_candidate(&self, mut f: impl FnMut(IVec3)) {
+        let mut tile_surface = |bbox: CoordBBox| {
+            for coord in bbox {
+                if coord.cmpeq(bbox.min).any() || coord.cmpeq(bbox.max).any() {
+                    f(coord);
+                }
+            }
+        };
+        for node_5 in &self.root_nodes {
+            for idx in active_tiles(&node_5.child_mask, &node_5.value_mask) {
+                tile_surface(node_5.tile_bbox(Index(idx as u32)));
+            }
+            for node_4 in node_5.nodes.values() {
+                for idx in active_tiles(&node_4.child_mask, &node_4.value_mask) {
+                    tile_surface(node_4.tile_bbox(Index(idx as u32)));
+                }
+            }
+        }
+        for node_3 in self.leaves() {
+            for idx in node_3.value_mask.iter_ones() {
+                f(node_3.offset_to_global_coord(Index(idx as u32)).0);
+            }
+        }
+    }
+
+    /// Grows the active region by one layer of voxels `iterations` times. Newly activated voxels
+    /// keep the value they had while inactive.
+    pub fn dilate_active_values(&mut self, iterations: u32, neighbors: NearestNeighbors) {
+        let offsets = neighbors.offsets();
+        for _ in 0..iterations {
+            let mut grow = vec![];
+            self.for_each_boundary_candidate(|coord| {
+                for &offset in &offsets {
+                    if !self.is_value_on(coord + offset) {
+                        grow.push(coord + offset);
+                    }
+                }
+            });
+            if grow.is_empty() {
+                break;
+            }
+            for coord in grow {
+                let value = self.get_value(coord);
+                self.set_value_on(coord, value);
+            }
+        }
+    }
+
+    /// Shrinks the active region by one layer of voxels `iterations` times, deactivating every
+    /// active voxel that has an inactive neighbor. Values are left unchanged.
+    pub fn erode_active_values(&mut self, iterations: u32, neighbors: NearestNeighbors) {
+        let offsets = neighbors.offsets();
+        for _ in 0..iterations {
+            let mut shrink = vec![];
+            self.for_each_boundary_candidate(|coord| {
+                if offsets
+                    .iter()
+                    .any(|&offset| !self.is_value_on(coord + offset))
+                {
+                    shrink.push(coord);
+                }
+            });
+            if shrink.is_empty() {
+                break;
+            }
+            for coord in shrink {
+                let value = self.get_value(coord);
+                self.set_value_off(coord, value);
+            }
+        }
+    }
+}
+
+/// Grows the active region of `grid`, see [`Tree::dilate_active_values`].
+pub fn dilate_active_values<ValueTy: Copy, const L5: u32, const L4: u32, const L3: u32>(
+    grid: &mut Grid<ValueTy, L5, L4, L3>,
+    iterations: u32,
+    neighbors: NearestNeighbors,
+) {
+    grid.tree.dilate_active_values(iterations, neighbors);
+}
+
+/// Shrinks the active region of `grid`, see [`Tree::erode_active_values`].
+pub fn erode_active_values<ValueTy: Copy, const L5: u32, const L4: u32, const L3: u32>(
+    grid: &mut Grid<ValueTy, L5, L4, L3>,
+    iterations: u32,
+    neighbors: NearestNeighbors,
+) {
+    grid.tree.erode_active_values(iterations, neighbors);
+}
@@ -0,0 +1,868 @@
+use crate::coordinates::{CoordBBox, Index};
+use crate::data_structure::{
+    load_mask_words, mask_words, Grid, GridClass, GridDescriptor, MaskWord, Node, Node3, Node4,
+    Node5, Tree,
+};
+use crate::transform::Transform;
+
+use bitvec::prelude::*;
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
+use glam::{DMat4, DVec3, IVec3, Vec3};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Magic number starting NanoVDB grids and files, "NanoVDB0" in little endian.
+const MAGIC: u64 = 0x3042_4456_6f6e_614e;
+/// Magic number of grids written by later NanoVDB versions, "NanoVDB1".
+const MAGIC_GRID: u64 = 0x3142_4456_6f6e_614e;
+/// Magic number of files written by later NanoVDB versions, "NanoVDB2".
+const MAGIC_FILE: u64 = 0x3242_4456_6f6e_614e;
+
+/// NanoVDB version 32.4.2, packed as `major << 21 | minor << 10 | patch`.
+const VERSION: u32 = (32 << 21) | (4 << 10) | 2;
+
+/// Every node and header in a NanoVDB buffer starts at a multiple of this many bytes.
+const ALIGNMENT: usize = 32;
+
+const GRID_DATA_SIZE: usize = 672;
+const TREE_DATA_SIZE: usize = 64;
+const MAX_NAME_SIZE: usize = 256;
+const FILE_HEADER_SIZE: usize = 16;
+const FILE_METADATA_SIZE: usize = 176;
+
+/// Grid flags stating that bounding boxes are set and nodes are stored breadth first.
+const GRID_FLAGS: u32 = 2 | 32;
+/// Leaf flag stating that the bounding box of the leaf is set.
+const LEAF_HAS_BBOX: u8 = 2;
+
+const fn align(size: usize) -> usize {
+    (size + ALIGNMENT - 1) & !(ALIGNMENT - 1)
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum NanoVdbError {
+    #[error("Magic bytes mismatched")]
+    MagicMismatch,
+    #[error("Unsupported NanoVDB version {0}")]
+    UnsupportedVersion(u32),
+    #[error("Grid type {0} doesn't match the requested value type")]
+    GridTypeMismatch(u32),
+    #[error("Unsupported codec {0}")]
+    UnsupportedCodec(u16),
+    #[error("Offset {0} lies outside of the buffer")]
+    OutOfBounds(usize),
+    #[error("IoError")]
+    IoError(#[from] std::io::Error),
+}
+
+/// Values that can be stored in NanoVDB grids, see [`to_nanovdb`] and [`from_nanovdb`].
+pub trait NanoVdbValue: Copy + PartialEq {
+    /// Value of NanoVDB's `GridType` enum for grids of this value type
+    const GRID_TYPE: u32;
+    /// OpenVDB name of this value type, see [`Tree::type_name`]
+    const TYPE_NAME: &'static str;
+    /// Size of a value in bytes
+    const SIZE: usize;
+
+    fn write(self, out: &mut Vec<u8>);
+
+    /// Reads a value from the first [`NanoVdbValue::SIZE`] bytes of `bytes`.
+    fn read(bytes: &[u8]) -> Self;
+}
+
+impl NanoVdbValue for f32 {
+    const GRID_TYPE: u32 = 1;
+    const TYPE_NAME: &'static str = "float";
+    const SIZE: usize = 4;
+
+    fn write(self, out: &mut Vec<u8>) {
+        out.write_f32::<LittleEndian>(self).unwrap();
+    }
+
+    fn read(bytes: &[u8]) -> Self {
+        LittleEndian::read_f32(bytes)
+    }
+}
+
+impl NanoVdbValue for Vec3 {
+    const GRID_TYPE: u32 = 6;
+    const TYPE_NAME: &'static str = "vec3s";
+    const SIZE: usize = 12;
+
+    fn write(self, out: &mut Vec<u8>) {
+        for component in self.to_array() {
+            out.write_f32::<LittleEndian>(component).unwrap();
+        }
+    }
+
+    fn read(bytes: &[u8]) -> Self {
+        let mut components = [0.0; 3];
+        LittleEndian::read_f32_into(&bytes[..12], &mut components);
+        Vec3::from_array(components)
+    }
+}
+
+/// Sizes of the headers and nodes of a NanoVDB tree with values of type `ValueTy`. Statistics
+/// of both supported value types are single precision floats.
+struct Layout<ValueTy>(std::marker::PhantomData<ValueTy>);
+
+impl<ValueTy: NanoVdbValue> Layout<ValueTy> {
+    const STATS_SIZE: usize = 4;
+    /// Size of the minimum, maximum, average and standard deviation stored with every node
+    const STATISTICS: usize = 2 * ValueTy::SIZE + 2 * Self::STATS_SIZE;
+    const ROOT_SIZE: usize = align(24 + 4 + ValueTy::SIZE + Self::STATISTICS);
+    const ROOT_TILE_SIZE: usize = align(8 + 8 + 4 + ValueTy::SIZE);
+    /// Entries of internal node tables hold either a value or a 64-bit child offset
+    const TABLE_ENTRY_SIZE: usize = (if ValueTy::SIZE > 8 { ValueTy::SIZE } else { 8 } + 7) & !7;
+
+    /// Size of the bounding box, flags, masks and statistics preceding the table of an
+    /// internal node
+    const fn internal_header_size(log2_dim: usize) -> usize {
+        let mask_size = (1 << (3 * log2_dim)) / 8;
+        align(24 + 8 + 2 * mask_size + Self::STATISTICS)
+    }
+
+    const fn internal_size(log2_dim: usize) -> usize {
+        align(Self::internal_header_size(log2_dim) + (1 << (3 * log2_dim)) * Self::TABLE_ENTRY_SIZE)
+    }
+
+    const UPPER_SIZE: usize = Self::internal_size(5);
+    const LOWER_SIZE: usize = Self::internal_size(4);
+    const LEAF_HEADER_SIZE: usize = align(12 + 3 + 1 + 64 + Self::STATISTICS);
+    const LEAF_SIZE: usize = align(Self::LEAF_HEADER_SIZE + 512 * ValueTy::SIZE);
+}
+
+/// Appends zeros up to `position` bytes, the start of the next field.
+fn pad_to(out: &mut Vec<u8>, position: usize) {
+    debug_assert!(out.len() <= position);
+    out.resize(position, 0);
+}
+
+fn write_coord(out: &mut Vec<u8>, coord: IVec3) {
+    for component in coord.to_array() {
+        out.write_i32::<LittleEndian>(component).unwrap();
+    }
+}
+
+fn write_bbox(out: &mut Vec<u8>, bbox: &CoordBBox, origin: IVec3) {
+    // Empty nodes report a degenerate box at their origin, from which NanoVDB derives it
+    if bbox.is_empty() {
+        write_coord(out, origin);
+        write_coord(out, origin);
+    } else {
+        write_coord(out, bbox.min);
+        write_coord(out, bbox.max);
+    }
+}
+
+fn write_dvec3(out: &mut Vec<u8>, v: DVec3) {
+    for component in v.to_array() {
+        out.write_f64::<LittleEndian>(component).unwrap();
+    }
+}
+
+/// Name of `grid` as stored by NanoVDB, truncated to leave room for the terminating zero.
+fn grid_name<ValueTy>(grid: &Grid<ValueTy>) -> &[u8] {
+    let name = grid.descriptor.name.as_bytes();
+    &name[..name.len().min(MAX_NAME_SIZE - 1)]
+}
+
+/// Value of NanoVDB's `GridClass` enum for `grid`.
+fn grid_class<ValueTy>(grid: &Grid<ValueTy>) -> u32 {
+    match grid.grid_class() {
+        GridClass::LevelSet => 1,
+        GridClass::FogVolume => 2,
+        GridClass::Staggered => 3,
+        GridClass::Unknown => 0,
+    }
+}
+
+/// World space bounds of the index space region covered by the voxels in `bbox`.
+fn world_bbox<ValueTy>(grid: &Grid<ValueTy>, bbox: &CoordBBox) -> (DVec3, DVec3) {
+    if bbox.is_empty() {
+        return (DVec3::ZERO, DVec3::ZERO);
+    }
+    let (min, max) = (bbox.min.as_dvec3(), (bbox.max + 1).as_dvec3());
+    let mut world_min = DVec3::splat(f64::INFINITY);
+    let mut world_max = DVec3::splat(f64::NEG_INFINITY);
+    for corner in 0..8 {
+        let select = |bit: i32, axis: usize| {
+            if corner & bit == 0 {
+                min[axis]
+            } else {
+                max[axis]
+            }
+        };
+        let index = DVec3::new(select(1, 0), select(2, 1), select(4, 2));
+        let world = grid.transform.index_to_world_f64(index);
+        world_min = world_min.min(world);
+        world_max = world_max.max(world);
+    }
+    (world_min, world_max)
+}
+
+/// Bounding box of the active voxels and tiles of `node_3`.
+fn leaf_bbox<ValueTy>(node_3: &Node3<ValueTy>) -> CoordBBox {
+    let mut bbox = CoordBBox::empty();
+    for idx in node_3.value_mask.iter_ones() {
+        let coord = node_3.offset_to_global_coord(Index(idx as u32));
+        bbox.expand_coord(coord.0);
+    }
+    bbox
+}
+
+/// Bounding box of the active voxels and tiles of `node_4`.
+fn lower_bbox<ValueTy>(node_4: &Node4<ValueTy>) -> CoordBBox {
+    let mut bbox = CoordBBox::empty();
+    for idx in 0..node_4.data.len() {
+        if node_4.child_mask[idx] {
+            bbox.expand_bbox(&leaf_bbox(&node_4.nodes[&(idx as u32)]));
+        } else if node_4.value_mask[idx] {
+            bbox.expand_bbox(&node_4.tile_bbox(Index(idx as u32)));
+        }
+    }
+    bbox
+}
+
+/// Bounding box of the active voxels and tiles of `node_5`.
+fn upper_bbox<ValueTy>(node_5: &Node5<ValueTy>) -> CoordBBox {
+    let mut bbox = CoordBBox::empty();
+    for idx in 0..node_5.data.len() {
+        if node_5.child_mask[idx] {
+            bbox.expand_bbox(&lower_bbox(&node_5.nodes[&(idx as u32)]));
+        } else if node_5.value_mask[idx] {
+            bbox.expand_bbox(&node_5.tile_bbox(Index(idx as u32)));
+        }
+    }
+    bbox
+}
+
+/// Writes the header and table of an internal node, with `child_offset` giving the byte offset
+/// from this node to its child at a table index.
+#[allow(clippy::too_many_arguments)]
+fn write_internal<ValueTy: NanoVdbValue>(
+    out: &mut Vec<u8>,
+    bbox: &CoordBBox,
+    origin: IVec3,
+    value_mask: &[u64],
+    child_mask: &[u64],
+    data: &[ValueTy],
+    is_child: impl Fn(usize) -> bool,
+    mut child_offset: impl FnMut(usize) -> i64,
+    log2_dim: usize,
+) {
+    let start = out.len();
+    let mask_words = (1 << (3 * log2_dim)) / 64;
+    write_bbox(out, bbox, origin);
+    out.write_u64::<LittleEndian>(0).unwrap();
+    for mask in [value_mask, child_mask] {
+        for word in 0..mask_words {
+            out.write_u64::<LittleEndian>(mask.get(word).copied().unwrap_or(0))
+                .unwrap();
+        }
+    }
+    // Statistics are not computed, the grid flags don't claim them
+    out.resize(out.len() + Layout::<ValueTy>::STATISTICS, 0);
+    pad_to(out, align(out.len()));
+    for (idx, &value) in data.iter().enumerate() {
+        let entry = out.len();
+        if is_child(idx) {
+            out.write_i64::<LittleEndian>(child_offset(idx)).unwrap();
+        } else {
+            value.write(out);
+        }
+        pad_to(out, entry + Layout::<ValueTy>::TABLE_ENTRY_SIZE);
+    }
+    pad_to(out, align(out.len()));
+    debug_assert_eq!(
+        out.len() - start,
+        Layout::<ValueTy>::internal_size(log2_dim)
+    );
+}
+
+/// Converts `grid` into a NanoVDB grid buffer, the format GPU renderers and compute shaders
+/// read directly, matching the layout of NanoVDB version 32.4.
+///
+/// Nodes are stored breadth first and their bounding boxes are set, while node statistics
+/// such as minimum and maximum values are left zero and flagged as missing. Grid names are
+/// truncated to 255 bytes. Checksums are disabled.
+pub fn to_nanovdb<ValueTy: NanoVdbValue>(grid: &Grid<ValueTy>) -> Vec<u8> {
+    type L<V> = Layout<V>;
+    let tree = &grid.tree;
+
+    // Nodes in breadth first order, upper nodes in the order of their root keys and children in
+    // the order of the tables of their parents
+    let key = |origin: IVec3| {
+        let [x, y, z] = origin.to_array().map(|c| (c as u32 >> 12) as u64);
+        z | (y << 21) | (x << 42)
+    };
+    let mut uppers = tree.root_nodes.iter().collect::<Vec<_>>();
+    uppers.sort_by_key(|node_5| key(node_5.origin));
+    let lowers = uppers
+        .iter()
+        .flat_map(|node_5| {
+            (0..node_5.data.len())
+                .filter(|&idx| node_5.child_mask[idx])
+                .map(|idx| &node_5.nodes[&(idx as u32)])
+        })
+        .collect::<Vec<_>>();
+    let leaves = lowers
+        .iter()
+        .flat_map(|node_4| {
+            (0..node_4.data.len())
+                .filter(|&idx| node_4.child_mask[idx])
+                .map(|idx| node_4.nodes[&(idx as u32)].as_ref())
+        })
+        .collect::<Vec<_>>();
+
+    let tree_offset = GRID_DATA_SIZE;
+    let root_offset = tree_offset + TREE_DATA_SIZE;
+    let upper_offset =
+        root_offset + L::<ValueTy>::ROOT_SIZE + uppers.len() * L::<ValueTy>::ROOT_TILE_SIZE;
+    let lower_offset = upper_offset + uppers.len() * L::<ValueTy>::UPPER_SIZE;
+    let leaf_offset = lower_offset + lowers.len() * L::<ValueTy>::LOWER_SIZE;
+    let grid_size = leaf_offset + leaves.len() * L::<ValueTy>::LEAF_SIZE;
+
+    let bbox = tree.eval_active_voxel_bounding_box();
+    let count_tiles = |mask: &bitvec::vec::BitVec<MaskWord>,
+                       child: &bitvec::vec::BitVec<MaskWord>| {
+        (0..mask.len())
+            .filter(|&idx| mask[idx] && !child[idx])
+            .count() as u32
+    };
+    let lower_tiles = lowers
+        .iter()
+        .map(|node_4| count_tiles(&node_4.value_mask, &node_4.child_mask))
+        .sum::<u32>();
+    let upper_tiles = uppers
+        .iter()
+        .map(|node_5| count_tiles(&node_5.value_mask, &node_5.child_mask))
+        .sum::<u32>();
+
+    let mut out = Vec::with_capacity(grid_size);
+
+    // GridData
+    out.write_u64::<LittleEndian>(MAGIC).unwrap();
+    out.write_u64::<LittleEndian>(u64::MAX).unwrap();
+    out.write_u32::<LittleEndian>(VERSION).unwrap();
+    out.write_u32::<LittleEndian>(GRID_FLAGS).unwrap();
+    out.write_u32::<LittleEndian>(0).unwrap();
+    out.write_u32::<LittleEndian>(1).unwrap();
+    out.write_u64::<LittleEndian>(grid_size as u64).unwrap();
+    out.write_all(grid_name(grid)).unwrap();
+    pad_to(&mut out, 40 + MAX_NAME_SIZE);
+
+    // Map, the index to world matrix in row major order in single and double precision
+    let matrix = grid.transform.map.to_matrix();
+    let inverse = matrix.inverse();
+    let rows = |m: glam::DMat4| {
+        let mut values = [0.0; 9];
+        for (row, values) in values.chunks_mut(3).enumerate() {
+            for (column, value) in values.iter_mut().enumerate() {
+                *value = m.col(column)[row];
+            }
+        }
+        values
+    };
+    let translation = matrix.w_axis.truncate();
+    for m in [matrix, inverse] {
+        for value in rows(m) {
+            out.write_f32::<LittleEndian>(value as f32).unwrap();
+        }
+    }
+    for value in translation.to_array() {
+        out.write_f32::<LittleEndian>(value as f32).unwrap();
+    }
+    out.write_f32::<LittleEndian>(1.0).unwrap();
+    for m in [matrix, inverse] {
+        for value in rows(m) {
+            out.write_f64::<LittleEndian>(value).unwrap();
+        }
+    }
+    write_dvec3(&mut out, translation);
+    out.write_f64::<LittleEndian>(1.0).unwrap();
+
+    let (world_min, world_max) = world_bbox(grid, &bbox);
+    write_dvec3(&mut out, world_min);
+    write_dvec3(&mut out, world_max);
+    write_dvec3(&mut out, grid.transform.voxel_size());
+    out.write_u32::<LittleEndian>(grid_class(grid)).unwrap();
+    out.write_u32::<LittleEndian>(ValueTy::GRID_TYPE).unwrap();
+    // No blind data, followed by unused fields
+    out.write_i64::<LittleEndian>(0).unwrap();
+    out.write_u32::<LittleEndian>(0).unwrap();
+    pad_to(&mut out, GRID_DATA_SIZE);
+
+    // TreeData, offsets to the first leaf, lower, upper and root node relative to the tree
+    for (count, offset) in [
+        (leaves.len(), leaf_offset),
+        (lowers.len(), lower_offset),
+        (uppers.len(), upper_offset),
+    ] {
+        let offset = if count > 0 { offset - tree_offset } else { 0 };
+        out.write_u64::<LittleEndian>(offset as u64).unwrap();
+    }
+    out.write_u64::<LittleEndian>((root_offset - tree_offset) as u64)
+        .unwrap();
+    for count in [leaves.len(), lowers.len(), uppers.len()] {
+        out.write_u32::<LittleEndian>(count as u32).unwrap();
+    }
+    for count in [lower_tiles, upper_tiles, 0] {
+        out.write_u32::<LittleEndian>(count).unwrap();
+    }
+    out.write_u64::<LittleEndian>(tree.active_voxel_count())
+        .unwrap();
+    debug_assert_eq!(out.len(), root_offset);
+
+    // RootData followed by its tiles, which all point to upper nodes
+    write_bbox(&mut out, &bbox, IVec3::ZERO);
+    out.write_u32::<LittleEndian>(uppers.len() as u32).unwrap();
+    tree.background.write(&mut out);
+    out.resize(out.len() + L::<ValueTy>::STATISTICS, 0);
+    pad_to(&mut out, root_offset + L::<ValueTy>::ROOT_SIZE);
+    for (idx, node_5) in uppers.iter().enumerate() {
+        let tile = out.len();
+        out.write_u64::<LittleEndian>(key(node_5.origin)).unwrap();
+        let child = upper_offset + idx * L::<ValueTy>::UPPER_SIZE;
+        out.write_i64::<LittleEndian>((child - root_offset) as i64)
+            .unwrap();
+        out.write_u32::<LittleEndian>(0).unwrap();
+        tree.background.write(&mut out);
+        pad_to(&mut out, tile + L::<ValueTy>::ROOT_TILE_SIZE);
+    }
+    debug_assert_eq!(out.len(), upper_offset);
+
+    let mut next_lower = 0;
+    for node_5 in &uppers {
+        let node_offset = out.len();
+        let mut child = 0;
+        write_internal(
+            &mut out,
+            &upper_bbox(node_5),
+            node_5.origin,
+            &mask_words(&node_5.value_mask),
+            &mask_words(&node_5.child_mask),
+            &node_5.data,
+            |idx| node_5.child_mask[idx],
+            |_| {
+                let offset = lower_offset + (next_lower + child) * L::<ValueTy>::LOWER_SIZE;
+                child += 1;
+                (offset - node_offset) as i64
+            },
+            5,
+        );
+        next_lower += child;
+    }
+
+    let mut next_leaf = 0;
+    for node_4 in &lowers {
+        let node_offset = out.len();
+        let mut child = 0;
+        write_internal(
+            &mut out,
+            &lower_bbox(node_4),
+            node_4.origin,
+            &mask_words(&node_4.value_mask),
+            &mask_words(&node_4.child_mask),
+            &node_4.data,
+            |idx| node_4.child_mask[idx],
+            |_| {
+                let offset = leaf_offset + (next_leaf + child) * L::<ValueTy>::LEAF_SIZE;
+                child += 1;
+                (offset - node_offset) as i64
+            },
+            4,
+        );
+        next_leaf += child;
+    }
+
+    for node_3 in &leaves {
+        let leaf = out.len();
+        let bbox = leaf_bbox(node_3);
+        if bbox.is_empty() {
+            write_coord(&mut out, node_3.origin);
+            out.write_all(&[0; 3]).unwrap();
+            out.write_u8(0).unwrap();
+        } else {
+            write_coord(&mut out, bbox.min);
+            let extent = bbox.max - bbox.min;
+            out.write_all(&extent.to_array().map(|c| c as u8)).unwrap();
+            out.write_u8(LEAF_HAS_BBOX).unwrap();
+        }
+        for word in mask_words(&node_3.value_mask) {
+            out.write_u64::<LittleEndian>(word).unwrap();
+        }
+        out.resize(out.len() + L::<ValueTy>::STATISTICS, 0);
+        pad_to(&mut out, leaf + L::<ValueTy>::LEAF_HEADER_SIZE);
+        for &value in &node_3.buffer {
+            value.write(&mut out);
+        }
+        pad_to(&mut out, leaf + L::<ValueTy>::LEAF_SIZE);
+    }
+    debug_assert_eq!(out.len(), grid_size);
+    out
+}
+
+/// Hash of grid names stored in NanoVDB files to speed up lookups by name.
+fn name_key(name: &[u8]) -> u64 {
+    let mut hash = 0u64;
+    for &byte in name {
+        let overflow = hash >> (64 - 8);
+        hash = hash.wrapping_mul(67).wrapping_add(byte as u64 + overflow);
+    }
+    hash
+}
+
+/// Writes `grid` to a `.nvdb` file at `path`, an uncompressed NanoVDB file holding the
+/// buffer produced by [`to_nanovdb`].
+pub fn write_nanovdb<ValueTy: NanoVdbValue>(
+    grid: &Grid<ValueTy>,
+    path: impl AsRef<Path>,
+) -> std::io::Result<()> {
+    let buffer = to_nanovdb(grid);
+    let mut out = vec![];
+
+    // File header: magic, version, grid count and codec
+    out.write_u64::<LittleEndian>(MAGIC)?;
+    out.write_u32::<LittleEndian>(VERSION)?;
+    out.write_u16::<LittleEndian>(1)?;
+    out.write_u16::<LittleEndian>(0)?;
+
+    // Grid metadata, repeating what readers need to find grids without parsing them
+    let name = grid_name(grid);
+    let bbox = grid.tree.eval_active_voxel_bounding_box();
+    out.write_u64::<LittleEndian>(buffer.len() as u64)?;
+    out.write_u64::<LittleEndian>(buffer.len() as u64)?;
+    out.write_u64::<LittleEndian>(name_key(name))?;
+    out.write_u64::<LittleEndian>(grid.tree.active_voxel_count())?;
+    out.write_u32::<LittleEndian>(ValueTy::GRID_TYPE)?;
+    out.write_u32::<LittleEndian>(grid_class(grid))?;
+    let (world_min, world_max) = world_bbox(grid, &bbox);
+    write_dvec3(&mut out, world_min);
+    write_dvec3(&mut out, world_max);
+    write_bbox(&mut out, &bbox, IVec3::ZERO);
+    write_dvec3(&mut out, grid.transform.voxel_size());
+    out.write_u32::<LittleEndian>(name.len() as u32 + 1)?;
+    // Leaf, lower and upper node counts followed by the tile counts, copied from the tree
+    let tree = &buffer[GRID_DATA_SIZE..GRID_DATA_SIZE + TREE_DATA_SIZE];
+    out.write_all(&tree[32..44])?;
+    out.write_u32::<LittleEndian>(1)?;
+    out.write_all(&tree[44..56])?;
+    out.write_u16::<LittleEndian>(0)?;
+    out.write_u16::<LittleEndian>(0)?;
+    out.write_u32::<LittleEndian>(VERSION)?;
+    out.write_all(name)?;
+    out.write_u8(0)?;
+
+    out.write_all(&buffer)?;
+    std::fs::write(path, out)
+}
+
+/// Bounds checked little endian reads from a NanoVDB buffer.
+struct Buffer<'a>(&'a [u8]);
+
+impl<'a> Buffer<'a> {
+    fn bytes(&self, offset: usize, len: usize) -> Result<&'a [u8], NanoVdbError> {
+        offset
+            .checked_add(len)
+            .and_then(|end| self.0.get(offset..end))
+            .ok_or(NanoVdbError::OutOfBounds(offset))
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16, NanoVdbError> {
+        Ok(LittleEndian::read_u16(self.bytes(offset, 2)?))
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32, NanoVdbError> {
+        Ok(LittleEndian::read_u32(self.bytes(offset, 4)?))
+    }
+
+    fn u64(&self, offset: usize) -> Result<u64, NanoVdbError> {
+        Ok(LittleEndian::read_u64(self.bytes(offset, 8)?))
+    }
+
+    fn f64(&self, offset: usize) -> Result<f64, NanoVdbError> {
+        Ok(LittleEndian::read_f64(self.bytes(offset, 8)?))
+    }
+
+    fn coord(&self, offset: usize) -> Result<IVec3, NanoVdbError> {
+        let mut components = [0; 3];
+        LittleEndian::read_i32_into(self.bytes(offset, 12)?, &mut components);
+        Ok(IVec3::from_array(components))
+    }
+
+    fn value<ValueTy: NanoVdbValue>(&self, offset: usize) -> Result<ValueTy, NanoVdbError> {
+        Ok(ValueTy::read(self.bytes(offset, ValueTy::SIZE)?))
+    }
+
+    fn mask(&self, offset: usize, words: usize) -> Result<BitVec<MaskWord, Lsb0>, NanoVdbError> {
+        let mut words = vec![0; words];
+        LittleEndian::read_u64_into(self.bytes(offset, 8 * words.len())?, &mut words);
+        let mut mask = bitvec![MaskWord, Lsb0; 0; 64 * words.len()];
+        load_mask_words(&mut mask, &words);
+        Ok(mask)
+    }
+
+    /// Position of a node stored `relative` bytes after the node at `base`.
+    fn child(&self, base: usize, relative: u64) -> Result<usize, NanoVdbError> {
+        base.checked_add_signed(relative as i64 as isize)
+            .filter(|&offset| offset < self.0.len())
+            .ok_or(NanoVdbError::OutOfBounds(base))
+    }
+}
+
+fn check_version(version: u32) -> Result<(), NanoVdbError> {
+    if version >> 21 == VERSION >> 21 {
+        Ok(())
+    } else {
+        Err(NanoVdbError::UnsupportedVersion(version))
+    }
+}
+
+fn read_leaf<ValueTy: NanoVdbValue>(
+    buffer: &Buffer,
+    leaf: usize,
+) -> Result<Node3<ValueTy>, NanoVdbError> {
+    type L<V> = Layout<V>;
+    let values = leaf + L::<ValueTy>::LEAF_HEADER_SIZE;
+    let buffer_values = (0..512)
+        .map(|idx| buffer.value(values + idx * ValueTy::SIZE))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Node3 {
+        buffer: buffer_values,
+        value_mask: buffer.mask(leaf + 16, 8)?,
+        origin: buffer.coord(leaf)? & !7,
+    })
+}
+
+/// Reads the header and table of the internal node at `node`, returning its origin, masks and
+/// table values along with the positions of its children by table index.
+#[allow(clippy::type_complexity)]
+fn read_internal<ValueTy: NanoVdbValue>(
+    buffer: &Buffer,
+    node: usize,
+    log2_dim: usize,
+    background: ValueTy,
+) -> Result<
+    (
+        IVec3,
+        BitVec<MaskWord, Lsb0>,
+        BitVec<MaskWord, Lsb0>,
+        Vec<ValueTy>,
+        Vec<(u32, usize)>,
+    ),
+    NanoVdbError,
+> {
+    type L<V> = Layout<V>;
+    let mask_words = (1 << (3 * log2_dim)) / 64;
+    let dim = 1 << (log2_dim + if log2_dim == 5 { 7 } else { 3 });
+    let origin = buffer.coord(node)? & !(dim - 1);
+    let value_mask = buffer.mask(node + 32, mask_words)?;
+    let child_mask = buffer.mask(node + 32 + 8 * mask_words, mask_words)?;
+    let table = node + L::<ValueTy>::internal_header_size(log2_dim);
+    let mut data = Vec::with_capacity(child_mask.len());
+    let mut children = vec![];
+    for idx in 0..child_mask.len() {
+        let entry = table + idx * L::<ValueTy>::TABLE_ENTRY_SIZE;
+        if child_mask[idx] {
+            children.push((idx as u32, buffer.child(node, buffer.u64(entry)?)?));
+            data.push(background);
+        } else {
+            data.push(buffer.value(entry)?);
+        }
+    }
+    Ok((origin, value_mask, child_mask, data, children))
+}
+
+/// Converts a NanoVDB grid buffer, such as those produced by [`to_nanovdb`], back into a
+/// [`Grid`] so it can be inspected and edited, reading the layout of NanoVDB version 32.
+///
+/// Values, active states and tiles of every level are restored. The grid class and transform
+/// come from the grid header, node statistics and blind data are ignored. Root level tiles
+/// that are active or differ from the background become uniform upper nodes.
+pub fn from_nanovdb<ValueTy: NanoVdbValue>(buffer: &[u8]) -> Result<Grid<ValueTy>, NanoVdbError> {
+    type L<V> = Layout<V>;
+    let buffer = Buffer(buffer);
+    if ![MAGIC, MAGIC_GRID].contains(&buffer.u64(0)?) {
+        return Err(NanoVdbError::MagicMismatch);
+    }
+    check_version(buffer.u32(16)?)?;
+    let grid_type = buffer.u32(636)?;
+    if grid_type != ValueTy::GRID_TYPE {
+        return Err(NanoVdbError::GridTypeMismatch(grid_type));
+    }
+    let grid_size = buffer.u64(32)? as usize;
+    let buffer = Buffer(buffer.bytes(0, grid_size)?);
+
+    let name = buffer_name(&buffer)?;
+
+    // Index to world matrix in double precision, stored row major
+    let mut matrix = DMat4::IDENTITY;
+    for row in 0..3 {
+        for column in 0..3 {
+            matrix.col_mut(column)[row] = buffer.f64(384 + 8 * (3 * row + column))?;
+        }
+        matrix.w_axis[row] = buffer.f64(528 + 8 * row)?;
+    }
+
+    let root = GRID_DATA_SIZE + buffer.u64(GRID_DATA_SIZE + 24)? as usize;
+    let tile_count = buffer.u32(root + 24)? as usize;
+    let background = buffer.value::<ValueTy>(root + 28)?;
+    let mut tree = Tree::<ValueTy>::new(background);
+    for tile in 0..tile_count {
+        let tile = root + L::<ValueTy>::ROOT_SIZE + tile * L::<ValueTy>::ROOT_TILE_SIZE;
+        let key = buffer.u64(tile)?;
+        let origin = [42, 21, 0].map(|shift| (((key >> shift) as u32 & 0x1f_ffff) << 12) as i32);
+        let origin = IVec3::from_array(origin);
+        let child = buffer.u64(tile + 8)?;
+        if child == 0 {
+            let active = buffer.u32(tile + 16)? != 0;
+            let value = buffer.value::<ValueTy>(tile + 20)?;
+            if active || value != background {
+                tree.root_nodes.push(Node5::new(origin, value, active));
+            }
+            continue;
+        }
+
+        let upper = buffer.child(root, child)?;
+        let (_, value_mask, child_mask, data, children) =
+            read_internal(&buffer, upper, 5, background)?;
+        let mut nodes = HashMap::with_capacity(children.len());
+        for (idx, lower) in children {
+            let (origin, value_mask, child_mask, data, children) =
+                read_internal(&buffer, lower, 4, background)?;
+            let leaves = children
+                .into_iter()
+                .map(|(idx, leaf)| Ok((idx, Arc::new(read_leaf(&buffer, leaf)?))))
+                .collect::<Result<_, NanoVdbError>>()?;
+            let node_4 = Node4 {
+                child_mask,
+                value_mask,
+                nodes: leaves,
+                data,
+                origin,
+            };
+            nodes.insert(idx, node_4);
+        }
+        tree.root_nodes.push(Node5 {
+            child_mask,
+            value_mask,
+            nodes,
+            data,
+            origin,
+        });
+    }
+
+    let mut grid = Grid {
+        tree,
+        transform: Transform::from_matrix_simplified(matrix),
+        descriptor: GridDescriptor::new(name, Tree::<ValueTy>::type_name(ValueTy::TYPE_NAME)),
+    };
+    grid.set_grid_class(match buffer.u32(632)? {
+        1 => GridClass::LevelSet,
+        2 => GridClass::FogVolume,
+        3 => GridClass::Staggered,
+        _ => GridClass::Unknown,
+    });
+    Ok(grid)
+}
+
+/// Grid types and buffers of the grids stored in the `.nvdb` file `file`, in order.
+fn file_grids(file: &[u8]) -> Result<Vec<(u32, &[u8])>, NanoVdbError> {
+    let buffer = Buffer(file);
+    let mut grids = vec![];
+    let mut segment = 0;
+    while segment < file.len() {
+        if ![MAGIC, MAGIC_FILE].contains(&buffer.u64(segment)?) {
+            return Err(NanoVdbError::MagicMismatch);
+        }
+        check_version(buffer.u32(segment + 8)?)?;
+        let grid_count = buffer.u16(segment + 12)? as usize;
+        let codec = buffer.u16(segment + 14)?;
+        if codec != 0 {
+            return Err(NanoVdbError::UnsupportedCodec(codec));
+        }
+
+        // Metadata and names of all grids come first, followed by their buffers
+        let mut metadata = segment + FILE_HEADER_SIZE;
+        let mut grid_types = vec![];
+        for _ in 0..grid_count {
+            let grid_size = buffer.u64(metadata + 8)? as usize;
+            grid_types.push((buffer.u32(metadata + 32)?, grid_size));
+            metadata += FILE_METADATA_SIZE + buffer.u32(metadata + 136)? as usize;
+        }
+        let mut offset = metadata;
+        for (grid_type, grid_size) in grid_types {
+            grids.push((grid_type, buffer.bytes(offset, grid_size)?));
+            offset += grid_size;
+        }
+        segment = offset;
+    }
+    Ok(grids)
+}
+
+/// Name stored in the grid buffer `buffer`.
+fn buffer_name(buffer: &Buffer) -> Result<String, NanoVdbError> {
+    let name = buffer.bytes(40, MAX_NAME_SIZE)?;
+    let name = &name[..name.iter().position(|&c| c == 0).unwrap_or(name.len())];
+    Ok(String::from_utf8_lossy(name).into_owned())
+}
+
+/// Names and buffers of the grids stored in the `.nvdb` file `file`, the buffers in the layout
+/// produced by [`to_nanovdb`], so they can be handed to GPU code without converting them.
+pub fn nanovdb_file_buffers(file: &[u8]) -> Result<Vec<(String, &[u8])>, NanoVdbError> {
+    file_grids(file)?
+        .into_iter()
+        .map(|(_, buffer)| Ok((buffer_name(&Buffer(buffer))?, buffer)))
+        .collect()
+}
+
+/// Reads the grids with values of type `ValueTy` from the `.nvdb` file at `path`, in the order
+/// they are stored, skipping grids of other value types. Only uncompressed files are
+/// supported, such as those written by [`write_nanovdb`].
+pub fn read_nanovdb<ValueTy: NanoVdbValue>(
+    path: impl AsRef<Path>,
+) -> Result<Vec<Grid<ValueTy>>, NanoVdbError> {
+    let file = std::fs::read(path)?;
+    file_grids(&file)?
+        .into_iter()
+        .filter(|&(grid_type, _)| grid_type == ValueTy::GRID_TYPE)
+        .map(|(_, buffer)| from_nanovdb(buffer))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::diff_grids;
+    use crate::primitives::tests::sphere;
+
+    #[test]
+    fn sphere_round_trips() {
+        let mut grid = sphere(Vec3::ZERO);
+        grid.descriptor.name = "sphere".into();
+        let buffer = to_nanovdb(&grid);
+        let read = from_nanovdb::<f32>(&buffer).unwrap();
+
+        let diff = diff_grids(&grid, &read, 0.0);
+        assert_eq!(diff.only_in_a, None);
+        assert_eq!(diff.only_in_b, None);
+        assert_eq!(diff.exceeding_tolerance, None);
+        assert_eq!(diff.common_active, grid.active_voxel_count());
+        assert!(diff.transform_deviation < 1e-12);
+        assert_eq!(read.descriptor.name, grid.descriptor.name);
+        assert_eq!(read.grid_class(), grid.grid_class());
+    }
+
+    #[test]
+    fn rejects_other_value_types() {
+        let grid = sphere(Vec3::ZERO);
+        assert!(from_nanovdb::<Vec3>(&to_nanovdb(&grid)).is_err());
+        assert!(matches!(
+            from_nanovdb::<f32>(&[0; 64]),
+            Err(NanoVdbError::MagicMismatch)
+        ));
+    }
+}
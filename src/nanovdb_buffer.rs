@@ -0,0 +1,196 @@
+use crate::asset_loader::{VdbAsset, VdbPlugin};
+use crate::data_structure::Grid;
+use crate::nanovdb::{nanovdb_file_buffers, to_nanovdb, NanoVdbValue};
+
+use bevy::asset::{
+    load_internal_asset, AssetLoader, BoxedFuture, HandleUntyped, LoadContext, LoadedAsset,
+};
+use bevy::ecs::system::lifetimeless::SRes;
+use bevy::ecs::system::SystemParamItem;
+use bevy::prelude::*;
+use bevy::reflect::{TypePath, TypeUuid};
+use bevy::render::render_asset::{PrepareAssetError, RenderAsset, RenderAssetPlugin};
+use bevy::render::render_resource::{Buffer, BufferInitDescriptor, BufferUsages, ShaderDefVal};
+use bevy::render::renderer::RenderDevice;
+use std::collections::HashSet;
+
+/// Shader module with sparse lookups into `float` NanoVDB buffers, imported in WGSL as
+/// `vdb_rs::nanovdb`. It reads the buffer bound at the group and binding given by
+/// [`nanovdb_shader_defs`], and provides
+///
+/// - `nanovdb_get_value(ijk: vec3<i32>) -> f32`, the value of a voxel
+/// - `nanovdb_is_active(ijk: vec3<i32>) -> bool`
+/// - `nanovdb_world_to_index(world: vec3<f32>) -> vec3<f32>`
+/// - `nanovdb_sample(index: vec3<f32>) -> f32`, trilinear interpolation in index space
+pub const NANOVDB_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0x5d1c_93a7_e40b_82f6);
+
+/// Shader defs placing the buffer read by the `vdb_rs::nanovdb` shader module at `binding` of
+/// bind group `group`, to add to the shader defs of pipelines importing it.
+pub fn nanovdb_shader_defs(group: u32, binding: u32) -> [ShaderDefVal; 2] {
+    [
+        ShaderDefVal::UInt("NANOVDB_GROUP".into(), group),
+        ShaderDefVal::UInt("NANOVDB_BINDING".into(), binding),
+    ]
+}
+
+/// A grid in the NanoVDB layout, see [`to_nanovdb`], uploaded to a storage buffer in the
+/// render world so shaders can traverse the sparse tree instead of sampling a dense 3D texture.
+/// The uploaded buffer is a [`GpuNanoVdbBuffer`] in `RenderAssets<NanoVdbBuffer>`.
+#[derive(Clone, Debug, TypeUuid, TypePath)]
+#[uuid = "9b0e6c1f-2d4a-4e8b-a37c-5f1d0c6e2b94"]
+pub struct NanoVdbBuffer {
+    pub bytes: Vec<u8>,
+}
+
+impl NanoVdbBuffer {
+    pub fn from_grid<ValueTy: NanoVdbValue>(grid: &Grid<ValueTy>) -> Self {
+        Self {
+            bytes: to_nanovdb(grid),
+        }
+    }
+}
+
+/// A [`NanoVdbBuffer`] in GPU memory, bindable as a `var<storage, read> array<u32>`.
+pub struct GpuNanoVdbBuffer {
+    pub buffer: Buffer,
+    /// Size of the buffer in bytes
+    pub size: u64,
+}
+
+impl RenderAsset for NanoVdbBuffer {
+    type ExtractedAsset = NanoVdbBuffer;
+    type PreparedAsset = GpuNanoVdbBuffer;
+    type Param = SRes<RenderDevice>;
+
+    fn extract_asset(&self) -> Self::ExtractedAsset {
+        self.clone()
+    }
+
+    fn prepare_asset(
+        extracted_asset: Self::ExtractedAsset,
+        render_device: &mut SystemParamItem<Self::Param>,
+    ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
+        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("nanovdb_buffer"),
+            contents: &extracted_asset.bytes,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+        });
+        Ok(GpuNanoVdbBuffer {
+            buffer,
+            size: extracted_asset.bytes.len() as u64,
+        })
+    }
+}
+
+/// Loads `.nvdb` files, such as those baked by [`crate::bake_vdb`], as [`NanoVdbBuffer`]s
+/// without converting them. The first grid of a file is its default asset and every grid is
+/// labeled by its name, as in `asset_server.load("smoke.nvdb#density")`.
+#[derive(Default)]
+pub struct NanoVdbBufferLoader;
+
+impl AssetLoader for NanoVdbBufferLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let grids = nanovdb_file_buffers(bytes).map_err(|error| {
+                bevy::asset::Error::new(error)
+                    .context(format!("Failed to load {}", load_context.path().display()))
+            })?;
+            for (index, (name, buffer)) in grids.into_iter().enumerate() {
+                let buffer = NanoVdbBuffer {
+                    bytes: buffer.to_vec(),
+                };
+                if index == 0 {
+                    load_context.set_default_asset(LoadedAsset::new(buffer.clone()));
+                }
+                load_context.set_labeled_asset(&name, LoadedAsset::new(buffer));
+            }
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["nvdb"]
+    }
+}
+
+/// Gives the entity a `Handle<NanoVdbBuffer>` holding a `float` grid of a [`VdbAsset`], which
+/// [`NanoVdbBufferPlugin`] converts again whenever the asset is loaded or changes.
+#[derive(Component, Clone, Debug)]
+pub struct NanoVdbGrid {
+    pub asset: Handle<VdbAsset>,
+    pub grid: String,
+}
+
+impl NanoVdbGrid {
+    pub fn new(asset: Handle<VdbAsset>, grid: impl Into<String>) -> Self {
+        Self {
+            asset,
+            grid: grid.into(),
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn update_nanovdb_grids(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<VdbAsset>>,
+    grids: Query<(Entity, Ref<NanoVdbGrid>, Option<&Handle<NanoVdbBuffer>>)>,
+    assets: Res<Assets<VdbAsset>>,
+    mut buffers: ResMut<Assets<NanoVdbBuffer>>,
+) {
+    let changed_assets = events
+        .iter()
+        .filter_map(|event| match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => Some(handle),
+            AssetEvent::Removed { .. } => None,
+        })
+        .collect::<HashSet<_>>();
+    for (entity, nanovdb_grid, buffer_handle) in &grids {
+        if !nanovdb_grid.is_changed() && !changed_assets.contains(&nanovdb_grid.asset) {
+            continue;
+        }
+        let Some(asset) = assets.get(&nanovdb_grid.asset) else {
+            // Not loaded yet, the buffer is made once it is
+            continue;
+        };
+        let Some(grid) = asset.float_grid(&nanovdb_grid.grid) else {
+            log::warn!("No float grid named {} to convert", nanovdb_grid.grid);
+            continue;
+        };
+        let buffer = NanoVdbBuffer::from_grid(grid);
+        match buffer_handle.and_then(|handle| buffers.get_mut(handle)) {
+            Some(existing) => *existing = buffer,
+            None => {
+                commands.entity(entity).insert(buffers.add(buffer));
+            }
+        }
+    }
+}
+
+/// Registers [`NanoVdbBuffer`] and [`NanoVdbBufferLoader`], uploads them to storage buffers in
+/// the render world, keeps the buffers of [`NanoVdbGrid`]s up to date and loads the
+/// `vdb_rs::nanovdb` shader module, see [`NANOVDB_SHADER_HANDLE`].
+pub struct NanoVdbBufferPlugin;
+
+impl Plugin for NanoVdbBufferPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            NANOVDB_SHADER_HANDLE,
+            "nanovdb_buffer.wgsl",
+            Shader::from_wgsl
+        );
+        if !app.is_plugin_added::<VdbPlugin>() {
+            app.add_plugins(VdbPlugin);
+        }
+        app.add_asset::<NanoVdbBuffer>()
+            .init_asset_loader::<NanoVdbBufferLoader>()
+            .add_plugins(RenderAssetPlugin::<NanoVdbBuffer>::default())
+            .add_systems(Update, update_nanovdb_grids);
+    }
+}
@@ -0,0 +1,126 @@
+// Sparse lookups into a `float` NanoVDB grid buffer, as made by `to_nanovdb` and uploaded by
+// `NanoVdbBufferPlugin`. The group and binding of the buffer are set by the `NANOVDB_GROUP` and
+// `NANOVDB_BINDING` shader defs, see `nanovdb_shader_defs`.
+
+#define_import_path vdb_rs::nanovdb
+
+@group(#{NANOVDB_GROUP}) @binding(#{NANOVDB_BINDING})
+var<storage, read> nanovdb: array<u32>;
+
+// Byte offsets of the fields of the grid, tree, root, internal nodes and leaves of a `float`
+// grid
+const GRID_MAP_INVERSE: u32 = 332u;
+const GRID_MAP_TRANSLATION: u32 = 368u;
+const TREE: u32 = 672u;
+const TREE_ROOT: u32 = 696u;
+const ROOT_TILE_COUNT: u32 = 24u;
+const ROOT_BACKGROUND: u32 = 28u;
+const ROOT_TILES: u32 = 64u;
+const ROOT_TILE_SIZE: u32 = 32u;
+const UPPER_VALUE_MASK: u32 = 32u;
+const UPPER_CHILD_MASK: u32 = 4128u;
+const UPPER_TABLE: u32 = 8256u;
+const LOWER_VALUE_MASK: u32 = 32u;
+const LOWER_CHILD_MASK: u32 = 544u;
+const LOWER_TABLE: u32 = 1088u;
+const LEAF_VALUE_MASK: u32 = 16u;
+const LEAF_VALUES: u32 = 96u;
+
+fn nanovdb_read_uint(byte: u32) -> u32 {
+    return nanovdb[byte >> 2u];
+}
+
+fn nanovdb_read_float(byte: u32) -> f32 {
+    return bitcast<f32>(nanovdb[byte >> 2u]);
+}
+
+fn nanovdb_bit(mask: u32, n: u32) -> bool {
+    return (nanovdb_read_uint(mask + (n >> 5u) * 4u) & (1u << (n & 31u))) != 0u;
+}
+
+// Where the lookup of a voxel ended: the byte offset of the value and its active state.
+struct NanoVdbLookup {
+    value: u32,
+    is_active: bool,
+};
+
+fn nanovdb_lookup(ijk: vec3<i32>) -> NanoVdbLookup {
+    let root = TREE + nanovdb_read_uint(TREE_ROOT);
+    let c = bitcast<vec3<u32>>(ijk);
+
+    // Root tiles are keyed by the coordinates of their upper node, packed into 64 bits
+    let key = c >> vec3(12u);
+    let key_low = key.z | (key.y << 21u);
+    let key_high = (key.y >> 11u) | (key.x << 10u);
+    var upper = 0u;
+    let tile_count = nanovdb_read_uint(root + ROOT_TILE_COUNT);
+    for (var i = 0u; i < tile_count; i += 1u) {
+        let tile = root + ROOT_TILES + i * ROOT_TILE_SIZE;
+        if nanovdb_read_uint(tile) == key_low && nanovdb_read_uint(tile + 4u) == key_high {
+            upper = root + nanovdb_read_uint(tile + 8u);
+            break;
+        }
+    }
+    if upper == 0u {
+        return NanoVdbLookup(root + ROOT_BACKGROUND, false);
+    }
+
+    let n5 = (((c.x & 4095u) >> 7u) << 10u) | (((c.y & 4095u) >> 7u) << 5u) | ((c.z & 4095u) >> 7u);
+    let entry5 = upper + UPPER_TABLE + n5 * 8u;
+    if !nanovdb_bit(upper + UPPER_CHILD_MASK, n5) {
+        return NanoVdbLookup(entry5, nanovdb_bit(upper + UPPER_VALUE_MASK, n5));
+    }
+    let lower = upper + nanovdb_read_uint(entry5);
+
+    let n4 = (((c.x & 127u) >> 3u) << 8u) | (((c.y & 127u) >> 3u) << 4u) | ((c.z & 127u) >> 3u);
+    let entry4 = lower + LOWER_TABLE + n4 * 8u;
+    if !nanovdb_bit(lower + LOWER_CHILD_MASK, n4) {
+        return NanoVdbLookup(entry4, nanovdb_bit(lower + LOWER_VALUE_MASK, n4));
+    }
+    let leaf = lower + nanovdb_read_uint(entry4);
+
+    let n3 = ((c.x & 7u) << 6u) | ((c.y & 7u) << 3u) | (c.z & 7u);
+    return NanoVdbLookup(leaf + LEAF_VALUES + n3 * 4u, nanovdb_bit(leaf + LEAF_VALUE_MASK, n3));
+}
+
+// Value of the voxel at index space coordinate `ijk`, the background outside the tree.
+fn nanovdb_get_value(ijk: vec3<i32>) -> f32 {
+    return nanovdb_read_float(nanovdb_lookup(ijk).value);
+}
+
+fn nanovdb_is_active(ijk: vec3<i32>) -> bool {
+    return nanovdb_lookup(ijk).is_active;
+}
+
+// Index space position of the world space position `world`.
+fn nanovdb_world_to_index(world: vec3<f32>) -> vec3<f32> {
+    let p = world - vec3(
+        nanovdb_read_float(GRID_MAP_TRANSLATION),
+        nanovdb_read_float(GRID_MAP_TRANSLATION + 4u),
+        nanovdb_read_float(GRID_MAP_TRANSLATION + 8u),
+    );
+    var index = vec3(0.0);
+    for (var row = 0u; row < 3u; row += 1u) {
+        let m = GRID_MAP_INVERSE + row * 12u;
+        index[row] = dot(vec3(nanovdb_read_float(m), nanovdb_read_float(m + 4u), nanovdb_read_float(m + 8u)), p);
+    }
+    return index;
+}
+
+// Trilinear interpolation of the voxel values around the index space position `index`.
+fn nanovdb_sample(index: vec3<f32>) -> f32 {
+    let base = floor(index);
+    let t = index - base;
+    let ijk = vec3<i32>(base);
+    var x: array<f32, 2>;
+    for (var dx = 0; dx < 2; dx += 1) {
+        var y: array<f32, 2>;
+        for (var dy = 0; dy < 2; dy += 1) {
+            let a = nanovdb_get_value(ijk + vec3(dx, dy, 0));
+            let b = nanovdb_get_value(ijk + vec3(dx, dy, 1));
+            y[dy] = mix(a, b, t.z);
+        }
+        x[dx] = mix(y[0], y[1], t.y);
+    }
+    return mix(x[0], x[1], t.x);
+}
@@ -0,0 +1,280 @@
+use crate::coordinates::CoordBBox;
+use crate::data_structure::{Grid, GridClass, GridDescriptor, Tree};
+use crate::transform::Transform;
+
+use glam::{DVec3, IVec3, Vec3};
+
+/// Gradients of Perlin's improved noise, the midpoints of the edges of a cube.
+const GRADIENTS: [DVec3; 12] = [
+    DVec3::new(1.0, 1.0, 0.0),
+    DVec3::new(-1.0, 1.0, 0.0),
+    DVec3::new(1.0, -1.0, 0.0),
+    DVec3::new(-1.0, -1.0, 0.0),
+    DVec3::new(1.0, 0.0, 1.0),
+    DVec3::new(-1.0, 0.0, 1.0),
+    DVec3::new(1.0, 0.0, -1.0),
+    DVec3::new(-1.0, 0.0, -1.0),
+    DVec3::new(0.0, 1.0, 1.0),
+    DVec3::new(0.0, -1.0, 1.0),
+    DVec3::new(0.0, 1.0, -1.0),
+    DVec3::new(0.0, -1.0, -1.0),
+];
+
+/// Seeds of the three noise potentials whose curl gives [`FractalNoise::curl`].
+const CURL_SEEDS: [u64; 3] = [
+    0x2545_f491_4f6c_dd1d,
+    0x9e37_79b9_7f4a_7c15,
+    0xd1b5_4a32_d192_ed03,
+];
+
+/// Step of the finite differences taking the curl, in wavelengths of the base frequency.
+const CURL_STEP: f64 = 1e-4;
+
+/// Hashes the lattice point `cell` with `seed`, mixing with the SplitMix64 finalizer.
+fn hash(seed: u64, cell: IVec3) -> u64 {
+    let mut z = seed
+        ^ (cell.x as u32 as u64).wrapping_mul(0x8cb9_2ba7_2f3d_8dd7)
+        ^ (cell.y as u32 as u64).wrapping_mul(0xd6e8_feb8_6659_fd93)
+        ^ (cell.z as u32 as u64).wrapping_mul(0xa076_1d64_78bd_642f);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Perlin gradient noise at `p` with a lattice spacing of one, roughly within `[-1, 1]`.
+fn perlin(seed: u64, p: DVec3) -> f64 {
+    let cell = p.floor();
+    let f = p - cell;
+    // Quintic fade, so the noise has continuous second derivatives
+    let u = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+    let cell = cell.as_ivec3();
+
+    let corner = |offset: IVec3| {
+        let gradient = GRADIENTS[(hash(seed, cell + offset) % 12) as usize];
+        gradient.dot(f - offset.as_dvec3())
+    };
+    let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+    let x00 = lerp(
+        corner(IVec3::new(0, 0, 0)),
+        corner(IVec3::new(1, 0, 0)),
+        u.x,
+    );
+    let x10 = lerp(
+        corner(IVec3::new(0, 1, 0)),
+        corner(IVec3::new(1, 1, 0)),
+        u.x,
+    );
+    let x01 = lerp(
+        corner(IVec3::new(0, 0, 1)),
+        corner(IVec3::new(1, 0, 1)),
+        u.x,
+    );
+    let x11 = lerp(
+        corner(IVec3::new(0, 1, 1)),
+        corner(IVec3::new(1, 1, 1)),
+        u.x,
+    );
+    lerp(lerp(x00, x10, u.y), lerp(x01, x11, u.y), u.z)
+}
+
+/// Fractal Brownian motion built from octaves of Perlin noise, the usual basis of procedural
+/// clouds and smoke.
+///
+/// Every octave doubles the frequency and halves the amplitude of the previous one by default.
+/// The same seed always gives the same noise.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FractalNoise {
+    seed: u64,
+    octaves: u32,
+    frequency: f64,
+    lacunarity: f64,
+    gain: f64,
+}
+
+impl FractalNoise {
+    /// Noise of four octaves with a base frequency of one per world unit.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            octaves: 4,
+            frequency: 1.0,
+            lacunarity: 2.0,
+            gain: 0.5,
+        }
+    }
+
+    /// Number of octaves summed up, at least one.
+    pub fn with_octaves(mut self, octaves: u32) -> Self {
+        self.octaves = octaves.max(1);
+        self
+    }
+
+    /// Frequency of the first octave in features per world unit.
+    pub fn with_frequency(mut self, frequency: f64) -> Self {
+        self.frequency = frequency;
+        self
+    }
+
+    /// Factor between the frequencies of successive octaves.
+    pub fn with_lacunarity(mut self, lacunarity: f64) -> Self {
+        self.lacunarity = lacunarity;
+        self
+    }
+
+    /// Factor between the amplitudes of successive octaves.
+    pub fn with_gain(mut self, gain: f64) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    fn fbm(&self, seed: u64, position: DVec3) -> f64 {
+        let (mut sum, mut total) = (0.0, 0.0);
+        let (mut frequency, mut amplitude) = (self.frequency, 1.0);
+        for octave in 0..self.octaves {
+            // Every octave gets its own lattice, so they don't all vanish at the origin
+            sum += amplitude * perlin(seed.wrapping_add(octave as u64), position * frequency);
+            total += amplitude;
+            frequency *= self.lacunarity;
+            amplitude *= self.gain;
+        }
+        sum / total
+    }
+
+    /// Noise at the world space `position`, roughly within `[-1, 1]`.
+    pub fn sample(&self, position: DVec3) -> f64 {
+        self.fbm(self.seed, position)
+    }
+
+    /// Curl noise at the world space `position`: the curl of a vector potential made of three
+    /// independent noises, a divergence free field that swirls like turbulent flow.
+    ///
+    /// The curl is scaled by the base wavelength, so its magnitude stays around one at any
+    /// frequency.
+    pub fn curl(&self, position: DVec3) -> DVec3 {
+        let step = CURL_STEP / self.frequency;
+        let potential =
+            |axis: usize, offset: DVec3| self.fbm(self.seed ^ CURL_SEEDS[axis], position + offset);
+        let derivative = |axis: usize, direction: DVec3| {
+            let offset = direction * step;
+            (potential(axis, offset) - potential(axis, -offset)) / (2.0 * step)
+        };
+        DVec3::new(
+            derivative(2, DVec3::Y) - derivative(1, DVec3::Z),
+            derivative(0, DVec3::Z) - derivative(2, DVec3::X),
+            derivative(1, DVec3::X) - derivative(0, DVec3::Y),
+        ) / self.frequency
+    }
+}
+
+/// Generates fog volumes with densities driven by [`FractalNoise`], for clouds and other
+/// atmospherics.
+///
+/// Noise values are remapped from `[-1, 1]` to `[0, 1]`, values below the threshold are cut
+/// away and the rest is stretched back to `[0, 1]` and scaled by the density. Sample positions
+/// can be displaced along curl noise first, which turns the blobs of plain noise into wisps.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NoiseFog {
+    noise: FractalNoise,
+    density: f32,
+    threshold: f32,
+    warp: f64,
+}
+
+impl NoiseFog {
+    pub fn new(noise: FractalNoise) -> Self {
+        Self {
+            noise,
+            density: 1.0,
+            threshold: 0.0,
+            warp: 0.0,
+        }
+    }
+
+    /// Density where the remapped noise reaches one, clamped to `[0, 1]`.
+    pub fn with_density(mut self, density: f32) -> Self {
+        self.density = density.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Remapped noise value below which the fog is empty, clamped to `[0, 0.99]`. Higher values
+    /// give sparser clouds.
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold.clamp(0.0, 0.99);
+        self
+    }
+
+    /// Displaces sample positions along the curl noise of the same [`FractalNoise`] by up to
+    /// about `warp` world units.
+    pub fn with_curl_warp(mut self, warp: f64) -> Self {
+        self.warp = warp;
+        self
+    }
+
+    /// Density at the world space `position`.
+    pub fn density(&self, position: DVec3) -> f32 {
+        let position = if self.warp != 0.0 {
+            position + self.noise.curl(position) * self.warp
+        } else {
+            position
+        };
+        let value = (self.noise.sample(position) * 0.5 + 0.5) as f32;
+        ((value - self.threshold) / (1.0 - self.threshold)).clamp(0.0, 1.0) * self.density
+    }
+
+    /// Fills the world space box from `min` to `max` with noise, using voxels of `voxel_size`
+    /// world units. Voxels with a non-zero density are active.
+    pub fn fill<const L5: u32, const L4: u32, const L3: u32>(
+        &self,
+        min: Vec3,
+        max: Vec3,
+        voxel_size: f64,
+    ) -> Grid<f32, L5, L4, L3> {
+        let transform = Transform::from_voxel_size(voxel_size);
+        let min = transform.world_to_index_f64(min.as_dvec3()).ceil();
+        let max = transform.world_to_index_f64(max.as_dvec3()).floor();
+
+        let mut tree = Tree::<f32, L5, L4, L3>::new(0.0);
+        for coord in CoordBBox::new(min.as_ivec3(), max.as_ivec3()) {
+            let density = self.density(transform.index_to_world_f64(coord.as_dvec3()));
+            if density > 0.0 {
+                tree.set_value_on(coord, density);
+            }
+        }
+
+        let mut descriptor = GridDescriptor::new("", Tree::<f32, L5, L4, L3>::type_name("float"));
+        descriptor.set_grid_class(GridClass::FogVolume);
+        Grid {
+            tree,
+            transform,
+            descriptor,
+        }
+    }
+
+    /// Multiplies the active voxels of the fog volume `grid` with the noise density, carving
+    /// detail into an existing shape such as the output of [`sdf_to_fog_volume`]. Voxels whose
+    /// density drops to zero become inactive.
+    ///
+    /// [`sdf_to_fog_volume`]: crate::sdf_to_fog_volume
+    pub fn modulate<const L5: u32, const L4: u32, const L3: u32>(
+        &self,
+        grid: &Grid<f32, L5, L4, L3>,
+    ) -> Grid<f32, L5, L4, L3> {
+        let mut tree = Tree::<f32, L5, L4, L3>::new(grid.tree.background);
+        let mut accessor = grid.tree.accessor();
+        grid.tree.for_each_active_voxel(|coord| {
+            let world = grid.transform.index_to_world_f64(coord.as_dvec3());
+            let density = accessor.get_value(coord) * self.density(world);
+            if density > 0.0 {
+                tree.set_value_on(coord, density);
+            }
+        });
+
+        let mut result = Grid {
+            tree,
+            transform: grid.transform.clone(),
+            descriptor: grid.descriptor.clone(),
+        };
+        result.set_grid_class(GridClass::FogVolume);
+        result
+    }
+}
@@ -0,0 +1,536 @@
+use crate::coordinates::CoordBBox;
+use crate::data_structure::Grid;
+use crate::dense::{copy_to_dense, Dense};
+use crate::transform::Transform;
+
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
+use glam::{DVec3, IVec3, Vec3};
+use std::io::{Read, Write};
+use std::path::Path;
+
+#[derive(thiserror::Error, Debug)]
+pub enum NpyError {
+    #[error("Invalid header: {0}")]
+    InvalidHeader(String),
+    #[error("Array type {0} doesn't match the requested value type")]
+    TypeMismatch(String),
+    #[error("Array {0} not found in archive")]
+    MissingArray(String),
+    #[error("Unsupported: {0}")]
+    Unsupported(String),
+    #[error("IoError")]
+    IoError(#[from] std::io::Error),
+}
+
+/// Values that can be stored in NumPy arrays, see [`write_npy`] and [`read_npy`].
+pub trait NpyValue: Copy {
+    /// NumPy type string of a component, e.g. `<f4`
+    const DESCR: &'static str;
+    /// Number of components, stored along an extra trailing axis when larger than one
+    const COMPONENTS: usize;
+    /// Size of a value in bytes
+    const SIZE: usize;
+
+    fn write(self, out: &mut Vec<u8>);
+
+    /// Reads a value from the first [`NpyValue::SIZE`] bytes of `bytes`.
+    fn read(bytes: &[u8]) -> Self;
+}
+
+impl NpyValue for f32 {
+    const DESCR: &'static str = "<f4";
+    const COMPONENTS: usize = 1;
+    const SIZE: usize = 4;
+
+    fn write(self, out: &mut Vec<u8>) {
+        out.write_f32::<LittleEndian>(self).unwrap();
+    }
+
+    fn read(bytes: &[u8]) -> Self {
+        LittleEndian::read_f32(bytes)
+    }
+}
+
+impl NpyValue for f64 {
+    const DESCR: &'static str = "<f8";
+    const COMPONENTS: usize = 1;
+    const SIZE: usize = 8;
+
+    fn write(self, out: &mut Vec<u8>) {
+        out.write_f64::<LittleEndian>(self).unwrap();
+    }
+
+    fn read(bytes: &[u8]) -> Self {
+        LittleEndian::read_f64(bytes)
+    }
+}
+
+impl NpyValue for i32 {
+    const DESCR: &'static str = "<i4";
+    const COMPONENTS: usize = 1;
+    const SIZE: usize = 4;
+
+    fn write(self, out: &mut Vec<u8>) {
+        out.write_i32::<LittleEndian>(self).unwrap();
+    }
+
+    fn read(bytes: &[u8]) -> Self {
+        LittleEndian::read_i32(bytes)
+    }
+}
+
+impl NpyValue for bool {
+    const DESCR: &'static str = "|b1";
+    const COMPONENTS: usize = 1;
+    const SIZE: usize = 1;
+
+    fn write(self, out: &mut Vec<u8>) {
+        out.push(self as u8);
+    }
+
+    fn read(bytes: &[u8]) -> Self {
+        bytes[0] != 0
+    }
+}
+
+impl NpyValue for Vec3 {
+    const DESCR: &'static str = "<f4";
+    const COMPONENTS: usize = 3;
+    const SIZE: usize = 12;
+
+    fn write(self, out: &mut Vec<u8>) {
+        for component in self.to_array() {
+            out.write_f32::<LittleEndian>(component).unwrap();
+        }
+    }
+
+    fn read(bytes: &[u8]) -> Self {
+        let mut components = [0.0; 3];
+        LittleEndian::read_f32_into(&bytes[..12], &mut components);
+        Vec3::from_array(components)
+    }
+}
+
+const NPY_MAGIC: &[u8] = b"\x93NUMPY";
+
+/// Serializes `values` as a version 1.0 `.npy` file of `shape`, in C order.
+fn encode_npy<ValueTy: NpyValue>(values: &[ValueTy], shape: &[usize]) -> Vec<u8> {
+    let mut shape = shape.to_vec();
+    if ValueTy::COMPONENTS > 1 {
+        shape.push(ValueTy::COMPONENTS);
+    }
+    let shape = match shape.as_slice() {
+        [len] => format!("({len},)"),
+        shape => {
+            let shape = shape.iter().map(usize::to_string).collect::<Vec<_>>();
+            format!("({})", shape.join(", "))
+        }
+    };
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {shape}, }}",
+        ValueTy::DESCR
+    );
+    // The header is padded with spaces and a newline so the data starts 64 byte aligned
+    let unpadded = NPY_MAGIC.len() + 4 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - unpadded % 64) % 64));
+    header.push('\n');
+
+    let mut out = Vec::with_capacity(10 + header.len() + values.len() * ValueTy::SIZE);
+    out.extend_from_slice(NPY_MAGIC);
+    out.extend_from_slice(&[1, 0]);
+    out.write_u16::<LittleEndian>(header.len() as u16).unwrap();
+    out.extend_from_slice(header.as_bytes());
+    for &value in values {
+        value.write(&mut out);
+    }
+    out
+}
+
+/// Parses a `.npy` file holding values of type `ValueTy`, returning its shape without the
+/// trailing component axis and the values in C order.
+fn decode_npy<ValueTy: NpyValue>(bytes: &[u8]) -> Result<(Vec<usize>, Vec<ValueTy>), NpyError> {
+    let invalid = |message: &str| NpyError::InvalidHeader(message.to_owned());
+    if !bytes.starts_with(NPY_MAGIC) || bytes.len() < 10 {
+        return Err(invalid("missing NPY magic"));
+    }
+    let (header_len, header_start) = match bytes[6] {
+        1 => (LittleEndian::read_u16(&bytes[8..10]) as usize, 10),
+        2 | 3 if bytes.len() >= 12 => (LittleEndian::read_u32(&bytes[8..12]) as usize, 12),
+        version => {
+            return Err(NpyError::Unsupported(format!(
+                "NPY format version {version}"
+            )))
+        }
+    };
+    let header = bytes
+        .get(header_start..header_start + header_len)
+        .ok_or_else(|| invalid("truncated header"))?;
+    let header = String::from_utf8_lossy(header);
+
+    // The header is a Python dict literal with the keys descr, fortran_order and shape
+    let value_of = |key: &str| {
+        let start = header
+            .find(&format!("'{key}'"))
+            .ok_or_else(|| invalid(&format!("missing key {key}")))?;
+        let value = header[start + key.len() + 2..].trim_start();
+        Ok::<_, NpyError>(value.strip_prefix(':').unwrap_or(value).trim_start())
+    };
+    let descr = value_of("descr")?;
+    let descr = descr
+        .strip_prefix('\'')
+        .and_then(|descr| descr.split('\'').next())
+        .ok_or_else(|| invalid("descr"))?;
+    // Byte order is irrelevant for single byte types, and native order is little endian on
+    // the machines that wrote most files
+    let byte_order = descr.chars().next().unwrap_or('|');
+    let matches = descr.get(1..) == ValueTy::DESCR.get(1..)
+        && (byte_order == '<' || byte_order == '=' || ValueTy::SIZE / ValueTy::COMPONENTS == 1);
+    if !matches {
+        return Err(NpyError::TypeMismatch(descr.to_owned()));
+    }
+    let fortran_order = value_of("fortran_order")?.starts_with("True");
+    let shape = value_of("shape")?;
+    let shape = shape
+        .strip_prefix('(')
+        .and_then(|shape| shape.split(')').next())
+        .ok_or_else(|| invalid("shape"))?;
+    let mut shape = shape
+        .split(',')
+        .map(str::trim)
+        .filter(|len| !len.is_empty())
+        .map(|len| len.parse::<usize>().map_err(|_| invalid("shape")))
+        .collect::<Result<Vec<_>, _>>()?;
+    if ValueTy::COMPONENTS > 1 {
+        if shape.last() != Some(&ValueTy::COMPONENTS) || fortran_order {
+            return Err(NpyError::TypeMismatch(format!(
+                "{descr} of shape {shape:?}"
+            )));
+        }
+        shape.pop();
+    }
+
+    let count = shape.iter().product::<usize>();
+    let data_start = header_start + header_len;
+    let data = bytes
+        .get(data_start..data_start + count * ValueTy::SIZE)
+        .ok_or_else(|| invalid("not enough data"))?;
+    let mut values = data
+        .chunks_exact(ValueTy::SIZE)
+        .map(ValueTy::read)
+        .collect::<Vec<_>>();
+    if fortran_order && shape.len() > 1 {
+        // Reorder from the first axis varying fastest to the last one varying fastest
+        let mut reordered = Vec::with_capacity(count);
+        let mut index = vec![0; shape.len()];
+        for _ in 0..count {
+            let mut offset = 0;
+            for axis in (0..shape.len()).rev() {
+                offset = offset * shape[axis] + index[axis];
+            }
+            reordered.push(values[offset]);
+            for axis in (0..shape.len()).rev() {
+                index[axis] += 1;
+                if index[axis] < shape[axis] {
+                    break;
+                }
+                index[axis] = 0;
+            }
+        }
+        values = reordered;
+    }
+    Ok((shape, values))
+}
+
+/// Dense block of `values` of a three dimensional `shape`, with its first voxel at `origin`.
+fn dense_from_shape<ValueTy: NpyValue>(
+    shape: &[usize],
+    values: Vec<ValueTy>,
+    origin: IVec3,
+) -> Result<Dense<ValueTy>, NpyError> {
+    let &[x, y, z] = shape else {
+        return Err(NpyError::InvalidHeader(format!(
+            "expected three dimensions, found shape {shape:?}"
+        )));
+    };
+    let bbox = CoordBBox::new(
+        origin,
+        origin + IVec3::new(x as i32, y as i32, z as i32) - 1,
+    );
+    Ok(Dense::from_data(bbox, values).expect("shape matches the number of values"))
+}
+
+/// Writes the values of `grid` inside `bbox` to a `.npy` file at `path`, as a C order array of
+/// shape `[x, y, z]`, with an extra trailing axis for the components of vectors. Voxels not
+/// stored in the tree take their tile or background value.
+pub fn write_npy<ValueTy: NpyValue>(
+    grid: &Grid<ValueTy>,
+    bbox: CoordBBox,
+    path: impl AsRef<Path>,
+) -> std::io::Result<()> {
+    let dense = copy_to_dense(grid, bbox);
+    let dims = dense.dims().to_array().map(|len| len as usize);
+    std::fs::write(path, encode_npy(&dense.data, &dims))
+}
+
+/// Reads a three dimensional `.npy` file, indexed as `[x][y][z]`, into a dense block with its
+/// first voxel at `origin`, which can be copied into a grid with [`copy_from_dense`].
+///
+/// The file must hold little endian values of type `ValueTy`, with an extra trailing axis for
+/// the components of vectors. Both C and Fortran order are supported.
+///
+/// [`copy_from_dense`]: crate::copy_from_dense
+pub fn read_npy<ValueTy: NpyValue>(
+    path: impl AsRef<Path>,
+    origin: IVec3,
+) -> Result<Dense<ValueTy>, NpyError> {
+    let (shape, values) = decode_npy(&std::fs::read(path)?)?;
+    dense_from_shape(&shape, values, origin)
+}
+
+/// Writes the values of `grid` inside `bbox` to a `.npz` archive at `path`, as read by
+/// `numpy.load`, holding the arrays:
+///
+/// - `values`, the values as written by [`write_npy`]
+/// - `index_min`, the index space coordinate of the first voxel as three `int32`
+/// - `origin`, the world space position of the center of the first voxel as three `float64`
+/// - `spacing`, the world space size of a voxel along each axis as three `float64`
+///
+/// Rotations in the grid transform are not stored. The archive is not compressed.
+pub fn write_npz<ValueTy: NpyValue>(
+    grid: &Grid<ValueTy>,
+    bbox: CoordBBox,
+    path: impl AsRef<Path>,
+) -> std::io::Result<()> {
+    let dense = copy_to_dense(grid, bbox);
+    let dims = dense.dims().to_array().map(|len| len as usize);
+    let origin = grid.transform.index_to_world_f64(bbox.min.as_dvec3());
+    let entries = [
+        ("values.npy", encode_npy(&dense.data, &dims)),
+        ("index_min.npy", encode_npy(&bbox.min.to_array(), &[3])),
+        ("origin.npy", encode_npy(&origin.to_array(), &[3])),
+        (
+            "spacing.npy",
+            encode_npy(&grid.transform.voxel_size().to_array(), &[3]),
+        ),
+    ];
+    std::fs::write(path, write_zip(&entries)?)
+}
+
+/// Reads a `.npz` archive as written by [`write_npz`], returning the dense block of `values`
+/// and the transform given by `origin` and `spacing`.
+///
+/// Only `values` is required, a missing `index_min` places the first voxel at the origin of
+/// index space, and missing `origin` and `spacing` arrays give an identity transform. Both
+/// `numpy.savez` and `numpy.savez_compressed` archives can be read.
+pub fn read_npz<ValueTy: NpyValue>(
+    path: impl AsRef<Path>,
+) -> Result<(Dense<ValueTy>, Transform), NpyError> {
+    let archive = std::fs::read(path)?;
+    let entries = read_zip(&archive)?;
+    let entry = |name: &str| {
+        entries
+            .iter()
+            .find(|(entry, _)| entry == &format!("{name}.npy"))
+            .map(|(_, bytes)| bytes.as_slice())
+    };
+    let vector = |name: &str| -> Result<Option<[f64; 3]>, NpyError> {
+        let Some(bytes) = entry(name) else {
+            return Ok(None);
+        };
+        let (_, values) = decode_npy::<f64>(bytes)?;
+        let vector = values
+            .try_into()
+            .map_err(|_| NpyError::InvalidHeader(format!("{name} should hold three values")))?;
+        Ok(Some(vector))
+    };
+
+    let index_min = match entry("index_min") {
+        Some(bytes) => {
+            let (_, values) = decode_npy::<i32>(bytes)?;
+            let values: [i32; 3] = values.try_into().map_err(|_| {
+                NpyError::InvalidHeader("index_min should hold three values".to_owned())
+            })?;
+            IVec3::from_array(values)
+        }
+        None => IVec3::ZERO,
+    };
+    let spacing = vector("spacing")?.map_or(DVec3::ONE, DVec3::from_array);
+    let origin = vector("origin")?.map_or(index_min.as_dvec3() * spacing, DVec3::from_array);
+    let values = entry("values").ok_or_else(|| NpyError::MissingArray("values".to_owned()))?;
+    let (shape, values) = decode_npy(values)?;
+    let dense = dense_from_shape(&shape, values, index_min)?;
+    let transform =
+        Transform::from_scale_translation(spacing, origin - index_min.as_dvec3() * spacing);
+    Ok((dense, transform))
+}
+
+/// Zip archive with the uncompressed `entries`, in the subset of the format NumPy reads.
+fn write_zip(entries: &[(&str, Vec<u8>)]) -> std::io::Result<Vec<u8>> {
+    let too_large = || std::io::Error::other("npz archives are limited to 4 GiB");
+    let mut out = vec![];
+    let mut central = vec![];
+    for (name, data) in entries {
+        let mut crc = flate2::Crc::new();
+        crc.update(data);
+        let size = u32::try_from(data.len()).map_err(|_| too_large())?;
+        let offset = u32::try_from(out.len()).map_err(|_| too_large())?;
+
+        // Fields shared by the local and central headers: version needed, flags, stored
+        // method, a date of 1980-01-01, checksum, sizes and name length
+        let mut common = vec![];
+        common.write_u16::<LittleEndian>(20)?;
+        common.write_u16::<LittleEndian>(0)?;
+        common.write_u16::<LittleEndian>(0)?;
+        common.write_u16::<LittleEndian>(0)?;
+        common.write_u16::<LittleEndian>(0x21)?;
+        common.write_u32::<LittleEndian>(crc.sum())?;
+        common.write_u32::<LittleEndian>(size)?;
+        common.write_u32::<LittleEndian>(size)?;
+        common.write_u16::<LittleEndian>(name.len() as u16)?;
+        common.write_u16::<LittleEndian>(0)?;
+
+        out.write_u32::<LittleEndian>(0x0403_4b50)?;
+        out.write_all(&common)?;
+        out.write_all(name.as_bytes())?;
+        out.write_all(data)?;
+
+        central.write_u32::<LittleEndian>(0x0201_4b50)?;
+        central.write_u16::<LittleEndian>(20)?;
+        central.write_all(&common)?;
+        // Comment length, disk, internal and external attributes, then the local header
+        central.write_u16::<LittleEndian>(0)?;
+        central.write_u16::<LittleEndian>(0)?;
+        central.write_u16::<LittleEndian>(0)?;
+        central.write_u32::<LittleEndian>(0)?;
+        central.write_u32::<LittleEndian>(offset)?;
+        central.write_all(name.as_bytes())?;
+    }
+    let central_offset = u32::try_from(out.len()).map_err(|_| too_large())?;
+    out.write_all(&central)?;
+
+    out.write_u32::<LittleEndian>(0x0605_4b50)?;
+    out.write_u16::<LittleEndian>(0)?;
+    out.write_u16::<LittleEndian>(0)?;
+    out.write_u16::<LittleEndian>(entries.len() as u16)?;
+    out.write_u16::<LittleEndian>(entries.len() as u16)?;
+    out.write_u32::<LittleEndian>(central.len() as u32)?;
+    out.write_u32::<LittleEndian>(central_offset)?;
+    out.write_u16::<LittleEndian>(0)?;
+    Ok(out)
+}
+
+/// Names and contents of the stored or deflated entries of a zip `archive`.
+fn read_zip(archive: &[u8]) -> Result<Vec<(String, Vec<u8>)>, NpyError> {
+    let invalid = || NpyError::InvalidHeader("invalid npz archive".to_owned());
+    let bytes = |offset: usize, len: usize| archive.get(offset..offset + len).ok_or_else(invalid);
+    let u16_at = |offset: usize| bytes(offset, 2).map(LittleEndian::read_u16);
+    let u32_at = |offset: usize| bytes(offset, 4).map(LittleEndian::read_u32);
+
+    // The end of central directory record is followed by a comment of at most 64 KiB
+    let end = (0..archive.len().saturating_sub(21))
+        .rev()
+        .take(u16::MAX as usize + 22)
+        .find(|&offset| u32_at(offset).ok() == Some(0x0605_4b50))
+        .ok_or_else(invalid)?;
+    let count = u16_at(end + 10)? as usize;
+    let mut central = u32_at(end + 16)? as usize;
+    if central == u32::MAX as usize {
+        return Err(NpyError::Unsupported(
+            "npz archives larger than 4 GiB".to_owned(),
+        ));
+    }
+
+    let mut entries = Vec::with_capacity(count);
+    for _ in 0..count {
+        if u32_at(central)? != 0x0201_4b50 {
+            return Err(invalid());
+        }
+        let method = u16_at(central + 10)?;
+        let mut compressed_size = u32_at(central + 20)? as u64;
+        let mut size = u32_at(central + 24)? as u64;
+        let name_len = u16_at(central + 28)? as usize;
+        let extra_len = u16_at(central + 30)? as usize;
+        let comment_len = u16_at(central + 32)? as usize;
+        let mut offset = u32_at(central + 42)? as u64;
+        let name = String::from_utf8_lossy(bytes(central + 46, name_len)?).into_owned();
+
+        // Sizes and offsets that don't fit are stored in the zip64 extra field, in order
+        let mut extra = central + 46 + name_len;
+        let extra_end = extra + extra_len;
+        while extra + 4 <= extra_end {
+            let (id, len) = (u16_at(extra)?, u16_at(extra + 2)? as usize);
+            if id == 1 {
+                let mut field = extra + 4;
+                for value in [&mut size, &mut compressed_size, &mut offset] {
+                    if *value == u32::MAX as u64 && field + 8 <= extra + 4 + len {
+                        *value = LittleEndian::read_u64(bytes(field, 8)?);
+                        field += 8;
+                    }
+                }
+            }
+            extra += 4 + len;
+        }
+        central = extra_end + comment_len;
+
+        let local = offset as usize;
+        if u32_at(local)? != 0x0403_4b50 {
+            return Err(invalid());
+        }
+        let data = local + 30 + u16_at(local + 26)? as usize + u16_at(local + 28)? as usize;
+        let data = bytes(data, compressed_size as usize)?;
+        let data = match method {
+            0 => data.to_vec(),
+            8 => {
+                let mut decoded = Vec::with_capacity(size as usize);
+                flate2::read::DeflateDecoder::new(data).read_to_end(&mut decoded)?;
+                decoded
+            }
+            method => {
+                return Err(NpyError::Unsupported(format!(
+                    "npz compression method {method}"
+                )))
+            }
+        };
+        entries.push((name, data));
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::tests::sphere;
+
+    #[test]
+    fn npy_round_trips() {
+        let values = (0..24)
+            .map(|idx| Vec3::splat(idx as f32))
+            .collect::<Vec<_>>();
+        let bytes = encode_npy(&values, &[2, 3, 4]);
+        // The header is padded so the values start 64 byte aligned
+        assert_eq!((bytes.len() - values.len() * 12) % 64, 0);
+        let (shape, read) = decode_npy::<Vec3>(&bytes).unwrap();
+        assert_eq!(shape, [2, 3, 4]);
+        assert_eq!(read, values);
+        assert!(matches!(
+            decode_npy::<f64>(&bytes),
+            Err(NpyError::TypeMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn npz_of_sphere_round_trips() {
+        let grid = sphere(Vec3::ZERO);
+        let bbox = grid.eval_active_voxel_bounding_box();
+        let path = std::env::temp_dir().join(format!("vdb-rs-npy-{}.npz", std::process::id()));
+        write_npz(&grid, bbox, &path).unwrap();
+        let read = read_npz::<f32>(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let (dense, transform) = read.unwrap();
+        assert_eq!(dense.bbox, bbox);
+        assert_eq!(dense.data, copy_to_dense(&grid, bbox).data);
+        let world = transform.index_to_world_f64(DVec3::new(3.0, -2.0, 5.0));
+        assert!(world.distance(DVec3::new(0.3, -0.2, 0.5)) < 1e-12);
+    }
+}
@@ -0,0 +1,233 @@
+use crate::coordinates::{CoordBBox, GlobalCoord};
+use crate::data_structure::{ArchiveHeader, GridDescriptor, Node, Node3, Tree};
+use crate::reader::{ParseError, VdbReader};
+use crate::transform::Transform;
+
+use bytemuck::Pod;
+use glam::IVec3;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::{Arc, Mutex};
+
+/// Value and active state of the voxel at `coord` inside `leaf`.
+fn leaf_value<ValueTy: Copy, const L3: u32>(
+    leaf: &Node3<ValueTy, L3>,
+    coord: IVec3,
+) -> (ValueTy, bool) {
+    let offset = leaf.global_coord_to_offset(GlobalCoord(coord)).0 as usize;
+    (leaf.buffer[offset], leaf.value_mask[offset])
+}
+
+/// Leaves currently in memory, with the file they are paged in from.
+struct Pager<R, ValueTy, const L3: u32> {
+    reader: R,
+    max_resident_leaves: usize,
+    /// Resident leaves by origin, with the time they were last used
+    resident: HashMap<IVec3, (Arc<Node3<ValueTy, L3>>, u64)>,
+    /// Origins of the resident leaves by the time they were last used, oldest first
+    last_used: BTreeMap<u64, IVec3>,
+    clock: u64,
+}
+
+impl<R, ValueTy, const L3: u32> Pager<R, ValueTy, L3> {
+    /// Marks the resident leaf at `origin` as used just now and returns it.
+    fn touch(&mut self, origin: IVec3) -> Option<Arc<Node3<ValueTy, L3>>> {
+        let (leaf, used) = self.resident.get_mut(&origin)?;
+        self.last_used.remove(used);
+        self.clock += 1;
+        *used = self.clock;
+        self.last_used.insert(self.clock, origin);
+        Some(leaf.clone())
+    }
+
+    /// Makes `leaf` resident, evicting the least recently used leaves beyond the limit.
+    fn insert(&mut self, leaf: Arc<Node3<ValueTy, L3>>) {
+        self.clock += 1;
+        self.last_used.insert(self.clock, leaf.origin);
+        self.resident.insert(leaf.origin, (leaf, self.clock));
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while self.resident.len() > self.max_resident_leaves.max(1) {
+            let (_, origin) = self.last_used.pop_first().unwrap();
+            self.resident.remove(&origin);
+        }
+    }
+}
+
+/// Grid whose leaf values stay in the file until they are looked up, for sampling files much
+/// larger than the available memory. Created with [`VdbReader::into_paged_grid`].
+///
+/// The tree topology, tiles and leaf masks are read up front. Leaves are read and decompressed
+/// on first access and kept in memory until more than the resident limit are, at which point the
+/// least recently used ones are dropped to be read again from the file when needed. Leaves
+/// handed out by [`PagedGrid::leaf`] stay valid after being evicted.
+///
+/// The file is shared behind a lock, so a [`PagedGrid`] can be sampled from many threads at
+/// once. Use a [`PagedValueAccessor`] per thread to skip the lock for lookups within the same
+/// leaf.
+pub struct PagedGrid<R, ValueTy, const L5: u32 = 5, const L4: u32 = 4, const L3: u32 = 3> {
+    /// Topology of the grid, with empty leaf buffers
+    topology: Tree<ValueTy, L5, L4, L3>,
+    pub transform: Transform,
+    pub descriptor: GridDescriptor,
+    header: ArchiveHeader,
+    /// File position of the values of every leaf, by origin
+    leaf_offsets: HashMap<IVec3, u64>,
+    pager: Mutex<Pager<R, ValueTy, L3>>,
+}
+
+impl<R: Read + Seek, ValueTy: Pod, const L5: u32, const L4: u32, const L3: u32>
+    PagedGrid<R, ValueTy, L5, L4, L3>
+{
+    pub(crate) fn new(
+        reader: R,
+        header: ArchiveHeader,
+        descriptor: GridDescriptor,
+        topology: Tree<ValueTy, L5, L4, L3>,
+        transform: Transform,
+        leaf_offsets: HashMap<IVec3, u64>,
+        max_resident_leaves: usize,
+    ) -> Self {
+        Self {
+            topology,
+            transform,
+            descriptor,
+            header,
+            leaf_offsets,
+            pager: Mutex::new(Pager {
+                reader,
+                max_resident_leaves,
+                resident: HashMap::new(),
+                last_used: BTreeMap::new(),
+                clock: 0,
+            }),
+        }
+    }
+
+    /// Background value of the tree.
+    pub fn background(&self) -> ValueTy {
+        self.topology.background
+    }
+
+    /// Number of leaves in the tree, resident or not.
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_offsets.len()
+    }
+
+    /// Number of leaves currently in memory.
+    pub fn resident_leaf_count(&self) -> usize {
+        self.pager.lock().unwrap().resident.len()
+    }
+
+    /// Maximum number of leaves kept in memory.
+    pub fn max_resident_leaves(&self) -> usize {
+        self.pager.lock().unwrap().max_resident_leaves
+    }
+
+    /// Changes the maximum number of leaves kept in memory, evicting the least recently used
+    /// ones right away if more are resident. At least one leaf is always kept.
+    pub fn set_max_resident_leaves(&self, max_resident_leaves: usize) {
+        let mut pager = self.pager.lock().unwrap();
+        pager.max_resident_leaves = max_resident_leaves;
+        pager.evict();
+    }
+
+    /// Number of active voxels, computed from the topology without paging in any leaves.
+    pub fn active_voxel_count(&self) -> u64 {
+        self.topology.active_voxel_count()
+    }
+
+    /// Bounding box of the active voxels, computed from the topology without paging in any
+    /// leaves.
+    pub fn eval_active_voxel_bounding_box(&self) -> CoordBBox {
+        self.topology.eval_active_voxel_bounding_box()
+    }
+
+    /// Leaf containing `coord`, paged in from the file if it isn't resident, or `None` if
+    /// `coord` lies in a tile or outside the tree.
+    pub fn leaf(&self, coord: IVec3) -> Result<Option<Arc<Node3<ValueTy, L3>>>, ParseError> {
+        let Some(origin) = self.topology.probe_leaf(coord).map(|leaf| leaf.origin) else {
+            return Ok(None);
+        };
+        let mut pager = self.pager.lock().unwrap();
+        if let Some(leaf) = pager.touch(origin) {
+            return Ok(Some(leaf));
+        }
+
+        let reader = &mut pager.reader;
+        reader.seek(SeekFrom::Start(self.leaf_offsets[&origin]))?;
+        let leaf = Arc::new(VdbReader::<R>::read_leaf(
+            reader,
+            &self.header,
+            &self.descriptor,
+            self.topology.background,
+            origin,
+        )?);
+        pager.insert(leaf.clone());
+        Ok(Some(leaf))
+    }
+
+    /// Value and active state of the voxel at `coord`, see [`Tree::probe_value`].
+    pub fn probe_value(&self, coord: IVec3) -> Result<(ValueTy, bool), ParseError> {
+        Ok(match self.leaf(coord)? {
+            Some(leaf) => leaf_value(&leaf, coord),
+            None => self.topology.probe_value(coord),
+        })
+    }
+
+    /// Value of the voxel at `coord`.
+    pub fn get_value(&self, coord: IVec3) -> Result<ValueTy, ParseError> {
+        Ok(self.probe_value(coord)?.0)
+    }
+
+    /// Creates a [`PagedValueAccessor`] for fast repeated lookups into this grid.
+    pub fn accessor(&self) -> PagedValueAccessor<'_, R, ValueTy, L5, L4, L3> {
+        PagedValueAccessor {
+            grid: self,
+            leaf: None,
+        }
+    }
+}
+
+/// Accessor into a [`PagedGrid`] that holds on to the most recently visited leaf, so repeated
+/// lookups of nearby voxels neither lock the file nor traverse the tree. The held leaf stays in
+/// memory even if the grid evicts it.
+pub struct PagedValueAccessor<
+    'a,
+    R,
+    ValueTy,
+    const L5: u32 = 5,
+    const L4: u32 = 4,
+    const L3: u32 = 3,
+> {
+    grid: &'a PagedGrid<R, ValueTy, L5, L4, L3>,
+    leaf: Option<Arc<Node3<ValueTy, L3>>>,
+}
+
+impl<R: Read + Seek, ValueTy: Pod, const L5: u32, const L4: u32, const L3: u32>
+    PagedValueAccessor<'_, R, ValueTy, L5, L4, L3>
+{
+    /// Value and active state of the voxel at `coord`, see [`Tree::probe_value`].
+    pub fn probe_value(&mut self, coord: IVec3) -> Result<(ValueTy, bool), ParseError> {
+        if let Some(leaf) = &self.leaf {
+            if coord >> L3 == leaf.origin >> L3 {
+                return Ok(leaf_value(leaf, coord));
+            }
+        }
+        match self.grid.leaf(coord)? {
+            Some(leaf) => {
+                let value = leaf_value(&leaf, coord);
+                self.leaf = Some(leaf);
+                Ok(value)
+            }
+            None => Ok(self.grid.topology.probe_value(coord)),
+        }
+    }
+
+    /// Value of the voxel at `coord`.
+    pub fn get_value(&mut self, coord: IVec3) -> Result<ValueTy, ParseError> {
+        Ok(self.probe_value(coord)?.0)
+    }
+}
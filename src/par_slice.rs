@@ -0,0 +1,46 @@
+//! Data parallel helpers over slices, running on the rayon thread pool when the `rayon` feature
+//! is enabled and serially otherwise.
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+/// Applies `f` to all `items`, stopping at the first error.
+pub(crate) fn par_try_map<T: Sync, R: Send, E: Send>(
+    items: &[T],
+    f: impl Fn(&T) -> Result<R, E> + Sync + Send,
+) -> Result<Vec<R>, E> {
+    #[cfg(feature = "rayon")]
+    return items.par_iter().map(f).collect();
+    #[cfg(not(feature = "rayon"))]
+    items.iter().map(f).collect()
+}
+
+/// Folds `f` over all `items` into accumulators created by `init`, combining the accumulators
+/// of different threads with `merge`.
+pub(crate) fn par_fold<T: Sync, A: Send>(
+    items: &[T],
+    init: impl Fn() -> A + Sync + Send,
+    f: impl Fn(&mut A, &T) + Sync + Send,
+    merge: impl Fn(&mut A, &A) + Sync + Send,
+) -> A {
+    #[cfg(feature = "rayon")]
+    return items
+        .par_iter()
+        .fold(&init, |mut acc, item| {
+            f(&mut acc, item);
+            acc
+        })
+        .reduce(&init, |mut acc, other| {
+            merge(&mut acc, &other);
+            acc
+        });
+    #[cfg(not(feature = "rayon"))]
+    {
+        let _ = merge;
+        let mut acc = init();
+        for item in items {
+            f(&mut acc, item);
+        }
+        acc
+    }
+}
@@ -0,0 +1,77 @@
+use crate::coordinates::{CoordBBox, Index};
+use crate::data_structure::{active_tiles, Grid, Node, Node3, Tree};
+
+use glam::IVec3;
+use rayon::prelude::*;
+
+/// A unit of parallel work for [`Tree::par_iter_on`]: either a leaf or an active tile.
+enum ActiveRegion<'a, ValueTy, const L3: u32> {
+    Leaf(&'a Node3<ValueTy, L3>),
+    Tile(CoordBBox, ValueTy),
+}
+
+impl<ValueTy, const L5: u32, const L4: u32, const L3: u32> Tree<ValueTy, L5, L4, L3>
+where
+    ValueTy: Copy + Send + Sync,
+{
+    /// Parallel iterator over all leaf nodes in the tree.
+    pub fn par_iter_leafs(&self) -> impl ParallelIterator<Item = &Node3<ValueTy, L3>> {
+        self.leaves().collect::<Vec<_>>().into_par_iter()
+    }
+
+    /// Parallel iterator over the index-space coordinate and value of every active voxel,
+    /// including every voxel covered by an active tile. Work is split per leaf and per tile.
+    pub fn par_iter_on(&self) -> impl ParallelIterator<Item = (IVec3, ValueTy)> + '_ {
+        let mut regions = vec![];
+        for node_5 in &self.root_nodes {
+            for idx in active_tiles(&node_5.child_mask, &node_5.value_mask) {
+                regions.push(ActiveRegion::Tile(
+                    node_5.tile_bbox(Index(idx as u32)),
+                    node_5.data[idx],
+                ));
+            }
+            for node_4 in node_5.nodes.values() {
+                for idx in active_tiles(&node_4.child_mask, &node_4.value_mask) {
+                    regions.push(ActiveRegion::Tile(
+                        node_4.tile_bbox(Index(idx as u32)),
+                        node_4.data[idx],
+                    ));
+                }
+            }
+        }
+        regions.extend(self.leaves().map(ActiveRegion::Leaf));
+
+        regions
+            .into_par_iter()
+            .flat_map_iter(|region| -> Box<dyn Iterator<Item = _> + Send + '_> {
+                match region {
+                    ActiveRegion::Leaf(node_3) => {
+                        Box::new(node_3.value_mask.iter_ones().map(move |idx| {
+                            (
+                                node_3.offset_to_global_coord(Index(idx as u32)).0,
+                                node_3.buffer[idx],
+                            )
+                        }))
+                    }
+                    ActiveRegion::Tile(bbox, value) => {
+                        Box::new(bbox.into_iter().map(move |coord| (coord, value)))
+                    }
+                }
+            })
+    }
+}
+
+impl<ValueTy, const L5: u32, const L4: u32, const L3: u32> Grid<ValueTy, L5, L4, L3>
+where
+    ValueTy: Copy + Send + Sync,
+{
+    /// Parallel iterator over all leaf nodes in the grid, see [`Tree::par_iter_leafs`].
+    pub fn par_iter_leafs(&self) -> impl ParallelIterator<Item = &Node3<ValueTy, L3>> {
+        self.tree.par_iter_leafs()
+    }
+
+    /// Parallel iterator over all active voxels in the grid, see [`Tree::par_iter_on`].
+    pub fn par_iter_on(&self) -> impl ParallelIterator<Item = (IVec3, ValueTy)> + '_ {
+        self.tree.par_iter_on()
+    }
+}
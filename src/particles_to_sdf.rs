@@ -0,0 +1,107 @@
+use crate::coordinates::CoordBBox;
+use crate::data_structure::{Grid, GridClass, GridDescriptor, Tree};
+use crate::transform::Transform;
+
+use glam::{DVec3, Vec3};
+
+/// Rasterizes the union of `spheres`, given as world space centers and radii, into a level set
+/// with voxels of `voxel_size` world units and a narrow band of `half_width` voxels.
+fn spheres_to_sdf<const L5: u32, const L4: u32, const L3: u32>(
+    spheres: impl Iterator<Item = (DVec3, f64)>,
+    voxel_size: f64,
+    half_width: f32,
+) -> Grid<f32, L5, L4, L3> {
+    let half_width = half_width.max(1.0);
+    let background = half_width * voxel_size as f32;
+    let transform = Transform::from_voxel_size(voxel_size);
+
+    let mut tree = Tree::<f32, L5, L4, L3>::new(background);
+    for (center, radius) in spheres {
+        let center = transform.world_to_index_f64(center);
+        let radius = radius / voxel_size;
+        let extent = radius + half_width as f64;
+        let min = (center - extent).floor().as_ivec3();
+        let max = (center + extent).ceil().as_ivec3();
+        for coord in CoordBBox::new(min, max) {
+            let distance = (coord.as_dvec3().distance(center) - radius) as f32 * voxel_size as f32;
+            if distance >= background {
+                continue;
+            }
+            // Union with the particles rasterized so far, voxels deep inside become inactive
+            let value = tree.get_value(coord).min(distance);
+            if value > -background {
+                tree.set_value_on(coord, value);
+            } else {
+                tree.set_value_off(coord, -background);
+            }
+        }
+    }
+    tree.prune_level_set();
+
+    let mut descriptor = GridDescriptor::new("", Tree::<f32, L5, L4, L3>::type_name("float"));
+    descriptor.set_grid_class(GridClass::LevelSet);
+    Grid {
+        tree,
+        transform,
+        descriptor,
+    }
+}
+
+/// Converts particles, given as world space position, radius and velocity, into a level set of
+/// the union of their spheres, with voxels of `voxel_size` world units and a narrow band of
+/// `half_width` voxels on either side of the surface. Velocities are ignored, see
+/// [`particle_trails_to_sdf`] for rasterizing motion trails.
+///
+/// This surfaces FLIP or SPH particle caches directly. `half_width` is raised to one voxel if
+/// smaller, and particles much smaller than a voxel only leave a trace in the band values.
+pub fn particles_to_sdf<const L5: u32, const L4: u32, const L3: u32>(
+    points: &[(Vec3, f32, Vec3)],
+    voxel_size: f64,
+    half_width: f32,
+) -> Grid<f32, L5, L4, L3> {
+    spheres_to_sdf(
+        points
+            .iter()
+            .map(|&(position, radius, _)| (position.as_dvec3(), radius as f64)),
+        voxel_size,
+        half_width,
+    )
+}
+
+/// Variant of [`particles_to_sdf`] that also rasterizes a trail behind every particle, covering
+/// the distance it travels in one unit of time at its velocity.
+///
+/// Trails are made of spheres shrinking linearly from the particle radius at its position to
+/// nothing at the end of the trail, spaced `delta` times their radius apart. Spheres smaller
+/// than a voxel are dropped, ending the trail early. OpenVDB uses a `delta` of one by default,
+/// smaller values give smoother trails at a higher cost.
+pub fn particle_trails_to_sdf<const L5: u32, const L4: u32, const L3: u32>(
+    points: &[(Vec3, f32, Vec3)],
+    voxel_size: f64,
+    half_width: f32,
+    delta: f32,
+) -> Grid<f32, L5, L4, L3> {
+    let delta = delta.max(0.01) as f64;
+    let spheres = points
+        .iter()
+        .flat_map(move |&(position, radius, velocity)| {
+            let position = position.as_dvec3();
+            let radius = radius as f64;
+            let velocity = velocity.as_dvec3();
+            let length = velocity.length();
+            let direction = velocity.normalize_or_zero();
+
+            let mut travelled = 0.0;
+            std::iter::from_fn(move || {
+                let trail_radius = radius * (1.0 - travelled / length.max(f64::MIN_POSITIVE));
+                // The particle itself is always rasterized, even without velocity
+                if travelled > 0.0 && (travelled > length || trail_radius < voxel_size) {
+                    return None;
+                }
+                let sphere = (position - direction * travelled, trail_radius);
+                travelled += delta * trail_radius.max(voxel_size);
+                Some(sphere)
+            })
+        });
+    spheres_to_sdf(spheres, voxel_size, half_width)
+}
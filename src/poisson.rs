@@ -0,0 +1,223 @@
+use crate::data_structure::{Grid, Tree};
+
+use glam::IVec3;
+use std::collections::HashMap;
+
+/// Marks a missing neighbor in the matrix of [`PoissonSolver`].
+const NO_NEIGHBOR: u32 = u32::MAX;
+
+/// Outcome of [`PoissonSolver::solve`], matching OpenVDB's `math::pcg::State`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SolverState {
+    /// Whether the relative error dropped below the tolerance
+    pub success: bool,
+    pub iterations: u32,
+    /// Largest residual relative to the largest right-hand side value
+    pub relative_error: f64,
+    /// Largest residual
+    pub absolute_error: f64,
+}
+
+/// Solves the Poisson equation `∇²p = b` on the active voxels of a grid with the
+/// preconditioned conjugate gradient method, matching OpenVDB's `tools::poisson`.
+///
+/// This is the pressure projection at the heart of smoke and liquid solvers: solving with the
+/// divergence of a velocity field gives the pressure whose gradient, subtracted from the
+/// velocities, makes them divergence free. The Laplacian uses the seven point stencil, so the
+/// voxels are assumed to be cubic, and the matrix is preconditioned with its diagonal.
+///
+/// Unknowns are the active voxels of the right-hand side. Neighbors outside of them take their
+/// value from the boundary grids: active voxels of the Neumann grid are solid walls where the
+/// derivative of `p` across the face, pointing out of the domain, is the value stored there,
+/// active voxels of the Dirichlet grid fix `p` to their value and all other neighbors fix it to
+/// zero, like free surfaces. Problems without any Dirichlet boundary only have a solution if
+/// the right-hand side is consistent with the Neumann values, and it is unique up to a
+/// constant.
+pub struct PoissonSolver<'a, const L5: u32 = 5, const L4: u32 = 4, const L3: u32 = 3> {
+    dirichlet: Option<&'a Grid<f32, L5, L4, L3>>,
+    neumann: Option<&'a Grid<f32, L5, L4, L3>>,
+    max_iterations: u32,
+    tolerance: f64,
+}
+
+impl<'a, const L5: u32, const L4: u32, const L3: u32> Default for PoissonSolver<'a, L5, L4, L3> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, const L5: u32, const L4: u32, const L3: u32> PoissonSolver<'a, L5, L4, L3> {
+    /// Solver with zero Dirichlet boundaries, stopping after 1000 iterations or once the
+    /// relative error drops below `1e-6`.
+    pub fn new() -> Self {
+        Self {
+            dirichlet: None,
+            neumann: None,
+            max_iterations: 1000,
+            tolerance: 1e-6,
+        }
+    }
+
+    /// Fixes the solution to the values of the active voxels of `grid` where they border the
+    /// domain.
+    pub fn with_dirichlet(mut self, grid: &'a Grid<f32, L5, L4, L3>) -> Self {
+        self.dirichlet = Some(grid);
+        self
+    }
+
+    /// Treats the active voxels of `grid` as solid walls with the outward derivatives of the
+    /// solution stored in them, zero for walls at rest. Neumann voxels take precedence over
+    /// Dirichlet voxels.
+    pub fn with_neumann(mut self, grid: &'a Grid<f32, L5, L4, L3>) -> Self {
+        self.neumann = Some(grid);
+        self
+    }
+
+    pub fn with_max_iterations(mut self, max_iterations: u32) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// Relative error, the largest residual over the largest right-hand side value, at which
+    /// the solver stops.
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Solves for the world space Laplacian of the result being `rhs` at its active voxels,
+    /// returning the solution with the topology, transform and descriptor of `rhs`.
+    pub fn solve(&self, rhs: &Grid<f32, L5, L4, L3>) -> (Grid<f32, L5, L4, L3>, SolverState) {
+        let dx = rhs.voxel_size().x as f64;
+        let mut coords = vec![];
+        rhs.tree.for_each_active_voxel(|coord| coords.push(coord));
+        let index = coords
+            .iter()
+            .enumerate()
+            .map(|(idx, &coord)| (coord, idx as u32))
+            .collect::<HashMap<_, _>>();
+
+        // Negated Laplacian scaled by dx², which is symmetric positive (semi)definite
+        let mut diagonal = vec![0.0; coords.len()];
+        let mut neighbors = vec![[NO_NEIGHBOR; 6]; coords.len()];
+        let mut b = vec![0.0; coords.len()];
+        let mut rhs_accessor = rhs.tree.accessor();
+        let mut dirichlet = self.dirichlet.map(|grid| grid.tree.accessor());
+        let mut neumann = self.neumann.map(|grid| grid.tree.accessor());
+        let offsets = [
+            IVec3::X,
+            IVec3::NEG_X,
+            IVec3::Y,
+            IVec3::NEG_Y,
+            IVec3::Z,
+            IVec3::NEG_Z,
+        ];
+        for (idx, &coord) in coords.iter().enumerate() {
+            b[idx] = -rhs_accessor.get_value(coord) as f64 * dx * dx;
+            for (slot, offset) in offsets.into_iter().enumerate() {
+                let neighbor = coord + offset;
+                if let Some(&neighbor_idx) = index.get(&neighbor) {
+                    diagonal[idx] += 1.0;
+                    neighbors[idx][slot] = neighbor_idx;
+                } else if let Some(value) = neumann
+                    .as_mut()
+                    .map(|accessor| accessor.probe_value(neighbor))
+                    .and_then(|(value, active)| active.then_some(value))
+                {
+                    // The wall fixes the difference to the neighbor instead of its value
+                    b[idx] += value as f64 * dx;
+                } else {
+                    diagonal[idx] += 1.0;
+                    if let Some(value) = dirichlet
+                        .as_mut()
+                        .map(|accessor| accessor.probe_value(neighbor))
+                        .and_then(|(value, active)| active.then_some(value))
+                    {
+                        b[idx] += value as f64;
+                    }
+                }
+            }
+        }
+
+        let multiply = |x: &[f64], out: &mut [f64]| {
+            for idx in 0..x.len() {
+                let mut sum = diagonal[idx] * x[idx];
+                for &neighbor in &neighbors[idx] {
+                    if neighbor != NO_NEIGHBOR {
+                        sum -= x[neighbor as usize];
+                    }
+                }
+                out[idx] = sum;
+            }
+        };
+        let precondition = |r: &[f64], out: &mut [f64]| {
+            for idx in 0..r.len() {
+                out[idx] = if diagonal[idx] > 0.0 {
+                    r[idx] / diagonal[idx]
+                } else {
+                    r[idx]
+                };
+            }
+        };
+        let dot = |a: &[f64], b: &[f64]| a.iter().zip(b).map(|(a, b)| a * b).sum::<f64>();
+        let max_norm = |a: &[f64]| a.iter().fold(0.0f64, |max, value| max.max(value.abs()));
+
+        let mut x = vec![0.0; coords.len()];
+        let mut r = b.clone();
+        let mut z = vec![0.0; coords.len()];
+        let mut q = vec![0.0; coords.len()];
+        let b_norm = max_norm(&b);
+        let mut state = SolverState {
+            absolute_error: max_norm(&r),
+            ..Default::default()
+        };
+        let relative = |absolute: f64| {
+            if b_norm > 0.0 {
+                absolute / b_norm
+            } else {
+                absolute
+            }
+        };
+        state.relative_error = relative(state.absolute_error);
+        state.success = state.relative_error <= self.tolerance;
+
+        precondition(&r, &mut z);
+        let mut p = z.clone();
+        let mut rho = dot(&r, &z);
+        while !state.success && state.iterations < self.max_iterations {
+            multiply(&p, &mut q);
+            let pq = dot(&p, &q);
+            if pq <= 0.0 {
+                break;
+            }
+            let alpha = rho / pq;
+            for idx in 0..x.len() {
+                x[idx] += alpha * p[idx];
+                r[idx] -= alpha * q[idx];
+            }
+            state.iterations += 1;
+            state.absolute_error = max_norm(&r);
+            state.relative_error = relative(state.absolute_error);
+            state.success = state.relative_error <= self.tolerance;
+
+            precondition(&r, &mut z);
+            let rho_next = dot(&r, &z);
+            let beta = rho_next / rho;
+            rho = rho_next;
+            for idx in 0..p.len() {
+                p[idx] = z[idx] + beta * p[idx];
+            }
+        }
+
+        let mut tree = Tree::<f32, L5, L4, L3>::new(0.0);
+        for (coord, value) in coords.into_iter().zip(x) {
+            tree.set_value_on(coord, value as f32);
+        }
+        let solution = Grid {
+            tree,
+            transform: rhs.transform.clone(),
+            descriptor: rhs.descriptor.clone(),
+        };
+        (solution, state)
+    }
+}
@@ -0,0 +1,321 @@
+use crate::coordinates::CoordBBox;
+use crate::data_structure::{Grid, GridClass, GridDescriptor, Tree};
+use crate::mesh_to_volume::mesh_to_volume;
+use crate::transform::Transform;
+
+use glam::{DVec2, DVec3, Vec3};
+
+/// Golden ratio, which places the vertices of the dodecahedron and icosahedron.
+const PHI: f64 = 1.618_033_988_749_895;
+
+/// Rasterizes the signed distance function `distance`, mapping world space positions to world
+/// space distances, into a level set with voxels of `voxel_size` world units and a narrow band
+/// of `half_width` voxels. The surface must lie within the world space box from `min` to `max`.
+fn analytic_to_sdf<const L5: u32, const L4: u32, const L3: u32>(
+    min: DVec3,
+    max: DVec3,
+    voxel_size: f64,
+    half_width: f32,
+    distance: impl Fn(DVec3) -> f64,
+) -> Grid<f32, L5, L4, L3> {
+    let half_width = half_width.max(1.0);
+    let background = half_width * voxel_size as f32;
+    let transform = Transform::from_voxel_size(voxel_size);
+
+    let mut tree = Tree::<f32, L5, L4, L3>::new(background);
+    let min = (transform.world_to_index_f64(min) - half_width as f64).floor();
+    let max = (transform.world_to_index_f64(max) + half_width as f64).ceil();
+    for coord in CoordBBox::new(min.as_ivec3(), max.as_ivec3()) {
+        let value = distance(transform.index_to_world_f64(coord.as_dvec3())) as f32;
+        if value >= background {
+            continue;
+        }
+        // Voxels deep inside become inactive, so pruning collapses the interior into tiles
+        if value > -background {
+            tree.set_value_on(coord, value);
+        } else {
+            tree.set_value_off(coord, -background);
+        }
+    }
+    tree.prune_level_set();
+
+    let mut descriptor = GridDescriptor::new("", Tree::<f32, L5, L4, L3>::type_name("float"));
+    descriptor.set_grid_class(GridClass::LevelSet);
+    Grid {
+        tree,
+        transform,
+        descriptor,
+    }
+}
+
+/// Level set of a sphere of `radius` around `center`, with voxels of `voxel_size` world units
+/// and a narrow band of `half_width` voxels on either side of the surface, matching OpenVDB's
+/// `tools::createLevelSetSphere`.
+///
+/// Like every generator in this module, distances are exact and `half_width` is raised to one
+/// voxel if smaller.
+pub fn create_level_set_sphere<const L5: u32, const L4: u32, const L3: u32>(
+    radius: f32,
+    center: Vec3,
+    voxel_size: f64,
+    half_width: f32,
+) -> Grid<f32, L5, L4, L3> {
+    let (center, radius) = (center.as_dvec3(), radius as f64);
+    analytic_to_sdf(
+        center - radius,
+        center + radius,
+        voxel_size,
+        half_width,
+        |p| p.distance(center) - radius,
+    )
+}
+
+/// Level set of the axis aligned box from `min` to `max`, see [`create_level_set_sphere`].
+/// Distances outside the box are measured to its rounded off edges and corners.
+pub fn create_level_set_box<const L5: u32, const L4: u32, const L3: u32>(
+    min: Vec3,
+    max: Vec3,
+    voxel_size: f64,
+    half_width: f32,
+) -> Grid<f32, L5, L4, L3> {
+    let (min, max) = (min.as_dvec3(), max.as_dvec3());
+    let (center, half_extent) = ((min + max) * 0.5, (max - min) * 0.5);
+    analytic_to_sdf(min, max, voxel_size, half_width, |p| {
+        let q = (p - center).abs() - half_extent;
+        q.max(DVec3::ZERO).length() + q.max_element().min(0.0)
+    })
+}
+
+/// Level set of a torus around `center` lying in the xz-plane, with the tube of `minor_radius`
+/// running along a circle of `major_radius`, see [`create_level_set_sphere`].
+pub fn create_level_set_torus<const L5: u32, const L4: u32, const L3: u32>(
+    major_radius: f32,
+    minor_radius: f32,
+    center: Vec3,
+    voxel_size: f64,
+    half_width: f32,
+) -> Grid<f32, L5, L4, L3> {
+    let center = center.as_dvec3();
+    let (major, minor) = (major_radius as f64, minor_radius as f64);
+    let extent = DVec3::new(major + minor, minor, major + minor);
+    analytic_to_sdf(
+        center - extent,
+        center + extent,
+        voxel_size,
+        half_width,
+        |p| {
+            let p = p - center;
+            DVec2::new(DVec2::new(p.x, p.z).length() - major, p.y).length() - minor
+        },
+    )
+}
+
+/// Level set of a capsule, the points within `radius` of the segment from `a` to `b`, see
+/// [`create_level_set_sphere`].
+pub fn create_level_set_capsule<const L5: u32, const L4: u32, const L3: u32>(
+    a: Vec3,
+    b: Vec3,
+    radius: f32,
+    voxel_size: f64,
+    half_width: f32,
+) -> Grid<f32, L5, L4, L3> {
+    let (a, b, radius) = (a.as_dvec3(), b.as_dvec3(), radius as f64);
+    let axis = b - a;
+    let length_squared = axis.length_squared();
+    analytic_to_sdf(
+        a.min(b) - radius,
+        a.max(b) + radius,
+        voxel_size,
+        half_width,
+        |p| {
+            let t = if length_squared > 0.0 {
+                ((p - a).dot(axis) / length_squared).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            p.distance(a + axis * t) - radius
+        },
+    )
+}
+
+/// The five convex regular polyhedra, see [`create_level_set_platonic`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PlatonicSolid {
+    Tetrahedron,
+    Cube,
+    Octahedron,
+    Dodecahedron,
+    Icosahedron,
+}
+
+impl PlatonicSolid {
+    /// Vertices of the solid on the unit sphere.
+    fn vertices(self) -> Vec<DVec3> {
+        let signs = |v: DVec3| {
+            let mut vertices = vec![];
+            for x in [-1.0, 1.0] {
+                for y in [-1.0, 1.0] {
+                    for z in [-1.0, 1.0] {
+                        let vertex = v * DVec3::new(x, y, z);
+                        if !vertices.contains(&vertex) {
+                            vertices.push(vertex);
+                        }
+                    }
+                }
+            }
+            vertices
+        };
+        // Cyclic permutations of the coordinates of `v` with every combination of signs
+        let permutations = |v: DVec3| {
+            [v, DVec3::new(v.y, v.z, v.x), DVec3::new(v.z, v.x, v.y)]
+                .into_iter()
+                .flat_map(signs)
+                .collect::<Vec<_>>()
+        };
+        let vertices = match self {
+            PlatonicSolid::Tetrahedron => vec![
+                DVec3::new(1.0, 1.0, 1.0),
+                DVec3::new(1.0, -1.0, -1.0),
+                DVec3::new(-1.0, 1.0, -1.0),
+                DVec3::new(-1.0, -1.0, 1.0),
+            ],
+            PlatonicSolid::Cube => signs(DVec3::ONE),
+            PlatonicSolid::Octahedron => permutations(DVec3::X),
+            PlatonicSolid::Dodecahedron => {
+                let mut vertices = signs(DVec3::ONE);
+                vertices.extend(permutations(DVec3::new(0.0, 1.0 / PHI, PHI)));
+                vertices
+            }
+            PlatonicSolid::Icosahedron => permutations(DVec3::new(0.0, 1.0, PHI)),
+        };
+        vertices.into_iter().map(DVec3::normalize).collect()
+    }
+
+    /// Triangulated faces of the convex hull of `vertices`, found as the planes through three
+    /// vertices that have all others on one side. The vertices of each face are sorted by angle
+    /// around its centroid before fanning, so faces with more than three vertices don't produce
+    /// overlapping triangles.
+    fn faces(vertices: &[DVec3]) -> Vec<[u32; 3]> {
+        const EPSILON: f64 = 1e-9;
+        let mut planes = Vec::<(DVec3, f64)>::new();
+        for i in 0..vertices.len() {
+            for j in i + 1..vertices.len() {
+                for k in j + 1..vertices.len() {
+                    let (a, b, c) = (vertices[i], vertices[j], vertices[k]);
+                    let mut normal = (b - a).cross(c - a).normalize_or_zero();
+                    if normal == DVec3::ZERO {
+                        continue;
+                    }
+                    if normal.dot(a) < 0.0 {
+                        normal = -normal;
+                    }
+                    let offset = normal.dot(a);
+                    let is_face = vertices.iter().all(|v| normal.dot(*v) <= offset + EPSILON);
+                    let is_new = planes
+                        .iter()
+                        .all(|(other, _)| other.distance(normal) > EPSILON);
+                    if is_face && is_new {
+                        planes.push((normal, offset));
+                    }
+                }
+            }
+        }
+
+        let mut triangles = vec![];
+        for (normal, offset) in planes {
+            let mut face = (0..vertices.len() as u32)
+                .filter(|&idx| (normal.dot(vertices[idx as usize]) - offset).abs() <= EPSILON)
+                .collect::<Vec<_>>();
+            let centroid = face
+                .iter()
+                .map(|&idx| vertices[idx as usize])
+                .sum::<DVec3>()
+                / face.len() as f64;
+            let u = (vertices[face[0] as usize] - centroid).normalize();
+            let v = normal.cross(u);
+            let angle = |idx: u32| {
+                let d = vertices[idx as usize] - centroid;
+                d.dot(v).atan2(d.dot(u))
+            };
+            face.sort_by(|&a, &b| angle(a).total_cmp(&angle(b)));
+            for pair in face[1..].windows(2) {
+                triangles.push([face[0], pair[0], pair[1]]);
+            }
+        }
+        triangles
+    }
+}
+
+/// Level set of a platonic `solid` around `center`, with its vertices at a distance of `scale`
+/// from the center, see [`create_level_set_sphere`]. Matches OpenVDB's
+/// `tools::createLevelSetPlatonic`, which also rasterizes the solid as a mesh.
+pub fn create_level_set_platonic<const L5: u32, const L4: u32, const L3: u32>(
+    solid: PlatonicSolid,
+    scale: f32,
+    center: Vec3,
+    voxel_size: f64,
+    half_width: f32,
+) -> Grid<f32, L5, L4, L3> {
+    let vertices = solid.vertices();
+    let triangles = PlatonicSolid::faces(&vertices);
+    let positions = vertices
+        .into_iter()
+        .map(|vertex| (vertex * scale as f64).as_vec3() + center)
+        .collect::<Vec<_>>();
+    mesh_to_volume(&positions, &triangles, voxel_size, half_width)
+}
+
+/// Level set of a dodecahedron, see [`create_level_set_platonic`].
+pub fn create_level_set_dodecahedron<const L5: u32, const L4: u32, const L3: u32>(
+    scale: f32,
+    center: Vec3,
+    voxel_size: f64,
+    half_width: f32,
+) -> Grid<f32, L5, L4, L3> {
+    create_level_set_platonic(
+        PlatonicSolid::Dodecahedron,
+        scale,
+        center,
+        voxel_size,
+        half_width,
+    )
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::data_structure::VdbLevel;
+
+    use glam::IVec3;
+
+    /// Level set of a unit sphere around `center` with voxels of 0.1 and a background of 0.3,
+    /// shared by the tests of the tools that consume level sets.
+    pub(crate) fn sphere(center: Vec3) -> Grid<f32> {
+        create_level_set_sphere(1.0, center, 0.1, 3.0)
+    }
+
+    #[test]
+    fn sphere_values_are_distances() {
+        let grid = sphere(Vec3::ZERO);
+        assert_eq!(grid.tree.background, 0.3);
+        assert_eq!(grid.grid_class(), GridClass::LevelSet);
+        for (position, value, level) in grid.iter() {
+            assert_eq!(level, VdbLevel::Voxel);
+            let distance = grid.transform.index_to_world(position).length() - 1.0;
+            assert!(
+                (value - distance).abs() < 1e-4,
+                "{position}: {value} != {distance}"
+            );
+        }
+        assert!(grid.tree.get_value(IVec3::ZERO) < 0.0);
+        assert!(grid.tree.get_value(IVec3::splat(20)) > 0.0);
+    }
+
+    #[test]
+    fn box_contains_its_center_only() {
+        let grid: Grid<f32> = create_level_set_box(Vec3::splat(-1.0), Vec3::ONE, 0.1, 3.0);
+        assert_eq!(grid.tree.get_value(IVec3::ZERO), -0.3);
+        assert!((grid.tree.get_value(IVec3::new(10, 0, 0))).abs() < 1e-4);
+        assert_eq!(grid.tree.get_value(IVec3::splat(20)), 0.3);
+    }
+}
@@ -0,0 +1,232 @@
+//! Python bindings, a lightweight alternative to the OpenVDB Python wheels for reading,
+//! sampling and converting `float` grids. Build the `vdb_rs` extension module with the
+//! `python` feature, e.g. `maturin build --features python,pyo3/extension-module`:
+//!
+//! ```python
+//! import vdb_rs
+//!
+//! density = vdb_rs.read_grid("smoke.vdb", "density")
+//! values = density.sample(points)  # (N, 3) world space positions
+//! array = density.to_numpy()  # active bounding box as a [x][y][z] array
+//! vdb_rs.write_nanovdb("smoke.nvdb", density)
+//! ```
+
+// The `PyResult` conversions generated by the pyo3 macros trip this lint
+#![allow(clippy::useless_conversion)]
+
+use crate::data_structure::{Grid, GridDescriptor, Tree};
+use crate::nanovdb::write_nanovdb as write_nanovdb_file;
+use crate::reader::{ParseError, VdbReader};
+use crate::sampling::{BoxSampler, GridSampler};
+use crate::transform::Transform;
+
+use glam::{DVec3, IVec3};
+use numpy::{IntoPyArray, PyArray1, PyArray2, PyArray3, PyReadonlyArray2, PyReadonlyArray3};
+use pyo3::exceptions::{PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+
+fn to_py_err(error: ParseError) -> PyErr {
+    match error {
+        ParseError::IoError(error) => error.into(),
+        ParseError::InvalidGridName(name) => PyKeyError::new_err(name),
+        error => PyValueError::new_err(error.to_string()),
+    }
+}
+
+fn open(path: PathBuf) -> PyResult<VdbReader<BufReader<File>>> {
+    VdbReader::new(BufReader::new(File::open(path)?)).map_err(to_py_err)
+}
+
+fn is_float_grid(gd: &GridDescriptor) -> bool {
+    gd.grid_type.starts_with("Tree_float_")
+}
+
+/// A grid of `float` values.
+#[pyclass(name = "FloatGrid", module = "vdb_rs")]
+pub struct PyFloatGrid {
+    grid: Grid<f32>,
+}
+
+#[pymethods]
+impl PyFloatGrid {
+    /// Grid holding the elements of a `[x][y][z]` indexed array as active voxels, with its
+    /// `[0, 0, 0]` element at index `origin`. Elements equal to `background` are left out.
+    #[staticmethod]
+    #[pyo3(signature = (array, origin = (0, 0, 0), voxel_size = 1.0, background = 0.0, name = ""))]
+    fn from_numpy(
+        array: PyReadonlyArray3<'_, f32>,
+        origin: (i32, i32, i32),
+        voxel_size: f64,
+        background: f32,
+        name: &str,
+    ) -> Self {
+        let tree = Tree::from_ndarray(array.as_array(), origin.into(), background);
+        Self {
+            grid: Grid {
+                tree,
+                transform: Transform::from_voxel_size(voxel_size),
+                descriptor: GridDescriptor::new(name, Tree::<f32>::type_name("float")),
+            },
+        }
+    }
+
+    #[getter]
+    fn name(&self) -> &str {
+        &self.grid.descriptor.name
+    }
+
+    #[setter]
+    fn set_name(&mut self, name: String) {
+        self.grid.descriptor.name = name.into();
+    }
+
+    /// Class of the grid, e.g. `level set` or `fog volume`.
+    #[getter]
+    fn grid_class(&self) -> &'static str {
+        self.grid.grid_class().name()
+    }
+
+    #[getter]
+    fn background(&self) -> f32 {
+        self.grid.tree.background
+    }
+
+    #[getter]
+    fn voxel_size(&self) -> (f64, f64, f64) {
+        self.grid.transform.voxel_size().into()
+    }
+
+    /// The row major 4x4 index to world matrix, with positions as column vectors.
+    fn index_to_world<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<f64>> {
+        let matrix = self.grid.transform.map.to_matrix();
+        let rows = (0..4).map(|row| (0..4).map(|column| matrix.col(column)[row]).collect());
+        PyArray2::from_vec2_bound(py, &rows.collect::<Vec<Vec<f64>>>()).unwrap()
+    }
+
+    fn active_voxel_count(&self) -> u64 {
+        self.grid.active_voxel_count()
+    }
+
+    /// Inclusive index space bounding box of the active voxels as `(min, max)`, or `None` if
+    /// the grid has no active voxels.
+    #[allow(clippy::type_complexity)]
+    fn active_bbox(&self) -> Option<((i32, i32, i32), (i32, i32, i32))> {
+        let bbox = self.grid.eval_active_voxel_bounding_box();
+        (!bbox.is_empty()).then(|| (bbox.min.into(), bbox.max.into()))
+    }
+
+    /// Value of the voxel at index `(i, j, k)`.
+    fn value(&self, i: i32, j: i32, k: i32) -> f32 {
+        self.grid.tree.get_value(IVec3::new(i, j, k))
+    }
+
+    /// Trilinearly interpolated values at an `(N, 3)` array of world space positions.
+    fn sample<'py>(
+        &self,
+        py: Python<'py>,
+        points: PyReadonlyArray2<'_, f64>,
+    ) -> PyResult<Bound<'py, PyArray1<f32>>> {
+        let points = points.as_array();
+        if points.ncols() != 3 {
+            return Err(PyValueError::new_err("points must have shape (N, 3)"));
+        }
+        let mut sampler = GridSampler::<BoxSampler, f32>::new(&self.grid);
+        let values = points
+            .rows()
+            .into_iter()
+            .map(|point| sampler.sample(DVec3::new(point[0], point[1], point[2])))
+            .collect::<Vec<_>>();
+        Ok(values.into_pyarray_bound(py))
+    }
+
+    /// Values inside the inclusive index space box from `min` to `max` as a `[x][y][z]` indexed
+    /// array, the active bounding box if not given. Voxels not stored in the grid take their
+    /// tile or background value.
+    #[pyo3(signature = (min = None, max = None))]
+    fn to_numpy<'py>(
+        &self,
+        py: Python<'py>,
+        min: Option<(i32, i32, i32)>,
+        max: Option<(i32, i32, i32)>,
+    ) -> Bound<'py, PyArray3<f32>> {
+        let mut bbox = self.grid.eval_active_voxel_bounding_box();
+        if let Some(min) = min {
+            bbox.min = min.into();
+        }
+        if let Some(max) = max {
+            bbox.max = max.into();
+        }
+        self.grid.to_ndarray(bbox).into_pyarray_bound(py)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "FloatGrid(name={:?}, class={:?}, active_voxels={})",
+            self.grid.descriptor.name,
+            self.grid_class(),
+            self.grid.active_voxel_count()
+        )
+    }
+}
+
+/// Names of all grids in the VDB file at `path`, in no particular order.
+#[pyfunction]
+fn grid_names(path: PathBuf) -> PyResult<Vec<String>> {
+    Ok(open(path)?.available_grids())
+}
+
+/// Reads the `float` grid called `name` from the VDB file at `path`.
+#[pyfunction]
+fn read_grid(path: PathBuf, name: &str) -> PyResult<PyFloatGrid> {
+    let mut reader = open(path)?;
+    let gd = reader
+        .grid_descriptors
+        .get(name)
+        .ok_or_else(|| PyKeyError::new_err(name.to_owned()))?;
+    if !is_float_grid(gd) {
+        let message = format!("Grid type {} isn't a float grid", gd.grid_type);
+        return Err(PyValueError::new_err(message));
+    }
+    let grid = reader.read_grid::<f32>(name).map_err(to_py_err)?;
+    Ok(PyFloatGrid { grid })
+}
+
+/// Reads all `float` grids from the VDB file at `path` in file order, skipping grids of other
+/// value types.
+#[pyfunction]
+fn read(path: PathBuf) -> PyResult<Vec<PyFloatGrid>> {
+    let mut reader = open(path)?;
+    let mut descriptors = reader
+        .grid_descriptors
+        .values()
+        .filter(|gd| is_float_grid(gd))
+        .map(|gd| (gd.grid_pos, gd.name.clone()))
+        .collect::<Vec<_>>();
+    descriptors.sort();
+    descriptors
+        .into_iter()
+        .map(|(_, name)| {
+            let grid = reader.read_grid::<f32>(&name).map_err(to_py_err)?;
+            Ok(PyFloatGrid { grid })
+        })
+        .collect()
+}
+
+/// Writes `grid` to a NanoVDB file at `path`, ready for GPU upload.
+#[pyfunction]
+fn write_nanovdb(path: PathBuf, grid: &PyFloatGrid) -> PyResult<()> {
+    Ok(write_nanovdb_file(&grid.grid, path)?)
+}
+
+#[pymodule]
+fn vdb_rs(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyFloatGrid>()?;
+    module.add_function(wrap_pyfunction!(grid_names, module)?)?;
+    module.add_function(wrap_pyfunction!(read, module)?)?;
+    module.add_function(wrap_pyfunction!(read_grid, module)?)?;
+    module.add_function(wrap_pyfunction!(write_nanovdb, module)?)?;
+    Ok(())
+}
@@ -0,0 +1,128 @@
+use crate::coordinates::CoordBBox;
+use crate::data_structure::Grid;
+use crate::dense::copy_to_dense;
+use crate::json::{json_array, json_string};
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use glam::Vec3;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Values that can be written to raw binary files, see [`export_raw`].
+pub trait RawValue: Copy {
+    /// Name of the component type in the descriptor, e.g. `float32`
+    const TYPE_NAME: &'static str;
+    /// Number of components, 1 for scalars and 3 for vectors
+    const COMPONENTS: u32;
+
+    /// Writes the components of the value in little endian.
+    fn write_raw(self, out: &mut impl Write) -> std::io::Result<()>;
+}
+
+impl RawValue for f32 {
+    const TYPE_NAME: &'static str = "float32";
+    const COMPONENTS: u32 = 1;
+
+    fn write_raw(self, out: &mut impl Write) -> std::io::Result<()> {
+        out.write_f32::<LittleEndian>(self)
+    }
+}
+
+impl RawValue for f64 {
+    const TYPE_NAME: &'static str = "float64";
+    const COMPONENTS: u32 = 1;
+
+    fn write_raw(self, out: &mut impl Write) -> std::io::Result<()> {
+        out.write_f64::<LittleEndian>(self)
+    }
+}
+
+impl RawValue for i32 {
+    const TYPE_NAME: &'static str = "int32";
+    const COMPONENTS: u32 = 1;
+
+    fn write_raw(self, out: &mut impl Write) -> std::io::Result<()> {
+        out.write_i32::<LittleEndian>(self)
+    }
+}
+
+impl RawValue for bool {
+    const TYPE_NAME: &'static str = "uint8";
+    const COMPONENTS: u32 = 1;
+
+    fn write_raw(self, out: &mut impl Write) -> std::io::Result<()> {
+        out.write_u8(self as u8)
+    }
+}
+
+impl RawValue for Vec3 {
+    const TYPE_NAME: &'static str = "float32";
+    const COMPONENTS: u32 = 3;
+
+    fn write_raw(self, out: &mut impl Write) -> std::io::Result<()> {
+        for component in self.to_array() {
+            out.write_f32::<LittleEndian>(component)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes the values of `grid` inside `bbox` to `path` as a contiguous little endian array,
+/// along with a JSON descriptor next to it with the same name and a `.json` extension, the
+/// simplest format for custom engines and machine learning pipelines to load.
+///
+/// The array has the layout of [`Dense`], indexed as `[x][y][z]` with `z` varying fastest and
+/// vector components interleaved, so it loads as a row major array of shape `dims`. Voxels not
+/// stored in the tree take their tile or background value. The descriptor holds:
+///
+/// - `name`, the name of the grid
+/// - `value_type` and `components`, e.g. `float32` and `3` for vector grids
+/// - `dims`, the number of voxels along x, y and z
+/// - `bbox_min`, the index space coordinate of the first voxel
+/// - `voxel_size`, the world space size of a voxel along each axis
+/// - `origin`, the world space position of the center of the first voxel
+/// - `index_to_world`, the row major 4x4 matrix of the grid transform
+///
+/// [`Dense`]: crate::Dense
+pub fn export_raw<ValueTy: RawValue>(
+    grid: &Grid<ValueTy>,
+    bbox: CoordBBox,
+    path: impl AsRef<Path>,
+) -> std::io::Result<()> {
+    let path = path.as_ref();
+    let dense = copy_to_dense(grid, bbox);
+    let mut out = BufWriter::new(File::create(path)?);
+    for &value in &dense.data {
+        value.write_raw(&mut out)?;
+    }
+    out.flush()?;
+
+    let matrix = grid.transform.map.to_matrix();
+    let rows = (0..4).flat_map(|row| (0..4).map(move |column| matrix.col(column)[row]));
+    let origin = grid.transform.index_to_world_f64(bbox.min.as_dvec3());
+    let descriptor = format!(
+        concat!(
+            "{{\n",
+            "  \"name\": {},\n",
+            "  \"value_type\": \"{}\",\n",
+            "  \"components\": {},\n",
+            "  \"byte_order\": \"little\",\n",
+            "  \"dims\": {},\n",
+            "  \"bbox_min\": {},\n",
+            "  \"voxel_size\": {},\n",
+            "  \"origin\": {},\n",
+            "  \"index_to_world\": {}\n",
+            "}}\n"
+        ),
+        json_string(&grid.descriptor.name),
+        ValueTy::TYPE_NAME,
+        ValueTy::COMPONENTS,
+        json_array(dense.dims().to_array()),
+        json_array(bbox.min.to_array()),
+        json_array(grid.transform.voxel_size().to_array()),
+        json_array(origin.to_array()),
+        json_array(rows),
+    );
+    std::fs::write(path.with_extension("json"), descriptor)
+}
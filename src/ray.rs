@@ -0,0 +1,78 @@
+use crate::transform::Transform;
+
+use glam::DVec3;
+
+/// Ray `origin + t * direction` restricted to the parameter range `[t0, t1]`, matching
+/// OpenVDB's `math::Ray`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: DVec3,
+    pub direction: DVec3,
+    pub t0: f64,
+    pub t1: f64,
+}
+
+impl Ray {
+    /// Ray from `origin` along `direction` over the range `[0, inf)`. The direction is not
+    /// normalized, so `t` is measured in multiples of its length.
+    pub fn new(origin: DVec3, direction: DVec3) -> Self {
+        Self {
+            origin,
+            direction,
+            t0: 0.0,
+            t1: f64::INFINITY,
+        }
+    }
+
+    pub fn with_range(mut self, t0: f64, t1: f64) -> Self {
+        self.t0 = t0;
+        self.t1 = t1;
+        self
+    }
+
+    /// Position at parameter `t` along the ray.
+    pub fn at(&self, t: f64) -> DVec3 {
+        self.origin + self.direction * t
+    }
+
+    /// This world space ray in the index space of `transform`. The direction is transformed
+    /// along with the origin, so `t` refers to the same point in both spaces.
+    pub fn world_to_index(&self, transform: &Transform) -> Self {
+        let origin = transform.world_to_index_f64(self.origin);
+        Self {
+            origin,
+            direction: transform.world_to_index_f64(self.origin + self.direction) - origin,
+            ..*self
+        }
+    }
+
+    /// This index space ray in the world space of `transform`, see [`Ray::world_to_index`].
+    pub fn index_to_world(&self, transform: &Transform) -> Self {
+        let origin = transform.index_to_world_f64(self.origin);
+        Self {
+            origin,
+            direction: transform.index_to_world_f64(self.origin + self.direction) - origin,
+            ..*self
+        }
+    }
+
+    /// Restricts the range of the ray to the part inside the box from `min` to `max`, or
+    /// returns `None` if the ray misses the box within its range.
+    pub fn clip(&self, min: DVec3, max: DVec3) -> Option<Self> {
+        let (mut t0, mut t1) = (self.t0, self.t1);
+        for axis in 0..3 {
+            let (origin, direction) = (self.origin[axis], self.direction[axis]);
+            if direction == 0.0 {
+                if origin < min[axis] || origin > max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let a = (min[axis] - origin) / direction;
+            let b = (max[axis] - origin) / direction;
+            t0 = t0.max(a.min(b));
+            t1 = t1.min(a.max(b));
+        }
+        (t0 <= t1).then(|| self.with_range(t0, t1))
+    }
+}
@@ -0,0 +1,111 @@
+use crate::coordinates::CoordBBox;
+use crate::data_structure::Grid;
+use crate::ray::Ray;
+use crate::sampling::{BoxSampler, GridSampler};
+
+use glam::{DVec3, IVec3};
+
+/// Number of bisection steps used to refine a bracketed surface crossing.
+const REFINE_STEPS: u32 = 12;
+
+/// Smallest step taken while sphere tracing, in voxels, so rays grazing the surface still make
+/// progress.
+const MIN_STEP: f64 = 0.2;
+
+/// Intersects rays with the surface of a level set, matching OpenVDB's
+/// `tools::LevelSetRayIntersector`.
+///
+/// Rays are sphere traced: the signed distance sampled at the current position is a safe step
+/// size, as no surface can be closer than that. Outside the narrow band the background value
+/// bounds the distance from below, so empty space is crossed in steps of the band width. The
+/// intersector keeps a [`GridSampler`] around, so tracing many coherent rays stays fast.
+pub struct LevelSetRayIntersector<'a, const L5: u32 = 5, const L4: u32 = 4, const L3: u32 = 3> {
+    grid: &'a Grid<f32, L5, L4, L3>,
+    sampler: GridSampler<'a, BoxSampler, f32, L5, L4, L3>,
+    isovalue: f32,
+    /// Index space region outside of which the level set holds no surface
+    bbox: CoordBBox,
+}
+
+impl<'a, const L5: u32, const L4: u32, const L3: u32> LevelSetRayIntersector<'a, L5, L4, L3> {
+    /// Intersector for the zero crossing of the level set `grid`.
+    pub fn new(grid: &'a Grid<f32, L5, L4, L3>) -> Self {
+        let mut bbox = grid.tree.eval_active_voxel_bounding_box();
+        bbox.expand(1);
+        Self {
+            grid,
+            sampler: GridSampler::new(grid),
+            isovalue: 0.0,
+            bbox,
+        }
+    }
+
+    /// Intersects the `isovalue` crossing of the level set instead of its zero crossing.
+    pub fn with_isovalue(mut self, isovalue: f32) -> Self {
+        self.isovalue = isovalue;
+        self
+    }
+
+    /// First intersection of the world space `ray` with the surface within its range, returning
+    /// the ray parameter, the world space position and the outward unit normal there.
+    ///
+    /// Rays starting inside the surface report where they leave it.
+    pub fn intersects(&mut self, ray: &Ray) -> Option<(f64, DVec3, DVec3)> {
+        if self.bbox.is_empty() {
+            return None;
+        }
+        let index_ray = ray
+            .world_to_index(&self.grid.transform)
+            .clip(self.bbox.min.as_dvec3(), self.bbox.max.as_dvec3())?;
+
+        // Converts distances in world units to distances along the ray
+        let world_length = ray.direction.length();
+        if world_length == 0.0 {
+            return None;
+        }
+        let min_step = MIN_STEP * self.grid.transform.voxel_size().min_element() / world_length;
+
+        let mut distance = |t: f64| self.sampler.sample_index(index_ray.at(t)) - self.isovalue;
+        let mut t = index_ray.t0;
+        let mut value = distance(t);
+        let inside = value < 0.0;
+        loop {
+            let step = (value.abs() as f64 / world_length).max(min_step);
+            if t >= index_ray.t1 {
+                return None;
+            }
+            let next = (t + step).min(index_ray.t1);
+            let next_value = distance(next);
+            if (next_value < 0.0) != inside {
+                // Bisect the bracket, then place the hit by linear interpolation
+                let (mut a, mut b, mut value_a, mut value_b) = (t, next, value, next_value);
+                for _ in 0..REFINE_STEPS {
+                    let middle = (a + b) * 0.5;
+                    let value_middle = distance(middle);
+                    if (value_middle < 0.0) == inside {
+                        (a, value_a) = (middle, value_middle);
+                    } else {
+                        (b, value_b) = (middle, value_middle);
+                    }
+                }
+                let hit = a + (b - a) * (value_a / (value_a - value_b)) as f64;
+                let index = index_ray.at(hit);
+                return Some((hit, ray.at(hit), self.normal(index)));
+            }
+            (t, value) = (next, next_value);
+        }
+    }
+
+    /// Outward unit normal of the level set at the index space position `index`, from central
+    /// differences over half a voxel.
+    fn normal(&mut self, index: DVec3) -> DVec3 {
+        let gradient = DVec3::from_array([IVec3::X, IVec3::Y, IVec3::Z].map(|axis| {
+            let offset = axis.as_dvec3() * 0.5;
+            (self.sampler.sample_index(index + offset) - self.sampler.sample_index(index - offset))
+                as f64
+        }));
+        // Gradients transform with the inverse transpose of the index to world matrix
+        let matrix = self.grid.transform.map.to_matrix().inverse().transpose();
+        matrix.transform_vector3(gradient).normalize_or_zero()
+    }
+}
@@ -1,19 +1,28 @@
+use crate::arena::{slot_origin, ArenaTree};
 use crate::coordinates::Index;
 use crate::data_structure::{
-    ArchiveHeader, Compression, Grid, GridDescriptor, Metadata, MetadataValue, Node, Node3, Node4,
-    Node5, NodeHeader, NodeMetaData, Tree,
+    load_mask_words, mask_words, ArchiveHeader, Compression, Grid, GridDescriptor, MaskWord,
+    Metadata, MetadataValue, Node, Node3, Node4, Node5, NodeHeader, NodeMetaData, Tree,
 };
-use crate::transform::Map;
+use crate::interning::InternedStr;
+use crate::paging::PagedGrid;
+use crate::simd::{count_ones, expand_active, f16_to_f32, f32_to_f16};
+use crate::soa::SoaTree;
+use crate::transform::{Map, Transform};
 
 use bitvec::prelude::*;
+#[cfg(feature = "blosc")]
 use blosc_src::blosc_cbuffer_sizes;
-use bytemuck::{bytes_of_mut, cast_slice_mut, Pod, Zeroable};
+use bytemuck::{bytes_of_mut, cast_slice_mut, Pod};
 use byteorder::{LittleEndian, ReadBytesExt};
-
+use glam::{DVec3, IVec3, Vec3};
 use half::f16;
 use log::{trace, warn};
+use std::any::TypeId;
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::io::{Read, Seek, SeekFrom};
+use std::sync::Arc;
 
 pub const OPENVDB_MIN_SUPPORTED_VERSION: u32 = OPENVDB_FILE_VERSION_ROOTNODE_MAP;
 
@@ -46,8 +55,20 @@ pub enum ParseError {
     InvalidBloscData,
     #[error("Unsupported Blosc format")]
     UnsupportedBloscFormat,
+    #[error("Blosc compressed data can't be read without the blosc feature")]
+    BloscUnavailable,
     #[error("Invalid grid name: {0}.")]
     InvalidGridName(String),
+    #[error("Grid type {0} doesn't match the requested tree configuration")]
+    TreeConfigMismatch(String),
+    #[error("Data stored as {0} bytes where {1} were expected")]
+    InvalidDataSize(u64, usize),
+    #[error("Unsupported transform map {0}")]
+    UnsupportedTransform(String),
+    #[error("Trees with {0} buffers are not supported")]
+    UnsupportedBufferCount(u32),
+    #[error("Inactive values of type {0} can't be stored as the negated background")]
+    UnsupportedNegation(String),
     #[error("IoError")]
     IoError(#[from] std::io::Error),
 }
@@ -61,6 +82,45 @@ fn read_string<R: Read + Seek>(reader: &mut R, len: usize) -> Result<String, Par
     Ok(string)
 }
 
+/// Negates every component of `value` for the signed scalar and vector types grids are stored
+/// as, returns `None` for types that have no negative.
+pub(crate) fn negative<T: Pod>(value: T) -> Option<T> {
+    fn negate<T: Pod, C: Pod>(mut value: T, neg: impl Fn(C) -> C) -> T {
+        for component in cast_slice_mut::<T, C>(std::slice::from_mut(&mut value)) {
+            *component = neg(*component);
+        }
+        value
+    }
+
+    let type_id = TypeId::of::<T>();
+    let is_any = |ids: &[TypeId]| ids.contains(&type_id);
+    if is_any(&[
+        TypeId::of::<f32>(),
+        TypeId::of::<Vec3>(),
+        TypeId::of::<[f32; 3]>(),
+    ]) {
+        Some(negate(value, |c: f32| -c))
+    } else if is_any(&[
+        TypeId::of::<f64>(),
+        TypeId::of::<DVec3>(),
+        TypeId::of::<[f64; 3]>(),
+    ]) {
+        Some(negate(value, |c: f64| -c))
+    } else if is_any(&[TypeId::of::<f16>()]) {
+        Some(negate(value, |c: f16| -c))
+    } else if is_any(&[
+        TypeId::of::<i32>(),
+        TypeId::of::<IVec3>(),
+        TypeId::of::<[i32; 3]>(),
+    ]) {
+        Some(negate(value, i32::wrapping_neg))
+    } else if is_any(&[TypeId::of::<i64>()]) {
+        Some(negate(value, i64::wrapping_neg))
+    } else {
+        None
+    }
+}
+
 fn read_d_vec3<R: Read + Seek>(reader: &mut R) -> Result<glam::DVec3, ParseError> {
     let x = reader.read_f64::<LittleEndian>()?;
     let y = reader.read_f64::<LittleEndian>()?;
@@ -76,15 +136,110 @@ fn read_i_vec3<R: Read + Seek>(reader: &mut R) -> Result<glam::IVec3, ParseError
     Ok(glam::IVec3::new(x, y, z))
 }
 
+/// Reads a mask stored as little endian 64-bit words.
+fn read_mask<R: Read>(
+    reader: &mut R,
+    mask: &mut BitSlice<MaskWord, Lsb0>,
+) -> Result<(), ParseError> {
+    // Leaf masks fit on the stack, larger ones are rare enough to allocate
+    let len = mask.len().div_ceil(64);
+    let (mut stack, mut heap) = ([0; 8], vec![]);
+    let words = if len <= stack.len() {
+        &mut stack[..len]
+    } else {
+        heap.resize(len, 0);
+        heap.as_mut_slice()
+    };
+    reader.read_u64_into::<LittleEndian>(words)?;
+    load_mask_words(mask, words);
+    Ok(())
+}
+
+thread_local! {
+    /// Compressed bytes of the node being read, kept between nodes to avoid an allocation per
+    /// node.
+    static COMPRESSED_SCRATCH: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+    /// Active values of the node being read before they are expanded, in 8-byte words so they
+    /// can be viewed as most value types.
+    static VALUE_SCRATCH: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The first `len` values of type `T` in `words`, which grow as needed, unless `T` needs more
+/// than 8-byte alignment.
+fn typed_scratch<T: Pod>(words: &mut Vec<u64>, len: usize) -> Option<&mut [T]> {
+    let bytes = len * std::mem::size_of::<T>();
+    if words.len() * 8 < bytes {
+        words.resize(bytes.div_ceil(8), 0);
+    }
+    bytemuck::try_cast_slice_mut(&mut cast_slice_mut::<u64, u8>(words)[..bytes]).ok()
+}
+
+/// Decompresses blosc data into `dest`, which must be exactly the size of the uncompressed data.
+#[cfg(feature = "blosc")]
+fn decompress_blosc(blosc_data: &[u8], dest: &mut [u8]) -> Result<(), ParseError> {
+    let mut nbytes: usize = 0;
+    let mut cbytes: usize = 0;
+    let mut blocksize: usize = 0;
+    unsafe {
+        blosc_cbuffer_sizes(
+            blosc_data.as_ptr().cast(),
+            &mut nbytes,
+            &mut cbytes,
+            &mut blocksize,
+        )
+    };
+    if nbytes == 0 {
+        return Err(ParseError::UnsupportedBloscFormat);
+    }
+    if nbytes != dest.len() {
+        return Err(ParseError::InvalidBloscData);
+    }
+    let error = unsafe {
+        blosc_src::blosc_decompress_ctx(
+            blosc_data.as_ptr().cast(),
+            dest.as_mut_ptr().cast(),
+            nbytes,
+            1,
+        )
+    };
+    if error < 1 {
+        return Err(ParseError::InvalidBloscData);
+    }
+    Ok(())
+}
+
+/// Blosc is a C library, builds without it such as WebAssembly can only read grids compressed
+/// with zlib or not at all.
+#[cfg(not(feature = "blosc"))]
+fn decompress_blosc(_blosc_data: &[u8], _dest: &mut [u8]) -> Result<(), ParseError> {
+    Err(ParseError::BloscUnavailable)
+}
+
 #[derive(Debug)]
 pub struct VdbReader<R: Read + Seek> {
-    reader: R,
+    pub(crate) reader: R,
     pub header: ArchiveHeader,
-    pub grid_descriptors: HashMap<String, GridDescriptor>,
+    pub grid_descriptors: HashMap<InternedStr, GridDescriptor>,
+    /// Names of the grids in the order their descriptors are chained in the archive, which an
+    /// [`crate::ArchiveEditor`] may have made differ from the order of their data
+    pub(crate) grid_order: Vec<InternedStr>,
 }
 
 impl<R: Read + Seek> VdbReader<R> {
     pub fn new(mut reader: R) -> Result<Self, ParseError> {
+        let header = Self::read_header(&mut reader)?;
+        let (grid_descriptors, grid_order) = Self::read_grid_descriptors(&header, &mut reader)?;
+
+        Ok(Self {
+            reader,
+            header,
+            grid_descriptors,
+            grid_order,
+        })
+    }
+
+    /// Reads the archive header, which is followed by the first grid descriptor.
+    pub(crate) fn read_header(reader: &mut R) -> Result<ArchiveHeader, ParseError> {
         let magic = reader.read_u64::<LittleEndian>()?;
         if magic == 0x2042445600000000 {
             return Err(ParseError::MagicMismatch);
@@ -124,13 +279,13 @@ impl<R: Read + Seek> VdbReader<R> {
         let guid = if file_version >= OPENVDB_FILE_VERSION_BOOST_UUID {
             // UUID is stored as fixed-length ASCII string
             // The extra 4 bytes are for the hyphens.
-            read_string(&mut reader, 36)?
+            read_string(reader, 36)?
         } else {
             // Older versions stored the UUID as a byte string.
-            todo!("File version {}", file_version);
+            return Err(ParseError::UnsupportedVersion(file_version));
         };
 
-        let meta_data = Self::read_metadata(&mut reader)?;
+        let meta_data = Self::read_metadata(reader)?;
         let grid_count = reader.read_u32::<LittleEndian>()?;
 
         let header = ArchiveHeader {
@@ -143,27 +298,153 @@ impl<R: Read + Seek> VdbReader<R> {
             meta_data,
             grid_count,
         };
-
-        let grid_descriptors = Self::read_grid_descriptors(&header, &mut reader)?;
-
-        Ok(Self {
-            reader,
-            header,
-            grid_descriptors,
-        })
+        Ok(header)
     }
 
     pub fn read_grid<ExpectedTy: Pod>(
         &mut self,
         name: &str,
     ) -> Result<Grid<ExpectedTy>, ParseError> {
-        let grid_descriptor = self.grid_descriptors.get(name).cloned();
-        let gd = grid_descriptor.ok_or_else(|| ParseError::InvalidGridName(name.to_owned()))?;
+        self.read_grid_with_config(name)
+    }
+
+    /// Reads a grid whose tree uses a non-standard node configuration, e.g. a `Tree_float_4_3_2`
+    /// grid is read with `read_grid_with_config::<f32, 4, 3, 2>`.
+    pub fn read_grid_with_config<ExpectedTy: Pod, const L5: u32, const L4: u32, const L3: u32>(
+        &mut self,
+        name: &str,
+    ) -> Result<Grid<ExpectedTy, L5, L4, L3>, ParseError> {
+        let gd = self.descriptor_for::<ExpectedTy, L5, L4, L3>(name)?;
         Self::read_grid_internal(&self.header, &mut self.reader, gd)
     }
 
+    /// Reads the tree of the grid `name` straight into an [`ArenaTree`], which stores its nodes
+    /// in one slab per level instead of allocating every node. Read its transform with
+    /// [`VdbReader::read_grid_transform`].
+    pub fn read_arena_tree<ExpectedTy: Pod>(
+        &mut self,
+        name: &str,
+    ) -> Result<ArenaTree<ExpectedTy>, ParseError> {
+        self.read_arena_tree_with_config(name)
+    }
+
+    /// Variant of [`VdbReader::read_arena_tree`] for non-standard node configurations, see
+    /// [`VdbReader::read_grid_with_config`].
+    pub fn read_arena_tree_with_config<
+        ExpectedTy: Pod,
+        const L5: u32,
+        const L4: u32,
+        const L3: u32,
+    >(
+        &mut self,
+        name: &str,
+    ) -> Result<ArenaTree<ExpectedTy, L5, L4, L3>, ParseError> {
+        let gd = self.descriptor_for::<ExpectedTy, L5, L4, L3>(name)?;
+        Self::read_arena_tree_internal(&self.header, &mut self.reader, gd)
+    }
+
+    /// Reads the `vec3s` grid `name` into a [`SoaTree`], which splits every leaf into one plane
+    /// per component. Read its transform with [`VdbReader::read_grid_transform`].
+    pub fn read_soa_tree(&mut self, name: &str) -> Result<SoaTree, ParseError> {
+        self.read_soa_tree_with_config(name)
+    }
+
+    /// Variant of [`VdbReader::read_soa_tree`] for non-standard node configurations, see
+    /// [`VdbReader::read_grid_with_config`].
+    pub fn read_soa_tree_with_config<const L5: u32, const L4: u32, const L3: u32>(
+        &mut self,
+        name: &str,
+    ) -> Result<SoaTree<L5, L4, L3>, ParseError> {
+        let grid = self.read_grid_with_config::<[f32; 3], L5, L4, L3>(name)?;
+        Ok(SoaTree::from(grid.tree.map_values(glam::Vec3::from)))
+    }
+
+    /// Turns this reader into a [`PagedGrid`] for the grid `name`, which reads only the tree
+    /// topology up front and pages leaf values in from the file as they are looked up, keeping
+    /// at most `max_resident_leaves` of them in memory.
+    pub fn into_paged_grid<ExpectedTy: Pod>(
+        self,
+        name: &str,
+        max_resident_leaves: usize,
+    ) -> Result<PagedGrid<R, ExpectedTy>, ParseError> {
+        self.into_paged_grid_with_config(name, max_resident_leaves)
+    }
+
+    /// Variant of [`VdbReader::into_paged_grid`] for non-standard node configurations, see
+    /// [`VdbReader::read_grid_with_config`].
+    pub fn into_paged_grid_with_config<
+        ExpectedTy: Pod,
+        const L5: u32,
+        const L4: u32,
+        const L3: u32,
+    >(
+        mut self,
+        name: &str,
+        max_resident_leaves: usize,
+    ) -> Result<PagedGrid<R, ExpectedTy, L5, L4, L3>, ParseError> {
+        let gd = self.descriptor_for::<ExpectedTy, L5, L4, L3>(name)?;
+        let reader = &mut self.reader;
+        let (transform, tree) =
+            Self::read_grid_head::<ExpectedTy, L5, L4, L3>(&self.header, reader, &gd)?;
+
+        // Leaf values are stored in the same order as the topology, remember where each starts
+        gd.seek_to_blocks(reader)?;
+        let mut leaf_offsets = HashMap::new();
+        for node_5 in &tree.root_nodes {
+            for idx in node_5.child_mask.iter_ones() {
+                let node_4 = &node_5.nodes[&(idx as u32)];
+                for idx in node_4.child_mask.iter_ones() {
+                    let node_3 = &node_4.nodes[&(idx as u32)];
+                    leaf_offsets.insert(node_3.origin, reader.stream_position()?);
+                    Self::skip_leaf::<ExpectedTy>(reader, &self.header, &gd, 1 << (3 * L3))?;
+                }
+            }
+        }
+
+        Ok(PagedGrid::new(
+            self.reader,
+            self.header,
+            gd,
+            tree,
+            transform,
+            leaf_offsets,
+            max_resident_leaves,
+        ))
+    }
+
     pub fn available_grids(&self) -> Vec<String> {
-        self.grid_descriptors.keys().cloned().collect()
+        self.grid_descriptors
+            .keys()
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    /// Reads only the transform of the grid `name`, skipping its tree.
+    pub fn read_grid_transform(&mut self, name: &str) -> Result<Transform, ParseError> {
+        let gd = self
+            .grid_descriptors
+            .get(name)
+            .ok_or_else(|| ParseError::InvalidGridName(name.to_owned()))?;
+        Self::read_grid_transform_internal(&self.header, &mut self.reader, gd)
+    }
+
+    /// Descriptor of the grid `name`, checked to be stored with the node configuration of
+    /// `Tree<ValueTy, L5, L4, L3>`.
+    pub(crate) fn descriptor_for<ValueTy, const L5: u32, const L4: u32, const L3: u32>(
+        &self,
+        name: &str,
+    ) -> Result<GridDescriptor, ParseError> {
+        let gd = self
+            .grid_descriptors
+            .get(name)
+            .ok_or_else(|| ParseError::InvalidGridName(name.to_owned()))?;
+        if !gd
+            .grid_type
+            .contains(&Tree::<ValueTy, L5, L4, L3>::config_suffix())
+        {
+            return Err(ParseError::TreeConfigMismatch(gd.grid_type.to_string()));
+        }
+        Ok(gd.clone())
     }
 
     fn read_name(reader: &mut R) -> Result<String, ParseError> {
@@ -171,7 +452,12 @@ impl<R: Read + Seek> VdbReader<R> {
         read_string(reader, len)
     }
 
-    fn read_transform(reader: &mut R) -> Result<Map, ParseError> {
+    /// Reads a grid name, type name or metadata key, which repeat across the grids of a file.
+    fn read_interned_name(reader: &mut R) -> Result<InternedStr, ParseError> {
+        Ok(InternedStr::new(&Self::read_name(reader)?))
+    }
+
+    pub(crate) fn read_transform(reader: &mut R) -> Result<Map, ParseError> {
         let name = Self::read_name(reader)?;
 
         Ok(match name.as_str() {
@@ -190,7 +476,16 @@ impl<R: Read + Seek> VdbReader<R> {
                 inv_scale_sqr: read_d_vec3(reader)?,
                 inv_twice_scale: read_d_vec3(reader)?,
             },
-            v => panic!("Not supported {}", v),
+            "AffineMap" => {
+                let mut matrix = [0.0; 16];
+                reader.read_f64_into::<LittleEndian>(&mut matrix)?;
+                // OpenVDB stores the matrix row-major for row vectors, which is the column-major
+                // layout of the equivalent matrix for column vectors
+                Map::AffineMap {
+                    matrix: glam::DMat4::from_cols_array(&matrix),
+                }
+            }
+            v => return Err(ParseError::UnsupportedTransform(v.to_owned())),
         })
     }
 
@@ -199,22 +494,49 @@ impl<R: Read + Seek> VdbReader<R> {
         log_2_dim: u32,
         header: &ArchiveHeader,
         gd: &GridDescriptor,
+        background: ValueTy,
     ) -> Result<NodeHeader<ValueTy>, ParseError> {
         let linear_dim = (1 << (3 * log_2_dim)) as usize;
 
-        let mut child_mask = bitvec![u64, Lsb0; 0; linear_dim];
-        let mut value_mask = bitvec![u64, Lsb0; 0; linear_dim];
-        reader.read_u64_into::<LittleEndian>(child_mask.as_raw_mut_slice())?;
-        reader.read_u64_into::<LittleEndian>(value_mask.as_raw_mut_slice())?;
-
-        let linear_dim = if header.file_version < OPENVDB_FILE_VERSION_NODE_MASK_COMPRESSION {
-            child_mask.count_zeros()
+        let mut child_mask = bitvec![MaskWord, Lsb0; 0; linear_dim];
+        let mut value_mask = bitvec![MaskWord, Lsb0; 0; linear_dim];
+        read_mask(reader, &mut child_mask)?;
+        read_mask(reader, &mut value_mask)?;
+
+        let data = if header.file_version < OPENVDB_FILE_VERSION_NODE_MASK_COMPRESSION {
+            // Older versions only store values for the slots that don't hold a child node, expand
+            // those so there is exactly one value per slot.
+            let tiles = Self::read_compressed(
+                reader,
+                header,
+                gd,
+                child_mask.count_zeros(),
+                value_mask.as_bitslice(),
+                background,
+            )?;
+            let mut tiles = tiles.into_iter();
+            child_mask
+                .iter()
+                .by_vals()
+                .map(|is_child| {
+                    if is_child {
+                        background
+                    } else {
+                        tiles.next().unwrap_or(background)
+                    }
+                })
+                .collect()
         } else {
-            (1 << (3 * log_2_dim)) as usize
+            Self::read_compressed(
+                reader,
+                header,
+                gd,
+                linear_dim,
+                value_mask.as_bitslice(),
+                background,
+            )?
         };
 
-        let data = Self::read_compressed(reader, header, gd, linear_dim, value_mask.as_bitslice())?;
-
         Ok(NodeHeader {
             child_mask,
             value_mask,
@@ -223,104 +545,118 @@ impl<R: Read + Seek> VdbReader<R> {
         })
     }
 
+    /// Reads `dest.len()` values stored with the compression of `gd` straight into `dest`,
+    /// staging compressed bytes in a per-thread buffer reused across nodes.
     fn read_compressed_data<T: Pod>(
         reader: &mut R,
         _archive: &ArchiveHeader,
         gd: &GridDescriptor,
-        count: usize,
-    ) -> Result<Vec<T>, ParseError> {
-        Ok(if gd.compression.contains(Compression::BLOSC) {
-            let num_compressed_bytes = reader.read_i64::<LittleEndian>()?;
-            let compressed_count = num_compressed_bytes / std::mem::size_of::<T>() as i64;
-
-            trace!("Reading blosc data, {} bytes", num_compressed_bytes);
-            if num_compressed_bytes <= 0 {
-                let mut data = vec![T::zeroed(); (-compressed_count) as usize];
-                reader.read_exact(cast_slice_mut(&mut data))?;
-                assert_eq!(-compressed_count as usize, count);
-                data
-            } else {
-                let mut blosc_data = vec![0u8; num_compressed_bytes as usize];
-                reader.read_exact(&mut blosc_data)?;
-                if count > 0 {
-                    let mut nbytes: usize = 0;
-                    let mut cbytes: usize = 0;
-                    let mut blocksize: usize = 0;
-                    unsafe {
-                        blosc_cbuffer_sizes(
-                            blosc_data.as_ptr().cast(),
-                            &mut nbytes,
-                            &mut cbytes,
-                            &mut blocksize,
-                        )
-                    };
-                    if nbytes == 0 {
-                        return Err(ParseError::UnsupportedBloscFormat);
-                    }
-                    let dest_size = nbytes / std::mem::size_of::<T>();
-                    let mut dest: Vec<T> = vec![Zeroable::zeroed(); dest_size];
-                    let error = unsafe {
-                        blosc_src::blosc_decompress_ctx(
-                            blosc_data.as_ptr().cast(),
-                            dest.as_mut_ptr().cast(),
-                            nbytes,
-                            1,
-                        )
-                    };
-                    if error < 1 {
-                        return Err(ParseError::InvalidBloscData);
-                    }
-                    dest
-                } else {
-                    trace!(
-                        "Skipping blosc decompression because of a {}-count read",
-                        count
-                    );
-                    vec![T::zeroed(); 0]
-                }
+        dest: &mut [T],
+    ) -> Result<(), ParseError> {
+        let count = dest.len();
+        let dest: &mut [u8] = cast_slice_mut(dest);
+        let blosc = gd.compression.contains(Compression::BLOSC);
+        if !blosc && !gd.compression.contains(Compression::ZIP) {
+            trace!("Reading uncompressed data, {} elements", count);
+            reader.read_exact(dest)?;
+            return Ok(());
+        }
+
+        let num_compressed_bytes = reader.read_i64::<LittleEndian>()?;
+        trace!(
+            "Reading {} data, {} bytes",
+            if blosc { "blosc" } else { "zipped" },
+            num_compressed_bytes
+        );
+        if num_compressed_bytes <= 0 {
+            // Stored as is when compressing didn't pay off
+            let num_bytes = num_compressed_bytes.unsigned_abs();
+            if num_bytes != dest.len() as u64 {
+                return Err(ParseError::InvalidDataSize(num_bytes, dest.len()));
             }
-        } else if gd.compression.contains(Compression::ZIP) {
-            let num_zipped_bytes = reader.read_i64::<LittleEndian>()?;
-            let compressed_count = num_zipped_bytes / std::mem::size_of::<T>() as i64;
-
-            trace!("Reading zipped data, {} bytes", num_zipped_bytes);
-            if num_zipped_bytes <= 0 {
-                let mut data = vec![T::zeroed(); (-compressed_count) as usize];
-                reader.read_exact(cast_slice_mut(&mut data))?;
-                data
+            reader.read_exact(dest)?;
+            return Ok(());
+        }
+        COMPRESSED_SCRATCH.with(|scratch| {
+            let mut scratch = scratch.borrow_mut();
+            scratch.resize(num_compressed_bytes as usize, 0);
+            reader.read_exact(&mut scratch)?;
+            if count == 0 {
+                trace!("Skipping decompression because of a 0-count read");
+            } else if blosc {
+                decompress_blosc(&scratch, dest)?;
             } else {
-                let mut zipped_data = vec![0u8; num_zipped_bytes as usize];
-                reader.read_exact(&mut zipped_data)?;
-
-                let mut zip_reader = flate2::read::ZlibDecoder::new(zipped_data.as_slice());
-                let mut data = vec![T::zeroed(); count];
-                zip_reader.read_exact(cast_slice_mut(&mut data))?;
-                data
+                flate2::read::ZlibDecoder::new(scratch.as_slice()).read_exact(dest)?;
             }
-        } else {
-            trace!("Reading uncompressed data, {} elements", count);
-
-            let mut data = vec![T::zeroed(); count];
-            reader.read_exact(cast_slice_mut(&mut data))?;
-            data
+            Ok(())
         })
     }
 
+    /// Reads `dest.len()` values, converting them from or to half floats if the grid stores
+    /// them with a different precision than `T`.
+    fn read_values<T: Pod>(
+        reader: &mut R,
+        archive: &ArchiveHeader,
+        gd: &GridDescriptor,
+        dest: &mut [T],
+    ) -> Result<(), ParseError> {
+        // jb-todo: we may need to extend this to vector types
+        if gd.meta_data.is_half_float()
+            && std::any::TypeId::of::<T>() == std::any::TypeId::of::<f32>()
+        {
+            let mut data = vec![f16::ZERO; dest.len()];
+            Self::read_compressed_data(reader, archive, gd, &mut data)?;
+            f16_to_f32(&data, cast_slice_mut(dest));
+        } else if !gd.meta_data.is_half_float()
+            && std::any::TypeId::of::<T>() == std::any::TypeId::of::<f16>()
+        {
+            let mut data = vec![0.0f32; dest.len()];
+            Self::read_compressed_data(reader, archive, gd, &mut data)?;
+            f32_to_f16(&data, cast_slice_mut(dest));
+        } else {
+            Self::read_compressed_data(reader, archive, gd, dest)?;
+        }
+        Ok(())
+    }
+
     fn read_compressed<T: Pod>(
         reader: &mut R,
         archive: &ArchiveHeader,
         gd: &GridDescriptor,
         num_values: usize,
-        value_mask: &BitSlice<u64, Lsb0>,
+        value_mask: &BitSlice<MaskWord, Lsb0>,
+        background: T,
     ) -> Result<Vec<T>, ParseError> {
+        let mut data = vec![T::zeroed(); num_values];
+        Self::read_compressed_into(reader, archive, gd, value_mask, background, &mut data)?;
+        Ok(data)
+    }
+
+    /// Reads the values of a node into `dest`, one per slot, expanding them if only the active
+    /// ones are stored.
+    fn read_compressed_into<T: Pod>(
+        reader: &mut R,
+        archive: &ArchiveHeader,
+        gd: &GridDescriptor,
+        value_mask: &BitSlice<MaskWord, Lsb0>,
+        background: T,
+        dest: &mut [T],
+    ) -> Result<(), ParseError> {
+        let num_values = dest.len();
         let mut meta_data: NodeMetaData = NodeMetaData::NoMaskAndAllVals;
         if archive.file_version >= OPENVDB_FILE_VERSION_NODE_MASK_COMPRESSION {
             meta_data = reader.read_u8()?.try_into()?;
         }
 
-        // jb-todo: proper background value support
-        let mut inactive_val0 = T::zeroed();
-        let mut inactive_val1 = T::zeroed();
+        let mut inactive_val0 = match meta_data {
+            NodeMetaData::NoMaskAndMinusBg | NodeMetaData::MaskAndNoInactiveVals => {
+                negative(background).ok_or_else(|| {
+                    ParseError::UnsupportedNegation(std::any::type_name::<T>().to_owned())
+                })?
+            }
+            _ => background,
+        };
+        let mut inactive_val1 = background;
         if meta_data == NodeMetaData::NoMaskAndOneInactiveVal
             || meta_data == NodeMetaData::MaskAndOneInactiveVal
             || meta_data == NodeMetaData::MaskAndTwoInactiveVals
@@ -332,74 +668,166 @@ impl<R: Read + Seek> VdbReader<R> {
             }
         }
 
-        let mut selection_mask = bitvec![u64, Lsb0; 0; num_values];
+        let mut selection_mask = bitvec![MaskWord, Lsb0; 0; num_values];
 
         if meta_data == NodeMetaData::MaskAndNoInactiveVals
             || meta_data == NodeMetaData::MaskAndOneInactiveVal
             || meta_data == NodeMetaData::MaskAndTwoInactiveVals
         {
-            // let selection_mask = reader.read_u32::<LittleEndian>()?;
-            reader.read_u64_into::<LittleEndian>(selection_mask.as_raw_mut_slice())?;
+            read_mask(reader, &mut selection_mask)?;
         }
 
         let count = if gd.compression.contains(Compression::ACTIVE_MASK)
             && meta_data != NodeMetaData::NoMaskAndAllVals
             && archive.file_version >= OPENVDB_FILE_VERSION_NODE_MASK_COMPRESSION
         {
-            value_mask.count_ones()
+            count_ones(value_mask)
         } else {
             num_values
         };
+        if count == num_values {
+            return Self::read_values(reader, archive, gd, dest);
+        }
 
-        // jb-todo: we may need to extend this to vector types
-        let data = if gd.meta_data.is_half_float()
+        trace!("Expanding active mask data {} to {}", count, num_values);
+        VALUE_SCRATCH.with(|scratch| {
+            let mut scratch = scratch.borrow_mut();
+            let mut fallback = vec![];
+            let values = match typed_scratch::<T>(&mut scratch, count) {
+                Some(values) => values,
+                None => {
+                    fallback.resize(count, T::zeroed());
+                    fallback.as_mut_slice()
+                }
+            };
+            Self::read_values(reader, archive, gd, values)?;
+            expand_active(
+                values,
+                &value_mask[..num_values],
+                &selection_mask,
+                [inactive_val0, inactive_val1],
+                dest,
+            );
+            Ok(())
+        })
+    }
+
+    /// Moves past the values of a node stored like [`Self::read_compressed_into`] reads them,
+    /// without decompressing them.
+    fn skip_compressed<T: Pod>(
+        reader: &mut R,
+        archive: &ArchiveHeader,
+        gd: &GridDescriptor,
+        value_mask: &BitSlice<MaskWord, Lsb0>,
+    ) -> Result<(), ParseError> {
+        let num_values = value_mask.len();
+        let mut meta_data: NodeMetaData = NodeMetaData::NoMaskAndAllVals;
+        if archive.file_version >= OPENVDB_FILE_VERSION_NODE_MASK_COMPRESSION {
+            meta_data = reader.read_u8()?.try_into()?;
+        }
+
+        let mut skipped = match meta_data {
+            NodeMetaData::NoMaskAndOneInactiveVal | NodeMetaData::MaskAndOneInactiveVal => {
+                std::mem::size_of::<T>()
+            }
+            NodeMetaData::MaskAndTwoInactiveVals => 2 * std::mem::size_of::<T>(),
+            _ => 0,
+        };
+        if meta_data == NodeMetaData::MaskAndNoInactiveVals
+            || meta_data == NodeMetaData::MaskAndOneInactiveVal
+            || meta_data == NodeMetaData::MaskAndTwoInactiveVals
+        {
+            skipped += num_values.div_ceil(64) * 8;
+        }
+
+        let count = if gd.compression.contains(Compression::ACTIVE_MASK)
+            && meta_data != NodeMetaData::NoMaskAndAllVals
+            && archive.file_version >= OPENVDB_FILE_VERSION_NODE_MASK_COMPRESSION
+        {
+            count_ones(value_mask)
+        } else {
+            num_values
+        };
+        // Values are stored at the precision of the grid, see `read_values`
+        let value_size = if gd.meta_data.is_half_float()
             && std::any::TypeId::of::<T>() == std::any::TypeId::of::<f32>()
         {
-            let data = Self::read_compressed_data::<f16>(reader, archive, gd, count)?;
-            bytemuck::cast_vec(data.into_iter().map(f16::to_f32).collect::<Vec<f32>>())
+            std::mem::size_of::<f16>()
         } else if !gd.meta_data.is_half_float()
             && std::any::TypeId::of::<T>() == std::any::TypeId::of::<f16>()
         {
-            let data = Self::read_compressed_data::<f32>(reader, archive, gd, count)?;
-            bytemuck::cast_vec(data.into_iter().map(f16::from_f32).collect::<Vec<_>>())
+            std::mem::size_of::<f32>()
         } else {
-            Self::read_compressed_data(reader, archive, gd, count)?
+            std::mem::size_of::<T>()
         };
 
-        Ok(
-            if gd.compression.contains(Compression::ACTIVE_MASK) && data.len() != num_values {
-                trace!(
-                    "Expanding active mask data {} to {}",
-                    data.len(),
-                    num_values
-                );
+        if gd.compression.contains(Compression::BLOSC) || gd.compression.contains(Compression::ZIP)
+        {
+            reader.seek(SeekFrom::Current(skipped as i64))?;
+            // Negative when stored as is
+            let num_bytes = reader.read_i64::<LittleEndian>()?.unsigned_abs();
+            reader.seek(SeekFrom::Current(num_bytes as i64))?;
+        } else {
+            reader.seek(SeekFrom::Current((skipped + count * value_size) as i64))?;
+        }
+        Ok(())
+    }
 
-                let mut expanded = vec![T::zeroed(); num_values];
-                let mut read_idx = 0;
-                for dest_idx in 0..num_values {
-                    expanded[dest_idx] = if value_mask[dest_idx] {
-                        let v = data[read_idx];
-                        read_idx += 1;
-                        v
-                    } else if selection_mask[dest_idx] {
-                        inactive_val1
-                    } else {
-                        inactive_val0
-                    }
-                }
-                expanded
-            } else {
-                data
-            },
-        )
+    /// Reads the leaf whose data starts at the current position of `reader`, with `origin`
+    /// taken from the topology.
+    pub(crate) fn read_leaf<ValueTy: Pod, const L3: u32>(
+        reader: &mut R,
+        header: &ArchiveHeader,
+        gd: &GridDescriptor,
+        background: ValueTy,
+        mut origin: glam::IVec3,
+    ) -> Result<Node3<ValueTy, L3>, ParseError> {
+        let linear_dim = (1 << (3 * L3)) as usize;
+        let mut value_mask = bitvec![MaskWord, Lsb0; 0; linear_dim];
+        read_mask(reader, &mut value_mask)?;
+        if header.file_version < OPENVDB_FILE_VERSION_NODE_MASK_COMPRESSION {
+            origin = read_i_vec3(reader)?;
+            let num_buffers = reader.read_u8()?;
+            if num_buffers != 1 {
+                return Err(ParseError::UnsupportedBufferCount(num_buffers.into()));
+            }
+        }
+        let buffer = Self::read_compressed(
+            reader,
+            header,
+            gd,
+            linear_dim,
+            value_mask.as_bitslice(),
+            background,
+        )?;
+        Ok(Node3 {
+            buffer,
+            value_mask,
+            origin,
+        })
+    }
+
+    /// Moves past the leaf whose data starts at the current position of `reader`.
+    fn skip_leaf<ValueTy: Pod>(
+        reader: &mut R,
+        header: &ArchiveHeader,
+        gd: &GridDescriptor,
+        linear_dim: usize,
+    ) -> Result<(), ParseError> {
+        let mut value_mask = bitvec![MaskWord, Lsb0; 0; linear_dim];
+        read_mask(reader, &mut value_mask)?;
+        if header.file_version < OPENVDB_FILE_VERSION_NODE_MASK_COMPRESSION {
+            reader.seek(SeekFrom::Current(3 * 4 + 1))?;
+        }
+        Self::skip_compressed::<ValueTy>(reader, header, gd, value_mask.as_bitslice())
     }
 
-    fn read_metadata(reader: &mut R) -> Result<Metadata, ParseError> {
+    pub(crate) fn read_metadata(reader: &mut R) -> Result<Metadata, ParseError> {
         let meta_data_count = reader.read_u32::<LittleEndian>()?;
         let mut meta_data = Metadata::default();
 
         for _ in 0..meta_data_count {
-            let name = Self::read_name(reader)?;
+            let name = Self::read_interned_name(reader)?;
             let data_type = Self::read_name(reader)?;
 
             let len = reader.read_u32::<LittleEndian>()?;
@@ -448,15 +876,18 @@ impl<R: Read + Seek> VdbReader<R> {
         Ok(meta_data)
     }
 
-    fn read_tree_topology<ValueTy: Pod>(
+    pub(crate) fn read_tree_topology<ValueTy: Pod, const L5: u32, const L4: u32, const L3: u32>(
         header: &ArchiveHeader,
         gd: &GridDescriptor,
         reader: &mut R,
-    ) -> Result<Tree<ValueTy>, ParseError> {
+    ) -> Result<Tree<ValueTy, L5, L4, L3>, ParseError> {
         let buffer_count = reader.read_u32::<LittleEndian>()?;
-        assert_eq!(buffer_count, 1, "Multi-buffer trees are not supported");
+        if buffer_count != 1 {
+            return Err(ParseError::UnsupportedBufferCount(buffer_count));
+        }
 
-        let _root_node_background_value = reader.read_u32::<LittleEndian>()?;
+        let mut background = ValueTy::zeroed();
+        reader.read_exact(bytes_of_mut(&mut background))?;
         let number_of_tiles = reader.read_u32::<LittleEndian>()?;
         let number_of_root_nodes = reader.read_u32::<LittleEndian>()?;
 
@@ -464,15 +895,15 @@ impl<R: Read + Seek> VdbReader<R> {
 
         for _tile_idx in 0..number_of_tiles {
             let _vec = read_i_vec3(reader)?;
-            let _value = reader.read_u32::<LittleEndian>()?;
+            let mut _value = ValueTy::zeroed();
+            reader.read_exact(bytes_of_mut(&mut _value))?;
             let _active = reader.read_u8()?;
         }
 
         for _root_idx in 0..number_of_root_nodes {
             let origin = read_i_vec3(reader)?;
 
-            let node_5 =
-                Self::read_node_header::<ValueTy>(reader, 5 /* 32 * 32 * 32 */, header, gd)?;
+            let node_5 = Self::read_node_header::<ValueTy>(reader, L5, header, gd, background)?;
             let mut child_5 = HashMap::default();
 
             let mut root = Node5 {
@@ -484,10 +915,7 @@ impl<R: Read + Seek> VdbReader<R> {
             };
 
             for idx in node_5.child_mask.iter_ones() {
-                let node_4 = Self::read_node_header::<ValueTy>(
-                    reader, 4, /* 16 * 16 * 16 */
-                    header, gd,
-                )?;
+                let node_4 = Self::read_node_header::<ValueTy>(reader, L4, header, gd, background)?;
                 let mut child_4 = HashMap::default();
 
                 let mut cur_node_4 = Node4 {
@@ -499,18 +927,18 @@ impl<R: Read + Seek> VdbReader<R> {
                 };
 
                 for idx in node_4.child_mask.iter_ones() {
-                    let linear_dim = (1 << (3 * 3)) as usize;
+                    let linear_dim = (1 << (3 * L3)) as usize;
 
-                    let mut value_mask = bitvec![u64, Lsb0; 0; linear_dim];
-                    reader.read_u64_into::<LittleEndian>(value_mask.as_raw_mut_slice())?;
+                    let mut value_mask = bitvec![MaskWord, Lsb0; 0; linear_dim];
+                    read_mask(reader, &mut value_mask)?;
 
                     child_4.insert(
                         idx as u32,
-                        Node3 {
+                        Arc::new(Node3 {
                             buffer: vec![],
                             value_mask,
                             origin: cur_node_4.offset_to_global_coord(Index(idx as u32)).0,
-                        },
+                        }),
                     );
                 }
 
@@ -523,14 +951,17 @@ impl<R: Read + Seek> VdbReader<R> {
             root_nodes.push(root);
         }
 
-        Ok(Tree { root_nodes })
+        Ok(Tree {
+            root_nodes,
+            background,
+        })
     }
 
-    fn read_tree_data<ValueTy: Pod>(
+    fn read_tree_data<ValueTy: Pod, const L5: u32, const L4: u32, const L3: u32>(
         header: &ArchiveHeader,
         gd: &GridDescriptor,
         reader: &mut R,
-        tree: &mut Tree<ValueTy>,
+        tree: &mut Tree<ValueTy, L5, L4, L3>,
     ) -> Result<(), ParseError> {
         gd.seek_to_blocks(reader)?;
 
@@ -540,16 +971,18 @@ impl<R: Read + Seek> VdbReader<R> {
                 let node_4 = node_5.nodes.get_mut(&(idx as u32)).unwrap();
 
                 for idx in node_4.child_mask.iter_ones() {
-                    let node_3 = node_4.nodes.get_mut(&(idx as u32)).unwrap();
+                    let node_3 = Arc::make_mut(node_4.nodes.get_mut(&(idx as u32)).unwrap());
 
-                    let linear_dim = (1 << (3 * 3)) as usize;
-                    let mut value_mask = bitvec![u64, Lsb0; 0; linear_dim];
-                    reader.read_u64_into::<LittleEndian>(value_mask.as_raw_mut_slice())?;
+                    let linear_dim = (1 << (3 * L3)) as usize;
+                    let mut value_mask = bitvec![MaskWord, Lsb0; 0; linear_dim];
+                    read_mask(reader, &mut value_mask)?;
 
                     if header.file_version < OPENVDB_FILE_VERSION_NODE_MASK_COMPRESSION {
                         node_3.origin = read_i_vec3(reader)?;
                         let num_buffers = reader.read_u8()?;
-                        assert_eq!(num_buffers, 1);
+                        if num_buffers != 1 {
+                            return Err(ParseError::UnsupportedBufferCount(num_buffers.into()));
+                        }
                     }
 
                     let data = Self::read_compressed(
@@ -558,6 +991,7 @@ impl<R: Read + Seek> VdbReader<R> {
                         gd,
                         linear_dim,
                         value_mask.as_bitslice(),
+                        tree.background,
                     )?;
 
                     node_3.buffer = data;
@@ -568,82 +1002,198 @@ impl<R: Read + Seek> VdbReader<R> {
         Ok(())
     }
 
-    fn read_grid_internal<ValueTy: Pod>(
+    fn read_grid_internal<ValueTy: Pod, const L5: u32, const L4: u32, const L3: u32>(
         header: &ArchiveHeader,
         reader: &mut R,
         gd: GridDescriptor,
-    ) -> Result<Grid<ValueTy>, ParseError> {
-        gd.seek_to_grid(reader).unwrap();
+    ) -> Result<Grid<ValueTy, L5, L4, L3>, ParseError> {
+        let (transform, mut tree) = Self::read_grid_head(header, reader, &gd)?;
+        Self::read_tree_data(header, &gd, reader, &mut tree)?;
+
+        Ok(Grid {
+            tree,
+            transform,
+            descriptor: gd,
+        })
+    }
+
+    /// Seeks to the grid of `gd` and reads everything stored before its tree: the grid header,
+    /// its metadata and its transform.
+    pub(crate) fn read_grid_transform_internal(
+        header: &ArchiveHeader,
+        reader: &mut R,
+        gd: &GridDescriptor,
+    ) -> Result<Transform, ParseError> {
+        gd.seek_to_grid(reader)?;
         // Having to re-do this is ugly, as we already did this while parsing the descriptor
         if header.file_version >= OPENVDB_FILE_VERSION_NODE_MASK_COMPRESSION {
-            let _: Compression = reader.read_u32::<LittleEndian>()?.try_into().unwrap();
+            let _: Compression = reader.read_u32::<LittleEndian>()?.try_into()?;
+        }
+        let _ = Self::read_metadata(reader)?;
+        if header.file_version < OPENVDB_FILE_VERSION_GRID_INSTANCING {
+            return Err(ParseError::UnsupportedVersion(header.file_version));
         }
-        let _ = Self::read_metadata(reader).unwrap();
+        Ok(Transform::new(Self::read_transform(reader)?))
+    }
 
-        if header.file_version >= OPENVDB_FILE_VERSION_GRID_INSTANCING {
-            let transform = Self::read_transform(reader)?;
-            let mut tree = Self::read_tree_topology(header, &gd, reader)?;
-            Self::read_tree_data(header, &gd, reader, &mut tree)?;
+    /// Seeks to the grid of `gd` and reads everything stored before its leaf values, its
+    /// transform and the topology of its tree. The leaves of the returned tree have empty
+    /// buffers until their values are read.
+    pub(crate) fn read_grid_head<ValueTy: Pod, const L5: u32, const L4: u32, const L3: u32>(
+        header: &ArchiveHeader,
+        reader: &mut R,
+        gd: &GridDescriptor,
+    ) -> Result<(Transform, Tree<ValueTy, L5, L4, L3>), ParseError> {
+        let transform = Self::read_grid_transform_internal(header, reader, gd)?;
+        let tree = Self::read_tree_topology(header, gd, reader)?;
+        Ok((transform, tree))
+    }
 
-            Ok(Grid {
-                tree,
-                transform,
-                descriptor: gd,
-            })
-        } else {
-            todo!("Old file version not supported {}", header.file_version);
+    fn read_arena_tree_internal<ValueTy: Pod, const L5: u32, const L4: u32, const L3: u32>(
+        header: &ArchiveHeader,
+        reader: &mut R,
+        gd: GridDescriptor,
+    ) -> Result<ArenaTree<ValueTy, L5, L4, L3>, ParseError> {
+        let _ = Self::read_grid_transform_internal(header, reader, &gd)?;
+
+        // Topology, in the same order as `read_tree_topology`, so nodes land in their slabs in
+        // the order they are stored
+        let buffer_count = reader.read_u32::<LittleEndian>()?;
+        if buffer_count != 1 {
+            return Err(ParseError::UnsupportedBufferCount(buffer_count));
+        }
+
+        let mut background = ValueTy::zeroed();
+        reader.read_exact(bytes_of_mut(&mut background))?;
+        let number_of_tiles = reader.read_u32::<LittleEndian>()?;
+        let number_of_root_nodes = reader.read_u32::<LittleEndian>()?;
+
+        for _tile_idx in 0..number_of_tiles {
+            let _vec = read_i_vec3(reader)?;
+            let mut _value = ValueTy::zeroed();
+            reader.read_exact(bytes_of_mut(&mut _value))?;
+            let _active = reader.read_u8()?;
         }
+
+        let mut tree = ArenaTree::new(background);
+        let mut leaf_mask = vec![0; (1usize << (3 * L3)).div_ceil(64)];
+        for _root_idx in 0..number_of_root_nodes {
+            let origin = read_i_vec3(reader)?;
+            let node_5 = Self::read_node_header::<ValueTy>(reader, L5, header, &gd, background)?;
+            tree.push_upper(
+                origin,
+                mask_words(&node_5.child_mask),
+                mask_words(&node_5.value_mask),
+                node_5.data,
+            );
+
+            for idx in node_5.child_mask.iter_ones() {
+                let node_4 =
+                    Self::read_node_header::<ValueTy>(reader, L4, header, &gd, background)?;
+                let origin_4 = slot_origin(origin, idx, L5, L4 + L3);
+                tree.push_lower(
+                    origin_4,
+                    mask_words(&node_4.child_mask),
+                    mask_words(&node_4.value_mask),
+                    node_4.data,
+                );
+
+                for idx in node_4.child_mask.iter_ones() {
+                    reader.read_u64_into::<LittleEndian>(&mut leaf_mask)?;
+                    tree.push_leaf(slot_origin(origin_4, idx, L4, L3), &leaf_mask);
+                }
+            }
+        }
+
+        // Leaf values follow in the same order
+        tree.allocate_leaf_values(background);
+        gd.seek_to_blocks(reader)?;
+        let linear_dim = (1 << (3 * L3)) as usize;
+        let mut value_mask = bitvec![MaskWord, Lsb0; 0; linear_dim];
+        for leaf_idx in 0..tree.leaf_count() {
+            read_mask(reader, &mut value_mask)?;
+            let (origin, values) = tree.leaf_mut(leaf_idx);
+            if header.file_version < OPENVDB_FILE_VERSION_NODE_MASK_COMPRESSION {
+                *origin = read_i_vec3(reader)?;
+                let num_buffers = reader.read_u8()?;
+                if num_buffers != 1 {
+                    return Err(ParseError::UnsupportedBufferCount(num_buffers.into()));
+                }
+            }
+            Self::read_compressed_into(
+                reader,
+                header,
+                &gd,
+                value_mask.as_bitslice(),
+                background,
+                values,
+            )?;
+        }
+
+        Ok(tree)
     }
 
     fn read_grid_descriptors(
         header: &ArchiveHeader,
         reader: &mut R,
-    ) -> Result<HashMap<String, GridDescriptor>, ParseError> {
+    ) -> Result<(HashMap<InternedStr, GridDescriptor>, Vec<InternedStr>), ParseError> {
         // Should be guaranteed by minimum file version
         assert!(header.has_grid_offsets);
 
         let mut result = HashMap::new();
+        let mut order = vec![];
         for _ in 0..header.grid_count {
-            let name = Self::read_name(reader)?;
-            let grid_type = Self::read_name(reader)?;
-
-            let instance_parent = if header.file_version >= OPENVDB_FILE_VERSION_GRID_INSTANCING {
-                Self::read_name(reader)?
-            } else {
-                todo!("instance_parent, file version: {}", header.file_version)
-            };
-
-            let grid_pos = reader.read_u64::<LittleEndian>()?;
-            let block_pos = reader.read_u64::<LittleEndian>()?;
-            let end_pos = reader.read_u64::<LittleEndian>()?;
-
-            let mut gd = GridDescriptor {
-                name: name.clone(),
-                file_version: header.file_version,
-                grid_type,
-                instance_parent,
-                grid_pos,
-                block_pos,
-                end_pos,
-                compression: header.compression,
-                meta_data: Default::default(),
-            };
-
-            gd.seek_to_grid(reader)?;
-            if header.file_version >= OPENVDB_FILE_VERSION_NODE_MASK_COMPRESSION {
-                gd.compression = reader.read_u32::<LittleEndian>()?.try_into()?;
-            }
-            gd.meta_data = Self::read_metadata(reader)?;
-
+            let gd = Self::read_grid_descriptor(header, reader)?;
+            let name = gd.name.clone();
             assert!(
                 result.insert(name.clone(), gd).is_none(),
                 "Grid named {name} already exists"
             );
+            order.push(name);
+        }
+
+        Ok((result, order))
+    }
+
+    /// Reads the grid descriptor at the current position along with the compression and
+    /// metadata of its grid, leaving `reader` at the next descriptor.
+    pub(crate) fn read_grid_descriptor(
+        header: &ArchiveHeader,
+        reader: &mut R,
+    ) -> Result<GridDescriptor, ParseError> {
+        let name = Self::read_interned_name(reader)?;
+        let grid_type = Self::read_interned_name(reader)?;
 
-            reader.seek(SeekFrom::Start(end_pos))?;
+        let instance_parent = if header.file_version >= OPENVDB_FILE_VERSION_GRID_INSTANCING {
+            Self::read_interned_name(reader)?
+        } else {
+            return Err(ParseError::UnsupportedVersion(header.file_version));
+        };
+
+        let grid_pos = reader.read_u64::<LittleEndian>()?;
+        let block_pos = reader.read_u64::<LittleEndian>()?;
+        let end_pos = reader.read_u64::<LittleEndian>()?;
+
+        let mut gd = GridDescriptor {
+            name,
+            file_version: header.file_version,
+            grid_type,
+            instance_parent,
+            grid_pos,
+            block_pos,
+            end_pos,
+            compression: header.compression,
+            meta_data: Default::default(),
+        };
+
+        gd.seek_to_grid(reader)?;
+        if header.file_version >= OPENVDB_FILE_VERSION_NODE_MASK_COMPRESSION {
+            gd.compression = reader.read_u32::<LittleEndian>()?.try_into()?;
         }
+        gd.meta_data = Self::read_metadata(reader)?;
 
-        Ok(result)
+        reader.seek(SeekFrom::Start(end_pos))?;
+        Ok(gd)
     }
 }
 
@@ -671,3 +1221,18 @@ impl TryFrom<u32> for Compression {
         Self::from_bits(v).ok_or(ParseError::InvalidCompression(v))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negative_negates_signed_components() {
+        assert_eq!(negative(0.5f32), Some(-0.5));
+        assert_eq!(negative(f16::ONE), Some(-f16::ONE));
+        assert_eq!(negative(3i64), Some(-3));
+        assert_eq!(negative([1i32, -2, 3]), Some([-1, 2, -3]));
+        assert_eq!(negative([0.5f64, -1.0, 0.0]), Some([-0.5, 1.0, -0.0]));
+        assert_eq!(negative(7u8), None);
+    }
+}
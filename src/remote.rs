@@ -0,0 +1,216 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::{Error, ErrorKind, Read, Result, Seek, SeekFrom};
+
+/// Random access to the bytes of a file, such as an object in S3 or GCS read with HTTP range
+/// requests, see [`RemoteVdbReader`].
+///
+/// Implementing it for an HTTP client takes a `HEAD` request for [`ReadAt::size`], reading the
+/// `Content-Length`, and a `GET` with a `Range: bytes={offset}-{offset + buf.len() - 1}` header
+/// for [`ReadAt::read_at`].
+pub trait ReadAt {
+    /// Total size of the file in bytes.
+    fn size(&self) -> Result<u64>;
+
+    /// Reads the bytes starting at `offset` into `buf`, returning how many were read. Fewer
+    /// than `buf.len()` bytes are only read at the end of the file.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize>;
+}
+
+impl ReadAt for [u8] {
+    fn size(&self) -> Result<u64> {
+        Ok(self.len() as u64)
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        let start = usize::try_from(offset).map_or(self.len(), |offset| offset.min(self.len()));
+        let len = buf.len().min(self.len() - start);
+        buf[..len].copy_from_slice(&self[start..start + len]);
+        Ok(len)
+    }
+}
+
+impl ReadAt for Vec<u8> {
+    fn size(&self) -> Result<u64> {
+        self.as_slice().size()
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        self.as_slice().read_at(offset, buf)
+    }
+}
+
+impl<S: ReadAt + ?Sized> ReadAt for &S {
+    fn size(&self) -> Result<u64> {
+        (**self).size()
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        (**self).read_at(offset, buf)
+    }
+}
+
+/// Adapts a [`ReadAt`] source, such as a file behind HTTP range requests, to the [`Read`] and
+/// [`Seek`] traits [`VdbReader`] reads from, so grids can be listed and read without downloading
+/// the whole file.
+///
+/// Small reads are served from blocks of [`RemoteVdbReader::with_block_size`] bytes, fetched
+/// once and kept in a least recently used cache of [`RemoteVdbReader::with_cache_blocks`]
+/// blocks, so parsing headers and node masks takes few requests. Reads of at least a block,
+/// like compressed voxel data, are fetched directly with a single request. Opening a file with
+/// [`VdbReader::new`] only touches its header and the metadata of every grid, and
+/// [`VdbReader::read_grid`] only the byte range of the requested grid.
+///
+/// [`VdbReader`]: crate::VdbReader
+/// [`VdbReader::new`]: crate::VdbReader::new
+/// [`VdbReader::read_grid`]: crate::VdbReader::read_grid
+pub struct RemoteVdbReader<S> {
+    source: S,
+    size: u64,
+    position: u64,
+    block_size: usize,
+    cache_blocks: usize,
+    blocks: HashMap<u64, Vec<u8>>,
+    /// Cached block indices, least recently used first
+    recent: VecDeque<u64>,
+    request_count: usize,
+    fetched_bytes: u64,
+}
+
+impl<S: ReadAt> RemoteVdbReader<S> {
+    /// Wraps `source`, querying its size, with blocks of 64 KiB and a cache of 64 blocks.
+    pub fn new(source: S) -> Result<Self> {
+        let size = source.size()?;
+        Ok(Self {
+            source,
+            size,
+            position: 0,
+            block_size: 64 * 1024,
+            cache_blocks: 64,
+            blocks: HashMap::new(),
+            recent: VecDeque::new(),
+            request_count: 0,
+            fetched_bytes: 0,
+        })
+    }
+
+    /// Sets the number of bytes fetched at once for small reads, at least 1. Larger blocks take
+    /// fewer requests for the same data, at the cost of fetching bytes that may not be needed.
+    pub fn with_block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size.max(1);
+        self.blocks.clear();
+        self.recent.clear();
+        self
+    }
+
+    /// Sets the number of blocks kept in memory, at least 1.
+    pub fn with_cache_blocks(mut self, cache_blocks: usize) -> Self {
+        self.cache_blocks = cache_blocks.max(1);
+        self
+    }
+
+    /// Total size of the source in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Number of [`ReadAt::read_at`] calls made so far, e.g. HTTP requests.
+    pub fn request_count(&self) -> usize {
+        self.request_count
+    }
+
+    /// Number of bytes fetched from the source so far.
+    pub fn fetched_bytes(&self) -> u64 {
+        self.fetched_bytes
+    }
+
+    /// The wrapped source.
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+
+    /// Reads `buf.len()` bytes at `offset` from the source, fewer only at its end.
+    fn fetch(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            // Sources claiming to have read more than asked for are clamped to the buffer
+            let read = self
+                .source
+                .read_at(offset + filled as u64, &mut buf[filled..])?
+                .min(buf.len() - filled);
+            self.request_count += 1;
+            self.fetched_bytes += read as u64;
+            if read == 0 {
+                break;
+            }
+            filled += read;
+        }
+        Ok(filled)
+    }
+
+    /// The cached block `index`, fetched from the source if needed.
+    fn block(&mut self, index: u64) -> Result<&[u8]> {
+        if self.blocks.contains_key(&index) {
+            self.recent.retain(|&recent| recent != index);
+        } else {
+            let start = index * self.block_size as u64;
+            let len = (self.size - start).min(self.block_size as u64) as usize;
+            let mut block = vec![0; len];
+            let read = self.fetch(start, &mut block)?;
+            block.truncate(read);
+            if self.blocks.len() >= self.cache_blocks {
+                if let Some(oldest) = self.recent.pop_front() {
+                    self.blocks.remove(&oldest);
+                }
+            }
+            self.blocks.insert(index, block);
+        }
+        self.recent.push_back(index);
+        Ok(&self.blocks[&index])
+    }
+}
+
+impl<S: ReadAt> Read for RemoteVdbReader<S> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if self.position >= self.size || buf.is_empty() {
+            return Ok(0);
+        }
+        let len = (self.size - self.position).min(buf.len() as u64) as usize;
+        let buf = &mut buf[..len];
+        let index = self.position / self.block_size as u64;
+
+        let read = if len >= self.block_size && !self.blocks.contains_key(&index) {
+            let position = self.position;
+            self.fetch(position, buf)?
+        } else {
+            let start = (self.position - index * self.block_size as u64) as usize;
+            let block = self.block(index)?;
+            // Blocks end early when the source turns out shorter than its reported size, which
+            // reads as the end of the file
+            let read = block.len().saturating_sub(start).min(len);
+            if read == 0 {
+                return Ok(0);
+            }
+            buf[..read].copy_from_slice(&block[start..start + read]);
+            read
+        };
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl<S: ReadAt> Seek for RemoteVdbReader<S> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.size.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        self.position = position.ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.position)
+    }
+}
@@ -0,0 +1,143 @@
+use crate::data_structure::Grid;
+use crate::hdda::HddaHit;
+use crate::ray::Ray;
+use crate::sampling::{BoxSampler, GridSampler};
+
+use glam::{DVec3, Vec3, Vec4};
+
+/// Transmittance below which marching stops, as nothing behind can visibly contribute.
+const MIN_TRANSMITTANCE: f32 = 1e-3;
+
+/// Pinhole camera for [`render`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    /// World space position of the camera
+    pub eye: DVec3,
+    /// World space position the camera looks at
+    pub target: DVec3,
+    pub up: DVec3,
+    /// Vertical field of view in degrees
+    pub fov_y: f64,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Camera {
+    /// World space ray through the pixel at column `x` and row `y`, counted from the top left
+    /// corner, with fractional values addressing positions within a pixel. The direction is
+    /// normalized, so ray parameters are world space distances.
+    pub fn ray(&self, x: f64, y: f64) -> Ray {
+        let forward = (self.target - self.eye).normalize();
+        let right = forward.cross(self.up).normalize();
+        let up = right.cross(forward);
+        let half_height = (self.fov_y.to_radians() * 0.5).tan();
+        let half_width = half_height * self.width as f64 / self.height as f64;
+        let u = (x / self.width as f64 * 2.0 - 1.0) * half_width;
+        let v = (1.0 - y / self.height as f64 * 2.0) * half_height;
+        Ray::new(self.eye, (forward + right * u + up * v).normalize())
+    }
+}
+
+/// RGBA image produced by [`render`], stored row by row from the top left corner with colors
+/// premultiplied by alpha.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<Vec4>,
+}
+
+impl Image {
+    /// Pixels as 8-bit RGBA with straight alpha, the layout most image encoders expect.
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|pixel| {
+                let color = if pixel.w > 0.0 {
+                    pixel.truncate() / pixel.w
+                } else {
+                    Vec3::ZERO
+                };
+                color
+                    .extend(pixel.w)
+                    .to_array()
+                    .map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8)
+            })
+            .collect()
+    }
+}
+
+/// Marches `ray` front to back through `grid` and returns its premultiplied color, see
+/// [`render`].
+fn render_pixel<const L5: u32, const L4: u32, const L3: u32>(
+    grid: &Grid<f32, L5, L4, L3>,
+    sampler: &mut GridSampler<'_, BoxSampler, f32, L5, L4, L3>,
+    ray: &Ray,
+    step: f64,
+    transfer: &impl Fn(f32) -> Vec4,
+) -> Vec4 {
+    let mut color = Vec3::ZERO;
+    let mut transmittance = 1.0;
+    for hit in grid.hdda(ray).stop_at_leaves() {
+        let (t0, t1) = match hit {
+            HddaHit::Tile { t0, t1, .. }
+            | HddaHit::Leaf { t0, t1, .. }
+            | HddaHit::Voxel { t0, t1, .. } => (t0, t1),
+        };
+        // Samples lie on a fixed lattice along the ray, so neighboring spans don't band
+        let mut t = (t0 / step).ceil() * step;
+        while t < t1 {
+            let sample = transfer(sampler.sample(ray.at(t)));
+            let alpha = 1.0 - (-sample.w * step as f32).exp();
+            color += sample.truncate() * alpha * transmittance;
+            transmittance *= 1.0 - alpha;
+            if transmittance < MIN_TRANSMITTANCE {
+                return color.extend(1.0 - transmittance);
+            }
+            t += step;
+        }
+    }
+    color.extend(1.0 - transmittance)
+}
+
+/// Renders the density grid `grid` as seen by `camera`, for quickly inspecting what a grid
+/// contains.
+///
+/// Rays are marched front to back with steps of `step_size` voxels through the active leaves
+/// and tiles of the grid, skipping empty space. `transfer` maps sampled densities to an RGB
+/// color and an extinction coefficient per world unit in the alpha channel. Rows are rendered
+/// in parallel when the `rayon` feature is enabled.
+pub fn render<const L5: u32, const L4: u32, const L3: u32>(
+    grid: &Grid<f32, L5, L4, L3>,
+    camera: &Camera,
+    step_size: f32,
+    transfer: impl Fn(f32) -> Vec4 + Sync,
+) -> Image {
+    let step = step_size as f64 * grid.transform.voxel_size().min_element();
+    let render_row = |(y, row): (usize, &mut [Vec4])| {
+        let mut sampler = GridSampler::new(grid);
+        for (x, pixel) in row.iter_mut().enumerate() {
+            let ray = camera.ray(x as f64 + 0.5, y as f64 + 0.5);
+            *pixel = render_pixel(grid, &mut sampler, &ray, step, &transfer);
+        }
+    };
+
+    let mut pixels = vec![Vec4::ZERO; camera.width as usize * camera.height as usize];
+    let width = camera.width.max(1) as usize;
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        pixels
+            .par_chunks_mut(width)
+            .enumerate()
+            .for_each(render_row);
+    }
+    #[cfg(not(feature = "rayon"))]
+    pixels.chunks_mut(width).enumerate().for_each(render_row);
+
+    Image {
+        width: camera.width,
+        height: camera.height,
+        pixels,
+    }
+}
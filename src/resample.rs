@@ -0,0 +1,105 @@
+use crate::coordinates::CoordBBox;
+use crate::data_structure::{Grid, Tree};
+use crate::sampling::{BoxSampler, PointSampler, QuadraticSampler, Sampler, ValueAccessor};
+use crate::transform::Transform;
+
+use glam::{DVec3, IVec3};
+use std::ops::{Add, Mul};
+
+/// How values between voxel centers are reconstructed when resampling.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Value of the nearest voxel.
+    Point,
+    /// Trilinear blend of the 8 surrounding voxels.
+    #[default]
+    Trilinear,
+    /// Triquadratic blend of the 27 surrounding voxels.
+    Triquadratic,
+}
+
+impl Interpolation {
+    /// Samples `tree` at the fractional index space position `index`, returning the value and
+    /// whether any of the voxels that contributed to it is active.
+    pub fn sample<ValueTy, const L5: u32, const L4: u32, const L3: u32>(
+        self,
+        tree: &Tree<ValueTy, L5, L4, L3>,
+        index: DVec3,
+    ) -> (ValueTy, bool)
+    where
+        ValueTy: Copy + Add<Output = ValueTy> + Mul<f32, Output = ValueTy>,
+    {
+        self.probe(&mut tree.accessor(), index)
+    }
+
+    pub(crate) fn probe<ValueTy, const L5: u32, const L4: u32, const L3: u32>(
+        self,
+        accessor: &mut ValueAccessor<'_, ValueTy, L5, L4, L3>,
+        index: DVec3,
+    ) -> (ValueTy, bool)
+    where
+        ValueTy: Copy + Add<Output = ValueTy> + Mul<f32, Output = ValueTy>,
+    {
+        match self {
+            Interpolation::Point => PointSampler::probe_index(accessor, index),
+            Interpolation::Trilinear => BoxSampler::probe_index(accessor, index),
+            Interpolation::Triquadratic => QuadraticSampler::probe_index(accessor, index),
+        }
+    }
+}
+
+/// Resamples `source` into the index space of `target`, sampling it at the world space position
+/// of every voxel of the new grid that lies within the active region of `source`.
+///
+/// A voxel of the result is active if any voxel of `source` that contributed to it is active.
+pub fn resample_to_match<ValueTy, const L5: u32, const L4: u32, const L3: u32>(
+    source: &Grid<ValueTy, L5, L4, L3>,
+    target: &Transform,
+    interpolation: Interpolation,
+) -> Grid<ValueTy, L5, L4, L3>
+where
+    ValueTy: Copy + Add<Output = ValueTy> + Mul<f32, Output = ValueTy>,
+{
+    let mut tree = Tree::new(source.tree.background);
+
+    let source_bbox = source.eval_active_voxel_bounding_box();
+    if !source_bbox.is_empty() {
+        // Bounding box of the source's active region in the index space of the target
+        let mut bbox = CoordBBox::empty();
+        for corner in 0..8 {
+            let select = IVec3::new(corner >> 2 & 1, corner >> 1 & 1, corner & 1);
+            let index = source_bbox.min + select * (source_bbox.max - source_bbox.min);
+            let world = source.transform.index_to_world_f64(index.as_dvec3());
+            let target_index = target.world_to_index_f64(world);
+            bbox.expand_coord(target_index.floor().as_ivec3());
+            bbox.expand_coord(target_index.ceil().as_ivec3());
+        }
+        bbox.expand(1);
+
+        let mut accessor = source.tree.accessor();
+        for coord in bbox {
+            let world = target.index_to_world_f64(coord.as_dvec3());
+            let index = source.transform.world_to_index_f64(world);
+            let (value, active) = interpolation.probe(&mut accessor, index);
+            if active {
+                tree.set_value_on(coord, value);
+            }
+        }
+    }
+
+    Grid {
+        tree,
+        transform: target.clone(),
+        descriptor: source.descriptor.clone(),
+    }
+}
+
+impl<ValueTy, const L5: u32, const L4: u32, const L3: u32> Grid<ValueTy, L5, L4, L3>
+where
+    ValueTy: Copy + Add<Output = ValueTy> + Mul<f32, Output = ValueTy>,
+{
+    /// Resamples this grid into the index space of `target`, see [`resample_to_match`].
+    pub fn resample_to_match(&self, target: &Transform, interpolation: Interpolation) -> Self {
+        resample_to_match(self, target, interpolation)
+    }
+}
@@ -0,0 +1,219 @@
+use crate::data_structure::Grid;
+use crate::reader::{ParseError, VdbReader};
+
+use bytemuck::Pod;
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::Arc;
+
+/// What [`VdbReader::salvage`] found of the grids of an archive.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArchiveSalvage {
+    pub file_len: u64,
+    /// Number of grids announced by the header
+    pub grid_count: u32,
+    /// Grids whose descriptor was read but whose data extends past the end of the file
+    pub truncated_grids: Vec<String>,
+    /// Number of grids whose descriptor couldn't be read, their names are unknown
+    pub lost_grids: u32,
+    /// Error that stopped reading the descriptors, if any
+    pub error: Option<String>,
+}
+
+impl ArchiveSalvage {
+    /// Whether every grid is stored in full.
+    pub fn is_complete(&self) -> bool {
+        self.lost_grids == 0 && self.truncated_grids.is_empty()
+    }
+}
+
+/// What [`VdbReader::read_grid_salvaged`] recovered of a grid.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GridSalvage {
+    pub name: String,
+    pub leaves_recovered: usize,
+    /// Origins of the leaves whose values couldn't be read, in the order they are stored
+    pub lost_leaves: Vec<glam::IVec3>,
+    /// Number of active voxels in the lost leaves, known from the topology
+    pub lost_active_voxels: u64,
+    /// Error that stopped reading the leaves, if any
+    pub error: Option<String>,
+}
+
+impl GridSalvage {
+    /// Whether all leaves were read.
+    pub fn is_complete(&self) -> bool {
+        self.lost_leaves.is_empty()
+    }
+}
+
+impl<R: Read + Seek> VdbReader<R> {
+    /// Opens an archive that may be truncated or damaged, like the output of a crashed
+    /// simulation, keeping the grid descriptors read before the first failure instead of
+    /// erroring out. Read the grids with [`VdbReader::read_grid_salvaged`].
+    ///
+    /// Only fails if the archive header can't be read.
+    pub fn salvage(mut reader: R) -> Result<(Self, ArchiveSalvage), ParseError> {
+        let header = Self::read_header(&mut reader)?;
+        let descriptors_pos = reader.stream_position()?;
+        let file_len = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(descriptors_pos))?;
+
+        let mut salvage = ArchiveSalvage {
+            file_len,
+            grid_count: header.grid_count,
+            truncated_grids: vec![],
+            lost_grids: header.grid_count,
+            error: None,
+        };
+        let mut grid_descriptors = HashMap::new();
+        let mut grid_order = vec![];
+        for _ in 0..header.grid_count {
+            match Self::read_grid_descriptor(&header, &mut reader) {
+                Ok(gd) => {
+                    if gd.end_pos > file_len {
+                        salvage.truncated_grids.push(gd.name.to_string());
+                    }
+                    salvage.lost_grids -= 1;
+                    grid_order.push(gd.name.clone());
+                    grid_descriptors.insert(gd.name.clone(), gd);
+                }
+                Err(err) => {
+                    salvage.error = Some(err.to_string());
+                    break;
+                }
+            }
+        }
+
+        let reader = Self {
+            reader,
+            header,
+            grid_descriptors,
+            grid_order,
+        };
+        Ok((reader, salvage))
+    }
+
+    /// Reads as much of the grid `name` as possible, see [`VdbReader::salvage`]. Leaves whose
+    /// values can't be read are left out of the tree, so their voxels read as background.
+    ///
+    /// Fails if the transform or topology of the grid can't be read, without them none of its
+    /// values can be placed.
+    pub fn read_grid_salvaged<ExpectedTy: Pod>(
+        &mut self,
+        name: &str,
+    ) -> Result<(Grid<ExpectedTy>, GridSalvage), ParseError> {
+        self.read_grid_salvaged_with_config(name)
+    }
+
+    /// [`VdbReader::read_grid_salvaged`] for grids with a non-standard node configuration, see
+    /// [`VdbReader::read_grid_with_config`].
+    pub fn read_grid_salvaged_with_config<
+        ExpectedTy: Pod,
+        const L5: u32,
+        const L4: u32,
+        const L3: u32,
+    >(
+        &mut self,
+        name: &str,
+    ) -> Result<(Grid<ExpectedTy, L5, L4, L3>, GridSalvage), ParseError> {
+        let gd = self.descriptor_for::<ExpectedTy, L5, L4, L3>(name)?;
+        let reader = &mut self.reader;
+        let header = &self.header;
+        let (transform, mut tree) =
+            Self::read_grid_head::<ExpectedTy, L5, L4, L3>(header, reader, &gd)?;
+
+        // Leaf values are stored in topology order, read them until the first failure and
+        // drop the leaves that follow
+        let mut salvage = GridSalvage {
+            name: name.to_owned(),
+            leaves_recovered: 0,
+            lost_leaves: vec![],
+            lost_active_voxels: 0,
+            error: None,
+        };
+        let background = tree.background;
+        gd.seek_to_blocks(reader)?;
+        for node_5 in &mut tree.root_nodes {
+            for idx_5 in node_5.child_mask.iter_ones() {
+                let node_4 = node_5.nodes.get_mut(&(idx_5 as u32)).unwrap();
+                let children = node_4.child_mask.iter_ones().collect::<Vec<_>>();
+                for idx in children {
+                    let slot = idx as u32;
+                    if salvage.error.is_none() {
+                        let origin = node_4.nodes[&slot].origin;
+                        match Self::read_leaf(reader, header, &gd, background, origin) {
+                            Ok(leaf) => {
+                                node_4.nodes.insert(slot, Arc::new(leaf));
+                                salvage.leaves_recovered += 1;
+                                continue;
+                            }
+                            Err(err) => salvage.error = Some(err.to_string()),
+                        }
+                    }
+
+                    let leaf = node_4.nodes.remove(&slot).unwrap();
+                    salvage.lost_active_voxels += leaf.value_mask.count_ones() as u64;
+                    salvage.lost_leaves.push(leaf.origin);
+                    node_4.child_mask.set(idx, false);
+                    node_4.value_mask.set(idx, false);
+                    node_4.data[idx] = background;
+                }
+            }
+        }
+
+        let grid = Grid {
+            tree,
+            transform,
+            descriptor: gd,
+        };
+        Ok((grid, salvage))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::tests::{archive, COORD};
+
+    use glam::IVec3;
+    use std::io::Cursor;
+
+    #[test]
+    fn truncated_leaf_is_lost() {
+        let mut file = archive(&[("a", 1.5), ("b", -2.0)]);
+        let end_pos = VdbReader::new(&mut file).unwrap().grid_descriptors["a"].end_pos;
+        // Cut the value of the only leaf of "a" in half, which also drops the descriptor of "b"
+        let mut bytes = file.into_inner();
+        bytes.truncate(end_pos as usize - 2);
+
+        let (mut reader, salvage) = VdbReader::salvage(Cursor::new(bytes)).unwrap();
+        assert_eq!(salvage.grid_count, 2);
+        assert_eq!(salvage.truncated_grids, ["a"]);
+        assert_eq!(salvage.lost_grids, 1);
+        assert!(salvage.error.is_some());
+        assert!(!salvage.is_complete());
+
+        let (grid, salvage) = reader.read_grid_salvaged::<f32>("a").unwrap();
+        assert_eq!(salvage.leaves_recovered, 0);
+        assert_eq!(salvage.lost_leaves, [IVec3::ZERO]);
+        assert_eq!(salvage.lost_active_voxels, 1);
+        assert!(salvage.error.is_some());
+        assert_eq!(grid.tree.leaf_count(), 0);
+        assert_eq!(grid.tree.probe_value(COORD), (0.0, false));
+    }
+
+    #[test]
+    fn intact_archive_is_complete() {
+        let (mut reader, salvage) =
+            VdbReader::salvage(archive(&[("a", 1.5), ("b", -2.0)])).unwrap();
+        assert!(salvage.is_complete());
+        assert_eq!(salvage.error, None);
+        for (name, value) in [("a", 1.5), ("b", -2.0)] {
+            let (grid, salvage) = reader.read_grid_salvaged::<f32>(name).unwrap();
+            assert!(salvage.is_complete());
+            assert_eq!(salvage.leaves_recovered, 1);
+            assert_eq!(grid.tree.probe_value(COORD), (value, true));
+        }
+    }
+}
@@ -0,0 +1,498 @@
+use crate::coordinates::GlobalCoord;
+use crate::data_structure::{Grid, Node, Node3, Tree};
+
+use glam::{DVec3, IVec3, Vec3};
+use std::ops::{Add, Mul};
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+/// Read-only accessor that caches the most recently visited leaf, so repeated lookups of nearby
+/// voxels skip the traversal from the root.
+pub struct ValueAccessor<'a, ValueTy, const L5: u32 = 5, const L4: u32 = 4, const L3: u32 = 3> {
+    tree: &'a Tree<ValueTy, L5, L4, L3>,
+    leaf: Option<&'a Node3<ValueTy, L3>>,
+}
+
+impl<'a, ValueTy: Copy, const L5: u32, const L4: u32, const L3: u32>
+    ValueAccessor<'a, ValueTy, L5, L4, L3>
+{
+    pub fn new(tree: &'a Tree<ValueTy, L5, L4, L3>) -> Self {
+        Self { tree, leaf: None }
+    }
+
+    /// The tree this accessor reads from.
+    pub fn tree(&self) -> &'a Tree<ValueTy, L5, L4, L3> {
+        self.tree
+    }
+
+    /// Value and active state of the voxel at `coord`, see [`Tree::probe_value`].
+    pub fn probe_value(&mut self, coord: IVec3) -> (ValueTy, bool) {
+        let cached = self
+            .leaf
+            .filter(|node_3| coord >> L3 == node_3.origin >> L3)
+            .or_else(|| {
+                let leaf = self.tree.probe_leaf(coord);
+                if leaf.is_some() {
+                    self.leaf = leaf;
+                }
+                leaf
+            });
+        match cached {
+            Some(node_3) => {
+                let offset = node_3.global_coord_to_offset(GlobalCoord(coord)).0 as usize;
+                (node_3.buffer[offset], node_3.value_mask[offset])
+            }
+            None => self.tree.probe_value(coord),
+        }
+    }
+
+    /// Value of the voxel at `coord`.
+    pub fn get_value(&mut self, coord: IVec3) -> ValueTy {
+        self.probe_value(coord).0
+    }
+
+    /// Active state of the voxel at `coord`.
+    pub fn is_value_on(&mut self, coord: IVec3) -> bool {
+        self.probe_value(coord).1
+    }
+}
+
+impl<ValueTy: Copy, const L5: u32, const L4: u32, const L3: u32> Tree<ValueTy, L5, L4, L3> {
+    /// Creates a [`ValueAccessor`] for fast repeated lookups into this tree.
+    pub fn accessor(&self) -> ValueAccessor<'_, ValueTy, L5, L4, L3> {
+        ValueAccessor::new(self)
+    }
+
+    /// Creates a [`SharedValueAccessor`] for fast repeated lookups into this tree from many
+    /// threads at once.
+    pub fn shared_accessor(&self) -> SharedValueAccessor<'_, ValueTy, L5, L4, L3> {
+        SharedValueAccessor::new(self)
+    }
+}
+
+/// Number of leaf caches in a [`SharedValueAccessor`], threads beyond this share a cache.
+const SHARED_CACHE_SLOTS: usize = 64;
+
+static NEXT_CACHE_SLOT: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// Index of the calling thread's leaf cache in every [`SharedValueAccessor`].
+    static CACHE_SLOT: usize = NEXT_CACHE_SLOT.fetch_add(1, Ordering::Relaxed) % SHARED_CACHE_SLOTS;
+}
+
+/// Most recently visited leaf of one thread, padded to a cache line so threads don't contend.
+#[repr(align(64))]
+struct LeafCache<ValueTy, const L3: u32>(AtomicPtr<Node3<ValueTy, L3>>);
+
+/// Read-only accessor that is `Send + Sync`, so one instance can serve lookups from many threads
+/// without cloning the tree or locking it. Every thread keeps its own most recently visited leaf,
+/// like a [`ValueAccessor`] would.
+///
+/// Caches are lock free. Only when more than 64 threads use the same accessor do some of them
+/// share a cache, which costs extra traversals but never gives wrong results.
+pub struct SharedValueAccessor<'a, ValueTy, const L5: u32 = 5, const L4: u32 = 4, const L3: u32 = 3>
+{
+    tree: &'a Tree<ValueTy, L5, L4, L3>,
+    leaves: Box<[LeafCache<ValueTy, L3>]>,
+}
+
+impl<'a, ValueTy: Copy, const L5: u32, const L4: u32, const L3: u32>
+    SharedValueAccessor<'a, ValueTy, L5, L4, L3>
+{
+    pub fn new(tree: &'a Tree<ValueTy, L5, L4, L3>) -> Self {
+        Self {
+            tree,
+            leaves: (0..SHARED_CACHE_SLOTS)
+                .map(|_| LeafCache(AtomicPtr::new(std::ptr::null_mut())))
+                .collect(),
+        }
+    }
+
+    /// The tree this accessor reads from.
+    pub fn tree(&self) -> &'a Tree<ValueTy, L5, L4, L3> {
+        self.tree
+    }
+
+    /// Value and active state of the voxel at `coord`, see [`Tree::probe_value`].
+    pub fn probe_value(&self, coord: IVec3) -> (ValueTy, bool) {
+        let cache = &self.leaves[CACHE_SLOT.with(|slot| *slot)].0;
+        // SAFETY: only leaves of `self.tree` are stored in the cache, and the tree is borrowed
+        // immutably for `'a` so they stay alive and unchanged.
+        let cached = unsafe { cache.load(Ordering::Relaxed).as_ref() }
+            .filter(|node_3| coord >> L3 == node_3.origin >> L3)
+            .or_else(|| {
+                let leaf = self.tree.probe_leaf(coord);
+                if let Some(leaf) = leaf {
+                    cache.store(std::ptr::from_ref(leaf).cast_mut(), Ordering::Relaxed);
+                }
+                leaf
+            });
+        match cached {
+            Some(node_3) => {
+                let offset = node_3.global_coord_to_offset(GlobalCoord(coord)).0 as usize;
+                (node_3.buffer[offset], node_3.value_mask[offset])
+            }
+            None => self.tree.probe_value(coord),
+        }
+    }
+
+    /// Value of the voxel at `coord`.
+    pub fn get_value(&self, coord: IVec3) -> ValueTy {
+        self.probe_value(coord).0
+    }
+
+    /// Active state of the voxel at `coord`.
+    pub fn is_value_on(&self, coord: IVec3) -> bool {
+        self.probe_value(coord).1
+    }
+}
+
+/// Voxel lookups a [`Sampler`] reads from, implemented by [`ValueAccessor`] for use on one
+/// thread and by [`SharedValueAccessor`] (or a reference to one) for use from many threads.
+pub trait ProbeValue<ValueTy> {
+    /// Value and active state of the voxel at `coord`, see [`Tree::probe_value`].
+    fn probe_value(&mut self, coord: IVec3) -> (ValueTy, bool);
+}
+
+impl<ValueTy: Copy, const L5: u32, const L4: u32, const L3: u32> ProbeValue<ValueTy>
+    for ValueAccessor<'_, ValueTy, L5, L4, L3>
+{
+    fn probe_value(&mut self, coord: IVec3) -> (ValueTy, bool) {
+        ValueAccessor::probe_value(self, coord)
+    }
+}
+
+impl<ValueTy: Copy, const L5: u32, const L4: u32, const L3: u32> ProbeValue<ValueTy>
+    for SharedValueAccessor<'_, ValueTy, L5, L4, L3>
+{
+    fn probe_value(&mut self, coord: IVec3) -> (ValueTy, bool) {
+        SharedValueAccessor::probe_value(self, coord)
+    }
+}
+
+impl<ValueTy: Copy, const L5: u32, const L4: u32, const L3: u32> ProbeValue<ValueTy>
+    for &SharedValueAccessor<'_, ValueTy, L5, L4, L3>
+{
+    fn probe_value(&mut self, coord: IVec3) -> (ValueTy, bool) {
+        SharedValueAccessor::probe_value(self, coord)
+    }
+}
+
+/// Reconstructs values between voxel centers.
+pub trait Sampler {
+    /// Samples the tree behind `accessor` at the fractional index space position `index`,
+    /// returning the value and whether any of the voxels that contributed to it is active.
+    fn probe_index<ValueTy>(
+        accessor: &mut impl ProbeValue<ValueTy>,
+        index: DVec3,
+    ) -> (ValueTy, bool)
+    where
+        ValueTy: Copy + Add<Output = ValueTy> + Mul<f32, Output = ValueTy>;
+
+    /// Samples the tree behind `accessor` at the fractional index space position `index`.
+    fn sample_index<ValueTy>(accessor: &mut impl ProbeValue<ValueTy>, index: DVec3) -> ValueTy
+    where
+        ValueTy: Copy + Add<Output = ValueTy> + Mul<f32, Output = ValueTy>,
+    {
+        Self::probe_index(accessor, index).0
+    }
+
+    /// Samples `grid` at the world space position `world`.
+    ///
+    /// Every call starts with an empty cache, use [`GridSampler`] when sampling many nearby
+    /// positions.
+    fn sample<ValueTy, const L5: u32, const L4: u32, const L3: u32>(
+        grid: &Grid<ValueTy, L5, L4, L3>,
+        world: DVec3,
+    ) -> ValueTy
+    where
+        ValueTy: Copy + Add<Output = ValueTy> + Mul<f32, Output = ValueTy>,
+    {
+        Self::sample_index(
+            &mut grid.tree.accessor(),
+            grid.transform.world_to_index_f64(world),
+        )
+    }
+}
+
+/// Nearest neighbor sampling, returns the value of the closest voxel.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PointSampler;
+
+impl Sampler for PointSampler {
+    fn probe_index<ValueTy>(
+        accessor: &mut impl ProbeValue<ValueTy>,
+        index: DVec3,
+    ) -> (ValueTy, bool)
+    where
+        ValueTy: Copy + Add<Output = ValueTy> + Mul<f32, Output = ValueTy>,
+    {
+        accessor.probe_value(index.round().as_ivec3())
+    }
+}
+
+/// Trilinear interpolation of the 8 voxels surrounding the sample position.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BoxSampler;
+
+impl Sampler for BoxSampler {
+    fn probe_index<ValueTy>(
+        accessor: &mut impl ProbeValue<ValueTy>,
+        index: DVec3,
+    ) -> (ValueTy, bool)
+    where
+        ValueTy: Copy + Add<Output = ValueTy> + Mul<f32, Output = ValueTy>,
+    {
+        let base = index.floor();
+        let t = (index - base).as_vec3();
+        let base = base.as_ivec3();
+
+        let mut active = false;
+        let mut sample = |offset: IVec3| {
+            let (value, is_active) = accessor.probe_value(base + offset);
+            active |= is_active;
+            value
+        };
+        let lerp = |a: ValueTy, b: ValueTy, t: f32| a * (1.0 - t) + b * t;
+
+        let c00 = lerp(
+            sample(IVec3::new(0, 0, 0)),
+            sample(IVec3::new(0, 0, 1)),
+            t.z,
+        );
+        let c01 = lerp(
+            sample(IVec3::new(0, 1, 0)),
+            sample(IVec3::new(0, 1, 1)),
+            t.z,
+        );
+        let c10 = lerp(
+            sample(IVec3::new(1, 0, 0)),
+            sample(IVec3::new(1, 0, 1)),
+            t.z,
+        );
+        let c11 = lerp(
+            sample(IVec3::new(1, 1, 0)),
+            sample(IVec3::new(1, 1, 1)),
+            t.z,
+        );
+        let c0 = lerp(c00, c01, t.y);
+        let c1 = lerp(c10, c11, t.y);
+        (lerp(c0, c1, t.x), active)
+    }
+}
+
+/// Triquadratic interpolation of the 27 voxels around the sample position, which gives smoother
+/// results than [`BoxSampler`] at the cost of more lookups. Matches OpenVDB's
+/// `tools::QuadraticSampler`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct QuadraticSampler;
+
+impl Sampler for QuadraticSampler {
+    fn probe_index<ValueTy>(
+        accessor: &mut impl ProbeValue<ValueTy>,
+        index: DVec3,
+    ) -> (ValueTy, bool)
+    where
+        ValueTy: Copy + Add<Output = ValueTy> + Mul<f32, Output = ValueTy>,
+    {
+        let base = index.floor();
+        let t = (index - base).as_vec3();
+        let base = base.as_ivec3();
+
+        // Quadratic through the values at -1, 0 and 1, evaluated at `t`
+        let kernel = |values: [ValueTy; 3], t: f32| {
+            values[0] * (0.5 * t * (t - 1.0))
+                + values[1] * (1.0 - t * t)
+                + values[2] * (0.5 * t * (t + 1.0))
+        };
+
+        let mut active = false;
+        let mut sample = |offset: IVec3| {
+            let (value, is_active) = accessor.probe_value(base + offset);
+            active |= is_active;
+            value
+        };
+
+        let vx = [-1, 0, 1].map(|x| {
+            let vy = [-1, 0, 1].map(|y| {
+                let vz = [-1, 0, 1].map(|z| sample(IVec3::new(x, y, z)));
+                kernel(vz, t.z)
+            });
+            kernel(vy, t.y)
+        });
+        (kernel(vx, t.x), active)
+    }
+}
+
+/// Trilinear sampler for staggered (MAC) vector grids, as written by fluid solvers. The x, y and
+/// z components of a voxel are stored on the centers of its lower x, y and z faces, so each
+/// component is interpolated at its own offset position. Matches OpenVDB's
+/// `tools::StaggeredBoxSampler`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StaggeredBoxSampler;
+
+impl StaggeredBoxSampler {
+    /// Samples the staggered tree behind `accessor` at the fractional index space position
+    /// `index`, returning the value and whether any of the voxels that contributed to it is
+    /// active.
+    pub fn probe_index(accessor: &mut impl ProbeValue<Vec3>, index: DVec3) -> (Vec3, bool) {
+        let (x, x_active) = BoxSampler::probe_index(accessor, index + DVec3::new(0.5, 0.0, 0.0));
+        let (y, y_active) = BoxSampler::probe_index(accessor, index + DVec3::new(0.0, 0.5, 0.0));
+        let (z, z_active) = BoxSampler::probe_index(accessor, index + DVec3::new(0.0, 0.0, 0.5));
+        (Vec3::new(x.x, y.y, z.z), x_active || y_active || z_active)
+    }
+
+    /// Samples the staggered tree behind `accessor` at the fractional index space position
+    /// `index`.
+    pub fn sample_index(accessor: &mut impl ProbeValue<Vec3>, index: DVec3) -> Vec3 {
+        Self::probe_index(accessor, index).0
+    }
+
+    /// Samples the staggered `grid` at the world space position `world`.
+    pub fn sample<const L5: u32, const L4: u32, const L3: u32>(
+        grid: &Grid<Vec3, L5, L4, L3>,
+        world: DVec3,
+    ) -> Vec3 {
+        Self::sample_index(
+            &mut grid.tree.accessor(),
+            grid.transform.world_to_index_f64(world),
+        )
+    }
+}
+
+/// Samples a grid in world space with sampler `S`, keeping an accessor around between calls so
+/// coherent access patterns like ray marching stay fast.
+///
+/// The accessor defaults to a [`ValueAccessor`]. To sample one grid from many threads, give every
+/// thread a sampler over a reference to the same [`SharedValueAccessor`] with
+/// [`GridSampler::with_accessor`].
+pub struct GridSampler<
+    'a,
+    S,
+    ValueTy,
+    const L5: u32 = 5,
+    const L4: u32 = 4,
+    const L3: u32 = 3,
+    A = ValueAccessor<'a, ValueTy, L5, L4, L3>,
+> {
+    grid: &'a Grid<ValueTy, L5, L4, L3>,
+    accessor: A,
+    sampler: std::marker::PhantomData<S>,
+}
+
+impl<'a, S, ValueTy, const L5: u32, const L4: u32, const L3: u32>
+    GridSampler<'a, S, ValueTy, L5, L4, L3>
+where
+    S: Sampler,
+    ValueTy: Copy + Add<Output = ValueTy> + Mul<f32, Output = ValueTy>,
+{
+    pub fn new(grid: &'a Grid<ValueTy, L5, L4, L3>) -> Self {
+        Self::with_accessor(grid, grid.tree.accessor())
+    }
+}
+
+impl<'a, S, ValueTy, const L5: u32, const L4: u32, const L3: u32, A>
+    GridSampler<'a, S, ValueTy, L5, L4, L3, A>
+where
+    S: Sampler,
+    ValueTy: Copy + Add<Output = ValueTy> + Mul<f32, Output = ValueTy>,
+    A: ProbeValue<ValueTy>,
+{
+    /// Creates a sampler that reads `grid` through `accessor`, which must access `grid.tree`.
+    pub fn with_accessor(grid: &'a Grid<ValueTy, L5, L4, L3>, accessor: A) -> Self {
+        Self {
+            grid,
+            accessor,
+            sampler: std::marker::PhantomData,
+        }
+    }
+
+    /// Samples the grid at the world space position `world`.
+    pub fn sample(&mut self, world: DVec3) -> ValueTy {
+        self.sample_index(self.grid.transform.world_to_index_f64(world))
+    }
+
+    /// Samples the grid at the fractional index space position `index`.
+    pub fn sample_index(&mut self, index: DVec3) -> ValueTy {
+        S::sample_index(&mut self.accessor, index)
+    }
+}
+
+impl<'a, S, ValueTy, const L5: u32, const L4: u32, const L3: u32>
+    GridSampler<'a, S, ValueTy, L5, L4, L3, SharedValueAccessor<'a, ValueTy, L5, L4, L3>>
+where
+    S: Sampler,
+    ValueTy: Copy + Add<Output = ValueTy> + Mul<f32, Output = ValueTy>,
+{
+    /// Creates a sampler over a [`SharedValueAccessor`], which samples through a shared
+    /// reference so one instance can serve many threads.
+    pub fn new_shared(grid: &'a Grid<ValueTy, L5, L4, L3>) -> Self {
+        Self::with_accessor(grid, grid.tree.shared_accessor())
+    }
+
+    /// Like [`GridSampler::sample`], through a shared reference.
+    pub fn sample_shared(&self, world: DVec3) -> ValueTy {
+        self.sample_index_shared(self.grid.transform.world_to_index_f64(world))
+    }
+
+    /// Like [`GridSampler::sample_index`], through a shared reference.
+    pub fn sample_index_shared(&self, index: DVec3) -> ValueTy {
+        S::sample_index(&mut &self.accessor, index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::tests::sphere;
+
+    #[test]
+    fn shared_accessor_samples_like_value_accessor_from_many_threads() {
+        let grid = sphere(Vec3::ZERO);
+        let positions = (0..2000)
+            .map(|i| {
+                let t = i as f64 * 0.001;
+                DVec3::new(t.sin(), (3.0 * t).cos(), (7.0 * t).sin()) * 1.2
+            })
+            .collect::<Vec<_>>();
+
+        let mut box_sampler = GridSampler::<BoxSampler, _>::new(&grid);
+        let mut quadratic_sampler = GridSampler::<QuadraticSampler, _>::new(&grid);
+        let expected = positions
+            .iter()
+            .map(|&p| (box_sampler.sample(p), quadratic_sampler.sample(p)))
+            .collect::<Vec<_>>();
+
+        let accessor = grid.tree.shared_accessor();
+        std::thread::scope(|scope| {
+            let threads = (0..8)
+                .map(|thread| {
+                    let (accessor, positions) = (&accessor, &positions);
+                    let grid = &grid;
+                    scope.spawn(move || {
+                        let mut box_sampler =
+                            GridSampler::<BoxSampler, _, 5, 4, 3, _>::with_accessor(grid, accessor);
+                        let mut quadratic_sampler =
+                            GridSampler::<QuadraticSampler, _, 5, 4, 3, _>::with_accessor(
+                                grid, accessor,
+                            );
+                        // Every thread walks the positions from a different start, so the caches
+                        // of the threads point at different leaves most of the time
+                        let offset = thread * positions.len() / 8;
+                        (0..positions.len())
+                            .map(|i| (i + offset) % positions.len())
+                            .map(|i| {
+                                let p = positions[i];
+                                (i, box_sampler.sample(p), quadratic_sampler.sample(p))
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            for thread in threads {
+                for (i, box_value, quadratic_value) in thread.join().unwrap() {
+                    assert_eq!((box_value, quadratic_value), expected[i]);
+                }
+            }
+        });
+    }
+}
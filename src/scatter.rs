@@ -0,0 +1,150 @@
+use crate::coordinates::{CoordBBox, Index};
+use crate::data_structure::{active_tiles, Grid, Node, Node3, Tree};
+use crate::simd::count_ones;
+
+use glam::{DVec3, IVec3, Vec3};
+
+/// Small deterministic random number generator, so scattering the same grid always yields the
+/// same points.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform integer in `[0, n)`.
+    fn below(&mut self, n: u64) -> u64 {
+        ((self.next_u64() as u128 * n as u128) >> 64) as u64
+    }
+
+    /// Uniformly distributed world space position within the voxel at `coord`, which covers
+    /// half a voxel on either side of its center.
+    fn point_in_voxel<ValueTy, const L5: u32, const L4: u32, const L3: u32>(
+        &mut self,
+        grid: &Grid<ValueTy, L5, L4, L3>,
+        coord: IVec3,
+    ) -> Vec3 {
+        let offset = DVec3::new(self.next_f64(), self.next_f64(), self.next_f64()) - 0.5;
+        grid.transform
+            .index_to_world_f64(coord.as_dvec3() + offset)
+            .as_vec3()
+    }
+}
+
+/// Active voxels of a leaf or an active tile.
+enum ActiveRegion<'a, ValueTy, const L3: u32> {
+    Leaf(&'a Node3<ValueTy, L3>),
+    Tile(CoordBBox),
+}
+
+impl<'a, ValueTy, const L3: u32> ActiveRegion<'a, ValueTy, L3> {
+    fn voxel_count(&self) -> u64 {
+        match self {
+            Self::Leaf(node_3) => count_ones(&node_3.value_mask) as u64,
+            Self::Tile(bbox) => bbox.volume(),
+        }
+    }
+
+    /// Coordinate of the `n`th active voxel in this region.
+    fn nth_voxel(&self, n: u64) -> IVec3 {
+        match self {
+            Self::Leaf(node_3) => {
+                let idx = node_3.value_mask.iter_ones().nth(n as usize).unwrap();
+                node_3.offset_to_global_coord(Index(idx as u32)).0
+            }
+            Self::Tile(bbox) => {
+                let dim = bbox.dim();
+                let (x, y) = (dim.x as u64, dim.y as u64);
+                bbox.min + IVec3::new((n % x) as i32, (n / x % y) as i32, (n / x / y) as i32)
+            }
+        }
+    }
+}
+
+fn active_regions<ValueTy, const L5: u32, const L4: u32, const L3: u32>(
+    tree: &Tree<ValueTy, L5, L4, L3>,
+) -> Vec<ActiveRegion<'_, ValueTy, L3>> {
+    let mut regions = vec![];
+    for node_5 in &tree.root_nodes {
+        for idx in active_tiles(&node_5.child_mask, &node_5.value_mask) {
+            regions.push(ActiveRegion::Tile(node_5.tile_bbox(Index(idx as u32))));
+        }
+        for node_4 in node_5.nodes.values() {
+            for idx in active_tiles(&node_4.child_mask, &node_4.value_mask) {
+                regions.push(ActiveRegion::Tile(node_4.tile_bbox(Index(idx as u32))));
+            }
+        }
+    }
+    regions.extend(tree.leaves().map(ActiveRegion::Leaf));
+    regions
+}
+
+/// Scatters `count` points uniformly over the active voxels of `grid`, including the voxels
+/// covered by active tiles, and returns their world space positions. Every active voxel is
+/// equally likely to receive each point, regardless of its value.
+///
+/// Scattering is deterministic, the same grid always yields the same points.
+pub fn scatter_uniform<ValueTy, const L5: u32, const L4: u32, const L3: u32>(
+    grid: &Grid<ValueTy, L5, L4, L3>,
+    count: usize,
+) -> Vec<Vec3> {
+    let regions = active_regions(&grid.tree);
+    let ends = regions
+        .iter()
+        .scan(0, |total, region| {
+            *total += region.voxel_count();
+            Some(*total)
+        })
+        .collect::<Vec<_>>();
+    let total = ends.last().copied().unwrap_or(0);
+    if total == 0 {
+        return vec![];
+    }
+
+    let mut rng = SplitMix64(0x5eed);
+    let mut points = Vec::with_capacity(count);
+    for _ in 0..count {
+        let n = rng.below(total);
+        let region = ends.partition_point(|&end| end <= n);
+        let start = if region == 0 { 0 } else { ends[region - 1] };
+        let coord = regions[region].nth_voxel(n - start);
+        points.push(rng.point_in_voxel(grid, coord));
+    }
+    points
+}
+
+/// Scatters points over the active voxels of the density grid `grid`, with an expected
+/// `points_per_voxel` times the voxel value in every voxel, and returns their world space
+/// positions. Fractional expectations are rounded randomly, and voxels with values of zero or
+/// below receive no points.
+///
+/// Scattering is deterministic, the same grid always yields the same points.
+pub fn scatter_dense<const L5: u32, const L4: u32, const L3: u32>(
+    grid: &Grid<f32, L5, L4, L3>,
+    points_per_voxel: f32,
+) -> Vec<Vec3> {
+    let mut rng = SplitMix64(0x5eed);
+    let mut accessor = grid.tree.accessor();
+    let mut points = vec![];
+    grid.tree.for_each_active_voxel(|coord| {
+        let expected = (points_per_voxel * accessor.get_value(coord)).max(0.0) as f64;
+        let mut count = expected.floor() as u64;
+        if rng.next_f64() < expected.fract() {
+            count += 1;
+        }
+        for _ in 0..count {
+            points.push(rng.point_in_voxel(grid, coord));
+        }
+    });
+    points
+}
@@ -0,0 +1,209 @@
+use crate::asset_loader::{VdbAsset, VdbPlugin};
+use crate::surface_mesh::VdbSurface;
+use crate::volume_render::VdbVolume;
+
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+
+/// Plays a numbered sequence of `.vdb` files, e.g. a simulation cache, one file per frame.
+///
+/// Files of the frames just ahead of the playhead are loaded in the background by the
+/// `AssetServer` and dropped once played. When the file of the playhead's frame has loaded it
+/// becomes the current frame, and the asset of a [`VdbVolume`] or [`VdbSurface`] on the same
+/// entity is swapped for it; until then the previous frame stays on screen. Spawn those with a
+/// default handle, [`VdbSequencePlugin`] fills it in.
+///
+/// ```no_run
+/// use bevy::prelude::*;
+/// use vdb_rs::{VdbSequence, VdbSequencePlugin, VdbVolume, VdbVolumePlugin};
+///
+/// fn setup(mut commands: Commands) {
+///     commands.spawn((
+///         VdbSequence::new("smoke.####.vdb", 1..=120).with_fps(30.0),
+///         VdbVolume::new(Handle::default(), "density"),
+///         SpatialBundle::default(),
+///     ));
+/// }
+///
+/// App::new()
+///     .add_plugins((DefaultPlugins, VdbVolumePlugin, VdbSequencePlugin))
+///     .add_systems(Startup, setup)
+///     .run();
+/// ```
+#[derive(Component, Clone, Debug)]
+pub struct VdbSequence {
+    /// Asset path of the files, with a run of `#` standing for the zero padded frame number,
+    /// e.g. `smoke.####.vdb` for `smoke.0001.vdb`
+    pub pattern: String,
+    pub frames: RangeInclusive<u32>,
+    pub fps: f32,
+    /// Whether playback wraps around to the first frame, or stops at the last one
+    pub looping: bool,
+    pub playing: bool,
+    /// Number of frames after the playhead loaded ahead of time
+    pub preload: u32,
+    time: f32,
+    handles: HashMap<u32, Handle<VdbAsset>>,
+    current: Option<(u32, Handle<VdbAsset>)>,
+}
+
+impl VdbSequence {
+    /// Plays `frames` of `pattern` in a loop at 24 frames per second, loading 8 frames ahead.
+    pub fn new(pattern: impl Into<String>, frames: RangeInclusive<u32>) -> Self {
+        Self {
+            pattern: pattern.into(),
+            frames,
+            fps: 24.0,
+            looping: true,
+            playing: true,
+            preload: 8,
+            time: 0.0,
+            handles: HashMap::new(),
+            current: None,
+        }
+    }
+
+    pub fn with_fps(mut self, fps: f32) -> Self {
+        self.fps = fps;
+        self
+    }
+
+    pub fn with_looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    pub fn with_preload(mut self, preload: u32) -> Self {
+        self.preload = preload;
+        self
+    }
+
+    /// Asset path of the file of `frame`.
+    pub fn frame_path(&self, frame: u32) -> String {
+        match self.pattern.find('#') {
+            Some(start) => {
+                let width = self.pattern[start..]
+                    .chars()
+                    .take_while(|&c| c == '#')
+                    .count();
+                format!(
+                    "{}{:0width$}{}",
+                    &self.pattern[..start],
+                    frame,
+                    &self.pattern[start + width..]
+                )
+            }
+            None => self.pattern.clone(),
+        }
+    }
+
+    fn frame_count(&self) -> u32 {
+        (self.frames.end() + 1).saturating_sub(*self.frames.start())
+    }
+
+    /// The frame to show at the current time, which may still be loading.
+    pub fn playhead(&self) -> u32 {
+        let index = (self.time * self.fps).max(0.0) as u32;
+        let index = if self.looping {
+            index % self.frame_count().max(1)
+        } else {
+            index.min(self.frame_count().saturating_sub(1))
+        };
+        self.frames.start() + index
+    }
+
+    /// Moves the playhead to `frame`.
+    pub fn seek(&mut self, frame: u32) {
+        let frame = frame.clamp(*self.frames.start(), *self.frames.end());
+        self.time = (frame - self.frames.start()) as f32 / self.fps;
+    }
+
+    /// The frame on screen, if any has loaded yet.
+    pub fn current_frame(&self) -> Option<u32> {
+        self.current.as_ref().map(|(frame, _)| *frame)
+    }
+
+    /// The asset of the frame on screen, if any has loaded yet.
+    pub fn current_asset(&self) -> Option<&Handle<VdbAsset>> {
+        self.current.as_ref().map(|(_, handle)| handle)
+    }
+
+    /// The playhead followed by the frames to load ahead of it.
+    fn window(&self) -> Vec<u32> {
+        let start = self.playhead() - self.frames.start();
+        let count = self.frame_count();
+        (start..=start.saturating_add(self.preload))
+            .filter_map(|index| match self.looping {
+                true => Some(index % count),
+                false => (index < count).then_some(index),
+            })
+            .map(|index| self.frames.start() + index)
+            .collect()
+    }
+}
+
+fn play_sequences(
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    assets: Res<Assets<VdbAsset>>,
+    mut sequences: Query<(
+        &mut VdbSequence,
+        Option<&mut VdbVolume>,
+        Option<&mut VdbSurface>,
+    )>,
+) {
+    for (mut sequence, volume, surface) in &mut sequences {
+        if sequence.frame_count() == 0 {
+            continue;
+        }
+        if sequence.playing {
+            sequence.time += time.delta_seconds();
+            let last = (sequence.frame_count() - 1) as f32 / sequence.fps;
+            if !sequence.looping && sequence.time >= last {
+                sequence.time = last;
+                sequence.playing = false;
+            }
+        }
+
+        let window = sequence.window();
+        for &frame in &window {
+            if !sequence.handles.contains_key(&frame) {
+                let handle = asset_server.load(sequence.frame_path(frame).as_str());
+                sequence.handles.insert(frame, handle);
+            }
+        }
+        // Dropping the handles of played frames unloads them
+        sequence.handles.retain(|frame, _| window.contains(frame));
+
+        let playhead = window[0];
+        if sequence.current_frame() == Some(playhead) {
+            continue;
+        }
+        let handle = sequence.handles[&playhead].clone();
+        if !assets.contains(&handle) {
+            // Still loading, keep showing the previous frame
+            continue;
+        }
+        if let Some(mut volume) = volume {
+            volume.asset = handle.clone();
+        }
+        if let Some(mut surface) = surface {
+            surface.asset = handle.clone();
+        }
+        sequence.current = Some((playhead, handle));
+    }
+}
+
+/// Plays the [`VdbSequence`]s, add [`crate::VdbVolumePlugin`] or [`crate::VdbSurfacePlugin`]
+/// to display them.
+pub struct VdbSequencePlugin;
+
+impl Plugin for VdbSequencePlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<VdbPlugin>() {
+            app.add_plugins(VdbPlugin);
+        }
+        app.add_systems(Update, play_sequences);
+    }
+}
@@ -0,0 +1,29 @@
+//! Compact serde representation of node masks: their length followed by their raw 64-bit
+//! words, rather than one boolean per bit.
+
+use crate::data_structure::{load_mask_words, mask_words, MaskWord};
+
+use bitvec::prelude::*;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+pub(crate) fn serialize<S: Serializer>(
+    mask: &BitVec<MaskWord, Lsb0>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    (mask.len() as u64, mask_words(mask)).serialize(serializer)
+}
+
+pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BitVec<MaskWord, Lsb0>, D::Error> {
+    let (len, words) = <(u64, Vec<u64>)>::deserialize(deserializer)?;
+    if len > words.len() as u64 * 64 {
+        return Err(serde::de::Error::custom(format!(
+            "mask of {len} bits stored in {} words",
+            words.len()
+        )));
+    }
+    let mut mask = bitvec![MaskWord, Lsb0; 0; len as usize];
+    load_mask_words(&mut mask, &words);
+    Ok(mask)
+}
@@ -0,0 +1,298 @@
+//! Vectorized versions of the leaf level loops that dominate reading large grids, picked at
+//! runtime from the instruction sets of the CPU, with scalar fallbacks for other targets.
+
+use crate::data_structure::MaskWord;
+
+use bitvec::domain::Domain;
+use bitvec::prelude::*;
+use bytemuck::Pod;
+use half::f16;
+use half::slice::HalfFloatSliceExt;
+
+/// Number of set bits of `mask`.
+pub(crate) fn count_ones(mask: &BitSlice<MaskWord, Lsb0>) -> usize {
+    match mask.domain() {
+        Domain::Region {
+            head: None,
+            body,
+            tail: None,
+        } => count_word_ones(body),
+        _ => mask.count_ones(),
+    }
+}
+
+fn count_word_ones(words: &[MaskWord]) -> usize {
+    #[cfg(target_arch = "x86_64")]
+    if std::arch::is_x86_feature_detected!("popcnt") {
+        // SAFETY: the CPU supports the instructions enabled for the function
+        return unsafe { count_word_ones_popcnt(words) };
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        // SAFETY: NEON is part of the aarch64 baseline
+        return unsafe { count_word_ones_neon(words) };
+    }
+    #[allow(unreachable_code)]
+    words.iter().map(|word| word.count_ones() as usize).sum()
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "popcnt")]
+unsafe fn count_word_ones_popcnt(words: &[MaskWord]) -> usize {
+    words.iter().map(|word| word.count_ones() as usize).sum()
+}
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn count_word_ones_neon(words: &[MaskWord]) -> usize {
+    use std::arch::aarch64::*;
+
+    let bytes: &[u8] = bytemuck::cast_slice(words);
+    let mut chunks = bytes.chunks_exact(16);
+    let mut count = 0;
+    for chunk in &mut chunks {
+        // Bits per byte, summed across the 16 bytes of the vector
+        count += vaddlvq_u8(vcntq_u8(vld1q_u8(chunk.as_ptr()))) as usize;
+    }
+    count
+        + chunks
+            .remainder()
+            .iter()
+            .map(|byte| byte.count_ones() as usize)
+            .sum::<usize>()
+}
+
+/// Converts half floats to single precision into `dst`, using F16C or NEON when available.
+pub(crate) fn f16_to_f32(src: &[f16], dst: &mut [f32]) {
+    src.convert_to_f32_slice(dst);
+}
+
+/// Converts single precision floats to half floats into `dst`, using F16C or NEON when
+/// available.
+pub(crate) fn f32_to_f16(src: &[f32], dst: &mut [f16]) {
+    dst.convert_from_f32_slice(src);
+}
+
+/// Spreads `values`, stored for the set bits of `value_mask` only, over all `value_mask.len()`
+/// slots of `expanded`. Other slots take `inactive[1]` where `selection_mask`, of the same
+/// length, is set and `inactive[0]` elsewhere.
+///
+/// Masks are processed 64 bits at a time: full and empty words are copied and filled in bulk,
+/// and mixed words of 4 and 8 byte values use the expanding loads of AVX-512 when available.
+pub(crate) fn expand_active<T: Pod>(
+    values: &[T],
+    value_mask: &BitSlice<MaskWord, Lsb0>,
+    selection_mask: &BitSlice<MaskWord, Lsb0>,
+    inactive: [T; 2],
+    expanded: &mut [T],
+) {
+    assert_eq!(value_mask.len(), selection_mask.len());
+    assert_eq!(value_mask.len(), expanded.len());
+    assert!(
+        values.len() >= count_ones(value_mask),
+        "Too few active values"
+    );
+    let mut read_idx = 0;
+    for ((out, value_bits), selection_bits) in expanded
+        .chunks_mut(64)
+        .zip(value_mask.chunks(64))
+        .zip(selection_mask.chunks(64))
+    {
+        let len = out.len();
+        let value_word = value_bits.load_le::<u64>();
+        let selection_word = selection_bits.load_le::<u64>();
+        let all = u64::MAX >> (64 - len);
+        if value_word == all {
+            out.copy_from_slice(&values[read_idx..read_idx + len]);
+        } else if value_word == 0 && selection_word == 0 {
+            out.fill(inactive[0]);
+        } else if !expand_word_simd(
+            &values[read_idx..],
+            value_word,
+            selection_word,
+            inactive,
+            out,
+        ) {
+            for (idx, slot) in out.iter_mut().enumerate() {
+                *slot = if value_word & (1 << idx) != 0 {
+                    read_idx += 1;
+                    values[read_idx - 1]
+                } else {
+                    inactive[(selection_word >> idx & 1) as usize]
+                };
+            }
+            continue;
+        }
+        read_idx += value_word.count_ones() as usize;
+    }
+}
+
+/// Expands the values of one mask word into `out` with AVX-512, returning whether it did.
+#[allow(unused_variables)]
+fn expand_word_simd<T: Pod>(
+    values: &[T],
+    value_word: u64,
+    selection_word: u64,
+    inactive: [T; 2],
+    out: &mut [T],
+) -> bool {
+    #[cfg(target_arch = "x86_64")]
+    if out.len() == 64 && std::arch::is_x86_feature_detected!("avx512f") {
+        use bytemuck::{try_cast, try_cast_slice, try_cast_slice_mut};
+
+        if let (Ok(inactive), Ok(values), Ok(out)) = (
+            try_cast::<_, [u32; 2]>(inactive),
+            try_cast_slice(values),
+            try_cast_slice_mut(out),
+        ) {
+            // SAFETY: the CPU supports AVX-512F, `out` has 64 slots and `values` holds at least
+            // the active values of the word, which are the only ones loaded
+            unsafe { expand_word_avx512_32(values, value_word, selection_word, inactive, out) };
+            return true;
+        }
+        if let (Ok(inactive), Ok(values), Ok(out)) = (
+            try_cast::<_, [u64; 2]>(inactive),
+            try_cast_slice(values),
+            try_cast_slice_mut(out),
+        ) {
+            // SAFETY: as above
+            unsafe { expand_word_avx512_64(values, value_word, selection_word, inactive, out) };
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx512f")]
+unsafe fn expand_word_avx512_32(
+    values: &[u32],
+    value_word: u64,
+    selection_word: u64,
+    inactive: [u32; 2],
+    out: &mut [u32],
+) {
+    use std::arch::x86_64::*;
+
+    let inactive_0 = _mm512_set1_epi32(inactive[0] as i32);
+    let inactive_1 = _mm512_set1_epi32(inactive[1] as i32);
+    let mut read_idx = 0;
+    for lane in 0..4 {
+        let active = (value_word >> (16 * lane)) as u16;
+        let selected = (selection_word >> (16 * lane)) as u16;
+        let background = _mm512_mask_blend_epi32(selected, inactive_0, inactive_1);
+        let expanded =
+            _mm512_mask_expandloadu_epi32(background, active, values.as_ptr().add(read_idx).cast());
+        _mm512_storeu_si512(out.as_mut_ptr().add(16 * lane).cast(), expanded);
+        read_idx += active.count_ones() as usize;
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx512f")]
+unsafe fn expand_word_avx512_64(
+    values: &[u64],
+    value_word: u64,
+    selection_word: u64,
+    inactive: [u64; 2],
+    out: &mut [u64],
+) {
+    use std::arch::x86_64::*;
+
+    let inactive_0 = _mm512_set1_epi64(inactive[0] as i64);
+    let inactive_1 = _mm512_set1_epi64(inactive[1] as i64);
+    let mut read_idx = 0;
+    for lane in 0..8 {
+        let active = (value_word >> (8 * lane)) as u8;
+        let selected = (selection_word >> (8 * lane)) as u8;
+        let background = _mm512_mask_blend_epi64(selected, inactive_0, inactive_1);
+        let expanded =
+            _mm512_mask_expandloadu_epi64(background, active, values.as_ptr().add(read_idx).cast());
+        _mm512_storeu_si512(out.as_mut_ptr().add(8 * lane).cast(), expanded);
+        read_idx += active.count_ones() as usize;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic mask words with runs of full and empty words between random ones.
+    fn random_words(count: usize) -> Vec<MaskWord> {
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        (0..count)
+            .map(|idx| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                match idx % 5 {
+                    0 => MaskWord::MAX,
+                    1 => 0,
+                    _ => state as MaskWord,
+                }
+            })
+            .collect()
+    }
+
+    fn expand_scalar<T: Pod>(
+        values: &[T],
+        value_mask: &BitSlice<MaskWord, Lsb0>,
+        selection_mask: &BitSlice<MaskWord, Lsb0>,
+        inactive: [T; 2],
+    ) -> Vec<T> {
+        let mut values = values.iter();
+        value_mask
+            .iter()
+            .by_vals()
+            .zip(selection_mask.iter().by_vals())
+            .map(|(active, selected)| match active {
+                true => *values.next().unwrap(),
+                false => inactive[selected as usize],
+            })
+            .collect()
+    }
+
+    fn assert_expands_like_scalar<T: Pod + PartialEq + std::fmt::Debug>(
+        value: impl Fn(usize) -> T,
+    ) {
+        let words = random_words(40);
+        let (value_words, selection_words) = words.split_at(20);
+        // Lengths that end in the middle of a word take the scalar path for the last one
+        for len in [512, 1000] {
+            let value_mask = &value_words.view_bits::<Lsb0>()[..len];
+            let selection_mask = &selection_words.view_bits::<Lsb0>()[..len];
+            let values = (0..count_ones(value_mask)).map(&value).collect::<Vec<_>>();
+            let inactive = [value(1 << 20), value((1 << 20) + 1)];
+
+            let mut expanded = vec![T::zeroed(); len];
+            expand_active(&values, value_mask, selection_mask, inactive, &mut expanded);
+            assert_eq!(
+                expanded,
+                expand_scalar(&values, value_mask, selection_mask, inactive)
+            );
+        }
+    }
+
+    #[test]
+    fn count_ones_matches_scalar() {
+        let words = random_words(37);
+        for len in [0, 1, 2, 8, 37] {
+            let expected: usize = words[..len].iter().map(|w| w.count_ones() as usize).sum();
+            assert_eq!(count_word_ones(&words[..len]), expected);
+            assert_eq!(count_ones(words[..len].view_bits()), expected);
+        }
+        let bits = &words.view_bits::<Lsb0>()[3..1000];
+        assert_eq!(
+            count_ones(bits),
+            bits.iter().by_vals().filter(|&bit| bit).count()
+        );
+    }
+
+    #[test]
+    fn expand_active_matches_scalar() {
+        assert_expands_like_scalar(|idx| idx as u8);
+        assert_expands_like_scalar(|idx| idx as f32);
+        assert_expands_like_scalar(|idx| idx as u64);
+        assert_expands_like_scalar(|idx| [idx as f32, 1.0, 2.0]);
+    }
+}
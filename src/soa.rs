@@ -0,0 +1,376 @@
+use crate::data_structure::{MaskWord, Node3, Tree};
+
+use bitvec::prelude::*;
+use glam::{DVec3, IVec3, Vec3, Vec4};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Offset of `coord` within the leaf of size `2^L3` containing it, see
+/// [`Node::global_coord_to_offset`](crate::Node::global_coord_to_offset).
+fn leaf_offset<const L3: u32>(coord: IVec3) -> usize {
+    let local = coord & ((1 << L3) - 1);
+    ((local.x << (2 * L3)) | (local.y << L3) | local.z) as usize
+}
+
+/// Leaf node of a [`SoaTree`], with its vectors split into one contiguous plane of values per
+/// component instead of interleaved, so loops over a single component touch only its values and
+/// vectorize.
+#[derive(Debug, Clone)]
+pub struct SoaLeaf<const L3: u32 = 3> {
+    /// Values of the `x`, `y` and `z` components, in the voxel order of [`Node3::buffer`]
+    pub planes: [Vec<f32>; 3],
+    value_mask: BitVec<MaskWord, Lsb0>,
+    origin: IVec3,
+}
+
+impl<const L3: u32> SoaLeaf<L3> {
+    /// Splits the interleaved values of `leaf` into planes.
+    pub fn from_leaf(leaf: &Node3<Vec3, L3>) -> Self {
+        let plane = |axis: usize| leaf.buffer.iter().map(|v| v[axis]).collect();
+        Self {
+            planes: [plane(0), plane(1), plane(2)],
+            value_mask: leaf.value_mask.clone(),
+            origin: leaf.origin,
+        }
+    }
+
+    /// Interleaves the planes back into a regular leaf.
+    pub fn to_leaf(&self) -> Node3<Vec3, L3> {
+        let [x, y, z] = &self.planes;
+        Node3 {
+            buffer: x
+                .iter()
+                .zip(y)
+                .zip(z)
+                .map(|((&x, &y), &z)| Vec3::new(x, y, z))
+                .collect(),
+            value_mask: self.value_mask.clone(),
+            origin: self.origin,
+        }
+    }
+
+    pub fn origin(&self) -> IVec3 {
+        self.origin
+    }
+
+    /// Active states of the voxels, which are part of the topology and can't be changed.
+    pub fn value_mask(&self) -> &BitSlice<MaskWord, Lsb0> {
+        &self.value_mask
+    }
+
+    /// Value of the voxel at `offset`, see [`Node3::buffer`].
+    pub fn value(&self, offset: usize) -> Vec3 {
+        let [x, y, z] = &self.planes;
+        Vec3::new(x[offset], y[offset], z[offset])
+    }
+
+    pub fn set_value(&mut self, offset: usize, value: Vec3) {
+        for (plane, component) in self.planes.iter_mut().zip(value.to_array()) {
+            plane[offset] = component;
+        }
+    }
+}
+
+/// Vector tree whose leaves store their values as one plane per component, see [`SoaLeaf`].
+///
+/// Reconstructing a vector from three planes costs a little on single voxel lookups, but
+/// component-wise operations such as [`SoaTree::map_component`] and [`SoaTree::magnitude`] run
+/// over contiguous `f32` values, and [`SoaValueAccessor::sample_box`] interpolates all corners
+/// of a component at once. The topology and tiles are those of a regular [`Tree`] and can't be
+/// changed, convert with [`SoaTree::to_tree`] to edit them. Conversion happens transparently
+/// when reading with [`VdbReader::read_soa_tree`] and when (de)serializing with serde, which
+/// use the interleaved layout of [`Tree`].
+///
+/// [`VdbReader::read_soa_tree`]: crate::VdbReader::read_soa_tree
+#[derive(Debug, Clone)]
+pub struct SoaTree<const L5: u32 = 5, const L4: u32 = 4, const L3: u32 = 3> {
+    /// Topology and tiles of the tree, with empty leaf buffers
+    topology: Tree<Vec3, L5, L4, L3>,
+    leaves: HashMap<IVec3, SoaLeaf<L3>>,
+}
+
+impl<const L5: u32, const L4: u32, const L3: u32> SoaTree<L5, L4, L3> {
+    /// Splits the leaves of `tree` into planes, sharing nothing with it.
+    pub fn from_tree(tree: &Tree<Vec3, L5, L4, L3>) -> Self {
+        Self::from_owned_tree(tree.clone())
+    }
+
+    /// Variant of [`SoaTree::from_tree`] that reuses the nodes of `tree`.
+    pub(crate) fn from_owned_tree(mut topology: Tree<Vec3, L5, L4, L3>) -> Self {
+        let mut leaves = HashMap::with_capacity(topology.leaf_count());
+        for node_5 in &mut topology.root_nodes {
+            for node_4 in node_5.nodes.values_mut() {
+                for node_3 in node_4.nodes.values_mut() {
+                    leaves.insert(node_3.origin, SoaLeaf::from_leaf(node_3));
+                    *node_3 = Arc::new(Node3 {
+                        buffer: vec![],
+                        value_mask: node_3.value_mask.clone(),
+                        origin: node_3.origin,
+                    });
+                }
+            }
+        }
+        Self { topology, leaves }
+    }
+
+    /// Interleaves the leaves back into a regular [`Tree`].
+    pub fn to_tree(&self) -> Tree<Vec3, L5, L4, L3> {
+        let mut tree = self.topology.clone();
+        for node_5 in &mut tree.root_nodes {
+            for node_4 in node_5.nodes.values_mut() {
+                for node_3 in node_4.nodes.values_mut() {
+                    *node_3 = Arc::new(self.leaves[&node_3.origin].to_leaf());
+                }
+            }
+        }
+        tree
+    }
+
+    pub fn background(&self) -> Vec3 {
+        self.topology.background
+    }
+
+    /// Number of leaf nodes in the tree.
+    pub fn leaf_count(&self) -> usize {
+        self.leaves.len()
+    }
+
+    /// All leaf nodes of the tree, in no particular order.
+    pub fn leaves(&self) -> impl Iterator<Item = &SoaLeaf<L3>> {
+        self.leaves.values()
+    }
+
+    /// Mutable variant of [`SoaTree::leaves`], to change leaf values in place.
+    pub fn leaves_mut(&mut self) -> impl Iterator<Item = &mut SoaLeaf<L3>> {
+        self.leaves.values_mut()
+    }
+
+    /// Leaf containing `coord`, if any.
+    pub fn probe_leaf(&self, coord: IVec3) -> Option<&SoaLeaf<L3>> {
+        self.leaves.get(&(coord & !((1 << L3) - 1)))
+    }
+
+    /// Mutable variant of [`SoaTree::probe_leaf`].
+    pub fn probe_leaf_mut(&mut self, coord: IVec3) -> Option<&mut SoaLeaf<L3>> {
+        self.leaves.get_mut(&(coord & !((1 << L3) - 1)))
+    }
+
+    /// Value and active state of the voxel at `coord`, see [`Tree::probe_value`].
+    pub fn probe_value(&self, coord: IVec3) -> (Vec3, bool) {
+        match self.probe_leaf(coord) {
+            Some(leaf) => {
+                let offset = leaf_offset::<L3>(coord);
+                (leaf.value(offset), leaf.value_mask[offset])
+            }
+            None => self.topology.probe_value(coord),
+        }
+    }
+
+    /// Value of the voxel at `coord`, see [`SoaTree::probe_value`].
+    pub fn get_value(&self, coord: IVec3) -> Vec3 {
+        self.probe_value(coord).0
+    }
+
+    /// Active state of the voxel at `coord`, see [`SoaTree::probe_value`].
+    pub fn is_value_on(&self, coord: IVec3) -> bool {
+        self.probe_value(coord).1
+    }
+
+    /// Number of active voxels in the tree, see [`Tree::active_voxel_count`].
+    pub fn active_voxel_count(&self) -> u64 {
+        self.topology.active_voxel_count()
+    }
+
+    /// Replaces the `axis` component of every value, including tiles and the background, with
+    /// `f(component)`.
+    pub fn map_component(&mut self, axis: usize, mut f: impl FnMut(f32) -> f32) {
+        let topology = &mut self.topology;
+        topology.background[axis] = f(topology.background[axis]);
+        for node_5 in &mut topology.root_nodes {
+            for tile in &mut node_5.data {
+                tile[axis] = f(tile[axis]);
+            }
+            for node_4 in node_5.nodes.values_mut() {
+                for tile in &mut node_4.data {
+                    tile[axis] = f(tile[axis]);
+                }
+            }
+        }
+        for leaf in self.leaves.values_mut() {
+            for value in &mut leaf.planes[axis] {
+                *value = f(*value);
+            }
+        }
+    }
+
+    /// Multiplies every value component-wise by `factor`.
+    pub fn scale(&mut self, factor: Vec3) {
+        for axis in 0..3 {
+            self.map_component(axis, |value| value * factor[axis]);
+        }
+    }
+
+    /// Scalar tree with the same topology holding the `axis` component of every value, see
+    /// [`Tree::split`].
+    pub fn component(&self, axis: usize) -> Tree<f32, L5, L4, L3> {
+        self.scalar_tree(|v| v[axis], |leaf| leaf.planes[axis].clone())
+    }
+
+    /// Scalar tree with the same topology holding the length of every value.
+    pub fn magnitude(&self) -> Tree<f32, L5, L4, L3> {
+        self.scalar_tree(Vec3::length, |leaf| {
+            let [x, y, z] = &leaf.planes;
+            x.iter()
+                .zip(y)
+                .zip(z)
+                .map(|((x, y), z)| (x * x + y * y + z * z).sqrt())
+                .collect()
+        })
+    }
+
+    /// Scalar tree with the same topology, with `tile` applied to the tiles and background and
+    /// the leaf buffers computed by `leaf`.
+    fn scalar_tree(
+        &self,
+        tile: impl FnMut(Vec3) -> f32,
+        mut leaf: impl FnMut(&SoaLeaf<L3>) -> Vec<f32>,
+    ) -> Tree<f32, L5, L4, L3> {
+        let mut tree = self.topology.map_values(tile);
+        for node_5 in &mut tree.root_nodes {
+            for node_4 in node_5.nodes.values_mut() {
+                for node_3 in node_4.nodes.values_mut() {
+                    Arc::make_mut(node_3).buffer = leaf(&self.leaves[&node_3.origin]);
+                }
+            }
+        }
+        tree
+    }
+
+    /// Creates a [`SoaValueAccessor`] for fast repeated lookups into this tree.
+    pub fn accessor(&self) -> SoaValueAccessor<'_, L5, L4, L3> {
+        SoaValueAccessor {
+            tree: self,
+            leaf: None,
+        }
+    }
+}
+
+impl<const L5: u32, const L4: u32, const L3: u32> From<&Tree<Vec3, L5, L4, L3>>
+    for SoaTree<L5, L4, L3>
+{
+    fn from(tree: &Tree<Vec3, L5, L4, L3>) -> Self {
+        Self::from_tree(tree)
+    }
+}
+
+impl<const L5: u32, const L4: u32, const L3: u32> From<Tree<Vec3, L5, L4, L3>>
+    for SoaTree<L5, L4, L3>
+{
+    fn from(tree: Tree<Vec3, L5, L4, L3>) -> Self {
+        Self::from_owned_tree(tree)
+    }
+}
+
+impl<const L5: u32, const L4: u32, const L3: u32> From<&SoaTree<L5, L4, L3>>
+    for Tree<Vec3, L5, L4, L3>
+{
+    fn from(tree: &SoaTree<L5, L4, L3>) -> Self {
+        tree.to_tree()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<const L5: u32, const L4: u32, const L3: u32> serde::Serialize for SoaTree<L5, L4, L3> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_tree().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, const L5: u32, const L4: u32, const L3: u32> serde::Deserialize<'de>
+    for SoaTree<L5, L4, L3>
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Tree::deserialize(deserializer).map(Self::from_owned_tree)
+    }
+}
+
+/// Read-only accessor into a [`SoaTree`] that caches the most recently visited leaf, see
+/// [`ValueAccessor`](crate::ValueAccessor).
+pub struct SoaValueAccessor<'a, const L5: u32 = 5, const L4: u32 = 4, const L3: u32 = 3> {
+    tree: &'a SoaTree<L5, L4, L3>,
+    leaf: Option<&'a SoaLeaf<L3>>,
+}
+
+impl<'a, const L5: u32, const L4: u32, const L3: u32> SoaValueAccessor<'a, L5, L4, L3> {
+    /// The tree this accessor reads from.
+    pub fn tree(&self) -> &'a SoaTree<L5, L4, L3> {
+        self.tree
+    }
+
+    /// Leaf containing `coord`, from the cache if possible.
+    fn leaf(&mut self, coord: IVec3) -> Option<&'a SoaLeaf<L3>> {
+        if let Some(leaf) = self.leaf {
+            if coord >> L3 == leaf.origin >> L3 {
+                return Some(leaf);
+            }
+        }
+        let leaf = self.tree.probe_leaf(coord);
+        if leaf.is_some() {
+            self.leaf = leaf;
+        }
+        leaf
+    }
+
+    /// Value and active state of the voxel at `coord`, see [`Tree::probe_value`].
+    pub fn probe_value(&mut self, coord: IVec3) -> (Vec3, bool) {
+        match self.leaf(coord) {
+            Some(leaf) => {
+                let offset = leaf_offset::<L3>(coord);
+                (leaf.value(offset), leaf.value_mask[offset])
+            }
+            None => self.tree.topology.probe_value(coord),
+        }
+    }
+
+    /// Value of the voxel at `coord`.
+    pub fn get_value(&mut self, coord: IVec3) -> Vec3 {
+        self.probe_value(coord).0
+    }
+
+    /// Trilinear interpolation at the fractional index space position `index`, with the same
+    /// result as [`BoxSampler`](crate::BoxSampler) on the interleaved tree.
+    ///
+    /// When the 8 surrounding voxels lie in the same leaf, the 4 interpolations along `z` of
+    /// every component are done at once on its plane.
+    pub fn sample_box(&mut self, index: DVec3) -> Vec3 {
+        let base = index.floor();
+        let t = (index - base).as_vec3();
+        let base = base.as_ivec3();
+        let lerp = |a: Vec3, b: Vec3, t: f32| a * (1.0 - t) + b * t;
+
+        let local = base & ((1 << L3) - 1);
+        let corners = match self.leaf(base) {
+            Some(leaf) if local.cmplt(IVec3::splat((1 << L3) - 1)).all() => {
+                // Offsets of the (x, y) corners at the lower z, the upper z is one further
+                let offset = leaf_offset::<L3>(base);
+                let lower = [0, 1 << L3, 1 << (2 * L3), (1 << (2 * L3)) + (1 << L3)]
+                    .map(|corner| offset + corner);
+                let [x, y, z] = leaf.planes.each_ref().map(|plane| {
+                    let low = Vec4::from_array(lower.map(|i| plane[i]));
+                    let high = Vec4::from_array(lower.map(|i| plane[i + 1]));
+                    low * (1.0 - t.z) + high * t.z
+                });
+                [0, 1, 2, 3].map(|corner| Vec3::new(x[corner], y[corner], z[corner]))
+            }
+            _ => [(0, 0), (0, 1), (1, 0), (1, 1)].map(|(x, y)| {
+                let low = self.get_value(base + IVec3::new(x, y, 0));
+                let high = self.get_value(base + IVec3::new(x, y, 1));
+                lerp(low, high, t.z)
+            }),
+        };
+        let c0 = lerp(corners[0], corners[1], t.y);
+        let c1 = lerp(corners[2], corners[3], t.y);
+        lerp(c0, c1, t.x)
+    }
+}
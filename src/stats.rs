@@ -0,0 +1,208 @@
+use crate::data_structure::{active_tiles, Grid, Node, Node4, Node5, Tree};
+use crate::par_slice::par_fold;
+
+/// Statistics over the active values of a grid, where active tiles count for every voxel they
+/// cover. Non-finite values are counted separately and don't contribute to the other fields.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Statistics {
+    /// Number of finite active values
+    pub count: u64,
+    /// Number of active NaN or infinite values
+    pub non_finite_count: u64,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    /// Sum of squared differences from the mean
+    m2: f64,
+}
+
+impl Default for Statistics {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            non_finite_count: 0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            mean: 0.0,
+            m2: 0.0,
+        }
+    }
+}
+
+impl Statistics {
+    /// Adds `value` to the statistics as if it occurred `weight` times.
+    pub fn add(&mut self, value: f64, weight: u64) {
+        if !value.is_finite() {
+            self.non_finite_count += weight;
+            return;
+        }
+        self.merge(&Statistics {
+            count: weight,
+            non_finite_count: 0,
+            min: value,
+            max: value,
+            mean: value,
+            m2: 0.0,
+        });
+    }
+
+    /// Combines the statistics of two disjoint sets of values.
+    pub fn merge(&mut self, other: &Statistics) {
+        self.non_finite_count += other.non_finite_count;
+        if other.count == 0 {
+            return;
+        }
+        let count = self.count + other.count;
+        let delta = other.mean - self.mean;
+        self.mean += delta * other.count as f64 / count as f64;
+        self.m2 += other.m2 + delta * delta * self.count as f64 * other.count as f64 / count as f64;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.count = count;
+    }
+
+    /// Population variance of the values, zero if there are none.
+    pub fn variance(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.m2 / self.count as f64
+        }
+    }
+
+    pub fn std_dev(&self) -> f64 {
+        self.variance().sqrt()
+    }
+}
+
+/// Number of active values per bin, for bins of equal width between `min` and `max`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    pub min: f64,
+    pub max: f64,
+    pub counts: Vec<u64>,
+    /// Number of values outside `[min, max]`, including non-finite values
+    pub outside_count: u64,
+}
+
+impl Histogram {
+    pub fn new(min: f64, max: f64, bin_count: usize) -> Self {
+        Self {
+            min,
+            max,
+            counts: vec![0; bin_count],
+            outside_count: 0,
+        }
+    }
+
+    pub fn bin_width(&self) -> f64 {
+        (self.max - self.min) / self.counts.len() as f64
+    }
+
+    /// Adds `value` to its bin as if it occurred `weight` times, `max` itself falls in the last
+    /// bin.
+    pub fn add(&mut self, value: f64, weight: u64) {
+        if !(self.min..=self.max).contains(&value) || self.counts.is_empty() {
+            self.outside_count += weight;
+            return;
+        }
+        let bin = (((value - self.min) / self.bin_width()) as usize).min(self.counts.len() - 1);
+        self.counts[bin] += weight;
+    }
+
+    /// Adds the counts of a histogram with the same bins.
+    pub fn merge(&mut self, other: &Histogram) {
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+        self.outside_count += other.outside_count;
+    }
+}
+
+/// Folds `f` over all active values of `tree` as `(value, weight)` pairs, processing the leaves
+/// in parallel when the `rayon` feature is enabled and combining the results with `merge`.
+fn fold_active_values<ValueTy, T, const L5: u32, const L4: u32, const L3: u32>(
+    tree: &Tree<ValueTy, L5, L4, L3>,
+    init: impl Fn() -> T + Sync,
+    f: impl Fn(&mut T, f64, u64) + Sync,
+    merge: impl Fn(&mut T, &T) + Sync,
+) -> T
+where
+    ValueTy: Copy + Into<f64> + Sync,
+    T: Send,
+{
+    let mut result = init();
+    for node_5 in &tree.root_nodes {
+        for idx in active_tiles(&node_5.child_mask, &node_5.value_mask) {
+            f(
+                &mut result,
+                node_5.data[idx].into(),
+                Node5::<ValueTy, L5, L4, L3>::TILE_VOXEL_COUNT,
+            );
+        }
+        for node_4 in node_5.nodes.values() {
+            for idx in active_tiles(&node_4.child_mask, &node_4.value_mask) {
+                f(
+                    &mut result,
+                    node_4.data[idx].into(),
+                    Node4::<ValueTy, L4, L3>::TILE_VOXEL_COUNT,
+                );
+            }
+        }
+    }
+
+    let leaves = tree.leaves().collect::<Vec<_>>();
+    let partial = par_fold(
+        &leaves,
+        &init,
+        |partial, node_3| {
+            for idx in node_3.value_mask.iter_ones() {
+                f(partial, node_3.buffer[idx].into(), 1);
+            }
+        },
+        &merge,
+    );
+    merge(&mut result, &partial);
+    result
+}
+
+impl<ValueTy, const L5: u32, const L4: u32, const L3: u32> Tree<ValueTy, L5, L4, L3>
+where
+    ValueTy: Copy + Into<f64> + Sync,
+{
+    /// Extrema, mean and variance of all active values.
+    pub fn statistics(&self) -> Statistics {
+        fold_active_values(
+            self,
+            Statistics::default,
+            |stats, value, weight| stats.add(value, weight),
+            |stats, other| stats.merge(other),
+        )
+    }
+
+    /// Histogram of all active values with `bin_count` equally sized bins between `min` and
+    /// `max`, use [`Tree::statistics`] to find the range of the values.
+    pub fn histogram(&self, min: f64, max: f64, bin_count: usize) -> Histogram {
+        fold_active_values(
+            self,
+            || Histogram::new(min, max, bin_count),
+            |histogram, value, weight| histogram.add(value, weight),
+            |histogram, other| histogram.merge(other),
+        )
+    }
+}
+
+impl<ValueTy, const L5: u32, const L4: u32, const L3: u32> Grid<ValueTy, L5, L4, L3>
+where
+    ValueTy: Copy + Into<f64> + Sync,
+{
+    /// Statistics over the active values of this grid, see [`Tree::statistics`].
+    pub fn statistics(&self) -> Statistics {
+        self.tree.statistics()
+    }
+
+    /// Histogram of the active values of this grid, see [`Tree::histogram`].
+    pub fn histogram(&self, min: f64, max: f64, bin_count: usize) -> Histogram {
+        self.tree.histogram(min, max, bin_count)
+    }
+}
@@ -0,0 +1,145 @@
+use crate::asset_loader::{VdbAsset, VdbPlugin};
+use crate::data_structure::Grid;
+use crate::mesh_io::{mesh_normals, read_ply};
+use crate::volume_to_mesh::volume_to_mesh;
+
+use bevy::asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::render::mesh::Indices;
+use bevy::render::render_resource::PrimitiveTopology;
+use std::collections::HashSet;
+
+/// Extracts the `isovalue` surface of `grid`, usually zero for a level set, as a Bevy mesh with
+/// world space positions, vertex normals and indices, see [`volume_to_mesh`] and
+/// [`mesh_normals`]. Triangles are wound counter-clockwise when seen from the outside, Bevy's
+/// front face.
+pub fn sdf_to_bevy_mesh<const L5: u32, const L4: u32, const L3: u32>(
+    grid: &Grid<f32, L5, L4, L3>,
+    isovalue: f32,
+) -> Mesh {
+    let (positions, triangles) = volume_to_mesh(grid, isovalue);
+    let normals = mesh_normals(&positions, &triangles, &[]);
+    triangle_mesh(&positions, &normals, &triangles)
+}
+
+fn triangle_mesh(positions: &[Vec3], normals: &[Vec3], triangles: &[[u32; 3]]) -> Mesh {
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        positions.iter().map(Vec3::to_array).collect::<Vec<_>>(),
+    );
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_NORMAL,
+        normals.iter().map(Vec3::to_array).collect::<Vec<_>>(),
+    );
+    mesh.set_indices(Some(Indices::U32(triangles.concat())));
+    mesh
+}
+
+/// Loads `.ply` meshes, such as the surfaces baked by [`crate::bake_vdb`], as Bevy meshes so
+/// level sets don't have to be meshed at startup. Only binary little endian files are
+/// supported, see [`read_ply`], and normals are computed for meshes without them.
+#[derive(Default)]
+pub struct PlyMeshLoader;
+
+impl AssetLoader for PlyMeshLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let (positions, mut normals, triangles) = read_ply(bytes).map_err(|error| {
+                bevy::asset::Error::new(error)
+                    .context(format!("Failed to load {}", load_context.path().display()))
+            })?;
+            if normals.is_empty() {
+                normals = mesh_normals(&positions, &triangles, &[]);
+            }
+            let mesh = triangle_mesh(&positions, &normals, &triangles);
+            load_context.set_default_asset(LoadedAsset::new(mesh));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ply"]
+    }
+}
+
+/// Gives the entity a mesh of the `isovalue` surface of a `float` grid of a [`VdbAsset`], which
+/// [`VdbSurfacePlugin`] regenerates whenever the asset is loaded or changes, e.g. when it is hot
+/// reloaded. Add a `PbrBundle` with a material to render it.
+#[derive(Component, Clone, Debug)]
+pub struct VdbSurface {
+    pub asset: Handle<VdbAsset>,
+    pub grid: String,
+    pub isovalue: f32,
+}
+
+impl VdbSurface {
+    /// The zero isosurface of the level set `grid` of `asset`.
+    pub fn new(asset: Handle<VdbAsset>, grid: impl Into<String>) -> Self {
+        Self {
+            asset,
+            grid: grid.into(),
+            isovalue: 0.0,
+        }
+    }
+
+    pub fn with_isovalue(mut self, isovalue: f32) -> Self {
+        self.isovalue = isovalue;
+        self
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn update_surfaces(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<VdbAsset>>,
+    surfaces: Query<(Entity, Ref<VdbSurface>, Option<&Handle<Mesh>>)>,
+    assets: Res<Assets<VdbAsset>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let changed_assets = events
+        .iter()
+        .filter_map(|event| match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => Some(handle),
+            AssetEvent::Removed { .. } => None,
+        })
+        .collect::<HashSet<_>>();
+    for (entity, surface, mesh_handle) in &surfaces {
+        if !surface.is_changed() && !changed_assets.contains(&surface.asset) {
+            continue;
+        }
+        let Some(asset) = assets.get(&surface.asset) else {
+            // Not loaded yet, the mesh is made once it is
+            continue;
+        };
+        let Some(grid) = asset.float_grid(&surface.grid) else {
+            log::warn!("No float grid named {} to mesh", surface.grid);
+            continue;
+        };
+        let mesh = sdf_to_bevy_mesh(grid, surface.isovalue);
+        match mesh_handle.and_then(|handle| meshes.get_mut(handle)) {
+            Some(existing) => *existing = mesh,
+            None => {
+                commands.entity(entity).insert(meshes.add(mesh));
+            }
+        }
+    }
+}
+
+/// Keeps the meshes of entities with a [`VdbSurface`] in sync with their grids, and registers
+/// [`PlyMeshLoader`] for baked surfaces.
+pub struct VdbSurfacePlugin;
+
+impl Plugin for VdbSurfacePlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<VdbPlugin>() {
+            app.add_plugins(VdbPlugin);
+        }
+        app.init_asset_loader::<PlyMeshLoader>()
+            .add_systems(Update, update_surfaces);
+    }
+}
@@ -0,0 +1,150 @@
+use crate::coordinates::CoordBBox;
+use crate::data_structure::{Grid, Node, Node3, Node4, Node5};
+use crate::visitor::NodeVisitor;
+
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::render::texture::Image;
+use glam::{IVec3, UVec3, Vec3};
+use half::f16;
+
+/// Texel format of the textures made by [`grid_to_image_3d`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VolumeTextureFormat {
+    /// Single channel half precision floats, half the memory of [`VolumeTextureFormat::R32Float`]
+    #[default]
+    R16Float,
+    R32Float,
+    /// Four channel half precision floats, for vector grids
+    Rgba16Float,
+}
+
+impl VolumeTextureFormat {
+    pub fn texture_format(self) -> TextureFormat {
+        match self {
+            VolumeTextureFormat::R16Float => TextureFormat::R16Float,
+            VolumeTextureFormat::R32Float => TextureFormat::R32Float,
+            VolumeTextureFormat::Rgba16Float => TextureFormat::Rgba16Float,
+        }
+    }
+}
+
+/// Values that can be stored in the textures made by [`grid_to_image_3d`].
+pub trait TextureValue: Copy {
+    /// Channels of the texel holding this value, single channel formats only keep red. Like
+    /// GPUs expand single channel texels when sampling, scalars become `(value, 0, 0, 1)`.
+    fn to_rgba(self) -> [f32; 4];
+}
+
+impl TextureValue for f32 {
+    fn to_rgba(self) -> [f32; 4] {
+        [self, 0.0, 0.0, 1.0]
+    }
+}
+
+impl TextureValue for Vec3 {
+    fn to_rgba(self) -> [f32; 4] {
+        [self.x, self.y, self.z, 1.0]
+    }
+}
+
+/// Copies the values of a tree into a dense box stored with `x` varying fastest, the layout of
+/// 3D textures. Tiles are filled a row at a time and nodes outside the box are skipped.
+struct TextureFiller<ValueTy> {
+    bbox: CoordBBox,
+    dims: UVec3,
+    values: Vec<ValueTy>,
+}
+
+impl<ValueTy: Copy> TextureFiller<ValueTy> {
+    fn index(&self, coord: IVec3) -> usize {
+        let local = (coord - self.bbox.min).as_uvec3();
+        (local.x + self.dims.x * (local.y + self.dims.y * local.z)) as usize
+    }
+
+    fn overlaps(&self, origin: IVec3, voxel_dim: u32) -> bool {
+        let node_bbox = CoordBBox::new(origin, origin + IVec3::splat(voxel_dim as i32 - 1));
+        node_bbox.has_overlap(&self.bbox)
+    }
+}
+
+impl<ValueTy: Copy, const L5: u32, const L4: u32, const L3: u32> NodeVisitor<ValueTy, L5, L4, L3>
+    for TextureFiller<ValueTy>
+{
+    fn visit_node_5(&mut self, node: &Node5<ValueTy, L5, L4, L3>) -> bool {
+        self.overlaps(node.origin, Node5::<ValueTy, L5, L4, L3>::VOXEL_DIM)
+    }
+
+    fn visit_node_4(&mut self, node: &Node4<ValueTy, L4, L3>) -> bool {
+        self.overlaps(node.origin, Node4::<ValueTy, L4, L3>::VOXEL_DIM)
+    }
+
+    fn visit_node_3(&mut self, node: &Node3<ValueTy, L3>) -> bool {
+        self.overlaps(node.origin, Node3::<ValueTy, L3>::VOXEL_DIM)
+    }
+
+    fn visit_tile(&mut self, bbox: CoordBBox, value: ValueTy, _active: bool) {
+        let bbox = bbox.intersection(&self.bbox);
+        if bbox.is_empty() {
+            return;
+        }
+        for z in bbox.min.z..=bbox.max.z {
+            for y in bbox.min.y..=bbox.max.y {
+                let start = self.index(IVec3::new(bbox.min.x, y, z));
+                let end = self.index(IVec3::new(bbox.max.x, y, z));
+                self.values[start..=end].fill(value);
+            }
+        }
+    }
+
+    fn visit_voxel(&mut self, coord: IVec3, value: ValueTy, _active: bool) {
+        if self.bbox.is_inside(coord) {
+            let idx = self.index(coord);
+            self.values[idx] = value;
+        }
+    }
+}
+
+/// 3D texture holding the values of `grid` inside the index space `bbox`, so grids can be
+/// sampled in shaders, e.g. as a `texture_3d<f32>` in WGSL. Texel `(x, y, z)` of the texture
+/// holds the voxel at `bbox.min + (x, y, z)`, and voxels not stored in the tree take their tile
+/// or background value. An empty `bbox` yields a single texel holding the background.
+///
+/// Tiles are written a row at a time and nodes outside `bbox` are skipped, so a bounding box
+/// of a sparse or mostly constant grid converts quickly.
+pub fn grid_to_image_3d<ValueTy: TextureValue, const L5: u32, const L4: u32, const L3: u32>(
+    grid: &Grid<ValueTy, L5, L4, L3>,
+    bbox: CoordBBox,
+    format: VolumeTextureFormat,
+) -> Image {
+    let mut filler = TextureFiller {
+        bbox,
+        dims: bbox.dim().max(UVec3::ONE),
+        values: vec![grid.tree.background; bbox.volume().max(1) as usize],
+    };
+    if !bbox.is_empty() {
+        grid.tree.visit(&mut filler);
+    }
+    let dims = filler.dims;
+
+    let texels = filler.values.into_iter().map(TextureValue::to_rgba);
+    let data = match format {
+        VolumeTextureFormat::R16Float => texels
+            .flat_map(|[red, ..]| f16::from_f32(red).to_le_bytes())
+            .collect(),
+        VolumeTextureFormat::R32Float => texels.flat_map(|[red, ..]| red.to_le_bytes()).collect(),
+        VolumeTextureFormat::Rgba16Float => texels
+            .flat_map(|rgba| rgba.map(|channel| f16::from_f32(channel).to_le_bytes()))
+            .flatten()
+            .collect(),
+    };
+    Image::new(
+        Extent3d {
+            width: dims.x,
+            height: dims.y,
+            depth_or_array_layers: dims.z,
+        },
+        TextureDimension::D3,
+        data,
+        format.texture_format(),
+    )
+}
@@ -1,4 +1,7 @@
-#[derive(Debug)]
+use glam::{DMat4, DVec3, Vec3};
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Map {
     UniformScaleMap {
         scale_values: glam::DVec3,
@@ -15,4 +18,168 @@ pub enum Map {
         inv_scale_sqr: glam::DVec3,
         inv_twice_scale: glam::DVec3,
     },
+    /// General affine map, `matrix` transforms index space column vectors to world space.
+    AffineMap { matrix: glam::DMat4 },
+}
+
+impl Map {
+    /// Map with cubic voxels of edge length `voxel_size` and no translation.
+    pub fn uniform_scale(voxel_size: f64) -> Self {
+        let scale = DVec3::splat(voxel_size);
+        Map::UniformScaleMap {
+            scale_values: scale,
+            voxel_size: scale,
+            scale_values_inverse: scale.recip(),
+            inv_scale_sqr: (scale * scale).recip(),
+            inv_twice_scale: (2.0 * scale).recip(),
+        }
+    }
+
+    /// Map that scales index space by `scale` and then translates it by `translation`.
+    pub fn scale_translate(scale: DVec3, translation: DVec3) -> Self {
+        Map::ScaleTranslateMap {
+            translation,
+            scale_values: scale,
+            voxel_size: scale,
+            scale_values_inverse: scale.recip(),
+            inv_scale_sqr: (scale * scale).recip(),
+            inv_twice_scale: (2.0 * scale).recip(),
+        }
+    }
+
+    /// Scale along each index space axis, for an affine map this is the length of the
+    /// transformed axes.
+    pub fn scale(&self) -> DVec3 {
+        match self {
+            Map::UniformScaleMap { scale_values, .. }
+            | Map::ScaleTranslateMap { scale_values, .. } => *scale_values,
+            Map::AffineMap { matrix } => DVec3::new(
+                matrix.x_axis.truncate().length(),
+                matrix.y_axis.truncate().length(),
+                matrix.z_axis.truncate().length(),
+            ),
+        }
+    }
+
+    pub fn translation(&self) -> DVec3 {
+        match self {
+            Map::UniformScaleMap { .. } => DVec3::ZERO,
+            Map::ScaleTranslateMap { translation, .. } => *translation,
+            Map::AffineMap { matrix } => matrix.w_axis.truncate(),
+        }
+    }
+
+    /// Matrix transforming index space column vectors to world space.
+    pub fn to_matrix(&self) -> DMat4 {
+        match self {
+            Map::AffineMap { matrix } => *matrix,
+            _ => DMat4::from_translation(self.translation()) * DMat4::from_scale(self.scale()),
+        }
+    }
+}
+
+/// Mapping between index space, where voxels live at integer coordinates, and world space.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transform {
+    pub map: Map,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::from_voxel_size(1.0)
+    }
+}
+
+impl Transform {
+    pub fn new(map: Map) -> Self {
+        Self { map }
+    }
+
+    /// Transform with cubic voxels of edge length `voxel_size` and no translation.
+    pub fn from_voxel_size(voxel_size: f64) -> Self {
+        Self::new(Map::uniform_scale(voxel_size))
+    }
+
+    /// Transform that scales index space by `scale` and then translates it by `translation`.
+    pub fn from_scale_translation(scale: DVec3, translation: DVec3) -> Self {
+        Self::new(Map::scale_translate(scale, translation))
+    }
+
+    /// Size of a voxel along each world space axis.
+    pub fn voxel_size(&self) -> DVec3 {
+        self.map.scale().abs()
+    }
+
+    pub fn translation(&self) -> DVec3 {
+        self.map.translation()
+    }
+
+    /// Transform with an arbitrary affine index to world space `matrix`.
+    pub fn from_matrix(matrix: DMat4) -> Self {
+        Self::new(Map::AffineMap { matrix })
+    }
+
+    /// Transform with the index to world space `matrix`, using the simplest map that represents
+    /// it, for matrices read from other formats.
+    pub(crate) fn from_matrix_simplified(matrix: DMat4) -> Self {
+        let scale = DVec3::new(matrix.x_axis.x, matrix.y_axis.y, matrix.z_axis.z);
+        let translation = matrix.w_axis.truncate();
+        if matrix != DMat4::from_translation(translation) * DMat4::from_scale(scale) {
+            Self::from_matrix(matrix)
+        } else if scale == DVec3::splat(scale.x) && translation == DVec3::ZERO {
+            Self::from_voxel_size(scale.x)
+        } else {
+            Self::from_scale_translation(scale, translation)
+        }
+    }
+
+    pub fn index_to_world_f64(&self, index: DVec3) -> DVec3 {
+        match &self.map {
+            Map::AffineMap { matrix } => matrix.transform_point3(index),
+            map => index * map.scale() + map.translation(),
+        }
+    }
+
+    pub fn world_to_index_f64(&self, world: DVec3) -> DVec3 {
+        match &self.map {
+            Map::AffineMap { matrix } => matrix.inverse().transform_point3(world),
+            map => (world - map.translation()) / map.scale(),
+        }
+    }
+
+    pub fn index_to_world(&self, index: Vec3) -> Vec3 {
+        self.index_to_world_f64(index.as_dvec3()).as_vec3()
+    }
+
+    pub fn world_to_index(&self, world: Vec3) -> Vec3 {
+        self.world_to_index_f64(world.as_dvec3()).as_vec3()
+    }
+
+    /// Transform that applies `self` followed by `other`, treating the world space of `self` as
+    /// the index space of `other`.
+    pub fn then(&self, other: &Transform) -> Transform {
+        let scale = self.map.scale() * other.map.scale();
+        let translation = self.map.translation() * other.map.scale() + other.map.translation();
+        match (&self.map, &other.map) {
+            (Map::AffineMap { .. }, _) | (_, Map::AffineMap { .. }) => {
+                Self::from_matrix(other.map.to_matrix() * self.map.to_matrix())
+            }
+            (Map::UniformScaleMap { .. }, Map::UniformScaleMap { .. }) => {
+                Self::from_voxel_size(scale.x)
+            }
+            _ => Self::from_scale_translation(scale, translation),
+        }
+    }
+
+    /// Transform mapping the world space of this transform back to its index space.
+    pub fn inverse(&self) -> Transform {
+        match &self.map {
+            Map::AffineMap { matrix } => Self::from_matrix(matrix.inverse()),
+            map => {
+                let scale = map.scale().recip();
+                Self::from_scale_translation(scale, -map.translation() * scale)
+            }
+        }
+    }
 }
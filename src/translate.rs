@@ -0,0 +1,112 @@
+use crate::coordinates::{CoordBBox, Index};
+use crate::data_structure::{Grid, Node, Node4, Tree, VdbLevel};
+use crate::transform::Transform;
+use crate::visitor::NodeVisitor;
+
+use glam::{IVec3, Mat4};
+use std::sync::Arc;
+
+/// Copies every non-background value into `tree` at an offset, used for translations that
+/// aren't aligned to the leaf nodes.
+struct Translator<'a, ValueTy, const L5: u32, const L4: u32, const L3: u32> {
+    tree: &'a mut Tree<ValueTy, L5, L4, L3>,
+    offset: IVec3,
+}
+
+impl<ValueTy, const L5: u32, const L4: u32, const L3: u32> NodeVisitor<ValueTy, L5, L4, L3>
+    for Translator<'_, ValueTy, L5, L4, L3>
+where
+    ValueTy: Copy + PartialEq,
+{
+    fn visit_tile(&mut self, bbox: CoordBBox, value: ValueTy, active: bool) {
+        if active || value != self.tree.background {
+            for coord in bbox {
+                self.tree.set_value(coord + self.offset, value, active);
+            }
+        }
+    }
+
+    fn visit_voxel(&mut self, coord: IVec3, value: ValueTy, active: bool) {
+        if active || value != self.tree.background {
+            self.tree.set_value(coord + self.offset, value, active);
+        }
+    }
+}
+
+impl<ValueTy, const L5: u32, const L4: u32, const L3: u32> Tree<ValueTy, L5, L4, L3>
+where
+    ValueTy: Copy + PartialEq,
+{
+    /// Moves all voxels and tiles of the tree by `offset` in index space.
+    ///
+    /// When `offset` is a multiple of the leaf size the leaf nodes are moved over without copying
+    /// their buffers, other offsets fall back to copying every stored value.
+    pub fn translate(&mut self, offset: IVec3) {
+        if offset == IVec3::ZERO {
+            return;
+        }
+        let old = std::mem::replace(self, Tree::new(self.background));
+
+        if (offset % (1 << L3)) != IVec3::ZERO {
+            old.visit(&mut Translator { tree: self, offset });
+            return;
+        }
+
+        let tile_4_dim = Node4::<ValueTy, L4, L3>::VOXEL_DIM as i32;
+        let tiles_aligned = (offset % tile_4_dim) == IVec3::ZERO;
+        for mut node_5 in old.root_nodes {
+            for idx in 0..node_5.data.len() {
+                let (value, active) = (node_5.data[idx], node_5.value_mask[idx]);
+                if let Some(node_4) = node_5.nodes.remove(&(idx as u32)) {
+                    self.translate_node_4(node_4, offset);
+                } else if active || value != self.background {
+                    let bbox = node_5.tile_bbox(Index(idx as u32));
+                    if tiles_aligned {
+                        self.add_tile(VdbLevel::Node4, bbox.min + offset, value, active);
+                    } else {
+                        // The tile straddles several nodes at its new position, split it into
+                        // leaf sized tiles
+                        let leaf_tiles = CoordBBox::new(bbox.min >> L3, bbox.max >> L3);
+                        for leaf_tile in leaf_tiles {
+                            let coord = (leaf_tile << L3) + offset;
+                            self.add_tile(VdbLevel::Node3, coord, value, active);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn translate_node_4(&mut self, mut node_4: Node4<ValueTy, L4, L3>, offset: IVec3) {
+        for idx in 0..node_4.data.len() {
+            let (value, active) = (node_4.data[idx], node_4.value_mask[idx]);
+            if let Some(mut node_3) = node_4.nodes.remove(&(idx as u32)) {
+                Arc::make_mut(&mut node_3).origin += offset;
+                self.add_leaf(node_3);
+            } else if active || value != self.background {
+                let coord = node_4.offset_to_global_coord(Index(idx as u32)).0 + offset;
+                self.add_tile(VdbLevel::Node3, coord, value, active);
+            }
+        }
+    }
+}
+
+impl<ValueTy, const L5: u32, const L4: u32, const L3: u32> Grid<ValueTy, L5, L4, L3>
+where
+    ValueTy: Copy + PartialEq,
+{
+    /// Moves the contents of the grid by `offset` voxels, see [`Tree::translate`].
+    pub fn translate(&mut self, offset: IVec3) {
+        self.tree.translate(offset);
+    }
+}
+
+impl<ValueTy, const L5: u32, const L4: u32, const L3: u32> Grid<ValueTy, L5, L4, L3> {
+    /// Applies `matrix` to the world space of the grid, after its current transform. The voxels
+    /// themselves are left untouched.
+    pub fn apply_transform(&mut self, matrix: Mat4) {
+        self.transform = self
+            .transform
+            .then(&Transform::from_matrix(matrix.as_dmat4()));
+    }
+}
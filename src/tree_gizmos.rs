@@ -0,0 +1,170 @@
+use crate::asset_loader::{VdbAsset, VdbPlugin};
+use crate::coordinates::CoordBBox;
+use crate::data_structure::{Grid, Node, Node3, Node4, Node5};
+use crate::visitor::NodeVisitor;
+
+use bevy::prelude::*;
+
+/// Draws the tree of a `float` grid of a [`VdbAsset`] as wireframe boxes with Bevy's `Gizmos`:
+/// the bounds of every allocated upper, lower and leaf node, and the bounding box of the active
+/// voxels, each level in its own color. Levels without a color are not drawn.
+///
+/// Boxes are drawn in the world space of the grid, placed in the scene by the `GlobalTransform`
+/// of the entity if it has one, so they line up with a [`crate::VdbVolume`] or
+/// [`crate::VdbSurface`] on the same entity.
+#[derive(Component, Clone, Debug)]
+pub struct VdbTreeGizmos {
+    pub asset: Handle<VdbAsset>,
+    pub grid: String,
+    pub node_5_color: Option<Color>,
+    pub node_4_color: Option<Color>,
+    pub node_3_color: Option<Color>,
+    pub active_bbox_color: Option<Color>,
+}
+
+impl VdbTreeGizmos {
+    /// Draws upper nodes in red, lower nodes in yellow, leaves in green and the active voxel
+    /// bounding box in cyan.
+    pub fn new(asset: Handle<VdbAsset>, grid: impl Into<String>) -> Self {
+        Self {
+            asset,
+            grid: grid.into(),
+            node_5_color: Some(Color::RED),
+            node_4_color: Some(Color::YELLOW),
+            node_3_color: Some(Color::GREEN),
+            active_bbox_color: Some(Color::CYAN),
+        }
+    }
+
+    pub fn with_node_5_color(mut self, color: Option<Color>) -> Self {
+        self.node_5_color = color;
+        self
+    }
+
+    pub fn with_node_4_color(mut self, color: Option<Color>) -> Self {
+        self.node_4_color = color;
+        self
+    }
+
+    pub fn with_node_3_color(mut self, color: Option<Color>) -> Self {
+        self.node_3_color = color;
+        self
+    }
+
+    pub fn with_active_bbox_color(mut self, color: Option<Color>) -> Self {
+        self.active_bbox_color = color;
+        self
+    }
+}
+
+/// Collects the index space bounds of the nodes of the levels to draw, without descending
+/// below the lowest of them.
+struct NodeBoxes<'a> {
+    gizmos: &'a VdbTreeGizmos,
+    boxes: Vec<(CoordBBox, Color)>,
+}
+
+impl NodeBoxes<'_> {
+    fn push(&mut self, origin: IVec3, voxel_dim: u32, color: Color) {
+        let max = origin + IVec3::splat(voxel_dim as i32 - 1);
+        self.boxes.push((CoordBBox::new(origin, max), color));
+    }
+}
+
+impl<ValueTy> NodeVisitor<ValueTy> for NodeBoxes<'_> {
+    fn visit_node_5(&mut self, node: &Node5<ValueTy>) -> bool {
+        if let Some(color) = self.gizmos.node_5_color {
+            self.push(node.origin, Node5::<ValueTy>::VOXEL_DIM, color);
+        }
+        self.gizmos.node_4_color.is_some() || self.gizmos.node_3_color.is_some()
+    }
+
+    fn visit_node_4(&mut self, node: &Node4<ValueTy>) -> bool {
+        if let Some(color) = self.gizmos.node_4_color {
+            self.push(node.origin, Node4::<ValueTy>::VOXEL_DIM, color);
+        }
+        self.gizmos.node_3_color.is_some()
+    }
+
+    fn visit_node_3(&mut self, node: &Node3<ValueTy>) -> bool {
+        if let Some(color) = self.gizmos.node_3_color {
+            self.push(node.origin, Node3::<ValueTy>::VOXEL_DIM, color);
+        }
+        false
+    }
+}
+
+/// Draws the twelve edges of the region covered by the voxels in `bbox`, with voxels centered
+/// on their index space coordinates.
+fn draw_bbox(
+    gizmos: &mut Gizmos,
+    grid: &Grid<f32>,
+    to_scene: &GlobalTransform,
+    bbox: &CoordBBox,
+    color: Color,
+) {
+    let (min, max) = (bbox.min.as_vec3() - 0.5, bbox.max.as_vec3() + 0.5);
+    let corners: [Vec3; 8] = std::array::from_fn(|corner| {
+        let index = Vec3::select(
+            BVec3::new(corner & 1 != 0, corner & 2 != 0, corner & 4 != 0),
+            max,
+            min,
+        );
+        to_scene.transform_point(grid.transform.index_to_world(index))
+    });
+    for corner in 0..8 {
+        for axis in [1, 2, 4] {
+            if corner & axis == 0 {
+                gizmos.line(corners[corner], corners[corner | axis], color);
+            }
+        }
+    }
+}
+
+fn draw_tree_gizmos(
+    mut gizmos: Gizmos,
+    trees: Query<(&VdbTreeGizmos, Option<&GlobalTransform>)>,
+    assets: Res<Assets<VdbAsset>>,
+) {
+    for (tree_gizmos, to_scene) in &trees {
+        let Some(grid) = assets
+            .get(&tree_gizmos.asset)
+            .and_then(|asset| asset.float_grid(&tree_gizmos.grid))
+        else {
+            continue;
+        };
+        let to_scene = to_scene.copied().unwrap_or_default();
+        let mut node_boxes = NodeBoxes {
+            gizmos: tree_gizmos,
+            boxes: vec![],
+        };
+        if tree_gizmos.node_5_color.is_some()
+            || tree_gizmos.node_4_color.is_some()
+            || tree_gizmos.node_3_color.is_some()
+        {
+            grid.tree.visit(&mut node_boxes);
+        }
+        for (bbox, color) in &node_boxes.boxes {
+            draw_bbox(&mut gizmos, grid, &to_scene, bbox, *color);
+        }
+        if let Some(color) = tree_gizmos.active_bbox_color {
+            let bbox = grid.eval_active_voxel_bounding_box();
+            if !bbox.is_empty() {
+                draw_bbox(&mut gizmos, grid, &to_scene, &bbox, color);
+            }
+        }
+    }
+}
+
+/// Draws the [`VdbTreeGizmos`] every frame, needs Bevy's `GizmoPlugin`, part of the default
+/// plugins.
+pub struct VdbTreeGizmosPlugin;
+
+impl Plugin for VdbTreeGizmosPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<VdbPlugin>() {
+            app.add_plugins(VdbPlugin);
+        }
+        app.add_systems(Update, draw_tree_gizmos);
+    }
+}
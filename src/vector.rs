@@ -0,0 +1,58 @@
+use crate::data_structure::{Grid, GridValueType, Tree};
+
+use glam::Vec3;
+
+impl<const L5: u32, const L4: u32, const L3: u32> Tree<Vec3, L5, L4, L3> {
+    /// Splits the tree into one scalar tree per component, each with the same topology.
+    pub fn split(&self) -> [Tree<f32, L5, L4, L3>; 3] {
+        [
+            self.map_values(|v| v.x),
+            self.map_values(|v| v.y),
+            self.map_values(|v| v.z),
+        ]
+    }
+
+    /// Combines three scalar trees into a vector tree covering the union of their topologies,
+    /// the inverse of [`Tree::split`].
+    pub fn from_components(
+        x: &Tree<f32, L5, L4, L3>,
+        y: &Tree<f32, L5, L4, L3>,
+        z: &Tree<f32, L5, L4, L3>,
+    ) -> Self {
+        let add = |a: (Vec3, bool), b: (Vec3, bool)| (a.0 + b.0, a.1 || b.1);
+        let mut tree = x.map_values(|v| Vec3::new(v, 0.0, 0.0));
+        tree.combine_with(&y.map_values(|v| Vec3::new(0.0, v, 0.0)), add);
+        tree.combine_with(&z.map_values(|v| Vec3::new(0.0, 0.0, v)), add);
+        tree
+    }
+}
+
+impl<const L5: u32, const L4: u32, const L3: u32> Grid<Vec3, L5, L4, L3> {
+    /// Splits the grid into one scalar grid per component, see [`Tree::split`]. The transform
+    /// and descriptor are copied to every component, with the grid type set to a float tree.
+    pub fn split(&self) -> [Grid<f32, L5, L4, L3>; 3] {
+        let mut descriptor = self.descriptor.clone();
+        descriptor.grid_type = Tree::<f32, L5, L4, L3>::type_name(f32::TYPE_NAME).into();
+        self.tree.split().map(|tree| Grid {
+            tree,
+            transform: self.transform.clone(),
+            descriptor: descriptor.clone(),
+        })
+    }
+
+    /// Combines three scalar grids into a vector grid, see [`Tree::from_components`]. The
+    /// transform and descriptor are taken from `x`, with the grid type set to a vector tree.
+    pub fn from_components(
+        x: &Grid<f32, L5, L4, L3>,
+        y: &Grid<f32, L5, L4, L3>,
+        z: &Grid<f32, L5, L4, L3>,
+    ) -> Self {
+        let mut descriptor = x.descriptor.clone();
+        descriptor.grid_type = Tree::<Vec3, L5, L4, L3>::type_name(Vec3::TYPE_NAME).into();
+        Grid {
+            tree: Tree::from_components(&x.tree, &y.tree, &z.tree),
+            transform: x.transform.clone(),
+            descriptor,
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use crate::coordinates::{CoordBBox, Index};
+use crate::data_structure::{Grid, Node, Node3, Node4, Node5, Tree};
+
+use glam::IVec3;
+
+/// Callbacks for a depth-first traversal of a [`Tree`], see [`Tree::visit`].
+///
+/// All callbacks default to doing nothing, the node callbacks return whether the traversal
+/// should descend into the children and tiles of that node.
+pub trait NodeVisitor<ValueTy, const L5: u32 = 5, const L4: u32 = 4, const L3: u32 = 3> {
+    fn visit_node_5(&mut self, _node: &Node5<ValueTy, L5, L4, L3>) -> bool {
+        true
+    }
+
+    fn visit_node_4(&mut self, _node: &Node4<ValueTy, L4, L3>) -> bool {
+        true
+    }
+
+    fn visit_node_3(&mut self, _node: &Node3<ValueTy, L3>) -> bool {
+        true
+    }
+
+    /// Called for every tile of an internal node, `bbox` is the region the tile covers.
+    fn visit_tile(&mut self, _bbox: CoordBBox, _value: ValueTy, _active: bool) {}
+
+    /// Called for every voxel stored in a leaf node.
+    fn visit_voxel(&mut self, _coord: IVec3, _value: ValueTy, _active: bool) {}
+}
+
+impl<ValueTy: Copy, const L5: u32, const L4: u32, const L3: u32> Tree<ValueTy, L5, L4, L3> {
+    /// Visits all nodes, tiles and voxels of the tree depth-first. Nodes are visited before their
+    /// contents, and the contents of a node are visited in offset order.
+    pub fn visit(&self, visitor: &mut impl NodeVisitor<ValueTy, L5, L4, L3>) {
+        for node_5 in &self.root_nodes {
+            if !visitor.visit_node_5(node_5) {
+                continue;
+            }
+            for idx in 0..node_5.data.len() {
+                if !node_5.child_mask[idx] {
+                    visitor.visit_tile(
+                        node_5.tile_bbox(Index(idx as u32)),
+                        node_5.data[idx],
+                        node_5.value_mask[idx],
+                    );
+                    continue;
+                }
+                let node_4 = &node_5.nodes[&(idx as u32)];
+                if !visitor.visit_node_4(node_4) {
+                    continue;
+                }
+                for idx in 0..node_4.data.len() {
+                    if !node_4.child_mask[idx] {
+                        visitor.visit_tile(
+                            node_4.tile_bbox(Index(idx as u32)),
+                            node_4.data[idx],
+                            node_4.value_mask[idx],
+                        );
+                        continue;
+                    }
+                    let node_3 = &node_4.nodes[&(idx as u32)];
+                    if !visitor.visit_node_3(node_3) {
+                        continue;
+                    }
+                    for (idx, &value) in node_3.buffer.iter().enumerate() {
+                        visitor.visit_voxel(
+                            node_3.offset_to_global_coord(Index(idx as u32)).0,
+                            value,
+                            node_3.value_mask[idx],
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<ValueTy: Copy, const L5: u32, const L4: u32, const L3: u32> Grid<ValueTy, L5, L4, L3> {
+    /// Visits the tree of this grid, see [`Tree::visit`].
+    pub fn visit(&self, visitor: &mut impl NodeVisitor<ValueTy, L5, L4, L3>) {
+        self.tree.visit(visitor);
+    }
+}
@@ -0,0 +1,352 @@
+use crate::asset_loader::{VdbAsset, VdbPlugin};
+use crate::data_structure::Grid;
+use crate::texture_3d::{grid_to_image_3d, VolumeTextureFormat};
+
+use bevy::asset::{load_internal_asset, HandleUntyped};
+use bevy::pbr::{
+    MaterialPipeline, MaterialPipelineKey, MaterialPlugin, NotShadowCaster, NotShadowReceiver,
+};
+use bevy::prelude::*;
+use bevy::reflect::{TypePath, TypeUuid};
+use bevy::render::mesh::{MeshVertexBufferLayout, VertexAttributeValues};
+use bevy::render::render_resource::{
+    AsBindGroup, Extent3d, Face, RenderPipelineDescriptor, ShaderRef, SpecializedMeshPipelineError,
+    TextureDimension, TextureFormat,
+};
+use glam::{DMat4, DVec3};
+use half::f16;
+
+pub const VOLUME_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0x2665_6e01_32ba_3b7c);
+
+/// Number of texels of the lookup texture a [`TransferFunction`] is baked into.
+const TRANSFER_FUNCTION_SIZE: u32 = 256;
+
+/// Maps densities, normalized to `[0, 1]` over the range of the density grid, to the color
+/// light scattered by the volume takes, and a factor in `[0, 1]` on its extinction in alpha.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransferFunction {
+    /// Colors at increasing normalized densities, linearly interpolated in between
+    stops: Vec<(f32, Color)>,
+}
+
+impl Default for TransferFunction {
+    /// White at every density.
+    fn default() -> Self {
+        Self::constant(Color::WHITE)
+    }
+}
+
+impl TransferFunction {
+    /// Transfer function through `stops` of normalized densities and colors, the first and last
+    /// colors extend to zero and one.
+    pub fn new(stops: impl IntoIterator<Item = (f32, Color)>) -> Self {
+        let mut stops = stops.into_iter().collect::<Vec<_>>();
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self { stops }
+    }
+
+    /// Transfer function mapping every density to `color`.
+    pub fn constant(color: Color) -> Self {
+        Self::new([(0.0, color)])
+    }
+
+    /// Linear color and alpha at the normalized `density`, transparent without stops.
+    pub fn evaluate(&self, density: f32) -> Vec4 {
+        let color = |idx: usize| Vec4::from(self.stops[idx].1.as_linear_rgba_f32());
+        let next = self.stops.partition_point(|&(stop, _)| stop <= density);
+        match next {
+            _ if self.stops.is_empty() => Vec4::ZERO,
+            0 => color(0),
+            next if next == self.stops.len() => color(next - 1),
+            next => {
+                let (start, end) = (self.stops[next - 1].0, self.stops[next].0);
+                color(next - 1).lerp(color(next), (density - start) / (end - start))
+            }
+        }
+    }
+
+    /// Lookup texture of this transfer function, sampled by the volume shader.
+    fn to_image(&self) -> Image {
+        let data = (0..TRANSFER_FUNCTION_SIZE)
+            .map(|texel| (texel as f32 + 0.5) / TRANSFER_FUNCTION_SIZE as f32)
+            .flat_map(|density| self.evaluate(density).to_array())
+            .flat_map(|channel| f16::from_f32(channel).to_le_bytes())
+            .collect();
+        Image::new(
+            Extent3d {
+                width: TRANSFER_FUNCTION_SIZE,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba16Float,
+        )
+    }
+}
+
+pub use uniform::VolumeUniform;
+
+// The layout checks `ShaderType` generates for every field are never called
+#[allow(dead_code)]
+mod uniform {
+    use bevy::math::{Mat4, Vec2, Vec4};
+    use bevy::render::render_resource::ShaderType;
+
+    #[derive(Clone, Copy, Debug, ShaderType)]
+    pub struct VolumeUniform {
+        /// Maps the local space of the volume mesh to the texture coordinates of the grids
+        pub object_to_uvw: Mat4,
+        pub uvw_to_object: Mat4,
+        /// Linear color emitted per world unit at an emission grid value of one
+        pub emission_color: Vec4,
+        /// Density values mapped to zero and one for the transfer function lookup
+        pub density_range: Vec2,
+        /// Extinction per world unit at a density value of one
+        pub density_scale: f32,
+        pub step_count: u32,
+        /// Number of steps marched towards the light for shadows, zero disables shadowing
+        pub shadow_step_count: u32,
+    }
+}
+
+/// Ray marching material rendering the box of a mesh as a participating medium, lit by the
+/// first directional light and the ambient light of the scene. Usually made by
+/// [`VdbVolumePlugin`] from a [`VdbVolume`].
+///
+/// Back faces of the mesh are drawn so the camera can move inside the volume, which means opaque
+/// objects inside the box hide the whole volume behind them.
+#[derive(AsBindGroup, TypeUuid, TypePath, Clone, Debug)]
+#[uuid = "f016aa4b-36ee-4e26-93d0-713d7c0584ca"]
+pub struct VolumeMaterial {
+    #[uniform(0)]
+    pub uniform: VolumeUniform,
+    #[texture(1, dimension = "3d")]
+    #[sampler(2)]
+    pub density: Handle<Image>,
+    /// Emission grid values over the same box as `density`, no emission if not set
+    #[texture(3, dimension = "3d")]
+    #[sampler(4)]
+    pub emission: Option<Handle<Image>>,
+    /// Baked [`TransferFunction`]
+    #[texture(5)]
+    #[sampler(6)]
+    pub transfer_function: Handle<Image>,
+}
+
+impl Material for VolumeMaterial {
+    fn fragment_shader() -> ShaderRef {
+        VOLUME_SHADER_HANDLE.typed().into()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        AlphaMode::Premultiplied
+    }
+
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayout,
+        _key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        descriptor.primitive.cull_mode = Some(Face::Front);
+        Ok(())
+    }
+}
+
+/// Renders the `float` grids of a [`VdbAsset`] as a volume, once the asset is loaded. The
+/// [`VdbVolumePlugin`] adds the mesh and [`VolumeMaterial`] to the entity, which also needs a
+/// `SpatialBundle` to be placed in the scene.
+#[derive(Component, Clone, Debug)]
+pub struct VdbVolume {
+    pub asset: Handle<VdbAsset>,
+    /// Name of the grid holding the density of the medium
+    pub density_grid: String,
+    /// Name of the grid holding emission strengths, e.g. temperature, with the same transform as
+    /// the density grid
+    pub emission_grid: Option<String>,
+    pub emission_color: Color,
+    /// Extinction per world unit at a density of one
+    pub density_scale: f32,
+    pub transfer_function: TransferFunction,
+    /// Number of samples along every view ray through the volume
+    pub step_count: u32,
+    /// Whether the medium shadows itself from the directional light
+    pub shadows: bool,
+    /// Number of samples along every ray towards the light when `shadows` is set
+    pub shadow_step_count: u32,
+}
+
+impl VdbVolume {
+    /// Renders the density grid `density_grid` of `asset` as a white medium, with 128 steps and
+    /// shadows of 32 steps.
+    pub fn new(asset: Handle<VdbAsset>, density_grid: impl Into<String>) -> Self {
+        Self {
+            asset,
+            density_grid: density_grid.into(),
+            emission_grid: None,
+            emission_color: Color::WHITE,
+            density_scale: 1.0,
+            transfer_function: TransferFunction::default(),
+            step_count: 128,
+            shadows: true,
+            shadow_step_count: 32,
+        }
+    }
+
+    /// Makes the medium emit `color` times the values of `emission_grid`.
+    pub fn with_emission(mut self, emission_grid: impl Into<String>, color: Color) -> Self {
+        self.emission_grid = Some(emission_grid.into());
+        self.emission_color = color;
+        self
+    }
+
+    pub fn with_density_scale(mut self, density_scale: f32) -> Self {
+        self.density_scale = density_scale;
+        self
+    }
+
+    pub fn with_transfer_function(mut self, transfer_function: TransferFunction) -> Self {
+        self.transfer_function = transfer_function;
+        self
+    }
+
+    pub fn with_step_count(mut self, step_count: u32) -> Self {
+        self.step_count = step_count;
+        self
+    }
+
+    pub fn with_shadows(mut self, shadows: bool) -> Self {
+        self.shadows = shadows;
+        self
+    }
+}
+
+/// Marks volumes whose mesh and material are up to date, or that failed to build.
+#[derive(Component)]
+struct VolumeBuilt;
+
+/// Cube of the grid textures, from the outer faces of the first voxel to those of the last.
+fn uvw_to_object(grid: &Grid<f32>, bbox: crate::coordinates::CoordBBox) -> Mat4 {
+    let index_to_world = grid.transform.map.to_matrix();
+    let uvw_to_index = DMat4::from_translation(bbox.min.as_dvec3() - DVec3::splat(0.5))
+        * DMat4::from_scale(bbox.dim().as_dvec3());
+    (index_to_world * uvw_to_index).as_mat4()
+}
+
+#[allow(clippy::type_complexity)]
+fn build_volumes(
+    mut commands: Commands,
+    volumes: Query<(Entity, &VdbVolume), Or<(Without<VolumeBuilt>, Changed<VdbVolume>)>>,
+    assets: Res<Assets<VdbAsset>>,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<VolumeMaterial>>,
+) {
+    for (entity, volume) in &volumes {
+        let Some(asset) = assets.get(&volume.asset) else {
+            // Not loaded yet
+            continue;
+        };
+        commands.entity(entity).insert(VolumeBuilt);
+        let Some(density) = asset.float_grid(&volume.density_grid) else {
+            log::warn!("No float grid named {} to render", volume.density_grid);
+            continue;
+        };
+        let bbox = density.eval_active_voxel_bounding_box();
+        if bbox.is_empty() {
+            continue;
+        }
+
+        let range = density
+            .iter()
+            .map(|(_, value, _)| value)
+            .fold(Vec2::new(f32::MAX, f32::MIN), |range, value| {
+                Vec2::new(range.x.min(value), range.y.max(value))
+            });
+        let emission = volume.emission_grid.as_ref().and_then(|name| {
+            let grid = asset.float_grid(name);
+            if grid.is_none() {
+                log::warn!("No float grid named {name} to render as emission");
+            }
+            grid.map(|grid| grid_to_image_3d(grid, bbox, VolumeTextureFormat::R16Float))
+        });
+        let uvw_to_object = uvw_to_object(density, bbox);
+
+        let mut mesh = Mesh::from(shape::Cube { size: 1.0 });
+        if let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION)
+        {
+            for position in positions {
+                let uvw = Vec3::from(*position) + 0.5;
+                *position = uvw_to_object.transform_point3(uvw).to_array();
+            }
+        }
+
+        let material = VolumeMaterial {
+            uniform: VolumeUniform {
+                object_to_uvw: uvw_to_object.inverse(),
+                uvw_to_object,
+                emission_color: Vec4::from(volume.emission_color.as_linear_rgba_f32()),
+                density_range: range,
+                density_scale: volume.density_scale,
+                step_count: volume.step_count,
+                shadow_step_count: if volume.shadows {
+                    volume.shadow_step_count
+                } else {
+                    0
+                },
+            },
+            density: images.add(grid_to_image_3d(
+                density,
+                bbox,
+                VolumeTextureFormat::R16Float,
+            )),
+            emission: emission.map(|image| images.add(image)),
+            transfer_function: images.add(volume.transfer_function.to_image()),
+        };
+        commands.entity(entity).insert((
+            meshes.add(mesh),
+            materials.add(material),
+            NotShadowCaster,
+            NotShadowReceiver,
+        ));
+    }
+}
+
+/// Renders entities with a [`VdbVolume`] by ray marching their grids with a [`VolumeMaterial`]:
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use vdb_rs::{VdbVolume, VdbVolumePlugin};
+/// fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+///     let smoke = asset_server.load("smoke.vdb");
+///     commands.spawn((
+///         VdbVolume::new(smoke, "density").with_emission("temperature", Color::ORANGE),
+///         SpatialBundle::default(),
+///     ));
+/// }
+///
+/// App::new()
+///     .add_plugins((DefaultPlugins, VdbVolumePlugin))
+///     .add_systems(Startup, setup)
+///     .run();
+/// ```
+pub struct VdbVolumePlugin;
+
+impl Plugin for VdbVolumePlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            VOLUME_SHADER_HANDLE,
+            "volume_render.wgsl",
+            Shader::from_wgsl
+        );
+        if !app.is_plugin_added::<VdbPlugin>() {
+            app.add_plugins(VdbPlugin);
+        }
+        app.add_plugins(MaterialPlugin::<VolumeMaterial>::default())
+            .add_systems(Update, build_volumes);
+    }
+}
@@ -0,0 +1,111 @@
+// Ray marches a `VolumeMaterial` through the box its mesh covers, the unit cube of texture
+// coordinates (uvw) of the density and emission textures.
+
+#import bevy_pbr::mesh_view_bindings view, lights
+#import bevy_pbr::mesh_bindings mesh
+#import bevy_pbr::mesh_vertex_output MeshVertexOutput
+
+struct VolumeUniform {
+    object_to_uvw: mat4x4<f32>,
+    uvw_to_object: mat4x4<f32>,
+    emission_color: vec4<f32>,
+    density_range: vec2<f32>,
+    density_scale: f32,
+    step_count: u32,
+    shadow_step_count: u32,
+};
+
+@group(1) @binding(0)
+var<uniform> volume: VolumeUniform;
+@group(1) @binding(1)
+var density_texture: texture_3d<f32>;
+@group(1) @binding(2)
+var density_sampler: sampler;
+@group(1) @binding(3)
+var emission_texture: texture_3d<f32>;
+@group(1) @binding(4)
+var emission_sampler: sampler;
+@group(1) @binding(5)
+var transfer_texture: texture_2d<f32>;
+@group(1) @binding(6)
+var transfer_sampler: sampler;
+
+// Distances along the ray where it enters and leaves the unit cube, it misses the cube if it
+// leaves before entering.
+fn intersect_unit_cube(origin: vec3<f32>, direction: vec3<f32>) -> vec2<f32> {
+    let a = -origin / direction;
+    let b = (vec3(1.0) - origin) / direction;
+    let near = min(a, b);
+    let far = max(a, b);
+    return vec2(max(max(near.x, near.y), near.z), min(min(far.x, far.y), far.z));
+}
+
+fn sample_density(uvw: vec3<f32>) -> f32 {
+    return max(textureSampleLevel(density_texture, density_sampler, uvw, 0.0).r, 0.0);
+}
+
+// World space length of a uvw space vector.
+fn world_length(uvw: vec3<f32>) -> f32 {
+    return length((mesh.model * (volume.uvw_to_object * vec4(uvw, 0.0))).xyz);
+}
+
+@fragment
+fn fragment(in: MeshVertexOutput) -> @location(0) vec4<f32> {
+    // The inverse of the model matrix, without relying on `inverse`, which WGSL lacks
+    let world_to_uvw = volume.object_to_uvw * transpose(mesh.inverse_transpose_model);
+    let camera = (world_to_uvw * vec4(view.world_position, 1.0)).xyz;
+    let surface = (world_to_uvw * vec4(in.world_position.xyz, 1.0)).xyz;
+    let direction = normalize(surface - camera);
+    let hit = intersect_unit_cube(camera, direction);
+    let start = max(hit.x, 0.0);
+    if hit.y <= start {
+        discard;
+    }
+    let step_count = max(volume.step_count, 1u);
+    let step = (hit.y - start) / f32(step_count);
+    let step_length = world_length(direction * step);
+
+    var light_direction = vec3(0.0, 0.0, 1.0);
+    var light_color = vec3(0.0);
+    if lights.n_directional_lights > 0u {
+        let light = lights.directional_lights[0];
+        light_direction = normalize((world_to_uvw * vec4(light.direction_to_light, 0.0)).xyz);
+        light_color = light.color.rgb;
+    }
+    let ambient_color = lights.ambient_color.rgb;
+    let range = volume.density_range;
+
+    var transmittance = 1.0;
+    var color = vec3(0.0);
+    for (var i = 0u; i < step_count; i += 1u) {
+        let uvw = camera + direction * (start + (f32(i) + 0.5) * step);
+        let density = sample_density(uvw);
+        let lookup = clamp((density - range.x) / max(range.y - range.x, 1e-6), 0.0, 1.0);
+        let transfer = textureSampleLevel(transfer_texture, transfer_sampler, vec2(lookup, 0.5), 0.0);
+        let extinction = density * volume.density_scale * transfer.a;
+        let emission = textureSampleLevel(emission_texture, emission_sampler, uvw, 0.0).r
+            * volume.emission_color.rgb;
+
+        var light_transmittance = 1.0;
+        if volume.shadow_step_count > 0u && extinction > 0.0 {
+            let shadow_step = max(intersect_unit_cube(uvw, light_direction).y, 0.0)
+                / f32(volume.shadow_step_count);
+            var optical_depth = 0.0;
+            for (var j = 0u; j < volume.shadow_step_count; j += 1u) {
+                optical_depth += sample_density(uvw + light_direction * ((f32(j) + 0.5) * shadow_step));
+            }
+            let shadow_length = world_length(light_direction * shadow_step);
+            light_transmittance = exp(-optical_depth * volume.density_scale * shadow_length);
+        }
+
+        let absorbed = 1.0 - exp(-extinction * step_length);
+        let scattered = transfer.rgb * (light_color * light_transmittance + ambient_color);
+        color += transmittance * (absorbed * scattered + emission * step_length);
+        transmittance *= 1.0 - absorbed;
+        if transmittance < 0.005 {
+            break;
+        }
+    }
+    // Premultiplied alpha
+    return vec4(color, 1.0 - transmittance);
+}
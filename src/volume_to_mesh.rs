@@ -0,0 +1,401 @@
+use crate::data_structure::Grid;
+
+use glam::{IVec3, Vec3};
+use std::collections::{HashMap, HashSet};
+
+/// Cube edges as pairs of corners, where corner `i` sits at `(i & 1, i >> 1 & 1, i >> 2 & 1)`.
+/// Edges `0..4` run along x, `4..8` along y and `8..12` along z.
+const EDGES: [[usize; 2]; 12] = [
+    [0, 1],
+    [2, 3],
+    [4, 5],
+    [6, 7],
+    [0, 2],
+    [1, 3],
+    [4, 6],
+    [5, 7],
+    [0, 4],
+    [1, 5],
+    [2, 6],
+    [3, 7],
+];
+
+/// Corners of each cube face in cyclic order.
+const FACES: [[usize; 4]; 6] = [
+    [0, 2, 6, 4],
+    [1, 3, 7, 5],
+    [0, 1, 5, 4],
+    [2, 3, 7, 6],
+    [0, 1, 3, 2],
+    [4, 5, 7, 6],
+];
+
+fn corner_offset(corner: usize) -> IVec3 {
+    IVec3::new(
+        corner as i32 & 1,
+        corner as i32 >> 1 & 1,
+        corner as i32 >> 2 & 1,
+    )
+}
+
+fn edge_index(a: usize, b: usize) -> usize {
+    EDGES
+        .iter()
+        .position(|&edge| edge == [a.min(b), a.max(b)])
+        .unwrap()
+}
+
+/// Triangles, as triples of cube edges, for every combination of inside corners, where bit `i`
+/// of the index is set if corner `i` is inside.
+///
+/// The table is derived by connecting the crossings on every face of the cube into segments and
+/// chaining those into loops. Faces with four crossings keep their inside corners separated,
+/// a rule that only depends on the corners of the face, so neighboring cubes always agree and
+/// the resulting mesh is watertight. Every loop is wound so its normal points from the inside
+/// towards the outside corners.
+pub(crate) fn triangle_table() -> Vec<Vec<[usize; 3]>> {
+    (0..256usize)
+        .map(|case| {
+            let inside = |corner: usize| case >> corner & 1 == 1;
+
+            let mut neighbors = HashMap::<usize, Vec<usize>>::new();
+            for face in FACES {
+                let edges = (0..4).map(|k| edge_index(face[k], face[(k + 1) % 4]));
+                let crossed = edges
+                    .clone()
+                    .filter(|&edge| inside(EDGES[edge][0]) != inside(EDGES[edge][1]))
+                    .collect::<Vec<_>>();
+                let segments = match crossed.len() {
+                    2 => vec![[crossed[0], crossed[1]]],
+                    4 => (0..4)
+                        .filter(|&k| inside(face[k]))
+                        .map(|k| {
+                            [
+                                edge_index(face[(k + 3) % 4], face[k]),
+                                edge_index(face[k], face[(k + 1) % 4]),
+                            ]
+                        })
+                        .collect(),
+                    _ => vec![],
+                };
+                for [a, b] in segments {
+                    neighbors.entry(a).or_default().push(b);
+                    neighbors.entry(b).or_default().push(a);
+                }
+            }
+
+            let mut triangles = vec![];
+            let mut visited = HashSet::new();
+            let mut starts = neighbors.keys().copied().collect::<Vec<_>>();
+            starts.sort_unstable();
+            for start in starts {
+                if !visited.insert(start) {
+                    continue;
+                }
+                let mut polygon = vec![start];
+                let mut current = start;
+                while let Some(&next) = neighbors[&current]
+                    .iter()
+                    .find(|&&edge| !visited.contains(&edge))
+                {
+                    visited.insert(next);
+                    polygon.push(next);
+                    current = next;
+                }
+
+                // Orient the loop by comparing its normal with the direction from the inside
+                // to the outside corners of its edges
+                let midpoint = |edge: usize| {
+                    let [a, b] = EDGES[edge];
+                    (corner_offset(a) + corner_offset(b)).as_vec3() * 0.5
+                };
+                let mut normal = Vec3::ZERO;
+                let mut outward = Vec3::ZERO;
+                for (k, &edge) in polygon.iter().enumerate() {
+                    let next = polygon[(k + 1) % polygon.len()];
+                    normal += midpoint(edge).cross(midpoint(next));
+                    let [a, b] = EDGES[edge];
+                    let (inner, outer) = if inside(a) { (a, b) } else { (b, a) };
+                    outward += (corner_offset(outer) - corner_offset(inner)).as_vec3();
+                }
+                if normal.dot(outward) < 0.0 {
+                    polygon.reverse();
+                }
+
+                for k in 1..polygon.len() - 1 {
+                    triangles.push([polygon[0], polygon[k], polygon[k + 1]]);
+                }
+            }
+            triangles
+        })
+        .collect()
+}
+
+/// Origins of every cube that has an active voxel or the surface of an active tile as one of its
+/// corners, in a deterministic order.
+fn surface_cubes<const L5: u32, const L4: u32, const L3: u32>(
+    grid: &Grid<f32, L5, L4, L3>,
+) -> Vec<IVec3> {
+    let mut cubes = HashSet::new();
+    grid.tree.for_each_boundary_candidate(|coord| {
+        for corner in 0..8 {
+            cubes.insert(coord - corner_offset(corner));
+        }
+    });
+    let mut cubes = cubes.into_iter().collect::<Vec<_>>();
+    cubes.sort_unstable_by_key(|coord| coord.to_array());
+    cubes
+}
+
+/// Extracts the `isovalue` surface of `grid` as an indexed triangle mesh with world space
+/// positions, using marching cubes over the active voxels and tiles. Values below `isovalue`
+/// are inside, and triangles are wound counter-clockwise when seen from the outside.
+///
+/// Vertices are shared between neighboring triangles, so a closed surface yields a watertight
+/// mesh.
+pub fn volume_to_mesh<const L5: u32, const L4: u32, const L3: u32>(
+    grid: &Grid<f32, L5, L4, L3>,
+    isovalue: f32,
+) -> (Vec<Vec3>, Vec<[u32; 3]>) {
+    let table = triangle_table();
+    let mut accessor = grid.tree.accessor();
+    let mut positions = vec![];
+    let mut triangles = vec![];
+    // Vertices keyed by the lower corner of their edge and the axis of the edge
+    let mut vertices = HashMap::<(IVec3, usize), u32>::new();
+    for origin in surface_cubes(grid) {
+        let values = [0, 1, 2, 3, 4, 5, 6, 7]
+            .map(|corner| accessor.get_value(origin + corner_offset(corner)));
+        let case = (0..8)
+            .filter(|&corner| values[corner] < isovalue)
+            .fold(0, |case, corner| case | 1 << corner);
+
+        for triangle in &table[case] {
+            triangles.push(triangle.map(|edge| {
+                let [a, b] = EDGES[edge];
+                let start = origin + corner_offset(a);
+                *vertices.entry((start, edge / 4)).or_insert_with(|| {
+                    let t = (isovalue - values[a]) / (values[b] - values[a]);
+                    let index = start
+                        .as_vec3()
+                        .lerp((origin + corner_offset(b)).as_vec3(), t);
+                    positions.push(
+                        grid.transform
+                            .index_to_world_f64(index.as_dvec3())
+                            .as_vec3(),
+                    );
+                    positions.len() as u32 - 1
+                })
+            }));
+        }
+    }
+    (positions, triangles)
+}
+
+/// Cube crossed by the surface, as seen by the dual contouring mesher.
+struct SurfaceCell {
+    /// Sum of the positions where the surface crosses the edges of the cube, in index space
+    crossing_sum: Vec3,
+    crossing_count: u32,
+    /// Direction of the gradient at the center of the cube
+    normal: Vec3,
+    /// Lower corner and size of the block of cubes whose vertex this cube uses
+    cluster: (IVec3, i32),
+}
+
+/// Extracts the `isovalue` surface of `grid` as a quad-dominant mesh with world space positions,
+/// like OpenVDB's `volumeToMesh`. Values below `isovalue` are inside, and faces are wound
+/// counter-clockwise when seen from the outside.
+///
+/// The mesh is built by dual contouring: every cube crossed by the surface gets one vertex at the
+/// mean of its edge crossings, and every crossed edge becomes a quad connecting the vertices of
+/// the four cubes around it. With a non-zero `adaptivity`, aligned blocks of 2, 4 and up to the
+/// size of a leaf node share a single vertex when the surface normals of all their cubes deviate
+/// from their mean by at most `adaptivity` times 30 degrees. Quads touching fewer than four
+/// distinct vertices collapse into triangles or vanish, so flat regions are covered by far fewer
+/// faces. An `adaptivity` of zero meshes uniformly, and values above one are clamped, which keeps
+/// sharp features such as the edges of a box intact.
+///
+/// Returns the positions, the triangles and the quads of the mesh.
+pub fn volume_to_mesh_adaptive<const L5: u32, const L4: u32, const L3: u32>(
+    grid: &Grid<f32, L5, L4, L3>,
+    isovalue: f32,
+    adaptivity: f32,
+) -> (Vec<Vec3>, Vec<[u32; 3]>, Vec<[u32; 4]>) {
+    let mut accessor = grid.tree.accessor();
+    let mut cells = HashMap::<IVec3, SurfaceCell>::new();
+    let mut crossed_edges = vec![];
+    for origin in surface_cubes(grid) {
+        let values = [0, 1, 2, 3, 4, 5, 6, 7]
+            .map(|corner| accessor.get_value(origin + corner_offset(corner)));
+        let inside = values.map(|value| value < isovalue);
+        if inside.iter().all(|&inside| inside) || !inside.iter().any(|&inside| inside) {
+            continue;
+        }
+
+        let mut crossing_sum = Vec3::ZERO;
+        let mut crossing_count = 0;
+        for (edge, [a, b]) in EDGES.into_iter().enumerate() {
+            if inside[a] == inside[b] {
+                continue;
+            }
+            let t = (isovalue - values[a]) / (values[b] - values[a]);
+            crossing_sum += corner_offset(a)
+                .as_vec3()
+                .lerp(corner_offset(b).as_vec3(), t);
+            crossing_count += 1;
+            // Edges starting at the origin of the cube, so every edge is visited once
+            if a == 0 {
+                crossed_edges.push((origin, edge / 4, inside[a]));
+            }
+        }
+        let mut gradient = Vec3::ZERO;
+        for (corner, value) in values.into_iter().enumerate() {
+            gradient += (corner_offset(corner) * 2 - IVec3::ONE).as_vec3() * value;
+        }
+
+        cells.insert(
+            origin,
+            SurfaceCell {
+                crossing_sum: crossing_sum + origin.as_vec3() * crossing_count as f32,
+                crossing_count,
+                normal: gradient.normalize_or_zero(),
+                cluster: (origin, 1),
+            },
+        );
+    }
+
+    // Merge blocks of cubes level by level, a block is only merged if all its sub-blocks are
+    let min_cos = (adaptivity.min(1.0) * std::f32::consts::FRAC_PI_6).cos();
+    let mut size = 2;
+    while adaptivity > 0.0 && size <= 1 << L3 {
+        let mut blocks = HashMap::<IVec3, Vec<IVec3>>::new();
+        for &origin in cells.keys() {
+            blocks
+                .entry(origin.div_euclid(IVec3::splat(size)) * size)
+                .or_default()
+                .push(origin);
+        }
+        for (block, members) in blocks {
+            let normals = members.iter().map(|origin| cells[origin].normal);
+            let mean = normals.clone().sum::<Vec3>().normalize_or_zero();
+            let mergeable = members
+                .iter()
+                .all(|origin| cells[origin].cluster.1 == size / 2)
+                && normals.clone().all(|normal| normal.dot(mean) >= min_cos);
+            if mergeable {
+                for origin in members {
+                    cells.get_mut(&origin).unwrap().cluster = (block, size);
+                }
+            }
+        }
+        size *= 2;
+    }
+
+    // One vertex per cluster, at the mean of all edge crossings of its cubes
+    let mut clusters = cells.values().map(|cell| cell.cluster).collect::<Vec<_>>();
+    clusters.sort_unstable_by_key(|&(origin, size)| (origin.to_array(), size));
+    clusters.dedup();
+    let vertices = clusters
+        .iter()
+        .enumerate()
+        .map(|(idx, &cluster)| (cluster, idx as u32))
+        .collect::<HashMap<_, _>>();
+    let mut sums = vec![(Vec3::ZERO, 0); clusters.len()];
+    for cell in cells.values() {
+        let sum = &mut sums[vertices[&cell.cluster] as usize];
+        sum.0 += cell.crossing_sum;
+        sum.1 += cell.crossing_count;
+    }
+    let positions = sums
+        .into_iter()
+        .map(|(sum, count)| {
+            let index = sum / count as f32;
+            grid.transform
+                .index_to_world_f64(index.as_dvec3())
+                .as_vec3()
+        })
+        .collect();
+
+    let mut triangles = vec![];
+    let mut quads = vec![];
+    crossed_edges.sort_unstable_by_key(|&(origin, axis, _)| (origin.to_array(), axis));
+    for (start, axis, start_inside) in crossed_edges {
+        // The four cubes around the edge, counter-clockwise when looking down the edge
+        let u = IVec3::AXES[(axis + 1) % 3];
+        let v = IVec3::AXES[(axis + 2) % 3];
+        let mut cubes = [start - u - v, start - v, start, start - u];
+        if !start_inside {
+            cubes.reverse();
+        }
+        // Every cube around a crossed edge is crossed by the surface as well, they can only be
+        // missing next to inactive values that don't form a proper narrow band
+        if !cubes.iter().all(|cube| cells.contains_key(cube)) {
+            continue;
+        }
+        let mut face = Vec::with_capacity(4);
+        for cube in cubes {
+            let vertex = vertices[&cells[&cube].cluster];
+            if face.last() != Some(&vertex) && face.first() != Some(&vertex) {
+                face.push(vertex);
+            }
+        }
+        match face[..] {
+            [a, b, c] => triangles.push([a, b, c]),
+            [a, b, c, d] => quads.push([a, b, c, d]),
+            _ => {}
+        }
+    }
+    (positions, triangles, quads)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::create_level_set_box;
+    use crate::primitives::tests::sphere;
+
+    /// Number of triangles sharing each undirected edge of `triangles`.
+    fn edge_counts(triangles: &[[u32; 3]]) -> HashMap<(u32, u32), usize> {
+        let mut counts = HashMap::new();
+        for &[a, b, c] in triangles {
+            for (start, end) in [(a, b), (b, c), (c, a)] {
+                *counts.entry((start.min(end), start.max(end))).or_default() += 1;
+            }
+        }
+        counts
+    }
+
+    #[test]
+    fn sphere_mesh_is_closed_and_on_the_surface() {
+        let grid = sphere(Vec3::ZERO);
+        let (positions, triangles) = volume_to_mesh(&grid, 0.0);
+        assert!(!triangles.is_empty());
+        for position in &positions {
+            assert!((position.length() - 1.0).abs() < 0.01, "{position}");
+        }
+        assert!(edge_counts(&triangles).values().all(|&count| count == 2));
+
+        // Counter-clockwise winding seen from outside makes face normals point away from the
+        // center, triangles through a voxel corner may be degenerate
+        for &[a, b, c] in &triangles {
+            let [a, b, c] = [a, b, c].map(|idx| positions[idx as usize]);
+            assert!((b - a).cross(c - a).dot(a + b + c) > -1e-6);
+        }
+    }
+
+    #[test]
+    fn adaptive_mesh_of_box_has_fewer_faces() {
+        let grid: Grid<f32> = create_level_set_box(Vec3::splat(-1.0), Vec3::ONE, 0.1, 3.0);
+        let (_, uniform) = volume_to_mesh(&grid, 0.0);
+        let (positions, triangles, quads) = volume_to_mesh_adaptive(&grid, 0.0, 1.0);
+        assert!(triangles.len() + 2 * quads.len() < uniform.len());
+        // Vertices are placed within the cells they were merged from
+        for position in &positions {
+            assert!(
+                (position.abs().max_element() - 1.0).abs() < 0.1,
+                "{position}"
+            );
+        }
+    }
+}
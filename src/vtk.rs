@@ -0,0 +1,214 @@
+use crate::coordinates::CoordBBox;
+use crate::data_structure::Grid;
+use crate::dense::copy_to_dense;
+
+use glam::{DVec3, IVec3, Vec3};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Values that can be written to VTK files, see [`write_vti`] and [`write_vtk_points`].
+pub trait VtkValue: Copy {
+    /// Name of the component type in XML files, e.g. `Float32`
+    const XML_TYPE: &'static str;
+    /// Name of the component type in legacy files, e.g. `float`
+    const LEGACY_TYPE: &'static str;
+    /// Number of components, 1 for scalars and 3 for vectors
+    const COMPONENTS: u32;
+
+    /// Writes the components of the value as ASCII, separated by spaces.
+    fn write_ascii(self, out: &mut impl Write) -> std::io::Result<()>;
+}
+
+impl VtkValue for f32 {
+    const XML_TYPE: &'static str = "Float32";
+    const LEGACY_TYPE: &'static str = "float";
+    const COMPONENTS: u32 = 1;
+
+    fn write_ascii(self, out: &mut impl Write) -> std::io::Result<()> {
+        write!(out, "{self}")
+    }
+}
+
+impl VtkValue for f64 {
+    const XML_TYPE: &'static str = "Float64";
+    const LEGACY_TYPE: &'static str = "double";
+    const COMPONENTS: u32 = 1;
+
+    fn write_ascii(self, out: &mut impl Write) -> std::io::Result<()> {
+        write!(out, "{self}")
+    }
+}
+
+impl VtkValue for i32 {
+    const XML_TYPE: &'static str = "Int32";
+    const LEGACY_TYPE: &'static str = "int";
+    const COMPONENTS: u32 = 1;
+
+    fn write_ascii(self, out: &mut impl Write) -> std::io::Result<()> {
+        write!(out, "{self}")
+    }
+}
+
+impl VtkValue for bool {
+    const XML_TYPE: &'static str = "UInt8";
+    const LEGACY_TYPE: &'static str = "unsigned_char";
+    const COMPONENTS: u32 = 1;
+
+    fn write_ascii(self, out: &mut impl Write) -> std::io::Result<()> {
+        write!(out, "{}", self as u8)
+    }
+}
+
+impl VtkValue for Vec3 {
+    const XML_TYPE: &'static str = "Float32";
+    const LEGACY_TYPE: &'static str = "float";
+    const COMPONENTS: u32 = 3;
+
+    fn write_ascii(self, out: &mut impl Write) -> std::io::Result<()> {
+        write!(out, "{} {} {}", self.x, self.y, self.z)
+    }
+}
+
+/// Name of the data array holding the values of `grid`, VTK names can't contain whitespace and
+/// XML attributes can't contain markup, so everything else is replaced by underscores.
+fn array_name<ValueTy>(grid: &Grid<ValueTy>) -> String {
+    let name = &grid.descriptor.name;
+    if name.is_empty() {
+        return "values".to_owned();
+    }
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "_-.".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Writes the values of `grid` inside `bbox` to a VTK XML ImageData file at `path`, which
+/// ParaView and other VTK based tools open directly, usually with a `.vti` extension.
+///
+/// Every voxel becomes a point of the image, placed at its world space position, so the
+/// spacing, origin and orientation of the image follow the transform of the grid. Values are
+/// stored as point data named after the grid, with voxels not stored in the tree taking their
+/// tile or background value as in [`copy_to_dense`]. Use
+/// [`Tree::eval_active_voxel_bounding_box`] to export all active voxels.
+///
+/// [`Tree::eval_active_voxel_bounding_box`]: crate::Tree::eval_active_voxel_bounding_box
+pub fn write_vti<ValueTy: VtkValue>(
+    grid: &Grid<ValueTy>,
+    bbox: CoordBBox,
+    path: impl AsRef<Path>,
+) -> std::io::Result<()> {
+    let dense = copy_to_dense(grid, bbox);
+    let mut out = BufWriter::new(File::create(path)?);
+
+    // Spacing is the length of the index space axes in world space, their directions form
+    // the orientation of the image
+    let matrix = grid.transform.map.to_matrix();
+    let axes = [matrix.x_axis, matrix.y_axis, matrix.z_axis].map(|axis| axis.truncate());
+    let spacing = DVec3::from_array(axes.map(|axis| axis.length()));
+    let directions = axes.map(|axis| axis.normalize_or_zero());
+    let origin = matrix.w_axis.truncate();
+    let extent = format!(
+        "{} {} {} {} {} {}",
+        bbox.min.x, bbox.max.x, bbox.min.y, bbox.max.y, bbox.min.z, bbox.max.z
+    );
+    let name = array_name(grid);
+    let attribute = if ValueTy::COMPONENTS == 1 {
+        "Scalars"
+    } else {
+        "Vectors"
+    };
+
+    writeln!(out, r#"<?xml version="1.0"?>"#)?;
+    writeln!(
+        out,
+        r#"<VTKFile type="ImageData" version="1.0" byte_order="LittleEndian">"#
+    )?;
+    write!(
+        out,
+        r#"  <ImageData WholeExtent="{extent}" Origin="{} {} {}" Spacing="{} {} {}" Direction=""#,
+        origin.x, origin.y, origin.z, spacing.x, spacing.y, spacing.z
+    )?;
+    // Direction is stored row major, with the image axes as its columns
+    for row in 0..3 {
+        for (column, direction) in directions.iter().enumerate() {
+            let separator = if row + column == 0 { "" } else { " " };
+            write!(out, "{separator}{}", direction[row])?;
+        }
+    }
+    writeln!(out, r#"">"#)?;
+    writeln!(out, r#"    <Piece Extent="{extent}">"#)?;
+    writeln!(out, r#"      <PointData {attribute}="{name}">"#)?;
+    writeln!(
+        out,
+        r#"        <DataArray type="{}" Name="{name}" NumberOfComponents="{}" format="ascii">"#,
+        ValueTy::XML_TYPE,
+        ValueTy::COMPONENTS
+    )?;
+    // VTK images vary fastest along x, the opposite of dense blocks
+    for z in bbox.min.z..=bbox.max.z {
+        for y in bbox.min.y..=bbox.max.y {
+            for x in bbox.min.x..=bbox.max.x {
+                dense
+                    .get(IVec3::new(x, y, z))
+                    .unwrap()
+                    .write_ascii(&mut out)?;
+                writeln!(out)?;
+            }
+        }
+    }
+    writeln!(out, "        </DataArray>")?;
+    writeln!(out, "      </PointData>")?;
+    writeln!(out, "    </Piece>")?;
+    writeln!(out, "  </ImageData>")?;
+    writeln!(out, "</VTKFile>")?;
+    out.flush()
+}
+
+/// Writes the active voxels of `grid` to a legacy VTK PolyData file at `path`, usually with a
+/// `.vtk` extension, as a point cloud that stays small for sparse grids.
+///
+/// Every active voxel becomes a vertex at its world space position, carrying its value as
+/// point data named after the grid. Active tiles are written as the voxels they cover.
+pub fn write_vtk_points<ValueTy: VtkValue>(
+    grid: &Grid<ValueTy>,
+    path: impl AsRef<Path>,
+) -> std::io::Result<()> {
+    let mut voxels = vec![];
+    grid.tree.for_each_active_voxel(|coord| voxels.push(coord));
+    let count = voxels.len();
+    let name = array_name(grid);
+    let mut out = BufWriter::new(File::create(path)?);
+
+    writeln!(out, "# vtk DataFile Version 3.0")?;
+    writeln!(out, "{name}")?;
+    writeln!(out, "ASCII")?;
+    writeln!(out, "DATASET POLYDATA")?;
+    writeln!(out, "POINTS {count} double")?;
+    for &coord in &voxels {
+        let world = grid.transform.index_to_world_f64(coord.as_dvec3());
+        writeln!(out, "{} {} {}", world.x, world.y, world.z)?;
+    }
+    writeln!(out, "VERTICES {count} {}", 2 * count)?;
+    for idx in 0..count {
+        writeln!(out, "1 {idx}")?;
+    }
+    writeln!(out, "POINT_DATA {count}")?;
+    if ValueTy::COMPONENTS == 1 {
+        writeln!(out, "SCALARS {name} {} 1", ValueTy::LEGACY_TYPE)?;
+        writeln!(out, "LOOKUP_TABLE default")?;
+    } else {
+        writeln!(out, "VECTORS {name} {}", ValueTy::LEGACY_TYPE)?;
+    }
+    let mut accessor = grid.tree.accessor();
+    for &coord in &voxels {
+        accessor.get_value(coord).write_ascii(&mut out)?;
+        writeln!(out)?;
+    }
+    out.flush()
+}